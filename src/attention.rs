@@ -0,0 +1,77 @@
+use crossterm::style::{Attribute, SetAttribute};
+
+/// ASCII BEL, rung by virtually every terminal ever made
+pub const BEL: u8 = 0x07;
+
+/// Events that warrant getting the caller's attention
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttentionEvent {
+    /// Another caller paged this user
+    // Paging and chat are not wired up yet
+    #[allow(dead_code)]
+    Page { from: String },
+    /// New private mail arrived during the session
+    NewMessage { count: usize },
+    /// This user was mentioned in chat
+    #[allow(dead_code)]
+    ChatMention { from: String },
+}
+
+impl AttentionEvent {
+    /// Short text shown on the visual flash line
+    pub fn headline(&self) -> String {
+        match self {
+            AttentionEvent::Page { from } => format!("{} is paging you", from),
+            AttentionEvent::NewMessage { count: 1 } => "You have a new private message".to_string(),
+            AttentionEvent::NewMessage { count } => {
+                format!("You have {} new private messages", count)
+            }
+            AttentionEvent::ChatMention { from } => format!("{} mentioned you in chat", from),
+        }
+    }
+}
+
+/// How a particular caller should be signalled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AttentionProfile {
+    /// User asked not to hear the bell
+    pub quiet: bool,
+    /// Print a highlighted notice line along with (or instead of) the bell
+    pub visual_flash: bool,
+    /// Terminal understands ANSI attributes, so the notice can be reverse video
+    pub supports_ansi: bool,
+}
+
+/// Build the bytes that signal `event` to a caller with `profile`
+///
+/// Quiet mode drops the BEL but keeps the visual line, so a quiet caller
+/// is still told something happened. Returns an empty buffer when there is
+/// nothing to send.
+pub fn attention_bytes(event: &AttentionEvent, profile: AttentionProfile) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    if !profile.quiet {
+        out.push(BEL);
+    }
+
+    if profile.visual_flash {
+        let notice = format!("*** {} ***", event.headline());
+        out.extend_from_slice(b"\r\n");
+        if profile.supports_ansi {
+            out.extend_from_slice(
+                format!(
+                    "{}{}{}",
+                    SetAttribute(Attribute::Reverse),
+                    notice,
+                    SetAttribute(Attribute::Reset)
+                )
+                .as_bytes(),
+            );
+        } else {
+            out.extend_from_slice(notice.as_bytes());
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+
+    out
+}
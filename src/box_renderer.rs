@@ -3,8 +3,10 @@ use crossterm::{
     style::{Color, Print, ResetColor, SetForegroundColor},
 };
 use std::io::Write;
+use std::str::FromStr;
 
 use crate::errors::{BbsError, BbsResult};
+use crate::text_width::{display_width, fit_to_width};
 
 #[derive(Debug, Clone)]
 pub struct BoxGlyphs {
@@ -95,6 +97,7 @@ pub struct BoxRenderer {
     pub style: BoxStyle,
     pub default_color: Option<Color>,
     use_colors: bool,
+    linear: bool,
 }
 
 impl BoxRenderer {
@@ -103,9 +106,21 @@ impl BoxRenderer {
             style,
             default_color: None,
             use_colors,
+            linear: false,
         }
     }
 
+    /// Plain linear output for screen readers: no borders, padding or
+    /// colors, one line of text per item
+    pub fn with_linear(mut self, linear: bool) -> Self {
+        self.linear = linear;
+        self
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.linear
+    }
+
     // pub fn with_color(mut self, color: Color) -> Self {
     //     self.default_color = Some(color);
     //     self
@@ -119,6 +134,11 @@ impl BoxRenderer {
         width: usize,
         color: Option<Color>,
     ) -> std::io::Result<()> {
+        if self.linear {
+            writer.queue(Print(format!("{}\n", title.trim())))?;
+            return Ok(());
+        }
+
         let box_color = if self.use_colors {
             color.or(self.default_color)
         } else {
@@ -130,7 +150,7 @@ impl BoxRenderer {
         }
 
         // Top border with title
-        let title_len = title.chars().count();
+        let title_len = display_width(title);
         let padding = if width > title_len + 4 {
             (width - title_len - 4) / 2
         } else {
@@ -171,6 +191,15 @@ impl BoxRenderer {
         width: usize,
         color: Option<Color>,
     ) -> std::io::Result<()> {
+        if self.linear {
+            // Blank padding lines are just noise when read aloud
+            let content = content.trim();
+            if !content.is_empty() {
+                writer.queue(Print(format!("{}\n", content)))?;
+            }
+            return Ok(());
+        }
+
         let box_color = if self.use_colors {
             color.or(self.default_color)
         } else {
@@ -187,23 +216,10 @@ impl BoxRenderer {
             writer.queue(ResetColor)?;
         }
 
-        // Content with padding
-        let content_len = content.chars().count();
-        let total_padding = width.saturating_sub(2);
+        // Content with padding, measured in terminal columns
+        let inner_width = width.saturating_sub(4);
 
-        if content_len <= total_padding {
-            writer.queue(Print(format!(
-                " {:<width$} ",
-                content,
-                width = total_padding.saturating_sub(2)
-            )))?;
-        } else {
-            let truncated: String = content
-                .chars()
-                .take(total_padding.saturating_sub(5))
-                .collect();
-            writer.queue(Print(format!(" {}... ", truncated)))?;
-        }
+        writer.queue(Print(format!(" {} ", fit_to_width(content, inner_width))))?;
 
         if let Some(c) = box_color {
             writer.queue(SetForegroundColor(c))?;
@@ -226,6 +242,10 @@ impl BoxRenderer {
         width: usize,
         color: Option<Color>,
     ) -> std::io::Result<()> {
+        if self.linear {
+            return Ok(());
+        }
+
         let box_color = if self.use_colors {
             color.or(self.default_color)
         } else {
@@ -263,6 +283,10 @@ impl BoxRenderer {
         width: usize,
         color: Option<Color>,
     ) -> std::io::Result<()> {
+        if self.linear {
+            return Ok(());
+        }
+
         let box_color = if self.use_colors {
             color.or(self.default_color)
         } else {
@@ -336,7 +360,13 @@ impl BoxRenderer {
                     description,
                     enabled,
                 } => {
-                    let content = if *enabled {
+                    let content = if self.linear {
+                        if *enabled {
+                            format!("{}: {}", key, description)
+                        } else {
+                            format!("{}: {} (unavailable)", key, description)
+                        }
+                    } else if *enabled {
                         format!("[{}] {}", key, description)
                     } else {
                         format!("[{}] {} (disabled)", key, description)
@@ -369,6 +399,14 @@ impl BoxRenderer {
     ) -> std::io::Result<()> {
         self.render_title_box(writer, title, width, color)?;
 
+        // Screen readers wrap for themselves
+        if self.linear {
+            for line in message.lines() {
+                self.render_content_line(writer, line, width, color)?;
+            }
+            return Ok(());
+        }
+
         // Empty line for padding
         self.render_content_line(writer, "", width, color)?;
 
@@ -381,7 +419,9 @@ impl BoxRenderer {
             for word in words {
                 if current_line.is_empty() {
                     current_line = word.to_string();
-                } else if current_line.len() + word.len() < max_content_width {
+                } else if display_width(&current_line) + 1 + display_width(word)
+                    <= max_content_width
+                {
                     current_line.push(' ');
                     current_line.push_str(word);
                 } else {
@@ -461,8 +501,10 @@ pub enum BoxStyle {
     Ascii,
 }
 
-impl BoxStyle {
-    pub fn from_str(s: &str) -> BbsResult<BoxStyle> {
+impl FromStr for BoxStyle {
+    type Err = BbsError;
+
+    fn from_str(s: &str) -> BbsResult<BoxStyle> {
         match s.to_lowercase().as_str() {
             // "double" => Ok(BoxStyleName::Double),
             // "single" => Ok(BoxStyleName::Single),
@@ -474,7 +516,9 @@ impl BoxStyle {
             ))),
         }
     }
+}
 
+impl BoxStyle {
     fn to_glyphs(self) -> BoxGlyphs {
         match self {
             // BoxStyleName::Double => BoxStyle::double(),
@@ -1,5 +1,9 @@
-use crate::bulletins::{Bulletin, BulletinRequest};
+use crate::bulletins::{Bulletin, BulletinReadState, BulletinRequest};
 use crate::errors::{BbsError, BbsResult};
+use crate::journal::{Journal, JournalEntry, write_snapshot};
+use crate::services::id_service::{IdKind, IdService};
+
+use jiff::Timestamp;
 
 use std::collections::HashMap;
 use std::fs;
@@ -29,7 +33,7 @@ impl From<(&Bulletin, bool)> for BulletinSummary {
         Self {
             // id: bulletin.id,
             title: bulletin.title.clone(),
-            author: bulletin.author.clone(),
+            author: bulletin.byline().to_string(),
             posted_display: bulletin.posted_display(),
             is_sticky: bulletin.is_sticky,
             is_read,
@@ -39,17 +43,32 @@ impl From<(&Bulletin, bool)> for BulletinSummary {
 
 pub trait BulletinStorage {
     fn load_bulletin(&self, id: u32) -> BbsResult<Option<Bulletin>>;
+    /// The bulletins among `ids` that exist, in the order asked for
+    fn get_many(&self, ids: &[u32]) -> BbsResult<Vec<Bulletin>>;
     fn save_bulletin(&mut self, bulletin: &Bulletin) -> BbsResult<()>;
     fn post_bulletin(
         &mut self,
         request: &BulletinRequest,
         config: &crate::config::BbsConfig,
     ) -> BbsResult<u32>;
-    fn mark_read(&mut self, bulletin_id: u32, username: &str) -> BbsResult<()>;
-    // fn list_bulletins(&self) -> BbsResult<Vec<Bulletin>>;
-    fn get_stats(&self, current_user: Option<&str>) -> BulletinStats;
-    // fn get_recent_bulletins(&self, limit: usize) -> BbsResult<Vec<Bulletin>>;
-    // fn get_unread_bulletins(&self, username: &str) -> BbsResult<Vec<Bulletin>>;
+    /// Every bulletin in `area` (`None` for the main board), sticky ones
+    /// first, then newest first
+    fn list_bulletins(&self, area: Option<&str>) -> BbsResult<Vec<Bulletin>>;
+    /// Statistics for the main board
+    fn get_stats(&self, reads: Option<&BulletinReadState>) -> BulletinStats;
+    /// Statistics for `area` (`None` for the main board)
+    fn get_area_stats(
+        &self,
+        reads: Option<&BulletinReadState>,
+        area: Option<&str>,
+    ) -> BulletinStats;
+    /// Bulletins posted after `since`, newest first
+    fn get_bulletins_since(&self, since: Timestamp) -> BbsResult<Vec<Bulletin>>;
+    /// The `limit` newest bulletins in `area`, newest first
+    fn get_recent_bulletins(&self, limit: usize, area: Option<&str>) -> BbsResult<Vec<Bulletin>>;
+    /// Bulletins with an ID above `after_id`, lowest ID first
+    fn get_bulletins_after(&self, after_id: u32) -> BbsResult<Vec<Bulletin>>;
+    // fn get_unread_bulletins(&self, reads: &BulletinReadState) -> BbsResult<Vec<Bulletin>>;
     // fn get_bulletin_count(&self) -> BbsResult<usize>;
     // fn delete_bulletin(&mut self, id: u32) -> BbsResult<bool>;
     // fn set_sticky(&mut self, id: u32, sticky: bool) -> BbsResult<bool>;
@@ -59,11 +78,21 @@ pub trait BulletinStorage {
 pub struct JsonBulletinStorage {
     bulletins_file: PathBuf,
     bulletins_cache: HashMap<u32, Bulletin>,
-    next_id: u32,
+    ids: IdService,
+    journal: Journal,
 }
 
 impl JsonBulletinStorage {
+    /// Open bulletin storage with its own ID allocator for `data_dir`
+    // The server shares one allocator through `with_ids`; this is for tests and tools
+    #[allow(dead_code)]
     pub fn new<P: AsRef<Path>>(data_dir: P) -> BbsResult<Self> {
+        let ids = IdService::new(&data_dir)?;
+        Self::with_ids(data_dir, ids)
+    }
+
+    /// Open bulletin storage drawing IDs from a shared allocator
+    pub fn with_ids<P: AsRef<Path>>(data_dir: P, ids: IdService) -> BbsResult<Self> {
         let data_dir = data_dir.as_ref().to_path_buf();
         let bulletins_file = data_dir.join("bulletins.json");
 
@@ -75,96 +104,114 @@ impl JsonBulletinStorage {
         }
 
         let mut storage = Self {
+            journal: Journal::for_file(&bulletins_file),
             bulletins_file,
             bulletins_cache: HashMap::new(),
-            next_id: 1,
+            ids,
         };
 
         storage.load_all_bulletins()?;
         Ok(storage)
     }
 
-    /// Load all bulletins from the JSON file into the cache
+    /// Load all bulletins from the JSON file and its journal into the cache
     fn load_all_bulletins(&mut self) -> BbsResult<()> {
-        if !self.bulletins_file.exists() {
-            // Create empty bulletins file
-            let empty_bulletins: HashMap<u32, Bulletin> = HashMap::new();
-            self.save_all_bulletins(&empty_bulletins)?;
-            return Ok(());
-        }
-
-        let content = fs::read_to_string(&self.bulletins_file).map_err(|e| {
-            BbsError::Configuration(format!("Failed to read bulletins file: {}", e))
-        })?;
-
-        if content.trim().is_empty() {
-            return Ok(()); // Empty file is OK
-        }
+        let mut legacy_last = 0;
 
-        let data: serde_json::Value = serde_json::from_str(&content).map_err(|e| {
-            BbsError::Configuration(format!("Failed to parse bulletins file: {}", e))
-        })?;
+        let content = if self.bulletins_file.exists() {
+            fs::read_to_string(&self.bulletins_file).map_err(|e| {
+                BbsError::Configuration(format!("Failed to read bulletins file: {}", e))
+            })?
+        } else {
+            String::new()
+        };
 
-        // Load bulletins
-        if let Some(bulletins_obj) = data.get("bulletins").and_then(|v| v.as_object()) {
-            for (id_str, bulletin_data) in bulletins_obj {
-                let id: u32 = id_str.parse().map_err(|_| {
-                    BbsError::Configuration("Invalid bulletin ID in storage".to_string())
-                })?;
+        if !content.trim().is_empty() {
+            let data: serde_json::Value = serde_json::from_str(&content).map_err(|e| {
+                BbsError::Configuration(format!("Failed to parse bulletins file: {}", e))
+            })?;
 
-                let bulletin: Bulletin =
-                    serde_json::from_value(bulletin_data.clone()).map_err(|e| {
-                        BbsError::Configuration(format!("Failed to parse bulletin: {}", e))
+            // Load bulletins
+            if let Some(bulletins_obj) = data.get("bulletins").and_then(|v| v.as_object()) {
+                for (id_str, bulletin_data) in bulletins_obj {
+                    let id: u32 = id_str.parse().map_err(|_| {
+                        BbsError::Configuration("Invalid bulletin ID in storage".to_string())
                     })?;
 
-                self.bulletins_cache.insert(id, bulletin);
-                if id >= self.next_id {
-                    self.next_id = id + 1;
+                    let bulletin: Bulletin = serde_json::from_value(bulletin_data.clone())
+                        .map_err(|e| {
+                            BbsError::Configuration(format!("Failed to parse bulletin: {}", e))
+                        })?;
+
+                    self.bulletins_cache.insert(id, bulletin);
                 }
             }
+
+            // Files written before the shared allocator carry their own next_id
+            legacy_last = data
+                .get("next_id")
+                .and_then(|v| v.as_u64())
+                .map_or(0, |next_id| (next_id as u32).saturating_sub(1));
         }
 
-        // Load next_id
-        if let Some(next_id) = data.get("next_id").and_then(|v| v.as_u64()) {
-            self.next_id = next_id as u32;
+        // Changes made since the file was last compacted
+        for entry in self.journal.pending(&self.bulletins_file)? {
+            match entry {
+                JournalEntry::Put { key, value } => {
+                    self.bulletins_cache.insert(key, value);
+                }
+                JournalEntry::Delete { key } => {
+                    self.bulletins_cache.remove(&key);
+                }
+            }
         }
 
+        let max_id = self.bulletins_cache.keys().max().copied().unwrap_or(0);
+        self.ids
+            .reserve_through(IdKind::Bulletin, max_id.max(legacy_last))?;
+
+        if !self.bulletins_file.exists() || self.journal.path().exists() {
+            self.compact()?;
+        }
         Ok(())
     }
 
-    /// Save all bulletins from cache to the JSON file
-    fn save_all_bulletins(&self, bulletins: &HashMap<u32, Bulletin>) -> BbsResult<()> {
+    /// Rewrite bulletins.json from the cache and start a fresh journal
+    pub fn compact(&mut self) -> BbsResult<()> {
         let data = serde_json::json!({
-            "bulletins": bulletins,
-            "next_id": self.next_id
+            "bulletins": self.bulletins_cache,
         });
 
         let content = serde_json::to_string_pretty(&data).map_err(|e| {
             BbsError::Configuration(format!("Failed to serialize bulletins: {}", e))
         })?;
 
-        fs::write(&self.bulletins_file, content).map_err(|e| {
-            BbsError::Configuration(format!("Failed to write bulletins file: {}", e))
-        })?;
-
-        Ok(())
+        write_snapshot(&self.bulletins_file, &content)?;
+        self.journal.clear()
     }
 
-    /// Get statistics about bulletins
-    pub fn get_stats(&self, current_user: Option<&str>) -> BulletinStats {
-        let total_bulletins = self.bulletins_cache.len();
+    /// Get statistics about main board bulletins, using the current user's read state if logged in
+    pub fn get_stats(&self, reads: Option<&BulletinReadState>) -> BulletinStats {
+        self.get_area_stats(reads, None)
+    }
 
-        let unread_count = if let Some(username) = current_user {
-            self.bulletins_cache
-                .values()
-                .filter(|b| !b.is_read_by(username))
-                .count()
+    /// Get statistics about the bulletins in `area` (`None` for the main board)
+    pub fn get_area_stats(
+        &self,
+        reads: Option<&BulletinReadState>,
+        area: Option<&str>,
+    ) -> BulletinStats {
+        let in_area = || self.bulletins_cache.values().filter(|b| b.in_area(area));
+        let total_bulletins = in_area().count();
+
+        let unread_count = if let Some(reads) = reads {
+            in_area().filter(|b| !reads.is_read(b.id)).count()
         } else {
             total_bulletins // Anonymous users see all as unread
         };
 
         // Get recent bulletins for display
-        let mut recent_bulletins: Vec<&Bulletin> = self.bulletins_cache.values().collect();
+        let mut recent_bulletins: Vec<&Bulletin> = in_area().collect();
 
         // Sort: sticky posts first, then by posted_at (newest first)
         recent_bulletins.sort_by(|a, b| match (a.is_sticky, b.is_sticky) {
@@ -177,11 +224,7 @@ impl JsonBulletinStorage {
             .into_iter()
             .take(10)
             .map(|bulletin| {
-                let is_read = if let Some(username) = current_user {
-                    bulletin.is_read_by(username)
-                } else {
-                    false
-                };
+                let is_read = reads.is_some_and(|r| r.is_read(bulletin.id));
                 (bulletin, is_read).into()
             })
             .collect();
@@ -194,18 +237,40 @@ impl JsonBulletinStorage {
     }
 }
 
+/// Fold outstanding journal entries into bulletins.json on a clean close
+impl Drop for JsonBulletinStorage {
+    fn drop(&mut self) {
+        if self.journal.has_entries() {
+            let _ = self.compact();
+        }
+    }
+}
+
 impl BulletinStorage for JsonBulletinStorage {
     fn load_bulletin(&self, id: u32) -> BbsResult<Option<Bulletin>> {
         Ok(self.bulletins_cache.get(&id).cloned())
     }
 
+    fn get_many(&self, ids: &[u32]) -> BbsResult<Vec<Bulletin>> {
+        Ok(ids
+            .iter()
+            .filter_map(|id| self.bulletins_cache.get(id).cloned())
+            .collect())
+    }
+
     fn save_bulletin(&mut self, bulletin: &Bulletin) -> BbsResult<()> {
         // Update cache
         // TODO: can clone be removed?
         self.bulletins_cache.insert(bulletin.id, bulletin.clone());
 
-        // Save to file
-        self.save_all_bulletins(&self.bulletins_cache)?;
+        // Record the change; the file itself is only rewritten when compacting
+        self.journal.append(&JournalEntry::Put {
+            key: bulletin.id,
+            value: bulletin,
+        })?;
+        if self.journal.needs_compaction() {
+            self.compact()?;
+        }
 
         Ok(())
     }
@@ -218,49 +283,22 @@ impl BulletinStorage for JsonBulletinStorage {
     ) -> BbsResult<u32> {
         request.validate(config)?;
 
-        let id = self.next_id;
-        self.next_id += 1;
+        let id = self.ids.next_id(IdKind::Bulletin)?;
 
-        let bulletin = Bulletin::new(
+        let mut bulletin = Bulletin::new(
             id,
             request.title.trim().to_string(),
             request.content.trim().to_string(),
             request.author.clone(),
         );
+        bulletin.author_display = request.author_display.clone();
+        bulletin.area = request.area.clone();
 
         self.save_bulletin(&bulletin)?;
 
         Ok(id)
     }
 
-    /// Mark bulletin as read by user
-    fn mark_read(&mut self, bulletin_id: u32, username: &str) -> BbsResult<()> {
-        if let Some(bulletin) = self.bulletins_cache.get_mut(&bulletin_id) {
-            bulletin.mark_read_by(username);
-            self.save_all_bulletins(&self.bulletins_cache)?;
-            Ok(())
-        } else {
-            Err(BbsError::InvalidInput(format!(
-                "Bulletin {} not found",
-                bulletin_id
-            )))
-        }
-    }
-
-    // List all bulletins, sorted by post date (newest first)
-    // fn list_bulletins(&self) -> BbsResult<Vec<Bulletin>> {
-    //     let mut bulletins: Vec<Bulletin> = self.bulletins_cache.values().cloned().collect();
-    //
-    //     // Sort: sticky posts first, then by posted_at (newest first)
-    //     bulletins.sort_by(|a, b| match (a.is_sticky, b.is_sticky) {
-    //         (true, false) => std::cmp::Ordering::Less,
-    //         (false, true) => std::cmp::Ordering::Greater,
-    //         _ => b.posted_at.cmp(&a.posted_at),
-    //     });
-    //
-    //     Ok(bulletins)
-    // }
-    //
     /*
     /// Get recent bulletins (limited count)
     fn get_recent_bulletins(&self, limit: usize) -> BbsResult<Vec<Bulletin>> {
@@ -270,11 +308,11 @@ impl BulletinStorage for JsonBulletinStorage {
     }
 
     /// Get unread bulletins for a user
-    fn get_unread_bulletins(&self, username: &str) -> BbsResult<Vec<Bulletin>> {
+    fn get_unread_bulletins(&self, reads: &BulletinReadState) -> BbsResult<Vec<Bulletin>> {
         let bulletins: Vec<Bulletin> = self
             .bulletins_cache
             .values()
-            .filter(|b| !b.is_read_by(username))
+            .filter(|b| !reads.is_read(b.id))
             .cloned()
             .collect();
         Ok(bulletins)
@@ -307,7 +345,71 @@ impl BulletinStorage for JsonBulletinStorage {
     }
     */
 
-    fn get_stats(&self, current_user: Option<&str>) -> BulletinStats {
-        self.get_stats(current_user)
+    fn list_bulletins(&self, area: Option<&str>) -> BbsResult<Vec<Bulletin>> {
+        let mut bulletins: Vec<Bulletin> = self
+            .bulletins_cache
+            .values()
+            .filter(|b| b.in_area(area))
+            .cloned()
+            .collect();
+
+        // Sort: sticky posts first, then by posted_at (newest first)
+        bulletins.sort_by(|a, b| match (a.is_sticky, b.is_sticky) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => b.posted_at.cmp(&a.posted_at),
+        });
+
+        Ok(bulletins)
+    }
+
+    fn get_stats(&self, reads: Option<&BulletinReadState>) -> BulletinStats {
+        self.get_stats(reads)
+    }
+
+    fn get_area_stats(
+        &self,
+        reads: Option<&BulletinReadState>,
+        area: Option<&str>,
+    ) -> BulletinStats {
+        self.get_area_stats(reads, area)
+    }
+
+    fn get_bulletins_since(&self, since: Timestamp) -> BbsResult<Vec<Bulletin>> {
+        let mut bulletins: Vec<Bulletin> = self
+            .bulletins_cache
+            .values()
+            .filter(|b| b.posted_at > since)
+            .cloned()
+            .collect();
+
+        bulletins.sort_by_key(|b| std::cmp::Reverse(b.posted_at));
+
+        Ok(bulletins)
+    }
+
+    fn get_recent_bulletins(&self, limit: usize, area: Option<&str>) -> BbsResult<Vec<Bulletin>> {
+        let mut bulletins: Vec<Bulletin> = self
+            .bulletins_cache
+            .values()
+            .filter(|b| b.in_area(area))
+            .cloned()
+            .collect();
+        bulletins.sort_by_key(|b| std::cmp::Reverse(b.posted_at));
+        bulletins.truncate(limit);
+
+        Ok(bulletins)
+    }
+
+    fn get_bulletins_after(&self, after_id: u32) -> BbsResult<Vec<Bulletin>> {
+        let mut bulletins: Vec<Bulletin> = self
+            .bulletins_cache
+            .values()
+            .filter(|b| b.id > after_id)
+            .cloned()
+            .collect();
+        bulletins.sort_by_key(|b| b.id);
+
+        Ok(bulletins)
     }
 }
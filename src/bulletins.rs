@@ -1,6 +1,9 @@
 use crate::errors::{BbsError, BbsResult};
+use crate::post_check::{MAX_TITLE_LENGTH, PostField, PostIssue, PostProblem, check_field};
+use crate::text_width::{char_width, display_width};
 use jiff::Timestamp;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 /// A bulletin post
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,7 +14,12 @@ pub struct Bulletin {
     pub author: String,
     pub posted_at: Timestamp,
     pub is_sticky: bool,
-    pub read_by: Vec<String>, // usernames who have read this bulletin
+    /// The author's display name when the bulletin was posted
+    #[serde(default)]
+    pub author_display: Option<String>,
+    /// Club the bulletin was posted in; `None` for the main board
+    #[serde(default)]
+    pub area: Option<String>,
 }
 
 impl Bulletin {
@@ -23,18 +31,23 @@ impl Bulletin {
             author,
             posted_at: Timestamp::now(),
             is_sticky: false,
-            read_by: Vec::new(),
+            author_display: None,
+            area: None,
         }
     }
 
-    pub fn mark_read_by(&mut self, username: &str) {
-        if !self.read_by.contains(&username.to_string()) {
-            self.read_by.push(username.to_string());
+    /// Whether the bulletin lives in `area` (`None` being the main board)
+    pub fn in_area(&self, area: Option<&str>) -> bool {
+        match (&self.area, area) {
+            (None, None) => true,
+            (Some(mine), Some(area)) => mine.eq_ignore_ascii_case(area),
+            _ => false,
         }
     }
 
-    pub fn is_read_by(&self, username: &str) -> bool {
-        self.read_by.contains(&username.to_string())
+    /// Name to show for the author: the display name if there was one
+    pub fn byline(&self) -> &str {
+        self.author_display.as_deref().unwrap_or(&self.author)
     }
 
     pub fn posted_display(&self) -> String {
@@ -53,7 +66,7 @@ impl Bulletin {
         }
     }
 
-    /// Wrap `text` into lines with maximum `width` glyphs (measured as Unicode scalar count).
+    /// Wrap `text` into lines at most `width` terminal columns wide (wide characters count as two).
     /// Hard newlines ('\n') are preserved. Words are not split; if a single word is longer than
     /// `width` it will be placed on its own line (no hyphenation).
     pub fn get_content_lines(&self, width: usize) -> Vec<String> {
@@ -78,7 +91,7 @@ impl Bulletin {
             let mut current = String::new();
 
             for word in hard_line.split_whitespace() {
-                let word_len = word.chars().count();
+                let word_len = display_width(word);
 
                 // If word itself is longer than width, we must break it into chunks of size <= width.
                 if word_len > width {
@@ -88,15 +101,17 @@ impl Bulletin {
                         current = String::new();
                     }
 
-                    // Split the long word into char chunks of size `width`.
-                    let char_iter = word.chars();
+                    // Split the long word into chunks at most `width` columns wide.
                     let mut chunk = String::new();
-                    for ch in char_iter {
-                        chunk.push(ch);
-                        if chunk.chars().count() == width {
-                            out.push(chunk);
-                            chunk = String::new();
+                    let mut chunk_width = 0;
+                    for ch in word.chars() {
+                        let ch_width = char_width(ch);
+                        if chunk_width + ch_width > width && !chunk.is_empty() {
+                            out.push(std::mem::take(&mut chunk));
+                            chunk_width = 0;
                         }
+                        chunk.push(ch);
+                        chunk_width += ch_width;
                     }
                     if !chunk.is_empty() {
                         out.push(chunk);
@@ -109,7 +124,7 @@ impl Bulletin {
                         current.push_str(word);
                     } else {
                         // Would adding this word (with a space) exceed width?
-                        let new_len = current.chars().count() + 1 + word_len;
+                        let new_len = display_width(&current) + 1 + word_len;
                         if new_len <= width {
                             current.push(' ');
                             current.push_str(word);
@@ -133,12 +148,64 @@ impl Bulletin {
     }
 }
 
+/// Which bulletins a user has read, stored with the user record
+///
+/// Bulletin IDs are handed out in increasing order and most users read
+/// roughly front to back, so every ID up to `high_water` is read and only
+/// the IDs read out of order above it are kept individually.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulletinReadState {
+    /// Every bulletin with an ID at or below this has been read
+    pub high_water: u32,
+    /// Bulletins above `high_water` that have been read
+    pub read_above: BTreeSet<u32>,
+}
+
+impl BulletinReadState {
+    pub fn is_read(&self, bulletin_id: u32) -> bool {
+        bulletin_id <= self.high_water || self.read_above.contains(&bulletin_id)
+    }
+
+    /// Mark a bulletin read, folding contiguous IDs into the high-water mark
+    pub fn mark_read(&mut self, bulletin_id: u32) {
+        if self.is_read(bulletin_id) {
+            return;
+        }
+
+        self.read_above.insert(bulletin_id);
+        while self.read_above.remove(&(self.high_water + 1)) {
+            self.high_water += 1;
+        }
+    }
+
+    /// Fold the IDs up to the highest one read that aren't in `readable`
+    /// into the high-water mark
+    ///
+    /// `readable` holds the bulletins above the mark that the user may
+    /// read. Anything else below a read ID, a deleted bulletin or one in a
+    /// club they aren't in, would otherwise hold the mark back for good.
+    /// Returns whether the mark moved.
+    pub fn skip_unreadable(&mut self, readable: &BTreeSet<u32>) -> bool {
+        let before = self.high_water;
+        while !self.read_above.is_empty() {
+            let next = self.high_water + 1;
+            if !self.read_above.remove(&next) && readable.contains(&next) {
+                break;
+            }
+            self.high_water = next;
+        }
+        self.high_water != before
+    }
+}
+
 /// Request to create a new bulletin
 #[derive(Debug)]
 pub struct BulletinRequest {
     pub title: String,
     pub content: String,
     pub author: String,
+    pub author_display: Option<String>,
+    pub area: Option<String>,
 }
 
 impl BulletinRequest {
@@ -147,37 +214,53 @@ impl BulletinRequest {
             title,
             content,
             author,
+            author_display: None,
+            area: None,
         }
     }
 
-    pub fn validate(&self, config: &crate::config::BbsConfig) -> BbsResult<()> {
-        if self.title.trim().is_empty() {
-            return Err(BbsError::InvalidInput("Title cannot be empty".to_string()));
-        }
-
-        if self.title.len() > 100 {
-            return Err(BbsError::InvalidInput(
-                "Title too long (max 100 characters)".to_string(),
-            ));
-        }
+    /// Post in a club instead of on the main board
+    pub fn with_area(mut self, area: Option<String>) -> Self {
+        self.area = area;
+        self
+    }
 
-        if self.content.trim().is_empty() {
-            return Err(BbsError::InvalidInput(
-                "Content cannot be empty".to_string(),
-            ));
-        }
+    /// Show the bulletin under the author's display name
+    pub fn with_author_display(mut self, author_display: Option<String>) -> Self {
+        self.author_display = author_display;
+        self
+    }
 
-        if self.content.len() > config.features.max_message_length {
-            return Err(BbsError::InvalidInput(format!(
-                "Content too long (max {} characters)",
-                config.features.max_message_length
-            )));
+    pub fn validate(&self, config: &crate::config::BbsConfig) -> BbsResult<()> {
+        let issues = self.issues(config);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(BbsError::InvalidPost(issues))
         }
+    }
 
+    /// Everything that stops this bulletin being posted
+    pub fn issues(&self, config: &crate::config::BbsConfig) -> Vec<PostIssue> {
+        let filtered = &config.features.filtered_words;
+        let mut issues = Vec::new();
+        check_field(
+            PostField::Title,
+            &self.title,
+            MAX_TITLE_LENGTH,
+            filtered,
+            &mut issues,
+        );
+        check_field(
+            PostField::Content,
+            &self.content,
+            config.features.max_message_length,
+            filtered,
+            &mut issues,
+        );
         if self.author.trim().is_empty() {
-            return Err(BbsError::InvalidInput("Author cannot be empty".to_string()));
+            issues.push(PostIssue::new(PostField::Author, PostProblem::Empty));
         }
-
-        Ok(())
+        issues
     }
 }
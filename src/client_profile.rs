@@ -0,0 +1,60 @@
+//! Classify a client by how it handled the opening negotiation
+//!
+//! Modern clients answer every DO/WILL within a few milliseconds. A slow
+//! link shows up as long round trips, and very old or raw-socket clients
+//! never answer at all. Either way the session should go easy on them:
+//! full-screen redraws cost a lot over a laggy link and are just noise on
+//! a client that doesn't understand ANSI.
+
+use std::fmt;
+use std::time::Duration;
+use telnet_negotiation::NegotiationStats;
+
+/// Median round trip at or above which a client counts as laggy
+pub const LAGGY_ROUND_TRIP: Duration = Duration::from_millis(150);
+
+/// What the opening negotiation says about a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClientClass {
+    /// Answered promptly; use the full interface
+    #[default]
+    Modern,
+    /// Answers, but slowly
+    Laggy,
+    /// Refused or ignored everything we asked for
+    Minimal,
+}
+
+impl ClientClass {
+    /// Classify from the round-trip statistics of the opening requests
+    ///
+    /// With no requests made there is nothing to go on, so the client is
+    /// given the benefit of the doubt.
+    pub fn from_stats(stats: &NegotiationStats) -> Self {
+        if stats.timings.is_empty() {
+            return ClientClass::Modern;
+        }
+        if stats.accepted() == 0 {
+            return ClientClass::Minimal;
+        }
+        match stats.median_round_trip() {
+            Some(round_trip) if round_trip >= LAGGY_ROUND_TRIP => ClientClass::Laggy,
+            _ => ClientClass::Modern,
+        }
+    }
+
+    /// Whether to clear and repaint the screen between menus
+    pub fn redraws_screen(&self) -> bool {
+        matches!(self, ClientClass::Modern)
+    }
+}
+
+impl fmt::Display for ClientClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientClass::Modern => write!(f, "modern"),
+            ClientClass::Laggy => write!(f, "laggy"),
+            ClientClass::Minimal => write!(f, "minimal"),
+        }
+    }
+}
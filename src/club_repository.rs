@@ -0,0 +1,122 @@
+use crate::clubs::Club;
+use crate::errors::{BbsError, BbsResult};
+use crate::journal::{Journal, JournalEntry, write_snapshot};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub trait ClubStorage {
+    /// The club called `name`, ignoring case
+    fn load_club(&self, name: &str) -> BbsResult<Option<Club>>;
+    /// Add or replace a club
+    fn save_club(&mut self, club: &Club) -> BbsResult<()>;
+    /// Every club, by name
+    fn list_clubs(&self) -> BbsResult<Vec<Club>>;
+}
+
+/// JSON file-based club storage implementation
+///
+/// Clubs and their member lists live together in `clubs.json`, keyed by
+/// lowercased name.
+pub struct JsonClubStorage {
+    clubs_file: PathBuf,
+    clubs_cache: BTreeMap<String, Club>,
+    journal: Journal,
+}
+
+impl JsonClubStorage {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> BbsResult<Self> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+        let clubs_file = data_dir.join("clubs.json");
+
+        // Create data directory if it doesn't exist
+        if !data_dir.exists() {
+            fs::create_dir_all(&data_dir).map_err(|e| {
+                BbsError::Configuration(format!("Failed to create data directory: {}", e))
+            })?;
+        }
+
+        let mut storage = Self {
+            journal: Journal::for_file(&clubs_file),
+            clubs_file,
+            clubs_cache: BTreeMap::new(),
+        };
+
+        storage.load_all_clubs()?;
+        Ok(storage)
+    }
+
+    /// Load all clubs from the JSON file and its journal into the cache
+    fn load_all_clubs(&mut self) -> BbsResult<()> {
+        if self.clubs_file.exists() {
+            let content = fs::read_to_string(&self.clubs_file).map_err(|e| {
+                BbsError::Configuration(format!("Failed to read clubs file: {}", e))
+            })?;
+
+            if !content.trim().is_empty() {
+                self.clubs_cache = serde_json::from_str(&content).map_err(|e| {
+                    BbsError::Configuration(format!("Failed to parse clubs file: {}", e))
+                })?;
+            }
+        }
+
+        for entry in self.journal.pending(&self.clubs_file)? {
+            match entry {
+                JournalEntry::Put { key, value } => {
+                    self.clubs_cache.insert(key, value);
+                }
+                JournalEntry::Delete { key } => {
+                    self.clubs_cache.remove(&key);
+                }
+            }
+        }
+
+        if !self.clubs_file.exists() || self.journal.path().exists() {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite clubs.json from the cache and start a fresh journal
+    pub fn compact(&mut self) -> BbsResult<()> {
+        let content = serde_json::to_string_pretty(&self.clubs_cache)
+            .map_err(|e| BbsError::Configuration(format!("Failed to serialize clubs: {}", e)))?;
+
+        write_snapshot(&self.clubs_file, &content)?;
+        self.journal.clear()
+    }
+}
+
+/// Fold outstanding journal entries into clubs.json on a clean close
+impl Drop for JsonClubStorage {
+    fn drop(&mut self) {
+        if self.journal.has_entries() {
+            let _ = self.compact();
+        }
+    }
+}
+
+impl ClubStorage for JsonClubStorage {
+    fn load_club(&self, name: &str) -> BbsResult<Option<Club>> {
+        Ok(self.clubs_cache.get(&name.trim().to_lowercase()).cloned())
+    }
+
+    fn save_club(&mut self, club: &Club) -> BbsResult<()> {
+        let key = club.name.to_lowercase();
+        self.journal.append(&JournalEntry::Put {
+            key: &key,
+            value: club,
+        })?;
+        self.clubs_cache.insert(key, club.clone());
+
+        if self.journal.needs_compaction() {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn list_clubs(&self) -> BbsResult<Vec<Club>> {
+        Ok(self.clubs_cache.values().cloned().collect())
+    }
+}
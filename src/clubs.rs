@@ -0,0 +1,132 @@
+use crate::errors::{BbsError, BbsResult};
+use crate::users::{PasswordHash, PasswordHasher};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Longest club name, in characters
+pub const MAX_CLUB_NAME_LENGTH: usize = 30;
+
+/// How callers get into a club
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JoinRule {
+    /// Anyone who knows the password may join (hashed like user passwords)
+    Password { hash: String },
+    /// Callers ask to join and the SysOp approves them
+    Approval,
+}
+
+/// An invite-only bulletin area
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Club {
+    pub name: String,
+    pub description: String,
+    pub join: JoinRule,
+    /// Usernames of members, lowercased
+    pub members: BTreeSet<String>,
+    /// Usernames waiting for SysOp approval, lowercased
+    pub pending: BTreeSet<String>,
+    pub created_at: Timestamp,
+}
+
+impl Club {
+    pub fn is_member(&self, username: &str) -> bool {
+        self.members.contains(&username.to_lowercase())
+    }
+
+    pub fn is_pending(&self, username: &str) -> bool {
+        self.pending.contains(&username.to_lowercase())
+    }
+
+    pub fn needs_password(&self) -> bool {
+        matches!(self.join, JoinRule::Password { .. })
+    }
+
+    /// Whether `password` opens this club (always false for approval clubs)
+    pub fn check_password(&self, password: &str) -> BbsResult<bool> {
+        match &self.join {
+            JoinRule::Password { hash } => PasswordHasher::verify_password(password, hash),
+            JoinRule::Approval => Ok(false),
+        }
+    }
+
+    /// Whether `name` refers to this club (names ignore case)
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name.trim())
+    }
+}
+
+/// Request to create a club
+#[derive(Debug)]
+pub struct ClubRequest {
+    pub name: String,
+    pub description: String,
+    /// Join password; `None` makes membership by SysOp approval
+    pub password: Option<String>,
+}
+
+impl ClubRequest {
+    pub fn new(name: String, description: String, password: Option<String>) -> Self {
+        Self {
+            name,
+            description,
+            password,
+        }
+    }
+
+    pub fn validate(&self) -> BbsResult<()> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(BbsError::InvalidInput(
+                "Club name cannot be empty".to_string(),
+            ));
+        }
+
+        if name.chars().count() > MAX_CLUB_NAME_LENGTH {
+            return Err(BbsError::InvalidInput(format!(
+                "Club name too long (max {} characters)",
+                MAX_CLUB_NAME_LENGTH
+            )));
+        }
+
+        if !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        {
+            return Err(BbsError::InvalidInput(
+                "Club names may only use letters, digits, spaces, '-' and '_'".to_string(),
+            ));
+        }
+
+        if let Some(password) = &self.password
+            && password.len() < 4
+        {
+            return Err(BbsError::InvalidInput(
+                "Join password must be at least 4 characters".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Build the club, with its creator as the first member
+    pub fn into_club(self, creator: &str) -> BbsResult<Club> {
+        self.validate()?;
+
+        let join = match &self.password {
+            Some(password) => JoinRule::Password {
+                hash: PasswordHasher::hash_password(password)?,
+            },
+            None => JoinRule::Approval,
+        };
+
+        Ok(Club {
+            name: self.name.trim().to_string(),
+            description: self.description.trim().to_string(),
+            join,
+            members: BTreeSet::from([creator.to_lowercase()]),
+            pending: BTreeSet::new(),
+            created_at: Timestamp::now(),
+        })
+    }
+}
@@ -0,0 +1,41 @@
+use telnet_negotiation::{AnsiFilter, AnsiProfile, ColorDepth};
+
+/// Rewrites SGR color sequences in outgoing bytes to fit a terminal's depth
+///
+/// Render code is free to use any crossterm color, including `Color::Rgb`
+/// and `Color::AnsiValue`; this maps each one to the nearest color the
+/// caller can show, or drops it entirely for monochrome terminals. Escape
+/// sequences split across writes are held until complete. Everything other
+/// than SGR color parameters passes through untouched; the translation
+/// itself is the telnet crate's [`AnsiFilter`].
+pub struct ColorFilter {
+    inner: AnsiFilter,
+}
+
+impl ColorFilter {
+    pub fn new(depth: ColorDepth) -> Self {
+        Self {
+            inner: AnsiFilter::new(Self::profile(depth)),
+        }
+    }
+
+    pub fn depth(&self) -> ColorDepth {
+        self.inner.profile().color_depth
+    }
+
+    pub fn set_depth(&mut self, depth: ColorDepth) {
+        self.inner.set_profile(Self::profile(depth));
+    }
+
+    /// Translate a chunk of output, returning the bytes to send
+    pub fn filter(&mut self, input: &[u8]) -> Vec<u8> {
+        self.inner.filter(input)
+    }
+
+    fn profile(depth: ColorDepth) -> AnsiProfile {
+        AnsiProfile {
+            color_depth: depth,
+            ..AnsiProfile::FULL
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use crate::box_renderer::BoxStyle;
 use crate::errors::ConfigError;
+use crate::motd::ConnectionNotice;
+use crate::users::UsernameRules;
 
+use telnet_negotiation::{NegotiationProfile, TelnetOption};
+
+use std::fmt;
 use std::fs;
+use std::str::FromStr;
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
@@ -17,6 +23,205 @@ pub enum AutoDetectOption {
     Disabled,
 }
 
+/// Caller security levels, lowest to highest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityLevel {
+    /// Not logged in
+    Guest,
+    /// Any logged-in user
+    User,
+    /// The SysOp named in `[bbs]`
+    Sysop,
+}
+
+impl FromStr for SecurityLevel {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "guest" => Ok(SecurityLevel::Guest),
+            "user" => Ok(SecurityLevel::User),
+            "sysop" => Ok(SecurityLevel::Sysop),
+            _ => Err(ConfigError::InvalidValue(
+                "security level".to_string(),
+                s.to_string(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SecurityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityLevel::Guest => write!(f, "guest"),
+            SecurityLevel::User => write!(f, "user"),
+            SecurityLevel::Sysop => write!(f, "sysop"),
+        }
+    }
+}
+
+/// Another board callers can reach through the gateway
+///
+/// Written in the config file as `board = "Name|host:port"`, one line per
+/// board, listed in the order they appear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayBoard {
+    pub name: String,
+    /// `host:port` to open a telnet connection to
+    pub address: String,
+}
+
+impl FromStr for GatewayBoard {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::InvalidValue("board".to_string(), s.to_string());
+
+        let (name, address) = s.split_once('|').ok_or_else(invalid)?;
+        let (name, address) = (name.trim(), address.trim());
+        let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+        if name.is_empty() || host.is_empty() || port.parse::<u16>().is_err() {
+            return Err(invalid());
+        }
+
+        Ok(GatewayBoard {
+            name: name.to_string(),
+            address: address.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for GatewayBoard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}|{}", self.name, self.address)
+    }
+}
+
+/// A main menu entry that runs a program on the server
+///
+/// Written in the config file as
+/// `command = "key|Description|level|program args..."`, one line per
+/// command. The program is run directly, not through a shell, with the
+/// arguments split on spaces; they may use the variables in
+/// [`crate::external::VARIABLES`] as `{name}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalCommand {
+    /// Main menu key, lowercase
+    pub key: String,
+    pub description: String,
+    /// Lowest security level allowed to run it
+    pub level: SecurityLevel,
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl FromStr for ExternalCommand {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::InvalidValue("command".to_string(), s.to_string());
+
+        let mut fields = s.splitn(4, '|').map(str::trim);
+        let (Some(key), Some(description), Some(level), Some(command_line)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let mut words = command_line.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or_else(invalid)?;
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+
+        Ok(ExternalCommand {
+            key: key.to_lowercase(),
+            description: description.to_string(),
+            level: level.parse().map_err(|_| invalid())?,
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+impl fmt::Display for ExternalCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}|{}",
+            self.key.to_uppercase(),
+            self.description,
+            self.level,
+            self.program
+        )?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Who may use a feature
+///
+/// Written in the config file as `off`, `read-only`, a single level
+/// (`user`: read and post from that level up) or `read/post` levels
+/// (`guest/user`: guests read, users post).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureAccess {
+    /// Disabled for everyone
+    Off,
+    /// Everyone may read, nobody may post
+    ReadOnly,
+    /// Reading and posting each need a minimum security level
+    Gated {
+        read: SecurityLevel,
+        write: SecurityLevel,
+    },
+}
+
+impl FeatureAccess {
+    /// Read and post from `level` up
+    pub fn level(level: SecurityLevel) -> Self {
+        FeatureAccess::Gated {
+            read: level,
+            write: level,
+        }
+    }
+}
+
+impl FromStr for FeatureAccess {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(FeatureAccess::Off),
+            "read-only" => Ok(FeatureAccess::ReadOnly),
+            gated => match gated.split_once('/') {
+                Some((read, write)) => {
+                    let read: SecurityLevel = read.trim().parse()?;
+                    let write: SecurityLevel = write.trim().parse()?;
+                    // Posting without being able to read makes no sense
+                    Ok(FeatureAccess::Gated {
+                        read,
+                        write: write.max(read),
+                    })
+                }
+                None => Ok(FeatureAccess::level(gated.parse()?)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for FeatureAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureAccess::Off => write!(f, "off"),
+            FeatureAccess::ReadOnly => write!(f, "read-only"),
+            FeatureAccess::Gated { read, write } if read == write => write!(f, "{}", read),
+            FeatureAccess::Gated { read, write } => write!(f, "{}/{}", read, write),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BbsConfig {
     pub server: ServerConfig,
@@ -24,14 +229,31 @@ pub struct BbsConfig {
     pub timeouts: TimeoutConfig,
     pub features: FeatureConfig,
     pub ui: UIConfig,
+    pub scripting: ScriptingConfig,
+    pub gateway: GatewayConfig,
+    pub commands: CommandsConfig,
+    pub feed: FeedConfig,
+    pub metrics: MetricsConfig,
+    pub motd: MotdConfig,
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub telnet_port: u16,
     pub ssh_port: Option<u16>,
+    /// Port for browser terminals speaking telnet over WebSocket; off when `None`
+    pub websocket_port: Option<u16>,
+    /// Port for telnet clients that start TLS as soon as they connect; off when `None`
+    pub tls_port: Option<u16>,
+    /// PEM certificate chain for the TLS port and START_TLS; empty for none
+    pub tls_certificate: String,
+    /// PEM private key for `tls_certificate`
+    pub tls_private_key: String,
     pub bind_address: String,
     pub max_connections: usize,
+    pub start_tls: bool,
+    /// Telnet options asked for when a caller connects, in order
+    pub telnet_options: NegotiationProfile,
 }
 
 #[derive(Debug, Clone)]
@@ -56,8 +278,88 @@ pub struct FeatureConfig {
     pub require_registration: bool,
     pub max_message_length: usize,
     pub max_username_length: usize,
-    pub file_uploads_enabled: bool,
-    pub bulletins_enabled: bool,
+    /// Words bulletins and messages may not contain, matched whole and
+    /// ignoring case
+    pub filtered_words: Vec<String>,
+    /// Allowed characters, reserved names and case handling for usernames
+    pub usernames: UsernameRules,
+    // Per-feature access, enforced by the PermissionService
+    pub bulletins: FeatureAccess,
+    pub files: FeatureAccess,
+    pub messages: FeatureAccess,
+    // Read and saved, but nothing checks them until chat and doors exist
+    pub chat: FeatureAccess,
+    pub doors: FeatureAccess,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptingConfig {
+    /// Run SysOp scripts (needs the `scripting` build feature)
+    pub enabled: bool,
+    pub directory: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+    /// Offer the "Other Boards" menu
+    pub enabled: bool,
+    /// Lowest security level allowed to dial out
+    pub level: SecurityLevel,
+    /// Longest a caller may stay connected to another board
+    pub max_minutes: u64,
+    pub boards: Vec<GatewayBoard>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandsConfig {
+    /// Programs commands may run, exactly as written in `command` lines
+    pub allowed: Vec<String>,
+    /// Longest a command may run before it is killed
+    pub timeout: Duration,
+    pub commands: Vec<ExternalCommand>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedConfig {
+    /// Serve bulletins as an Atom feed over HTTP
+    pub enabled: bool,
+    pub http_port: u16,
+    /// Most recent bulletins included in the feed
+    pub max_items: usize,
+    /// Public address of the HTTP listener, used for feed links and IDs;
+    /// empty means `http://<bind_address>:<http_port>`
+    pub base_url: String,
+    /// Areas with a feed of their own: [`MAIN_AREA`] for the main board,
+    /// or a club's name; only those a guest may read are served
+    pub areas: Vec<String>,
+}
+
+/// How `[feed] areas` names the main board
+pub const MAIN_AREA: &str = "main";
+
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// Serve menu timings at /metrics on the feed's HTTP listener
+    pub enabled: bool,
+    /// Menu renders slower than this are logged as warnings
+    pub slow_render: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotdConfig {
+    /// Notices shown before the welcome screen, picked by caller address
+    pub notices: Vec<ConnectionNotice>,
+}
+
+impl FeedConfig {
+    /// Base URL for links, without a trailing slash
+    pub fn base_url(&self, bind_address: &str) -> String {
+        if self.base_url.is_empty() {
+            format!("http://{}:{}", bind_address, self.http_port)
+        } else {
+            self.base_url.trim_end_matches('/').to_string()
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -71,6 +373,13 @@ pub struct UIConfig {
     pub ansi_support: AutoDetectOption,
     pub color_support: AutoDetectOption,
     pub adaptive_layout: bool,
+    // Retro login sequence emulation
+    pub retro_modem_banner: bool,
+    pub retro_modem_speed: u32,
+    pub retro_login_matrix: bool,
+    pub retro_last_callers: bool,
+    // Print a highlighted notice line with the bell on new mail, pages, etc.
+    pub attention_flash: bool,
 }
 
 impl Default for BbsConfig {
@@ -79,8 +388,20 @@ impl Default for BbsConfig {
             server: ServerConfig {
                 telnet_port: 2323,
                 ssh_port: None,
+                websocket_port: None,
+                tls_port: None,
+                tls_certificate: String::new(),
+                tls_private_key: String::new(),
                 bind_address: "127.0.0.1".to_string(),
                 max_connections: 50,
+                start_tls: false,
+                telnet_options: NegotiationProfile::new([
+                    TelnetOption::TERMINAL_TYPE,
+                    TelnetOption::NAWS,
+                    TelnetOption::NEW_ENVIRON,
+                    TelnetOption::SEND_LOCATION,
+                    TelnetOption::SUPPRESS_GO_AHEAD,
+                ]),
             },
             bbs: BbsInfo {
                 name: "Rust BBS".to_string(),
@@ -99,8 +420,13 @@ impl Default for BbsConfig {
                 require_registration: false,
                 max_message_length: 4096,
                 max_username_length: 20,
-                file_uploads_enabled: true,
-                bulletins_enabled: true,
+                filtered_words: Vec::new(),
+                usernames: UsernameRules::default(),
+                bulletins: FeatureAccess::level(SecurityLevel::Guest),
+                files: FeatureAccess::level(SecurityLevel::Guest),
+                messages: FeatureAccess::level(SecurityLevel::User),
+                chat: FeatureAccess::Off,
+                doors: FeatureAccess::Off,
             },
             ui: UIConfig {
                 box_style: BoxStyle::Ascii,
@@ -112,6 +438,41 @@ impl Default for BbsConfig {
                 ansi_support: AutoDetectOption::Auto,
                 color_support: AutoDetectOption::Auto,
                 adaptive_layout: true,
+                // Retro login sequence emulation (all off by default)
+                retro_modem_banner: false,
+                retro_modem_speed: 38400,
+                retro_login_matrix: false,
+                retro_last_callers: false,
+                attention_flash: true,
+            },
+            scripting: ScriptingConfig {
+                enabled: false,
+                directory: "scripts".to_string(),
+            },
+            gateway: GatewayConfig {
+                enabled: false,
+                level: SecurityLevel::User,
+                max_minutes: 30,
+                boards: Vec::new(),
+            },
+            commands: CommandsConfig {
+                allowed: Vec::new(),
+                timeout: Duration::from_secs(10),
+                commands: Vec::new(),
+            },
+            feed: FeedConfig {
+                enabled: false,
+                http_port: 8080,
+                max_items: 20,
+                base_url: String::new(),
+                areas: vec![MAIN_AREA.to_string()],
+            },
+            metrics: MetricsConfig {
+                enabled: false,
+                slow_render: Duration::from_millis(250),
+            },
+            motd: MotdConfig {
+                notices: Vec::new(),
             },
         }
     }
@@ -162,6 +523,12 @@ impl BbsConfig {
                     "timeouts" => config.parse_timeout_config(key, value)?,
                     "features" => config.parse_feature_config(key, value)?,
                     "ui" => config.parse_ui_config(key, value)?,
+                    "scripting" => config.parse_scripting_config(key, value)?,
+                    "gateway" => config.parse_gateway_config(key, value)?,
+                    "commands" => config.parse_commands_config(key, value)?,
+                    "feed" => config.parse_feed_config(key, value)?,
+                    "metrics" => config.parse_metrics_config(key, value)?,
+                    "motd" => config.parse_motd_config(key, value)?,
                     _ => return Err(ConfigError::UnknownSection(current_section.clone())),
                 }
             }
@@ -186,6 +553,30 @@ impl BbsConfig {
                     })?);
                 }
             }
+            "websocket_port" => {
+                if value.is_empty() || value == "none" {
+                    self.server.websocket_port = None;
+                } else {
+                    self.server.websocket_port = Some(value.parse().map_err(|_| {
+                        ConfigError::InvalidValue(key.to_string(), value.to_string())
+                    })?);
+                }
+            }
+            "tls_port" => {
+                if value.is_empty() || value == "none" {
+                    self.server.tls_port = None;
+                } else {
+                    self.server.tls_port = Some(value.parse().map_err(|_| {
+                        ConfigError::InvalidValue(key.to_string(), value.to_string())
+                    })?);
+                }
+            }
+            "tls_certificate" => {
+                self.server.tls_certificate = value.to_string();
+            }
+            "tls_private_key" => {
+                self.server.tls_private_key = value.to_string();
+            }
             "bind_address" => {
                 self.server.bind_address = value.to_string();
             }
@@ -194,6 +585,20 @@ impl BbsConfig {
                     .parse()
                     .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
             }
+            "start_tls" => {
+                self.server.start_tls = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "telnet_options" => {
+                let profile: NegotiationProfile = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+                profile
+                    .check()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+                self.server.telnet_options = profile;
+            }
             _ => return Err(ConfigError::UnknownKey(key.to_string())),
         }
         Ok(())
@@ -202,7 +607,8 @@ impl BbsConfig {
     fn parse_ui_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
         match key {
             "box_style" => {
-                self.ui.box_style = BoxStyle::from_str(value)
+                self.ui.box_style = value
+                    .parse::<BoxStyle>()
                     .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
             }
             "width_mode" => {
@@ -264,6 +670,31 @@ impl BbsConfig {
                     .parse()
                     .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
             }
+            "retro_modem_banner" => {
+                self.ui.retro_modem_banner = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "retro_modem_speed" => {
+                self.ui.retro_modem_speed = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "retro_login_matrix" => {
+                self.ui.retro_login_matrix = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "retro_last_callers" => {
+                self.ui.retro_last_callers = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "attention_flash" => {
+                self.ui.attention_flash = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
 
             _ => return Err(ConfigError::UnknownKey(key.to_string())),
         }
@@ -318,21 +749,190 @@ impl BbsConfig {
                     .parse()
                     .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
             }
-            "file_uploads_enabled" => {
-                self.features.file_uploads_enabled = value
+            "username_unicode" | "username_digits" | "username_case_insensitive" => {
+                let enabled: bool = value
                     .parse()
                     .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+                match key {
+                    "username_unicode" => self.features.usernames.allow_unicode = enabled,
+                    "username_digits" => self.features.usernames.allow_digits = enabled,
+                    _ => self.features.usernames.case_insensitive = enabled,
+                }
+            }
+            "username_symbols" => {
+                // Letters and digits have their own switches; spaces are never allowed
+                if value
+                    .chars()
+                    .any(|c| c.is_alphanumeric() || c.is_whitespace() || c.is_control())
+                {
+                    return Err(ConfigError::InvalidValue(
+                        key.to_string(),
+                        value.to_string(),
+                    ));
+                }
+                self.features.usernames.symbols = value.to_string();
             }
-            "bulletins_enabled" => {
-                self.features.bulletins_enabled = value
+            "filtered_words" => {
+                self.features.filtered_words = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|word| !word.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+            "reserved_usernames" => {
+                self.features.usernames.reserved = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+            "bulletins" | "files" | "messages" | "chat" | "doors" => {
+                let access = value
+                    .parse::<FeatureAccess>()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+                match key {
+                    "bulletins" => self.features.bulletins = access,
+                    "files" => self.features.files = access,
+                    "messages" => self.features.messages = access,
+                    "chat" => self.features.chat = access,
+                    _ => self.features.doors = access,
+                }
+            }
+            // Older config files used plain on/off toggles
+            "file_uploads_enabled" | "bulletins_enabled" => {
+                let enabled: bool = value
                     .parse()
                     .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+                if !enabled {
+                    match key {
+                        "file_uploads_enabled" => self.features.files = FeatureAccess::Off,
+                        _ => self.features.bulletins = FeatureAccess::Off,
+                    }
+                }
             }
             _ => return Err(ConfigError::UnknownKey(key.to_string())),
         }
         Ok(())
     }
 
+    fn parse_scripting_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "enabled" => {
+                self.scripting.enabled = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "directory" => self.scripting.directory = value.to_string(),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    fn parse_gateway_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "enabled" => {
+                self.gateway.enabled = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "level" => self.gateway.level = value.parse()?,
+            "max_minutes" => {
+                self.gateway.max_minutes = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            // Repeated once per board
+            "board" => self.gateway.boards.push(value.parse()?),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    fn parse_commands_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "allowed" => {
+                self.commands.allowed = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|program| !program.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+            "timeout_seconds" => {
+                let seconds: u64 =
+                    value.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                        ConfigError::InvalidValue(key.to_string(), value.to_string())
+                    })?;
+                self.commands.timeout = Duration::from_secs(seconds);
+            }
+            // Repeated once per command
+            "command" => self.commands.commands.push(value.parse()?),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    fn parse_feed_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "enabled" => {
+                self.feed.enabled = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "http_port" => {
+                self.feed.http_port = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "max_items" => {
+                self.feed.max_items =
+                    value.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                        ConfigError::InvalidValue(key.to_string(), value.to_string())
+                    })?;
+            }
+            "base_url" => self.feed.base_url = value.to_string(),
+            "areas" => {
+                self.feed.areas = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|area| !area.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    fn parse_metrics_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "enabled" => {
+                self.metrics.enabled = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+            }
+            "slow_render_ms" => {
+                let millis: u64 = value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+                self.metrics.slow_render = Duration::from_millis(millis);
+            }
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    fn parse_motd_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            // Repeated once per notice
+            "notice" => self.motd.notices.push(value.parse()?),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
     fn to_config_file_format(&self) -> String {
         format!(
             r#"# Rust BBS Configuration File
@@ -342,8 +942,23 @@ impl BbsConfig {
 # Network configuration
 telnet_port = {}
 ssh_port = {}
+# Port for web terminals and browser MUD clients (telnet over WebSocket,
+# ws:// only; put a TLS proxy in front for wss://), or none
+websocket_port = {}
 bind_address = "{}"
 max_connections = {}
+# Offer TELNET START_TLS so capable clients can switch to TLS on this port
+start_tls = {}
+# Port for clients that use TLS from the start ("telnets", usually 992), or none
+tls_port = {}
+# PEM certificate chain and private key for start_tls and tls_port
+tls_certificate = "{}"
+tls_private_key = "{}"
+# Telnet options requested when a caller connects, in this order. Any of
+# BINARY, SUPPRESS_GO_AHEAD, END_OF_RECORD, TERMINAL_TYPE, NAWS,
+# TERMINAL_SPEED, SEND_LOCATION, NEW_ENVIRON, MXP and MSP. TERMINAL_TYPE is
+# skipped unless ANSI or color support is auto, NAWS unless width_mode is auto.
+telnet_options = "{}"
 
 [bbs]
 # BBS identification and branding
@@ -365,8 +980,26 @@ allow_anonymous = {}
 require_registration = {}
 max_message_length = {}
 max_username_length = {}
-file_uploads_enabled = {}
-bulletins_enabled = {}
+# Words bulletins and messages may not contain (whole words, any case)
+filtered_words = "{}"
+
+# Username rules: non-ASCII letters, digits, extra symbols, whether
+# case matters, and names nobody may register (the SysOp's own name
+# is always allowed for the SysOp account)
+username_unicode = {}
+username_digits = {}
+username_symbols = "{}"
+username_case_insensitive = {}
+reserved_usernames = "{}"
+
+# Feature access: "off", "read-only", a level ("guest", "user", "sysop")
+# to read and post from that level up, or "read/post" levels such as
+# "guest/user" to let guests read while only users post
+bulletins = "{}"
+files = "{}"
+messages = "{}"
+chat = "{}"
+doors = "{}"
 
 [ui]
 # User interface configuration
@@ -384,13 +1017,88 @@ width_value = {}           # Width in characters (fixed value or fallback for au
 ansi_support = "{}"        # "auto", "true", "false"  
 color_support = "{}"       # "auto", "true", "false"
 adaptive_layout = {}       # Enable responsive design
+
+# Retro login sequence emulation (each can be toggled individually)
+# Simulated modem dial-up ending in "CONNECT <speed>"
+retro_modem_banner = {}
+retro_modem_speed = {}
+# Pre-login matrix screen with a security question for new users
+retro_login_matrix = {}
+# Show the last callers list before the main menu
+retro_last_callers = {}
+
+# Highlighted notice line sent with the bell for new mail and pages
+# (users can silence the bell itself with quiet mode)
+attention_flash = {}
+
+[scripting]
+# SysOp scripts for login, new user and posting events, plus custom
+# main menu commands (cmd_<key>.rhai), written in Rhai; see the README
+enabled = {}
+directory = "{}"
+
+[gateway]
+# Let callers telnet out to other boards from the "Other Boards" menu
+enabled = {}
+# Lowest level allowed to dial out: "guest", "user" or "sysop"
+level = "{}"
+# Minutes before a gateway call is cut off
+max_minutes = {}
+# One line per board: board = "Name|host:port"
+{}
+
+[commands]
+# Main menu entries that run a program on the server and page through its
+# output. Only programs listed in allowed can run (comma separated, written
+# exactly as in the command lines), and each is stopped after timeout_seconds.
+allowed = "{}"
+timeout_seconds = {}
+# One line per command: command = "key|Description|level|program args..."
+# Arguments may use {{user}}, {{node}}, {{level}} and {{bbs_name}}; no shell is used.
+{}
+
+[feed]
+# Publish bulletins guests can read as an Atom feed at /bulletins.atom
+enabled = {}
+http_port = {}
+# Newest bulletins included in the feed
+max_items = {}
+# Address readers use to reach the feed; blank for http://bind_address:http_port
+base_url = "{}"
+# Areas with a feed: "main" for the main board (at /bulletins.atom) and any
+# club names (at /areas/<name>.atom), served only if guests may read them
+areas = "{}"
+
+[metrics]
+# Serve menu timings for Prometheus at /metrics on the feed's http_port
+enabled = {}
+# Log a warning when drawing a menu takes longer than this many milliseconds
+slow_render_ms = {}
+
+[motd]
+# Notices shown before the welcome screen to callers from some addresses.
+# One line per notice: notice = "range|text", where range is a CIDR block
+# (10.0.0.0/8), one address, a hostname or * for everyone. Put ! before the
+# range to show the notice to callers outside it; \n in the text starts a
+# new line.
+{}
 "#,
             self.server.telnet_port,
             self.server
                 .ssh_port
                 .map_or("none".to_string(), |p| p.to_string()),
+            self.server
+                .websocket_port
+                .map_or("none".to_string(), |p| p.to_string()),
             self.server.bind_address,
             self.server.max_connections,
+            self.server.start_tls,
+            self.server
+                .tls_port
+                .map_or("none".to_string(), |p| p.to_string()),
+            self.server.tls_certificate,
+            self.server.tls_private_key,
+            self.server.telnet_options,
             self.bbs.name,
             self.bbs.tagline,
             self.bbs.sysop_name,
@@ -403,8 +1111,17 @@ adaptive_layout = {}       # Enable responsive design
             self.features.require_registration,
             self.features.max_message_length,
             self.features.max_username_length,
-            self.features.file_uploads_enabled,
-            self.features.bulletins_enabled,
+            self.features.filtered_words.join(", "),
+            self.features.usernames.allow_unicode,
+            self.features.usernames.allow_digits,
+            self.features.usernames.symbols,
+            self.features.usernames.case_insensitive,
+            self.features.usernames.reserved.join(", "),
+            self.features.bulletins,
+            self.features.files,
+            self.features.messages,
+            self.features.chat,
+            self.features.doors,
             match self.ui.box_style {
                 // BoxStyleName::Double => "double",
                 // BoxStyleName::Single => "single",
@@ -429,6 +1146,55 @@ adaptive_layout = {}       # Enable responsive design
                 AutoDetectOption::Disabled => "false",
             },
             self.ui.adaptive_layout,
+            self.ui.retro_modem_banner,
+            self.ui.retro_modem_speed,
+            self.ui.retro_login_matrix,
+            self.ui.retro_last_callers,
+            self.ui.attention_flash,
+            self.scripting.enabled,
+            self.scripting.directory,
+            self.gateway.enabled,
+            self.gateway.level,
+            self.gateway.max_minutes,
+            if self.gateway.boards.is_empty() {
+                "# board = \"Example BBS|bbs.example.com:23\"".to_string()
+            } else {
+                self.gateway
+                    .boards
+                    .iter()
+                    .map(|board| format!("board = \"{}\"", board))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+            self.commands.allowed.join(", "),
+            self.commands.timeout.as_secs(),
+            if self.commands.commands.is_empty() {
+                "# command = \"U|System Uptime|guest|/usr/bin/uptime\"".to_string()
+            } else {
+                self.commands
+                    .commands
+                    .iter()
+                    .map(|command| format!("command = \"{}\"", command))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+            self.feed.enabled,
+            self.feed.http_port,
+            self.feed.max_items,
+            self.feed.base_url,
+            self.feed.areas.join(", "),
+            self.metrics.enabled,
+            self.metrics.slow_render.as_millis(),
+            if self.motd.notices.is_empty() {
+                "# notice = \"!192.168.0.0/16|You are calling from outside the LAN.\"".to_string()
+            } else {
+                self.motd
+                    .notices
+                    .iter()
+                    .map(|notice| format!("notice = \"{}\"", notice))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
         )
     }
 }
@@ -0,0 +1,168 @@
+//! Spotting names that only look different
+//!
+//! A cut-down take on the Unicode "skeleton" from UTS #39: each name is
+//! reduced to a rough visual prototype, and two names with the same
+//! skeleton could pass for one another on screen. The table covers the
+//! usual suspects (Cyrillic and Greek look-alikes, accented Latin letters,
+//! fullwidth forms, digits standing in for letters) rather than the whole
+//! confusables list, and it ignores case, spacing and punctuation, so
+//! "Sys_0p" and "sysop" collide.
+
+/// Visual prototype of `text`, for comparison with [`confusable`]
+pub fn skeleton(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        // Fullwidth ASCII (U+FF01..U+FF5E) maps straight onto ASCII
+        let c = match c as u32 {
+            0xFF01..=0xFF5E => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        };
+
+        if is_ignorable(c) {
+            continue;
+        }
+
+        // Capitals first: Greek Ν reads as N, but its lowercase ν as v
+        if let Some(ascii) = prototype(c) {
+            out.push_str(ascii);
+            continue;
+        }
+        for lower in c.to_lowercase() {
+            match prototype(lower) {
+                Some(ascii) => out.push_str(ascii),
+                None => out.push(lower),
+            }
+        }
+    }
+    out
+}
+
+/// Whether two names would be hard to tell apart
+pub fn confusable(a: &str, b: &str) -> bool {
+    let a = skeleton(a);
+    !a.is_empty() && a == skeleton(b)
+}
+
+/// Characters that don't change how a name reads: spacing, punctuation
+/// used as separators, and invisible format characters
+fn is_ignorable(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(c, '_' | '-' | '.' | '\'' | '`')
+        || matches!(
+            c,
+            '\u{00AD}' | '\u{034F}' | '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}'
+        )
+        // Combining marks (accents typed separately)
+        || matches!(c, '\u{0300}'..='\u{036F}')
+}
+
+/// ASCII prototype (in lowercase) for a character that has one
+fn prototype(c: char) -> Option<&'static str> {
+    Some(match c {
+        // Digits and ASCII look-alikes
+        '0' => "o",
+        '1' | 'i' | '|' | '!' => "l",
+        '3' => "e",
+        '4' | '@' => "a",
+        '5' | '$' => "s",
+        '7' => "t",
+        '8' => "b",
+        'm' => "rn",
+        'w' => "vv",
+
+        // Accented Latin letters
+        'à'..='å' | 'ā' | 'ă' | 'ą' | 'ǎ' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' | 'ǐ' | 'ł' | 'ĺ' | 'ļ' | 'ľ' | 'ŀ' => {
+            "l"
+        }
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' | 'ǒ' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ß' => "s",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' | 'ǔ' => "u",
+        'ŵ' => "vv",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+
+        // Cyrillic
+        'А' => "a",
+        'В' => "b",
+        'С' => "c",
+        'Е' => "e",
+        'Н' => "h",
+        'І' | 'Ӏ' => "l",
+        'Ј' => "j",
+        'К' => "k",
+        'М' => "rn",
+        'О' => "o",
+        'Р' => "p",
+        'Ѕ' => "s",
+        'Т' => "t",
+        'Х' => "x",
+        'У' => "y",
+        'а' => "a",
+        'в' => "b",
+        'с' => "c",
+        'ԁ' => "d",
+        'е' | 'ё' => "e",
+        'һ' => "h",
+        'і' | 'ї' | 'ӏ' => "l",
+        'ј' => "j",
+        'к' => "k",
+        'м' => "rn",
+        'н' => "h",
+        'о' => "o",
+        'р' => "p",
+        'ԛ' => "q",
+        'ѕ' => "s",
+        'т' => "t",
+        'п' => "n",
+        'у' => "y",
+        'х' => "x",
+        'ԝ' => "vv",
+        'ь' => "b",
+
+        // Greek
+        'Α' => "a",
+        'Β' => "b",
+        'Ε' => "e",
+        'Ζ' => "z",
+        'Η' => "h",
+        'Ι' => "l",
+        'Κ' => "k",
+        'Μ' => "rn",
+        'Ν' => "n",
+        'Ο' => "o",
+        'Ρ' => "p",
+        'Τ' => "t",
+        'Υ' => "y",
+        'Χ' => "x",
+        'α' => "a",
+        'β' => "b",
+        'ε' => "e",
+        'η' => "n",
+        'ι' => "l",
+        'κ' => "k",
+        'ν' => "v",
+        'ο' | 'σ' => "o",
+        'ρ' => "p",
+        'τ' => "t",
+        'υ' => "u",
+        'χ' => "x",
+        'γ' => "y",
+        'ω' => "vv",
+        'ζ' => "z",
+
+        _ => return None,
+    })
+}
@@ -0,0 +1,143 @@
+//! A caller's connection: telnet, WebSocket or TLS
+//!
+//! Most callers dial in with a telnet client on `telnet_port`. Browsers
+//! can't open raw sockets, so web terminals connect to `websocket_port`
+//! instead and carry the same telnet byte stream inside WebSocket frames.
+//! Clients that want encryption from the first byte use `tls_port`.
+//! Sessions run over any of them through [`Connection`] and never need to
+//! know which one they have.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use telnet_negotiation::tls::rustls::ServerConfig;
+use telnet_negotiation::{TlsStream, Transport, WebSocketTransport};
+
+/// Which port a caller came in on, and so how their connection opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    Telnet,
+    WebSocket,
+    Tls,
+}
+
+/// The transport under a caller's `TelnetStream`
+#[derive(Debug)]
+pub enum Connection {
+    /// A telnet client on the telnet port
+    Telnet(TcpStream),
+    /// A browser on the WebSocket port, after the upgrade
+    WebSocket(WebSocketTransport),
+    /// A telnet client on the TLS port, after the handshake
+    Tls(Box<TlsStream>),
+}
+
+impl Connection {
+    /// Open a caller's connection the way `port` expects
+    ///
+    /// The TLS port needs the certificate loaded by [`crate::tls::load`].
+    pub fn open(socket: TcpStream, port: Port) -> io::Result<Self> {
+        match port {
+            Port::Telnet => Ok(Connection::Telnet(socket)),
+            Port::WebSocket => Self::accept_websocket(socket),
+            Port::Tls => match crate::tls::server_config() {
+                Some(config) => Self::accept_tls(socket, config),
+                None => Err(io::Error::other("no TLS certificate loaded")),
+            },
+        }
+    }
+
+    /// Answer the WebSocket upgrade waiting on `socket`
+    pub fn accept_websocket(socket: TcpStream) -> io::Result<Self> {
+        WebSocketTransport::accept(socket).map(Connection::WebSocket)
+    }
+
+    /// Run the TLS handshake with the caller on `socket`
+    pub fn accept_tls(socket: TcpStream, config: Arc<ServerConfig>) -> io::Result<Self> {
+        TlsStream::accept(socket, config).map(|tls| Connection::Tls(Box::new(tls)))
+    }
+
+    /// The caller's address
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket().peer_addr()
+    }
+
+    /// Whether the caller came in on the TLS port
+    pub fn is_tls(&self) -> bool {
+        matches!(self, Connection::Tls(_))
+    }
+
+    /// Whether TELNET START_TLS can upgrade this connection; only plain
+    /// telnet ones can
+    pub fn can_start_tls(&self) -> bool {
+        matches!(self, Connection::Telnet(_))
+    }
+
+    fn socket(&self) -> &TcpStream {
+        match self {
+            Connection::Telnet(socket) => socket,
+            Connection::WebSocket(transport) => transport.get_ref(),
+            Connection::Tls(tls) => tls.get_ref(),
+        }
+    }
+}
+
+impl From<TcpStream> for Connection {
+    fn from(socket: TcpStream) -> Self {
+        Connection::Telnet(socket)
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Telnet(socket) => socket.read(buf),
+            Connection::WebSocket(transport) => transport.read(buf),
+            Connection::Tls(tls) => tls.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Telnet(socket) => socket.write(buf),
+            Connection::WebSocket(transport) => transport.write(buf),
+            Connection::Tls(tls) => tls.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Telnet(socket) => socket.flush(),
+            Connection::WebSocket(transport) => transport.flush(),
+            Connection::Tls(tls) => tls.flush(),
+        }
+    }
+}
+
+impl Transport for Connection {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.socket().read_timeout()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.socket().set_read_timeout(dur)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.socket().shutdown(how)
+    }
+
+    /// Only telnet connections clone, so START_TLS is telnet-only
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Connection::Telnet(socket) => socket.try_clone().map(Connection::Telnet),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only plain telnet connections can be cloned",
+            )),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Demo data for trying out a new board
+//!
+//! `moonbase --seed-demo` fills an empty data directory with a handful of
+//! callers, bulletin threads, a club and private mail, so a new SysOp has
+//! something to look at before the first real caller turns up. Everything
+//! goes in through the services, the same way a caller's posts would, so
+//! the demo data passes the board's own validation and permission checks.
+
+use crate::bulletins::BulletinRequest;
+use crate::clubs::ClubRequest;
+use crate::config::{BbsConfig, SecurityLevel};
+use crate::errors::{BbsError, BbsResult};
+use crate::journal::Journal;
+use crate::messages::MessageRequest;
+use crate::services::{Access, CoreServices, Feature};
+use crate::users::RegistrationRequest;
+
+use jiff::Timestamp;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Password for every demo account
+pub const DEMO_PASSWORD: &str = "demo";
+
+/// Storage files moved aside by [`wipe`]
+const DATA_FILES: [&str; 5] = [
+    "users.json",
+    "bulletins.json",
+    "messages.json",
+    "clubs.json",
+    "ids.json",
+];
+
+/// Demo callers: username, display name, email
+const USERS: [(&str, Option<&str>, Option<&str>); 5] = [
+    ("ada", Some("Ada Vance"), Some("ada@example.com")),
+    ("modemkid", Some("Modem Kid"), None),
+    ("nightowl", None, None),
+    ("ferrisfan", Some("Ferris Fan"), Some("ferris@example.com")),
+    ("pixelpam", Some("Pixel Pam"), None),
+];
+
+/// Main board bulletins, oldest first: author, title, text
+const BULLETINS: [(&str, &str, &str); 7] = [
+    (
+        "ada",
+        "Welcome to the demo board",
+        "Pull up a chair. This board is filled with demo data so you can \
+         see how things look; the SysOp can wipe it any time.",
+    ),
+    (
+        "modemkid",
+        "Re: Welcome to the demo board",
+        "Thanks Ada! Brings back memories of waiting for the 2400 baud \
+         handshake to finish.",
+    ),
+    (
+        "nightowl",
+        "Favourite terminal programs?",
+        "What is everyone calling in with these days? I'm on SyncTERM.",
+    ),
+    (
+        "ferrisfan",
+        "Re: Favourite terminal programs?",
+        "Plain old telnet in a terminal window. The board sizes itself to \
+         whatever my window is.",
+    ),
+    (
+        "pixelpam",
+        "Re: Favourite terminal programs?",
+        "NetRunner for the ANSI art, PuTTY when I'm at work.",
+    ),
+    (
+        "pixelpam",
+        "ANSI art night this Friday",
+        "Bring your best 80x25 pieces. Extra credit for anything with a \
+         moon in it.",
+    ),
+    (
+        "nightowl",
+        "Re: ANSI art night this Friday",
+        "Count me in, I'll be up anyway.",
+    ),
+];
+
+/// The demo club: name, description, join password
+const CLUB: (&str, &str, &str) = ("Retro Lab", "Old hardware and how to fix it", "solder");
+
+/// Club members and their posts: author, title, text
+const CLUB_BULLETINS: [(&str, &str, &str); 2] = [
+    (
+        "modemkid",
+        "Found a boxed 2400 baud modem",
+        "Still in the shrink wrap. Anyone have a serial cable to spare?",
+    ),
+    (
+        "ferrisfan",
+        "Re: Found a boxed 2400 baud modem",
+        "I have a drawer full. Bring it to the next meetup.",
+    ),
+];
+
+/// Private mail, oldest first: sender, recipient, subject, text
+const MESSAGES: [(&str, &str, &str, &str); 4] = [
+    (
+        "ada",
+        "nightowl",
+        "Welcome aboard",
+        "Glad you found us. Holler if anything on the board is confusing.",
+    ),
+    (
+        "nightowl",
+        "ada",
+        "Re: Welcome aboard",
+        "Will do, thanks! Loving the late night crowd here.",
+    ),
+    (
+        "pixelpam",
+        "modemkid",
+        "Art night",
+        "Could you run the slideshow on Friday? Your connection is the steadiest.",
+    ),
+    (
+        "ferrisfan",
+        "pixelpam",
+        "Colour palette",
+        "Which palette do you draw with? Mine comes out washed out in PuTTY.",
+    ),
+];
+
+/// What [`seed_demo`] added
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DemoReport {
+    pub users: usize,
+    pub bulletins: usize,
+    pub clubs: usize,
+    pub messages: usize,
+}
+
+/// Whether the board has no callers, bulletins or clubs yet
+pub fn is_empty(services: &CoreServices) -> BbsResult<bool> {
+    Ok(services.users.get_stats()?.total_users == 0
+        && services.bulletins.get_stats(None)?.total_bulletins == 0
+        && services.clubs.list_clubs()?.is_empty())
+}
+
+/// Fill an empty board with demo callers, bulletins, a club and mail
+///
+/// Bulletins and mail are left out when the board's settings don't let
+/// ordinary callers write them. The SysOp's own account is never made;
+/// they register it as usual.
+pub fn seed_demo(config: &BbsConfig, services: &CoreServices) -> BbsResult<DemoReport> {
+    if !is_empty(services)? {
+        return Err(BbsError::InvalidInput(
+            "The board already has data; demo data only goes on an empty board".to_string(),
+        ));
+    }
+
+    let mut report = DemoReport::default();
+    for (username, display_name, email) in USERS {
+        let request = RegistrationRequest::new(
+            username.to_string(),
+            email.map(str::to_string),
+            DEMO_PASSWORD.to_string(),
+        )
+        .with_display_name(display_name.map(str::to_string));
+        services.users.register(request, config)?;
+        report.users += 1;
+    }
+
+    let can_post = services
+        .permissions
+        .access(Feature::Bulletins, SecurityLevel::User)
+        == Access::Full;
+    if can_post {
+        let mut posted = Vec::new();
+        for (author, title, content) in BULLETINS {
+            posted.push(post(config, services, author, title, content, None)?);
+        }
+        report.bulletins += posted.len();
+
+        let (name, description, password) = CLUB;
+        let request = ClubRequest::new(
+            name.to_string(),
+            description.to_string(),
+            Some(password.to_string()),
+        );
+        services
+            .clubs
+            .create_club(request, &config.bbs.sysop_name, SecurityLevel::Sysop)?;
+        report.clubs += 1;
+        for (author, title, content) in CLUB_BULLETINS {
+            services.clubs.join_with_password(name, author, password)?;
+            post(config, services, author, title, content, Some(name))?;
+            report.bulletins += 1;
+        }
+
+        // Everyone has caught up on the welcome thread
+        for (username, _, _) in USERS {
+            services.users.mark_bulletins_read(username, &posted[..2])?;
+        }
+    }
+
+    let can_send = services
+        .permissions
+        .access(Feature::Messages, SecurityLevel::User)
+        == Access::Full;
+    if can_send {
+        for (sender, recipient, subject, content) in MESSAGES {
+            let request = MessageRequest::new(
+                recipient.to_string(),
+                subject.to_string(),
+                content.to_string(),
+                sender.to_string(),
+            )
+            .with_sender_display(display_name(sender));
+            services
+                .messages
+                .send_message(request, config, SecurityLevel::User)?;
+            report.messages += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+fn post(
+    config: &BbsConfig,
+    services: &CoreServices,
+    author: &str,
+    title: &str,
+    content: &str,
+    area: Option<&str>,
+) -> BbsResult<u32> {
+    let request = BulletinRequest::new(title.to_string(), content.to_string(), author.to_string())
+        .with_author_display(display_name(author))
+        .with_area(area.map(str::to_string));
+    services
+        .bulletins
+        .post_bulletin(request, config, SecurityLevel::User)
+}
+
+fn display_name(username: &str) -> Option<String> {
+    USERS
+        .iter()
+        .find(|(name, _, _)| *name == username)
+        .and_then(|(_, display_name, _)| display_name.map(str::to_string))
+}
+
+/// Move the board's storage files into a timestamped directory under
+/// `backups/`, leaving the data directory empty for [`seed_demo`]
+///
+/// Returns the backup directory.
+pub fn wipe<P: AsRef<Path>>(data_dir: P) -> BbsResult<PathBuf> {
+    let data_dir = data_dir.as_ref();
+    let backup_dir = data_dir
+        .join("backups")
+        .join(format!("wipe-{}", Timestamp::now().as_millisecond()));
+    fs::create_dir_all(&backup_dir).map_err(|e| {
+        BbsError::Configuration(format!("Failed to create backup directory: {}", e))
+    })?;
+
+    for file in DATA_FILES {
+        let src = data_dir.join(file);
+        let journal = Journal::for_file(&src).path().to_path_buf();
+        for src in [src, journal] {
+            if let Some(name) = src.file_name().filter(|_| src.exists()) {
+                fs::rename(&src, backup_dir.join(name)).map_err(|e| {
+                    BbsError::Configuration(format!("Failed to move {}: {}", src.display(), e))
+                })?;
+            }
+        }
+    }
+
+    Ok(backup_dir)
+}
@@ -1,3 +1,5 @@
+use crate::post_check::PostIssue;
+
 use std::fmt;
 
 /// Custom configuration errors
@@ -33,15 +35,24 @@ pub enum BbsError {
     /// Invalid user input
     InvalidInput(String),
 
+    /// A bulletin or message was refused; every problem found, in field order
+    InvalidPost(Vec<PostIssue>),
+
     /// Authentication failed (too many attempts, invalid credentials, etc.)
     AuthenticationFailed(String),
 
     /// Feature is disabled by configuration
-    // FeatureDisabled(String),
+    FeatureDisabled(String),
+
+    /// Caller's security level does not allow the action
+    PermissionDenied(String),
 
-    /// Client disconnected unexpectedly
+    /// Client disconnected unexpectedly (carrier drop)
     ClientDisconnected,
 
+    /// Client sent nothing within the configured timeout
+    Timeout,
+
     /// Configuration error
     Configuration(String),
 }
@@ -51,9 +62,15 @@ impl fmt::Display for BbsError {
         match self {
             BbsError::Io(err) => write!(f, "I/O error: {}", err),
             BbsError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            BbsError::InvalidPost(issues) => {
+                let issues: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+                write!(f, "Invalid input: {}", issues.join("; "))
+            }
             BbsError::AuthenticationFailed(msg) => write!(f, "Authentication failed: {}", msg),
-            // BbsError::FeatureDisabled(feature) => write!(f, "Feature '{}' is disabled", feature),
+            BbsError::FeatureDisabled(feature) => write!(f, "Feature '{}' is disabled", feature),
+            BbsError::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
             BbsError::ClientDisconnected => write!(f, "Client disconnected"),
+            BbsError::Timeout => write!(f, "Client timed out"),
             BbsError::Configuration(msg) => write!(f, "Configuration error: {}", msg),
         }
     }
@@ -75,7 +92,11 @@ impl From<std::io::Error> for BbsError {
         match err.kind() {
             ErrorKind::UnexpectedEof
             | ErrorKind::ConnectionReset
-            | ErrorKind::ConnectionAborted => BbsError::ClientDisconnected,
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::NotConnected => BbsError::ClientDisconnected,
+            // Read timeouts surface as WouldBlock on Unix, TimedOut on Windows
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => BbsError::Timeout,
             _ => BbsError::Io(err),
         }
     }
@@ -0,0 +1,157 @@
+//! In-process event bus shared by every session
+//!
+//! Anything that needs to reach other callers (new mail, logins, chat,
+//! pages, broadcasts) is published here as a [`BbsEvent`] and fanned out to
+//! each subscriber's own bounded queue. Publishing never blocks: a caller
+//! who is slow to drain their queue misses events rather than holding up
+//! the sender, and the subscription counts what was missed.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex};
+
+/// Events queued per subscriber before new ones are dropped
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+/// Something that happened which other sessions may care about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BbsEvent {
+    /// A caller logged in
+    Login { username: String },
+    /// A caller logged out or hung up
+    Logout { username: String },
+    /// A private message was delivered
+    NewMessage {
+        to: String,
+        from: String,
+        message_id: u32,
+    },
+    /// A notice for everyone online, usually from the SysOp
+    // Nothing publishes broadcasts or chat yet
+    #[allow(dead_code)]
+    Broadcast { from: String, text: String },
+    /// A line said in chat
+    #[allow(dead_code)]
+    ChatLine { from: String, text: String },
+}
+
+struct Subscriber {
+    id: u64,
+    sender: SyncSender<BbsEvent>,
+    missed: Arc<AtomicUsize>,
+}
+
+#[derive(Default)]
+struct BusState {
+    next_id: u64,
+    subscribers: Vec<Subscriber>,
+}
+
+/// Publish/subscribe hub; clones share the same subscribers
+#[derive(Clone)]
+pub struct EventBus {
+    state: Arc<Mutex<BusState>>,
+    capacity: usize,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUEUE_CAPACITY)
+    }
+}
+
+impl EventBus {
+    /// Create a bus whose subscribers each queue up to `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(BusState::default())),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Start receiving every event published from now on
+    pub fn subscribe(&self) -> Subscription {
+        let (sender, receiver) = sync_channel(self.capacity);
+        let missed = Arc::new(AtomicUsize::new(0));
+
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.push(Subscriber {
+            id,
+            sender,
+            missed: Arc::clone(&missed),
+        });
+
+        Subscription {
+            id,
+            receiver,
+            missed,
+            bus: self.clone(),
+        }
+    }
+
+    /// Send `event` to every subscriber, returning how many queued it
+    pub fn publish(&self, event: BbsEvent) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let mut delivered = 0;
+
+        state.subscribers.retain(
+            |subscriber| match subscriber.sender.try_send(event.clone()) {
+                Ok(()) => {
+                    delivered += 1;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    subscriber.missed.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                // The subscription was dropped without unsubscribing
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+        );
+
+        delivered
+    }
+
+    /// Number of live subscriptions
+    // Only tests look at this so far
+    #[allow(dead_code)]
+    pub fn subscriber_count(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .subscribers
+            .retain(|subscriber| subscriber.id != id);
+    }
+}
+
+/// One session's queue of events; unsubscribes when dropped
+pub struct Subscription {
+    id: u64,
+    receiver: Receiver<BbsEvent>,
+    missed: Arc<AtomicUsize>,
+    bus: EventBus,
+}
+
+impl Subscription {
+    /// Take every queued event without waiting
+    pub fn drain(&self) -> Vec<BbsEvent> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Number of events dropped because the queue was full, resetting it
+    pub fn take_missed(&self) -> usize {
+        self.missed.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.bus.unsubscribe(self.id);
+    }
+}
@@ -0,0 +1,235 @@
+//! Main menu commands that run a program on the server
+//!
+//! Each `[commands]` entry runs its program directly, never through a
+//! shell, with the caller's details filled into its arguments; the caller
+//! then pages through what it printed. Only programs on the `allowed` list
+//! run, each is killed once the timeout is up, and output past
+//! [`MAX_OUTPUT_BYTES`] is dropped, so a stuck or chatty program cannot
+//! hold a session.
+
+use crate::config::{CommandsConfig, ExternalCommand, SecurityLevel};
+use crate::errors::{BbsError, BbsResult};
+
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Variables command arguments may use as `{name}`
+pub const VARIABLES: &[&str] = &["user", "node", "level", "bbs_name"];
+
+/// Most output kept from one run
+pub const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// How often a running program is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Who a command runs for
+#[derive(Debug, Clone, Copy)]
+pub struct CommandCaller<'a> {
+    /// Logged-in username, or None for anonymous callers
+    pub username: Option<&'a str>,
+    pub level: SecurityLevel,
+    /// The caller's node (connection) number
+    pub node: u64,
+    pub bbs_name: &'a str,
+}
+
+/// What a program printed and how it finished
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Standard output as text, without control characters other than
+    /// newlines and tabs
+    pub text: String,
+    /// Output went past [`MAX_OUTPUT_BYTES`] and the rest was dropped
+    pub truncated: bool,
+    /// The program was killed for running past the timeout
+    pub timed_out: bool,
+    /// The program exited with status 0
+    pub success: bool,
+}
+
+/// Check the configured commands before the board starts
+///
+/// Every program must be on the allowlist, arguments may only use known
+/// variables, and keys must be unique and not shadow `reserved_keys`.
+pub fn check(config: &CommandsConfig, reserved_keys: &[&str]) -> BbsResult<()> {
+    for (index, command) in config.commands.iter().enumerate() {
+        let problem = if !config.allowed.contains(&command.program) {
+            Some(format!("'{}' is not in allowed", command.program))
+        } else if reserved_keys.contains(&command.key.as_str()) {
+            Some(format!(
+                "key '{}' is already used by the main menu",
+                command.key
+            ))
+        } else if config.commands[..index]
+            .iter()
+            .any(|c| c.key == command.key)
+        {
+            Some(format!("key '{}' is used twice", command.key))
+        } else {
+            command.args.iter().find_map(|arg| unknown_variable(arg))
+        };
+
+        if let Some(problem) = problem {
+            return Err(BbsError::Configuration(format!(
+                "command {}: {}",
+                command.key.to_uppercase(),
+                problem
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The command's arguments with `{name}` replaced for `caller`
+pub fn expand_args(command: &ExternalCommand, caller: CommandCaller) -> Vec<String> {
+    let value = |name: &str| match name {
+        "user" => Some(caller.username.unwrap_or("Anonymous").to_string()),
+        "node" => Some(caller.node.to_string()),
+        "level" => Some(caller.level.to_string()),
+        "bbs_name" => Some(caller.bbs_name.to_string()),
+        _ => None,
+    };
+
+    // One pass, so a username containing "{node}" stays as typed
+    command
+        .args
+        .iter()
+        .map(|arg| {
+            let mut out = String::with_capacity(arg.len());
+            let mut rest = arg.as_str();
+            while let Some(start) = rest.find('{') {
+                out.push_str(&rest[..start]);
+                match rest[start..].find('}') {
+                    Some(end) => {
+                        let name = &rest[start + 1..start + end];
+                        match value(name) {
+                            Some(value) => out.push_str(&value),
+                            None => out.push_str(&rest[start..=start + end]),
+                        }
+                        rest = &rest[start + end + 1..];
+                    }
+                    None => {
+                        out.push_str(&rest[start..]);
+                        rest = "";
+                    }
+                }
+            }
+            out.push_str(rest);
+            out
+        })
+        .collect()
+}
+
+/// Run `command` for `caller` and collect what it prints
+///
+/// Standard input is empty and standard error is discarded. Programs not
+/// on the allowlist are refused with `PermissionDenied`, even if
+/// [`check`] was skipped.
+pub fn run(
+    command: &ExternalCommand,
+    config: &CommandsConfig,
+    caller: CommandCaller,
+) -> io::Result<CommandOutput> {
+    if !config.allowed.contains(&command.program) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not an allowed program", command.program),
+        ));
+    }
+
+    let mut child = Command::new(&command.program)
+        .args(expand_args(command, caller))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Read on another thread so a program that never closes its output
+    // can still be timed out
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            match stdout.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if sender.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let deadline = Instant::now() + config.timeout;
+    let mut output = Vec::new();
+    let mut truncated = false;
+    let mut timed_out = false;
+    let status = loop {
+        // Output first, so the pipe never fills and stalls the program
+        while let Ok(chunk) = receiver.try_recv() {
+            truncated |= keep(&mut output, &chunk);
+        }
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            timed_out = true;
+            break None;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    // Whatever was written before the program exited; anything it left
+    // running with the pipe open gets a moment, not forever
+    let grace = Instant::now() + POLL_INTERVAL * 10;
+    while let Ok(chunk) = receiver.recv_timeout(POLL_INTERVAL) {
+        truncated |= keep(&mut output, &chunk);
+        if Instant::now() >= grace {
+            // Still writing, so whatever comes next is cut off
+            truncated = true;
+            break;
+        }
+    }
+
+    let text = String::from_utf8_lossy(&output)
+        .replace("\r\n", "\n")
+        .chars()
+        .filter(|&c| c == '\n' || c == '\t' || !c.is_control())
+        .collect();
+
+    Ok(CommandOutput {
+        text,
+        truncated,
+        timed_out,
+        success: status.is_some_and(|s| s.success()),
+    })
+}
+
+/// Add `chunk` to `output` up to [`MAX_OUTPUT_BYTES`], returning whether
+/// any of it was dropped
+fn keep(output: &mut Vec<u8>, chunk: &[u8]) -> bool {
+    let room = MAX_OUTPUT_BYTES - output.len();
+    output.extend_from_slice(&chunk[..chunk.len().min(room)]);
+    chunk.len() > room
+}
+
+/// Describe the first `{name}` in `arg` that is not a known variable
+fn unknown_variable(arg: &str) -> Option<String> {
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}')?;
+        let name = &rest[start + 1..start + end];
+        if !VARIABLES.contains(&name) {
+            return Some(format!("unknown variable '{{{}}}'", name));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    None
+}
@@ -0,0 +1,330 @@
+//! Atom feed of public bulletins, served over HTTP
+//!
+//! Bulletins a guest is allowed to read are published at
+//! [`FEED_PATH`] so people without an account can follow announcements
+//! in a feed reader. Each area listed in `[feed] areas` is served at
+//! [`feed_path`] if a guest may read it: the feed is held to club
+//! membership like any other read, so club bulletins stay with their
+//! members. An area that isn't listed has no feed. With `[metrics]`
+//! enabled the same listener also answers [`METRICS_PATH`] with menu
+//! timings for Prometheus, and with the feed on it offers the board guide
+//! as plain text at [`GUIDE_PATH`]. It is deliberately small: one
+//! request per connection, `GET` and `HEAD` only.
+
+use crate::bulletins::Bulletin;
+use crate::config::{BbsConfig, MAIN_AREA, SecurityLevel};
+use crate::errors::BbsError;
+use crate::guide::{GUIDE_PATH, board_guide};
+use crate::services::CoreServices;
+use crate::services::permission_service::{Access, Feature};
+
+use jiff::Timestamp;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Where the main board's feed is served
+pub const FEED_PATH: &str = "/bulletins.atom";
+
+/// Where club feeds are served, under a name made from the club's own
+/// (see [`feed_path`])
+const AREA_FEED_PREFIX: &str = "/areas/";
+
+/// Where menu timings are served, when metrics are enabled
+pub const METRICS_PATH: &str = "/metrics";
+
+/// How long a client gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header lines read before giving up on a request
+const MAX_HEADER_LINES: usize = 100;
+
+/// Where the feed for `area` (`None` being the main board) is served
+///
+/// Club names become lowercase ASCII with anything else turned into
+/// dashes, so "Retro Games!" is at `/areas/retro-games.atom`.
+pub fn feed_path(area: Option<&str>) -> String {
+    let Some(area) = area else {
+        return FEED_PATH.to_string();
+    };
+    let mut slug = String::with_capacity(area.len());
+    for c in area.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    format!("{}{}.atom", AREA_FEED_PREFIX, slug)
+}
+
+/// The areas `[feed] areas` publishes, `None` being the main board
+///
+/// Clubs are named as configured; whether they exist is up to the caller.
+pub fn published_areas(config: &BbsConfig) -> Vec<Option<&str>> {
+    config
+        .feed
+        .areas
+        .iter()
+        .map(|area| area.trim())
+        .map(|area| (!area.eq_ignore_ascii_case(MAIN_AREA)).then_some(area))
+        .collect()
+}
+
+/// The published areas a guest may read, which are the ones with a feed
+///
+/// Clubs come back named as the club is, and a listed club that doesn't
+/// exist (any more) or is for members only is left out.
+pub fn public_areas(
+    config: &BbsConfig,
+    services: &CoreServices,
+) -> Result<Vec<Option<String>>, BbsError> {
+    let mut areas = Vec::new();
+    for area in published_areas(config) {
+        let area = match area {
+            None => None,
+            Some(name) => match services.clubs.get_club(name)? {
+                Some(club) => Some(club.name),
+                None => continue,
+            },
+        };
+        let membership = services.clubs.membership(area.as_deref(), None)?;
+        if services
+            .permissions
+            .area_access(Feature::Bulletins, SecurityLevel::Guest, membership)
+            != Access::None
+        {
+            areas.push(area);
+        }
+    }
+    Ok(areas)
+}
+
+/// Render `bulletins` (newest first) from `area` as an Atom document
+pub fn atom_feed(config: &BbsConfig, area: Option<&str>, bulletins: &[Bulletin]) -> String {
+    let feed_url = format!(
+        "{}{}",
+        config.feed.base_url(&config.server.bind_address),
+        feed_path(area)
+    );
+    let title = match area {
+        None => format!("{} Bulletins", config.bbs.name),
+        Some(club) => format!("{} Bulletins: {}", config.bbs.name, club),
+    };
+    // An empty feed still needs an <updated>; "now" is as good as any
+    let updated = bulletins
+        .iter()
+        .map(|b| b.posted_at)
+        .max()
+        .unwrap_or_else(Timestamp::now);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape_xml(&title)));
+    xml.push_str(&format!(
+        "  <subtitle>{}</subtitle>\n",
+        escape_xml(&config.bbs.tagline)
+    ));
+    xml.push_str(&format!("  <id>{}</id>\n", escape_xml(&feed_url)));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" href=\"{}\"/>\n",
+        escape_xml(&feed_url)
+    ));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    xml.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape_xml(&config.bbs.sysop_name)
+    ));
+    xml.push_str("  <generator>Moonbase</generator>\n");
+
+    for bulletin in bulletins {
+        // Bulletin bodies store line breaks as a literal "\n"
+        let content = bulletin.content.replace("\\n", "\n");
+
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&bulletin.title)
+        ));
+        xml.push_str(&format!(
+            "    <id>{}#{}</id>\n",
+            escape_xml(&feed_url),
+            bulletin.id
+        ));
+        xml.push_str(&format!("    <updated>{}</updated>\n", bulletin.posted_at));
+        xml.push_str(&format!(
+            "    <author><name>{}</name></author>\n",
+            escape_xml(bulletin.byline())
+        ));
+        xml.push_str(&format!(
+            "    <content type=\"text\">{}</content>\n",
+            escape_xml(&content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Escape text for XML, dropping control characters XML 1.0 forbids
+/// (ANSI color codes in a bulletin would otherwise break the feed)
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Answer feed and metrics requests on `listener` until the process exits
+pub fn serve(listener: TcpListener, config: Arc<BbsConfig>, services: Arc<CoreServices>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let config = Arc::clone(&config);
+                let services = Arc::clone(&services);
+                thread::spawn(move || {
+                    if let Err(e) = handle_request(stream, &config, &services) {
+                        eprintln!("! Feed request failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("! Error accepting feed connection: {}", e),
+        }
+    }
+}
+
+fn handle_request(
+    mut stream: TcpStream,
+    config: &BbsConfig,
+    services: &CoreServices,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // The headers don't matter, but they have to be read off the socket
+    for _ in 0..MAX_HEADER_LINES {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    // Ignore any query string, which feed readers sometimes add
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET" | "HEAD", _) if config.feed.enabled && is_feed_path(path) => {
+            match published_feed(config, services, path) {
+                Ok(Some((area, bulletins))) => (
+                    "200 OK",
+                    "application/atom+xml; charset=utf-8",
+                    atom_feed(config, area.as_deref(), &bulletins),
+                ),
+                // Unpublished areas, and bulletins guests can't read, aren't public
+                Ok(None) | Err(BbsError::PermissionDenied(_) | BbsError::FeatureDisabled(_)) => {
+                    not_found()
+                }
+                Err(e) => {
+                    eprintln!("! Feed could not load bulletins: {}", e);
+                    (
+                        "500 Internal Server Error",
+                        "text/plain; charset=utf-8",
+                        "Internal server error\n".to_string(),
+                    )
+                }
+            }
+        }
+        ("GET" | "HEAD", GUIDE_PATH) if config.feed.enabled => (
+            "200 OK",
+            "text/plain; charset=utf-8",
+            board_guide(config, services),
+        ),
+        ("GET" | "HEAD", METRICS_PATH) if config.metrics.enabled => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            services.metrics.render_prometheus(),
+        ),
+        ("GET" | "HEAD", _) => not_found(),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            "Method not allowed\n".to_string(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+fn is_feed_path(path: &str) -> bool {
+    path == FEED_PATH || path.starts_with(AREA_FEED_PREFIX)
+}
+
+/// A published area (`None` being the main board) and its newest bulletins
+type AreaFeed = (Option<String>, Vec<Bulletin>);
+
+/// The public area served at `path`, with its newest bulletins as a
+/// guest sees them
+///
+/// `None` when no public area lives there (see [`public_areas`]).
+fn published_feed(
+    config: &BbsConfig,
+    services: &CoreServices,
+    path: &str,
+) -> Result<Option<AreaFeed>, BbsError> {
+    let Some(area) = public_areas(config, services)?
+        .into_iter()
+        .find(|area| feed_path(area.as_deref()) == path)
+    else {
+        return Ok(None);
+    };
+    let bulletins = services.bulletins.get_recent_bulletins(
+        config.feed.max_items,
+        area.as_deref(),
+        None,
+        SecurityLevel::Guest,
+    )?;
+    Ok(Some((area, bulletins)))
+}
+
+fn not_found() -> (&'static str, &'static str, String) {
+    (
+        "404 Not Found",
+        "text/plain; charset=utf-8",
+        "Not found\n".to_string(),
+    )
+}
@@ -0,0 +1,489 @@
+use crate::bulletins::Bulletin;
+use crate::clubs::Club;
+use crate::errors::{BbsError, BbsResult};
+use crate::journal::{Journal, replay_json, write_snapshot};
+use crate::messages::PrivateMessage;
+use crate::services::id_service::{IdKind, IdService};
+use crate::users::User;
+
+use jiff::Timestamp;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USERS_FILE: &str = "users.json";
+const BULLETINS_FILE: &str = "bulletins.json";
+const MESSAGES_FILE: &str = "messages.json";
+const CLUBS_FILE: &str = "clubs.json";
+const IDS_FILE: &str = "ids.json";
+
+/// A referential problem found in the data directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsckIssue {
+    /// User record stored under a key that differs from its username
+    UserKeyMismatch { key: String, username: String },
+    /// Two bulletin records claim the same ID
+    DuplicateBulletinId { id: u32, key: u32 },
+    /// A bulletin is filed under a club that no longer exists
+    BulletinClubMissing { id: u32, club: String },
+    /// ID counter would hand out an ID that is already taken
+    StaleIdCounter {
+        kind: IdKind,
+        last_id: u32,
+        max_id: u32,
+    },
+    /// Two message records claim the same ID
+    DuplicateMessageId { id: u32, key: u32 },
+    /// Message sender or recipient no longer exists
+    MessageUserMissing { id: u32, username: String },
+    /// A user's read marks reference a bulletin that does not exist
+    DanglingBulletinRead { username: String, bulletin_id: u32 },
+}
+
+impl fmt::Display for FsckIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsckIssue::UserKeyMismatch { key, username } => {
+                write!(f, "user stored as '{}' is named '{}'", key, username)
+            }
+            FsckIssue::DuplicateBulletinId { id, key } => {
+                write!(f, "bulletin filed under {} claims ID {}", key, id)
+            }
+            FsckIssue::BulletinClubMissing { id, club } => {
+                write!(f, "bulletin {} is in missing club '{}'", id, club)
+            }
+            FsckIssue::StaleIdCounter {
+                kind,
+                last_id,
+                max_id,
+            } => write!(
+                f,
+                "{:?} ID counter at {} is below highest stored ID {}",
+                kind, last_id, max_id
+            ),
+            FsckIssue::DuplicateMessageId { id, key } => {
+                write!(f, "message filed under {} claims ID {}", key, id)
+            }
+            FsckIssue::MessageUserMissing { id, username } => {
+                write!(f, "message {} references missing user '{}'", id, username)
+            }
+            FsckIssue::DanglingBulletinRead {
+                username,
+                bulletin_id,
+            } => write!(
+                f,
+                "user '{}' has a read mark for missing bulletin {}",
+                username, bulletin_id
+            ),
+        }
+    }
+}
+
+/// Result of scanning the data directory
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub issues: Vec<FsckIssue>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Offline integrity checker for the JSON data directory
+///
+/// Reads the storage files directly rather than through the repositories so
+/// it sees records the repositories would silently paper over, such as a
+/// bulletin whose stored ID disagrees with its key.
+pub struct DataChecker {
+    data_dir: PathBuf,
+    users: HashMap<String, User>,
+    bulletins: BTreeMap<u32, Bulletin>,
+    // Last ID recorded by pre-allocator bulletin files
+    legacy_bulletin_last: u32,
+    messages: BTreeMap<u32, PrivateMessage>,
+    // Keyed by lowercased name, as the club repository files them
+    clubs: BTreeMap<String, Club>,
+    ids: IdService,
+}
+
+impl DataChecker {
+    /// Load every storage file found in `data_dir`; missing files count as empty
+    pub fn load<P: AsRef<Path>>(data_dir: P) -> BbsResult<Self> {
+        let data_dir = data_dir.as_ref().to_path_buf();
+
+        let users: HashMap<String, User> = read_journaled(&data_dir.join(USERS_FILE), None)?
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| BbsError::Configuration(format!("Failed to parse users file: {}", e)))?
+            .unwrap_or_default();
+
+        let mut bulletins = BTreeMap::new();
+        let mut legacy_bulletin_last = 0;
+        if let Some(data) = read_journaled(&data_dir.join(BULLETINS_FILE), Some("bulletins"))? {
+            if let Some(obj) = data.get("bulletins") {
+                bulletins = serde_json::from_value(obj.clone()).map_err(|e| {
+                    BbsError::Configuration(format!("Failed to parse bulletins file: {}", e))
+                })?;
+            }
+            if let Some(next_id) = data.get("next_id").and_then(|v| v.as_u64()) {
+                legacy_bulletin_last = (next_id as u32).saturating_sub(1);
+            }
+        }
+
+        let messages: BTreeMap<u32, PrivateMessage> =
+            read_journaled(&data_dir.join(MESSAGES_FILE), None)?
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| {
+                    BbsError::Configuration(format!("Failed to parse messages file: {}", e))
+                })?
+                .unwrap_or_default();
+
+        let clubs: BTreeMap<String, Club> = read_journaled(&data_dir.join(CLUBS_FILE), None)?
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| BbsError::Configuration(format!("Failed to parse clubs file: {}", e)))?
+            .unwrap_or_default();
+
+        Ok(Self {
+            users,
+            bulletins,
+            legacy_bulletin_last,
+            messages,
+            clubs,
+            ids: IdService::new(&data_dir)?,
+            data_dir,
+        })
+    }
+
+    /// Scan all loaded records and report every issue found
+    pub fn check(&self) -> BbsResult<FsckReport> {
+        let mut issues = Vec::new();
+
+        let mut user_keys: Vec<&String> = self.users.keys().collect();
+        user_keys.sort();
+        for key in user_keys {
+            let user = &self.users[key];
+            if *key != user.username {
+                issues.push(FsckIssue::UserKeyMismatch {
+                    key: key.clone(),
+                    username: user.username.clone(),
+                });
+            }
+        }
+
+        issues.extend(
+            duplicate_keys(self.bulletins.iter().map(|(k, b)| (*k, b.id)))
+                .map(|(key, id)| FsckIssue::DuplicateBulletinId { id, key }),
+        );
+
+        for bulletin in self.bulletins.values() {
+            if let Some(club) = self.missing_club(bulletin) {
+                issues.push(FsckIssue::BulletinClubMissing {
+                    id: bulletin.id,
+                    club: club.to_string(),
+                });
+            }
+        }
+
+        for (kind, max_id) in [
+            (IdKind::Bulletin, self.max_bulletin_id()),
+            (IdKind::Message, self.max_message_id()),
+        ] {
+            let last_id = self.ids.last_id(kind)?;
+            if last_id < max_id {
+                issues.push(FsckIssue::StaleIdCounter {
+                    kind,
+                    last_id,
+                    max_id,
+                });
+            }
+        }
+
+        issues.extend(
+            duplicate_keys(self.messages.iter().map(|(k, m)| (*k, m.id)))
+                .map(|(key, id)| FsckIssue::DuplicateMessageId { id, key }),
+        );
+
+        for message in self.messages.values() {
+            for username in self.missing_participants(message) {
+                issues.push(FsckIssue::MessageUserMissing {
+                    id: message.id,
+                    username,
+                });
+            }
+        }
+
+        for (username, bulletin_id) in self.dangling_reads()? {
+            issues.push(FsckIssue::DanglingBulletinRead {
+                username,
+                bulletin_id,
+            });
+        }
+
+        Ok(FsckReport { issues })
+    }
+
+    /// Repair every issue that has a safe fix, backing the data up first
+    ///
+    /// Returns the backup directory. Duplicates are given fresh IDs, messages
+    /// are hidden from missing users (and dropped once nobody can see them),
+    /// bulletins in missing clubs are dropped rather than made public, and
+    /// read marks for missing bulletins are discarded.
+    pub fn repair(&mut self) -> BbsResult<PathBuf> {
+        let backup_dir = self.backup()?;
+
+        // Re-key users under their own names, unless that would clobber another account
+        let mismatched: Vec<String> = self
+            .users
+            .iter()
+            .filter(|(key, user)| **key != user.username)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in mismatched {
+            let username = self.users[&key].username.clone();
+            if !self.users.contains_key(&username)
+                && let Some(user) = self.users.remove(&key)
+            {
+                self.users.insert(username, user);
+            }
+        }
+
+        // Bring the counters past every stored ID before handing out new ones
+        self.ids.reserve_through(
+            IdKind::Bulletin,
+            self.max_bulletin_id().max(self.legacy_bulletin_last),
+        )?;
+        self.ids
+            .reserve_through(IdKind::Message, self.max_message_id())?;
+
+        // Give duplicate or mis-keyed bulletins fresh IDs
+        let dupes: Vec<u32> = duplicate_keys(self.bulletins.iter().map(|(k, b)| (*k, b.id)))
+            .map(|(key, _)| key)
+            .collect();
+        for key in dupes {
+            if let Some(mut bulletin) = self.bulletins.remove(&key) {
+                bulletin.id = self.ids.next_id(IdKind::Bulletin)?;
+                self.bulletins.insert(bulletin.id, bulletin);
+            }
+        }
+
+        // Same for messages
+        let dupes: Vec<u32> = duplicate_keys(self.messages.iter().map(|(k, m)| (*k, m.id)))
+            .map(|(key, _)| key)
+            .collect();
+        for key in dupes {
+            if let Some(mut message) = self.messages.remove(&key) {
+                message.id = self.ids.next_id(IdKind::Message)?;
+                self.messages.insert(message.id, message);
+            }
+        }
+
+        // Drop bulletins whose club is gone; the backup still has them
+        let orphaned: Vec<u32> = self
+            .bulletins
+            .iter()
+            .filter(|(_, bulletin)| self.missing_club(bulletin).is_some())
+            .map(|(key, _)| *key)
+            .collect();
+        for key in orphaned {
+            self.bulletins.remove(&key);
+        }
+
+        // Hide messages from users who no longer exist
+        let ids: Vec<u32> = self.messages.keys().copied().collect();
+        for id in ids {
+            let missing = self.missing_participants(&self.messages[&id]);
+            let message = self.messages.get_mut(&id).expect("id collected above");
+            for username in missing {
+                message.delete_for(&username);
+            }
+            if message.is_deleted_by_sender && message.is_deleted_by_recipient {
+                self.messages.remove(&id);
+            }
+        }
+
+        // Drop read marks for bulletins that are gone
+        let last_assigned = self.ids.last_id(IdKind::Bulletin)?;
+        for user in self.users.values_mut() {
+            let reads = &mut user.bulletin_reads;
+            reads
+                .read_above
+                .retain(|id| self.bulletins.contains_key(id));
+            reads.high_water = reads.high_water.min(last_assigned);
+        }
+
+        self.save()?;
+        Ok(backup_dir)
+    }
+
+    fn max_bulletin_id(&self) -> u32 {
+        self.bulletins
+            .iter()
+            .map(|(k, b)| (*k).max(b.id))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn max_message_id(&self) -> u32 {
+        self.messages
+            .iter()
+            .map(|(k, m)| (*k).max(m.id))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The club `bulletin` is filed under, if there is no such club
+    fn missing_club<'a>(&self, bulletin: &'a Bulletin) -> Option<&'a str> {
+        bulletin
+            .area
+            .as_deref()
+            .filter(|club| !self.clubs.contains_key(&club.trim().to_lowercase()))
+    }
+
+    /// Sender/recipient names that have no user record, skipping sides already deleted
+    fn missing_participants(&self, message: &PrivateMessage) -> Vec<String> {
+        let mut missing = Vec::new();
+        if !message.is_deleted_by_sender && !self.users.contains_key(&message.sender) {
+            missing.push(message.sender.clone());
+        }
+        if !message.is_deleted_by_recipient
+            && message.recipient != message.sender
+            && !self.users.contains_key(&message.recipient)
+        {
+            missing.push(message.recipient.clone());
+        }
+        missing
+    }
+
+    /// Read marks above a user's high-water mark for bulletins that no longer
+    /// exist, plus a high-water mark past any ID ever handed out
+    fn dangling_reads(&self) -> BbsResult<Vec<(String, u32)>> {
+        let last_assigned = self
+            .ids
+            .last_id(IdKind::Bulletin)?
+            .max(self.legacy_bulletin_last)
+            .max(self.max_bulletin_id());
+        let mut dangling = Vec::new();
+
+        let mut users: Vec<&User> = self.users.values().collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        for user in users {
+            let reads = &user.bulletin_reads;
+            if reads.high_water > last_assigned {
+                dangling.push((user.username.clone(), reads.high_water));
+            }
+            for id in &reads.read_above {
+                if !self.bulletins.contains_key(id) {
+                    dangling.push((user.username.clone(), *id));
+                }
+            }
+        }
+        Ok(dangling)
+    }
+
+    /// Copy the storage files into a timestamped directory under `backups/`
+    fn backup(&self) -> BbsResult<PathBuf> {
+        let backup_dir = self
+            .data_dir
+            .join("backups")
+            .join(format!("fsck-{}", Timestamp::now().as_millisecond()));
+        fs::create_dir_all(&backup_dir).map_err(|e| {
+            BbsError::Configuration(format!("Failed to create backup directory: {}", e))
+        })?;
+
+        for file in [USERS_FILE, BULLETINS_FILE, MESSAGES_FILE, IDS_FILE] {
+            let src = self.data_dir.join(file);
+            let journal = Journal::for_file(&src).path().to_path_buf();
+            for src in [src, journal] {
+                if let Some(name) = src.file_name().filter(|_| src.exists()) {
+                    fs::copy(&src, backup_dir.join(name)).map_err(|e| {
+                        BbsError::Configuration(format!(
+                            "Failed to back up {}: {}",
+                            src.display(),
+                            e
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        Ok(backup_dir)
+    }
+
+    /// Write the repaired records back in the same layout the repositories use
+    fn save(&self) -> BbsResult<()> {
+        let users = serde_json::to_string_pretty(&self.users)
+            .map_err(|e| BbsError::Configuration(format!("Failed to serialize users: {}", e)))?;
+        write_snapshot(&self.data_dir.join(USERS_FILE), &users)?;
+
+        let bulletins = serde_json::json!({
+            "bulletins": self.bulletins,
+        });
+        let bulletins = serde_json::to_string_pretty(&bulletins).map_err(|e| {
+            BbsError::Configuration(format!("Failed to serialize bulletins: {}", e))
+        })?;
+        write_snapshot(&self.data_dir.join(BULLETINS_FILE), &bulletins)?;
+
+        let messages = serde_json::to_string_pretty(&self.messages)
+            .map_err(|e| BbsError::Configuration(format!("Failed to serialize messages: {}", e)))?;
+        write_snapshot(&self.data_dir.join(MESSAGES_FILE), &messages)?;
+
+        // The files now hold everything the journals did
+        for file in [USERS_FILE, BULLETINS_FILE, MESSAGES_FILE] {
+            Journal::for_file(&self.data_dir.join(file)).clear()?;
+        }
+        Ok(())
+    }
+}
+
+/// Records whose stored ID disagrees with the key they are filed under
+///
+/// Keys are unique, so a record can only duplicate an ID by carrying one
+/// that differs from its own key; the correctly filed record keeps the ID.
+fn duplicate_keys(records: impl Iterator<Item = (u32, u32)>) -> impl Iterator<Item = (u32, u32)> {
+    records.filter(|(key, id)| key != id)
+}
+
+fn read_json(path: &Path) -> BbsResult<Option<serde_json::Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        BbsError::Configuration(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| BbsError::Configuration(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Read a storage file with its journal applied
+///
+/// Records live at the top level, or under `records` for files that wrap
+/// them in an object.
+fn read_journaled(path: &Path, records: Option<&str>) -> BbsResult<Option<serde_json::Value>> {
+    let mut data = read_json(path)?;
+    let had_data = data.is_some();
+
+    let root = data.get_or_insert_with(|| serde_json::json!({}));
+    let target = match records {
+        Some(key) => root
+            .as_object_mut()
+            .map(|obj| obj.entry(key).or_insert_with(|| serde_json::json!({}))),
+        None => Some(root),
+    };
+    let replayed = match target {
+        Some(serde_json::Value::Object(map)) => replay_json(path, map)?,
+        _ => false,
+    };
+
+    Ok(data.filter(|_| had_data || replayed))
+}
@@ -0,0 +1,204 @@
+//! Outbound telnet calls to other boards
+//!
+//! The gateway opens a client connection with the telnet crate's client
+//! mode and shuttles bytes between it and the caller until one side hangs
+//! up, the caller presses the escape key, or the time limit runs out.
+
+use crate::config::GatewayBoard;
+use crate::session_stream::SessionStream;
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use telnet_negotiation::{Side, TelnetOption, TelnetStream, TerminalCapabilities};
+
+/// Ctrl-], the same escape key as the classic telnet client
+pub const ESCAPE_BYTE: u8 = 0x1d;
+
+/// How long to wait for the other board to answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long each side is read before checking the other
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Window size reported when the caller's client never sent one
+const DEFAULT_WINDOW: (u16, u16) = (80, 24);
+
+/// Why a gateway call ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatewayEnd {
+    /// The other board closed the connection
+    RemoteClosed,
+    /// The caller pressed the escape key
+    Escaped,
+    /// The time limit ran out
+    TimeLimit,
+    /// The caller dropped their connection
+    CallerDisconnected,
+}
+
+impl GatewayEnd {
+    /// Short reason for the server log
+    pub fn describe(&self) -> &'static str {
+        match self {
+            GatewayEnd::RemoteClosed => "remote closed",
+            GatewayEnd::Escaped => "caller escaped",
+            GatewayEnd::TimeLimit => "time limit",
+            GatewayEnd::CallerDisconnected => "caller disconnected",
+        }
+    }
+}
+
+/// What happened on a gateway call, for logging
+#[derive(Debug, Clone, Copy)]
+pub struct GatewayReport {
+    pub end: GatewayEnd,
+    pub duration: Duration,
+    /// Bytes received from the other board
+    pub bytes_in: u64,
+    /// Bytes the caller sent to the other board
+    pub bytes_out: u64,
+}
+
+/// Open a telnet client connection to `board`
+///
+/// The other board is told the caller's terminal type and window size, so
+/// it can lay out its screens as if the caller had dialed it directly.
+pub fn connect(board: &GatewayBoard, caller: &TerminalCapabilities) -> io::Result<TelnetStream> {
+    let mut last_error = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} did not resolve", board.address),
+    );
+
+    for addr in board.address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(socket) => {
+                let mut remote = TelnetStream::client(socket);
+                if let Some(terminal_type) = &caller.terminal_type {
+                    remote.set_client_terminal_type(terminal_type);
+                }
+                remote.set_client_window_size(
+                    caller.width.unwrap_or(DEFAULT_WINDOW.0),
+                    caller.height.unwrap_or(DEFAULT_WINDOW.1),
+                )?;
+                return Ok(remote);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Relay bytes between the caller and the other board until the call ends
+///
+/// The caller's echo follows the other board's: when it takes over echoing
+/// (as most boards do) the caller's client is told to stop echoing locally,
+/// and local echo is restored afterwards. The caller's read timeout is put
+/// back before returning.
+pub fn proxy(
+    caller: &mut SessionStream,
+    remote: &mut TelnetStream,
+    limit: Duration,
+) -> io::Result<GatewayReport> {
+    remote.set_read_timeout(Some(POLL_INTERVAL))?;
+    let previous_timeout = caller.read_timeout()?;
+    caller.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let started = Instant::now();
+    let mut remote_echo = false;
+    let mut bytes_in = 0u64;
+    let mut bytes_out = 0u64;
+    let mut buffer = [0u8; 4096];
+
+    let outcome = loop {
+        if started.elapsed() >= limit {
+            break Ok(GatewayEnd::TimeLimit);
+        }
+
+        // Other board -> caller
+        match remote.read(&mut buffer) {
+            Ok(0) => break Ok(GatewayEnd::RemoteClosed),
+            Ok(n) => {
+                bytes_in += n as u64;
+                if let Err(e) = caller.write_all(&buffer[..n]).and_then(|_| caller.flush()) {
+                    break Err(e);
+                }
+            }
+            Err(e) if is_timeout(&e) => {}
+            // A reset from the other board ends the call, not the session
+            Err(_) => break Ok(GatewayEnd::RemoteClosed),
+        }
+
+        let echo = remote.is_option_enabled(Side::Remote, TelnetOption::ECHO);
+        if echo != remote_echo {
+            let result = if echo {
+                caller.request_echo_off()
+            } else {
+                caller.request_echo_on()
+            };
+            if let Err(e) = result {
+                break Err(e);
+            }
+            remote_echo = echo;
+        }
+
+        // Caller -> other board
+        match caller.read(&mut buffer) {
+            Ok(0) => break Ok(GatewayEnd::CallerDisconnected),
+            Ok(n) => {
+                let input = &buffer[..n];
+                let escape = input.iter().position(|&b| b == ESCAPE_BYTE);
+                let forward = &input[..escape.unwrap_or(n)];
+
+                bytes_out += forward.len() as u64;
+                if remote
+                    .write_all(forward)
+                    .and_then(|_| remote.flush())
+                    .is_err()
+                {
+                    break Ok(GatewayEnd::RemoteClosed);
+                }
+                if escape.is_some() {
+                    break Ok(GatewayEnd::Escaped);
+                }
+            }
+            Err(e) if is_timeout(&e) => {}
+            // The caller resized their window; tell the other board
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                if let Some(size) = caller.take_resize()
+                    && remote
+                        .set_client_window_size(size.width, size.height)
+                        .is_err()
+                {
+                    break Ok(GatewayEnd::RemoteClosed);
+                }
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    let _ = remote.shutdown(std::net::Shutdown::Both);
+    // Put the timeout back even when the call ended in an error
+    let restored = caller.set_read_timeout(previous_timeout);
+    let end = outcome?;
+    restored?;
+
+    if remote_echo && end != GatewayEnd::CallerDisconnected {
+        caller.request_echo_on()?;
+    }
+
+    Ok(GatewayReport {
+        end,
+        duration: started.elapsed(),
+        bytes_in,
+        bytes_out,
+    })
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
@@ -0,0 +1,263 @@
+//! The Board Guide: a plain-text manual written from this board's settings
+//!
+//! What a caller can do depends on the config (features turned off or
+//! limited to some levels, gateway boards, timeouts) and on what the SysOp
+//! has added since (clubs, script commands). Writing the guide from those
+//! each time it is asked for keeps it in step with the menus. Callers page
+//! through it from the main menu, and the feed listener serves it at
+//! [`GUIDE_PATH`] for download.
+
+use crate::clubs::JoinRule;
+use crate::config::{BbsConfig, FeatureAccess, SecurityLevel};
+use crate::services::CoreServices;
+
+/// Where the guide is served when the feed is on
+pub const GUIDE_PATH: &str = "/guide.txt";
+
+/// The guide for this board as it is configured right now
+pub fn board_guide(config: &BbsConfig, services: &CoreServices) -> String {
+    let mut guide = Guide::default();
+    let features = &config.features;
+
+    guide.line(&format!("{} - BOARD GUIDE", config.bbs.name));
+    guide.line(&config.bbs.tagline);
+    guide.line(&format!(
+        "SysOp: {} | Location: {} | Est. {}",
+        config.bbs.sysop_name, config.bbs.location, config.bbs.established
+    ));
+
+    guide.section("GETTING STARTED");
+    if features.allow_anonymous {
+        guide.line("Guests can look around without an account. Press L at the main menu to");
+        guide.line("log in, or to register if you are new.");
+    } else {
+        guide.line("You need to log in before using the board; new callers can register at");
+        guide.line("the login prompt.");
+    }
+    guide.line(&username_rules(config));
+    guide.line(&format!(
+        "Callers idle for {} minutes are disconnected.",
+        config.timeouts.idle_timeout.as_secs() / 60
+    ));
+
+    guide.section("MAIN MENU");
+    guide.key(
+        "1",
+        &format!("Bulletin Board ({})", access(features.bulletins)),
+    );
+    guide.key("2", "User Directory");
+    guide.key(
+        "3",
+        &format!("Private Messages ({})", access(features.messages)),
+    );
+    if features.files == FeatureAccess::Off {
+        guide.key("4", "File Library (turned off)");
+    } else {
+        guide.key("4", "File Library (coming soon)");
+    }
+    if config.gateway.enabled {
+        guide.key(
+            "G",
+            &format!("Other Boards ({})", level_only(config.gateway.level)),
+        );
+    }
+    for command in &config.commands.commands {
+        guide.key(
+            &command.key.to_uppercase(),
+            &format!("{} ({})", command.description, level_only(command.level)),
+        );
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(scripts) = &services.scripts {
+        for command in scripts.commands() {
+            guide.key(&command.key.to_uppercase(), &command.description);
+        }
+    }
+    if features.bulletins != FeatureAccess::Off {
+        guide.key("S", "Board Statistics");
+    }
+    guide.key("F", "Client Fingerprint (SysOp only)");
+    if features.allow_anonymous {
+        guide.key("L", "Login / Register");
+    }
+    guide.key("O", "Logout");
+    guide.key("H", "Board Guide (this guide)");
+    guide.key("Q", "Quit");
+
+    if features.bulletins != FeatureAccess::Off {
+        guide.section("BULLETIN BOARD");
+        guide.key("L", "List all bulletins");
+        guide.key("#", "Read a bulletin by its number");
+        guide.key("N", "Read the next unread bulletin");
+        guide.key("M", "Mark all read");
+        guide.key("P", "Post a new bulletin");
+        guide.key("H / S", "Hide or show bulletins you have read");
+        guide.key("U / A", "Show only unread, or all bulletins");
+        guide.key("C", "Clubs");
+        guide.key("X", "Leave the club you are in");
+        guide.key("B", "Back to the main menu");
+        guide.line(&format!(
+            "Bulletins can be up to {} characters.",
+            features.max_message_length
+        ));
+
+        let clubs = services.clubs.list_clubs().unwrap_or_default();
+        if !clubs.is_empty() {
+            guide.blank();
+            guide.line("Clubs are bulletin areas for members only:");
+            for club in clubs {
+                let join = match club.join {
+                    JoinRule::Password { .. } => "join with its password",
+                    JoinRule::Approval => "ask to join; the SysOp approves",
+                };
+                guide.line(&format!(
+                    "  {} - {} ({})",
+                    club.name, club.description, join
+                ));
+            }
+        }
+    }
+
+    if features.messages != FeatureAccess::Off {
+        guide.section("PRIVATE MESSAGES");
+        guide.key("I", "Inbox");
+        guide.key("S", "Sent messages");
+        guide.key("C", "Compose a new message");
+        guide.key("A", "Mark all read (inbox)");
+        guide.key("X", "Delete read messages (inbox)");
+        guide.key("R / D", "Reply to or delete the message you are reading");
+        guide.key("M", "Back to the main menu");
+        guide.line(&format!(
+            "Messages can be up to {} characters, and you must be logged in.",
+            features.max_message_length
+        ));
+    }
+
+    guide.section("USER DIRECTORY");
+    guide.key("L", "List all users");
+    guide.key("W", "Who's online");
+    guide.key("T / N", "Sort by last login or by username");
+    guide.key("R", "Screen reader mode");
+    guide.line("Once logged in:");
+    guide.key("P", "View your profile");
+    guide.key("D", "Set your display name");
+    guide.key("F", "Forward your private messages to another user");
+    guide.key("V", "Set a vacation reply");
+    guide.key("Q", "Quiet mode (no bell on new mail)");
+
+    if config.gateway.enabled {
+        guide.section("OTHER BOARDS");
+        guide.line(&format!(
+            "Calls to other boards last up to {} minutes. Press Ctrl-] to come back.",
+            config.gateway.max_minutes
+        ));
+        for board in &config.gateway.boards {
+            guide.line(&format!("  {}", board.name));
+        }
+    }
+
+    if config.feed.enabled {
+        let base_url = config.feed.base_url(&config.server.bind_address);
+        guide.section("ON THE WEB");
+        let areas = crate::feed::public_areas(config, services).unwrap_or_default();
+        for area in areas {
+            guide.line(&format!(
+                "{} bulletins: {}{}",
+                area.as_deref().unwrap_or("Public"),
+                base_url,
+                crate::feed::feed_path(area.as_deref())
+            ));
+        }
+        guide.line(&format!("This guide: {}{}", base_url, GUIDE_PATH));
+    }
+
+    guide.text
+}
+
+/// Who may use a feature, in words
+fn access(access: FeatureAccess) -> String {
+    match access {
+        FeatureAccess::Off => "turned off".to_string(),
+        FeatureAccess::ReadOnly => "read only".to_string(),
+        FeatureAccess::Gated { read, write } if read == write => level_only(read).to_string(),
+        FeatureAccess::Gated { read, write } => {
+            format!("{} can read, {} can post", who(read), who(write))
+        }
+    }
+}
+
+fn level_only(level: SecurityLevel) -> &'static str {
+    match level {
+        SecurityLevel::Guest => "open to everyone",
+        SecurityLevel::User => "members only",
+        SecurityLevel::Sysop => "SysOp only",
+    }
+}
+
+fn who(level: SecurityLevel) -> &'static str {
+    match level {
+        SecurityLevel::Guest => "everyone",
+        SecurityLevel::User => "members",
+        SecurityLevel::Sysop => "the SysOp",
+    }
+}
+
+/// What a new username may look like
+fn username_rules(config: &BbsConfig) -> String {
+    let rules = &config.features.usernames;
+    let mut allowed = vec![if rules.allow_unicode {
+        "letters"
+    } else {
+        "letters A-Z"
+    }];
+    if rules.allow_digits {
+        allowed.push("digits");
+    }
+    let symbols = format!("any of {}", rules.symbols);
+    if !rules.symbols.is_empty() {
+        allowed.push(&symbols);
+    }
+    let last = allowed.pop().unwrap_or_default();
+    let allowed = if allowed.is_empty() {
+        last.to_string()
+    } else {
+        format!("{} and {}", allowed.join(", "), last)
+    };
+    format!(
+        "Usernames are up to {} characters: {}.{}",
+        config.features.max_username_length,
+        allowed,
+        if rules.case_insensitive {
+            " Case doesn't matter when logging in."
+        } else {
+            ""
+        }
+    )
+}
+
+/// Text under construction, one line at a time
+#[derive(Default)]
+struct Guide {
+    text: String,
+}
+
+impl Guide {
+    fn line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+
+    fn blank(&mut self) {
+        self.text.push('\n');
+    }
+
+    fn section(&mut self, title: &str) {
+        self.blank();
+        self.line(title);
+    }
+
+    /// A hotkey and what it does, lined up in two columns
+    fn key(&mut self, key: &str, description: &str) {
+        self.line(&format!("  {:<6} {}", key, description));
+    }
+}
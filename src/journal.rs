@@ -0,0 +1,204 @@
+//! Write-ahead journal for the JSON storage files
+//!
+//! Rewriting a whole JSON file on every change gets slow as the board
+//! grows, and a crash halfway through `fs::write` leaves it truncated.
+//! Instead, each repository appends one line per change to a journal next
+//! to its file (`users.json` gets `users.journal`) and only rewrites the
+//! file itself, via a temp file and rename, when compacting:
+//!
+//! ```text
+//! {"op":"put","key":"alice","value":{...}}
+//! {"op":"delete","key":17}
+//! ```
+//!
+//! Repositories also compact when they are dropped. After a crash, the
+//! journal is replayed over the file at startup, unless the file is newer
+//! (a compaction finished but the crash came before the journal was
+//! cleared), and then compacted. Entries hold whole records, so replaying
+//! one twice does no harm.
+
+use crate::errors::{BbsError, BbsResult};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Journal entries written before a repository compacts its file
+pub const COMPACT_AFTER: usize = 256;
+
+/// One change to a keyed collection of records
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JournalEntry<K, V> {
+    /// Add or replace the record under `key`
+    Put { key: K, value: V },
+    /// Remove the record under `key`
+    Delete { key: K },
+}
+
+/// Append-only change log for one JSON storage file
+pub struct Journal {
+    path: PathBuf,
+    file: Option<File>,
+    // Entries written since the last compaction
+    entries: usize,
+}
+
+impl Journal {
+    /// The journal belonging to `data_file`
+    pub fn for_file(data_file: &Path) -> Self {
+        Self {
+            path: data_file.with_extension("journal"),
+            file: None,
+            entries: 0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries still to be applied to `data_file`
+    ///
+    /// Empty when there is no journal or `data_file` was written after it.
+    /// A torn last line (the crash came mid-append) is ignored; damage
+    /// anywhere else is an error.
+    pub fn pending<K, V>(&mut self, data_file: &Path) -> BbsResult<Vec<JournalEntry<K, V>>>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        if !self.path.exists() || is_newer(data_file, &self.path) {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.path).map_err(|e| {
+            BbsError::Configuration(format!("Failed to read {}: {}", self.path.display(), e))
+        })?;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+        let mut entries = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) if i + 1 == lines.len() => break,
+                Err(e) => {
+                    return Err(BbsError::Configuration(format!(
+                        "Failed to parse {} line {}: {}",
+                        self.path.display(),
+                        i + 1,
+                        e
+                    )));
+                }
+            }
+        }
+        self.entries = entries.len();
+        Ok(entries)
+    }
+
+    /// Durably record one change
+    pub fn append<K: Serialize, V: Serialize>(
+        &mut self,
+        entry: &JournalEntry<K, V>,
+    ) -> BbsResult<()> {
+        let mut line = serde_json::to_string(entry).map_err(|e| {
+            BbsError::Configuration(format!("Failed to serialize journal entry: {}", e))
+        })?;
+        line.push('\n');
+
+        let write_error =
+            |e| BbsError::Configuration(format!("Failed to write {}: {}", self.path.display(), e));
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .map_err(write_error)?;
+            self.file = Some(file);
+        }
+        if let Some(file) = self.file.as_mut() {
+            // One write per entry, so a crash can only tear the last line
+            file.write_all(line.as_bytes()).map_err(write_error)?;
+            file.sync_data().map_err(write_error)?;
+        }
+
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Whether anything has been written since the last compaction
+    pub fn has_entries(&self) -> bool {
+        self.entries > 0
+    }
+
+    /// Whether enough entries have piled up to rewrite the file
+    pub fn needs_compaction(&self) -> bool {
+        self.entries >= COMPACT_AFTER
+    }
+
+    /// Forget every entry; call once the data file holds them all
+    pub fn clear(&mut self) -> BbsResult<()> {
+        self.file = None;
+        self.entries = 0;
+        if self.path.exists() {
+            fs::remove_file(&self.path).map_err(|e| {
+                BbsError::Configuration(format!("Failed to remove {}: {}", self.path.display(), e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Replace `path` with `content` via a temp file and rename, so a crash
+/// leaves either the old file or the new one
+pub fn write_snapshot(path: &Path, content: &str) -> BbsResult<()> {
+    let write_error =
+        |e| BbsError::Configuration(format!("Failed to write {}: {}", path.display(), e));
+
+    let tmp_file = path.with_extension("json.tmp");
+    let mut file = File::create(&tmp_file).map_err(write_error)?;
+    file.write_all(content.as_bytes()).map_err(write_error)?;
+    file.sync_all().map_err(write_error)?;
+    fs::rename(&tmp_file, path).map_err(write_error)
+}
+
+/// Apply `data_file`'s pending journal to its records as raw JSON, keyed
+/// the way `serde_json` writes map keys
+///
+/// For tools that read the files directly rather than through a
+/// repository. Returns whether there was anything to apply.
+pub fn replay_json(
+    data_file: &Path,
+    records: &mut serde_json::Map<String, serde_json::Value>,
+) -> BbsResult<bool> {
+    let entries: Vec<JournalEntry<serde_json::Value, serde_json::Value>> =
+        Journal::for_file(data_file).pending(data_file)?;
+    let key = |key: serde_json::Value| match key {
+        serde_json::Value::String(s) => s,
+        other => other.to_string(),
+    };
+
+    let replayed = !entries.is_empty();
+    for entry in entries {
+        match entry {
+            JournalEntry::Put { key: k, value } => {
+                records.insert(key(k), value);
+            }
+            JournalEntry::Delete { key: k } => {
+                records.remove(&key(k));
+            }
+        }
+    }
+    Ok(replayed)
+}
+
+/// Whether `data_file` was written after `journal`
+fn is_newer(data_file: &Path, journal: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(data_file), modified(journal)) {
+        (Some(data), Some(journal)) => data > journal,
+        _ => false,
+    }
+}
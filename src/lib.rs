@@ -1,13 +1,35 @@
+pub mod attention;
 pub mod box_renderer;
 pub mod bulletin_repository;
 pub mod bulletins;
+pub mod client_profile;
+pub mod club_repository;
+pub mod clubs;
+pub mod color;
 pub mod config;
+pub mod confusables;
+pub mod connection;
+pub mod demo;
 pub mod errors;
+pub mod events;
+pub mod external;
+pub mod feed;
+pub mod fsck;
+pub mod gateway;
+pub mod guide;
+pub mod journal;
 pub mod menu;
 pub mod message_repository;
 pub mod messages;
+pub mod motd;
+pub mod post_check;
+#[cfg(feature = "scripting")]
+pub mod scripting;
 pub mod services;
 pub mod session;
+pub mod session_stream;
+pub mod text_width;
+pub mod tls;
 pub mod user_repository;
 pub mod users;
 
@@ -1,22 +1,46 @@
+mod attention;
 mod box_renderer;
 mod bulletin_repository;
 mod bulletins;
+mod client_profile;
+mod club_repository;
+mod clubs;
+mod color;
 mod config;
+mod confusables;
+mod connection;
+mod demo;
 mod errors;
+mod events;
+mod external;
+mod feed;
+mod fsck;
+mod gateway;
+mod guide;
+mod journal;
 mod menu;
 mod message_repository;
 mod messages;
+mod motd;
+mod post_check;
+#[cfg(feature = "scripting")]
+mod scripting;
 mod services;
 mod session;
+mod session_stream;
+mod text_width;
+mod tls;
 mod user_repository;
 mod users;
 
 use box_renderer::BoxRenderer;
 use bulletin_repository::JsonBulletinStorage;
+use club_repository::JsonClubStorage;
 use config::BbsConfig;
-use errors::BbsResult;
+use connection::{Connection, Port};
+use errors::{BbsError, BbsResult};
 use message_repository::JsonMessageStorage;
-use services::CoreServices;
+use services::{CoreServices, IdService};
 use session::BbsSession;
 use user_repository::JsonUserStorage;
 
@@ -28,6 +52,26 @@ use std::thread;
 
 /// Moonbase entry point
 fn main() -> BbsResult<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--fsck") {
+        let repair = args.iter().any(|a| a == "--repair");
+        let clean = run_fsck("data", repair)?;
+        std::process::exit(if clean { 0 } else { 1 });
+    }
+    if let Some(pos) = args.iter().position(|a| a == "--export") {
+        let (Some(format), Some(dest)) = (args.get(pos + 1), args.get(pos + 2)) else {
+            eprintln!("Usage: moonbase --export <mbox|maildir> <path>");
+            std::process::exit(2);
+        };
+        run_export("data", format, dest)?;
+        return Ok(());
+    }
+    if args.iter().any(|a| a == "--seed-demo") {
+        let wipe = args.iter().any(|a| a == "--wipe");
+        let seeded = run_seed_demo("data", wipe)?;
+        std::process::exit(if seeded { 0 } else { 1 });
+    }
+
     // Load configuration
     let config = match BbsConfig::load_from_file("bbs.conf") {
         Ok(config) => {
@@ -46,6 +90,39 @@ fn main() -> BbsResult<()> {
         return Err(e);
     }
 
+    // Menu commands that run programs must be safe before anyone can call
+    if let Err(e) = external::check(&config.commands, menu::menu_main::RESERVED_KEYS) {
+        eprintln!("x Invalid [commands] entry: {}", e);
+        return Err(e);
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    if config.scripting.enabled {
+        eprintln!("! scripting is enabled but this build has no scripting support");
+    }
+
+    // The certificate serves both START_TLS and the TLS port
+    if !config.server.tls_certificate.is_empty() {
+        match tls::load(
+            &config.server.tls_certificate,
+            &config.server.tls_private_key,
+        ) {
+            Ok(()) => println!("+ TLS certificate loaded"),
+            Err(e) => {
+                eprintln!("x Failed to load TLS certificate: {}", e);
+                return Err(e);
+            }
+        }
+    }
+    if config.server.start_tls && tls::upgrader().is_none() {
+        eprintln!(
+            "! start_tls is enabled but no tls_certificate is set; sessions stay in plaintext"
+        );
+    }
+    if config.server.tls_port.is_some() && tls::server_config().is_none() {
+        eprintln!("! tls_port is set but no tls_certificate is; the TLS port stays closed");
+    }
+
     // Wrap config in Arc for sharing between threads
     let config = Arc::new(config);
 
@@ -61,8 +138,17 @@ fn main() -> BbsResult<()> {
         }
     };
 
+    // One ID allocator for every repository so IDs never collide across threads
+    let ids = match IdService::new("data") {
+        Ok(ids) => ids,
+        Err(e) => {
+            eprintln!("x Failed to initialize ID allocator: {}", e);
+            return Err(e);
+        }
+    };
+
     // Initialize shared bulletin storage
-    let bulletin_storage = match JsonBulletinStorage::new("data") {
+    let bulletin_storage = match JsonBulletinStorage::with_ids("data", ids.clone()) {
         Ok(storage) => {
             println!("+ Bulletin storage initialized");
             Arc::new(Mutex::new(storage))
@@ -74,7 +160,7 @@ fn main() -> BbsResult<()> {
     };
 
     // Initialize shared message storage
-    let message_storage = match JsonMessageStorage::new("data") {
+    let message_storage = match JsonMessageStorage::with_ids("data", ids) {
         Ok(storage) => {
             println!("+ Message storage initialized");
             Arc::new(Mutex::new(storage))
@@ -85,13 +171,60 @@ fn main() -> BbsResult<()> {
         }
     };
 
+    // Initialize shared club storage
+    let club_storage = match JsonClubStorage::new("data") {
+        Ok(storage) => {
+            println!("+ Club storage initialized");
+            Arc::new(Mutex::new(storage))
+        }
+        Err(e) => {
+            eprintln!("x Failed to initialize club storage: {}", e);
+            return Err(e);
+        }
+    };
+
     // Create services
-    let services = Arc::new(CoreServices::new(
+    let services = CoreServices::new(
+        &config.features,
         user_storage.clone() as Arc<Mutex<dyn crate::user_repository::UserStorage + Send>>,
         bulletin_storage.clone()
             as Arc<Mutex<dyn crate::bulletin_repository::BulletinStorage + Send>>,
         message_storage.clone() as Arc<Mutex<dyn crate::message_repository::MessageStorage + Send>>,
-    ));
+        club_storage as Arc<Mutex<dyn crate::club_repository::ClubStorage + Send>>,
+    );
+    let services = Arc::new(load_scripts(&config, services)?);
+
+    // Publish public bulletins for feed readers, and timings for monitoring
+    if config.feed.enabled || config.metrics.enabled {
+        let http_addr = format!("{}:{}", config.server.bind_address, config.feed.http_port);
+        match TcpListener::bind(&http_addr) {
+            Ok(listener) => {
+                if config.feed.enabled {
+                    let areas = feed::public_areas(&config, &services).unwrap_or_default();
+                    for area in &areas {
+                        println!(
+                            "+ Bulletin feed for {} at http://{}{}",
+                            area.as_deref().unwrap_or("the main board"),
+                            http_addr,
+                            feed::feed_path(area.as_deref())
+                        );
+                    }
+                    if areas.len() < feed::published_areas(&config).len() {
+                        eprintln!(
+                            "! Some [feed] areas have no feed: guests can't read them, or no such club"
+                        );
+                    }
+                }
+                if config.metrics.enabled {
+                    println!("+ Metrics at http://{}{}", http_addr, feed::METRICS_PATH);
+                }
+                let config = Arc::clone(&config);
+                let services = Arc::clone(&services);
+                thread::spawn(move || feed::serve(listener, config, services));
+            }
+            Err(e) => eprintln!("! HTTP listener disabled, cannot bind {}: {}", http_addr, e),
+        }
+    }
 
     // Start the server
     let bind_addr = format!(
@@ -115,14 +248,86 @@ fn main() -> BbsResult<()> {
 
     println!("\nPress Ctrl+C to stop the server\n");
 
-    // Accept connections with proper connection tracking
+    // Accept connections with proper connection tracking; browser callers
+    // on the WebSocket port count against the same limit
     let connection_count = Arc::new(AtomicU32::new(0));
-    let mut connection_id = 0u32;
+    let connection_ids = Arc::new(AtomicU32::new(0));
+
+    // Browser terminals and implicit-TLS clients get listeners of their own
+    let extra_ports = [
+        (
+            config.server.websocket_port,
+            Port::WebSocket,
+            "Web terminals connect to ws://",
+        ),
+        (
+            config
+                .server
+                .tls_port
+                .filter(|_| tls::server_config().is_some()),
+            Port::Tls,
+            "TLS telnet clients connect to ",
+        ),
+    ];
+    for (port_number, port, announcement) in extra_ports {
+        let Some(port_number) = port_number else {
+            continue;
+        };
+        let addr = format!("{}:{}", config.server.bind_address, port_number);
+        match TcpListener::bind(&addr) {
+            Ok(listener) => {
+                println!("> {}{}", announcement, addr);
+                let config = Arc::clone(&config);
+                let services = Arc::clone(&services);
+                let connection_count = Arc::clone(&connection_count);
+                let connection_ids = Arc::clone(&connection_ids);
+                thread::spawn(move || {
+                    accept_callers(
+                        listener,
+                        port,
+                        config,
+                        services,
+                        connection_count,
+                        connection_ids,
+                    )
+                });
+            }
+            Err(e) => eprintln!(
+                "! {:?} listener disabled, cannot bind {}: {}",
+                port, addr, e
+            ),
+        }
+    }
+
+    accept_callers(
+        listener,
+        Port::Telnet,
+        config,
+        services,
+        connection_count,
+        connection_ids,
+    );
+
+    Ok(())
+}
 
+/// Take callers from `listener` until it fails, a thread each
+///
+/// `port` says how their connections open: browsers upgrade to a
+/// WebSocket and TLS port callers finish a handshake before the session
+/// starts.
+fn accept_callers(
+    listener: TcpListener,
+    port: Port,
+    config: Arc<BbsConfig>,
+    services: Arc<CoreServices>,
+    connection_count: Arc<AtomicU32>,
+    connection_ids: Arc<AtomicU32>,
+) {
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                connection_id += 1;
+                let connection_id = connection_ids.fetch_add(1, Ordering::Relaxed) + 1;
                 let current_connections = connection_count.fetch_add(1, Ordering::Relaxed) + 1;
 
                 // Clone config for this thread
@@ -134,7 +339,9 @@ fn main() -> BbsResult<()> {
                         "!  Connection limit reached ({}/{}), rejecting connection",
                         current_connections, config.server.max_connections
                     );
-                    let _ = show_rejection(stream, config);
+                    let _ = open_connection(stream, port, &config)
+                        .map_err(BbsError::from)
+                        .and_then(|connection| show_rejection(connection, config));
                     connection_count.fetch_sub(1, Ordering::Relaxed);
                     continue;
                 }
@@ -154,29 +361,41 @@ fn main() -> BbsResult<()> {
 
                 // Spawn thread to handle connection
                 thread::spawn(move || {
-                    // Set connection timeout
-                    if let Err(e) =
-                        stream.set_read_timeout(Some(config.timeouts.connection_timeout))
-                    {
-                        eprintln!("Failed to set timeout for {}: {}", peer_addr, e);
-                    }
-
-                    // Handle the client session
-                    match handle_client(stream, config, services) {
-                        Ok(()) => {
-                            let remaining = conn_counter.fetch_sub(1, Ordering::Relaxed) - 1;
-                            println!(
-                                "> Client {} disconnected normally ({} connections remaining)",
-                                peer_addr, remaining
-                            );
-                        }
+                    let stream = match open_connection(stream, port, &config) {
+                        Ok(stream) => stream,
                         Err(e) => {
                             let remaining = conn_counter.fetch_sub(1, Ordering::Relaxed) - 1;
                             eprintln!(
-                                "! Error handling client {}: {} ({} connections remaining)",
-                                peer_addr, e, remaining
+                                "! Could not open {:?} connection from {}: {} ({} connections remaining)",
+                                port, peer_addr, e, remaining
                             );
+                            return;
                         }
+                    };
+
+                    // Pick the SysOp's notices for this caller's address
+                    let notices = motd::notices_for(peer_addr.ip(), &config.motd.notices);
+
+                    // Handle the client session
+                    let result = handle_client(stream, config, services, notices);
+                    let remaining = conn_counter.fetch_sub(1, Ordering::Relaxed) - 1;
+                    match result {
+                        Ok(()) => println!(
+                            "> Client {} disconnected normally ({} connections remaining)",
+                            peer_addr, remaining
+                        ),
+                        Err(BbsError::ClientDisconnected) => println!(
+                            "> Client {} dropped carrier ({} connections remaining)",
+                            peer_addr, remaining
+                        ),
+                        Err(BbsError::Timeout) => println!(
+                            "> Client {} timed out ({} connections remaining)",
+                            peer_addr, remaining
+                        ),
+                        Err(e) => eprintln!(
+                            "! Error handling client {}: {} ({} connections remaining)",
+                            peer_addr, e, remaining
+                        ),
                     }
                 });
             }
@@ -186,22 +405,189 @@ fn main() -> BbsResult<()> {
             }
         }
     }
+}
 
-    Ok(())
+/// Set the caller's timeout, then answer the WebSocket upgrade or TLS
+/// handshake their port expects
+fn open_connection(
+    stream: TcpStream,
+    port: Port,
+    config: &BbsConfig,
+) -> std::io::Result<Connection> {
+    // Set connection timeout
+    if let Err(e) = stream.set_read_timeout(Some(config.timeouts.connection_timeout)) {
+        eprintln!("Failed to set timeout: {}", e);
+    }
+    Connection::open(stream, port)
+}
+
+/// Load SysOp scripts into the services if scripting is enabled
+#[cfg(feature = "scripting")]
+fn load_scripts(config: &BbsConfig, services: CoreServices) -> BbsResult<CoreServices> {
+    if !config.scripting.enabled {
+        return Ok(services);
+    }
+
+    // Script commands may not take keys from [commands] either
+    let mut reserved_keys = menu::menu_main::RESERVED_KEYS.to_vec();
+    reserved_keys.extend(config.commands.commands.iter().map(|c| c.key.as_str()));
+
+    match scripting::ScriptHost::load(&config.scripting.directory, &reserved_keys) {
+        Ok(scripts) if scripts.is_empty() => {
+            println!("+ No scripts found in {}", config.scripting.directory);
+            Ok(services)
+        }
+        Ok(scripts) => {
+            println!(
+                "+ {} script(s) loaded from {}",
+                scripts.len(),
+                config.scripting.directory
+            );
+            Ok(services.with_scripts(scripts))
+        }
+        Err(e) => {
+            eprintln!("x Failed to load scripts: {}", e);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+fn load_scripts(_config: &BbsConfig, services: CoreServices) -> BbsResult<CoreServices> {
+    Ok(services)
 }
 
 /// Handle client BBS Session
 fn handle_client(
-    stream: TcpStream,
+    stream: Connection,
     config: Arc<BbsConfig>,
     services: Arc<CoreServices>,
+    notices: Vec<String>,
 ) -> BbsResult<()> {
-    // Phase 5: Wrap TcpStream with TelnetStream for transparent telnet handling
+    // Phase 5: Wrap the connection with TelnetStream for transparent telnet handling
     let telnet_stream = telnet_negotiation::TelnetStream::new(stream);
-    let mut session = BbsSession::new(config, services);
+    let mut session = BbsSession::new(config, services).with_connection_notices(notices);
     session.run(telnet_stream)
 }
 
+/// Check the data directory for dangling references, optionally repairing them
+///
+/// Returns true when the data is clean (or was repaired).
+fn run_fsck(data_dir: &str, repair: bool) -> BbsResult<bool> {
+    let mut checker = fsck::DataChecker::load(data_dir)?;
+    let report = checker.check()?;
+
+    if report.is_clean() {
+        println!("+ {}: no issues found", data_dir);
+        return Ok(true);
+    }
+
+    println!("! {}: {} issue(s) found", data_dir, report.issues.len());
+    for issue in &report.issues {
+        println!("  - {}", issue);
+    }
+
+    if !repair {
+        println!("Run with --fsck --repair to fix these (a backup is taken first)");
+        return Ok(false);
+    }
+
+    let backup_dir = checker.repair()?;
+    println!("+ Backup written to {}", backup_dir.display());
+
+    let remaining = checker.check()?;
+    if remaining.is_clean() {
+        println!("+ All issues repaired");
+        Ok(true)
+    } else {
+        println!(
+            "! {} issue(s) could not be repaired:",
+            remaining.issues.len()
+        );
+        for issue in &remaining.issues {
+            println!("  - {}", issue);
+        }
+        Ok(false)
+    }
+}
+
+/// Archive bulletins to `dest`, picking up after the last export there
+fn run_export(data_dir: &str, format: &str, dest: &str) -> BbsResult<()> {
+    let format: services::ExportFormat = format.parse()?;
+    // Feature settings decide whether bulletins exist at all
+    let config = BbsConfig::load_from_file("bbs.conf").unwrap_or_default();
+
+    let ids = IdService::new(data_dir)?;
+    let services = CoreServices::new(
+        &config.features,
+        Arc::new(Mutex::new(JsonUserStorage::new(data_dir)?)),
+        Arc::new(Mutex::new(JsonBulletinStorage::with_ids(
+            data_dir,
+            ids.clone(),
+        )?)),
+        Arc::new(Mutex::new(JsonMessageStorage::with_ids(data_dir, ids)?)),
+        Arc::new(Mutex::new(JsonClubStorage::new(data_dir)?)),
+    );
+
+    let report = services.export.export(
+        format,
+        std::path::Path::new(dest),
+        config::SecurityLevel::Sysop,
+    )?;
+    if report.exported == 0 {
+        println!("+ {}: nothing new to export", dest);
+    } else {
+        println!(
+            "+ {}: exported {} bulletin(s), up to #{}",
+            dest, report.exported, report.last_id
+        );
+    }
+    Ok(())
+}
+
+/// Fill an empty board with demo data, moving existing data aside first
+/// if `wipe` is set
+///
+/// Returns false, having changed nothing, when the board already has data
+/// and `wipe` isn't set.
+fn run_seed_demo(data_dir: &str, wipe: bool) -> BbsResult<bool> {
+    let config = BbsConfig::load_from_file("bbs.conf").unwrap_or_default();
+
+    if wipe {
+        let backup_dir = demo::wipe(data_dir)?;
+        println!("+ Existing data moved to {}", backup_dir.display());
+    }
+
+    let ids = IdService::new(data_dir)?;
+    let services = CoreServices::new(
+        &config.features,
+        Arc::new(Mutex::new(JsonUserStorage::new(data_dir)?)),
+        Arc::new(Mutex::new(JsonBulletinStorage::with_ids(
+            data_dir,
+            ids.clone(),
+        )?)),
+        Arc::new(Mutex::new(JsonMessageStorage::with_ids(data_dir, ids)?)),
+        Arc::new(Mutex::new(JsonClubStorage::new(data_dir)?)),
+    );
+
+    if !demo::is_empty(&services)? {
+        println!("! {} already has users, bulletins or clubs", data_dir);
+        println!("Run with --seed-demo --wipe to replace them (they are moved to a backup first)");
+        return Ok(false);
+    }
+
+    let report = demo::seed_demo(&config, &services)?;
+    println!(
+        "+ Added {} users, {} bulletins, {} club(s) and {} messages",
+        report.users, report.bulletins, report.clubs, report.messages
+    );
+    println!(
+        "+ Every demo account's password is \"{}\"",
+        demo::DEMO_PASSWORD
+    );
+    Ok(true)
+}
+
 /// Show Server startup messages in console log
 fn print_startup_banner(config: &BbsConfig) -> BbsResult<()> {
     let box_renderer = BoxRenderer::new(config.ui.box_style, config.ui.use_colors);
@@ -225,6 +611,18 @@ fn print_startup_banner(config: &BbsConfig) -> BbsResult<()> {
             .map_or("  SSH Port:    Disabled".to_string(), |port| {
                 format!("  SSH Port:    {}", port)
             }),
+        config
+            .server
+            .websocket_port
+            .map_or("  WebSocket Port: Disabled".to_string(), |port| {
+                format!("  WebSocket Port: {}", port)
+            }),
+        config
+            .server
+            .tls_port
+            .map_or("  TLS Port:    Disabled".to_string(), |port| {
+                format!("  TLS Port:    {}", port)
+            }),
         format!("  Max Connections: {}", config.server.max_connections),
         format!(
             "  Connection Timeout: {}s",
@@ -254,7 +652,10 @@ fn print_startup_banner(config: &BbsConfig) -> BbsResult<()> {
 }
 
 /// Notify user BBS connection limit has been reached
-fn show_rejection(mut stream: TcpStream, config: Arc<BbsConfig>) -> BbsResult<()> {
+fn show_rejection(stream: Connection, config: Arc<BbsConfig>) -> BbsResult<()> {
+    // Through the telnet layer so bare \n goes out as CR LF
+    let mut stream = telnet_negotiation::TelnetStream::new(stream);
+
     // Create a simple box renderer for the rejection message
     let box_renderer =
         crate::box_renderer::BoxRenderer::new(config.ui.box_style, config.ui.use_colors);
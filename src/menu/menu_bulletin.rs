@@ -1,7 +1,12 @@
-use super::{Menu, MenuAction, MenuRender, MenuScreen};
+use super::{Menu, MenuAction, MenuRender, MenuScreen, post_preview_items};
 use crate::{
-    box_renderer::MenuItem, bulletin_repository::BulletinStats, bulletins::Bulletin,
+    box_renderer::MenuItem,
+    bulletin_repository::BulletinStats,
+    bulletins::Bulletin,
+    post_check::{PostIssue, mark_filtered},
+    services::{Access, Feature},
     session::BbsSession,
+    text_width::ellipsize,
 };
 
 /// Bulletin menu actions
@@ -10,11 +15,15 @@ pub enum BulletinMenuAction {
     Post,
     ToggleReadFilter,
     ToggleUnreadOnly,
+    MarkAllRead,
     Read(u32),
     List,
     BackToMenu,
     Submit { title: String, content: String },
     PostContent(String),
+    Preview { title: String, content: String },
+    Clubs,
+    LeaveClub,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,19 +40,34 @@ pub enum BulletinMenuState {
     Reading(Bulletin),      // Reading specific bulletin ID
     Posting,                // Posting new bulletin
     PostingContent(String), // Posting - have title, getting content
+    /// Posting - written, waiting for the author to submit, edit or abort
+    Reviewing {
+        title: String,
+        content: String,
+        issues: Vec<PostIssue>,
+    },
 }
 
 /// Bulletin menu - can have state like current view, filters, etc.
 pub struct BulletinMenu {
     pub state: BulletinMenuState,
+    /// Club being browsed; `None` for the main board
+    pub area: Option<String>,
     show_read_bulletins: bool,
     show_only_unread: bool,
 }
 
+impl Default for BulletinMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BulletinMenu {
     pub fn new() -> Self {
         Self {
             state: BulletinMenuState::MainMenu,
+            area: None,
             show_read_bulletins: true,
             show_only_unread: false,
         }
@@ -70,7 +94,7 @@ impl MenuScreen for BulletinMenu {
     // }
 
     fn render(&self, data: &BbsSession) -> MenuRender {
-        if !data.config.features.bulletins_enabled {
+        if data.feature_access(Feature::Bulletins) == Access::None {
             return self.render_disabled_menu(data);
         }
 
@@ -82,11 +106,16 @@ impl MenuScreen for BulletinMenu {
             BulletinMenuState::PostingContent(title) => {
                 self.render_posting_content_menu(data, title)
             }
+            BulletinMenuState::Reviewing {
+                title,
+                content,
+                issues,
+            } => self.render_reviewing_menu(data, title, content, issues),
         }
     }
 
     fn handle_input(&self, data: &BbsSession, input: &str) -> MenuAction {
-        if !data.config.features.bulletins_enabled {
+        if data.feature_access(Feature::Bulletins) == Access::None {
             return self.handle_disabled_input(input);
         }
 
@@ -100,6 +129,11 @@ impl MenuScreen for BulletinMenu {
             BulletinMenuState::PostingContent(title) => {
                 self.handle_posting_content_input(data, input, title)
             }
+            BulletinMenuState::Reviewing {
+                title,
+                content,
+                issues,
+            } => self.handle_reviewing_input(input, title, content, issues),
         };
 
         // TODO: Not sure this is best way to do this...
@@ -115,10 +149,16 @@ impl MenuScreen for BulletinMenu {
                     MenuAction::BulletinSubmit { title, content }
                 }
                 BulletinMenuAction::PostContent(title) => MenuAction::BulletinPostContent(title),
+                BulletinMenuAction::Preview { title, content } => {
+                    MenuAction::BulletinPreview { title, content }
+                }
                 BulletinMenuAction::List => MenuAction::BulletinList,
                 BulletinMenuAction::BackToMenu => MenuAction::BulletinBackToMenu,
                 BulletinMenuAction::ToggleReadFilter => MenuAction::BulletinToggleReadFilter,
                 BulletinMenuAction::ToggleUnreadOnly => MenuAction::BulletinToggleUnreadOnly,
+                BulletinMenuAction::MarkAllRead => MenuAction::BulletinMarkAllRead,
+                BulletinMenuAction::Clubs => MenuAction::BulletinClubs,
+                BulletinMenuAction::LeaveClub => MenuAction::BulletinLeaveClub,
             },
         }
     }
@@ -126,8 +166,13 @@ impl MenuScreen for BulletinMenu {
 
 impl BulletinMenu {
     fn render_disabled_menu(&self, data: &BbsSession) -> MenuRender {
+        let reason = if data.services.permissions.is_enabled(Feature::Bulletins) {
+            "!  Your access level cannot use the Bulletin Board."
+        } else {
+            "!  Bulletin Board has been disabled by the SysOp."
+        };
         let items = vec![
-            MenuItem::info("!  Bulletin Board has been disabled by the SysOp."),
+            MenuItem::info(reason),
             MenuItem::info(&format!(
                 "Contact {} for more information.",
                 data.config.bbs.sysop_name
@@ -171,11 +216,7 @@ impl BulletinMenu {
                     "   "
                 };
 
-                let title = if summary.title.len() > 35 {
-                    format!("{}...", &summary.title[..32])
-                } else {
-                    summary.title.clone()
-                };
+                let title = ellipsize(&summary.title, 35);
 
                 items.push(MenuItem::info(&format!(
                     "{} [{}] {} - by {} ({})",
@@ -205,16 +246,19 @@ impl BulletinMenu {
 
             if stats.unread_count > 0 {
                 items.push(MenuItem::option("N", "Read next unread"));
+                if data.is_logged_in() {
+                    items.push(MenuItem::option("M", "Mark all read"));
+                }
             }
         }
 
         // Posting options
-        if data.is_logged_in() || data.allow_anonymous() {
+        if data.feature_access(Feature::Bulletins) == Access::Full {
             items.push(MenuItem::option("P", "Post new bulletin"));
         } else {
             items.push(MenuItem::disabled_option(
                 "P",
-                "Post new bulletin (login required)",
+                "Post new bulletin (read-only)",
             ));
         }
 
@@ -233,9 +277,21 @@ impl BulletinMenu {
             }
         }
 
+        items.push(MenuItem::option("C", "Clubs"));
+        if self.area.is_some() && data.is_logged_in() {
+            items.push(MenuItem::option("X", "Leave this club"));
+        }
         items.push(MenuItem::option("B", "Back to main"));
 
-        MenuRender::with_items("BULLETIN BOARD", items, "\nChoice: ")
+        MenuRender::with_items(&self.title(), items, "\nChoice: ")
+    }
+
+    /// Screen title: the board, or the club being browsed
+    fn title(&self) -> String {
+        match &self.area {
+            Some(club) => format!("CLUB: {}", club.to_uppercase()),
+            None => "BULLETIN BOARD".to_string(),
+        }
     }
 
     fn render_listing_menu(&self, data: &BbsSession, list: &[Bulletin]) -> MenuRender {
@@ -265,17 +321,13 @@ impl BulletinMenu {
             for (i, summary) in list.iter().enumerate() {
                 let status = if summary.is_sticky {
                     "[*]"
-                } else if !summary.is_read_by(&data.display_username()) {
+                } else if !data.has_read_bulletin(summary.id) {
                     "[N]"
                 } else {
                     "   "
                 };
 
-                let title = if summary.title.len() > 35 {
-                    format!("{}...", &summary.title[..32])
-                } else {
-                    summary.title.clone()
-                };
+                let title = ellipsize(&summary.title, 35);
 
                 menu.push(MenuItem::info(&format!(
                     "{} [{}] {} - by {} ({})",
@@ -304,16 +356,19 @@ impl BulletinMenu {
 
             if stats.unread_count > 0 {
                 menu.push(MenuItem::option("N", "Read next unread"));
+                if data.is_logged_in() {
+                    menu.push(MenuItem::option("M", "Mark all read"));
+                }
             }
         }
 
         // Posting options
-        if data.is_logged_in() || data.allow_anonymous() {
+        if data.feature_access(Feature::Bulletins) == Access::Full {
             menu.push(MenuItem::option("P", "Post new bulletin"));
         } else {
             menu.push(MenuItem::disabled_option(
                 "P",
-                "Post new bulletin (login required)",
+                "Post new bulletin (read-only)",
             ));
         }
 
@@ -338,9 +393,13 @@ impl BulletinMenu {
     }
 
     fn render_reading_menu(&self, data: &BbsSession, bulletin: &Bulletin) -> MenuRender {
+        let author = match &bulletin.author_display {
+            Some(display) => format!("{} ({})", display, bulletin.author),
+            None => bulletin.author.clone(),
+        };
         let mut items = vec![
             MenuItem::info(&format!("Bulletin #{}: {}", bulletin.id, bulletin.title)),
-            MenuItem::info(&format!("Author: {}", bulletin.author)),
+            MenuItem::info(&format!("Author: {}", author)),
             MenuItem::info(&format!("Posted: {}", bulletin.posted_display())),
             MenuItem::separator(),
         ];
@@ -384,7 +443,7 @@ impl BulletinMenu {
     }
 
     fn render_posting_menu(&self, data: &BbsSession) -> MenuRender {
-        let author = data.display_username();
+        let author = data.shown_name();
 
         let items = vec![
             MenuItem::info(&format!("Posting as: {}", author)),
@@ -401,7 +460,7 @@ impl BulletinMenu {
     }
 
     fn render_posting_content_menu(&self, data: &BbsSession, title: &str) -> MenuRender {
-        let author = data.display_username();
+        let author = data.shown_name();
 
         let items = vec![
             MenuItem::info(&format!("Posting as: {}", author)),
@@ -422,6 +481,25 @@ impl BulletinMenu {
         MenuRender::with_items("POST BULLETIN - CONTENT", items, "\nContent: ")
     }
 
+    fn render_reviewing_menu(
+        &self,
+        data: &BbsSession,
+        title: &str,
+        content: &str,
+        issues: &[PostIssue],
+    ) -> MenuRender {
+        let filtered = &data.config.features.filtered_words;
+
+        let mut items = vec![
+            MenuItem::info(&format!("Posting as: {}", data.shown_name())),
+            MenuItem::info(&format!("Title: {}", mark_filtered(title, filtered))),
+            MenuItem::blank(),
+        ];
+        items.extend(post_preview_items(data, content, issues));
+
+        MenuRender::with_items("POST BULLETIN - PREVIEW", items, "\nChoice: ")
+    }
+
     fn handle_disabled_input(&self, input: &str) -> MenuAction {
         match input.to_lowercase().as_str() {
             "b" => MenuAction::GoTo(Menu::Main),
@@ -436,15 +514,8 @@ impl BulletinMenu {
             // "l" => Action::Menu(MenuAction::ShowMessage(
             //     "Listing all bulletins... (Feature integration needed!)".to_string(),
             // )),
-            "l" => {
-                if data.is_logged_in() || data.allow_anonymous() {
-                    Action::Bulletin(BulletinMenuAction::List)
-                } else {
-                    Action::Menu(MenuAction::ShowMessage(
-                        "You must be logged in to list bulletins.".to_string(),
-                    ))
-                }
-            }
+            // Read access was already checked before dispatching here
+            "l" => Action::Bulletin(BulletinMenuAction::List),
             "r" => Action::Menu(MenuAction::ShowMessage(
                 "Enter bulletin number to read... (Feature integration needed!)".to_string(),
             )),
@@ -452,11 +523,11 @@ impl BulletinMenu {
                 "Reading next unread bulletin... (Feature integration needed!)".to_string(),
             )),
             "p" => {
-                if data.is_logged_in() || data.allow_anonymous() {
+                if data.feature_access(Feature::Bulletins) == Access::Full {
                     Action::Bulletin(BulletinMenuAction::Post)
                 } else {
                     Action::Menu(MenuAction::ShowMessage(
-                        "You must be logged in to post bulletins.".to_string(),
+                        "Your access level cannot post bulletins.".to_string(),
                     ))
                 }
             }
@@ -464,6 +535,11 @@ impl BulletinMenu {
             "s" => Action::Bulletin(BulletinMenuAction::ToggleReadFilter),
             "u" => Action::Bulletin(BulletinMenuAction::ToggleUnreadOnly),
             "a" => Action::Bulletin(BulletinMenuAction::ToggleUnreadOnly),
+            "m" if data.is_logged_in() => Action::Bulletin(BulletinMenuAction::MarkAllRead),
+            "c" => Action::Bulletin(BulletinMenuAction::Clubs),
+            "x" if self.area.is_some() && data.is_logged_in() => {
+                Action::Bulletin(BulletinMenuAction::LeaveClub)
+            }
             "b" => Action::Menu(MenuAction::GoTo(Menu::Main)),
             // Handle reading specific bulletin numbers
             num if num.chars().all(|c| c.is_ascii_digit()) => {
@@ -476,7 +552,7 @@ impl BulletinMenu {
                 }
             }
             _ => Action::Menu(MenuAction::ShowMessage(
-                "Invalid choice. Use L, R, N, P, H/S, U/A, or B.".to_string(),
+                "Invalid choice. Use L, R, N, M, P, H/S, U/A, C, or B.".to_string(),
             )),
         }
     }
@@ -487,11 +563,11 @@ impl BulletinMenu {
                 "Reading next unread bulletin... (Feature integration needed!)".to_string(),
             )),
             "p" => {
-                if data.is_logged_in() || data.allow_anonymous() {
+                if data.feature_access(Feature::Bulletins) == Access::Full {
                     Action::Bulletin(BulletinMenuAction::Post)
                 } else {
                     Action::Menu(MenuAction::ShowMessage(
-                        "You must be logged in to post bulletins.".to_string(),
+                        "Your access level cannot post bulletins.".to_string(),
                     ))
                 }
             }
@@ -499,6 +575,7 @@ impl BulletinMenu {
             "s" => Action::Bulletin(BulletinMenuAction::ToggleReadFilter),
             "u" => Action::Bulletin(BulletinMenuAction::ToggleUnreadOnly),
             "a" => Action::Bulletin(BulletinMenuAction::ToggleUnreadOnly),
+            "m" if data.is_logged_in() => Action::Bulletin(BulletinMenuAction::MarkAllRead),
             "b" => Action::Bulletin(BulletinMenuAction::BackToMenu),
             // Handle reading specific bulletin numbers
             num if num.chars().all(|c| c.is_ascii_digit()) => {
@@ -511,7 +588,7 @@ impl BulletinMenu {
                 }
             }
             _ => Action::Menu(MenuAction::ShowMessage(
-                "Invalid choice. Use L, R, N, P, H/S, U/A, or B.".to_string(),
+                "Invalid choice. Use L, R, N, M, P, H/S, U/A, or B.".to_string(),
             )),
         }
     }
@@ -549,19 +626,37 @@ impl BulletinMenu {
         }
     }
 
-    fn handle_posting_content_input(&self, data: &BbsSession, input: &str, title: &str) -> Action {
+    fn handle_posting_content_input(&self, _data: &BbsSession, input: &str, title: &str) -> Action {
         if input.trim().is_empty() {
             Action::Bulletin(BulletinMenuAction::BackToMenu)
-        } else if input.len() > data.config.features.max_message_length {
-            Action::Menu(MenuAction::ShowMessage(format!(
-                "Content too long (max {} characters). Try again.",
-                data.config.features.max_message_length
-            )))
         } else {
-            Action::Bulletin(BulletinMenuAction::Submit {
+            Action::Bulletin(BulletinMenuAction::Preview {
                 title: title.to_string(),
                 content: input.trim().to_string(),
             })
         }
     }
+
+    fn handle_reviewing_input(
+        &self,
+        input: &str,
+        title: &str,
+        content: &str,
+        issues: &[PostIssue],
+    ) -> Action {
+        match input.to_lowercase().as_str() {
+            "s" if issues.is_empty() => Action::Bulletin(BulletinMenuAction::Submit {
+                title: title.to_string(),
+                content: content.to_string(),
+            }),
+            "s" => Action::Menu(MenuAction::ShowMessage(
+                "Fix the problems listed first: E to edit, A to abort.".to_string(),
+            )),
+            "e" => Action::Bulletin(BulletinMenuAction::PostContent(title.to_string())),
+            "a" => Action::Bulletin(BulletinMenuAction::BackToMenu),
+            _ => Action::Menu(MenuAction::ShowMessage(
+                "Invalid choice. Use S, E, or A.".to_string(),
+            )),
+        }
+    }
 }
@@ -0,0 +1,63 @@
+use super::{Menu, MenuAction, MenuRender, MenuScreen};
+use crate::{box_renderer::MenuItem, session::BbsSession};
+
+/// Other boards callers can telnet out to
+pub struct GatewayMenu;
+
+impl Default for GatewayMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GatewayMenu {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MenuScreen for GatewayMenu {
+    fn render(&self, data: &BbsSession) -> MenuRender {
+        let gateway = &data.config.gateway;
+        let mut items = vec![
+            MenuItem::info(&format!(
+                "Calls are limited to {} minutes.",
+                gateway.max_minutes
+            )),
+            MenuItem::info(&format!(
+                "Press Ctrl-] to return to {} early.",
+                data.config.bbs.name
+            )),
+            MenuItem::separator(),
+        ];
+
+        if gateway.boards.is_empty() {
+            items.push(MenuItem::info("No other boards are listed."));
+        }
+        for (index, board) in gateway.boards.iter().enumerate() {
+            items.push(MenuItem::option(
+                &(index + 1).to_string(),
+                &format!("{} ({})", board.name, board.address),
+            ));
+        }
+
+        items.push(MenuItem::separator());
+        items.push(MenuItem::option("B", "Back to main"));
+
+        MenuRender::with_items("OTHER BOARDS", items, "\nChoice: ")
+    }
+
+    fn handle_input(&self, data: &BbsSession, input: &str) -> MenuAction {
+        let input = input.to_lowercase();
+        if input == "b" {
+            return MenuAction::GoTo(Menu::Main);
+        }
+
+        match input.parse::<usize>() {
+            Ok(number) if (1..=data.config.gateway.boards.len()).contains(&number) => {
+                MenuAction::GatewayConnect(number - 1)
+            }
+            _ => MenuAction::ShowMessage("Invalid choice. Please try again.".to_string()),
+        }
+    }
+}
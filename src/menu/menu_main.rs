@@ -1,9 +1,26 @@
 use super::{Menu, MenuAction, MenuRender, MenuScreen};
-use crate::{box_renderer::MenuItem, session::BbsSession};
+use crate::{
+    box_renderer::MenuItem,
+    services::{Access, Feature},
+    session::BbsSession,
+};
+
+/// Keys the main menu handles itself; script and external commands may
+/// not use them
+pub const RESERVED_KEYS: &[&str] = &[
+    "1", "2", "3", "4", "f", "g", "h", "s", "help", "?", "l", "login", "o", "logout", "q", "quit",
+    "exit",
+];
 
 /// Main menu - clean, stateless menu
 pub struct MainMenu;
 
+impl Default for MainMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MainMenu {
     pub fn new() -> Self {
         Self
@@ -32,7 +49,7 @@ impl MenuScreen for MainMenu {
         items.push(MenuItem::separator());
 
         // Menu options based on config
-        if data.config.features.bulletins_enabled {
+        if data.feature_access(Feature::Bulletins) != Access::None {
             items.push(MenuItem::option("1", "Bulletin Board"));
         } else {
             items.push(MenuItem::disabled_option("1", "Bulletin Board"));
@@ -62,14 +79,54 @@ impl MenuScreen for MainMenu {
             items.push(MenuItem::option("3", "Private Messages"));
         }
 
-        if data.config.features.file_uploads_enabled {
+        if data.feature_access(Feature::Files) != Access::None {
             items.push(MenuItem::option("4", "File Library"));
         } else {
             items.push(MenuItem::disabled_option("4", "File Library"));
         }
 
+        if data.config.gateway.enabled {
+            if data.security_level() >= data.config.gateway.level {
+                items.push(MenuItem::option("G", "Other Boards"));
+            } else {
+                items.push(MenuItem::disabled_option("G", "Other Boards"));
+            }
+        }
+
+        // Programs the SysOp lets callers run
+        for command in &data.config.commands.commands {
+            let key = command.key.to_uppercase();
+            if data.security_level() >= command.level {
+                items.push(MenuItem::option(&key, &command.description));
+            } else {
+                items.push(MenuItem::disabled_option(&key, &command.description));
+            }
+        }
+
+        // Custom commands from SysOp scripts
+        #[cfg(feature = "scripting")]
+        if let Some(scripts) = &data.services.scripts {
+            for command in scripts.commands() {
+                items.push(MenuItem::option(
+                    &command.key.to_uppercase(),
+                    &command.description,
+                ));
+            }
+        }
+
         items.push(MenuItem::separator());
 
+        if data.feature_access(Feature::Bulletins) != Access::None {
+            items.push(MenuItem::option("S", "Board Statistics"));
+        } else {
+            items.push(MenuItem::disabled_option("S", "Board Statistics"));
+        }
+
+        // SysOp tools
+        if data.is_sysop() {
+            items.push(MenuItem::option("F", "Client Fingerprint"));
+        }
+
         // Login/logout options
         if !data.is_logged_in() && data.allow_anonymous() {
             items.push(MenuItem::option("L", "Login / Register"));
@@ -77,6 +134,7 @@ impl MenuScreen for MainMenu {
             items.push(MenuItem::option("O", "Logout"));
         }
 
+        items.push(MenuItem::option("H", "Board Guide"));
         items.push(MenuItem::option("Q", "Quit"));
 
         MenuRender::with_items(&title, items, "\nEnter your choice: ")
@@ -84,17 +142,16 @@ impl MenuScreen for MainMenu {
 
     fn handle_input(&self, data: &BbsSession, input: &str) -> MenuAction {
         match input.to_lowercase().as_str() {
-            "1" => {
-                if data.config.features.bulletins_enabled {
-                    MenuAction::GoTo(Menu::Bulletins)
-                } else {
-                    MenuAction::ShowMessage("Bulletin Board is currently disabled.".to_string())
-                }
-            }
+            "1" => match feature_denied(data, Feature::Bulletins) {
+                None => MenuAction::GoTo(Menu::Bulletins),
+                Some(message) => MenuAction::ShowMessage(message),
+            },
             "2" => MenuAction::GoTo(Menu::Users),
 
             "3" => {
-                if data.is_logged_in() {
+                if let Some(message) = feature_denied(data, Feature::Messages) {
+                    MenuAction::ShowMessage(message)
+                } else if data.is_logged_in() {
                     MenuAction::GoTo(Menu::Messages)
                 } else {
                     MenuAction::ShowMessage(
@@ -102,13 +159,10 @@ impl MenuScreen for MainMenu {
                     )
                 }
             }
-            "4" => {
-                if data.config.features.file_uploads_enabled {
-                    MenuAction::ShowMessage("File Library coming soon!".to_string())
-                } else {
-                    MenuAction::ShowMessage("File Library is currently disabled.".to_string())
-                }
-            }
+            "4" => match feature_denied(data, Feature::Files) {
+                None => MenuAction::ShowMessage("File Library coming soon!".to_string()),
+                Some(message) => MenuAction::ShowMessage(message),
+            },
             "l" | "login" => {
                 if !data.is_logged_in() && data.allow_anonymous() {
                     MenuAction::Login
@@ -125,8 +179,62 @@ impl MenuScreen for MainMenu {
                     MenuAction::ShowMessage("You are not logged in.".to_string())
                 }
             }
+            "f" if data.is_sysop() => MenuAction::ShowClientFingerprint,
+            "h" | "help" | "?" => MenuAction::ShowBoardGuide,
+            "s" => match feature_denied(data, Feature::Bulletins) {
+                None => MenuAction::ShowBoardStats,
+                Some(message) => MenuAction::ShowMessage(message),
+            },
+            "g" if data.config.gateway.enabled => {
+                if data.security_level() >= data.config.gateway.level {
+                    MenuAction::GoTo(Menu::Gateway)
+                } else {
+                    MenuAction::ShowMessage(
+                        "Your access level cannot call other boards.".to_string(),
+                    )
+                }
+            }
             "q" | "quit" | "exit" => MenuAction::Quit,
-            _ => MenuAction::ShowMessage("Invalid choice. Please try again.".to_string()),
+            #[cfg(feature = "scripting")]
+            key if data
+                .services
+                .scripts
+                .as_ref()
+                .is_some_and(|s| s.commands().any(|c| c.key == key)) =>
+            {
+                MenuAction::RunScript(key.to_string())
+            }
+            key => match data
+                .config
+                .commands
+                .commands
+                .iter()
+                .position(|c| c.key == key)
+            {
+                Some(index)
+                    if data.security_level() >= data.config.commands.commands[index].level =>
+                {
+                    MenuAction::RunCommand(index)
+                }
+                Some(_) => MenuAction::ShowMessage(
+                    "Your access level cannot run that command.".to_string(),
+                ),
+                None => MenuAction::ShowMessage("Invalid choice. Please try again.".to_string()),
+            },
         }
     }
 }
+
+/// Explain why the caller cannot open a feature, or None if they can
+fn feature_denied(data: &BbsSession, feature: Feature) -> Option<String> {
+    if data.feature_access(feature) != Access::None {
+        None
+    } else if data.services.permissions.is_enabled(feature) {
+        Some(format!(
+            "Your access level cannot use the {}.",
+            feature.name()
+        ))
+    } else {
+        Some(format!("{} is currently disabled.", feature.name()))
+    }
+}
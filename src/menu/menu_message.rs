@@ -1,7 +1,9 @@
 use crate::box_renderer::MenuItem;
-use crate::menu::{MenuAction, MenuRender, MenuScreen};
+use crate::menu::{MenuAction, MenuRender, MenuScreen, post_preview_items};
 use crate::messages::PrivateMessage;
+use crate::post_check::{PostIssue, mark_filtered};
 use crate::session::BbsSession;
+use crate::text_width::fit_to_width;
 
 pub struct MessageMenu {
     pub state: MessageMenuState,
@@ -13,10 +15,26 @@ pub enum MessageMenuState {
     Inbox(Vec<PrivateMessage>),
     Sent(Vec<PrivateMessage>),
     Compose,
-    ComposeContent { recipient: String, subject: String },
+    ComposeContent {
+        recipient: String,
+        subject: String,
+    },
+    /// Written, waiting for the sender to submit, edit or abort
+    Review {
+        recipient: String,
+        subject: String,
+        content: String,
+        issues: Vec<PostIssue>,
+    },
     Reading(PrivateMessage),
 }
 
+impl Default for MessageMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MessageMenu {
     pub fn new() -> Self {
         Self {
@@ -35,6 +53,12 @@ impl MenuScreen for MessageMenu {
             MessageMenuState::ComposeContent { recipient, subject } => {
                 render_compose_content(session, recipient, subject)
             }
+            MessageMenuState::Review {
+                recipient,
+                subject,
+                content,
+                issues,
+            } => render_review(session, recipient, subject, content, issues),
             MessageMenuState::Reading(message) => render_message(session, message),
         }
     }
@@ -48,7 +72,13 @@ impl MenuScreen for MessageMenu {
             MessageMenuState::ComposeContent { recipient, subject } => {
                 handle_compose_content_input(session, input, recipient, subject)
             }
-            MessageMenuState::Reading(_message) => handle_reading_input(session, input),
+            MessageMenuState::Review {
+                recipient,
+                subject,
+                content,
+                issues,
+            } => handle_review_input(input, recipient, subject, content, issues),
+            MessageMenuState::Reading(message) => handle_reading_input(message, input),
         }
     }
 }
@@ -60,15 +90,15 @@ fn render_main_menu(session: &BbsSession) -> MenuRender {
     let mut items = vec![];
 
     // Get message stats if user is logged in
-    if let Some(user) = &session.user {
-        if let Ok(stats) = session.services.messages.get_stats(&user.username) {
-            items.extend([
-                MenuItem::info(&format!("Unread Messages: {}", stats.unread_count)),
-                MenuItem::info(&format!("Total Received: {}", stats.total_received)),
-                MenuItem::info(&format!("Total Sent: {}", stats.total_sent)),
-                MenuItem::blank(),
-            ]);
-        }
+    if let Some(user) = &session.user
+        && let Ok(stats) = session.services.messages.get_stats(&user.username)
+    {
+        items.extend([
+            MenuItem::info(&format!("Unread Messages: {}", stats.unread_count)),
+            MenuItem::info(&format!("Total Received: {}", stats.total_received)),
+            MenuItem::info(&format!("Total Sent: {}", stats.total_sent)),
+            MenuItem::blank(),
+        ]);
     }
 
     items.extend([
@@ -103,16 +133,8 @@ fn render_inbox(session: &BbsSession, messages: &[PrivateMessage]) -> MenuRender
 
         for (index, message) in messages.iter().enumerate().take(20) {
             let status = if message.is_unread() { "[N]" } else { "   " };
-            let from_truncated = if message.sender.len() > 12 {
-                format!("{}...", &message.sender[..9])
-            } else {
-                format!("{:12}", message.sender)
-            };
-            let subject_truncated = if message.subject.len() > 22 {
-                format!("{}...", &message.subject[..19])
-            } else {
-                format!("{:22}", message.subject)
-            };
+            let from_truncated = fit_to_width(message.sender_name(), 12);
+            let subject_truncated = fit_to_width(&message.subject, 22);
 
             items.push(MenuItem::info(&format!(
                 "{} {:2} | {} | {} | {}",
@@ -130,8 +152,14 @@ fn render_inbox(session: &BbsSession, messages: &[PrivateMessage]) -> MenuRender
         ]);
     }
 
+    items.push(MenuItem::blank());
+    if messages.iter().any(|m| m.is_unread()) {
+        items.push(MenuItem::option("A", "Mark All Read"));
+    }
+    if messages.iter().any(|m| !m.is_unread()) {
+        items.push(MenuItem::option("X", "Delete Read Messages"));
+    }
     items.extend([
-        MenuItem::blank(),
         MenuItem::option("C", "Compose New Message"),
         MenuItem::option("R", "Refresh Inbox"),
         MenuItem::option("B", "Back to Message Menu"),
@@ -161,16 +189,8 @@ fn render_sent(session: &BbsSession, messages: &[PrivateMessage]) -> MenuRender
 
         for (index, message) in messages.iter().enumerate().take(20) {
             let read_status = if message.is_unread() { "   " } else { "[R]" };
-            let to_truncated = if message.recipient.len() > 12 {
-                format!("{}...", &message.recipient[..9])
-            } else {
-                format!("{:12}", message.recipient)
-            };
-            let subject_truncated = if message.subject.len() > 22 {
-                format!("{}...", &message.subject[..19])
-            } else {
-                format!("{:22}", message.subject)
-            };
+            let to_truncated = fit_to_width(&message.recipient, 12);
+            let subject_truncated = fit_to_width(&message.subject, 22);
 
             items.push(MenuItem::info(&format!(
                 "{} {:2} | {} | {} | {}",
@@ -226,11 +246,36 @@ fn render_compose_content(_session: &BbsSession, recipient: &str, subject: &str)
     MenuRender::with_items(title, items, "Message: ")
 }
 
+fn render_review(
+    session: &BbsSession,
+    recipient: &str,
+    subject: &str,
+    content: &str,
+    issues: &[PostIssue],
+) -> MenuRender {
+    let title = "COMPOSE MESSAGE - PREVIEW";
+    let filtered = &session.config.features.filtered_words;
+
+    let mut items = vec![
+        MenuItem::info(&format!("To: {}", recipient)),
+        MenuItem::info(&format!("Subject: {}", mark_filtered(subject, filtered))),
+        MenuItem::blank(),
+    ];
+    items.extend(post_preview_items(session, content, issues));
+
+    MenuRender::with_items(title, items, "Choice: ")
+}
+
 fn render_message(session: &BbsSession, message: &PrivateMessage) -> MenuRender {
     let title = "READING MESSAGE";
 
+    // The login name alongside, so a display name can't hide who wrote it
+    let from = match &message.sender_display {
+        Some(display) => format!("{} ({})", display, message.sender),
+        None => message.sender.clone(),
+    };
     let mut items = vec![
-        MenuItem::info(&format!("From: {}", message.sender)),
+        MenuItem::info(&format!("From: {}", from)),
         MenuItem::info(&format!("To: {}", message.recipient)),
         MenuItem::info(&format!("Subject: {}", message.subject)),
         MenuItem::info(&format!("Sent: {}", message.sent_display())),
@@ -276,6 +321,8 @@ fn handle_inbox_input(
     match input.to_lowercase().as_str() {
         "c" | "compose" => MenuAction::MessageCompose,
         "r" | "refresh" => MenuAction::MessageInbox,
+        "a" => MenuAction::MessageMarkAllRead,
+        "x" => MenuAction::MessageDeleteRead,
         "b" | "back" => MenuAction::MessageBackToMenu,
         "m" | "main" => MenuAction::GoTo(crate::menu::Menu::Main),
         _ => {
@@ -341,7 +388,7 @@ fn handle_compose_content_input(
     if input.trim().is_empty() {
         MenuAction::MessageBackToMenu
     } else {
-        MenuAction::MessageSend {
+        MenuAction::MessagePreview {
             recipient: recipient.to_string(),
             subject: subject.to_string(),
             content: input.trim().to_string(),
@@ -349,16 +396,38 @@ fn handle_compose_content_input(
     }
 }
 
-fn handle_reading_input(_session: &BbsSession, input: &str) -> MenuAction {
+fn handle_review_input(
+    input: &str,
+    recipient: &str,
+    subject: &str,
+    content: &str,
+    issues: &[PostIssue],
+) -> MenuAction {
+    match input.to_lowercase().as_str() {
+        "s" if issues.is_empty() => MenuAction::MessageSend {
+            recipient: recipient.to_string(),
+            subject: subject.to_string(),
+            content: content.to_string(),
+        },
+        "s" => MenuAction::ShowMessage(
+            "Fix the problems listed first: E to edit, A to abort.".to_string(),
+        ),
+        "e" => MenuAction::MessageEditContent {
+            recipient: recipient.to_string(),
+            subject: subject.to_string(),
+        },
+        "a" => MenuAction::MessageBackToMenu,
+        _ => MenuAction::ShowMessage("Invalid choice. Use S, E, or A.".to_string()),
+    }
+}
+
+fn handle_reading_input(message: &PrivateMessage, input: &str) -> MenuAction {
     match input.to_lowercase().as_str() {
         "r" | "reply" => {
             // TODO: Implement reply functionality
             MenuAction::ShowMessage("Reply feature coming soon!".to_string())
         }
-        "d" | "delete" => {
-            // TODO: Get message ID from current state
-            MenuAction::ShowMessage("Delete feature coming soon!".to_string())
-        }
+        "d" | "delete" => MenuAction::MessageDelete(message.id),
         "b" | "back" => MenuAction::MessageInbox,
         "m" | "main" => MenuAction::GoTo(crate::menu::Menu::Main),
         _ => MenuAction::ShowMessage("Invalid choice. Please try again.".to_string()),
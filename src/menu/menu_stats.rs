@@ -0,0 +1,200 @@
+use super::{Menu, MenuAction, MenuRender, MenuScreen};
+use crate::{
+    box_renderer::MenuItem,
+    services::stats_service::{Activity, BoardStats},
+    session::BbsSession,
+    text_width::{display_width, ellipsize, pad_to_width},
+};
+
+/// Areas listed on the board view; the rest are counted
+const MAX_LISTED_AREAS: usize = 9;
+
+/// Busiest hours shown in each chart
+const MAX_LISTED_HOURS: usize = 5;
+
+/// Board Statistics: totals and activity charts for the board or one area
+pub struct StatsMenu {
+    /// Index into the caller's visible areas, or `None` for the whole board
+    pub area: Option<usize>,
+}
+
+impl Default for StatsMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsMenu {
+    pub fn new() -> Self {
+        Self { area: None }
+    }
+}
+
+/// Lay out `rows` of (label, count) as a horizontal bar chart in `width`
+/// columns, the longest bar going to the largest count
+///
+/// Labels and counts are padded to line up and any nonzero count gets at least one
+/// `#`. Too narrow for bars, rows are just the label and count.
+pub fn bar_chart(rows: &[(String, usize)], width: usize) -> Vec<String> {
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| display_width(label))
+        .max()
+        .unwrap_or(0)
+        .min(width / 3);
+    let count_width = rows
+        .iter()
+        .map(|(_, count)| count.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let most = rows.iter().map(|&(_, count)| count).max().unwrap_or(0);
+    let bar_room = width.saturating_sub(label_width + count_width + 2);
+
+    rows.iter()
+        .map(|(label, count)| {
+            let label = pad_to_width(&ellipsize(label, label_width), label_width);
+            if bar_room == 0 {
+                return format!("{} {:>count_width$}", label, count);
+            }
+            let bar = match *count {
+                0 => 0,
+                count => (count * bar_room / most).max(1),
+            };
+            format!(
+                "{} {} {:>count_width$}",
+                label,
+                pad_to_width(&"#".repeat(bar), bar_room),
+                count
+            )
+        })
+        .collect()
+}
+
+/// Posts-per-week and busiest-hours charts for one set of figures
+fn activity_items(activity: &Activity, width: usize) -> Vec<MenuItem> {
+    let mut items = vec![MenuItem::info("Posts per week:")];
+    let weeks: Vec<(String, usize)> = activity
+        .per_week
+        .iter()
+        .enumerate()
+        .map(|(weeks_ago, &count)| {
+            let label = match weeks_ago {
+                0 => "this wk".to_string(),
+                n => format!("{} wk ago", n),
+            };
+            (label, count)
+        })
+        .collect();
+    for line in bar_chart(&weeks, width) {
+        items.push(MenuItem::info(&line));
+    }
+
+    items.push(MenuItem::blank());
+    let hours: Vec<(String, usize)> = activity
+        .busiest_hours()
+        .into_iter()
+        .take(MAX_LISTED_HOURS)
+        .map(|(hour, count)| (format!("{:02}:00 UTC", hour), count))
+        .collect();
+    if hours.is_empty() {
+        items.push(MenuItem::info("Busiest hours: no posts yet"));
+    } else {
+        items.push(MenuItem::info("Busiest hours:"));
+        for line in bar_chart(&hours, width) {
+            items.push(MenuItem::info(&line));
+        }
+    }
+    items
+}
+
+impl StatsMenu {
+    fn render_board(&self, stats: &BoardStats, width: usize) -> Vec<MenuItem> {
+        let mut items = vec![
+            MenuItem::info(&format!(
+                "Users: {}   Bulletins: {}   Areas: {}",
+                stats.users,
+                stats.board.bulletins,
+                stats.areas.len()
+            )),
+            MenuItem::separator(),
+        ];
+        items.extend(activity_items(&stats.board, width));
+
+        items.push(MenuItem::separator());
+        items.push(MenuItem::info("Top areas:"));
+        let areas: Vec<(String, usize)> = stats
+            .areas
+            .iter()
+            .take(MAX_LISTED_AREAS)
+            .map(|area| (area.name().to_string(), area.activity.bulletins))
+            .collect();
+        // Room for the "[9] " an option adds
+        for (index, line) in bar_chart(&areas, width.saturating_sub(4))
+            .iter()
+            .enumerate()
+        {
+            items.push(MenuItem::option(&(index + 1).to_string(), line));
+        }
+        if stats.areas.len() > MAX_LISTED_AREAS {
+            items.push(MenuItem::info(&format!(
+                "...and {} more",
+                stats.areas.len() - MAX_LISTED_AREAS
+            )));
+        }
+        items
+    }
+}
+
+impl MenuScreen for StatsMenu {
+    fn render(&self, data: &BbsSession) -> MenuRender {
+        let mut items = vec![];
+        let width = data.effective_width().saturating_sub(4);
+
+        let Some(stats) = &data.board_stats else {
+            items.push(MenuItem::option("B", "Back to Main Menu"));
+            return MenuRender::with_items("BOARD STATISTICS", items, "\nChoice: ");
+        };
+        items.push(MenuItem::info(&format!(
+            "Figures as of {}",
+            stats.computed_at.strftime("%Y-%m-%d %H:%M UTC")
+        )));
+
+        let title = match self.area.and_then(|index| stats.areas.get(index)) {
+            Some(area) => {
+                items.push(MenuItem::info(&format!(
+                    "Bulletins: {}",
+                    area.activity.bulletins
+                )));
+                items.push(MenuItem::separator());
+                items.extend(activity_items(&area.activity, width));
+                items.push(MenuItem::separator());
+                items.push(MenuItem::option("A", "All Areas"));
+                format!("BOARD STATISTICS - {}", area.name().to_uppercase())
+            }
+            None => {
+                items.extend(self.render_board(stats, width));
+                items.push(MenuItem::separator());
+                "BOARD STATISTICS".to_string()
+            }
+        };
+        items.push(MenuItem::option("B", "Back to Main Menu"));
+
+        MenuRender::with_items(&title, items, "\nChoice: ")
+    }
+
+    fn handle_input(&self, data: &BbsSession, input: &str) -> MenuAction {
+        let areas = data.board_stats.as_ref().map_or(0, |s| s.areas.len());
+
+        match input.to_lowercase().as_str() {
+            "" | "b" | "q" => MenuAction::GoTo(Menu::Main),
+            "a" if self.area.is_some() => MenuAction::StatsArea(None),
+            number if self.area.is_none() => match number.parse::<usize>() {
+                Ok(n) if (1..=areas.min(MAX_LISTED_AREAS)).contains(&n) => {
+                    MenuAction::StatsArea(Some(n - 1))
+                }
+                _ => MenuAction::ShowMessage("Invalid choice. Please try again.".to_string()),
+            },
+            _ => MenuAction::ShowMessage("Invalid choice. Please try again.".to_string()),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use super::{Menu, MenuAction, MenuRender, MenuScreen};
+use crate::{
+    box_renderer::MenuItem,
+    services::{Access, Feature},
+    session::BbsSession,
+    text_width::ellipsize,
+};
+
+/// Most new bulletins listed by title; the rest are counted
+const MAX_LISTED_BULLETINS: usize = 5;
+
+/// "Since your last call" screen shown right after login
+pub struct SummaryMenu;
+
+impl Default for SummaryMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SummaryMenu {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MenuScreen for SummaryMenu {
+    fn render(&self, data: &BbsSession) -> MenuRender {
+        let mut items = vec![];
+
+        let Some(summary) = &data.logon_summary else {
+            items.push(MenuItem::option("C", "Continue to main menu"));
+            return MenuRender::with_items("SINCE YOUR LAST CALL", items, "\nChoice: ");
+        };
+
+        items.push(MenuItem::info(&format!(
+            "Last call: {}",
+            summary.last_call_display
+        )));
+        items.push(MenuItem::separator());
+
+        let mail = match (summary.unread_mail, summary.new_mail) {
+            (0, _) => "Private Messages: no unread mail".to_string(),
+            (unread, 0) => format!("Private Messages: {} unread", unread),
+            (unread, new) => format!("Private Messages: {} unread, {} new", unread, new),
+        };
+        if data.feature_access(Feature::Messages) != Access::None {
+            items.push(MenuItem::option("M", &mail));
+        }
+
+        if let Some(bulletins) = &summary.new_bulletins {
+            if bulletins.is_empty() {
+                items.push(MenuItem::option("B", "Bulletins: nothing new"));
+            } else {
+                items.push(MenuItem::option(
+                    "B",
+                    &format!("Bulletins: {} new", bulletins.len()),
+                ));
+                for (index, bulletin) in bulletins.iter().take(MAX_LISTED_BULLETINS).enumerate() {
+                    items.push(MenuItem::option(
+                        &(index + 1).to_string(),
+                        &format!(
+                            "{} by {}",
+                            ellipsize(&bulletin.title, 35),
+                            bulletin.byline()
+                        ),
+                    ));
+                }
+                if bulletins.len() > MAX_LISTED_BULLETINS {
+                    items.push(MenuItem::info(&format!(
+                        "...and {} more",
+                        bulletins.len() - MAX_LISTED_BULLETINS
+                    )));
+                }
+            }
+        }
+
+        items.push(MenuItem::separator());
+        items.push(MenuItem::option("C", "Continue to main menu"));
+
+        MenuRender::with_items("SINCE YOUR LAST CALL", items, "\nChoice: ")
+    }
+
+    fn handle_input(&self, data: &BbsSession, input: &str) -> MenuAction {
+        let new_bulletins = data
+            .logon_summary
+            .as_ref()
+            .and_then(|s| s.new_bulletins.as_ref());
+
+        match input.to_lowercase().as_str() {
+            "" | "c" => MenuAction::GoTo(Menu::Main),
+            "m" if data.feature_access(Feature::Messages) != Access::None => {
+                MenuAction::MessageInbox
+            }
+            "b" if new_bulletins.is_some() => MenuAction::BulletinList,
+            number => {
+                let bulletin = number
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=MAX_LISTED_BULLETINS).contains(n))
+                    .and_then(|n| new_bulletins?.get(n - 1));
+                match bulletin {
+                    Some(bulletin) => MenuAction::BulletinRead(bulletin.id),
+                    None => {
+                        MenuAction::ShowMessage("Invalid choice. Please try again.".to_string())
+                    }
+                }
+            }
+        }
+    }
+}
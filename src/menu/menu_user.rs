@@ -7,6 +7,12 @@ pub struct UserMenu {
     show_offline_users: bool,
 }
 
+impl Default for UserMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UserMenu {
     pub fn new() -> Self {
         Self {
@@ -73,10 +79,48 @@ impl MenuScreen for UserMenu {
             items.push(MenuItem::option("T", "Sort by last login"));
         }
 
-        if data.is_logged_in() {
+        if let Some(user) = &data.user {
             items.push(MenuItem::option("P", "View your profile"));
+            items.push(MenuItem::option(
+                "D",
+                &format!(
+                    "Display name: {}",
+                    user.display_name.as_deref().unwrap_or("(none)")
+                ),
+            ));
+            items.push(MenuItem::option(
+                "F",
+                &format!(
+                    "Forward messages to: {}",
+                    user.forward_to.as_deref().unwrap_or("(nobody)")
+                ),
+            ));
+            let vacation = if user.auto_reply.is_some() {
+                "on"
+            } else {
+                "off"
+            };
+            items.push(MenuItem::option(
+                "V",
+                &format!("Vacation reply: {}", vacation),
+            ));
+            let quiet = if user.quiet_mode { "on" } else { "off" };
+            items.push(MenuItem::option(
+                "Q",
+                &format!("Quiet mode (no bell): {}", quiet),
+            ));
         }
 
+        let screen_reader = if data.screen_reader_active() {
+            "on"
+        } else {
+            "off"
+        };
+        items.push(MenuItem::option(
+            "R",
+            &format!("Screen reader mode: {}", screen_reader),
+        ));
+
         items.push(MenuItem::option("B", "Back to main"));
 
         MenuRender::with_items("USER DIRECTORY", items, "\nChoice: ")
@@ -116,29 +160,20 @@ impl MenuScreen for UserMenu {
                 MenuAction::ShowMessage(total_msg)
             }
             "w" => {
-                // Build real online users list
-                let mut online_msg = "Users currently online:\n".to_string();
-
-                match &data.user_stats {
-                    Some(stats) => {
-                        // TODO: list all online users here?
-                        if stats.online_users > 1 {
-                            online_msg.push_str(&format!(
-                                "\n* ... and {} others",
-                                stats.online_users - 1
-                            ));
-                        } else {
-                            online_msg.push_str("* No users currently online");
-                        }
-                    }
-                    None => {
-                        // For now, show current user if logged in, or anonymous
-                        if data.is_logged_in() {
-                            online_msg.push_str(&format!("* {} (you)", data.display_username()));
-                        } else {
-                            online_msg.push_str("* Anonymous (you)");
-                        }
-                    }
+                let mut online_msg = "Users currently online:".to_string();
+                for session in data.services.presence.online() {
+                    let name = session.username.as_deref().unwrap_or("Anonymous");
+                    let you = if Some(session.id) == data.presence_id() {
+                        " (you)"
+                    } else {
+                        ""
+                    };
+                    let location = session
+                        .location
+                        .as_deref()
+                        .map(|location| format!(" - {}", location))
+                        .unwrap_or_default();
+                    online_msg.push_str(&format!("\n* {}{}{}", name, location, you));
                 }
 
                 MenuAction::ShowMessage(online_msg)
@@ -149,20 +184,28 @@ impl MenuScreen for UserMenu {
             "p" => {
                 if data.is_logged_in() {
                     let profile_msg = format!(
-                        "User Profile:\nUsername: {}\nJoined: Today\nLast Login: Now\n\n(Full profile features coming soon!)",
-                        data.display_username()
+                        "User Profile:\nUsername: {}\nDisplay name: {}\nJoined: Today\nLast Login: Now\n\n(Full profile features coming soon!)",
+                        data.display_username(),
+                        data.display_name().unwrap_or_else(|| "(none)".to_string())
                     );
                     MenuAction::ShowMessage(profile_msg)
                 } else {
                     MenuAction::ShowMessage("Invalid choice.".to_string())
                 }
             }
+            "q" if data.is_logged_in() => MenuAction::ToggleQuietMode,
+            "d" if data.is_logged_in() => MenuAction::SetDisplayName,
+            "f" if data.is_logged_in() => MenuAction::SetMailForward,
+            "v" if data.is_logged_in() => MenuAction::SetAutoReply,
+            "r" => MenuAction::ToggleScreenReader,
             "b" => MenuAction::GoTo(Menu::Main),
             _ => {
                 if data.is_logged_in() {
-                    MenuAction::ShowMessage("Invalid choice. Use L, W, N/T, P, or B.".to_string())
+                    MenuAction::ShowMessage(
+                        "Invalid choice. Use L, W, N/T, P, D, F, V, Q, R, or B.".to_string(),
+                    )
                 } else {
-                    MenuAction::ShowMessage("Invalid choice. Use L, W, N/T, or B.".to_string())
+                    MenuAction::ShowMessage("Invalid choice. Use L, W, N/T, R, or B.".to_string())
                 }
             }
         }
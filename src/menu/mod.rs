@@ -1,10 +1,16 @@
 pub mod menu_bulletin;
+pub mod menu_gateway;
 pub mod menu_main;
 pub mod menu_message;
+pub mod menu_stats;
+pub mod menu_summary;
 pub mod menu_user;
 // pub mod file_menu;
 
 use crate::box_renderer::MenuItem;
+use crate::bulletins::Bulletin;
+use crate::post_check::{PostIssue, PostSummary, mark_filtered};
+use crate::text_width::wrap_to_width;
 
 use crate::session::BbsSession;
 
@@ -15,9 +21,29 @@ pub enum Menu {
     Bulletins,
     Users,
     Messages,
+    Gateway,
+    /// What changed since the caller's last call, shown after login
+    Summary,
+    /// Board-wide activity figures and charts
+    Stats,
     // Files,
 }
 
+impl Menu {
+    /// Short lowercase name, used as a label in timing metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Menu::Main => "main",
+            Menu::Bulletins => "bulletins",
+            Menu::Users => "users",
+            Menu::Messages => "messages",
+            Menu::Gateway => "gateway",
+            Menu::Summary => "summary",
+            Menu::Stats => "stats",
+        }
+    }
+}
+
 /// Actions that menus can return
 #[derive(Debug, Clone, PartialEq)]
 pub enum MenuAction {
@@ -27,6 +53,25 @@ pub enum MenuAction {
     Logout,
     Quit,
     ShowMessage(String),
+    ShowClientFingerprint,
+    /// Page through the manual written from this board's config
+    ShowBoardGuide,
+    /// Open Board Statistics with fresh figures
+    ShowBoardStats,
+    /// Show one area's figures by its index on the statistics screen, or
+    /// the whole board for `None`
+    StatsArea(Option<usize>),
+    ToggleQuietMode,
+    ToggleScreenReader,
+    /// Ask for a new display name
+    SetDisplayName,
+    /// Ask who should get copies of private messages
+    SetMailForward,
+    /// Ask for a vacation reply
+    SetAutoReply,
+    /// Run the SysOp script bound to this main menu key
+    #[cfg(feature = "scripting")]
+    RunScript(String),
 
     // TODO: generalize this like GoToSubMenu(SubMenu)?
     // Bulletin-specific actions
@@ -37,16 +82,37 @@ pub enum MenuAction {
         content: String,
     },
     BulletinPostContent(String),
+    /// Show a finished bulletin for confirmation before posting
+    BulletinPreview {
+        title: String,
+        content: String,
+    },
     BulletinList,
     BulletinBackToMenu,
     BulletinToggleReadFilter,
     BulletinToggleUnreadOnly,
+    /// Mark everything in the area (or the listing on screen) read
+    BulletinMarkAllRead,
+    /// Pick a club to browse, join one, or (SysOp) run them
+    BulletinClubs,
+    BulletinLeaveClub,
 
     // Message-specific actions
     MessageInbox,
     MessageSent,
     MessageCompose,
     MessageComposeSubject(String),
+    /// Ask for the message text again
+    MessageEditContent {
+        recipient: String,
+        subject: String,
+    },
+    /// Show a finished message for confirmation before sending
+    MessagePreview {
+        recipient: String,
+        subject: String,
+        content: String,
+    },
     MessageSend {
         recipient: String,
         subject: String,
@@ -55,6 +121,16 @@ pub enum MenuAction {
     MessageRead(u32),
     MessageDelete(u32),
     MessageBackToMenu,
+    /// Mark every message in the inbox read
+    MessageMarkAllRead,
+    /// Delete every read message in the inbox
+    MessageDeleteRead,
+
+    /// Dial out to the gateway board at this index in the config
+    GatewayConnect(usize),
+
+    /// Run the `[commands]` entry at this index in the config
+    RunCommand(usize),
 }
 
 /// Statistics about users for display in menus
@@ -74,6 +150,19 @@ pub struct RecentLogin {
     pub is_current_user: bool,
 }
 
+/// What changed since the caller's previous login
+#[derive(Debug, Default, Clone)]
+pub struct LogonSummary {
+    pub last_call_display: String,
+    /// Unread private messages, old and new
+    pub unread_mail: usize,
+    /// Private messages received since the last call
+    pub new_mail: usize,
+    /// Bulletins posted since the last call, newest first; `None` if the
+    /// caller cannot read bulletins
+    pub new_bulletins: Option<Vec<Bulletin>>,
+}
+
 /// Output from menu rendering - contains all display information
 #[derive(Debug)]
 pub struct MenuRender {
@@ -100,6 +189,43 @@ impl MenuRender {
     }
 }
 
+/// The body of a bulletin or message preview: its size, the text with
+/// filtered words marked, anything stopping it going out, and the choice
+/// to submit, edit or abort
+pub fn post_preview_items(
+    session: &BbsSession,
+    content: &str,
+    issues: &[PostIssue],
+) -> Vec<MenuItem> {
+    let filtered = &session.config.features.filtered_words;
+    let width = session.effective_width().saturating_sub(4);
+
+    let mut items = vec![
+        MenuItem::info(&PostSummary::of(content).to_string()),
+        MenuItem::separator(),
+    ];
+    for line in content.lines() {
+        for wrapped in wrap_to_width(&mark_filtered(line, filtered), width) {
+            items.push(MenuItem::info(&wrapped));
+        }
+    }
+    items.push(MenuItem::separator());
+
+    if issues.is_empty() {
+        items.push(MenuItem::option("S", "Submit"));
+    } else {
+        items.push(MenuItem::info("Can't post this yet:"));
+        for issue in issues {
+            items.push(MenuItem::info(&format!("- {}", issue)));
+        }
+        items.push(MenuItem::blank());
+        items.push(MenuItem::disabled_option("S", "Submit"));
+    }
+    items.push(MenuItem::option("E", "Edit Text"));
+    items.push(MenuItem::option("A", "Abort"));
+    items
+}
+
 /// The Menu trait - clean interface with no I/O dependencies
 pub trait MenuScreen {
     /// Render the menu - pure function that returns display data
@@ -1,5 +1,9 @@
 use crate::errors::{BbsError, BbsResult};
-use crate::messages::{MessageRequest, MessageStats, MessageSummary, PrivateMessage};
+use crate::journal::{Journal, JournalEntry, write_snapshot};
+use crate::messages::{MessageRequest, MessageStats, PrivateMessage};
+use crate::services::id_service::{IdKind, IdService};
+
+use jiff::Timestamp;
 
 use std::collections::HashMap;
 use std::fs;
@@ -16,18 +20,38 @@ pub trait MessageStorage {
     fn get_sent(&self, username: &str) -> BbsResult<Vec<PrivateMessage>>;
     fn mark_read(&mut self, id: u32, username: &str) -> BbsResult<()>;
     fn delete_message(&mut self, id: u32, username: &str) -> BbsResult<()>;
+    /// The messages among `ids` that `username` can see, in the order asked for
+    fn get_many(&self, ids: &[u32], username: &str) -> BbsResult<Vec<PrivateMessage>>;
+    /// Mark every unread message among `ids` sent to `username` as read,
+    /// returning how many changed; others are skipped
+    fn mark_read_many(&mut self, ids: &[u32], username: &str) -> BbsResult<usize>;
+    /// Delete every message among `ids` that `username` can see, returning
+    /// how many went; others are skipped
+    fn delete_many(&mut self, ids: &[u32], username: &str) -> BbsResult<usize>;
     fn get_stats(&self, username: &str) -> MessageStats;
+    /// Inbox messages that arrived after `since`, newest first
+    fn get_inbox_since(&self, username: &str, since: Timestamp) -> BbsResult<Vec<PrivateMessage>>;
 }
 
 /// JSON file-based private message storage implementation
 pub struct JsonMessageStorage {
     messages_file: PathBuf,
     messages_cache: HashMap<u32, PrivateMessage>,
-    next_id: u32,
+    ids: IdService,
+    journal: Journal,
 }
 
 impl JsonMessageStorage {
+    /// Open message storage with its own ID allocator for `data_dir`
+    // The server shares one allocator through `with_ids`; this is for tests and tools
+    #[allow(dead_code)]
     pub fn new<P: AsRef<Path>>(data_dir: P) -> BbsResult<Self> {
+        let ids = IdService::new(&data_dir)?;
+        Self::with_ids(data_dir, ids)
+    }
+
+    /// Open message storage drawing IDs from a shared allocator
+    pub fn with_ids<P: AsRef<Path>>(data_dir: P, ids: IdService) -> BbsResult<Self> {
         let data_dir = data_dir.as_ref().to_path_buf();
         let messages_file = data_dir.join("messages.json");
 
@@ -39,60 +63,76 @@ impl JsonMessageStorage {
         }
 
         let mut storage = Self {
+            journal: Journal::for_file(&messages_file),
             messages_file,
             messages_cache: HashMap::new(),
-            next_id: 1,
+            ids,
         };
 
         storage.load_all_messages()?;
         Ok(storage)
     }
 
-    /// Load all messages from the JSON file into the cache
+    /// Load all messages from the JSON file and its journal into the cache
     fn load_all_messages(&mut self) -> BbsResult<()> {
-        if !self.messages_file.exists() {
-            // Create empty messages file
-            let empty_messages: HashMap<u32, PrivateMessage> = HashMap::new();
-            self.save_all_messages(&empty_messages)?;
-            return Ok(());
-        }
-
-        let content = fs::read_to_string(&self.messages_file)
-            .map_err(|e| BbsError::Configuration(format!("Failed to read messages file: {}", e)))?;
+        if self.messages_file.exists() {
+            let content = fs::read_to_string(&self.messages_file).map_err(|e| {
+                BbsError::Configuration(format!("Failed to read messages file: {}", e))
+            })?;
 
-        if content.trim().is_empty() {
-            let empty_messages: HashMap<u32, PrivateMessage> = HashMap::new();
-            self.save_all_messages(&empty_messages)?;
-            return Ok(());
+            if !content.trim().is_empty() {
+                self.messages_cache = serde_json::from_str(&content).map_err(|e| {
+                    BbsError::Configuration(format!("Failed to parse messages file: {}", e))
+                })?;
+            }
         }
 
-        let messages: HashMap<u32, PrivateMessage> =
-            serde_json::from_str(&content).map_err(|e| {
-                BbsError::Configuration(format!("Failed to parse messages file: {}", e))
-            })?;
+        // Changes made since the file was last compacted
+        for entry in self.journal.pending(&self.messages_file)? {
+            match entry {
+                JournalEntry::Put { key, value } => {
+                    self.messages_cache.insert(key, value);
+                }
+                JournalEntry::Delete { key } => {
+                    self.messages_cache.remove(&key);
+                }
+            }
+        }
 
-        self.next_id = messages.keys().max().unwrap_or(&0) + 1;
-        self.messages_cache = messages;
+        let max_id = self.messages_cache.keys().max().copied().unwrap_or(0);
+        self.ids.reserve_through(IdKind::Message, max_id)?;
 
+        if !self.messages_file.exists() || self.journal.path().exists() {
+            self.compact()?;
+        }
         Ok(())
     }
 
-    /// Save all messages from the cache to the JSON file
-    fn save_all_messages(&self, messages: &HashMap<u32, PrivateMessage>) -> BbsResult<()> {
-        let content = serde_json::to_string_pretty(messages)
+    /// Rewrite messages.json from the cache and start a fresh journal
+    pub fn compact(&mut self) -> BbsResult<()> {
+        let content = serde_json::to_string_pretty(&self.messages_cache)
             .map_err(|e| BbsError::Configuration(format!("Failed to serialize messages: {}", e)))?;
 
-        fs::write(&self.messages_file, content).map_err(|e| {
-            BbsError::Configuration(format!("Failed to write messages file: {}", e))
-        })?;
-
-        Ok(())
+        write_snapshot(&self.messages_file, &content)?;
+        self.journal.clear()
     }
 
-    /// Save a single message to the cache and file
+    /// Save a single message to the cache and journal
     fn save_message(&mut self, message: &PrivateMessage) -> BbsResult<()> {
         self.messages_cache.insert(message.id, message.clone());
-        self.save_all_messages(&self.messages_cache)
+        self.record(&JournalEntry::Put {
+            key: message.id,
+            value: message,
+        })
+    }
+
+    /// Append a change to the journal, compacting once it has grown
+    fn record(&mut self, entry: &JournalEntry<u32, &PrivateMessage>) -> BbsResult<()> {
+        self.journal.append(entry)?;
+        if self.journal.needs_compaction() {
+            self.compact()?;
+        }
+        Ok(())
     }
 
     /// Check if a user exists (placeholder - would need user storage reference)
@@ -103,6 +143,15 @@ impl JsonMessageStorage {
     }
 }
 
+/// Fold outstanding journal entries into messages.json on a clean close
+impl Drop for JsonMessageStorage {
+    fn drop(&mut self) {
+        if self.journal.has_entries() {
+            let _ = self.compact();
+        }
+    }
+}
+
 impl MessageStorage for JsonMessageStorage {
     fn send_message(
         &mut self,
@@ -121,16 +170,16 @@ impl MessageStorage for JsonMessageStorage {
         }
 
         // Create the message
-        let message = PrivateMessage::new(
-            self.next_id,
+        let mut message = PrivateMessage::new(
+            self.ids.next_id(IdKind::Message)?,
             request.sender.clone(),
             request.recipient.clone(),
             request.subject.clone(),
             request.content.clone(),
         );
 
+        message.sender_display = request.sender_display.clone();
         let message_id = message.id;
-        self.next_id += 1;
 
         // Save the message
         self.save_message(&message)?;
@@ -161,8 +210,14 @@ impl MessageStorage for JsonMessageStorage {
             .collect();
 
         // Sort by sent_at descending (newest first)
-        inbox.sort_by(|a, b| b.sent_at.cmp(&a.sent_at));
+        inbox.sort_by_key(|m| std::cmp::Reverse(m.sent_at));
+
+        Ok(inbox)
+    }
 
+    fn get_inbox_since(&self, username: &str, since: Timestamp) -> BbsResult<Vec<PrivateMessage>> {
+        let mut inbox = self.get_inbox(username)?;
+        inbox.retain(|msg| msg.sent_at > since);
         Ok(inbox)
     }
 
@@ -175,7 +230,7 @@ impl MessageStorage for JsonMessageStorage {
             .collect();
 
         // Sort by sent_at descending (newest first)
-        sent.sort_by(|a, b| b.sent_at.cmp(&a.sent_at));
+        sent.sort_by_key(|m| std::cmp::Reverse(m.sent_at));
 
         Ok(sent)
     }
@@ -185,8 +240,8 @@ impl MessageStorage for JsonMessageStorage {
             // Only the recipient can mark a message as read
             if message.recipient == username {
                 message.mark_read();
-                self.save_all_messages(&self.messages_cache)?;
-                Ok(())
+                let message = message.clone();
+                self.save_message(&message)
             } else {
                 Err(BbsError::InvalidInput(
                     "Only the recipient can mark a message as read".to_string(),
@@ -206,10 +261,11 @@ impl MessageStorage for JsonMessageStorage {
                 // If both sender and recipient have deleted, remove from cache
                 if message.is_deleted_by_sender && message.is_deleted_by_recipient {
                     self.messages_cache.remove(&id);
+                    self.record(&JournalEntry::Delete { key: id })
+                } else {
+                    let message = message.clone();
+                    self.save_message(&message)
                 }
-
-                self.save_all_messages(&self.messages_cache)?;
-                Ok(())
             } else {
                 Err(BbsError::InvalidInput(
                     "You don't have permission to delete this message".to_string(),
@@ -220,6 +276,45 @@ impl MessageStorage for JsonMessageStorage {
         }
     }
 
+    fn get_many(&self, ids: &[u32], username: &str) -> BbsResult<Vec<PrivateMessage>> {
+        Ok(ids
+            .iter()
+            .filter_map(|id| self.messages_cache.get(id))
+            .filter(|message| message.is_visible_to(username))
+            .cloned()
+            .collect())
+    }
+
+    fn mark_read_many(&mut self, ids: &[u32], username: &str) -> BbsResult<usize> {
+        let mut marked = 0;
+        for id in ids {
+            let unread = self
+                .messages_cache
+                .get(id)
+                .is_some_and(|m| m.recipient == username && m.is_unread());
+            if unread {
+                self.mark_read(*id, username)?;
+                marked += 1;
+            }
+        }
+        Ok(marked)
+    }
+
+    fn delete_many(&mut self, ids: &[u32], username: &str) -> BbsResult<usize> {
+        let mut deleted = 0;
+        for id in ids {
+            let visible = self
+                .messages_cache
+                .get(id)
+                .is_some_and(|m| m.is_visible_to(username));
+            if visible {
+                self.delete_message(*id, username)?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
     fn get_stats(&self, username: &str) -> MessageStats {
         let inbox_messages: Vec<&PrivateMessage> = self
             .messages_cache
@@ -237,21 +332,10 @@ impl MessageStorage for JsonMessageStorage {
         let total_received = inbox_messages.len();
         let total_sent = sent_messages.len();
 
-        // Get recent messages (last 10 from inbox)
-        let mut recent_inbox: Vec<&PrivateMessage> = inbox_messages;
-        recent_inbox.sort_by(|a, b| b.sent_at.cmp(&a.sent_at));
-        recent_inbox.truncate(10);
-
-        let recent_messages: Vec<MessageSummary> = recent_inbox
-            .iter()
-            .map(|msg| MessageSummary::from(*msg))
-            .collect();
-
         MessageStats {
             unread_count,
             total_received,
             total_sent,
-            recent_messages,
         }
     }
 }
@@ -1,4 +1,6 @@
 use crate::errors::{BbsError, BbsResult};
+use crate::post_check::{MAX_TITLE_LENGTH, PostField, PostIssue, PostProblem, check_field};
+use crate::text_width::{char_width, display_width};
 use jiff::Timestamp;
 use serde::{Deserialize, Serialize};
 
@@ -14,6 +16,9 @@ pub struct PrivateMessage {
     pub read_at: Option<Timestamp>,
     pub is_deleted_by_sender: bool,
     pub is_deleted_by_recipient: bool,
+    /// The sender's display name when the message was sent
+    #[serde(default)]
+    pub sender_display: Option<String>,
 }
 
 impl PrivateMessage {
@@ -34,9 +39,15 @@ impl PrivateMessage {
             read_at: None,
             is_deleted_by_sender: false,
             is_deleted_by_recipient: false,
+            sender_display: None,
         }
     }
 
+    /// Name to show for the sender: the display name if there was one
+    pub fn sender_name(&self) -> &str {
+        self.sender_display.as_deref().unwrap_or(&self.sender)
+    }
+
     pub fn is_unread(&self) -> bool {
         self.read_at.is_none()
     }
@@ -100,7 +111,7 @@ impl PrivateMessage {
             let mut current = String::new();
 
             for word in hard_line.split_whitespace() {
-                let word_len = word.chars().count();
+                let word_len = display_width(word);
 
                 if word_len > width {
                     if !current.is_empty() {
@@ -108,14 +119,16 @@ impl PrivateMessage {
                         current = String::new();
                     }
 
-                    let char_iter = word.chars();
                     let mut chunk = String::new();
-                    for ch in char_iter {
-                        chunk.push(ch);
-                        if chunk.chars().count() == width {
-                            out.push(chunk);
-                            chunk = String::new();
+                    let mut chunk_width = 0;
+                    for ch in word.chars() {
+                        let ch_width = char_width(ch);
+                        if chunk_width + ch_width > width && !chunk.is_empty() {
+                            out.push(std::mem::take(&mut chunk));
+                            chunk_width = 0;
                         }
+                        chunk.push(ch);
+                        chunk_width += ch_width;
                     }
                     if !chunk.is_empty() {
                         out.push(chunk);
@@ -124,7 +137,7 @@ impl PrivateMessage {
                     if current.is_empty() {
                         current.push_str(word);
                     } else {
-                        let new_len = current.chars().count() + 1 + word_len;
+                        let new_len = display_width(&current) + 1 + word_len;
                         if new_len <= width {
                             current.push(' ');
                             current.push_str(word);
@@ -153,6 +166,10 @@ pub struct MessageRequest {
     pub subject: String,
     pub content: String,
     pub sender: String,
+    pub sender_display: Option<String>,
+    /// Sent by the system on a user's behalf (a forward or vacation reply);
+    /// automatic messages are never forwarded or answered in turn
+    pub automatic: bool,
 }
 
 impl MessageRequest {
@@ -162,59 +179,63 @@ impl MessageRequest {
             subject,
             content,
             sender,
+            sender_display: None,
+            automatic: false,
         }
     }
 
-    pub fn validate(&self, config: &crate::config::BbsConfig) -> BbsResult<()> {
-        if self.recipient.trim().is_empty() {
-            return Err(BbsError::InvalidInput(
-                "Recipient cannot be empty".to_string(),
-            ));
-        }
-
-        if self.recipient.len() > config.features.max_username_length {
-            return Err(BbsError::InvalidInput(format!(
-                "Recipient name too long (max {} characters)",
-                config.features.max_username_length
-            )));
-        }
-
-        if self.subject.trim().is_empty() {
-            return Err(BbsError::InvalidInput(
-                "Subject cannot be empty".to_string(),
-            ));
-        }
-
-        if self.subject.len() > 100 {
-            return Err(BbsError::InvalidInput(
-                "Subject too long (max 100 characters)".to_string(),
-            ));
-        }
+    /// Mark the message as sent by the system rather than typed by a user
+    pub fn sent_automatically(mut self) -> Self {
+        self.automatic = true;
+        self
+    }
 
-        if self.content.trim().is_empty() {
-            return Err(BbsError::InvalidInput(
-                "Message content cannot be empty".to_string(),
-            ));
-        }
+    /// Show the message as from the sender's display name
+    pub fn with_sender_display(mut self, sender_display: Option<String>) -> Self {
+        self.sender_display = sender_display;
+        self
+    }
 
-        if self.content.len() > config.features.max_message_length {
-            return Err(BbsError::InvalidInput(format!(
-                "Message too long (max {} characters)",
-                config.features.max_message_length
-            )));
+    pub fn validate(&self, config: &crate::config::BbsConfig) -> BbsResult<()> {
+        let issues = self.issues(config);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(BbsError::InvalidPost(issues))
         }
+    }
 
+    /// Everything that stops this message being sent
+    pub fn issues(&self, config: &crate::config::BbsConfig) -> Vec<PostIssue> {
+        let filtered = &config.features.filtered_words;
+        let mut issues = Vec::new();
+        check_field(
+            PostField::Recipient,
+            &self.recipient,
+            config.features.max_username_length,
+            &[],
+            &mut issues,
+        );
+        check_field(
+            PostField::Subject,
+            &self.subject,
+            MAX_TITLE_LENGTH,
+            filtered,
+            &mut issues,
+        );
+        check_field(
+            PostField::Content,
+            &self.content,
+            config.features.max_message_length,
+            filtered,
+            &mut issues,
+        );
         if self.sender.trim().is_empty() {
-            return Err(BbsError::InvalidInput("Sender cannot be empty".to_string()));
-        }
-
-        if self.sender == self.recipient {
-            return Err(BbsError::InvalidInput(
-                "Cannot send message to yourself".to_string(),
-            ));
+            issues.push(PostIssue::new(PostField::Author, PostProblem::Empty));
+        } else if self.sender == self.recipient {
+            issues.push(PostIssue::new(PostField::Recipient, PostProblem::ToSelf));
         }
-
-        Ok(())
+        issues
     }
 }
 
@@ -224,29 +245,4 @@ pub struct MessageStats {
     pub unread_count: usize,
     pub total_received: usize,
     pub total_sent: usize,
-    pub recent_messages: Vec<MessageSummary>,
-}
-
-/// Summary of a message for menu display
-#[derive(Debug, Clone)]
-pub struct MessageSummary {
-    pub id: u32,
-    pub from: String,
-    pub to: String,
-    pub subject: String,
-    pub sent_display: String,
-    pub is_read: bool,
-}
-
-impl From<&PrivateMessage> for MessageSummary {
-    fn from(message: &PrivateMessage) -> Self {
-        Self {
-            id: message.id,
-            from: message.sender.clone(),
-            to: message.recipient.clone(),
-            subject: message.subject.clone(),
-            sent_display: message.sent_display(),
-            is_read: !message.is_unread(),
-        }
-    }
 }
@@ -0,0 +1,183 @@
+//! Connection notices picked by the caller's address
+//!
+//! The SysOp lists notices in `[motd]`, each for a range of addresses: a
+//! CIDR block, a single address, a hostname or everyone. When a call comes
+//! in, the notices matching its peer address are shown before the welcome
+//! screen, so outside callers can get a warning banner while a partner
+//! network gets a greeting of its own.
+
+use crate::errors::ConfigError;
+
+use std::fmt;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::str::FromStr;
+
+/// Addresses a notice is shown to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressRange {
+    /// Every caller, written `*`
+    Any,
+    /// A CIDR block such as `192.168.0.0/16`; a bare address matches only itself
+    Network { base: IpAddr, prefix: u8 },
+    /// Whatever the hostname resolves to when the call comes in
+    Host(String),
+}
+
+impl AddressRange {
+    /// Whether `peer` falls in this range
+    ///
+    /// Hostnames are looked up on every call, so a partner board that
+    /// changes address is still recognised. A failed lookup matches nothing.
+    pub fn contains(&self, peer: IpAddr) -> bool {
+        let peer = peer.to_canonical();
+        match self {
+            AddressRange::Any => true,
+            AddressRange::Network { base, prefix } => match (base, peer) {
+                (IpAddr::V4(base), IpAddr::V4(peer)) => {
+                    let mask = u32::MAX.checked_shl(32 - u32::from(*prefix)).unwrap_or(0);
+                    u32::from(*base) & mask == u32::from(peer) & mask
+                }
+                (IpAddr::V6(base), IpAddr::V6(peer)) => {
+                    let mask = u128::MAX.checked_shl(128 - u32::from(*prefix)).unwrap_or(0);
+                    u128::from(*base) & mask == u128::from(peer) & mask
+                }
+                _ => false,
+            },
+            AddressRange::Host(host) => match (host.as_str(), 0).to_socket_addrs() {
+                Ok(mut addrs) => addrs.any(|addr| addr.ip().to_canonical() == peer),
+                Err(e) => {
+                    eprintln!(
+                        "! Could not look up {} for a connection notice: {}",
+                        host, e
+                    );
+                    false
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::InvalidValue("address range".to_string(), s.to_string());
+
+        if s == "*" {
+            return Ok(AddressRange::Any);
+        }
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let Ok(base) = address.parse::<IpAddr>() else {
+            // Anything that isn't an address is taken as a hostname
+            let hostname = !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+            return if hostname {
+                Ok(AddressRange::Host(s.to_ascii_lowercase()))
+            } else {
+                Err(invalid())
+            };
+        };
+
+        let bits = address_bits(base);
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|&p| p <= bits)
+                .ok_or_else(invalid)?,
+            None => bits,
+        };
+        Ok(AddressRange::Network { base, prefix })
+    }
+}
+
+impl fmt::Display for AddressRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressRange::Any => write!(f, "*"),
+            AddressRange::Network { base, prefix } if *prefix == address_bits(*base) => {
+                write!(f, "{}", base)
+            }
+            AddressRange::Network { base, prefix } => write!(f, "{}/{}", base, prefix),
+            AddressRange::Host(host) => write!(f, "{}", host),
+        }
+    }
+}
+
+/// Length of an address in bits, the longest prefix it can have
+fn address_bits(address: IpAddr) -> u8 {
+    if address.is_ipv4() { 32 } else { 128 }
+}
+
+/// A notice shown before the welcome screen to callers from some addresses
+///
+/// Written in the config file as `notice = "range|text"`, one line per
+/// notice. A `!` in front of the range shows the notice to everyone
+/// outside it instead, and `\n` in the text starts a new line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionNotice {
+    pub range: AddressRange,
+    /// Show to callers outside `range` rather than inside it
+    pub outside: bool,
+    pub text: String,
+}
+
+impl ConnectionNotice {
+    /// Whether a caller from `peer` should see this notice
+    pub fn applies_to(&self, peer: IpAddr) -> bool {
+        self.range.contains(peer) != self.outside
+    }
+}
+
+impl FromStr for ConnectionNotice {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (range, text) = s
+            .split_once('|')
+            .ok_or_else(|| ConfigError::InvalidValue("notice".to_string(), s.to_string()))?;
+        let text = text.trim().replace("\\n", "\n");
+        if text.is_empty() {
+            return Err(ConfigError::InvalidValue(
+                "notice".to_string(),
+                s.to_string(),
+            ));
+        }
+
+        let range = range.trim();
+        let (outside, range) = match range.strip_prefix('!') {
+            Some(range) => (true, range.trim()),
+            None => (false, range),
+        };
+        Ok(ConnectionNotice {
+            range: range.parse()?,
+            outside,
+            text,
+        })
+    }
+}
+
+impl fmt::Display for ConnectionNotice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}|{}",
+            if self.outside { "!" } else { "" },
+            self.range,
+            self.text.replace('\n', "\\n")
+        )
+    }
+}
+
+/// Text of every notice for a caller from `peer`, in config order
+pub fn notices_for(peer: IpAddr, notices: &[ConnectionNotice]) -> Vec<String> {
+    notices
+        .iter()
+        .filter(|notice| notice.applies_to(peer))
+        .map(|notice| notice.text.clone())
+        .collect()
+}
@@ -0,0 +1,216 @@
+//! Size summary and validation for bulletins and messages
+//!
+//! Before anything is posted the caller sees a [`PostSummary`] and every
+//! problem found, each as a [`PostIssue`] naming the field it is in, so the
+//! preview can point at the title or the word that needs changing. The
+//! repositories run the same checks and refuse with
+//! [`BbsError::InvalidPost`](crate::errors::BbsError::InvalidPost).
+
+use crate::text_width::wrap_to_width;
+
+use std::fmt;
+
+/// Screen size the preview's estimate assumes
+pub const SCREEN_COLUMNS: usize = 80;
+pub const SCREEN_ROWS: usize = 24;
+
+/// Longest title or subject
+pub const MAX_TITLE_LENGTH: usize = 100;
+
+/// How big a post is, as the preview shows it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostSummary {
+    /// Lines once wrapped at [`SCREEN_COLUMNS`]
+    pub lines: usize,
+    pub words: usize,
+    pub characters: usize,
+    /// Screens of [`SCREEN_ROWS`] lines needed to show it all
+    pub screens: usize,
+}
+
+impl PostSummary {
+    pub fn of(content: &str) -> Self {
+        let lines = content
+            .lines()
+            .map(|line| wrap_to_width(line, SCREEN_COLUMNS).len())
+            .sum();
+        Self {
+            lines,
+            words: content.split_whitespace().count(),
+            characters: content.chars().count(),
+            screens: lines.div_ceil(SCREEN_ROWS),
+        }
+    }
+}
+
+impl fmt::Display for PostSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, {} {}, {} {} - about {} {} at {}x{}",
+            self.lines,
+            plural(self.lines, "line"),
+            self.words,
+            plural(self.words, "word"),
+            self.characters,
+            plural(self.characters, "character"),
+            self.screens,
+            plural(self.screens, "screen"),
+            SCREEN_COLUMNS,
+            SCREEN_ROWS
+        )
+    }
+}
+
+/// The part of a bulletin or message an issue is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostField {
+    Recipient,
+    Subject,
+    Title,
+    Content,
+    /// The bulletin's author or the message's sender
+    Author,
+}
+
+impl fmt::Display for PostField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PostField::Recipient => "Recipient",
+            PostField::Subject => "Subject",
+            PostField::Title => "Title",
+            PostField::Content => "Content",
+            PostField::Author => "Author",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What is wrong with a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostProblem {
+    Empty,
+    /// Longer than `max` characters
+    TooLong {
+        max: usize,
+    },
+    /// Contains a word from `filtered_words`, as written in the post
+    FilteredWord(String),
+    /// A message addressed to its own sender
+    ToSelf,
+}
+
+/// One problem that stops a bulletin or message being posted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostIssue {
+    pub field: PostField,
+    pub problem: PostProblem,
+}
+
+impl PostIssue {
+    pub fn new(field: PostField, problem: PostProblem) -> Self {
+        Self { field, problem }
+    }
+}
+
+impl fmt::Display for PostIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            PostProblem::Empty => write!(f, "{} cannot be empty", self.field),
+            PostProblem::TooLong { max } => {
+                write!(f, "{} too long (max {} characters)", self.field, max)
+            }
+            PostProblem::FilteredWord(word) => {
+                write!(f, "{} contains a filtered word: {}", self.field, word)
+            }
+            PostProblem::ToSelf => write!(f, "Cannot send message to yourself"),
+        }
+    }
+}
+
+/// Check one field for emptiness, length and filtered words
+pub fn check_field(
+    field: PostField,
+    text: &str,
+    max: usize,
+    filtered_words: &[String],
+    issues: &mut Vec<PostIssue>,
+) {
+    if text.trim().is_empty() {
+        issues.push(PostIssue::new(field, PostProblem::Empty));
+        return;
+    }
+    if text.len() > max {
+        issues.push(PostIssue::new(field, PostProblem::TooLong { max }));
+    }
+    for word in find_filtered(text, filtered_words) {
+        issues.push(PostIssue::new(field, PostProblem::FilteredWord(word)));
+    }
+}
+
+/// Words of `text` that are on the filter list, ignoring case, each once
+/// and as written
+pub fn find_filtered(text: &str, filtered_words: &[String]) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    for (_, word) in words(text) {
+        if is_filtered(word, filtered_words) && !found.iter().any(|w| w.eq_ignore_ascii_case(word))
+        {
+            found.push(word.to_string());
+        }
+    }
+    found
+}
+
+/// `text` with each filtered word wrapped in `>>` and `<<`, so it stands
+/// out on screen and to screen readers alike
+pub fn mark_filtered(text: &str, filtered_words: &[String]) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut done = 0;
+    for (start, word) in words(text) {
+        if is_filtered(word, filtered_words) {
+            marked.push_str(&text[done..start]);
+            marked.push_str(">>");
+            marked.push_str(word);
+            marked.push_str("<<");
+            done = start + word.len();
+        }
+    }
+    marked.push_str(&text[done..]);
+    marked
+}
+
+fn is_filtered(word: &str, filtered_words: &[String]) -> bool {
+    filtered_words.iter().any(|f| f.eq_ignore_ascii_case(word))
+}
+
+/// Runs of letters, digits and inner apostrophes, with where each starts
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let end = std::iter::once((text.len(), ' '));
+    for (i, c) in text.char_indices().chain(end) {
+        let in_word = c.is_alphanumeric() || c == '\'';
+        match start {
+            None if in_word => start = Some(i),
+            Some(s) if !in_word => {
+                let run = &text[s..i];
+                let word = run.trim_matches('\'');
+                if !word.is_empty() {
+                    let leading = run.len() - run.trim_start_matches('\'').len();
+                    words.push((s + leading, word));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        noun.to_string()
+    } else {
+        format!("{}s", noun)
+    }
+}
@@ -0,0 +1,411 @@
+//! SysOp scripts for session events and custom menu commands
+//!
+//! Scripts are [Rhai](https://rhai.rs) files in the configured directory:
+//! - `on_login.rhai`, `on_new_user.rhai`, `on_post.rhai` run on those events
+//! - `cmd_<key>.rhai` adds `<key>` to the main menu; a first line of
+//!   `//: Description` gives its menu text
+//!
+//! ```text
+//! // Comments start with '//'
+//! say(`Welcome back, ${user}!`);
+//! if unread > 0 { say(`You have ${unread} unread messages.`); }
+//! notify(sysop, "New caller", `${user} just signed up.`);
+//! return;
+//! ```
+//!
+//! The engine is sandboxed. Scripts see the read-only variables listed in
+//! [`VARIABLES`] and two functions: `say(text)` prints a line to the caller
+//! and `notify(user, subject, body)` sends private mail from
+//! [`SCRIPT_SENDER`]. Nothing else from the host is registered: there is no
+//! `eval`, no `import`, no file or network access, and runs are capped in
+//! operations, call depth, output and mail, so a broken script cannot take
+//! a session down.
+
+use crate::config::{BbsConfig, SecurityLevel};
+use crate::errors::{BbsError, BbsResult};
+use crate::messages::MessageRequest;
+use crate::services::CoreServices;
+
+use rhai::packages::{CorePackage, LogicPackage, Package};
+use rhai::{AST, Dynamic, Engine, INT, Scope};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Script file extension
+const EXTENSION: &str = "rhai";
+
+/// Largest script file that will be loaded
+const MAX_SCRIPT_BYTES: u64 = 16 * 1024;
+
+/// Sender name on mail sent by scripts
+pub const SCRIPT_SENDER: &str = "System";
+
+/// Lines one run may print
+const MAX_OUTPUT_LINES: usize = 50;
+
+/// Private messages one run may send
+const MAX_NOTIFICATIONS: usize = 3;
+
+/// Engine operations one run may take before it is stopped
+const MAX_OPERATIONS: u64 = 50_000;
+
+/// Deepest nesting of script function calls
+const MAX_CALL_LEVELS: usize = 16;
+
+/// Deepest nesting of expressions, at top level and inside functions
+const MAX_EXPR_DEPTHS: (usize, usize) = (32, 16);
+
+/// Longest string a script may build
+const MAX_STRING_SIZE: usize = 4 * 1024;
+
+/// Most items in an array or object map
+const MAX_COLLECTION_SIZE: usize = 256;
+
+/// Variables scripts may read
+pub const VARIABLES: &[&str] = &[
+    "user",
+    "level",
+    "bbs_name",
+    "sysop",
+    "unread",
+    "users",
+    "bulletins",
+    // on_post only; 0 and "" elsewhere
+    "bulletin_id",
+    "title",
+];
+
+/// Session events scripts can react to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptEvent {
+    /// An existing user logged in
+    Login,
+    /// A new account was registered (the caller is now logged in as it)
+    NewUser,
+    /// The caller posted a bulletin
+    Post { bulletin_id: u32, title: String },
+}
+
+impl ScriptEvent {
+    /// Name of the script file stem that handles this event
+    pub fn hook_name(&self) -> &'static str {
+        match self {
+            ScriptEvent::Login => "on_login",
+            ScriptEvent::NewUser => "on_new_user",
+            ScriptEvent::Post { .. } => "on_post",
+        }
+    }
+}
+
+/// Who a script runs for
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptCaller<'a> {
+    /// Logged-in username, or None for anonymous callers
+    pub username: Option<&'a str>,
+    pub level: SecurityLevel,
+}
+
+/// A custom main menu command provided by a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCommand {
+    pub key: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+struct Script {
+    /// File name, for error messages
+    name: String,
+    ast: AST,
+}
+
+/// Loaded scripts, shared by every session
+#[derive(Debug, Default)]
+pub struct ScriptHost {
+    hooks: HashMap<String, Script>,
+    commands: Vec<(ScriptCommand, Script)>,
+}
+
+impl ScriptHost {
+    /// Load and check every script in `directory`
+    ///
+    /// A missing directory means no scripts. Any script that fails to
+    /// compile, or reads a variable not in [`VARIABLES`], is an error, so
+    /// mistakes show up at startup rather than mid-session. Command keys
+    /// may not shadow the built-in keys in `reserved_keys`.
+    pub fn load<P: AsRef<Path>>(directory: P, reserved_keys: &[&str]) -> BbsResult<Self> {
+        let directory = directory.as_ref();
+        let mut host = Self::default();
+        if !directory.is_dir() {
+            return Ok(host);
+        }
+
+        let mut paths: Vec<_> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .collect();
+        paths.sort();
+
+        // Declared by name only: constants would be folded into the scripts
+        let engine = sandbox();
+        let mut variables = Scope::new();
+        for name in VARIABLES {
+            variables.push(*name, ());
+        }
+
+        for path in paths {
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let name = path.display().to_string();
+
+            if fs::metadata(&path)?.len() > MAX_SCRIPT_BYTES {
+                return Err(BbsError::Configuration(format!(
+                    "{}: script is larger than {} bytes",
+                    name, MAX_SCRIPT_BYTES
+                )));
+            }
+            let source = fs::read_to_string(&path)?;
+            let ast = engine
+                .compile_with_scope(&variables, &source)
+                .map_err(|e| BbsError::Configuration(format!("{}: {}", name, e)))?;
+            let script = Script {
+                name: name.clone(),
+                ast,
+            };
+
+            if let Some(key) = stem.strip_prefix("cmd_") {
+                let key = key.to_lowercase();
+                if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(BbsError::Configuration(format!(
+                        "{}: command key must be letters or digits",
+                        name
+                    )));
+                }
+                if reserved_keys.contains(&key.as_str()) {
+                    return Err(BbsError::Configuration(format!(
+                        "{}: key '{}' is already used by the main menu",
+                        name, key
+                    )));
+                }
+                let description = source
+                    .lines()
+                    .next()
+                    .and_then(|line| line.strip_prefix("//:"))
+                    .map(|d| d.trim().to_string())
+                    .unwrap_or_else(|| key.clone());
+                host.commands
+                    .push((ScriptCommand { key, description }, script));
+            } else if ["on_login", "on_new_user", "on_post"].contains(&stem) {
+                host.hooks.insert(stem.to_string(), script);
+            } else {
+                return Err(BbsError::Configuration(format!(
+                    "{}: not an event hook or cmd_<key> script",
+                    name
+                )));
+            }
+        }
+
+        Ok(host)
+    }
+
+    /// Number of loaded scripts
+    pub fn len(&self) -> usize {
+        self.hooks.len() + self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Custom main menu commands, in key order
+    pub fn commands(&self) -> impl Iterator<Item = &ScriptCommand> {
+        self.commands.iter().map(|(command, _)| command)
+    }
+
+    /// Run the hook for `event`, returning the lines it printed
+    pub fn run_hook(
+        &self,
+        event: &ScriptEvent,
+        caller: ScriptCaller,
+        services: &CoreServices,
+        config: &BbsConfig,
+    ) -> Vec<String> {
+        match self.hooks.get(event.hook_name()) {
+            Some(script) => {
+                let mut variables = Variables::new(caller, services, config);
+                if let ScriptEvent::Post { bulletin_id, title } = event {
+                    variables.bulletin_id = INT::from(*bulletin_id);
+                    variables.title = title.clone();
+                }
+                execute(script, variables, services, config)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Run the custom command bound to `key`, if there is one
+    pub fn run_command(
+        &self,
+        key: &str,
+        caller: ScriptCaller,
+        services: &CoreServices,
+        config: &BbsConfig,
+    ) -> Option<Vec<String>> {
+        let (_, script) = self
+            .commands
+            .iter()
+            .find(|(command, _)| command.key.eq_ignore_ascii_case(key))?;
+        let variables = Variables::new(caller, services, config);
+        Some(execute(script, variables, services, config))
+    }
+}
+
+/// An engine with the language core and nothing of the host
+///
+/// Without a module resolver `import` has nothing to load, and `print` and
+/// `debug` go nowhere; `say` and `notify` are registered per run.
+fn sandbox() -> Engine {
+    let mut engine = Engine::new_raw();
+    engine.register_global_module(CorePackage::new().as_shared_module());
+    engine.register_global_module(LogicPackage::new().as_shared_module());
+    engine.disable_symbol("eval");
+    engine.set_strict_variables(true);
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_expr_depths(MAX_EXPR_DEPTHS.0, MAX_EXPR_DEPTHS.1);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(MAX_COLLECTION_SIZE);
+    engine
+}
+
+/// The values behind [`VARIABLES`] for one run
+struct Variables {
+    user: String,
+    level: String,
+    bbs_name: String,
+    sysop: String,
+    unread: INT,
+    users: INT,
+    bulletins: INT,
+    bulletin_id: INT,
+    title: String,
+}
+
+impl Variables {
+    fn new(caller: ScriptCaller, services: &CoreServices, config: &BbsConfig) -> Self {
+        let unread = caller
+            .username
+            .and_then(|u| services.messages.get_unread_count(u).ok())
+            .unwrap_or(0);
+        let users = services
+            .users
+            .get_stats()
+            .map(|s| s.total_users)
+            .unwrap_or(0);
+        let bulletins = services
+            .bulletins
+            .get_stats(None)
+            .map(|s| s.total_bulletins)
+            .unwrap_or(0);
+
+        Self {
+            user: caller.username.unwrap_or("Anonymous").to_string(),
+            level: caller.level.to_string(),
+            bbs_name: config.bbs.name.clone(),
+            sysop: config.bbs.sysop_name.clone(),
+            unread: unread as INT,
+            users: users as INT,
+            bulletins: bulletins as INT,
+            bulletin_id: 0,
+            title: String::new(),
+        }
+    }
+
+    /// Constants scripts can read but not assign
+    fn into_scope(self) -> Scope<'static> {
+        // In the order of VARIABLES
+        let values: [Dynamic; VARIABLES.len()] = [
+            self.user.into(),
+            self.level.into(),
+            self.bbs_name.into(),
+            self.sysop.into(),
+            self.unread.into(),
+            self.users.into(),
+            self.bulletins.into(),
+            self.bulletin_id.into(),
+            self.title.into(),
+        ];
+        let mut scope = Scope::new();
+        for (name, value) in VARIABLES.iter().zip(values) {
+            scope.push_constant_dynamic(*name, value);
+        }
+        scope
+    }
+}
+
+/// What one run asked for, applied once the script has finished
+#[derive(Debug, Default)]
+struct RunEffects {
+    output: Vec<String>,
+    /// (recipient, subject, body)
+    notifications: Vec<(String, String, String)>,
+}
+
+/// Run `script` in a fresh sandbox, returning the lines it printed
+///
+/// A script that fails or hits a limit keeps whatever it printed and sent
+/// before that; the error is logged rather than passed to the caller.
+fn execute(
+    script: &Script,
+    variables: Variables,
+    services: &CoreServices,
+    config: &BbsConfig,
+) -> Vec<String> {
+    let effects = Arc::new(Mutex::new(RunEffects::default()));
+    let mut engine = sandbox();
+
+    let say_effects = Arc::clone(&effects);
+    engine.register_fn("say", move |text: Dynamic| {
+        let mut effects = lock(&say_effects);
+        if effects.output.len() < MAX_OUTPUT_LINES {
+            effects.output.push(text.to_string());
+        }
+    });
+    let notify_effects = Arc::clone(&effects);
+    engine.register_fn("notify", move |to: &str, subject: &str, body: &str| {
+        let mut effects = lock(&notify_effects);
+        if effects.notifications.len() < MAX_NOTIFICATIONS {
+            effects
+                .notifications
+                .push((to.to_string(), subject.to_string(), body.to_string()));
+        }
+    });
+
+    let mut scope = variables.into_scope();
+    if let Err(e) = engine.run_ast_with_scope(&mut scope, &script.ast) {
+        eprintln!("! Script {} stopped: {}", script.name, e);
+    }
+
+    let effects = std::mem::take(&mut *lock(&effects));
+    for (to, subject, body) in effects.notifications {
+        let request = MessageRequest::new(to, subject, body, SCRIPT_SENDER.to_string());
+        // A failed notification should not break the caller's session
+        if let Err(e) = services
+            .messages
+            .send_message(request, config, SecurityLevel::Sysop)
+        {
+            eprintln!("! Script notification failed: {}", e);
+        }
+    }
+    effects.output
+}
+
+fn lock(effects: &Mutex<RunEffects>) -> std::sync::MutexGuard<'_, RunEffects> {
+    effects
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
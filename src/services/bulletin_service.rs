@@ -1,47 +1,189 @@
 use crate::bulletin_repository::{BulletinStats, BulletinStorage};
-use crate::bulletins::{Bulletin, BulletinRequest};
-use crate::config::BbsConfig;
-use crate::errors::{BbsError, BbsResult};
+use crate::bulletins::{Bulletin, BulletinReadState, BulletinRequest};
+use crate::config::{BbsConfig, SecurityLevel};
+use crate::errors::BbsResult;
+use crate::services::club_service::ClubService;
+use crate::services::lock_storage;
+use crate::services::permission_service::{Access, Feature, PermissionService};
+use jiff::Timestamp;
+use std::collections::BTreeSet;
 use std::sync::{Arc, Mutex};
 
+/// Bulletins on the main board and in clubs
+///
+/// Club bulletins are only handed to members (and the SysOp); `username`
+/// is the logged-in caller, or `None` for a guest.
 pub struct BulletinService {
     storage: Arc<Mutex<dyn BulletinStorage + Send>>,
+    permissions: Arc<PermissionService>,
+    clubs: ClubService,
 }
 
 impl BulletinService {
-    pub fn new(storage: Arc<Mutex<dyn BulletinStorage + Send>>) -> Self {
-        Self { storage }
+    pub fn new(
+        storage: Arc<Mutex<dyn BulletinStorage + Send>>,
+        permissions: Arc<PermissionService>,
+        clubs: ClubService,
+    ) -> Self {
+        Self {
+            storage,
+            permissions,
+            clubs,
+        }
     }
 
-    pub fn post_bulletin(&self, request: BulletinRequest, config: &BbsConfig) -> BbsResult<u32> {
-        let mut storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
+    /// Post to the request's area; clubs take posts from members only
+    pub fn post_bulletin(
+        &self,
+        request: BulletinRequest,
+        config: &BbsConfig,
+        level: SecurityLevel,
+    ) -> BbsResult<u32> {
+        let author = (level > SecurityLevel::Guest).then_some(request.author.as_str());
+        let membership = self.clubs.membership(request.area.as_deref(), author)?;
+        self.permissions
+            .require_area_write(Feature::Bulletins, level, membership)?;
+        let mut storage = lock_storage(&self.storage);
         storage.post_bulletin(&request, config)
     }
 
-    pub fn get_bulletin(&self, id: u32) -> BbsResult<Option<Bulletin>> {
-        let storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
-        storage.load_bulletin(id)
+    pub fn get_bulletin(
+        &self,
+        id: u32,
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<Option<Bulletin>> {
+        self.permissions.require_read(Feature::Bulletins, level)?;
+        let bulletin = lock_storage(&self.storage).load_bulletin(id)?;
+        if let Some(bulletin) = &bulletin {
+            let membership = self.clubs.membership(bulletin.area.as_deref(), username)?;
+            self.permissions
+                .require_area_read(Feature::Bulletins, level, membership)?;
+        }
+        Ok(bulletin)
     }
 
-    pub fn mark_read(&self, bulletin_id: u32, username: &str) -> BbsResult<()> {
-        let mut storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
-        storage.mark_read(bulletin_id, username)
+    /// The bulletins among `ids` that the caller may read, in the order
+    /// asked for
+    pub fn get_many(
+        &self,
+        ids: &[u32],
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<Bulletin>> {
+        self.permissions.require_read(Feature::Bulletins, level)?;
+        let bulletins = lock_storage(&self.storage).get_many(ids)?;
+
+        let mut visible = Vec::with_capacity(bulletins.len());
+        for bulletin in bulletins {
+            let membership = self.clubs.membership(bulletin.area.as_deref(), username)?;
+            if self
+                .permissions
+                .area_access(Feature::Bulletins, level, membership)
+                != Access::None
+            {
+                visible.push(bulletin);
+            }
+        }
+        Ok(visible)
+    }
+
+    /// Statistics for the main board
+    pub fn get_stats(&self, reads: Option<&BulletinReadState>) -> BbsResult<BulletinStats> {
+        let storage = lock_storage(&self.storage);
+        Ok(storage.get_stats(reads))
+    }
+
+    /// Statistics for `area` (`None` for the main board)
+    pub fn get_area_stats(
+        &self,
+        reads: Option<&BulletinReadState>,
+        area: Option<&str>,
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<BulletinStats> {
+        let membership = self.clubs.membership(area, username)?;
+        self.permissions
+            .require_area_read(Feature::Bulletins, level, membership)?;
+        let storage = lock_storage(&self.storage);
+        Ok(storage.get_area_stats(reads, area))
+    }
+
+    /// Every bulletin in `area`, sticky ones first, then newest first
+    pub fn list_bulletins(
+        &self,
+        area: Option<&str>,
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<Bulletin>> {
+        let membership = self.clubs.membership(area, username)?;
+        self.permissions
+            .require_area_read(Feature::Bulletins, level, membership)?;
+        let storage = lock_storage(&self.storage);
+        storage.list_bulletins(area)
+    }
+
+    /// Bulletins posted since `since` that the caller may read, newest first
+    pub fn get_bulletins_since(
+        &self,
+        since: Timestamp,
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<Bulletin>> {
+        self.permissions.require_read(Feature::Bulletins, level)?;
+        let bulletins = lock_storage(&self.storage).get_bulletins_since(since)?;
+
+        let mut visible = Vec::with_capacity(bulletins.len());
+        for bulletin in bulletins {
+            let membership = self.clubs.membership(bulletin.area.as_deref(), username)?;
+            if self
+                .permissions
+                .area_access(Feature::Bulletins, level, membership)
+                != Access::None
+            {
+                visible.push(bulletin);
+            }
+        }
+        Ok(visible)
+    }
+
+    /// IDs above `after_id` of the bulletins the caller may read, in any area
+    pub fn readable_after(
+        &self,
+        after_id: u32,
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<BTreeSet<u32>> {
+        self.permissions.require_read(Feature::Bulletins, level)?;
+        let bulletins = lock_storage(&self.storage).get_bulletins_after(after_id)?;
+
+        let mut readable = BTreeSet::new();
+        for bulletin in bulletins {
+            let membership = self.clubs.membership(bulletin.area.as_deref(), username)?;
+            if self
+                .permissions
+                .area_access(Feature::Bulletins, level, membership)
+                != Access::None
+            {
+                readable.insert(bulletin.id);
+            }
+        }
+        Ok(readable)
     }
 
-    pub fn get_stats(&self, current_user: Option<&str>) -> BbsResult<BulletinStats> {
-        let storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
-        Ok(storage.get_stats(current_user))
+    /// The `limit` newest bulletins in `area` (`None` being the main
+    /// board), newest first
+    pub fn get_recent_bulletins(
+        &self,
+        limit: usize,
+        area: Option<&str>,
+        username: Option<&str>,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<Bulletin>> {
+        let membership = self.clubs.membership(area, username)?;
+        self.permissions
+            .require_area_read(Feature::Bulletins, level, membership)?;
+        let storage = lock_storage(&self.storage);
+        storage.get_recent_bulletins(limit, area)
     }
 }
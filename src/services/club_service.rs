@@ -0,0 +1,165 @@
+use crate::club_repository::ClubStorage;
+use crate::clubs::{Club, ClubRequest};
+use crate::config::SecurityLevel;
+use crate::errors::{BbsError, BbsResult};
+use crate::services::lock_storage;
+use crate::services::permission_service::Membership;
+use std::sync::{Arc, Mutex};
+
+/// Invite-only bulletin areas and their member lists
+///
+/// Clones share the same storage, so the bulletin service can check
+/// membership against the clubs the SysOp manages here.
+#[derive(Clone)]
+pub struct ClubService {
+    storage: Arc<Mutex<dyn ClubStorage + Send>>,
+}
+
+impl ClubService {
+    pub fn new(storage: Arc<Mutex<dyn ClubStorage + Send>>) -> Self {
+        Self { storage }
+    }
+
+    /// Start a new club; only the SysOp may, and they become its first member
+    pub fn create_club(
+        &self,
+        request: ClubRequest,
+        creator: &str,
+        level: SecurityLevel,
+    ) -> BbsResult<Club> {
+        require_sysop(level, "create clubs")?;
+
+        let mut storage = lock_storage(&self.storage);
+        if storage.load_club(&request.name)?.is_some() {
+            return Err(BbsError::InvalidInput(format!(
+                "A club named '{}' already exists",
+                request.name.trim()
+            )));
+        }
+
+        let club = request.into_club(creator)?;
+        storage.save_club(&club)?;
+        Ok(club)
+    }
+
+    pub fn get_club(&self, name: &str) -> BbsResult<Option<Club>> {
+        lock_storage(&self.storage).load_club(name)
+    }
+
+    pub fn list_clubs(&self) -> BbsResult<Vec<Club>> {
+        lock_storage(&self.storage).list_clubs()
+    }
+
+    /// Where `username` stands with `area` (`None` being the main board)
+    ///
+    /// Guests are outsiders everywhere but the main board, and so is
+    /// everyone in an area that no longer exists.
+    pub fn membership(&self, area: Option<&str>, username: Option<&str>) -> BbsResult<Membership> {
+        let Some(area) = area else {
+            return Ok(Membership::Open);
+        };
+        let Some(username) = username else {
+            return Ok(Membership::Outsider);
+        };
+        Ok(match self.get_club(area)? {
+            Some(club) if club.is_member(username) => Membership::Member,
+            _ => Membership::Outsider,
+        })
+    }
+
+    /// Join a password club
+    pub fn join_with_password(&self, name: &str, username: &str, password: &str) -> BbsResult<()> {
+        let mut storage = lock_storage(&self.storage);
+        let mut club = find(&*storage, name)?;
+
+        if !club.needs_password() {
+            return Err(BbsError::InvalidInput(format!(
+                "{} takes new members by SysOp approval",
+                club.name
+            )));
+        }
+        if !club.check_password(password)? {
+            return Err(BbsError::AuthenticationFailed(
+                "Wrong club password".to_string(),
+            ));
+        }
+
+        club.pending.remove(&username.to_lowercase());
+        club.members.insert(username.to_lowercase());
+        storage.save_club(&club)
+    }
+
+    /// Ask the SysOp to let `username` into an approval club
+    pub fn request_membership(&self, name: &str, username: &str) -> BbsResult<()> {
+        let mut storage = lock_storage(&self.storage);
+        let mut club = find(&*storage, name)?;
+
+        if club.needs_password() {
+            return Err(BbsError::InvalidInput(format!(
+                "{} needs its join password",
+                club.name
+            )));
+        }
+        if club.is_member(username) {
+            return Ok(());
+        }
+
+        club.pending.insert(username.to_lowercase());
+        storage.save_club(&club)
+    }
+
+    /// Let a pending caller in (SysOp only)
+    pub fn approve(&self, name: &str, username: &str, level: SecurityLevel) -> BbsResult<()> {
+        require_sysop(level, "approve club members")?;
+
+        let mut storage = lock_storage(&self.storage);
+        let mut club = find(&*storage, name)?;
+        if !club.pending.remove(&username.to_lowercase()) {
+            return Err(BbsError::InvalidInput(format!(
+                "{} has not asked to join {}",
+                username, club.name
+            )));
+        }
+
+        club.members.insert(username.to_lowercase());
+        storage.save_club(&club)
+    }
+
+    /// Turn down a pending request (SysOp only)
+    pub fn decline(&self, name: &str, username: &str, level: SecurityLevel) -> BbsResult<()> {
+        require_sysop(level, "decline club members")?;
+
+        let mut storage = lock_storage(&self.storage);
+        let mut club = find(&*storage, name)?;
+        if club.pending.remove(&username.to_lowercase()) {
+            storage.save_club(&club)?;
+        }
+        Ok(())
+    }
+
+    /// Give up membership
+    pub fn leave(&self, name: &str, username: &str) -> BbsResult<()> {
+        let mut storage = lock_storage(&self.storage);
+        let mut club = find(&*storage, name)?;
+        if club.members.remove(&username.to_lowercase()) {
+            storage.save_club(&club)?;
+        }
+        Ok(())
+    }
+}
+
+fn find(storage: &(dyn ClubStorage + Send), name: &str) -> BbsResult<Club> {
+    storage
+        .load_club(name)?
+        .ok_or_else(|| BbsError::InvalidInput(format!("No club named '{}'", name.trim())))
+}
+
+fn require_sysop(level: SecurityLevel, action: &str) -> BbsResult<()> {
+    if level < SecurityLevel::Sysop {
+        return Err(BbsError::PermissionDenied(format!(
+            "only the SysOp can {}",
+            action
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::services::lock_storage;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A post or message that was still being written when the call ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Draft {
+    /// A bulletin with its title entered, for the main board or a club
+    Bulletin { title: String, area: Option<String> },
+    /// A private message with its recipient and subject entered
+    Message { recipient: String, subject: String },
+}
+
+/// Unfinished drafts kept for callers who lost their connection
+///
+/// Drafts live in memory until the caller logs in again (or the server
+/// restarts); each user has at most one, the most recent.
+#[derive(Clone, Default)]
+pub struct DraftService {
+    drafts: Arc<Mutex<HashMap<String, Draft>>>,
+}
+
+impl DraftService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `draft` for `username`, replacing any older one
+    pub fn save(&self, username: &str, draft: Draft) {
+        lock_storage(&self.drafts).insert(username.to_lowercase(), draft);
+    }
+
+    /// Hand back `username`'s draft, if any, and forget it
+    pub fn take(&self, username: &str) -> Option<Draft> {
+        lock_storage(&self.drafts).remove(&username.to_lowercase())
+    }
+}
@@ -0,0 +1,291 @@
+//! Archive bulletins as mail, in mbox or Maildir format
+//!
+//! Each run only writes bulletins with an ID above the last one exported
+//! to the same destination, which is remembered in a small state file
+//! beside it (`<path>.moonbase-export` for mbox, `.moonbase-export`
+//! inside a Maildir). Private messages are never exported.
+
+use crate::bulletin_repository::BulletinStorage;
+use crate::bulletins::Bulletin;
+use crate::config::SecurityLevel;
+use crate::errors::{BbsError, BbsResult};
+use crate::services::lock_storage;
+use crate::services::permission_service::{Access, Feature, Membership, PermissionService};
+
+use jiff::fmt::rfc2822::DateTimePrinter;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Mail domain for exported addresses and Message-IDs; `.invalid` so
+/// nothing tries to deliver to it
+pub const EXPORT_DOMAIN: &str = "moonbase.invalid";
+
+/// Name of the file recording the last exported bulletin ID
+const STATE_FILE: &str = ".moonbase-export";
+
+/// Archive layout to write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One file, messages appended (mboxrd quoting)
+    Mbox,
+    /// A directory with one file per message
+    Maildir,
+}
+
+impl FromStr for ExportFormat {
+    type Err = BbsError;
+
+    fn from_str(s: &str) -> BbsResult<ExportFormat> {
+        match s.to_lowercase().as_str() {
+            "mbox" => Ok(ExportFormat::Mbox),
+            "maildir" => Ok(ExportFormat::Maildir),
+            _ => Err(BbsError::InvalidInput(format!(
+                "Unknown export format '{}' (expected mbox or maildir)",
+                s
+            ))),
+        }
+    }
+}
+
+/// What an export run wrote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportReport {
+    /// Bulletins written this run
+    pub exported: usize,
+    /// Highest bulletin ID exported so far, this run or earlier
+    pub last_id: u32,
+}
+
+pub struct ExportService {
+    storage: Arc<Mutex<dyn BulletinStorage + Send>>,
+    permissions: Arc<PermissionService>,
+}
+
+impl ExportService {
+    pub fn new(
+        storage: Arc<Mutex<dyn BulletinStorage + Send>>,
+        permissions: Arc<PermissionService>,
+    ) -> Self {
+        Self {
+            storage,
+            permissions,
+        }
+    }
+
+    /// Export bulletins not yet written to `dest`
+    pub fn export(
+        &self,
+        format: ExportFormat,
+        dest: &Path,
+        level: SecurityLevel,
+    ) -> BbsResult<ExportReport> {
+        self.permissions.require_read(Feature::Bulletins, level)?;
+
+        let state_path = state_path(format, dest);
+        let last_id = read_last_id(&state_path)?;
+
+        let mut bulletins = {
+            let storage = lock_storage(&self.storage);
+            storage.get_bulletins_after(last_id)?
+        };
+
+        let Some(newest) = bulletins.last().map(|b| b.id) else {
+            return Ok(ExportReport {
+                exported: 0,
+                last_id,
+            });
+        };
+
+        // Club posts stay out of the archive unless the SysOp is exporting
+        let clubs_visible =
+            self.permissions
+                .area_access(Feature::Bulletins, level, Membership::Outsider)
+                != Access::None;
+        bulletins.retain(|b| b.area.is_none() || clubs_visible);
+
+        match format {
+            ExportFormat::Mbox => write_mbox(dest, &bulletins)?,
+            ExportFormat::Maildir => write_maildir(dest, &bulletins)?,
+        }
+        // Only after the messages are safely written
+        write_last_id(&state_path, newest)?;
+
+        Ok(ExportReport {
+            exported: bulletins.len(),
+            last_id: newest,
+        })
+    }
+}
+
+/// Render a bulletin as an RFC 5322 message with LF line endings
+pub fn format_message(bulletin: &Bulletin) -> String {
+    let date = DateTimePrinter::new()
+        .timestamp_to_string(&bulletin.posted_at)
+        .unwrap_or_default();
+    // Bulletin bodies store line breaks as a literal "\n"
+    let mut body = bulletin.content.replace("\\n", "\n");
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+
+    format!(
+        "From: {} <{}>\n\
+         Subject: {}\n\
+         Date: {}\n\
+         Message-ID: <bulletin-{}@{}>\n\
+         X-Moonbase-Bulletin-Id: {}\n\
+         MIME-Version: 1.0\n\
+         Content-Type: text/plain; charset=utf-8\n\
+         Content-Transfer-Encoding: 8bit\n\
+         \n\
+         {}",
+        encode_header(bulletin.byline()),
+        address(&bulletin.author),
+        encode_header(&bulletin.title),
+        date,
+        bulletin.id,
+        EXPORT_DOMAIN,
+        bulletin.id,
+        body
+    )
+}
+
+fn write_mbox(dest: &Path, bulletins: &[Bulletin]) -> BbsResult<()> {
+    let mut mbox = String::new();
+    for bulletin in bulletins {
+        let posted = bulletin.posted_at.strftime("%a %b %e %H:%M:%S %Y");
+        mbox.push_str(&format!("From {} {}\n", address(&bulletin.author), posted));
+        for line in format_message(bulletin).lines() {
+            // mboxrd: quote any line that would read as a separator
+            if line.trim_start_matches('>').starts_with("From ") {
+                mbox.push('>');
+            }
+            mbox.push_str(line);
+            mbox.push('\n');
+        }
+        mbox.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dest)
+        .map_err(|e| io_error("open", dest, e))?;
+    file.write_all(mbox.as_bytes())
+        .map_err(|e| io_error("write", dest, e))
+}
+
+fn write_maildir(dest: &Path, bulletins: &[Bulletin]) -> BbsResult<()> {
+    for dir in ["tmp", "new", "cur"] {
+        let path = dest.join(dir);
+        fs::create_dir_all(&path).map_err(|e| io_error("create", &path, e))?;
+    }
+
+    for bulletin in bulletins {
+        // Stable names, so a rerun after a crash overwrites rather than duplicates
+        let name = format!(
+            "{}.B{}.moonbase",
+            bulletin.posted_at.as_second(),
+            bulletin.id
+        );
+        let tmp = dest.join("tmp").join(&name);
+        let new = dest.join("new").join(&name);
+        fs::write(&tmp, format_message(bulletin)).map_err(|e| io_error("write", &tmp, e))?;
+        fs::rename(&tmp, &new).map_err(|e| io_error("move", &new, e))?;
+    }
+    Ok(())
+}
+
+fn state_path(format: ExportFormat, dest: &Path) -> PathBuf {
+    match format {
+        ExportFormat::Mbox => {
+            let mut name = dest.as_os_str().to_owned();
+            name.push(STATE_FILE);
+            PathBuf::from(name)
+        }
+        ExportFormat::Maildir => dest.join(STATE_FILE),
+    }
+}
+
+fn read_last_id(path: &Path) -> BbsResult<u32> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let content = fs::read_to_string(path).map_err(|e| io_error("read", path, e))?;
+    content.trim().parse().map_err(|_| {
+        BbsError::Configuration(format!(
+            "{} does not hold a bulletin ID; delete it for a full export",
+            path.display()
+        ))
+    })
+}
+
+fn write_last_id(path: &Path, id: u32) -> BbsResult<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, format!("{}\n", id)).map_err(|e| io_error("write", &tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| io_error("write", path, e))
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> BbsError {
+    BbsError::Configuration(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+/// Mail address for a BBS user; usernames may hold non-ASCII letters,
+/// which don't belong in an address
+fn address(username: &str) -> String {
+    let local: String = username
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect::<String>()
+        .to_lowercase();
+    let local = if local.is_empty() { "user" } else { &local };
+    format!("{}@{}", local, EXPORT_DOMAIN)
+}
+
+/// Header text as-is when plain ASCII, otherwise as RFC 2047 encoded words
+fn encode_header(text: &str) -> String {
+    // Control characters (ANSI colors and the like) have no place in a header
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    if text.is_ascii() {
+        return text;
+    }
+
+    // Keep each encoded word within the 75 character limit
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in text.chars() {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(encoded_word(&chunk));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    words.push(encoded_word(&chunk));
+    words.join("\n ")
+}
+
+fn encoded_word(text: &str) -> String {
+    format!("=?UTF-8?B?{}?=", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = (group[0] as u32) << 16
+            | (*group.get(1).unwrap_or(&0) as u32) << 8
+            | *group.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
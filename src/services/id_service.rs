@@ -0,0 +1,119 @@
+use crate::errors::{BbsError, BbsResult};
+use crate::services::lock_storage;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Record types that draw IDs from the allocator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IdKind {
+    Bulletin,
+    Message,
+}
+
+impl IdKind {
+    fn key(self) -> &'static str {
+        match self {
+            IdKind::Bulletin => "bulletins",
+            IdKind::Message => "messages",
+        }
+    }
+}
+
+struct IdCounters {
+    ids_file: PathBuf,
+    // Last ID handed out per kind
+    last: BTreeMap<String, u32>,
+}
+
+/// Persistent ID allocator shared by all repositories
+///
+/// Counters live in `ids.json` and only ever move forward, so an ID is never
+/// reused even after the record holding it is purged. The counter is written
+/// to disk before the ID is returned. Clones share the same counters; create
+/// one per data directory and hand it to every repository.
+#[derive(Clone)]
+pub struct IdService {
+    counters: Arc<Mutex<IdCounters>>,
+}
+
+impl IdService {
+    pub fn new<P: AsRef<Path>>(data_dir: P) -> BbsResult<Self> {
+        let data_dir = data_dir.as_ref();
+
+        if !data_dir.exists() {
+            fs::create_dir_all(data_dir).map_err(|e| {
+                BbsError::Configuration(format!("Failed to create data directory: {}", e))
+            })?;
+        }
+
+        let ids_file = data_dir.join("ids.json");
+        let last = if ids_file.exists() {
+            let content = fs::read_to_string(&ids_file)
+                .map_err(|e| BbsError::Configuration(format!("Failed to read ids file: {}", e)))?;
+            if content.trim().is_empty() {
+                BTreeMap::new()
+            } else {
+                serde_json::from_str(&content).map_err(|e| {
+                    BbsError::Configuration(format!("Failed to parse ids file: {}", e))
+                })?
+            }
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            counters: Arc::new(Mutex::new(IdCounters { ids_file, last })),
+        })
+    }
+
+    /// Allocate the next ID for `kind`
+    pub fn next_id(&self, kind: IdKind) -> BbsResult<u32> {
+        let mut counters = self.lock();
+        let id = counters.last.get(kind.key()).copied().unwrap_or(0) + 1;
+        counters.last.insert(kind.key().to_string(), id);
+        counters.save()?;
+        Ok(id)
+    }
+
+    /// Make sure `id` and everything below it will never be allocated
+    ///
+    /// Repositories call this with the highest ID they load so data written
+    /// before the allocator existed is respected.
+    pub fn reserve_through(&self, kind: IdKind, id: u32) -> BbsResult<()> {
+        let mut counters = self.lock();
+        let last = counters.last.get(kind.key()).copied().unwrap_or(0);
+        if id > last {
+            counters.last.insert(kind.key().to_string(), id);
+            counters.save()?;
+        }
+        Ok(())
+    }
+
+    /// Highest ID handed out (or reserved) so far for `kind`
+    pub fn last_id(&self, kind: IdKind) -> BbsResult<u32> {
+        Ok(self.lock().last.get(kind.key()).copied().unwrap_or(0))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, IdCounters> {
+        lock_storage(&self.counters)
+    }
+}
+
+impl IdCounters {
+    /// Write via a temp file and rename so a crash never leaves a truncated counter
+    fn save(&self) -> BbsResult<()> {
+        let content = serde_json::to_string_pretty(&self.last)
+            .map_err(|e| BbsError::Configuration(format!("Failed to serialize ids: {}", e)))?;
+
+        let tmp_file = self.ids_file.with_extension("json.tmp");
+        fs::write(&tmp_file, content)
+            .map_err(|e| BbsError::Configuration(format!("Failed to write ids file: {}", e)))?;
+        fs::rename(&tmp_file, &self.ids_file)
+            .map_err(|e| BbsError::Configuration(format!("Failed to write ids file: {}", e)))?;
+
+        Ok(())
+    }
+}
@@ -1,62 +1,263 @@
+use crate::config::SecurityLevel;
 use crate::errors::BbsResult;
+use crate::events::{BbsEvent, EventBus};
 use crate::message_repository::MessageStorage;
 use crate::messages::{MessageRequest, MessageStats, PrivateMessage};
 
+use crate::services::lock_storage;
+use crate::services::permission_service::{Feature, PermissionService};
+use crate::user_repository::UserStorage;
+use crate::users::User;
+
+use jiff::Timestamp;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A sender gets at most one vacation reply from the same user in this long
+pub const AUTO_REPLY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest chain of forwards a single message will follow
+pub const MAX_FORWARDS: usize = 4;
 
 pub struct MessageService {
     storage: Arc<Mutex<dyn MessageStorage + Send>>,
+    permissions: Arc<PermissionService>,
+    events: EventBus,
+    /// Where forwarding and vacation reply settings are read from
+    users: Option<Arc<Mutex<dyn UserStorage + Send>>>,
+    /// When each (replier, sender) pair last had a vacation reply
+    auto_replies: Mutex<HashMap<(String, String), Timestamp>>,
 }
 
 impl MessageService {
-    pub fn new(storage: Arc<Mutex<dyn MessageStorage + Send>>) -> Self {
-        Self { storage }
+    pub fn new(
+        storage: Arc<Mutex<dyn MessageStorage + Send>>,
+        permissions: Arc<PermissionService>,
+        events: EventBus,
+    ) -> Self {
+        Self {
+            storage,
+            permissions,
+            events,
+            users: None,
+            auto_replies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Apply recipients' forwarding and vacation reply settings from `users`
+    pub fn with_users(mut self, users: Arc<Mutex<dyn UserStorage + Send>>) -> Self {
+        self.users = Some(users);
+        self
     }
 
+    /// Deliver a message and announce it on the event bus
+    ///
+    /// Once the message is delivered, copies go down the recipient's chain
+    /// of forwards and the recipient's vacation reply, if any, goes back to
+    /// the sender. Those are best effort: a failure is logged but doesn't
+    /// undo the delivery.
     pub fn send_message(
         &self,
         request: MessageRequest,
         config: &crate::config::BbsConfig,
+        level: SecurityLevel,
+    ) -> BbsResult<u32> {
+        self.permissions.require_write(Feature::Messages, level)?;
+        let message_id = self.deliver(&request, config)?;
+
+        if !request.automatic {
+            for follow_up in self.follow_ups(&request) {
+                if let Err(e) = self.deliver(&follow_up, config) {
+                    eprintln!(
+                        "! Could not send {} to {}: {}",
+                        follow_up.subject, follow_up.recipient, e
+                    );
+                }
+            }
+        }
+        Ok(message_id)
+    }
+
+    fn deliver(
+        &self,
+        request: &MessageRequest,
+        config: &crate::config::BbsConfig,
     ) -> BbsResult<u32> {
-        let mut storage = self.storage.lock().unwrap();
-        storage.send_message(&request, config)
+        let message_id = lock_storage(&self.storage).send_message(request, config)?;
+        self.events.publish(BbsEvent::NewMessage {
+            to: request.recipient.clone(),
+            from: request.sender.clone(),
+            message_id,
+        });
+        Ok(message_id)
+    }
+
+    /// Forwarded copies and the vacation reply owed for `request`
+    ///
+    /// Forwards are followed from user to user, but never back to someone
+    /// who already has the message and never more than [`MAX_FORWARDS`]
+    /// deep. Vacation replies only go to registered users, at most once per
+    /// [`AUTO_REPLY_INTERVAL`] each.
+    fn follow_ups(&self, request: &MessageRequest) -> Vec<MessageRequest> {
+        let Some(users) = &self.users else {
+            return Vec::new();
+        };
+        let storage = lock_storage(users);
+        let load = |username: &str| storage.load_user(username).ok().flatten();
+        let Some(recipient) = load(&request.recipient) else {
+            return Vec::new();
+        };
+        let original_sender = request.sender_display.as_deref().unwrap_or(&request.sender);
+
+        let mut follow_ups = Vec::new();
+        let mut has_copy = vec![request.sender.clone(), request.recipient.clone()];
+        let mut forwarder = recipient.clone();
+        while follow_ups.len() < MAX_FORWARDS
+            && let Some(target) = forwarder.forward_to.clone()
+            && !has_copy.contains(&target)
+            && let Some(next) = load(&target)
+        {
+            follow_ups.push(
+                MessageRequest::new(
+                    target.clone(),
+                    prefixed_subject("Fwd: ", &request.subject),
+                    format!(
+                        "Forwarded from {} to {}:\\n\\n{}",
+                        original_sender, request.recipient, request.content
+                    ),
+                    forwarder.username.clone(),
+                )
+                .with_sender_display(forwarder.display_name.clone())
+                .sent_automatically(),
+            );
+            has_copy.push(target);
+            forwarder = next;
+        }
+
+        if let Some(reply) = &recipient.auto_reply
+            && load(&request.sender).is_some()
+            && self.auto_reply_due(&recipient, &request.sender)
+        {
+            follow_ups.push(
+                MessageRequest::new(
+                    request.sender.clone(),
+                    prefixed_subject("Auto-reply: ", &request.subject),
+                    reply.clone(),
+                    recipient.username.clone(),
+                )
+                .with_sender_display(recipient.display_name.clone())
+                .sent_automatically(),
+            );
+        }
+        follow_ups
     }
 
-    pub fn get_inbox(&self, username: &str) -> BbsResult<Vec<PrivateMessage>> {
-        let storage = self.storage.lock().unwrap();
+    /// Whether `replier` may send `sender` a vacation reply now, recording
+    /// it if so
+    fn auto_reply_due(&self, replier: &User, sender: &str) -> bool {
+        let now = Timestamp::now();
+        let mut sent = lock_storage(&self.auto_replies);
+        let key = (replier.username.clone(), sender.to_string());
+        if let Some(last) = sent.get(&key)
+            && now.duration_since(*last).unsigned_abs() < AUTO_REPLY_INTERVAL
+        {
+            return false;
+        }
+        sent.insert(key, now);
+        true
+    }
+
+    pub fn get_inbox(
+        &self,
+        username: &str,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<PrivateMessage>> {
+        self.permissions.require_read(Feature::Messages, level)?;
+        let storage = lock_storage(&self.storage);
         storage.get_inbox(username)
     }
 
-    pub fn get_sent(&self, username: &str) -> BbsResult<Vec<PrivateMessage>> {
-        let storage = self.storage.lock().unwrap();
+    /// Inbox messages that arrived since `since`, newest first
+    pub fn get_inbox_since(
+        &self,
+        username: &str,
+        since: Timestamp,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<PrivateMessage>> {
+        self.permissions.require_read(Feature::Messages, level)?;
+        let storage = lock_storage(&self.storage);
+        storage.get_inbox_since(username, since)
+    }
+
+    pub fn get_sent(&self, username: &str, level: SecurityLevel) -> BbsResult<Vec<PrivateMessage>> {
+        self.permissions.require_read(Feature::Messages, level)?;
+        let storage = lock_storage(&self.storage);
         storage.get_sent(username)
     }
 
-    pub fn read_message(&self, id: u32, username: &str) -> BbsResult<Option<PrivateMessage>> {
-        let mut storage = self.storage.lock().unwrap();
+    pub fn read_message(
+        &self,
+        id: u32,
+        username: &str,
+        level: SecurityLevel,
+    ) -> BbsResult<Option<PrivateMessage>> {
+        self.permissions.require_read(Feature::Messages, level)?;
+        let mut storage = lock_storage(&self.storage);
 
         // Get the message first
         let message = storage.get_message(id, username)?;
 
         // If it exists and the user is the recipient, mark it as read
-        if let Some(ref msg) = message {
-            if msg.recipient == username && msg.is_unread() {
-                storage.mark_read(id, username)?;
-                // Return the updated message
-                return storage.get_message(id, username);
-            }
+        if let Some(ref msg) = message
+            && msg.recipient == username
+            && msg.is_unread()
+        {
+            storage.mark_read(id, username)?;
+            // Return the updated message
+            return storage.get_message(id, username);
         }
 
         Ok(message)
     }
 
     pub fn delete_message(&self, id: u32, username: &str) -> BbsResult<()> {
-        let mut storage = self.storage.lock().unwrap();
+        let mut storage = lock_storage(&self.storage);
         storage.delete_message(id, username)
     }
 
+    /// The messages among `ids` that `username` can see, in the order asked for
+    pub fn get_many(
+        &self,
+        ids: &[u32],
+        username: &str,
+        level: SecurityLevel,
+    ) -> BbsResult<Vec<PrivateMessage>> {
+        self.permissions.require_read(Feature::Messages, level)?;
+        let storage = lock_storage(&self.storage);
+        storage.get_many(ids, username)
+    }
+
+    /// Mark the unread messages among `ids` as read, returning how many changed
+    pub fn mark_read_many(
+        &self,
+        ids: &[u32],
+        username: &str,
+        level: SecurityLevel,
+    ) -> BbsResult<usize> {
+        self.permissions.require_read(Feature::Messages, level)?;
+        let mut storage = lock_storage(&self.storage);
+        storage.mark_read_many(ids, username)
+    }
+
+    /// Delete the messages among `ids`, returning how many went
+    pub fn delete_many(&self, ids: &[u32], username: &str) -> BbsResult<usize> {
+        let mut storage = lock_storage(&self.storage);
+        storage.delete_many(ids, username)
+    }
+
     pub fn get_stats(&self, username: &str) -> BbsResult<MessageStats> {
-        let storage = self.storage.lock().unwrap();
+        let storage = lock_storage(&self.storage);
         Ok(storage.get_stats(username))
     }
 
@@ -65,3 +266,16 @@ impl MessageService {
         Ok(stats.unread_count)
     }
 }
+
+/// `subject` with `prefix` added once, cut to fit the 100 character limit
+fn prefixed_subject(prefix: &str, subject: &str) -> String {
+    let mut prefixed = if subject.starts_with(prefix) {
+        subject.to_string()
+    } else {
+        format!("{}{}", prefix, subject)
+    };
+    while prefixed.len() > 100 {
+        prefixed.pop();
+    }
+    prefixed
+}
@@ -0,0 +1,236 @@
+use crate::services::lock_storage;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use telnet_negotiation::ConnectionStats;
+
+/// Where one pass of a session's menu loop spent its time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoopTiming {
+    /// Building and sending the menu screen
+    pub render: Duration,
+    /// Refreshing stats and handling the choice, less any waiting on the caller
+    pub storage: Duration,
+    /// Waiting for the caller to type something
+    pub input_wait: Duration,
+}
+
+/// Running totals for one menu across every session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MenuTimings {
+    pub iterations: u64,
+    /// Renders that took longer than the slow-render threshold
+    pub slow_renders: u64,
+    pub render_total: Duration,
+    pub render_max: Duration,
+    pub storage_total: Duration,
+    pub storage_max: Duration,
+    pub input_wait_total: Duration,
+}
+
+impl MenuTimings {
+    fn record(&mut self, timing: &LoopTiming, slow: bool) {
+        self.iterations += 1;
+        if slow {
+            self.slow_renders += 1;
+        }
+        self.render_total += timing.render;
+        self.render_max = self.render_max.max(timing.render);
+        self.storage_total += timing.storage;
+        self.storage_max = self.storage_max.max(timing.storage);
+        self.input_wait_total += timing.input_wait;
+    }
+}
+
+/// Metric name, type, help text and value of each exported family
+type Family = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&MenuTimings) -> String,
+);
+
+const FAMILIES: [Family; 7] = [
+    (
+        "moonbase_menu_iterations_total",
+        "counter",
+        "Passes through the session menu loop",
+        |t| t.iterations.to_string(),
+    ),
+    (
+        "moonbase_menu_slow_renders_total",
+        "counter",
+        "Menu renders slower than the slow-render threshold",
+        |t| t.slow_renders.to_string(),
+    ),
+    (
+        "moonbase_menu_render_seconds_total",
+        "counter",
+        "Time spent rendering and sending menus",
+        |t| seconds(t.render_total),
+    ),
+    (
+        "moonbase_menu_render_seconds_max",
+        "gauge",
+        "Slowest single menu render",
+        |t| seconds(t.render_max),
+    ),
+    (
+        "moonbase_menu_storage_seconds_total",
+        "counter",
+        "Time spent loading stats and handling menu choices",
+        |t| seconds(t.storage_total),
+    ),
+    (
+        "moonbase_menu_storage_seconds_max",
+        "gauge",
+        "Slowest single menu choice",
+        |t| seconds(t.storage_max),
+    ),
+    (
+        "moonbase_menu_input_wait_seconds_total",
+        "counter",
+        "Time spent waiting for callers to answer a menu",
+        |t| seconds(t.input_wait_total),
+    ),
+];
+
+/// Name, type, help text and value of each telnet protocol family
+type TelnetFamily = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&ConnectionStats) -> u64,
+);
+
+const TELNET_FAMILIES: [TelnetFamily; 9] = [
+    (
+        "moonbase_telnet_bytes_received_total",
+        "counter",
+        "Bytes received from callers, telnet sequences included",
+        |s| s.bytes_received,
+    ),
+    (
+        "moonbase_telnet_bytes_sent_total",
+        "counter",
+        "Bytes sent to callers, telnet sequences included",
+        |s| s.bytes_sent,
+    ),
+    (
+        "moonbase_telnet_sequences_received_total",
+        "counter",
+        "Telnet commands, negotiations and sub-negotiations received",
+        |s| s.sequences_received,
+    ),
+    (
+        "moonbase_telnet_negotiations_received_total",
+        "counter",
+        "WILL, WONT, DO and DONT received",
+        |s| s.negotiations_received,
+    ),
+    (
+        "moonbase_telnet_negotiations_sent_total",
+        "counter",
+        "WILL, WONT, DO and DONT sent",
+        |s| s.negotiations_sent,
+    ),
+    (
+        "moonbase_telnet_subnegotiations_received_total",
+        "counter",
+        "Sub-negotiations received",
+        |s| s.subnegotiations_received,
+    ),
+    (
+        "moonbase_telnet_subnegotiations_sent_total",
+        "counter",
+        "Sub-negotiations sent",
+        |s| s.subnegotiations_sent,
+    ),
+    (
+        "moonbase_telnet_subnegotiation_bytes_received_total",
+        "counter",
+        "Payload bytes of the sub-negotiations received",
+        |s| s.subnegotiation_bytes_received,
+    ),
+    (
+        "moonbase_telnet_subnegotiation_bytes_max",
+        "gauge",
+        "Largest sub-negotiation payload received",
+        |s| s.largest_subnegotiation as u64,
+    ),
+];
+
+/// Menu timings and telnet counters gathered from every session, for the
+/// metrics endpoint
+///
+/// Sessions report each pass of their menu loop and, every few seconds,
+/// the telnet traffic they have seen; the totals live until the board
+/// restarts. Clones share the same totals.
+#[derive(Clone, Default)]
+pub struct MetricsService {
+    menus: Arc<Mutex<BTreeMap<String, MenuTimings>>>,
+    telnet: Arc<Mutex<ConnectionStats>>,
+}
+
+impl MetricsService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one pass through `menu`, `slow` if its render went over the threshold
+    pub fn record(&self, menu: &str, timing: &LoopTiming, slow: bool) {
+        lock_storage(&self.menus)
+            .entry(menu.to_string())
+            .or_default()
+            .record(timing, slow);
+    }
+
+    /// Add telnet counters a session has gained since it last reported
+    pub fn record_telnet(&self, added: &ConnectionStats) {
+        *lock_storage(&self.telnet) += *added;
+    }
+
+    /// Telnet counters across every session so far
+    pub fn telnet(&self) -> ConnectionStats {
+        *lock_storage(&self.telnet)
+    }
+
+    /// Totals for every menu shown so far, by name
+    pub fn menus(&self) -> Vec<(String, MenuTimings)> {
+        lock_storage(&self.menus)
+            .iter()
+            .map(|(name, timings)| (name.clone(), *timings))
+            .collect()
+    }
+
+    /// All totals in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let menus = self.menus();
+        let mut out = String::new();
+
+        for (name, kind, help, value) in FAMILIES {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (menu, timings) in &menus {
+                let _ = writeln!(out, "{}{{menu=\"{}\"}} {}", name, menu, value(timings));
+            }
+        }
+
+        // Left out until a session has reported
+        let telnet = self.telnet();
+        if telnet != ConnectionStats::default() {
+            for (name, kind, help, value) in TELNET_FAMILIES {
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+                let _ = writeln!(out, "# TYPE {} {}", name, kind);
+                let _ = writeln!(out, "{} {}", name, value(&telnet));
+            }
+        }
+        out
+    }
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.6}", duration.as_secs_f64())
+}
@@ -1,30 +1,117 @@
 pub mod bulletin_service;
+pub mod club_service;
+pub mod draft_service;
+pub mod export_service;
+pub mod id_service;
 pub mod message_service;
+pub mod metrics_service;
+pub mod permission_service;
+pub mod presence_service;
+pub mod stats_service;
 pub mod user_service;
 
 pub use bulletin_service::BulletinService;
+pub use club_service::ClubService;
+pub use draft_service::{Draft, DraftService};
+pub use export_service::{ExportFormat, ExportService};
+pub use id_service::IdService;
 pub use message_service::MessageService;
+pub use metrics_service::{LoopTiming, MetricsService};
+pub use permission_service::{Access, Feature, PermissionService};
+pub use presence_service::PresenceService;
+pub use stats_service::{BoardStats, StatsService};
 pub use user_service::UserService;
 
-use std::sync::{Arc, Mutex};
+use crate::events::EventBus;
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Lock shared state, even if a session's thread panicked while holding it
+///
+/// A caller who vanishes mid-operation shouldn't lock every other session
+/// out of the data. Repositories save whole files after each change, so at
+/// worst the dead session's own change is lost.
+pub(crate) fn lock_storage<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
 
 // Container for all services
 pub struct CoreServices {
     pub users: UserService,
     pub bulletins: BulletinService,
+    /// Invite-only bulletin areas
+    pub clubs: ClubService,
     pub messages: MessageService,
+    /// Bulletin archives in mbox or Maildir format
+    pub export: ExportService,
+    pub permissions: Arc<PermissionService>,
+    /// Cross-session events: logins, new mail, chat and so on
+    pub events: EventBus,
+    /// Who is connected right now
+    pub presence: PresenceService,
+    /// Cached activity figures for the Board Statistics screen
+    pub stats: StatsService,
+    /// Unfinished posts saved when a caller drops carrier
+    pub drafts: DraftService,
+    /// Menu loop timings for the metrics endpoint
+    pub metrics: MetricsService,
+    /// SysOp scripts, if scripting is enabled
+    #[cfg(feature = "scripting")]
+    pub scripts: Option<Arc<crate::scripting::ScriptHost>>,
 }
 
 impl CoreServices {
     pub fn new(
+        features: &crate::config::FeatureConfig,
         user_storage: Arc<Mutex<dyn crate::user_repository::UserStorage + Send>>,
         bulletin_storage: Arc<Mutex<dyn crate::bulletin_repository::BulletinStorage + Send>>,
         message_storage: Arc<Mutex<dyn crate::message_repository::MessageStorage + Send>>,
+        club_storage: Arc<Mutex<dyn crate::club_repository::ClubStorage + Send>>,
     ) -> Self {
+        let permissions = Arc::new(PermissionService::new(features.clone()));
+        let events = EventBus::default();
+        let clubs = ClubService::new(club_storage);
+        let stats = StatsService::new(
+            Arc::clone(&bulletin_storage),
+            Arc::clone(&user_storage),
+            Arc::clone(&permissions),
+            clubs.clone(),
+        );
+
         Self {
-            users: UserService::new(user_storage),
-            bulletins: BulletinService::new(bulletin_storage),
-            messages: MessageService::new(message_storage),
+            users: UserService::new(Arc::clone(&user_storage))
+                .with_rules(features.usernames.clone()),
+            export: ExportService::new(Arc::clone(&bulletin_storage), Arc::clone(&permissions)),
+            bulletins: BulletinService::new(
+                bulletin_storage,
+                Arc::clone(&permissions),
+                clubs.clone(),
+            ),
+            clubs,
+            messages: MessageService::new(
+                message_storage,
+                Arc::clone(&permissions),
+                events.clone(),
+            )
+            .with_users(Arc::clone(&user_storage)),
+            permissions,
+            events,
+            presence: PresenceService::new(),
+            stats,
+            drafts: DraftService::new(),
+            metrics: MetricsService::new(),
+            #[cfg(feature = "scripting")]
+            scripts: None,
         }
     }
+
+    /// Attach loaded SysOp scripts
+    #[cfg(feature = "scripting")]
+    pub fn with_scripts(mut self, scripts: crate::scripting::ScriptHost) -> Self {
+        self.scripts = Some(Arc::new(scripts));
+        self
+    }
 }
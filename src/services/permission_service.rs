@@ -0,0 +1,158 @@
+use crate::config::{FeatureAccess, FeatureConfig, SecurityLevel};
+use crate::errors::{BbsError, BbsResult};
+
+/// Features whose access is configurable per security level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Bulletins,
+    Files,
+    Messages,
+}
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Bulletins => "Bulletin Board",
+            Feature::Files => "File Library",
+            Feature::Messages => "Private Messages",
+        }
+    }
+}
+
+/// What a caller may do with a feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    None,
+    Read,
+    Full,
+}
+
+/// Where a caller stands with the area something lives in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Membership {
+    /// Not in a club: the main board, open to everyone
+    Open,
+    /// In a club the caller belongs to
+    Member,
+    /// In a club the caller doesn't belong to
+    Outsider,
+}
+
+/// Central check for feature access, consulted by menus and services
+pub struct PermissionService {
+    features: FeatureConfig,
+}
+
+impl PermissionService {
+    pub fn new(features: FeatureConfig) -> Self {
+        Self { features }
+    }
+
+    /// Configured access setting for a feature
+    pub fn setting(&self, feature: Feature) -> FeatureAccess {
+        match feature {
+            Feature::Bulletins => self.features.bulletins,
+            Feature::Files => self.features.files,
+            Feature::Messages => self.features.messages,
+        }
+    }
+
+    /// Access a caller at `level` has to `feature`
+    pub fn access(&self, feature: Feature, level: SecurityLevel) -> Access {
+        match self.setting(feature) {
+            FeatureAccess::Off => Access::None,
+            FeatureAccess::ReadOnly => Access::Read,
+            FeatureAccess::Gated { write, .. } if level >= write => Access::Full,
+            FeatureAccess::Gated { read, .. } if level >= read => Access::Read,
+            FeatureAccess::Gated { .. } => Access::None,
+        }
+    }
+
+    /// Access to `feature` inside an area, given the caller's membership
+    ///
+    /// Members get their usual access; outsiders get none. The SysOp may
+    /// look inside every club.
+    pub fn area_access(
+        &self,
+        feature: Feature,
+        level: SecurityLevel,
+        membership: Membership,
+    ) -> Access {
+        match membership {
+            Membership::Outsider if level < SecurityLevel::Sysop => Access::None,
+            _ => self.access(feature, level),
+        }
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.setting(feature) != FeatureAccess::Off
+    }
+
+    /// Error unless a caller at `level` may read `feature`
+    pub fn require_read(&self, feature: Feature, level: SecurityLevel) -> BbsResult<()> {
+        self.require(feature, level, Access::Read)
+    }
+
+    /// Error unless a caller at `level` may post to `feature`
+    pub fn require_write(&self, feature: Feature, level: SecurityLevel) -> BbsResult<()> {
+        self.require(feature, level, Access::Full)
+    }
+
+    /// Error unless a caller at `level` may read `feature` in an area
+    pub fn require_area_read(
+        &self,
+        feature: Feature,
+        level: SecurityLevel,
+        membership: Membership,
+    ) -> BbsResult<()> {
+        self.require_area(feature, level, membership, Access::Read)
+    }
+
+    /// Error unless a caller at `level` may post to `feature` in an area
+    pub fn require_area_write(
+        &self,
+        feature: Feature,
+        level: SecurityLevel,
+        membership: Membership,
+    ) -> BbsResult<()> {
+        self.require_area(feature, level, membership, Access::Full)
+    }
+
+    fn require_area(
+        &self,
+        feature: Feature,
+        level: SecurityLevel,
+        membership: Membership,
+        needed: Access,
+    ) -> BbsResult<()> {
+        self.require(feature, level, needed)?;
+        if self.area_access(feature, level, membership) < needed {
+            return Err(BbsError::PermissionDenied(
+                "that area is for club members only".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn require(&self, feature: Feature, level: SecurityLevel, needed: Access) -> BbsResult<()> {
+        if !self.is_enabled(feature) {
+            return Err(BbsError::FeatureDisabled(feature.name().to_string()));
+        }
+
+        if self.access(feature, level) < needed {
+            let verb = if needed == Access::Full {
+                "post to"
+            } else {
+                "use"
+            };
+            return Err(BbsError::PermissionDenied(format!(
+                "{} access cannot {} {}",
+                level,
+                verb,
+                feature.name()
+            )));
+        }
+
+        Ok(())
+    }
+}
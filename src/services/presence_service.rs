@@ -0,0 +1,89 @@
+use crate::services::lock_storage;
+
+use jiff::Timestamp;
+use std::sync::{Arc, Mutex};
+
+/// A connected session, logged in or not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnlineSession {
+    pub id: u64,
+    /// Remote address the call came from
+    pub peer: String,
+    /// Who is logged in, if anyone yet
+    pub username: Option<String>,
+    pub connected_at: Timestamp,
+    /// Where the caller's client says they are (TELNET SEND-LOCATION)
+    pub location: Option<String>,
+}
+
+#[derive(Default)]
+struct PresenceState {
+    next_id: u64,
+    sessions: Vec<OnlineSession>,
+}
+
+/// Registry of who is connected right now
+///
+/// Sessions add themselves when the call comes in and remove themselves
+/// when it ends, hang-ups and timeouts included. Clones share the same
+/// registry.
+#[derive(Clone, Default)]
+pub struct PresenceService {
+    state: Arc<Mutex<PresenceState>>,
+}
+
+impl PresenceService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new connection from `peer`, returning its session ID
+    pub fn connect(&self, peer: &str) -> u64 {
+        let mut state = lock_storage(&self.state);
+        state.next_id += 1;
+        let id = state.next_id;
+        state.sessions.push(OnlineSession {
+            id,
+            peer: peer.to_string(),
+            username: None,
+            connected_at: Timestamp::now(),
+            location: None,
+        });
+        id
+    }
+
+    /// Record who is logged in on session `id` (None after logging out)
+    pub fn set_user(&self, id: u64, username: Option<&str>) {
+        let mut state = lock_storage(&self.state);
+        if let Some(session) = state.sessions.iter_mut().find(|s| s.id == id) {
+            session.username = username.map(str::to_string);
+        }
+    }
+
+    /// Record where the caller on session `id` says they are
+    pub fn set_location(&self, id: u64, location: Option<&str>) {
+        let mut state = lock_storage(&self.state);
+        if let Some(session) = state.sessions.iter_mut().find(|s| s.id == id) {
+            session.location = location.map(str::to_string);
+        }
+    }
+
+    /// Remove session `id`, returning what was known about it
+    ///
+    /// Safe to call more than once; later calls return None.
+    pub fn disconnect(&self, id: u64) -> Option<OnlineSession> {
+        let mut state = lock_storage(&self.state);
+        let index = state.sessions.iter().position(|s| s.id == id)?;
+        Some(state.sessions.remove(index))
+    }
+
+    /// Every connected session, oldest first
+    pub fn online(&self) -> Vec<OnlineSession> {
+        lock_storage(&self.state).sessions.clone()
+    }
+
+    /// Number of connected sessions
+    pub fn count(&self) -> usize {
+        lock_storage(&self.state).sessions.len()
+    }
+}
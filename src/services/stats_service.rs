@@ -0,0 +1,229 @@
+use crate::bulletin_repository::BulletinStorage;
+use crate::bulletins::Bulletin;
+use crate::config::SecurityLevel;
+use crate::errors::BbsResult;
+use crate::services::club_service::ClubService;
+use crate::services::lock_storage;
+use crate::services::permission_service::{Access, Feature, PermissionService};
+use crate::user_repository::UserStorage;
+
+use jiff::{SignedDuration, Timestamp};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Weeks of history in the posts-per-week trend
+pub const TREND_WEEKS: usize = 8;
+
+/// How long figures are served from the cache before being worked out again
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+const WEEK: SignedDuration = SignedDuration::from_hours(7 * 24);
+
+/// Figures and when they were worked out
+type Cached = Option<(Instant, Arc<BoardStats>)>;
+
+/// Posting activity for the whole board or one area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    pub bulletins: usize,
+    /// Bulletins posted in each of the last [`TREND_WEEKS`] weeks, this
+    /// week first
+    pub per_week: [usize; TREND_WEEKS],
+    /// Bulletins posted in each hour of the day, UTC
+    pub by_hour: [usize; 24],
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            bulletins: 0,
+            per_week: [0; TREND_WEEKS],
+            by_hour: [0; 24],
+        }
+    }
+}
+
+impl Activity {
+    fn count(&mut self, bulletin: &Bulletin, now: Timestamp) {
+        self.bulletins += 1;
+
+        let age = now.duration_since(bulletin.posted_at);
+        if !age.is_negative() {
+            let week = (age.as_secs() / WEEK.as_secs()) as usize;
+            if let Some(slot) = self.per_week.get_mut(week) {
+                *slot += 1;
+            }
+        }
+
+        let hour = bulletin.posted_at.as_second().rem_euclid(24 * 60 * 60) / (60 * 60);
+        self.by_hour[hour as usize] += 1;
+    }
+
+    /// Hours with any posts, busiest first, as (hour, bulletins)
+    pub fn busiest_hours(&self) -> Vec<(usize, usize)> {
+        let mut hours: Vec<(usize, usize)> = self
+            .by_hour
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        hours.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hours
+    }
+}
+
+/// One bulletin area's share of the board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AreaStats {
+    /// Club name, or `None` for the main board
+    pub area: Option<String>,
+    pub activity: Activity,
+}
+
+impl AreaStats {
+    /// Name to show on screen
+    pub fn name(&self) -> &str {
+        self.area.as_deref().unwrap_or("Main Board")
+    }
+}
+
+/// Figures for the Board Statistics screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardStats {
+    /// When the figures were worked out
+    pub computed_at: Timestamp,
+    pub users: usize,
+    /// Every bulletin on the board, clubs included
+    pub board: Activity,
+    /// Areas with posts, busiest first; the main board is always listed
+    pub areas: Vec<AreaStats>,
+}
+
+impl BoardStats {
+    /// Work out the figures for `bulletins` as of `now`
+    pub fn from_bulletins(bulletins: &[Bulletin], users: usize, now: Timestamp) -> Self {
+        let mut board = Activity::default();
+        let mut areas = vec![AreaStats {
+            area: None,
+            activity: Activity::default(),
+        }];
+
+        for bulletin in bulletins {
+            board.count(bulletin, now);
+            let area = bulletin.area.as_deref();
+            let index = match areas.iter().position(|a| match (a.area.as_deref(), area) {
+                (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                (a, b) => a == b,
+            }) {
+                Some(index) => index,
+                None => {
+                    areas.push(AreaStats {
+                        area: area.map(str::to_string),
+                        activity: Activity::default(),
+                    });
+                    areas.len() - 1
+                }
+            };
+            areas[index].activity.count(bulletin, now);
+        }
+
+        // Busiest first; ties go to the main board, then by name
+        areas.sort_by(|a, b| {
+            b.activity
+                .bulletins
+                .cmp(&a.activity.bulletins)
+                .then(a.area.is_some().cmp(&b.area.is_some()))
+                .then(a.area.cmp(&b.area))
+        });
+
+        Self {
+            computed_at: now,
+            users,
+            board,
+            areas,
+        }
+    }
+}
+
+/// Board-wide activity figures, worked out from storage and cached
+///
+/// Adding up every bulletin on each visit would be wasteful on a busy
+/// board, so figures are kept for [`DEFAULT_MAX_AGE`] and shared by every
+/// session. Clones share the same cache.
+#[derive(Clone)]
+pub struct StatsService {
+    bulletins: Arc<Mutex<dyn BulletinStorage + Send>>,
+    users: Arc<Mutex<dyn UserStorage + Send>>,
+    permissions: Arc<PermissionService>,
+    clubs: ClubService,
+    max_age: Duration,
+    cache: Arc<Mutex<Cached>>,
+}
+
+impl StatsService {
+    pub fn new(
+        bulletins: Arc<Mutex<dyn BulletinStorage + Send>>,
+        users: Arc<Mutex<dyn UserStorage + Send>>,
+        permissions: Arc<PermissionService>,
+        clubs: ClubService,
+    ) -> Self {
+        Self {
+            bulletins,
+            users,
+            permissions,
+            clubs,
+            max_age: DEFAULT_MAX_AGE,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Serve cached figures for `max_age` rather than the default
+    // Only tests look at this so far
+    #[allow(dead_code)]
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Figures for the whole board, from the cache while fresh
+    pub fn board_stats(&self) -> BbsResult<Arc<BoardStats>> {
+        let mut cache = lock_storage(&self.cache);
+        if let Some((computed, stats)) = cache.as_ref()
+            && computed.elapsed() < self.max_age
+        {
+            return Ok(Arc::clone(stats));
+        }
+
+        let bulletins = lock_storage(&self.bulletins).get_bulletins_since(Timestamp::MIN)?;
+        let users = lock_storage(&self.users).get_user_count()?;
+        let stats = Arc::new(BoardStats::from_bulletins(
+            &bulletins,
+            users,
+            Timestamp::now(),
+        ));
+        *cache = Some((Instant::now(), Arc::clone(&stats)));
+        Ok(stats)
+    }
+
+    /// Figures as the caller may see them: board-wide totals, and only the
+    /// areas they can read
+    pub fn stats_for(&self, username: Option<&str>, level: SecurityLevel) -> BbsResult<BoardStats> {
+        self.permissions.require_read(Feature::Bulletins, level)?;
+        let mut stats = BoardStats::clone(&*self.board_stats()?);
+
+        let mut visible = Vec::with_capacity(stats.areas.len());
+        for area in stats.areas {
+            let membership = self.clubs.membership(area.area.as_deref(), username)?;
+            if self
+                .permissions
+                .area_access(Feature::Bulletins, level, membership)
+                != Access::None
+            {
+                visible.push(area);
+            }
+        }
+        stats.areas = visible;
+        Ok(stats)
+    }
+}
@@ -1,38 +1,95 @@
+use crate::config::BbsConfig;
+use crate::confusables::confusable;
 use crate::errors::{BbsError, BbsResult};
 use crate::menu::UserStats;
+use crate::services::lock_storage;
 use crate::user_repository::UserStorage;
-use crate::users::{RegistrationRequest, User};
+use crate::users::{RegistrationRequest, User, UsernameRules, normalize_display_name};
+use std::collections::BTreeSet;
 use std::sync::{Arc, Mutex};
 
 pub struct UserService {
     storage: Arc<Mutex<dyn UserStorage + Send>>,
+    rules: UsernameRules,
 }
 
 impl UserService {
     pub fn new(storage: Arc<Mutex<dyn UserStorage + Send>>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            rules: UsernameRules::default(),
+        }
+    }
+
+    /// Use `rules` when matching usernames at login
+    pub fn with_rules(mut self, rules: UsernameRules) -> Self {
+        self.rules = rules;
+        self
     }
 
     pub fn authenticate(&self, username: &str, password: &str) -> BbsResult<Option<User>> {
-        let mut storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
-        storage.authenticate_user(username, password)
+        let mut storage = lock_storage(&self.storage);
+        let username = if self.rules.case_insensitive {
+            // Log in to "Alice" whether typed as "alice" or "ALICE"
+            storage
+                .list_users()?
+                .into_iter()
+                .find(|existing| self.rules.same_name(existing, username))
+                .unwrap_or_else(|| username.to_string())
+        } else {
+            username.to_string()
+        };
+        storage.authenticate_user(&username, password)
     }
 
     pub fn register(
         &self,
-        request: RegistrationRequest,
-        config: &crate::config::BbsConfig,
+        mut request: RegistrationRequest,
+        config: &BbsConfig,
     ) -> BbsResult<User> {
-        let mut storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
+        let mut storage = lock_storage(&self.storage);
+        let rules = &config.features.usernames;
+        if rules.case_insensitive
+            && let Some(existing) = storage
+                .list_users()?
+                .into_iter()
+                .find(|existing| rules.same_name(existing, &request.username))
+        {
+            return Err(BbsError::InvalidInput(format!(
+                "Username '{}' is already taken",
+                existing
+            )));
+        }
+        if let Some(display_name) = request.display_name.take() {
+            request.display_name = normalize_display_name(&display_name)?;
+        }
+        if let Some(display_name) = &request.display_name {
+            check_display_name(&*storage, &request.username, display_name, config)?;
+        }
         storage.register_user(&request, config)
     }
 
+    /// Set or clear (with a blank name) a user's display name and return
+    /// the updated user
+    pub fn set_display_name(
+        &self,
+        username: &str,
+        display_name: &str,
+        config: &BbsConfig,
+    ) -> BbsResult<User> {
+        let display_name = normalize_display_name(display_name)?;
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        if let Some(display_name) = &display_name {
+            check_display_name(&*storage, username, display_name, config)?;
+        }
+        user.display_name = display_name;
+        storage.save_user(&user)?;
+        Ok(user)
+    }
+
     // pub fn get_user(&self, username: &str) -> BbsResult<Option<User>> {
     //     let storage = self.storage.lock()
     //         .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
@@ -40,10 +97,182 @@ impl UserService {
     // }
 
     pub fn get_stats(&self) -> BbsResult<UserStats> {
-        let storage = self
-            .storage
-            .lock()
-            .map_err(|_| BbsError::Configuration("Storage lock poisoned".to_string()))?;
+        let storage = lock_storage(&self.storage);
         storage.get_stats()
     }
+
+    /// Record that `username` has read a bulletin and return the updated user
+    pub fn mark_bulletin_read(&self, username: &str, bulletin_id: u32) -> BbsResult<User> {
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        if !user.bulletin_reads.is_read(bulletin_id) {
+            user.bulletin_reads.mark_read(bulletin_id);
+            storage.save_user(&user)?;
+        }
+        Ok(user)
+    }
+
+    /// Record that `username` has read every bulletin in `bulletin_ids` and
+    /// return the updated user
+    pub fn mark_bulletins_read(&self, username: &str, bulletin_ids: &[u32]) -> BbsResult<User> {
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        let before = user.bulletin_reads.clone();
+        for id in bulletin_ids {
+            user.bulletin_reads.mark_read(*id);
+        }
+        if user.bulletin_reads != before {
+            storage.save_user(&user)?;
+        }
+        Ok(user)
+    }
+
+    /// Move `username`'s high-water mark past the bulletins they can't read
+    /// and return the updated user
+    ///
+    /// `readable` is every bulletin above the mark that they may read (see
+    /// [`BulletinReadState::skip_unreadable`](crate::bulletins::BulletinReadState::skip_unreadable)).
+    pub fn skip_unreadable_bulletins(
+        &self,
+        username: &str,
+        readable: &BTreeSet<u32>,
+    ) -> BbsResult<User> {
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        if user.bulletin_reads.skip_unreadable(readable) {
+            storage.save_user(&user)?;
+        }
+        Ok(user)
+    }
+
+    /// Turn quiet mode on or off and return the updated user
+    pub fn set_quiet_mode(&self, username: &str, quiet: bool) -> BbsResult<User> {
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        user.quiet_mode = quiet;
+        storage.save_user(&user)?;
+        Ok(user)
+    }
+
+    /// Set or clear the screen reader preference and return the updated user
+    pub fn set_screen_reader(
+        &self,
+        username: &str,
+        screen_reader: Option<bool>,
+    ) -> BbsResult<User> {
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        user.screen_reader = screen_reader;
+        storage.save_user(&user)?;
+        Ok(user)
+    }
+
+    /// Forward `username`'s private messages to `target`, or stop forwarding
+    /// with a blank target, and return the updated user
+    pub fn set_forward_to(&self, username: &str, target: &str) -> BbsResult<User> {
+        let target = target.trim();
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        user.forward_to = if target.is_empty() {
+            None
+        } else if target == username {
+            return Err(BbsError::InvalidInput(
+                "Cannot forward messages to yourself".to_string(),
+            ));
+        } else if !storage.user_exists(target)? {
+            return Err(BbsError::InvalidInput(format!(
+                "User '{}' does not exist",
+                target
+            )));
+        } else {
+            Some(target.to_string())
+        };
+        storage.save_user(&user)?;
+        Ok(user)
+    }
+
+    /// Set the vacation reply sent to people who message `username`, or
+    /// turn it off with blank text, and return the updated user
+    pub fn set_auto_reply(
+        &self,
+        username: &str,
+        text: &str,
+        config: &BbsConfig,
+    ) -> BbsResult<User> {
+        let text = text.trim();
+        if text.len() > config.features.max_message_length {
+            return Err(BbsError::InvalidInput(format!(
+                "Reply too long (max {} characters)",
+                config.features.max_message_length
+            )));
+        }
+        let mut storage = lock_storage(&self.storage);
+        let mut user = storage
+            .load_user(username)?
+            .ok_or_else(|| BbsError::InvalidInput(format!("User '{}' not found", username)))?;
+        user.auto_reply = (!text.is_empty()).then(|| text.to_string());
+        storage.save_user(&user)?;
+        Ok(user)
+    }
+
+    pub fn get_recent_logins(&self, limit: usize) -> BbsResult<Vec<User>> {
+        let storage = lock_storage(&self.storage);
+        storage.get_recent_logins(limit)
+    }
+}
+
+/// Refuse a display name that could pass for someone else: another
+/// user's username or display name, a reserved name, or the SysOp
+fn check_display_name(
+    storage: &(dyn UserStorage + Send),
+    owner: &str,
+    display_name: &str,
+    config: &BbsConfig,
+) -> BbsResult<()> {
+    let owner_is_sysop = owner.eq_ignore_ascii_case(&config.bbs.sysop_name);
+    let protected = config
+        .features
+        .usernames
+        .reserved
+        .iter()
+        .map(String::as_str)
+        .chain([config.bbs.sysop_name.as_str(), "Anonymous"]);
+    for name in protected {
+        if !owner_is_sysop && confusable(display_name, name) {
+            return Err(BbsError::InvalidInput(format!(
+                "Display name '{}' is reserved",
+                display_name
+            )));
+        }
+    }
+
+    for username in storage.list_users()? {
+        if username == owner {
+            continue;
+        }
+        let other = storage.load_user(&username)?;
+        let taken = confusable(display_name, &username)
+            || other
+                .and_then(|u| u.display_name)
+                .is_some_and(|shown| confusable(display_name, &shown));
+        if taken {
+            return Err(BbsError::InvalidInput(format!(
+                "Display name '{}' looks too much like another user",
+                display_name
+            )));
+        }
+    }
+    Ok(())
 }
@@ -1,8 +1,17 @@
+use crate::attention::{AttentionEvent, AttentionProfile, attention_bytes};
 use crate::box_renderer::{BoxRenderer, BoxStyle};
 use crate::bulletin_repository::BulletinStats;
-use crate::config::BbsConfig;
+use crate::client_profile::ClientClass;
+use crate::config::{BbsConfig, SecurityLevel};
 use crate::errors::{BbsError, BbsResult};
-use crate::menu::{Menu, MenuAction, MenuRender, MenuScreen, RecentLogin, UserStats};
+use crate::events::{BbsEvent, Subscription};
+use crate::external::CommandCaller;
+use crate::gateway::GatewayEnd;
+use crate::menu::{LogonSummary, Menu, MenuAction, MenuRender, MenuScreen, RecentLogin, UserStats};
+#[cfg(feature = "scripting")]
+use crate::scripting::{ScriptCaller, ScriptEvent};
+use crate::services::{Access, BoardStats, Feature, LoopTiming};
+use crate::text_width::{pad_to_width, wrap_to_width};
 
 use crate::bulletins::Bulletin;
 use crate::users::{RegistrationRequest, User};
@@ -13,13 +22,54 @@ use crossterm::{
     terminal::{Clear, ClearType},
 };
 
-use std::io::{Read, Write};
+use std::io::Write;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // Phase 5: Use TelnetStream for transparent telnet handling
 // Phase 7: Import terminal capabilities for adaptive UI
-use telnet_negotiation::{TelnetStream, TerminalCapabilities};
+use crate::connection::Connection;
+use crate::session_stream::SessionStream;
+use telnet_negotiation::{
+    ColorDepth, ControlEvent, Direction, LineReader, NegotiationProfile, TelnetOption,
+    TelnetServerBuilder, TelnetStream, TerminalCapabilities,
+};
+
+/// How long to collect option replies before showing the first screen;
+/// long enough for a terminal type cycle to reach the MTTS entry
+const NEGOTIATION_WAIT_MS: u64 = 250;
+
+/// How long a caller's window must keep its new size before the screen is
+/// redrawn, so dragging a window edge redraws once rather than every step
+const RESIZE_DEBOUNCE_MS: u64 = 150;
+
+/// How often a session adds its telnet counters to the board's metrics
+const TELNET_STATS_SECS: u64 = 10;
+
+/// What is on the caller's screen while waiting for input, so it can be
+/// drawn again when their window changes size
+enum Screen {
+    /// The current menu, followed by its prompt
+    Menu { prompt: String },
+    /// A message box waiting for Enter
+    Message {
+        title: String,
+        message: String,
+        color: Option<Color>,
+    },
+    /// A box of lines waiting for Enter
+    Lines {
+        title: String,
+        lines: Vec<String>,
+        color: Option<Color>,
+    },
+    /// One screenful of a long text, paged to fit the window
+    Page {
+        title: String,
+        text: String,
+        page: usize,
+    },
+}
 
 pub struct BbsSession {
     pub config: Arc<BbsConfig>,
@@ -27,14 +77,39 @@ pub struct BbsSession {
     pub menu_current: Menu,
     pub user_stats: Option<UserStats>,
     pub bulletin_stats: Option<BulletinStats>,
+    pub logon_summary: Option<LogonSummary>,
+    /// Figures for the Board Statistics screen, as this caller may see them
+    pub board_stats: Option<BoardStats>,
 
     // Session resources
     pub services: Arc<crate::services::CoreServices>,
     box_renderer: BoxRenderer,
     login_attempts: u8,
+    // Unread mail count last seen, to notice new arrivals
+    unread_seen: Option<usize>,
+    // Events from other sessions, subscribed for the life of the connection
+    events: Option<Subscription>,
+    // This connection's entry in the who's-online registry
+    presence_id: Option<u64>,
+    // Screen reader choice made before logging in (or as a guest)
+    screen_reader_override: Option<bool>,
+    // Login name offered by the client's telnet environment (USER)
+    suggested_username: Option<String>,
+    // [motd] notices picked for this caller's address when they connected
+    connection_notices: Vec<String>,
+    // Time spent waiting on the caller during the current menu pass
+    input_wait: Duration,
+    // What the caller is looking at while we wait on them
+    screen: Option<Screen>,
+    // The last read was cut short by the caller's Ctrl+C (IAC IP, BRK or AO)
+    interrupted: bool,
+    // Line editing for clients that send each key, echoing while we WILL ECHO
+    line_reader: LineReader,
 
     // Phase 7: Terminal capabilities for adaptive UI
     terminal_capabilities: TerminalCapabilities,
+    // How promptly the client answered the opening negotiation
+    client_class: ClientClass,
     effective_width: usize,
 
     // Menu instances (owned by session, can maintain state)
@@ -42,6 +117,9 @@ pub struct BbsSession {
     menu_bulletin: crate::menu::menu_bulletin::BulletinMenu,
     menu_user: crate::menu::menu_user::UserMenu,
     menu_message: crate::menu::menu_message::MessageMenu,
+    menu_gateway: crate::menu::menu_gateway::GatewayMenu,
+    menu_summary: crate::menu::menu_summary::SummaryMenu,
+    menu_stats: crate::menu::menu_stats::StatsMenu,
     // menu_file: crate::menu::menu_file::FileMenu,
 }
 
@@ -55,33 +133,87 @@ impl BbsSession {
             menu_current: Menu::Main,
             user_stats: None,
             bulletin_stats: None,
+            logon_summary: None,
+            board_stats: None,
 
             // Session resources
             services,
             box_renderer,
             login_attempts: 0,
+            unread_seen: None,
+            events: None,
+            presence_id: None,
+            screen_reader_override: None,
+            suggested_username: None,
+            connection_notices: Vec::new(),
+            input_wait: Duration::ZERO,
+            screen: None,
+            interrupted: false,
+            line_reader: LineReader::new(),
 
             // Phase 7: Initialize terminal capabilities
             terminal_capabilities: TerminalCapabilities::default(),
+            client_class: ClientClass::default(),
             effective_width: config.ui.width_value,
 
             menu_main: crate::menu::menu_main::MainMenu::new(),
             menu_bulletin: crate::menu::menu_bulletin::BulletinMenu::new(),
             menu_user: crate::menu::menu_user::UserMenu::new(),
             menu_message: crate::menu::menu_message::MessageMenu::new(),
+            menu_gateway: crate::menu::menu_gateway::GatewayMenu::new(),
+            menu_summary: crate::menu::menu_summary::SummaryMenu::new(),
+            menu_stats: crate::menu::menu_stats::StatsMenu::new(),
             // menu_file: crate::menu::menu_file::FileMenu::new(),
         }
     }
 
+    /// Show `notices` before the welcome screen
+    pub fn with_connection_notices(mut self, notices: Vec<String>) -> Self {
+        self.connection_notices = notices;
+        self
+    }
+
     pub fn is_logged_in(&self) -> bool {
         self.user.is_some()
     }
 
+    /// Check if the logged in user is the SysOp named in the config
+    pub fn is_sysop(&self) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|u| u.username.eq_ignore_ascii_case(&self.config.bbs.sysop_name))
+    }
+
     /// Helper to check if anonymous access is allowed
     pub fn allow_anonymous(&self) -> bool {
         self.config.features.allow_anonymous
     }
 
+    /// Whether the logged-in user has read a bulletin (anonymous users have read nothing)
+    pub fn has_read_bulletin(&self, bulletin_id: u32) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|u| u.bulletin_reads.is_read(bulletin_id))
+    }
+
+    /// Security level of the caller, used for feature access checks
+    pub fn security_level(&self) -> SecurityLevel {
+        if self.is_sysop() {
+            SecurityLevel::Sysop
+        } else if self.is_logged_in() {
+            SecurityLevel::User
+        } else {
+            SecurityLevel::Guest
+        }
+    }
+
+    /// What the caller may do with a feature, per the PermissionService
+    pub fn feature_access(&self, feature: Feature) -> Access {
+        self.services
+            .permissions
+            .access(feature, self.security_level())
+    }
+
     /// Get the current username, or "Anonymous" if not logged in
     pub fn display_username(&self) -> String {
         match &self.user {
@@ -91,28 +223,182 @@ impl BbsSession {
         }
     }
 
+    /// The logged-in user's display name, if they have set one
+    pub fn display_name(&self) -> Option<String> {
+        self.user.as_ref().and_then(|u| u.display_name.clone())
+    }
+
+    /// Name to show others: the display name, else the username
+    pub fn shown_name(&self) -> String {
+        match &self.user {
+            Some(u) => u.shown_name().to_string(),
+            None => "Anonymous".to_string(),
+        }
+    }
+
+    /// Whether output should be plain linear text for a screen reader
+    ///
+    /// A logged-in user's saved preference wins, then a choice made this
+    /// session, then the MTTS flag the client reported.
+    pub fn screen_reader_active(&self) -> bool {
+        self.user
+            .as_ref()
+            .and_then(|u| u.screen_reader)
+            .or(self.screen_reader_override)
+            .unwrap_or(self.terminal_capabilities.screen_reader)
+    }
+
+    /// This connection's ID in the who's-online registry
+    pub fn presence_id(&self) -> Option<u64> {
+        self.presence_id
+    }
+
     /// Get the effective terminal width for rendering
     pub fn effective_width(&self) -> usize {
         self.effective_width
     }
 
     /// Run the BBS session with the provided stream
-    pub fn run(&mut self, mut stream: TelnetStream) -> BbsResult<()> {
+    pub fn run(&mut self, stream: TelnetStream<Connection>) -> BbsResult<()> {
+        let peer = stream
+            .get_ref()
+            .peer_addr()
+            .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+        let mut stream = SessionStream::new(stream);
+        let metrics = self.services.metrics.clone();
+        stream.set_stats_hook(Duration::from_secs(TELNET_STATS_SECS), move |added| {
+            metrics.record_telnet(added)
+        });
+        self.events = Some(self.services.events.subscribe());
+        self.presence_id = Some(self.services.presence.connect(&peer));
+
+        let result = self.run_session(&mut stream);
+
+        if let Err(BbsError::Timeout) = result {
+            // The line is still up, so say why it's closing
+            let _ = stream.write_all(b"\r\n\r\nIdle too long - disconnecting.\r\n");
+            let _ = stream.flush();
+        }
+        stream.report_stats();
+        self.clean_up(&result, &peer);
+
+        result
+    }
+
+    /// Tidy up after the call ends, whether they quit, hung up or timed out
+    ///
+    /// Keeps any half-written post for next time, takes the caller off the
+    /// online list and tells other sessions they've gone.
+    fn clean_up(&mut self, result: &BbsResult<()>, peer: &str) {
+        let draft_saved = self.save_draft();
+
+        if let Err(BbsError::ClientDisconnected) = result {
+            println!(
+                "> Carrier drop: {} from {}{}",
+                self.display_username(),
+                peer,
+                if draft_saved { " (draft saved)" } else { "" }
+            );
+        }
+
+        if let Some(id) = self.presence_id.take() {
+            self.services.presence.disconnect(id);
+        }
+        self.log_out();
+        self.events = None;
+    }
+
+    /// Keep a bulletin or message the caller was part way through writing
+    fn save_draft(&mut self) -> bool {
+        use crate::menu::menu_bulletin::BulletinMenuState;
+        use crate::menu::menu_message::MessageMenuState;
+        use crate::services::Draft;
+
+        let Some(user) = &self.user else {
+            return false;
+        };
+        let draft = match (&self.menu_bulletin.state, &self.menu_message.state) {
+            (BulletinMenuState::PostingContent(title), _)
+            | (BulletinMenuState::Reviewing { title, .. }, _) => Draft::Bulletin {
+                title: title.clone(),
+                area: self.menu_bulletin.area.clone(),
+            },
+            (_, MessageMenuState::ComposeContent { recipient, subject })
+            | (
+                _,
+                MessageMenuState::Review {
+                    recipient, subject, ..
+                },
+            ) => Draft::Message {
+                recipient: recipient.clone(),
+                subject: subject.clone(),
+            },
+            _ => return false,
+        };
+
+        self.services.drafts.save(&user.username, draft);
+        true
+    }
+
+    /// Pick up where the caller left off if their last call dropped mid-post
+    fn resume_draft(&mut self) {
+        use crate::menu::menu_bulletin::BulletinMenuState;
+        use crate::menu::menu_message::MessageMenuState;
+        use crate::services::Draft;
+
+        let Some(user) = &self.user else {
+            return;
+        };
+        match self.services.drafts.take(&user.username) {
+            Some(Draft::Bulletin { title, area }) => {
+                self.menu_bulletin.area = area;
+                self.menu_bulletin.state = BulletinMenuState::PostingContent(title);
+            }
+            Some(Draft::Message { recipient, subject }) => {
+                self.menu_message.state = MessageMenuState::ComposeContent { recipient, subject };
+            }
+            None => {}
+        }
+    }
+
+    fn run_session(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
         // Set initial timeout
         stream.set_read_timeout(Some(self.config.timeouts.connection_timeout))?;
 
+        // Encrypt the connection first if the client supports START_TLS
+        self.negotiate_start_tls(stream)?;
+
         // Phase 7: Negotiate terminal capabilities
-        self.negotiate_terminal_capabilities(&mut stream)?;
+        self.negotiate_terminal_capabilities(stream)?;
 
         // Initialize terminal
-        self.initialize_terminal(&mut stream)?;
+        self.initialize_terminal(stream)?;
+
+        // Retro: simulated modem dial-up before anything else
+        if self.config.ui.retro_modem_banner {
+            self.show_modem_connect(stream)?;
+        }
+
+        // Notices the SysOp set for callers from this address
+        self.show_connection_notices(stream)?;
 
         // Show welcome screen
-        self.show_welcome(&mut stream)?;
+        self.show_welcome(stream)?;
 
-        // Check if anonymous access is allowed
-        if !self.config.features.allow_anonymous && self.user.is_none() {
-            self.force_login(&mut stream)?;
+        if self.config.ui.retro_login_matrix {
+            // Retro: the matrix screen replaces the plain login prompt
+            if !self.show_login_matrix(stream)? {
+                self.show_goodbye(stream)?;
+                return Ok(());
+            }
+        } else if !self.config.features.allow_anonymous && self.user.is_none() {
+            // Check if anonymous access is allowed
+            self.force_login(stream)?;
+        }
+
+        // Retro: last callers list before the main menu
+        if self.config.ui.retro_last_callers {
+            self.show_last_callers(stream)?;
         }
 
         // Initialize stats
@@ -120,7 +406,7 @@ impl BbsSession {
 
         // Main session loop
         loop {
-            if !self.menu_handle_loop(&mut stream)? {
+            if !self.menu_handle_loop(stream)? {
                 break; // User chose to quit
             }
         }
@@ -128,29 +414,88 @@ impl BbsSession {
         Ok(())
     }
 
+    /// Offer TELNET START_TLS and wait briefly for the client to take it up
+    ///
+    /// Runs before anything else is sent so the rest of the session,
+    /// login included, is encrypted. Clients that refuse or ignore the
+    /// offer continue in plaintext. Only callers on the telnet port are
+    /// offered it: TLS port callers are encrypted already, and a browser's
+    /// WebSocket can't be handed to a TLS backend.
+    fn negotiate_start_tls(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        if !self.config.server.start_tls || !stream.get_ref().can_start_tls() {
+            return Ok(());
+        }
+        let Some(upgrader) = crate::tls::upgrader() else {
+            return Ok(());
+        };
+
+        stream.offer_start_tls(upgrader)?;
+        stream.await_start_tls(Duration::from_millis(crate::tls::START_TLS_TIMEOUT_MS))?;
+        Ok(())
+    }
+
     /// Phase 7: Negotiate terminal capabilities for adaptive UI
-    fn negotiate_terminal_capabilities(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
+    fn negotiate_terminal_capabilities(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
         // Get capabilities from TelnetStream
         self.terminal_capabilities = stream.get_terminal_capabilities();
 
-        // Request terminal type if configured for auto-detection
-        if matches!(
+        // The SysOp's chosen options, in their order, less the probes this
+        // board's UI settings have no use for
+        let detect_terminal = matches!(
             self.config.ui.ansi_support,
             crate::config::AutoDetectOption::Auto
         ) || matches!(
             self.config.ui.color_support,
             crate::config::AutoDetectOption::Auto
-        ) {
-            let _ = stream.request_terminal_type()?;
-        }
+        );
+        let detect_width = matches!(self.config.ui.width_mode, crate::config::WidthMode::Auto);
+        let profile = NegotiationProfile::new(
+            self.config
+                .server
+                .telnet_options
+                .options()
+                .iter()
+                .copied()
+                .filter(|&option| match option {
+                    TelnetOption::TERMINAL_TYPE => detect_terminal,
+                    TelnetOption::NAWS => detect_width,
+                    _ => true,
+                }),
+        );
 
-        // Request window size if configured for auto-detection
-        if matches!(self.config.ui.width_mode, crate::config::WidthMode::Auto) {
-            let _ = stream.request_window_size()?;
+        // Give the client a moment to answer: the handshake ends once it
+        // has, or when the wait is up
+        TelnetServerBuilder::new()
+            .profile(profile)
+            .timeout(Duration::from_millis(NEGOTIATION_WAIT_MS))
+            .negotiate(&mut **stream)?;
+
+        self.suggested_username = stream
+            .environment()
+            .and_then(|environment| environment.user())
+            .filter(|user| {
+                user.len() <= self.config.features.max_username_length
+                    && user
+                        .chars()
+                        .all(|c| self.config.features.usernames.allows_char(c))
+            })
+            .map(str::to_string);
+
+        if let Some(id) = self.presence_id
+            && let Some(location) = stream.location()
+        {
+            self.services.presence.set_location(id, Some(location));
         }
 
-        // Give a moment for negotiation to complete
-        std::thread::sleep(Duration::from_millis(100));
+        // Slow or unresponsive clients get a lighter interface
+        self.client_class = ClientClass::from_stats(&stream.negotiation_stats());
+        if self.client_class != ClientClass::Modern {
+            println!("> Client classified as {}", self.client_class);
+            // Where each option ended up, for "my client doesn't work" reports
+            for line in stream.dump_options().to_string().lines() {
+                println!(">   {}", line);
+            }
+        }
 
         // Update capabilities after negotiation attempts
         self.terminal_capabilities = stream.get_terminal_capabilities();
@@ -158,14 +503,34 @@ impl BbsSession {
         // Calculate effective width
         self.effective_width = self.calculate_effective_width();
 
-        // Update box renderer with detected capabilities
-        let supports_color = self.resolve_color_support();
-        let box_style = self.resolve_box_style();
-        self.box_renderer = BoxRenderer::new(box_style, supports_color);
+        // Update box renderer and output colors with detected capabilities
+        self.apply_output_mode(stream);
+
+        // The first size report is already in the width; redraw on later ones
+        let _ = stream.take_resize();
+        stream.set_resize_debounce(Some(Duration::from_millis(RESIZE_DEBOUNCE_MS)));
+        stream.set_interrupt_on_resize(true);
+        stream.set_interrupt_on_signal(true);
 
         Ok(())
     }
 
+    /// Rebuild the box renderer and color depth for the current capabilities
+    /// and screen reader setting
+    fn apply_output_mode(&mut self, stream: &mut SessionStream) {
+        let linear = self.screen_reader_active();
+        let supports_color = self.resolve_color_support() && !linear;
+        let box_style = self.resolve_box_style();
+        self.box_renderer = BoxRenderer::new(box_style, supports_color).with_linear(linear);
+
+        // All output from here on is translated to what the terminal can show
+        stream.set_color_depth(if linear {
+            ColorDepth::Monochrome
+        } else {
+            self.resolve_color_depth()
+        });
+    }
+
     /// Calculate the effective terminal width based on configuration and detection
     fn calculate_effective_width(&self) -> usize {
         match &self.config.ui.width_mode {
@@ -189,15 +554,36 @@ impl BbsSession {
         }
     }
 
-    /// Resolve ANSI support and appropriate box style
-    fn resolve_box_style(&self) -> BoxStyle {
-        let ansi_supported = match &self.config.ui.ansi_support {
+    /// Color depth to translate output to
+    ///
+    /// Terminals that have not reported a type yet get the 16 basic colors,
+    /// which is what this BBS sent to everyone before depth was tracked.
+    fn resolve_color_depth(&self) -> ColorDepth {
+        let detected = if self.terminal_capabilities.terminal_type.is_some() {
+            self.terminal_capabilities.color_depth
+        } else {
+            ColorDepth::Basic16
+        };
+
+        match &self.config.ui.color_support {
+            crate::config::AutoDetectOption::Auto => detected,
+            crate::config::AutoDetectOption::Enabled => detected.max(ColorDepth::Basic16),
+            crate::config::AutoDetectOption::Disabled => ColorDepth::Monochrome,
+        }
+    }
+
+    /// Resolve ANSI support from config and detected capabilities
+    fn resolve_ansi_support(&self) -> bool {
+        match &self.config.ui.ansi_support {
             crate::config::AutoDetectOption::Auto => self.terminal_capabilities.supports_ansi,
             crate::config::AutoDetectOption::Enabled => true,
             crate::config::AutoDetectOption::Disabled => false,
-        };
+        }
+    }
 
-        if ansi_supported {
+    /// Resolve ANSI support and appropriate box style
+    fn resolve_box_style(&self) -> BoxStyle {
+        if self.resolve_ansi_support() {
             // Use configured style if ANSI is supported
             self.config.ui.box_style
         } else {
@@ -206,25 +592,138 @@ impl BbsSession {
         }
     }
 
-    /// Detect ANSI support from terminal type string (helper for negotiation)
-    fn detect_ansi_support(terminal_type: &str) -> bool {
-        let terminal_lower = terminal_type.to_lowercase();
-        terminal_lower.contains("xterm")
-            || terminal_lower.contains("ansi")
-            || terminal_lower.contains("vt100")
-            || terminal_lower.contains("linux")
-            || terminal_lower.contains("screen")
-            || terminal_lower.contains("tmux")
+    /// Ring the bell (and flash a notice) for an event, honoring quiet mode
+    fn signal_attention(
+        &mut self,
+        stream: &mut SessionStream,
+        event: AttentionEvent,
+    ) -> BbsResult<()> {
+        let profile = AttentionProfile {
+            quiet: self.user.as_ref().is_some_and(|u| u.quiet_mode),
+            visual_flash: self.config.ui.attention_flash,
+            supports_ansi: self.resolve_ansi_support() && !self.screen_reader_active(),
+        };
+
+        let bytes = attention_bytes(&event, profile);
+        if !bytes.is_empty() {
+            stream.write_all(&bytes)?;
+            stream.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Act on events published by other sessions since the last prompt
+    fn process_events(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let Some(events) = &self.events else {
+            return Ok(());
+        };
+        let pending = events.drain();
+        // A missed event may have been mail, so recount to be safe
+        let missed = events.take_missed() > 0;
+
+        let Some(user) = &self.user else {
+            return Ok(());
+        };
+        let mail_arrived = pending.iter().any(|event| {
+            matches!(event, BbsEvent::NewMessage { to, .. } if to.eq_ignore_ascii_case(&user.username))
+        });
+
+        if missed || mail_arrived || self.unread_seen.is_none() {
+            self.check_new_mail(stream)?;
+        }
+        Ok(())
+    }
+
+    /// Tell other sessions this caller just logged in
+    fn announce_login(&mut self) {
+        if let Some(user) = &self.user {
+            if let Some(id) = self.presence_id {
+                self.services.presence.set_user(id, Some(&user.username));
+            }
+            self.services.events.publish(BbsEvent::Login {
+                username: user.username.clone(),
+            });
+        }
+        self.resume_draft();
+    }
+
+    /// Forget the logged-in caller and tell other sessions they left
+    fn log_out(&mut self) {
+        if let Some(id) = self.presence_id {
+            self.services.presence.set_user(id, None);
+        }
+        if let Some(user) = self.user.take() {
+            self.services.events.publish(BbsEvent::Logout {
+                username: user.username,
+            });
+        }
+    }
+
+    /// Signal when private mail has arrived since the last check
+    fn check_new_mail(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let Some(user) = &self.user else {
+            return Ok(());
+        };
+        let unread = self.services.messages.get_unread_count(&user.username)?;
+
+        // The first check after login only sets the baseline
+        if let Some(seen) = self.unread_seen
+            && unread > seen
+        {
+            self.signal_attention(
+                stream,
+                AttentionEvent::NewMessage {
+                    count: unread - seen,
+                },
+            )?;
+        }
+        self.unread_seen = Some(unread);
+        Ok(())
     }
 
-    /// Detect color support from terminal type string (helper for negotiation)
-    fn detect_color_support(terminal_type: &str) -> bool {
-        let terminal_lower = terminal_type.to_lowercase();
-        terminal_lower.contains("xterm")
-            || terminal_lower.contains("color")
-            || terminal_lower.contains("256")
-            || terminal_lower.contains("screen")
-            || terminal_lower.contains("tmux")
+    /// Gather what changed since the caller's last call and show it next
+    ///
+    /// Skipped for callers without a previous login to compare against.
+    fn prepare_logon_summary(&mut self) -> BbsResult<()> {
+        let Some(user) = &self.user else {
+            return Ok(());
+        };
+        let (Some(since), Some(last_call_display)) =
+            (user.previous_login, user.previous_login_display())
+        else {
+            return Ok(());
+        };
+        let level = self.security_level();
+
+        let (unread_mail, new_mail) = if self.feature_access(Feature::Messages) != Access::None {
+            let unread = self.services.messages.get_unread_count(&user.username)?;
+            let new = self
+                .services
+                .messages
+                .get_inbox_since(&user.username, since, level)?;
+            (unread, new.len())
+        } else {
+            (0, 0)
+        };
+
+        let new_bulletins = if self.feature_access(Feature::Bulletins) != Access::None {
+            Some(
+                self.services
+                    .bulletins
+                    .get_bulletins_since(since, Some(&user.username), level)?,
+            )
+        } else {
+            None
+        };
+
+        self.logon_summary = Some(LogonSummary {
+            last_call_display,
+            unread_mail,
+            new_mail,
+            new_bulletins,
+        });
+        self.menu_current = Menu::Summary;
+        Ok(())
     }
 
     /// Get the current menu instance
@@ -234,6 +733,9 @@ impl BbsSession {
             Menu::Bulletins => &self.menu_bulletin,
             Menu::Users => &self.menu_user,
             Menu::Messages => &self.menu_message,
+            Menu::Gateway => &self.menu_gateway,
+            Menu::Summary => &self.menu_summary,
+            Menu::Stats => &self.menu_stats,
             // CurrentMenu::Files => &self.menu_file,
         }
     }
@@ -243,7 +745,7 @@ impl BbsSession {
         let stats = self.services.users.get_stats()?;
         let total_users = stats.total_users;
         let all_users = stats.all_users;
-        let online_users = stats.online_users;
+        let online_users = self.services.presence.count();
 
         // Get recent logins (limit to 5 most recent)
         let mut recent_logins = stats.recent_logins;
@@ -283,12 +785,20 @@ impl BbsSession {
     }
 
     /// Main menu loop - render, display, get input, handle action
-    fn menu_handle_loop(&mut self, stream: &mut TelnetStream) -> BbsResult<bool> {
+    fn menu_handle_loop(&mut self, stream: &mut SessionStream) -> BbsResult<bool> {
+        let menu = self.menu_current;
+        self.input_wait = Duration::ZERO;
+
         // 1. Check user stats
         // This has to come first because of the mutable borrow
+        let started = Instant::now();
         let _ = self.calculate_user_stats();
+        // Logging in or out can change the screen reader preference
+        self.apply_output_mode(stream);
+        let mut storage = started.elapsed();
 
         // 2. Get current menu and render
+        let started = Instant::now();
         let menu_render = {
             let menu_current = self.menu_get_current();
             menu_current.render(self)
@@ -296,24 +806,226 @@ impl BbsSession {
 
         // 3. Display menu (session handles I/O)
         self.menu_show(stream, &menu_render)?;
+        self.process_events(stream)?;
+        let render = started.elapsed();
 
         // 4. Get input (session handles I/O) - now we can borrow mutably
-        let input = self.get_input(stream, &menu_render.prompt)?;
+        let prompt = if self.box_renderer.is_linear() {
+            // Say where the caller is, since there is no box to look at
+            format!("\r\n{}: {} ", menu_render.title, menu_render.prompt.trim())
+        } else {
+            menu_render.prompt.clone()
+        };
+        self.screen = Some(Screen::Menu {
+            prompt: prompt.clone(),
+        });
+        let input = self.get_input(stream, &prompt)?;
 
         // 5. Handle input and process action
+        let started = Instant::now();
+        let waited = self.input_wait;
         let action = {
             let menu_current = self.menu_get_current();
             menu_current.handle_input(self, &input)
         };
 
         // 6. Process action (session handles state changes)
-        self.menu_handle_action(stream, action)
+        let result = self.menu_handle_action(stream, action);
+        // Prompts inside the action ("Press Enter", login) are the caller's time
+        storage += started.elapsed().saturating_sub(self.input_wait - waited);
+
+        self.record_timing(
+            menu,
+            LoopTiming {
+                render,
+                storage,
+                input_wait: self.input_wait,
+            },
+        );
+        result
+    }
+
+    /// Add a menu pass to the board's metrics, warning if the render was slow
+    fn record_timing(&self, menu: Menu, timing: LoopTiming) {
+        let slow = timing.render > self.config.metrics.slow_render;
+        if slow {
+            eprintln!(
+                "! Slow render: {} menu took {} ms for {}",
+                menu.name(),
+                timing.render.as_millis(),
+                self.user.as_ref().map_or("guest", |u| u.username.as_str())
+            );
+        }
+        self.services.metrics.record(menu.name(), &timing, slow);
+    }
+
+    /// Read a line from the caller, counting the wait toward this menu pass
+    ///
+    /// Returns the line without its ending, or `None` if they hung up. A
+    /// Ctrl+C gives an empty line and sets `interrupted`.
+    fn read_caller(&mut self, stream: &mut SessionStream) -> std::io::Result<Option<String>> {
+        let started = Instant::now();
+        let mut line = String::new();
+        self.interrupted = false;
+        let result = loop {
+            // A resize leaves any partial line buffered for the next try
+            match self.line_reader.read_line(&mut **stream, &mut line) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    if stream
+                        .take_control_events()
+                        .iter()
+                        .any(ControlEvent::is_signal)
+                    {
+                        self.line_reader.clear();
+                        self.interrupted = true;
+                        break Ok(Some(String::new()));
+                    }
+                    if let Err(e) = self.handle_resize(stream) {
+                        break Err(std::io::Error::other(e.to_string()));
+                    }
+                }
+                Ok(0) => break Ok(None),
+                Ok(_) => break Ok(Some(line)),
+                Err(e) => break Err(e),
+            }
+        };
+        self.input_wait += started.elapsed();
+        self.screen = None;
+        result
+    }
+
+    /// Pick up the caller's new window size and redraw what they were
+    /// looking at to fit it
+    fn handle_resize(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let _ = stream.take_resize();
+        self.terminal_capabilities = stream.get_terminal_capabilities();
+        let width = self.calculate_effective_width();
+        if width == self.effective_width {
+            return Ok(());
+        }
+        self.effective_width = width;
+
+        if let Some(screen) = &self.screen {
+            self.draw_screen(stream, screen)?;
+            stream.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Draw `screen` at the current width
+    fn draw_screen(&self, stream: &mut SessionStream, screen: &Screen) -> BbsResult<()> {
+        match screen {
+            Screen::Menu { prompt } => {
+                let render = self.menu_get_current().render(self);
+                self.menu_show(stream, &render)?;
+                stream.queue(Print(prompt))?;
+                return Ok(());
+            }
+            Screen::Message {
+                title,
+                message,
+                color,
+            } => {
+                self.clear_screen(stream)?;
+                self.box_renderer.render_message_box(
+                    stream,
+                    title,
+                    message,
+                    self.effective_width,
+                    *color,
+                )?;
+            }
+            Screen::Lines {
+                title,
+                lines,
+                color,
+            } => {
+                self.clear_screen(stream)?;
+                self.box_renderer
+                    .render_box(stream, title, lines, self.effective_width, *color)?;
+            }
+            Screen::Page { title, text, page } => {
+                let pages = self.paginate(text);
+                let page = (*page).min(pages.len() - 1);
+                self.clear_screen(stream)?;
+                self.box_renderer.render_box(
+                    stream,
+                    &format!("{} ({}/{})", title, page + 1, pages.len()),
+                    &pages[page],
+                    self.effective_width,
+                    Some(Color::Cyan),
+                )?;
+                if page + 1 < pages.len() {
+                    stream.queue(Print("\nEnter for more, Q to stop: "))?;
+                    return Ok(());
+                }
+            }
+        }
+        stream.queue(Print("\nPress Enter to continue..."))?;
+        Ok(())
+    }
+
+    /// `text` wrapped to the window and cut into screenfuls
+    fn paginate(&self, text: &str) -> Vec<Vec<String>> {
+        let lines: Vec<String> = text
+            .lines()
+            .flat_map(|line| wrap_to_width(line, self.effective_width.saturating_sub(4)))
+            .collect();
+        // Leave room for the box and the prompt
+        let rows = self.terminal_capabilities.height.unwrap_or(24) as usize;
+        let page_len = rows.saturating_sub(6).max(5);
+        let pages: Vec<Vec<String>> = lines.chunks(page_len).map(<[String]>::to_vec).collect();
+        if pages.is_empty() {
+            vec![Vec::new()]
+        } else {
+            pages
+        }
+    }
+
+    /// Let the caller page through `text`, stopping early with Q
+    fn page_through(
+        &mut self,
+        stream: &mut SessionStream,
+        title: &str,
+        text: String,
+    ) -> BbsResult<()> {
+        let mut page = 0;
+        loop {
+            let screen = Screen::Page {
+                title: title.to_string(),
+                text: text.clone(),
+                page,
+            };
+            self.draw_screen(stream, &screen)?;
+            self.screen = Some(screen);
+            let input = self.get_input(stream, "")?;
+
+            // The window may have changed size while they were reading
+            page += 1;
+            if self.interrupted
+                || input.to_lowercase().starts_with('q')
+                || page >= self.paginate(&text).len()
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Show `screen` and wait for the caller to press Enter
+    fn pause_on(&mut self, stream: &mut SessionStream, screen: Screen) -> BbsResult<()> {
+        self.draw_screen(stream, &screen)?;
+        stream.flush()?;
+
+        self.screen = Some(screen);
+        let _ = self.read_caller(stream);
+
+        Ok(())
     }
 
     /// Process menu actions and update session state
     fn menu_handle_action(
         &mut self,
-        stream: &mut TelnetStream,
+        stream: &mut SessionStream,
         action: MenuAction,
     ) -> BbsResult<bool> {
         match action {
@@ -327,7 +1039,9 @@ impl BbsSession {
                 Ok(true)
             }
             MenuAction::Logout => {
-                self.user = None;
+                self.log_out();
+                self.unread_seen = None;
+                self.logon_summary = None;
                 self.show_message_with_stream(
                     stream,
                     "SYSTEM MESSAGE",
@@ -349,6 +1063,71 @@ impl BbsSession {
                 )?;
                 Ok(true)
             }
+            MenuAction::ShowClientFingerprint => {
+                self.show_client_fingerprint(stream)?;
+                Ok(true)
+            }
+            MenuAction::ShowBoardGuide => {
+                let guide = crate::guide::board_guide(&self.config, &self.services);
+                self.page_through(stream, "BOARD GUIDE", guide)?;
+                Ok(true)
+            }
+            MenuAction::ShowBoardStats => {
+                let username = self.user.as_ref().map(|u| u.username.as_str());
+                let stats = self
+                    .services
+                    .stats
+                    .stats_for(username, self.security_level())?;
+                self.board_stats = Some(stats);
+                self.menu_stats.area = None;
+                self.menu_current = Menu::Stats;
+                Ok(true)
+            }
+            MenuAction::StatsArea(area) => {
+                self.menu_stats.area = area;
+                Ok(true)
+            }
+            MenuAction::ToggleScreenReader => {
+                let enabled = !self.screen_reader_active();
+                if let Some(user) = &self.user {
+                    let updated = self
+                        .services
+                        .users
+                        .set_screen_reader(&user.username, Some(enabled))?;
+                    self.user = Some(updated);
+                } else {
+                    self.screen_reader_override = Some(enabled);
+                }
+                self.apply_output_mode(stream);
+                Ok(true)
+            }
+            #[cfg(feature = "scripting")]
+            MenuAction::RunScript(key) => {
+                self.run_script_command(stream, &key)?;
+                Ok(true)
+            }
+            MenuAction::SetDisplayName => {
+                self.handle_set_display_name(stream)?;
+                Ok(true)
+            }
+            MenuAction::SetMailForward => {
+                self.handle_set_mail_forward(stream)?;
+                Ok(true)
+            }
+            MenuAction::SetAutoReply => {
+                self.handle_set_auto_reply(stream)?;
+                Ok(true)
+            }
+            MenuAction::ToggleQuietMode => {
+                if let Some(user) = &self.user {
+                    let updated = self
+                        .services
+                        .users
+                        .set_quiet_mode(&user.username, !user.quiet_mode)?;
+                    self.user = Some(updated);
+                }
+                Ok(true)
+            }
 
             // Bulletin-specific actions
             MenuAction::BulletinPost => {
@@ -356,6 +1135,8 @@ impl BbsSession {
                 Ok(true)
             }
             MenuAction::BulletinRead(id) => {
+                // Also reached from the logon summary
+                self.menu_current = Menu::Bulletins;
                 self.handle_bulletin_read(stream, id)?;
                 Ok(true)
             }
@@ -368,7 +1149,20 @@ impl BbsSession {
                     crate::menu::menu_bulletin::BulletinMenuState::PostingContent(title);
                 Ok(true)
             }
+            MenuAction::BulletinPreview { title, content } => {
+                let issues = self
+                    .bulletin_request(title.clone(), content.clone())
+                    .issues(&self.config);
+                self.menu_bulletin.state =
+                    crate::menu::menu_bulletin::BulletinMenuState::Reviewing {
+                        title,
+                        content,
+                        issues,
+                    };
+                Ok(true)
+            }
             MenuAction::BulletinList => {
+                self.menu_current = Menu::Bulletins;
                 let bulletins = self.get_all_bulletins()?;
                 self.menu_bulletin.state =
                     crate::menu::menu_bulletin::BulletinMenuState::Listing(bulletins);
@@ -388,9 +1182,22 @@ impl BbsSession {
                 self.refresh_bulletin_stats()?;
                 Ok(true)
             }
+            MenuAction::BulletinMarkAllRead => {
+                self.handle_bulletin_mark_all_read()?;
+                Ok(true)
+            }
+            MenuAction::BulletinClubs => {
+                self.handle_bulletin_clubs(stream)?;
+                Ok(true)
+            }
+            MenuAction::BulletinLeaveClub => {
+                self.handle_club_leave(stream)?;
+                Ok(true)
+            }
 
             // Message-specific actions
             MenuAction::MessageInbox => {
+                self.menu_current = Menu::Messages;
                 let messages = self.get_user_inbox()?;
                 self.menu_message.state =
                     crate::menu::menu_message::MessageMenuState::Inbox(messages);
@@ -419,6 +1226,30 @@ impl BbsSession {
                 }
                 Ok(true)
             }
+            MenuAction::MessageEditContent { recipient, subject } => {
+                self.menu_message.state =
+                    crate::menu::menu_message::MessageMenuState::ComposeContent {
+                        recipient,
+                        subject,
+                    };
+                Ok(true)
+            }
+            MenuAction::MessagePreview {
+                recipient,
+                subject,
+                content,
+            } => {
+                let issues = self
+                    .message_request(recipient.clone(), subject.clone(), content.clone())
+                    .issues(&self.config);
+                self.menu_message.state = crate::menu::menu_message::MessageMenuState::Review {
+                    recipient,
+                    subject,
+                    content,
+                    issues,
+                };
+                Ok(true)
+            }
             MenuAction::MessageSend {
                 recipient,
                 subject,
@@ -435,77 +1266,126 @@ impl BbsSession {
                 self.handle_message_delete(stream, id)?;
                 Ok(true)
             }
+            MenuAction::MessageMarkAllRead => {
+                self.handle_message_mark_all_read()?;
+                Ok(true)
+            }
+            MenuAction::MessageDeleteRead => {
+                self.handle_message_delete_read(stream)?;
+                Ok(true)
+            }
             MenuAction::MessageBackToMenu => {
                 self.menu_message.state = crate::menu::menu_message::MessageMenuState::MainMenu;
                 Ok(true)
             }
+
+            MenuAction::GatewayConnect(index) => {
+                self.handle_gateway_connect(stream, index)?;
+                Ok(true)
+            }
+            MenuAction::RunCommand(index) => {
+                self.handle_external_command(stream, index)?;
+                Ok(true)
+            }
         }
     }
 
     /// Get user input with a prompt - telnet handling now automatic via TelnetStream
-    fn get_input(&mut self, stream: &mut TelnetStream, prompt: &str) -> BbsResult<String> {
+    fn get_input(&mut self, stream: &mut SessionStream, prompt: &str) -> BbsResult<String> {
         stream.queue(Print(prompt))?;
         stream.flush()?;
 
-        let mut buffer = [0; 1024];
-        match stream.read(&mut buffer) {
-            Ok(0) => Err(BbsError::ClientDisconnected),
-            Ok(n) => {
-                // Phase 5: TelnetStream automatically handles all telnet processing
-                // We only receive clean application data here
-                let input = String::from_utf8_lossy(&buffer[0..n]);
-                Ok(input.trim().to_string())
-            }
+        // TelnetStream handles the telnet side; this is the caller's text
+        match self.read_caller(stream) {
+            Ok(None) => Err(BbsError::ClientDisconnected),
+            Ok(Some(input)) => Ok(input.trim().to_string()),
             Err(e) => Err(BbsError::from(e)),
         }
     }
 
     /// Phase 7: Secure password input with echo control
+    /// Ask for a username, offering the client's USER as the default
+    fn get_username(&mut self, stream: &mut SessionStream, label: &str) -> BbsResult<String> {
+        let Some(suggested) = self.suggested_username.clone() else {
+            return self.get_input(stream, &format!("{}: ", label));
+        };
+
+        let username = self.get_input(stream, &format!("{} [{}]: ", label, suggested))?;
+        Ok(if username.is_empty() {
+            suggested
+        } else {
+            username
+        })
+    }
+
     fn secure_password_input(
         &mut self,
-        stream: &mut TelnetStream,
+        stream: &mut SessionStream,
         prompt: &str,
     ) -> BbsResult<String> {
-        // Disable echo for password security
-        let _ = stream.request_echo_off()?;
+        // Echo goes off for the prompt and back on after; clients that
+        // send each key see a star for each
+        let started = Instant::now();
+        self.interrupted = false;
+        self.line_reader.set_mask(Some('*'));
+        let result = self.line_reader.read_secret(&mut **stream, prompt);
+        self.line_reader.set_mask(None);
+        self.input_wait += started.elapsed();
+        self.screen = None;
 
-        // Display prompt
-        stream.queue(Print(prompt))?;
-        stream.flush()?;
-
-        let mut buffer = [0; 1024];
-        let result = match stream.read(&mut buffer) {
-            Ok(0) => Err(BbsError::ClientDisconnected),
-            Ok(n) => {
-                let input = String::from_utf8_lossy(&buffer[0..n]);
-                Ok(input.trim().to_string())
+        match result {
+            Ok(Some(input)) => Ok(input.trim().to_string()),
+            Ok(None) => Err(BbsError::ClientDisconnected),
+            // Ctrl+C, as in read_caller
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                let _ = stream.take_control_events();
+                self.interrupted = true;
+                Ok(String::new())
             }
             Err(e) => Err(BbsError::from(e)),
-        };
-
-        // Re-enable echo after password input
-        let _ = stream.request_echo_on()?;
-
-        // Add a newline since echo was off
-        stream.queue(Print("\n"))?;
-        stream.flush()?;
+        }
+    }
 
-        result
+    /// Start a fresh screen
+    ///
+    /// Screen readers lose their place when the cursor jumps around, so in
+    /// linear mode this only starts a new line. Laggy and minimal clients
+    /// get the same treatment rather than a full repaint.
+    fn clear_screen(&self, stream: &mut SessionStream) -> BbsResult<()> {
+        if self.box_renderer.is_linear() || !self.client_class.redraws_screen() {
+            stream.queue(Print("\r\n"))?;
+        } else {
+            stream.queue(Clear(ClearType::All))?;
+            stream.queue(cursor::MoveTo(0, 0))?;
+        }
+        Ok(())
     }
 
     /// Initialize terminal state
-    fn initialize_terminal(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+    fn initialize_terminal(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        self.clear_screen(stream)?;
         stream.flush()?;
         Ok(())
     }
 
-    /// Show the welcome screen
-    fn show_welcome(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+    /// Show this caller's `[motd]` notices, if any, in one box
+    fn show_connection_notices(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        if self.connection_notices.is_empty() {
+            return Ok(());
+        }
+        let message = format!("\n{}\n", self.connection_notices.join("\n\n"));
+        self.pause_on(
+            stream,
+            Screen::Message {
+                title: "NOTICE".to_string(),
+                message,
+                color: Some(Color::Yellow),
+            },
+        )
+    }
 
+    /// Show the welcome screen
+    fn show_welcome(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
         let welcome_msg = format!(
             r#"
 *  {}  *
@@ -521,27 +1401,159 @@ Location: {}
             self.config.bbs.location
         );
 
-        self.box_renderer.render_message_box(
+        self.pause_on(
             stream,
-            "WELCOME",
-            &welcome_msg,
-            self.effective_width,
-            Some(Color::Magenta),
-        )?;
+            Screen::Message {
+                title: "WELCOME".to_string(),
+                message: welcome_msg,
+                color: Some(Color::Magenta),
+            },
+        )
+    }
 
-        stream.queue(Print("\nPress Enter to continue..."))?;
-        stream.flush()?;
+    /// Retro: simulate a modem dialing in and connecting
+    fn show_modem_connect(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let speed = self.config.ui.retro_modem_speed;
+        let steps = [
+            ("ATZ", 400),
+            ("OK", 300),
+            ("ATDT 555-0199", 1200),
+            ("RINGING", 900),
+            ("RINGING", 900),
+        ];
+
+        for (line, pause_ms) in steps {
+            stream.queue(Print(format!("{}\r\n", line)))?;
+            stream.flush()?;
+            std::thread::sleep(Duration::from_millis(pause_ms));
+        }
 
-        let mut buffer = [0; 1024];
-        let _ = stream.read(&mut buffer);
+        stream.queue(SetForegroundColor(Color::Green))?;
+        stream.queue(Print(format!("CONNECT {}\r\n", speed)))?;
+        stream.queue(ResetColor)?;
+        stream.flush()?;
+        std::thread::sleep(Duration::from_millis(600));
 
         Ok(())
     }
 
+    /// Retro: pre-login matrix screen
+    ///
+    /// Returns false if the caller chose to hang up.
+    fn show_login_matrix(&mut self, stream: &mut SessionStream) -> BbsResult<bool> {
+        let timeout = self.config.timeouts.login_timeout;
+        stream.set_read_timeout(Some(timeout))?;
+
+        while self.user.is_none() {
+            if self.login_attempts >= 3 {
+                return Err(BbsError::AuthenticationFailed(
+                    "Too many failed login attempts".to_string(),
+                ));
+            }
+
+            self.clear_screen(stream)?;
+
+            let mut matrix = format!(
+                "{}\n\n[L] Login to your account\n[N] New user application",
+                self.config.bbs.name
+            );
+            if self.config.features.allow_anonymous {
+                matrix.push_str("\n[G] Guest access");
+            }
+            matrix.push_str("\n[H] Hang up");
+
+            self.box_renderer.render_message_box(
+                stream,
+                "LOGIN MATRIX",
+                &matrix,
+                self.effective_width,
+                Some(Color::Cyan),
+            )?;
+
+            let choice = self.get_input(stream, "\nMatrix: ")?;
+            match choice.to_lowercase().as_str() {
+                "l" => {
+                    self.login_attempts += 1;
+                    self.handle_existing_login(stream)?;
+                }
+                "n" => {
+                    if self.ask_security_question(stream)? {
+                        self.handle_registration(stream)?;
+                    } else {
+                        self.login_attempts += 1;
+                        self.show_message_with_stream(
+                            stream,
+                            "SECURITY CHECK",
+                            "That answer is not correct.",
+                            Some(Color::Red),
+                        )?;
+                    }
+                }
+                "g" if self.config.features.allow_anonymous => break,
+                "h" => return Ok(false),
+                _ => {}
+            }
+        }
+
+        stream.set_read_timeout(Some(self.config.timeouts.connection_timeout))?;
+        Ok(true)
+    }
+
+    /// Retro: simple security question asked before a new user application
+    fn ask_security_question(&mut self, stream: &mut SessionStream) -> BbsResult<bool> {
+        // Vary the question per call without pulling in a random number crate
+        let seed = jiff::Timestamp::now().subsec_nanosecond().unsigned_abs();
+        let a = seed % 9 + 1;
+        let b = (seed / 10) % 9 + 1;
+
+        let answer = self.get_input(
+            stream,
+            &format!("\nSecurity question: what is {} plus {}? ", a, b),
+        )?;
+
+        Ok(answer.parse::<u32>().ok() == Some(a + b))
+    }
+
+    /// Retro: show the most recent callers before the main menu
+    fn show_last_callers(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let current = self.user.as_ref().map(|u| u.username.clone());
+        let callers: Vec<User> = self
+            .services
+            .users
+            .get_recent_logins(11)?
+            .into_iter()
+            .filter(|u| Some(&u.username) != current.as_ref())
+            .take(10)
+            .collect();
+
+        let mut lines = vec![String::new()];
+        if callers.is_empty() {
+            lines.push("You are the first caller. Welcome aboard!".to_string());
+        } else {
+            lines.extend(callers.iter().enumerate().map(|(i, u)| {
+                format!(
+                    "{:>2}. {} {}",
+                    i + 1,
+                    pad_to_width(&u.username, 20),
+                    u.last_login_display()
+                )
+            }));
+        }
+        lines.push(String::new());
+
+        self.pause_on(
+            stream,
+            Screen::Lines {
+                title: "LAST CALLERS".to_string(),
+                lines,
+                color: Some(Color::Cyan),
+            },
+        )
+    }
+
     /// Handle user login process
-    fn handle_login(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+    fn handle_login(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        self.clear_screen(stream)?;
 
         let instructions = "Choose an option:\n\n[L] Login with existing account\n[R] Register new account\n[C] Cancel";
         self.box_renderer.render_message_box(
@@ -579,9 +1591,8 @@ Location: {}
     }
 
     /// Handle login for existing user
-    fn handle_existing_login(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+    fn handle_existing_login(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        self.clear_screen(stream)?;
 
         self.box_renderer.render_message_box(
             stream,
@@ -591,7 +1602,7 @@ Location: {}
             Some(Color::Cyan),
         )?;
 
-        let username = self.get_input(stream, "\nUsername: ")?;
+        let username = self.get_username(stream, "\nUsername")?;
         if username.is_empty() {
             self.show_message_with_stream(
                 stream,
@@ -619,10 +1630,12 @@ Location: {}
         match registration_result? {
             Some(user) => {
                 self.user = Some(user.clone());
+                self.announce_login();
                 let welcome_msg = format!(
                     "Welcome back, {}!\n\nLast login: {}\nTotal logins: {}",
                     user.username,
-                    user.last_login_display(),
+                    user.previous_login_display()
+                        .unwrap_or_else(|| "never".to_string()),
                     user.login_count
                 );
                 self.show_message_with_stream(
@@ -630,7 +1643,10 @@ Location: {}
                     "LOGIN SUCCESS",
                     &welcome_msg,
                     Some(Color::Green),
-                )
+                )?;
+                #[cfg(feature = "scripting")]
+                self.run_script_hook(stream, ScriptEvent::Login)?;
+                self.prepare_logon_summary()
             }
             None => self.show_message_with_stream(
                 stream,
@@ -642,13 +1658,17 @@ Location: {}
     }
 
     /// Handle new user registration
-    fn handle_registration(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
-
+    fn handle_registration(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        self.clear_screen(stream)?;
+
+        let rules = self
+            .config
+            .features
+            .usernames
+            .describe(self.config.features.max_username_length);
         let instructions = format!(
-            "Create your account:\n\nUsername rules:\n- 1-{} characters\n- Letters, numbers, underscore only\n- Must be unique",
-            self.config.features.max_username_length
+            "Create your account:\n\nUsername rules:\n- {}",
+            rules.join("\n- ")
         );
         self.box_renderer.render_message_box(
             stream,
@@ -690,8 +1710,13 @@ Location: {}
             Some(email_input)
         };
 
+        // Get optional display name
+        let display_name = self.get_input(stream, "Display name (optional): ")?;
+        let display_name = (!display_name.is_empty()).then_some(display_name);
+
         // Create registration request
-        let request = RegistrationRequest::new(username.clone(), email, password);
+        let request = RegistrationRequest::new(username.clone(), email, password)
+            .with_display_name(display_name);
 
         // Attempt registration
         let registration_result = self.services.users.register(request, &self.config);
@@ -699,6 +1724,7 @@ Location: {}
         match registration_result {
             Ok(user) => {
                 self.user = Some(user.clone());
+                self.announce_login();
                 let success_msg = format!(
                     "Registration successful!\n\nWelcome to {}, {}!\nYour account has been created and you are now logged in.",
                     self.config.bbs.name, user.username
@@ -708,7 +1734,10 @@ Location: {}
                     "REGISTRATION SUCCESS",
                     &success_msg,
                     Some(Color::Green),
-                )
+                )?;
+                #[cfg(feature = "scripting")]
+                self.run_script_hook(stream, ScriptEvent::NewUser)?;
+                Ok(())
             }
             Err(e) => {
                 let error_msg = format!("Registration failed: {}", e);
@@ -722,12 +1751,95 @@ Location: {}
         }
     }
 
+    /// Change or clear the logged-in user's display name
+    fn handle_set_display_name(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let Some(username) = self.user.as_ref().map(|u| u.username.clone()) else {
+            return Ok(());
+        };
+
+        let input = self.get_input(stream, "\nNew display name (blank to clear): ")?;
+        match self
+            .services
+            .users
+            .set_display_name(&username, &input, &self.config)
+        {
+            Ok(user) => {
+                let message = match &user.display_name {
+                    Some(name) => format!("Your posts will now show as '{}'.", name),
+                    None => "Display name cleared; your username will be shown.".to_string(),
+                };
+                self.user = Some(user);
+                self.show_message_with_stream(stream, "DISPLAY NAME", &message, Some(Color::Green))
+            }
+            Err(e) => self.show_message_with_stream(
+                stream,
+                "DISPLAY NAME",
+                &format!("Could not change display name: {}", e),
+                Some(Color::Red),
+            ),
+        }
+    }
+
+    /// Change or stop forwarding of the logged-in user's private messages
+    fn handle_set_mail_forward(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let Some(username) = self.user.as_ref().map(|u| u.username.clone()) else {
+            return Ok(());
+        };
+
+        let input = self.get_input(stream, "\nForward messages to (blank to stop): ")?;
+        match self.services.users.set_forward_to(&username, &input) {
+            Ok(user) => {
+                let message = match &user.forward_to {
+                    Some(target) => format!("{} will get a copy of your private messages.", target),
+                    None => "Messages are no longer forwarded.".to_string(),
+                };
+                self.user = Some(user);
+                self.show_message_with_stream(stream, "FORWARDING", &message, Some(Color::Green))
+            }
+            Err(e) => self.show_message_with_stream(
+                stream,
+                "FORWARDING",
+                &format!("Could not change forwarding: {}", e),
+                Some(Color::Red),
+            ),
+        }
+    }
+
+    /// Set or clear the logged-in user's vacation reply
+    fn handle_set_auto_reply(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let Some(username) = self.user.as_ref().map(|u| u.username.clone()) else {
+            return Ok(());
+        };
+
+        let input = self.get_input(stream, "\nVacation reply (blank to turn off): ")?;
+        match self
+            .services
+            .users
+            .set_auto_reply(&username, &input, &self.config)
+        {
+            Ok(user) => {
+                let message = if user.auto_reply.is_some() {
+                    "People who message you will get your reply, once a day each."
+                } else {
+                    "Vacation reply turned off."
+                };
+                self.user = Some(user);
+                self.show_message_with_stream(stream, "VACATION REPLY", message, Some(Color::Green))
+            }
+            Err(e) => self.show_message_with_stream(
+                stream,
+                "VACATION REPLY",
+                &format!("Could not change vacation reply: {}", e),
+                Some(Color::Red),
+            ),
+        }
+    }
+
     /// Force login for restricted BBS
-    fn force_login(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
+    fn force_login(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
         let message = "This BBS requires registration to access. Anonymous access has been disabled by the SysOp.";
 
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+        self.clear_screen(stream)?;
 
         self.box_renderer.render_message_box(
             stream,
@@ -757,12 +1869,12 @@ Location: {}
     }
 
     /// Single login attempt
-    fn attempt_login(&mut self, stream: &mut TelnetStream) -> BbsResult<bool> {
+    fn attempt_login(&mut self, stream: &mut SessionStream) -> BbsResult<bool> {
         self.login_attempts += 1;
 
-        let username = self.get_input(
+        let username = self.get_username(
             stream,
-            &format!("Login attempt {}/3\nUsername: ", self.login_attempts),
+            &format!("Login attempt {}/3\nUsername", self.login_attempts),
         )?;
 
         if username.len() > self.config.features.max_username_length {
@@ -783,11 +1895,15 @@ Location: {}
             match self.services.users.authenticate(&username, &password)? {
                 Some(user) => {
                     self.user = Some(user.clone());
+                    self.announce_login();
                     stream.queue(SetForegroundColor(Color::Green))?;
                     stream.queue(Print(&format!("Welcome, {}!\n\n", user.username)))?;
                     stream.queue(ResetColor)?;
                     stream.flush()?;
                     std::thread::sleep(Duration::from_secs(1));
+                    #[cfg(feature = "scripting")]
+                    self.run_script_hook(stream, ScriptEvent::Login)?;
+                    self.prepare_logon_summary()?;
                     return Ok(false);
                 }
                 None => {
@@ -804,9 +1920,8 @@ Location: {}
     }
 
     /// Display a rendered menu
-    fn menu_show(&self, stream: &mut TelnetStream, render: &MenuRender) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+    fn menu_show(&self, stream: &mut SessionStream, render: &MenuRender) -> BbsResult<()> {
+        self.clear_screen(stream)?;
         self.box_renderer.render_menu(
             stream,
             &render.title,
@@ -820,35 +1935,117 @@ Location: {}
     /// Display a message box with stream
     fn show_message_with_stream(
         &mut self,
-        stream: &mut TelnetStream,
+        stream: &mut SessionStream,
         title: &str,
         message: &str,
         color: Option<Color>,
     ) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
-
-        self.box_renderer.render_message_box(
+        self.pause_on(
             stream,
-            title,
-            message,
-            self.effective_width,
-            color,
-        )?;
-
-        stream.queue(Print("\nPress Enter to continue..."))?;
-        stream.flush()?;
+            Screen::Message {
+                title: title.to_string(),
+                message: message.to_string(),
+                color,
+            },
+        )
+    }
 
-        let mut buffer = [0; 1024];
-        let _ = stream.read(&mut buffer);
+    /// SysOp view of what this connection's client negotiated
+    fn show_client_fingerprint(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        // A fresh probe, since negotiation timings are from connect time
+        let ping = stream.measure_round_trip(Duration::from_secs(2))?;
+        let caps = stream.get_terminal_capabilities();
+        let history = stream.negotiation_history();
+        let stats = stream.negotiation_stats();
+
+        let mut lines = vec![
+            String::new(),
+            format!(
+                "Terminal: {}",
+                caps.terminal_type.as_deref().unwrap_or("unknown")
+            ),
+            format!(
+                "Size:     {}",
+                match (caps.width, caps.height) {
+                    (Some(w), Some(h)) => format!("{}x{}", w, h),
+                    _ => "unknown".to_string(),
+                }
+            ),
+            format!(
+                "Colors:   {:?} (sending {:?})",
+                caps.color_depth,
+                stream.color_depth()
+            ),
+            format!("Charset:  {}", caps.charset.as_deref().unwrap_or("unknown")),
+            format!(
+                "Reader:   {}",
+                match (caps.screen_reader, self.screen_reader_active()) {
+                    (true, _) => "reported by client",
+                    (false, true) => "on (user choice)",
+                    (false, false) => "no",
+                }
+            ),
+            format!(
+                "TLS:      {}",
+                if stream.is_tls_active() {
+                    "yes (START_TLS)"
+                } else if stream.get_ref().is_tls() {
+                    "yes (TLS port)"
+                } else {
+                    "no"
+                }
+            ),
+            format!(
+                "Latency:  {} ({}/{} answered, {})",
+                match stats.median_round_trip() {
+                    Some(round_trip) => format!("{}ms median", round_trip.as_millis()),
+                    None => "unknown".to_string(),
+                },
+                stats.answered(),
+                stats.timings.len(),
+                self.client_class
+            ),
+            format!(
+                "Ping:     {}",
+                match ping {
+                    Some(round_trip) => format!("{}ms (timing mark)", round_trip.as_millis()),
+                    None => "no answer".to_string(),
+                }
+            ),
+            String::new(),
+            format!("Negotiation history ({} total):", history.total_recorded()),
+        ];
+
+        for record in history.chronological() {
+            let arrow = match record.direction {
+                Direction::Received => "<-",
+                Direction::Sent => "->",
+            };
+            lines.push(format!(
+                "{:>6}ms {} {:?} {:?} (L:{:?} R:{:?})",
+                record.elapsed.as_millis(),
+                arrow,
+                record.command,
+                record.option,
+                record.local_state,
+                record.remote_state
+            ));
+        }
+        lines.push(String::new());
 
-        Ok(())
+        self.pause_on(
+            stream,
+            Screen::Lines {
+                title: "CLIENT FINGERPRINT".to_string(),
+                lines,
+                color: Some(Color::Cyan),
+            },
+        )
     }
 
     /// Show goodbye screen
-    fn show_goodbye(&mut self, stream: &mut TelnetStream) -> BbsResult<()> {
-        stream.queue(Clear(ClearType::All))?;
-        stream.queue(cursor::MoveTo(0, 0))?;
+    fn show_goodbye(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        self.clear_screen(stream)?;
 
         let goodbye_msg = format!(
             "Thanks for visiting {}!\n\nSysOp: {}\n\n* Come back anytime! *",
@@ -871,15 +2068,33 @@ Location: {}
     }
 
     /// Handle bulletin reading
-    fn handle_bulletin_read(&mut self, stream: &mut TelnetStream, id: u32) -> BbsResult<()> {
+    fn handle_bulletin_read(&mut self, stream: &mut SessionStream, id: u32) -> BbsResult<()> {
         // Load bulletin from storage
-        let bulletin = self.services.bulletins.get_bulletin(id)?;
+        let bulletin = match self.services.bulletins.get_bulletin(
+            id,
+            self.user.as_ref().map(|u| u.username.as_str()),
+            self.security_level(),
+        ) {
+            Ok(bulletin) => bulletin,
+            Err(e @ (BbsError::PermissionDenied(_) | BbsError::FeatureDisabled(_))) => {
+                self.show_message_with_stream(
+                    stream,
+                    "ACCESS DENIED",
+                    &e.to_string(),
+                    Some(Color::Red),
+                )?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
 
         match bulletin {
             Some(bulletin) => {
                 // Mark as read for logged-in users
                 if let Some(user) = &self.user {
-                    self.services.bulletins.mark_read(id, &user.username)?;
+                    let updated = self.services.users.mark_bulletin_read(&user.username, id)?;
+                    self.user = Some(updated);
+                    self.skip_unreadable_bulletins()?;
                 }
 
                 // Set menu to reading state
@@ -905,20 +2120,84 @@ Location: {}
         }
     }
 
+    /// Keep bulletins the caller can't read from holding back their
+    /// high-water mark, once they've read past them
+    fn skip_unreadable_bulletins(&mut self) -> BbsResult<()> {
+        let Some(user) = &self.user else {
+            return Ok(());
+        };
+        if user.bulletin_reads.read_above.is_empty() {
+            return Ok(());
+        }
+        let readable = self.services.bulletins.readable_after(
+            user.bulletin_reads.high_water,
+            Some(&user.username),
+            self.security_level(),
+        )?;
+        let updated = self
+            .services
+            .users
+            .skip_unreadable_bulletins(&user.username, &readable)?;
+        self.user = Some(updated);
+        Ok(())
+    }
+
+    /// Mark the listing on screen, or else the whole area, read in one go
+    fn handle_bulletin_mark_all_read(&mut self) -> BbsResult<()> {
+        let Some(username) = self.user.as_ref().map(|u| u.username.clone()) else {
+            return Ok(());
+        };
+        let listed = match &self.menu_bulletin.state {
+            crate::menu::menu_bulletin::BulletinMenuState::Listing(list) => {
+                Some(list.iter().map(|b| b.id).collect::<Vec<_>>())
+            }
+            _ => None,
+        };
+        let ids = match &listed {
+            Some(ids) => ids.clone(),
+            None => self.get_all_bulletins()?.iter().map(|b| b.id).collect(),
+        };
+
+        let updated = self.services.users.mark_bulletins_read(&username, &ids)?;
+        self.user = Some(updated);
+        self.skip_unreadable_bulletins()?;
+
+        if let Some(ids) = listed {
+            let bulletins =
+                self.services
+                    .bulletins
+                    .get_many(&ids, Some(&username), self.security_level())?;
+            self.menu_bulletin.state =
+                crate::menu::menu_bulletin::BulletinMenuState::Listing(bulletins);
+        }
+        self.refresh_bulletin_stats()
+    }
+
+    /// A bulletin from this caller in the current area
+    fn bulletin_request(
+        &self,
+        title: String,
+        content: String,
+    ) -> crate::bulletins::BulletinRequest {
+        crate::bulletins::BulletinRequest::new(title, content, self.display_username())
+            .with_author_display(self.display_name())
+            .with_area(self.menu_bulletin.area.clone())
+    }
+
     /// Handle bulletin submission
     fn handle_bulletin_submit(
         &mut self,
-        stream: &mut TelnetStream,
+        stream: &mut SessionStream,
         title: String,
         content: String,
     ) -> BbsResult<()> {
-        let author = self.display_username();
-
-        // Create bulletin request
-        let request = crate::bulletins::BulletinRequest::new(title.clone(), content, author);
+        let request = self.bulletin_request(title.clone(), content.clone());
 
         // Post bulletin
-        let result = self.services.bulletins.post_bulletin(request, &self.config);
+        let result =
+            self.services
+                .bulletins
+                .post_bulletin(request, &self.config, self.security_level());
 
         match result {
             Ok(bulletin_id) => {
@@ -932,11 +2211,24 @@ Location: {}
                     Some(Color::Green),
                 )?;
 
+                #[cfg(feature = "scripting")]
+                self.run_script_hook(stream, ScriptEvent::Post { bulletin_id, title })?;
+
                 // Reset menu state and refresh stats
                 self.menu_bulletin.state = crate::menu::menu_bulletin::BulletinMenuState::MainMenu;
                 self.refresh_bulletin_stats()?;
                 Ok(())
             }
+            Err(BbsError::InvalidPost(issues)) => {
+                // Back to the preview, with what needs fixing
+                self.menu_bulletin.state =
+                    crate::menu::menu_bulletin::BulletinMenuState::Reviewing {
+                        title,
+                        content,
+                        issues,
+                    };
+                Ok(())
+            }
             Err(e) => {
                 self.show_message_with_stream(
                     stream,
@@ -949,27 +2241,428 @@ Location: {}
         }
     }
 
-    /// Refresh bulletin statistics
-    fn refresh_bulletin_stats(&mut self) -> BbsResult<()> {
-        let current_user = self.user.as_ref().map(|u| u.username.as_str());
+    /// Who SysOp scripts run as for this session
+    #[cfg(feature = "scripting")]
+    fn script_caller(&self) -> ScriptCaller<'_> {
+        ScriptCaller {
+            username: self.user.as_ref().map(|u| u.username.as_str()),
+            level: self.security_level(),
+        }
+    }
 
-        let stats = self.services.bulletins.get_stats(current_user)?;
+    /// Run the script hook for `event` and show anything it printed
+    #[cfg(feature = "scripting")]
+    fn run_script_hook(&mut self, stream: &mut SessionStream, event: ScriptEvent) -> BbsResult<()> {
+        let Some(scripts) = self.services.scripts.clone() else {
+            return Ok(());
+        };
+        let lines = scripts.run_hook(&event, self.script_caller(), &self.services, &self.config);
+        self.show_script_output(stream, "NOTICE", &lines)
+    }
+
+    /// Run a custom main menu command from a SysOp script
+    #[cfg(feature = "scripting")]
+    fn run_script_command(&mut self, stream: &mut SessionStream, key: &str) -> BbsResult<()> {
+        let Some(scripts) = self.services.scripts.clone() else {
+            return Ok(());
+        };
+        let Some(title) = scripts
+            .commands()
+            .find(|c| c.key == key)
+            .map(|c| c.description.to_uppercase())
+        else {
+            return Ok(());
+        };
+
+        let lines = scripts
+            .run_command(key, self.script_caller(), &self.services, &self.config)
+            .unwrap_or_default();
+        self.show_script_output(stream, &title, &lines)
+    }
+
+    #[cfg(feature = "scripting")]
+    fn show_script_output(
+        &mut self,
+        stream: &mut SessionStream,
+        title: &str,
+        lines: &[String],
+    ) -> BbsResult<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        self.show_message_with_stream(stream, title, &lines.join("\n"), Some(Color::Cyan))
+    }
+
+    /// Connect the caller to another board and relay until the call ends
+    fn handle_gateway_connect(
+        &mut self,
+        stream: &mut SessionStream,
+        index: usize,
+    ) -> BbsResult<()> {
+        let gateway = &self.config.gateway;
+        let Some(board) = gateway.boards.get(index).cloned() else {
+            return Ok(());
+        };
+        if !gateway.enabled || self.security_level() < gateway.level {
+            return Ok(());
+        }
+        let limit = Duration::from_secs(gateway.max_minutes * 60);
+        let caller = self.display_username();
+
+        self.clear_screen(stream)?;
+        stream.queue(Print(format!(
+            "Connecting to {} ({})...\r\nPress Ctrl-] to return to {}.\r\n\r\n",
+            board.name, board.address, self.config.bbs.name
+        )))?;
+        stream.flush()?;
+
+        let mut remote = match crate::gateway::connect(&board, &self.terminal_capabilities) {
+            Ok(remote) => remote,
+            Err(e) => {
+                eprintln!(
+                    "! Gateway: {} could not reach {} ({}): {}",
+                    caller, board.name, board.address, e
+                );
+                return self.show_message_with_stream(
+                    stream,
+                    "OTHER BOARDS",
+                    &format!("Could not reach {}: {}", board.name, e),
+                    Some(Color::Red),
+                );
+            }
+        };
+        println!(
+            "> Gateway: {} connected to {} ({})",
+            caller, board.name, board.address
+        );
+
+        let report = crate::gateway::proxy(stream, &mut remote, limit)?;
+        println!(
+            "> Gateway: {} left {} after {}s ({}, {} bytes in, {} bytes out)",
+            caller,
+            board.name,
+            report.duration.as_secs(),
+            report.end.describe(),
+            report.bytes_in,
+            report.bytes_out
+        );
+
+        let reason = match report.end {
+            GatewayEnd::CallerDisconnected => return Err(BbsError::ClientDisconnected),
+            GatewayEnd::RemoteClosed => format!("{} closed the connection.", board.name),
+            GatewayEnd::Escaped => format!("You left {}.", board.name),
+            GatewayEnd::TimeLimit => format!(
+                "Your {} minute gateway limit is up.",
+                self.config.gateway.max_minutes
+            ),
+        };
+        // Catch up on any resize the caller made during the call
+        self.handle_resize(stream)?;
+        // The other board may have left colors or the cursor anywhere
+        stream.queue(ResetColor)?;
+        self.show_message_with_stream(
+            stream,
+            "OTHER BOARDS",
+            &format!("{}\n\nWelcome back to {}.", reason, self.config.bbs.name),
+            Some(Color::Yellow),
+        )
+    }
+
+    /// Run a `[commands]` program and page the caller through its output
+    fn handle_external_command(
+        &mut self,
+        stream: &mut SessionStream,
+        index: usize,
+    ) -> BbsResult<()> {
+        let config = Arc::clone(&self.config);
+        let Some(command) = config.commands.commands.get(index) else {
+            return Ok(());
+        };
+        if self.security_level() < command.level {
+            return Ok(());
+        }
+        let title = command.description.to_uppercase();
+        let caller = CommandCaller {
+            username: self.user.as_ref().map(|u| u.username.as_str()),
+            level: self.security_level(),
+            node: self.presence_id.unwrap_or(0),
+            bbs_name: &config.bbs.name,
+        };
+
+        let started = Instant::now();
+        let output = match crate::external::run(command, &config.commands, caller) {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!(
+                    "! Command: {} could not run {} ({}): {}",
+                    self.display_username(),
+                    command.key.to_uppercase(),
+                    command.program,
+                    e
+                );
+                return self.show_message_with_stream(
+                    stream,
+                    &title,
+                    "That command is not available right now.",
+                    Some(Color::Red),
+                );
+            }
+        };
+        println!(
+            "> Command: {} ran {} ({}) in {}ms{}",
+            self.display_username(),
+            command.key.to_uppercase(),
+            command.program,
+            started.elapsed().as_millis(),
+            if output.timed_out { ", timed out" } else { "" }
+        );
+
+        let mut text = output.text;
+        if output.truncated {
+            text.push_str("\n[Output cut short]");
+        }
+        if output.timed_out {
+            text.push_str(&format!(
+                "\n[Stopped after {} seconds]",
+                config.commands.timeout.as_secs()
+            ));
+        }
+        if text.trim().is_empty() {
+            text = "(No output)".to_string();
+        }
+        self.page_through(stream, &title, text)
+    }
+
+    /// Refresh bulletin statistics for the area being browsed
+    fn refresh_bulletin_stats(&mut self) -> BbsResult<()> {
+        let level = self.security_level();
+        let username = self.user.as_ref().map(|u| u.username.as_str());
+        let reads = self.user.as_ref().map(|u| &u.bulletin_reads);
+
+        let stats = match self.services.bulletins.get_area_stats(
+            reads,
+            self.menu_bulletin.area.as_deref(),
+            username,
+            level,
+        ) {
+            Ok(stats) => stats,
+            // Membership lost mid-visit (left, or the club went away)
+            Err(BbsError::PermissionDenied(_)) => {
+                self.menu_bulletin.area = None;
+                self.services.bulletins.get_stats(reads)?
+            }
+            Err(e) => return Err(e),
+        };
 
         self.bulletin_stats = Some(stats);
         Ok(())
     }
 
-    /// Refresh bulletin statistics
+    /// Bulletins in the area being browsed
     fn get_all_bulletins(&mut self) -> BbsResult<Vec<Bulletin>> {
-        // This method is not currently used, but keeping for potential future use
-        // Would need to be implemented if needed
-        todo!("get_all_bulletins not implemented for service layer")
+        self.services.bulletins.list_bulletins(
+            self.menu_bulletin.area.as_deref(),
+            self.user.as_ref().map(|u| u.username.as_str()),
+            self.security_level(),
+        )
+    }
+
+    /// Switch the bulletin menu to a club, or back to the main board
+    fn enter_area(&mut self, area: Option<String>) -> BbsResult<()> {
+        self.menu_bulletin.area = area;
+        self.menu_bulletin.state = crate::menu::menu_bulletin::BulletinMenuState::MainMenu;
+        self.refresh_bulletin_stats()
+    }
+
+    /// List clubs and let the caller pick one to browse or join
+    fn handle_bulletin_clubs(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let clubs = self.services.clubs.list_clubs()?;
+        let is_sysop = self.security_level() >= SecurityLevel::Sysop;
+        let username = self.user.as_ref().map(|u| u.username.clone());
+
+        let mut listing = String::new();
+        if clubs.is_empty() {
+            listing.push_str("No clubs yet.\n");
+        }
+        for club in &clubs {
+            let status = match username.as_deref() {
+                Some(name) if club.is_member(name) => "member",
+                Some(name) if club.is_pending(name) => "awaiting approval",
+                _ if club.needs_password() => "password",
+                _ => "by approval",
+            };
+            listing.push_str(&format!("{} ({})\n", club.name, status));
+            if !club.description.is_empty() {
+                listing.push_str(&format!("  {}\n", club.description));
+            }
+        }
+        listing.push_str("\nEnter a club name, or blank for the main board.");
+        if is_sysop {
+            let pending: usize = clubs.iter().map(|c| c.pending.len()).sum();
+            listing.push_str(&format!(
+                "\n[+] Start a club  [?] Review requests ({} waiting)",
+                pending
+            ));
+        }
+
+        self.clear_screen(stream)?;
+        self.box_renderer.render_message_box(
+            stream,
+            "CLUBS",
+            &listing,
+            self.effective_width,
+            Some(Color::Cyan),
+        )?;
+
+        let choice = self.get_input(stream, "\nClub: ")?;
+        match choice.as_str() {
+            "" => self.enter_area(None),
+            "+" if is_sysop => self.handle_club_create(stream),
+            "?" if is_sysop => self.handle_club_requests(stream),
+            name => match clubs.into_iter().find(|c| c.is_named(name)) {
+                Some(club) => self.handle_club_enter(stream, club),
+                None => self.show_message_with_stream(
+                    stream,
+                    "CLUBS",
+                    &format!("No club named '{}'.", name),
+                    Some(Color::Red),
+                ),
+            },
+        }
+    }
+
+    /// Enter a club, joining it first if need be
+    fn handle_club_enter(
+        &mut self,
+        stream: &mut SessionStream,
+        club: crate::clubs::Club,
+    ) -> BbsResult<()> {
+        let Some(username) = self.user.as_ref().map(|u| u.username.clone()) else {
+            return self.show_message_with_stream(
+                stream,
+                "CLUBS",
+                "Log in to join clubs.",
+                Some(Color::Yellow),
+            );
+        };
+
+        if club.is_member(&username) || self.security_level() >= SecurityLevel::Sysop {
+            return self.enter_area(Some(club.name));
+        }
+
+        if club.needs_password() {
+            let password = self.secure_password_input(stream, "Club password: ")?;
+            if password.is_empty() {
+                return Ok(());
+            }
+            match self
+                .services
+                .clubs
+                .join_with_password(&club.name, &username, &password)
+            {
+                Ok(()) => self.enter_area(Some(club.name)),
+                Err(e) => {
+                    self.show_message_with_stream(stream, "CLUBS", &e.to_string(), Some(Color::Red))
+                }
+            }
+        } else if club.is_pending(&username) {
+            self.show_message_with_stream(
+                stream,
+                "CLUBS",
+                "Your request to join is waiting for the SysOp.",
+                Some(Color::Yellow),
+            )
+        } else {
+            let answer = self.get_input(stream, &format!("Ask to join {}? (y/N): ", club.name))?;
+            if !answer.eq_ignore_ascii_case("y") {
+                return Ok(());
+            }
+            self.services
+                .clubs
+                .request_membership(&club.name, &username)?;
+            self.show_message_with_stream(
+                stream,
+                "CLUBS",
+                "Request sent. The SysOp will review it.",
+                Some(Color::Green),
+            )
+        }
+    }
+
+    /// Start a new club (SysOp only)
+    fn handle_club_create(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let name = self.get_input(stream, "Club name: ")?;
+        if name.is_empty() {
+            return Ok(());
+        }
+        let description = self.get_input(stream, "Description: ")?;
+        let password =
+            self.secure_password_input(stream, "Join password (blank for SysOp approval): ")?;
+        let password = (!password.is_empty()).then_some(password);
+
+        let request = crate::clubs::ClubRequest::new(name, description, password);
+        let creator = self.display_username();
+        match self
+            .services
+            .clubs
+            .create_club(request, &creator, self.security_level())
+        {
+            Ok(club) => self.show_message_with_stream(
+                stream,
+                "CLUBS",
+                &format!("Club '{}' is open.", club.name),
+                Some(Color::Green),
+            ),
+            Err(e) => {
+                self.show_message_with_stream(stream, "CLUBS", &e.to_string(), Some(Color::Red))
+            }
+        }
+    }
+
+    /// Work through pending join requests (SysOp only)
+    fn handle_club_requests(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let level = self.security_level();
+        for club in self.services.clubs.list_clubs()? {
+            for username in &club.pending {
+                let answer = self.get_input(
+                    stream,
+                    &format!(
+                        "{} wants to join {} - [A]pprove, [D]ecline, [S]kip: ",
+                        username, club.name
+                    ),
+                )?;
+                match answer.to_lowercase().as_str() {
+                    "a" => self.services.clubs.approve(&club.name, username, level)?,
+                    "d" => self.services.clubs.decline(&club.name, username, level)?,
+                    _ => {}
+                }
+            }
+        }
+        self.show_message_with_stream(stream, "CLUBS", "No more requests.", Some(Color::Green))
+    }
+
+    /// Give up membership of the club being browsed
+    fn handle_club_leave(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let (Some(area), Some(username)) = (
+            self.menu_bulletin.area.clone(),
+            self.user.as_ref().map(|u| u.username.clone()),
+        ) else {
+            return Ok(());
+        };
+        let answer = self.get_input(stream, &format!("Leave {}? (y/N): ", area))?;
+        if !answer.eq_ignore_ascii_case("y") {
+            return Ok(());
+        }
+        self.services.clubs.leave(&area, &username)?;
+        self.enter_area(None)
     }
 
     /// Get user's inbox messages
     fn get_user_inbox(&self) -> BbsResult<Vec<crate::messages::PrivateMessage>> {
         if let Some(user) = &self.user {
-            self.services.messages.get_inbox(&user.username)
+            self.services
+                .messages
+                .get_inbox(&user.username, self.security_level())
         } else {
             Ok(Vec::new())
         }
@@ -978,16 +2671,29 @@ Location: {}
     /// Get user's sent messages
     fn get_user_sent_messages(&self) -> BbsResult<Vec<crate::messages::PrivateMessage>> {
         if let Some(user) = &self.user {
-            self.services.messages.get_sent(&user.username)
+            self.services
+                .messages
+                .get_sent(&user.username, self.security_level())
         } else {
             Ok(Vec::new())
         }
     }
 
+    /// A private message from this caller
+    fn message_request(
+        &self,
+        recipient: String,
+        subject: String,
+        content: String,
+    ) -> crate::messages::MessageRequest {
+        crate::messages::MessageRequest::new(recipient, subject, content, self.display_username())
+            .with_sender_display(self.display_name())
+    }
+
     /// Handle sending a private message
     fn handle_message_send(
         &mut self,
-        stream: &mut TelnetStream,
+        stream: &mut SessionStream,
         recipient: String,
         subject: String,
         content: String,
@@ -1004,16 +2710,13 @@ Location: {}
             return Ok(());
         }
 
-        // Create message request
-        let request = crate::messages::MessageRequest::new(
-            recipient.clone(),
-            subject.clone(),
-            content,
-            sender,
-        );
+        let request = self.message_request(recipient.clone(), subject.clone(), content.clone());
 
         // Send message
-        let result = self.services.messages.send_message(request, &self.config);
+        let result =
+            self.services
+                .messages
+                .send_message(request, &self.config, self.security_level());
 
         match result {
             Ok(message_id) => {
@@ -1031,6 +2734,16 @@ Location: {}
                 self.menu_message.state = crate::menu::menu_message::MessageMenuState::MainMenu;
                 Ok(())
             }
+            Err(BbsError::InvalidPost(issues)) => {
+                // Back to the preview, with what needs fixing
+                self.menu_message.state = crate::menu::menu_message::MessageMenuState::Review {
+                    recipient,
+                    subject,
+                    content,
+                    issues,
+                };
+                Ok(())
+            }
             Err(e) => {
                 self.show_message_with_stream(
                     stream,
@@ -1044,9 +2757,14 @@ Location: {}
     }
 
     /// Handle reading a private message
-    fn handle_message_read(&mut self, stream: &mut TelnetStream, id: u32) -> BbsResult<()> {
+    fn handle_message_read(&mut self, stream: &mut SessionStream, id: u32) -> BbsResult<()> {
         if let Some(user) = &self.user {
-            match self.services.messages.read_message(id, &user.username)? {
+            let level = self.security_level();
+            match self
+                .services
+                .messages
+                .read_message(id, &user.username, level)?
+            {
                 Some(message) => {
                     self.menu_message.state =
                         crate::menu::menu_message::MessageMenuState::Reading(message);
@@ -1076,8 +2794,56 @@ Location: {}
         }
     }
 
+    /// Mark every message in the inbox on screen read
+    fn handle_message_mark_all_read(&mut self) -> BbsResult<()> {
+        let (Some(user), crate::menu::menu_message::MessageMenuState::Inbox(messages)) =
+            (&self.user, &self.menu_message.state)
+        else {
+            return Ok(());
+        };
+        let level = self.security_level();
+        let ids: Vec<u32> = messages.iter().map(|m| m.id).collect();
+
+        self.services
+            .messages
+            .mark_read_many(&ids, &user.username, level)?;
+        let messages = self
+            .services
+            .messages
+            .get_many(&ids, &user.username, level)?;
+        self.menu_message.state = crate::menu::menu_message::MessageMenuState::Inbox(messages);
+        Ok(())
+    }
+
+    /// Delete every read message in the inbox on screen, after asking
+    fn handle_message_delete_read(&mut self, stream: &mut SessionStream) -> BbsResult<()> {
+        let (Some(username), crate::menu::menu_message::MessageMenuState::Inbox(messages)) = (
+            self.user.as_ref().map(|u| u.username.clone()),
+            &self.menu_message.state,
+        ) else {
+            return Ok(());
+        };
+        let ids: Vec<u32> = messages
+            .iter()
+            .filter(|m| !m.is_unread())
+            .map(|m| m.id)
+            .collect();
+
+        let answer = self.get_input(
+            stream,
+            &format!("Delete {} read message(s)? (y/N): ", ids.len()),
+        )?;
+        if !answer.eq_ignore_ascii_case("y") {
+            return Ok(());
+        }
+        self.services.messages.delete_many(&ids, &username)?;
+        let messages = self.get_user_inbox()?;
+        self.menu_message.state = crate::menu::menu_message::MessageMenuState::Inbox(messages);
+        Ok(())
+    }
+
     /// Handle deleting a private message
-    fn handle_message_delete(&mut self, stream: &mut TelnetStream, id: u32) -> BbsResult<()> {
+    fn handle_message_delete(&mut self, stream: &mut SessionStream, id: u32) -> BbsResult<()> {
         if let Some(user) = &self.user {
             match self.services.messages.delete_message(id, &user.username) {
                 Ok(()) => {
@@ -1115,7 +2881,7 @@ Location: {}
     // Show feature disabled message
     // fn show_feature_disabled(
     //     &mut self,
-    //     stream: &mut TelnetStream,
+    //     stream: &mut SessionStream,
     //     feature_name: &str,
     // ) -> BbsResult<()> {
     //     let width = self.config.ui.menu_width + 20;
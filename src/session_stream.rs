@@ -0,0 +1,67 @@
+use crate::color::ColorFilter;
+use crate::connection::Connection;
+
+use std::io::{self, Read, Write};
+use std::ops::{Deref, DerefMut};
+use telnet_negotiation::{ColorDepth, TelnetStream};
+
+/// A session's connection, with every write passed through the color filter
+///
+/// Session code writes here and never to the `TelnetStream` directly, so
+/// render calls can use any color and each caller still receives only what
+/// their terminal can show. Everything else derefs to the `TelnetStream`.
+pub struct SessionStream {
+    inner: TelnetStream<Connection>,
+    colors: ColorFilter,
+}
+
+impl SessionStream {
+    /// Wrap a stream; output passes through unchanged until a depth is set
+    pub fn new(inner: TelnetStream<Connection>) -> Self {
+        Self {
+            inner,
+            colors: ColorFilter::new(ColorDepth::TrueColor),
+        }
+    }
+
+    /// Color depth output is currently translated to
+    pub fn color_depth(&self) -> ColorDepth {
+        self.colors.depth()
+    }
+
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        self.colors.set_depth(depth);
+    }
+}
+
+impl Deref for SessionStream {
+    type Target = TelnetStream<Connection>;
+
+    fn deref(&self) -> &TelnetStream<Connection> {
+        &self.inner
+    }
+}
+
+impl DerefMut for SessionStream {
+    fn deref_mut(&mut self) -> &mut TelnetStream<Connection> {
+        &mut self.inner
+    }
+}
+
+impl Read for SessionStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for SessionStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let translated = self.colors.filter(buf);
+        self.inner.write_all(&translated)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
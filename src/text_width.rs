@@ -0,0 +1,109 @@
+//! Terminal column widths for text
+//!
+//! Most characters take one column, but CJK ideographs, fullwidth forms and
+//! emoji take two, and combining marks, zero-width joiners and variation
+//! selectors take none. Counting `chars()` gets all of those wrong, which
+//! pushes box borders out of line as soon as someone signs up as "ユーザー".
+//!
+//! Widths come from the `unicode-width` crate, which follows Unicode's
+//! East Asian Width property and general categories.
+
+use unicode_width::UnicodeWidthChar;
+
+/// Columns a single character occupies: 0, 1 or 2
+///
+/// Control characters do not advance the cursor in a meaningful way, so
+/// they count as 0.
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Columns `text` occupies on a terminal
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Longest prefix of `text` that fits in `max_width` columns
+///
+/// Never splits a character, so the result can be one column short when
+/// the next character is double width.
+pub fn truncate_to_width(text: &str, max_width: usize) -> &str {
+    let mut used = 0;
+    for (index, c) in text.char_indices() {
+        used += char_width(c);
+        if used > max_width {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+/// Pad `text` with spaces on the right to `width` columns
+///
+/// Text already at least `width` wide is returned unchanged.
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// `text` cut to at most `max_width` columns, ending in "..." if anything was cut
+pub fn ellipsize(text: &str, max_width: usize) -> String {
+    if display_width(text) <= max_width {
+        return text.to_string();
+    }
+
+    format!(
+        "{}...",
+        truncate_to_width(text, max_width.saturating_sub(3))
+    )
+}
+
+/// Exactly `width` columns of `text`: padded if short, cut with "..." if long
+pub fn fit_to_width(text: &str, width: usize) -> String {
+    pad_to_width(&ellipsize(text, width), width)
+}
+
+/// `text` broken at spaces into lines of at most `width` columns
+///
+/// Text that fits is returned as it is. Otherwise runs of spaces collapse,
+/// continuation lines keep the first line's indent, and a word too long
+/// for any line is broken across as many lines as it needs, so nothing is
+/// lost.
+pub fn wrap_to_width(text: &str, width: usize) -> Vec<String> {
+    if display_width(text) <= width {
+        return vec![text.to_string()];
+    }
+    let body = text.trim_start();
+    let indent = &text[..text.len() - body.len()];
+    let indent = truncate_to_width(indent, width.saturating_sub(1));
+    let room = width.saturating_sub(display_width(indent)).max(1);
+
+    let mut lines = Vec::new();
+    let mut line = indent.to_string();
+    for word in body.split_whitespace() {
+        if line.len() > indent.len() {
+            if display_width(&line) + 1 + display_width(word) <= width {
+                line.push(' ');
+                line.push_str(word);
+                continue;
+            }
+            lines.push(std::mem::replace(&mut line, indent.to_string()));
+        }
+
+        let mut rest = word;
+        while display_width(rest) > room {
+            let mut piece = truncate_to_width(rest, room);
+            if piece.is_empty() {
+                // A double-width character with one column of room
+                let first = rest.chars().next().map_or(0, char::len_utf8);
+                piece = &rest[..first];
+            }
+            line.push_str(piece);
+            lines.push(std::mem::replace(&mut line, indent.to_string()));
+            rest = &rest[piece.len()..];
+        }
+        line.push_str(rest);
+    }
+    lines.push(line);
+    lines
+}
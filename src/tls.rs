@@ -0,0 +1,43 @@
+use crate::connection::Connection;
+use crate::errors::{BbsError, BbsResult};
+
+use std::sync::{Arc, OnceLock};
+use telnet_negotiation::TlsUpgrader;
+use telnet_negotiation::tls::{self, RustlsUpgrader, rustls::ServerConfig};
+
+/// How long a client gets to answer a START_TLS offer before we carry on in plaintext
+pub const START_TLS_TIMEOUT_MS: u64 = 3000;
+
+/// The board's certificate, once loaded at startup
+static SERVER_CONFIG: OnceLock<Arc<ServerConfig>> = OnceLock::new();
+
+/// Load the certificate chain and private key (both PEM files) that TLS
+/// callers are served
+///
+/// Called once at startup, before any caller connects; later calls keep
+/// the first certificate.
+pub fn load(certificate: &str, private_key: &str) -> BbsResult<()> {
+    let read = |path: &str| {
+        std::fs::read(path)
+            .map_err(|e| BbsError::Configuration(format!("cannot read {}: {}", path, e)))
+    };
+    let config = tls::server_config(&read(certificate)?, &read(private_key)?)
+        .map_err(|e| BbsError::Configuration(format!("{}: {}", certificate, e)))?;
+    let _ = SERVER_CONFIG.set(config);
+    Ok(())
+}
+
+/// The rustls configuration for the TLS port, if a certificate is loaded
+pub fn server_config() -> Option<Arc<ServerConfig>> {
+    SERVER_CONFIG.get().cloned()
+}
+
+/// TLS backend for sessions that accept TELNET START_TLS
+///
+/// There is none until [`load`] has a certificate, and START_TLS is never
+/// offered without one. Callers on the WebSocket and TLS ports never reach
+/// it: the one is handled by a proxy in front, the other is already
+/// encrypted.
+pub fn upgrader() -> Option<Box<dyn TlsUpgrader<Connection>>> {
+    server_config().map(|config| Box::new(RustlsUpgrader::new(config)) as Box<_>)
+}
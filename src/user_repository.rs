@@ -1,4 +1,5 @@
 use crate::errors::{BbsError, BbsResult};
+use crate::journal::{Journal, JournalEntry, write_snapshot};
 use crate::menu::UserStats;
 use crate::users::{RegistrationRequest, User};
 
@@ -20,6 +21,7 @@ pub trait UserStorage {
     ) -> BbsResult<User>;
     fn authenticate_user(&mut self, username: &str, password: &str) -> BbsResult<Option<User>>;
     fn get_stats(&self) -> BbsResult<UserStats>;
+    fn get_recent_logins(&self, limit: usize) -> BbsResult<Vec<User>>;
 }
 
 /// JSON file-based user storage implementation
@@ -27,6 +29,7 @@ pub struct JsonUserStorage {
     //data_dir: PathBuf,
     users_file: PathBuf,
     users_cache: HashMap<String, User>,
+    journal: Journal,
 }
 
 impl JsonUserStorage {
@@ -44,6 +47,7 @@ impl JsonUserStorage {
 
         let mut storage = Self {
             // data_dir,
+            journal: Journal::for_file(&users_file),
             users_file,
             users_cache: HashMap::new(),
         };
@@ -54,38 +58,45 @@ impl JsonUserStorage {
         Ok(storage)
     }
 
-    /// Load all users from the JSON file into the cache
+    /// Load all users from the JSON file and its journal into the cache
     fn load_all_users(&mut self) -> BbsResult<()> {
-        if !self.users_file.exists() {
-            // Create empty users file
-            let empty_users: HashMap<String, User> = HashMap::new();
-            self.save_all_users(&empty_users)?;
-            return Ok(());
-        }
-
-        let content = fs::read_to_string(&self.users_file)
-            .map_err(|e| BbsError::Configuration(format!("Failed to read users file: {}", e)))?;
+        if self.users_file.exists() {
+            let content = fs::read_to_string(&self.users_file).map_err(|e| {
+                BbsError::Configuration(format!("Failed to read users file: {}", e))
+            })?;
 
-        if content.trim().is_empty() {
-            return Ok(()); // Empty file is OK
+            if !content.trim().is_empty() {
+                self.users_cache = serde_json::from_str(&content).map_err(|e| {
+                    BbsError::Configuration(format!("Failed to parse users file: {}", e))
+                })?;
+            }
         }
 
-        let users: HashMap<String, User> = serde_json::from_str(&content)
-            .map_err(|e| BbsError::Configuration(format!("Failed to parse users file: {}", e)))?;
+        // Changes made since the file was last compacted
+        for entry in self.journal.pending(&self.users_file)? {
+            match entry {
+                JournalEntry::Put { key, value } => {
+                    self.users_cache.insert(key, value);
+                }
+                JournalEntry::Delete { key } => {
+                    self.users_cache.remove(&key);
+                }
+            }
+        }
 
-        self.users_cache = users;
+        if !self.users_file.exists() || self.journal.path().exists() {
+            self.compact()?;
+        }
         Ok(())
     }
 
-    /// Save all users from cache to the JSON file
-    fn save_all_users(&self, users: &HashMap<String, User>) -> BbsResult<()> {
-        let content = serde_json::to_string_pretty(users)
+    /// Rewrite users.json from the cache and start a fresh journal
+    pub fn compact(&mut self) -> BbsResult<()> {
+        let content = serde_json::to_string_pretty(&self.users_cache)
             .map_err(|e| BbsError::Configuration(format!("Failed to serialize users: {}", e)))?;
 
-        fs::write(&self.users_file, content)
-            .map_err(|e| BbsError::Configuration(format!("Failed to write users file: {}", e)))?;
-
-        Ok(())
+        write_snapshot(&self.users_file, &content)?;
+        self.journal.clear()
     }
 
     /// Register a new user
@@ -106,11 +117,12 @@ impl JsonUserStorage {
         }
 
         // Create new user
-        let user = User::new(
+        let mut user = User::new(
             request.username.clone(),
             request.email.clone(),
             &request.password,
         )?;
+        user.display_name = request.display_name.clone();
 
         // Save user
         self.save_user(&user)?;
@@ -154,6 +166,28 @@ impl JsonUserStorage {
             recent_logins,
         })
     }
+
+    /// Get users who have logged in, most recent first
+    pub fn get_recent_logins(&self, limit: usize) -> BbsResult<Vec<User>> {
+        let mut users: Vec<User> = self
+            .users_cache
+            .values()
+            .filter(|u| u.login_count > 0)
+            .cloned()
+            .collect();
+        users.sort_by_key(|u| std::cmp::Reverse(u.last_login));
+        users.truncate(limit);
+        Ok(users)
+    }
+}
+
+/// Fold outstanding journal entries into users.json on a clean close
+impl Drop for JsonUserStorage {
+    fn drop(&mut self) {
+        if self.journal.has_entries() {
+            let _ = self.compact();
+        }
+    }
 }
 
 impl UserStorage for JsonUserStorage {
@@ -166,8 +200,14 @@ impl UserStorage for JsonUserStorage {
         // Update cache
         self.users_cache.insert(user.username.clone(), user.clone());
 
-        // Save to file
-        self.save_all_users(&self.users_cache)?;
+        // Record the change; the file itself is only rewritten when compacting
+        self.journal.append(&JournalEntry::Put {
+            key: &user.username,
+            value: user,
+        })?;
+        if self.journal.needs_compaction() {
+            self.compact()?;
+        }
 
         Ok(())
     }
@@ -201,6 +241,10 @@ impl UserStorage for JsonUserStorage {
     fn get_stats(&self) -> BbsResult<UserStats> {
         self.get_stats()
     }
+
+    fn get_recent_logins(&self, limit: usize) -> BbsResult<Vec<User>> {
+        self.get_recent_logins(limit)
+    }
 }
 
 // User statistics
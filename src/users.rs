@@ -1,4 +1,6 @@
+use crate::bulletins::BulletinReadState;
 use crate::errors::{BbsError, BbsResult};
+use crate::text_width::display_width;
 
 use jiff::Timestamp;
 use serde::{Deserialize, Serialize};
@@ -16,6 +18,26 @@ pub struct User {
     pub last_login: Timestamp,
     pub login_count: u32,
     pub is_active: bool,
+    #[serde(default)]
+    pub bulletin_reads: BulletinReadState,
+    /// Suppress the terminal bell for notifications
+    #[serde(default)]
+    pub quiet_mode: bool,
+    /// Screen reader mode; `None` follows what the client reports
+    #[serde(default)]
+    pub screen_reader: Option<bool>,
+    /// When the login before the current one happened, for "since last call"
+    #[serde(default)]
+    pub previous_login: Option<Timestamp>,
+    /// Name shown on posts and messages; logins always use `username`
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Another user who gets a copy of every private message sent here
+    #[serde(default)]
+    pub forward_to: Option<String>,
+    /// Vacation reply sent back to people who message this user
+    #[serde(default)]
+    pub auto_reply: Option<String>,
 }
 
 impl User {
@@ -32,9 +54,21 @@ impl User {
             last_login: now,
             login_count: 0,
             is_active: true,
+            bulletin_reads: BulletinReadState::default(),
+            quiet_mode: false,
+            screen_reader: None,
+            previous_login: None,
+            display_name: None,
+            forward_to: None,
+            auto_reply: None,
         })
     }
 
+    /// The display name if one is set, otherwise the username
+    pub fn shown_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
+
     // TODO: investigate secure string implementation like secrecy or zeroize
     //       for passing around secrets like passwords
     /// Verify a password against this user's stored hash
@@ -43,7 +77,11 @@ impl User {
     }
 
     /// Update the last login time and increment login count
+    ///
+    /// The login being replaced becomes `previous_login`; before the first
+    /// login that is the moment the account was created.
     pub fn record_login(&mut self) {
+        self.previous_login = Some(self.last_login);
         self.last_login = Timestamp::now();
         self.login_count += 1;
     }
@@ -61,29 +99,192 @@ impl User {
 
     /// Get a display-friendly string for last login time
     pub fn last_login_display(&self) -> String {
-        let now = Timestamp::now();
-        let duration_since = now.duration_since(self.last_login);
-
-        // Simple relative time display
-        let seconds = duration_since.as_secs();
-        if seconds < 60 {
-            "just now".to_string()
-        } else if seconds < 3600 {
-            format!("{} minutes ago", seconds / 60)
-        } else if seconds < 86400 {
-            format!("{} hours ago", seconds / 3600)
+        relative_time(self.last_login)
+    }
+
+    /// Display-friendly time of the login before this one, if any
+    pub fn previous_login_display(&self) -> Option<String> {
+        self.previous_login.map(relative_time)
+    }
+}
+
+/// Simple relative time display, e.g. "3 hours ago"
+fn relative_time(timestamp: Timestamp) -> String {
+    let duration_since = Timestamp::now().duration_since(timestamp);
+
+    let seconds = duration_since.as_secs();
+    if seconds < 60 {
+        "just now".to_string()
+    } else if seconds < 3600 {
+        format!("{} minutes ago", seconds / 60)
+    } else if seconds < 86400 {
+        format!("{} hours ago", seconds / 3600)
+    } else {
+        format!("{} days ago", seconds / 86400)
+    }
+}
+
+/// What a username may look like, set in `[features]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsernameRules {
+    /// Letters and digits outside ASCII (accented letters, CJK, ...)
+    pub allow_unicode: bool,
+    pub allow_digits: bool,
+    /// Punctuation allowed besides letters and digits
+    pub symbols: String,
+    /// Whether "Alice" and "alice" are the same account
+    pub case_insensitive: bool,
+    /// Names nobody may register, compared without regard to case
+    pub reserved: Vec<String>,
+}
+
+impl Default for UsernameRules {
+    fn default() -> Self {
+        Self {
+            allow_unicode: true,
+            allow_digits: true,
+            symbols: "_".to_string(),
+            case_insensitive: false,
+            reserved: vec!["sysop".to_string(), "admin".to_string(), "all".to_string()],
+        }
+    }
+}
+
+impl UsernameRules {
+    /// Whether `c` may appear in a username
+    pub fn allows_char(&self, c: char) -> bool {
+        if !self.allow_unicode && !c.is_ascii() {
+            return false;
+        }
+        c.is_alphabetic() || (self.allow_digits && c.is_numeric()) || self.symbols.contains(c)
+    }
+
+    /// Check the length and characters of `username`
+    pub fn check(&self, username: &str, max_length: usize) -> BbsResult<()> {
+        if username.is_empty() {
+            return Err(BbsError::InvalidInput(
+                "Username cannot be empty".to_string(),
+            ));
+        }
+
+        if username.len() > max_length {
+            return Err(BbsError::InvalidInput(format!(
+                "Username too long (max {} characters)",
+                max_length
+            )));
+        }
+
+        if !username.chars().all(|c| self.allows_char(c)) {
+            return Err(BbsError::InvalidInput(format!(
+                "Username can only contain {}",
+                self.allowed_description()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Whether `username` is on the reserved list
+    pub fn is_reserved(&self, username: &str) -> bool {
+        let username = username.to_lowercase();
+        self.reserved.iter().any(|r| r.to_lowercase() == username)
+    }
+
+    /// Whether two usernames name the same account
+    pub fn same_name(&self, a: &str, b: &str) -> bool {
+        if self.case_insensitive {
+            a.to_lowercase() == b.to_lowercase()
+        } else {
+            a == b
+        }
+    }
+
+    /// The rules as lines for the registration screen
+    pub fn describe(&self, max_length: usize) -> Vec<String> {
+        let mut lines = vec![
+            format!("1-{} characters", max_length),
+            format!("{} only", capitalize(&self.allowed_description())),
+        ];
+        if !self.case_insensitive {
+            lines.push("Upper and lower case are different".to_string());
+        }
+        lines.push("Must be unique".to_string());
+        lines
+    }
+
+    /// "letters, numbers and underscore", or as configured
+    fn allowed_description(&self) -> String {
+        let mut parts = vec![if self.allow_unicode {
+            "letters".to_string()
         } else {
-            format!("{} days ago", seconds / 86400)
+            "A-Z letters".to_string()
+        }];
+        if self.allow_digits {
+            parts.push("numbers".to_string());
+        }
+        for symbol in self.symbols.chars() {
+            parts.push(match symbol {
+                '_' => "underscore".to_string(),
+                '-' => "hyphen".to_string(),
+                '.' => "period".to_string(),
+                other => format!("'{}'", other),
+            });
         }
+
+        match parts.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+            _ => parts.join(""),
+        }
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
     }
 }
 
+/// Longest display name, in terminal columns
+pub const MAX_DISPLAY_NAME_WIDTH: usize = 30;
+
+/// Tidy a display name as typed: trims it and collapses runs of spaces
+///
+/// Blank input clears the display name (`None`). Control and invisible
+/// format characters are refused, since they could disguise the name.
+pub fn normalize_display_name(input: &str) -> BbsResult<Option<String>> {
+    let name = input.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    if name
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '\u{200B}'..='\u{200F}' | '\u{2028}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}'))
+    {
+        return Err(BbsError::InvalidInput(
+            "Display name contains invisible or control characters".to_string(),
+        ));
+    }
+
+    if display_width(&name) > MAX_DISPLAY_NAME_WIDTH {
+        return Err(BbsError::InvalidInput(format!(
+            "Display name too long (max {} characters)",
+            MAX_DISPLAY_NAME_WIDTH
+        )));
+    }
+
+    Ok(Some(name))
+}
+
 /// Registration request data
 #[derive(Debug)]
 pub struct RegistrationRequest {
     pub username: String,
     pub email: Option<String>,
     pub password: String,
+    pub display_name: Option<String>,
 }
 
 impl RegistrationRequest {
@@ -92,36 +293,32 @@ impl RegistrationRequest {
             username,
             email,
             password,
+            display_name: None,
         }
     }
 
+    /// Ask for a display name besides the username
+    pub fn with_display_name(mut self, display_name: Option<String>) -> Self {
+        self.display_name = display_name;
+        self
+    }
+
     /// Validate the registration request
     pub fn validate(&self, config: &crate::config::BbsConfig) -> BbsResult<()> {
         // Validate username
-        if self.username.is_empty() {
-            return Err(BbsError::InvalidInput(
-                "Username cannot be empty".to_string(),
-            ));
-        }
+        let rules = &config.features.usernames;
+        rules.check(&self.username, config.features.max_username_length)?;
 
-        if self.username.len() > config.features.max_username_length {
+        // The SysOp has to be able to claim their own name
+        if rules.is_reserved(&self.username)
+            && !self.username.eq_ignore_ascii_case(&config.bbs.sysop_name)
+        {
             return Err(BbsError::InvalidInput(format!(
-                "Username too long (max {} characters)",
-                config.features.max_username_length
+                "Username '{}' is reserved",
+                self.username
             )));
         }
 
-        // Check for valid characters (alphanumeric and underscore only)
-        if !self
-            .username
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_')
-        {
-            return Err(BbsError::InvalidInput(
-                "Username can only contain letters, numbers, and underscores".to_string(),
-            ));
-        }
-
         // Validate password
         if self.password.is_empty() {
             return Err(BbsError::InvalidInput(
@@ -135,6 +332,10 @@ impl RegistrationRequest {
             ));
         }
 
+        if let Some(display_name) = &self.display_name {
+            normalize_display_name(display_name)?;
+        }
+
         // Validate email if provided
         if let Some(ref email) = self.email
             && !email.is_empty()
@@ -0,0 +1,126 @@
+//! Hot path benchmarks: the parser on its own, then TelnetStream over a
+//! loopback socket.
+//!
+//! Run with `cargo bench -p telnet-negotiation`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use telnet_negotiation::{
+    IAC, ParserLimits, TelnetCommand, TelnetOption, TelnetParser, TelnetStream,
+};
+
+/// About one 80x25 screen of ANSI art, colour codes and all
+const SCREEN: usize = 80 * 25 * 4;
+
+/// A screen of printable text and escape sequences, with no IAC in it
+fn ansi_screen(len: usize) -> Vec<u8> {
+    b"\x1b[1;34m\xb0\xb1\xb2\xdb\x1b[0m MoonBase BBS \x1b[33m*\x1b[0m\r\n"
+        .iter()
+        .copied()
+        .cycle()
+        .take(len)
+        .collect()
+}
+
+/// The screen with a negotiation and an escaped 255 every 64 bytes
+fn interleaved(len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + len / 8);
+    for chunk in ansi_screen(len).chunks(64) {
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(&[
+            IAC,
+            TelnetCommand::WILL.to_byte(),
+            TelnetOption::SUPPRESS_GO_AHEAD.to_byte(),
+            IAC,
+            IAC,
+        ]);
+    }
+    out
+}
+
+/// One GMCP sub-negotiation carrying `len` bytes
+fn subnegotiation(len: usize) -> Vec<u8> {
+    let mut out = vec![
+        IAC,
+        TelnetCommand::SB.to_byte(),
+        TelnetOption::GMCP.to_byte(),
+    ];
+    out.extend(
+        b"Room.Info {\"exits\": [\"n\", \"s\"]} "
+            .iter()
+            .cycle()
+            .take(len),
+    );
+    out.extend_from_slice(&[IAC, TelnetCommand::SE.to_byte()]);
+    out
+}
+
+fn parser(c: &mut Criterion) {
+    let inputs = [
+        ("data", ansi_screen(SCREEN)),
+        ("interleaved", interleaved(SCREEN)),
+        ("subnegotiation", subnegotiation(SCREEN)),
+    ];
+
+    let mut group = c.benchmark_group("parser");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", name), input, |b, input| {
+            let mut parser = TelnetParser::with_limits(ParserLimits::unlimited());
+            b.iter(|| black_box(parser.parse(black_box(input))));
+        });
+        group.bench_with_input(
+            BenchmarkId::new("parse_borrowed", name),
+            input,
+            |b, input| {
+                let mut parser = TelnetParser::with_limits(ParserLimits::unlimited());
+                b.iter(|| parser.parse_borrowed(black_box(input)).count());
+            },
+        );
+    }
+    group.finish();
+}
+
+/// A peer that sends back whatever it receives, raw
+fn echo_server() -> TcpStream {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut buf = [0; 16 * 1024];
+        while let Ok(n @ 1..) = socket.read(&mut buf) {
+            if socket.write_all(&buf[..n]).is_err() {
+                break;
+            }
+        }
+    });
+    TcpStream::connect(addr).unwrap()
+}
+
+fn stream(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stream");
+    for (name, screen) in [
+        ("data", ansi_screen(SCREEN)),
+        ("escaped", vec![IAC; SCREEN]),
+    ] {
+        let mut stream = TelnetStream::new(echo_server());
+        stream.set_nodelay(true).unwrap();
+        let mut back = vec![0; screen.len()];
+
+        group.throughput(Throughput::Bytes(screen.len() as u64));
+        group.bench_function(BenchmarkId::new("round_trip", name), |b| {
+            b.iter(|| {
+                stream.write_all(&screen).unwrap();
+                stream.read_exact(&mut back).unwrap();
+            });
+        });
+        assert_eq!(back, screen);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parser, stream);
+criterion_main!(benches);
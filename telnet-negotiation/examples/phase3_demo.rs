@@ -99,7 +99,7 @@ fn main() {
     ];
 
     let result = parser.parse(&terminal_response);
-    if let Some(TelnetSequence::SubNegotiation { option, data }) = result.sequences.first() {
+    if let Some(TelnetSequence::SubNegotiation { option: _, data }) = result.sequences.first() {
         println!("     Response sub-data: {:?}", data);
         println!(
             "     Interpretation: IS command (byte 0) + 'ANSI' ({:?})",
@@ -145,7 +145,7 @@ fn main() {
 
     for (i, seq) in result.sequences.iter().enumerate() {
         if let TelnetSequence::Negotiation { command, option } = seq {
-            let meaning = match (command, option) {
+            let meaning = match (*command, *option) {
                 (TelnetCommand::WILL, TelnetOption::ECHO) => "Server will handle echoing",
                 (TelnetCommand::WILL, TelnetOption::SUPPRESS_GO_AHEAD) => {
                     "Server supports full-duplex"
@@ -181,10 +181,10 @@ fn main() {
             negotiator5.request_disable(side, option)
         };
 
-        if let Some(response) = &result.response {
-            if let TelnetSequence::Negotiation { command, option } = response {
-                println!("     -> Sends: IAC {:?} {:?}", command, option);
-            }
+        if let Some(response) = &result.response
+            && let TelnetSequence::Negotiation { command, option } = response
+        {
+            println!("     -> Sends: IAC {:?} {:?}", command, option);
         }
     }
 
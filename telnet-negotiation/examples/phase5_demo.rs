@@ -24,31 +24,23 @@ fn main() {
     let server_handle = thread::spawn(move || {
         println!("[Server] Listening for connections...");
 
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    println!(
-                        "[Server] New connection from: {}",
-                        stream
-                            .peer_addr()
-                            .unwrap_or_else(|_| "unknown".parse().unwrap())
-                    );
-
-                    // Wrap the TcpStream with TelnetStream for automatic telnet handling
-                    let mut telnet_stream = TelnetStream::with_debug(stream);
-
-                    // Handle the connection
-                    if let Err(e) = handle_server_connection(&mut telnet_stream) {
-                        eprintln!("[Server] Connection error: {}", e);
-                    }
-
-                    println!("[Server] Connection closed");
-                    break; // Only handle one connection for demo
-                }
-                Err(e) => {
-                    eprintln!("[Server] Accept error: {}", e);
-                    break;
+        // Only handle one connection for demo
+        match listener.accept() {
+            Ok((stream, peer_addr)) => {
+                println!("[Server] New connection from: {}", peer_addr);
+
+                // Wrap the TcpStream with TelnetStream for automatic telnet handling
+                let mut telnet_stream = TelnetStream::with_debug(stream);
+
+                // Handle the connection
+                if let Err(e) = handle_server_connection(&mut telnet_stream) {
+                    eprintln!("[Server] Connection error: {}", e);
                 }
+
+                println!("[Server] Connection closed");
+            }
+            Err(e) => {
+                eprintln!("[Server] Accept error: {}", e);
             }
         }
     });
@@ -97,10 +89,10 @@ fn handle_server_connection(stream: &mut TelnetStream) -> std::io::Result<()> {
     println!("[Server] Connection established, telnet processing active");
 
     // Send welcome message
-    stream.write(b"Welcome to the Phase 5 Demo Server!\r\n")?;
-    stream.write(b"This server uses TelnetStream for transparent telnet handling.\r\n")?;
-    stream.write(b"Type messages and they will be echoed back. Type 'quit' to exit.\r\n")?;
-    stream.write(b"\r\nPrompt> ")?;
+    stream.write_all(b"Welcome to the Phase 5 Demo Server!\r\n")?;
+    stream.write_all(b"This server uses TelnetStream for transparent telnet handling.\r\n")?;
+    stream.write_all(b"Type messages and they will be echoed back. Type 'quit' to exit.\r\n")?;
+    stream.write_all(b"\r\nPrompt> ")?;
     stream.flush()?;
 
     // Simple echo loop
@@ -117,15 +109,15 @@ fn handle_server_connection(stream: &mut TelnetStream) -> std::io::Result<()> {
 
                 // Check for quit command
                 if received.trim().eq_ignore_ascii_case("quit") {
-                    stream.write(b"Goodbye!\r\n")?;
+                    stream.write_all(b"Goodbye!\r\n")?;
                     stream.flush()?;
                     break;
                 }
 
                 // Echo back the message
-                stream.write(b"Echo: ")?;
-                stream.write(&buffer[..n])?;
-                stream.write(b"Prompt> ")?;
+                stream.write_all(b"Echo: ")?;
+                stream.write_all(&buffer[..n])?;
+                stream.write_all(b"Prompt> ")?;
                 stream.flush()?;
             }
             Err(e) => {
@@ -159,12 +151,12 @@ fn demonstrate_transparent_operation(stream: &mut TelnetStream) {
     for (i, message) in test_messages.iter().enumerate() {
         println!("   Sending message {}: {:?}", i + 1, message);
 
-        if let Err(e) = stream.write(message.as_bytes()) {
+        if let Err(e) = stream.write_all(message.as_bytes()) {
             eprintln!("[Client] Write error: {}", e);
             continue;
         }
 
-        if let Err(e) = stream.write(b"\r\n") {
+        if let Err(e) = stream.write_all(b"\r\n") {
             eprintln!("[Client] Write error: {}", e);
             continue;
         }
@@ -233,7 +225,7 @@ fn integration_example() {
     fn handle_client_old(stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
         let mut stream = stream;
         // Manual telnet handling would be required here
-        stream.write(b"Welcome!\r\n")?;
+        stream.write_all(b"Welcome!\r\n")?;
         Ok(())
     }
     */
@@ -242,7 +234,7 @@ fn integration_example() {
     fn _handle_client_new(stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
         let mut stream = TelnetStream::new(stream); // Only this line changes!
         // Everything else remains the same - telnet handling is now automatic
-        stream.write(b"Welcome!\r\n")?;
+        stream.write_all(b"Welcome!\r\n")?;
         Ok(())
     }
 
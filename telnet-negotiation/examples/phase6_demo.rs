@@ -223,13 +223,18 @@ fn demo_integration_capabilities() {
     println!("Capabilities Structure for BBS Integration:");
 
     // Simulate a fully negotiated terminal session
-    let mut caps = TerminalCapabilities::default();
-    caps.width = Some(80);
-    caps.height = Some(24);
-    caps.terminal_type = Some("XTERM-256COLOR".to_string());
-    caps.supports_ansi = true;
-    caps.supports_color = true;
-    caps.color_depth = ColorDepth::Extended256;
+    let caps = TerminalCapabilities {
+        width: Some(80),
+        height: Some(24),
+        terminal_type: Some("XTERM-256COLOR".to_string()),
+        supports_ansi: true,
+        supports_color: true,
+        color_depth: ColorDepth::Extended256,
+        screen_reader: false,
+        cursor_positioning: true,
+        charset: Some("UTF-8".to_string()),
+        utf8: true,
+    };
 
     println!("  Terminal: {:?}", caps.terminal_type);
     println!(
@@ -325,6 +330,7 @@ mod tests {
             supports_ansi: true,
             supports_color: true,
             color_depth: ColorDepth::Basic8,
+            screen_reader: false,
         };
 
         // Test BBS decision making
@@ -0,0 +1,46 @@
+//! Arbitrary bytes through `TelnetParser`, in chunks
+//!
+//! The first byte sets the chunk size, so IAC, SB and SE land on chunk
+//! boundaries. However the input is split, the parser must not panic,
+//! must never claim more than it was given, and must produce the same
+//! data and sequences as when parsing it in one go.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use telnet_negotiation::{ParseResult, ParserLimits, TelnetParser};
+
+fuzz_target!(|input: &[u8]| {
+    let Some((&chunk, input)) = input.split_first() else {
+        return;
+    };
+    let chunk = usize::from(chunk).max(1);
+
+    let whole = TelnetParser::with_limits(ParserLimits::unlimited()).parse(input);
+    check(&whole, input.len());
+
+    let mut parser = TelnetParser::with_limits(ParserLimits::unlimited());
+    let mut data = Vec::new();
+    let mut sequences = Vec::new();
+    for piece in input.chunks(chunk) {
+        let result = parser.parse(piece);
+        check(&result, piece.len());
+        data.extend(result.data);
+        sequences.extend(result.sequences);
+    }
+    assert_eq!(data, whole.data);
+    assert_eq!(sequences, whole.sequences);
+});
+
+fn check(result: &ParseResult, len: usize) {
+    assert!(result.bytes_consumed <= len);
+    assert!(result.data.len() <= len);
+    assert_eq!(result.sequence_offsets.len(), result.sequences.len());
+    assert!(result.sequence_offsets.is_sorted());
+    assert!(
+        result
+            .sequence_offsets
+            .last()
+            .is_none_or(|&offset| offset <= result.data.len())
+    );
+}
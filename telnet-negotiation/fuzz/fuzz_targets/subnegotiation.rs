@@ -0,0 +1,47 @@
+//! Arbitrary sub-negotiation payloads through the option handlers
+//!
+//! The first byte picks the handler and the rest is what the peer sent
+//! between IAC SB <option> and IAC SE. Handlers may reject the payload
+//! with an `OptionError` but must not panic on it. Add new handlers
+//! here as they land.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+use telnet_negotiation::options::{
+    EchoOption, LinemodeOption, MspOption, MsspOption, MxpOption, NawsOption, NewEnvironOption,
+    SendLocationOption, StartTlsOption, TelnetOptionHandler, TerminalSpeedOption,
+    TerminalTypeOption,
+};
+
+fn handler(pick: u8) -> Box<dyn TelnetOptionHandler> {
+    match pick % 12 {
+        0 => Box::new(NawsOption::new()),
+        1 => Box::new(TerminalTypeOption::new()),
+        2 => Box::new(NewEnvironOption::new()),
+        3 => Box::new(TerminalSpeedOption::new()),
+        4 => Box::new(LinemodeOption::new()),
+        5 => Box::new(SendLocationOption::new()),
+        6 => Box::new(StartTlsOption::new()),
+        7 => Box::new(EchoOption::new(true)),
+        8 => Box::new(EchoOption::new(false)),
+        9 => Box::new(MspOption::new()),
+        10 => Box::new(MsspOption::new(HashMap::new())),
+        _ => Box::new(MxpOption::new()),
+    }
+}
+
+fuzz_target!(|input: &[u8]| {
+    let Some((&pick, payload)) = input.split_first() else {
+        return;
+    };
+    let mut handler = handler(pick);
+    let option = handler.option_code();
+
+    // Twice, since several handlers keep state between payloads
+    for _ in 0..2 {
+        let _ = handler.handle_subnegotiation(payload);
+        assert_eq!(handler.option_code(), option);
+    }
+});
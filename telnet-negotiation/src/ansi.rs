@@ -0,0 +1,518 @@
+//! # ANSI downgrading
+//!
+//! Render code is simplest when it can use any escape sequence it likes:
+//! 24-bit color, cursor addressing, screen clears. [`AnsiFilter`] then
+//! fits that output to the terminal on the other end, following an
+//! [`AnsiProfile`] taken from its [`TerminalCapabilities`]:
+//!
+//! - SGR colors are brought down to the nearest the terminal can show,
+//!   from true color to the 256-color palette, the 16 basic colors, the
+//!   first 8, or none at all. Other SGR attributes are kept.
+//! - Terminals without ANSI attributes lose SGR sequences altogether.
+//! - Terminals without cursor control, such as a dumb terminal or a MUD
+//!   client's scrolling window, lose every other escape sequence: cursor
+//!   movement, erasing, scroll regions, charset selection and OSC strings.
+//!
+//! Sequences split across writes are held until complete, so the filter
+//! can sit on a stream; see
+//! [`TelnetStream::set_ansi_filtering`](crate::TelnetStream::set_ansi_filtering).
+//!
+//! ```rust
+//! use telnet_negotiation::{AnsiFilter, AnsiProfile, ColorDepth};
+//!
+//! let mut filter = AnsiFilter::new(AnsiProfile {
+//!     color_depth: ColorDepth::Basic16,
+//!     ..AnsiProfile::FULL
+//! });
+//! assert_eq!(filter.filter(b"\x1b[38;2;250;5;5mred"), b"\x1b[91mred");
+//!
+//! filter.set_profile(AnsiProfile::DUMB);
+//! assert_eq!(filter.filter(b"\x1b[2J\x1b[1;31mhi\x1b[0m"), b"hi");
+//! ```
+
+use crate::capabilities::{ColorDepth, TerminalCapabilities};
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Longest escape sequence held back while waiting for the rest of it
+const MAX_PENDING: usize = 64;
+
+/// The xterm default palette for the 16 basic colors
+const BASIC_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel values of the 6x6x6 cube in the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// What a terminal makes of escape sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnsiProfile {
+    /// SGR attributes (bold, underline, reverse) are understood
+    pub attributes: bool,
+    /// Colors SGR sequences are brought down to
+    pub color_depth: ColorDepth,
+    /// The cursor can be moved and the screen cleared on command
+    pub cursor_positioning: bool,
+}
+
+impl AnsiProfile {
+    /// Everything passes through untouched
+    pub const FULL: AnsiProfile = AnsiProfile {
+        attributes: true,
+        color_depth: ColorDepth::TrueColor,
+        cursor_positioning: true,
+    };
+
+    /// Every escape sequence is removed
+    pub const DUMB: AnsiProfile = AnsiProfile {
+        attributes: false,
+        color_depth: ColorDepth::Monochrome,
+        cursor_positioning: false,
+    };
+}
+
+impl Default for AnsiProfile {
+    fn default() -> Self {
+        AnsiProfile::FULL
+    }
+}
+
+impl From<&TerminalCapabilities> for AnsiProfile {
+    fn from(caps: &TerminalCapabilities) -> Self {
+        AnsiProfile {
+            attributes: caps.supports_ansi,
+            color_depth: caps.color_depth,
+            cursor_positioning: caps.cursor_positioning,
+        }
+    }
+}
+
+/// A color as it appears in an SGR sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SgrColor {
+    /// Terminal default (39/49)
+    Default,
+    /// One of the 16 basic colors
+    Basic(u8),
+    /// 256-color palette index
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Rewrites outgoing escape sequences to fit an [`AnsiProfile`]
+#[derive(Debug, Clone, Default)]
+pub struct AnsiFilter {
+    profile: AnsiProfile,
+    /// The start of an escape sequence whose end hasn't arrived yet
+    pending: Vec<u8>,
+}
+
+impl AnsiFilter {
+    pub fn new(profile: AnsiProfile) -> Self {
+        Self {
+            profile,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn profile(&self) -> AnsiProfile {
+        self.profile
+    }
+
+    /// Fit output from here on to `profile`; a sequence still held back
+    /// is fitted to it too
+    pub fn set_profile(&mut self, profile: AnsiProfile) {
+        self.profile = profile;
+    }
+
+    /// Translate a chunk of output, returning the bytes to send
+    pub fn filter(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        self.push(input, &mut output);
+        output
+    }
+
+    /// Translate `input`, appending the result to `output`
+    ///
+    /// An incomplete escape sequence at the end of `input` is held back
+    /// until the next call completes it.
+    pub fn push(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        if self.profile == AnsiProfile::FULL && self.pending.is_empty() {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(input);
+
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != ESC {
+                output.push(bytes[i]);
+                i += 1;
+                continue;
+            }
+
+            match sequence_end(&bytes[i..]) {
+                SequenceEnd::Complete(len) => {
+                    self.rewrite(&bytes[i..i + len], output);
+                    i += len;
+                }
+                SequenceEnd::Incomplete if bytes.len() - i <= MAX_PENDING => {
+                    self.pending = bytes[i..].to_vec();
+                    break;
+                }
+                // Not a sequence, or too long to be one we care about; a
+                // terminal without escapes still shouldn't see the ESC
+                SequenceEnd::Incomplete | SequenceEnd::Invalid => {
+                    if self.profile.cursor_positioning {
+                        output.push(ESC);
+                    }
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    /// Pass on, rewrite or drop one complete escape sequence
+    fn rewrite(&self, sequence: &[u8], output: &mut Vec<u8>) {
+        let sgr = sequence[1] == b'['
+            && sequence.ends_with(b"m")
+            && sequence[2..sequence.len() - 1]
+                .iter()
+                .all(|&b| b.is_ascii_digit() || b == b';' || b == b':');
+
+        if !sgr {
+            if self.profile.cursor_positioning {
+                output.extend_from_slice(sequence);
+            }
+        } else if self.profile.attributes {
+            self.rewrite_sgr(&sequence[2..sequence.len() - 1], output);
+        }
+    }
+
+    /// Rewrite the parameters of one SGR sequence (between `ESC [` and `m`)
+    fn rewrite_sgr(&self, params: &[u8], output: &mut Vec<u8>) {
+        // `ESC [ m` is a reset and has nothing to translate
+        if self.profile.color_depth == ColorDepth::TrueColor || params.is_empty() {
+            output.extend_from_slice(b"\x1b[");
+            output.extend_from_slice(params);
+            output.push(b'm');
+            return;
+        }
+
+        // Only digits and separators, as checked by `rewrite`
+        let text = String::from_utf8_lossy(params);
+        let values: Vec<Option<u16>> = text.split([';', ':']).map(|p| p.parse().ok()).collect();
+        let mut kept: Vec<String> = Vec::new();
+        let mut i = 0;
+
+        while i < values.len() {
+            let Some(code) = values[i] else {
+                // Empty parameter means 0
+                kept.push("0".to_string());
+                i += 1;
+                continue;
+            };
+
+            let (color, background, used) = match code {
+                30..=37 => (Some(SgrColor::Basic((code - 30) as u8)), false, 1),
+                40..=47 => (Some(SgrColor::Basic((code - 40) as u8)), true, 1),
+                90..=97 => (Some(SgrColor::Basic((code - 90 + 8) as u8)), false, 1),
+                100..=107 => (Some(SgrColor::Basic((code - 100 + 8) as u8)), true, 1),
+                39 => (Some(SgrColor::Default), false, 1),
+                49 => (Some(SgrColor::Default), true, 1),
+                38 | 48 => match extended_color(&values[i + 1..]) {
+                    Some((color, used)) => (Some(color), code == 48, 1 + used),
+                    // Malformed, so forward the rest untouched
+                    None => {
+                        kept.extend(
+                            values[i..]
+                                .iter()
+                                .map(|v| v.map(|n| n.to_string()).unwrap_or_default()),
+                        );
+                        break;
+                    }
+                },
+                _ => (None, false, 1),
+            };
+
+            match color {
+                Some(color) => {
+                    if let Some(encoded) = self.encode(color, background) {
+                        kept.push(encoded);
+                    }
+                }
+                None => kept.push(code.to_string()),
+            }
+            i += used;
+        }
+
+        // Everything was color on a monochrome terminal; an empty SGR
+        // would be a reset, so send nothing at all
+        if kept.is_empty() {
+            return;
+        }
+
+        output.extend_from_slice(format!("\x1b[{}m", kept.join(";")).as_bytes());
+    }
+
+    /// SGR parameters for `color` at this filter's depth, if it can be shown
+    fn encode(&self, color: SgrColor, background: bool) -> Option<String> {
+        let base = if background { 40 } else { 30 };
+
+        match (self.profile.color_depth, color) {
+            (ColorDepth::Monochrome, _) => None,
+            (_, SgrColor::Default) => Some((base + 9).to_string()),
+            (ColorDepth::Basic8, _) => Some((base + (to_basic(color) & 7) as u16).to_string()),
+            (ColorDepth::Basic16, _) | (_, SgrColor::Basic(_)) => {
+                Some(basic_code(base, to_basic(color)))
+            }
+            (_, SgrColor::Indexed(index)) => Some(format!("{};5;{}", base + 8, index)),
+            (ColorDepth::Extended256, SgrColor::Rgb(r, g, b)) => {
+                Some(format!("{};5;{}", base + 8, rgb_to_256(r, g, b)))
+            }
+            (ColorDepth::TrueColor, SgrColor::Rgb(r, g, b)) => {
+                Some(format!("{};2;{};{};{}", base + 8, r, g, b))
+            }
+        }
+    }
+}
+
+enum SequenceEnd {
+    /// Full sequence of this many bytes, starting at ESC
+    Complete(usize),
+    /// Starts like a sequence but the end has not arrived
+    Incomplete,
+    Invalid,
+}
+
+/// Find the end of the escape sequence at the start of `bytes` (which
+/// begins with ESC): CSI (`ESC [`), a string such as OSC (`ESC ]`) ended
+/// by BEL or `ESC \`, or `ESC` with intermediates and a final byte
+fn sequence_end(bytes: &[u8]) -> SequenceEnd {
+    match bytes.get(1) {
+        None => SequenceEnd::Incomplete,
+        Some(b'[') => {
+            for (offset, &byte) in bytes.iter().enumerate().skip(2) {
+                match byte {
+                    0x40..=0x7e => return SequenceEnd::Complete(offset + 1),
+                    0x20..=0x3f => {}
+                    _ => return SequenceEnd::Invalid,
+                }
+            }
+            SequenceEnd::Incomplete
+        }
+        Some(b']' | b'P' | b'X' | b'^' | b'_') => {
+            for (offset, &byte) in bytes.iter().enumerate().skip(2) {
+                match (byte, bytes.get(offset + 1)) {
+                    (BEL, _) => return SequenceEnd::Complete(offset + 1),
+                    (ESC, Some(b'\\')) => return SequenceEnd::Complete(offset + 2),
+                    (ESC, None) => return SequenceEnd::Incomplete,
+                    (ESC, Some(_)) => return SequenceEnd::Invalid,
+                    _ => {}
+                }
+            }
+            SequenceEnd::Incomplete
+        }
+        Some(_) => {
+            for (offset, &byte) in bytes.iter().enumerate().skip(1) {
+                match byte {
+                    0x30..=0x7e => return SequenceEnd::Complete(offset + 1),
+                    0x20..=0x2f => {}
+                    _ => return SequenceEnd::Invalid,
+                }
+            }
+            SequenceEnd::Incomplete
+        }
+    }
+}
+
+/// Parse the tail of a 38/48 parameter: `5;n` or `2;r;g;b`
+///
+/// Returns the color and how many parameters it used.
+fn extended_color(rest: &[Option<u16>]) -> Option<(SgrColor, usize)> {
+    let byte = |i: usize| {
+        rest.get(i)
+            .copied()
+            .flatten()
+            .and_then(|v| u8::try_from(v).ok())
+    };
+
+    match rest.first().copied().flatten()? {
+        5 => Some((SgrColor::Indexed(byte(1)?), 2)),
+        2 => Some((SgrColor::Rgb(byte(1)?, byte(2)?, byte(3)?), 4)),
+        _ => None,
+    }
+}
+
+fn basic_code(base: u16, index: u8) -> String {
+    if index < 8 {
+        (base + index as u16).to_string()
+    } else {
+        (base + 60 + (index - 8) as u16).to_string()
+    }
+}
+
+/// RGB value of a 256-color palette entry
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC_PALETTE[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[((i / 6) % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let dr = r1 as i32 - r2 as i32;
+    let dg = g1 as i32 - g2 as i32;
+    let db = b1 as i32 - b2 as i32;
+    (dr * dr + dg * dg + db * db) as u32
+}
+
+/// Nearest 256-color palette index, from the color cube or the gray ramp
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |v: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+
+    let average = (r as u16 + g as u16 + b as u16) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance((r, g, b), indexed_to_rgb(gray)) < distance((r, g, b), indexed_to_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// Nearest of the 16 basic colors
+fn to_basic(color: SgrColor) -> u8 {
+    let rgb = match color {
+        SgrColor::Basic(index) => return index,
+        SgrColor::Indexed(index) if index < 16 => return index,
+        SgrColor::Indexed(index) => indexed_to_rgb(index),
+        SgrColor::Rgb(r, g, b) => (r, g, b),
+        SgrColor::Default => return 7,
+    };
+
+    (0..BASIC_PALETTE.len())
+        .min_by_key(|&i| distance(rgb, BASIC_PALETTE[i]))
+        .unwrap_or(7) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(profile: AnsiProfile, input: &str) -> String {
+        String::from_utf8(AnsiFilter::new(profile).filter(input.as_bytes())).unwrap()
+    }
+
+    fn colors(color_depth: ColorDepth) -> AnsiProfile {
+        AnsiProfile {
+            color_depth,
+            ..AnsiProfile::FULL
+        }
+    }
+
+    #[test]
+    fn test_colors_step_down() {
+        let rgb = "\x1b[38;2;250;5;5m";
+        assert_eq!(filtered(colors(ColorDepth::TrueColor), rgb), rgb);
+        assert_eq!(
+            filtered(colors(ColorDepth::Extended256), rgb),
+            "\x1b[38;5;196m"
+        );
+        assert_eq!(filtered(colors(ColorDepth::Basic16), rgb), "\x1b[91m");
+        assert_eq!(filtered(colors(ColorDepth::Basic8), rgb), "\x1b[31m");
+        assert_eq!(filtered(colors(ColorDepth::Monochrome), rgb), "");
+
+        assert_eq!(
+            filtered(colors(ColorDepth::Monochrome), "\x1b[1;31;44mx\x1b[m"),
+            "\x1b[1mx\x1b[m"
+        );
+        // Private parameters aren't SGR colors
+        assert_eq!(
+            filtered(colors(ColorDepth::Basic8), "\x1b[>4;2m"),
+            "\x1b[>4;2m"
+        );
+    }
+
+    #[test]
+    fn test_cursor_control_removed() {
+        let vt100ish = AnsiProfile {
+            attributes: true,
+            color_depth: ColorDepth::Monochrome,
+            cursor_positioning: false,
+        };
+        let screen = "\x1b[2J\x1b[1;1H\x1b7\x1b]0;title\x07\x1b(B\x1b[4mtext\x1b[0m";
+        assert_eq!(filtered(vt100ish, screen), "\x1b[4mtext\x1b[0m");
+        assert_eq!(filtered(AnsiProfile::DUMB, screen), "text");
+        assert_eq!(filtered(AnsiProfile::FULL, screen), screen);
+
+        // A stray ESC doesn't reach a dumb terminal either
+        assert_eq!(filtered(AnsiProfile::DUMB, "a\x1b\x01b"), "a\x01b");
+    }
+
+    #[test]
+    fn test_sequence_split_across_writes() {
+        let mut filter = AnsiFilter::new(colors(ColorDepth::Basic8));
+        let mut out = filter.filter(b"a\x1b[38;2;0;0");
+        assert_eq!(out, b"a");
+        out.extend(filter.filter(b";255mb"));
+        assert_eq!(out, b"a\x1b[34mb");
+
+        let mut filter = AnsiFilter::new(AnsiProfile::DUMB);
+        let mut out = filter.filter(b"a\x1b]2;long ti");
+        out.extend(filter.filter(b"tle\x1b"));
+        out.extend(filter.filter(b"\\b"));
+        assert_eq!(out, b"ab");
+    }
+
+    #[test]
+    fn test_profile_from_capabilities() {
+        let caps = TerminalCapabilities {
+            supports_ansi: true,
+            color_depth: ColorDepth::Basic16,
+            cursor_positioning: true,
+            ..TerminalCapabilities::default()
+        };
+        assert_eq!(AnsiProfile::from(&caps), colors(ColorDepth::Basic16));
+        assert_eq!(
+            AnsiProfile::from(&TerminalCapabilities::default()),
+            AnsiProfile::DUMB
+        );
+    }
+}
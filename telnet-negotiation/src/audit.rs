@@ -0,0 +1,373 @@
+//! # Strict RFC Compliance Audit
+//!
+//! This module validates outgoing byte sequences against the framing rules of
+//! **RFC 854** and **RFC 855**, and against what the connection has
+//! negotiated, before they reach the wire.
+//!
+//! ## Key Concepts
+//!
+//! ### What Gets Checked
+//! Every write the stream performs is expected to be a complete unit: escaped
+//! application data, a single command, a negotiation, or a full sub-negotiation.
+//! The audit walks the bytes and reports the first violation it finds:
+//! - `IAC` must be followed by a known command byte or a second `IAC`
+//! - `WILL`/`WONT`/`DO`/`DONT` must carry an option byte
+//! - `IAC SB <option> ... IAC SE` must be closed in the same unit
+//! - Data bytes of 255 inside a sub-negotiation must be doubled
+//! - `IAC SE` may not appear outside a sub-negotiation
+//! - `WILL`/`DO` may only offer or ask for an option this end handles
+//! - `IAC SB <option>` is only sent for an option negotiated on either side
+//!
+//! ### Option Context
+//! The last two rules need to know about the connection. An
+//! [`OptionRegistry`] answers for it: `TelnetStream` passes its option
+//! handlers and its RFC 1143 negotiator state.
+//!
+//! ### Audit Mode
+//! With the `rfc-audit` cargo feature enabled, `TelnetStream` runs every
+//! outgoing write through [`audit_outgoing`]. Violations panic in debug builds
+//! so they surface in tests, and are logged to stderr in release builds so a
+//! live server keeps running.
+
+use crate::protocol::{IAC, TelnetCommand, TelnetOption};
+use std::fmt;
+
+/// What the audit knows about the connection the bytes are for
+pub trait OptionRegistry {
+    /// Whether this end handles `option`, so it may send WILL or DO for it
+    fn handles(&self, option: TelnetOption) -> bool;
+
+    /// Whether `option` is on, or being negotiated, on either side, so it
+    /// may send a sub-negotiation for it
+    fn is_negotiated(&self, option: TelnetOption) -> bool;
+}
+
+/// A framing violation found in outgoing bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditViolation {
+    /// IAC followed by a byte that is not a telnet command
+    UnknownCommand { offset: usize, byte: u8 },
+    /// Negotiation or SB command with no option byte after it
+    MissingOption {
+        offset: usize,
+        command: TelnetCommand,
+    },
+    /// IAC SB without a matching IAC SE
+    UnterminatedSubnegotiation { offset: usize },
+    /// IAC followed by something other than IAC or SE inside a sub-negotiation
+    UnescapedIacInSubnegotiation { offset: usize },
+    /// IAC SE with no open sub-negotiation
+    StraySubnegotiationEnd { offset: usize },
+    /// Lone IAC at the very end of the unit
+    TruncatedCommand { offset: usize },
+    /// WILL or DO for an option nothing on this end handles
+    UnhandledOption {
+        offset: usize,
+        command: TelnetCommand,
+        option: TelnetOption,
+    },
+    /// IAC SB for an option negotiated on neither side
+    NotNegotiated { offset: usize, option: TelnetOption },
+}
+
+impl fmt::Display for AuditViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditViolation::UnknownCommand { offset, byte } => {
+                write!(f, "IAC followed by unknown command {} at {}", byte, offset)
+            }
+            AuditViolation::MissingOption { offset, command } => {
+                write!(f, "IAC {:?} without option byte at {}", command, offset)
+            }
+            AuditViolation::UnterminatedSubnegotiation { offset } => {
+                write!(f, "IAC SB at {} is never closed by IAC SE", offset)
+            }
+            AuditViolation::UnescapedIacInSubnegotiation { offset } => {
+                write!(f, "unescaped IAC inside sub-negotiation at {}", offset)
+            }
+            AuditViolation::StraySubnegotiationEnd { offset } => {
+                write!(f, "IAC SE without IAC SB at {}", offset)
+            }
+            AuditViolation::TruncatedCommand { offset } => {
+                write!(f, "lone IAC at end of write at {}", offset)
+            }
+            AuditViolation::UnhandledOption {
+                offset,
+                command,
+                option,
+            } => {
+                write!(
+                    f,
+                    "IAC {:?} {:?} with no handler at {}",
+                    command, option, offset
+                )
+            }
+            AuditViolation::NotNegotiated { offset, option } => {
+                write!(
+                    f,
+                    "IAC SB {:?} before it was negotiated at {}",
+                    option, offset
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuditViolation {}
+
+/// Validate that a complete outgoing write is well-formed telnet that
+/// fits what `options` says about the connection
+///
+/// # Example
+/// ```
+/// use telnet_negotiation::audit::{audit_outgoing, AuditViolation, OptionRegistry};
+/// use telnet_negotiation::TelnetOption;
+///
+/// /// Handles ECHO, and nothing is negotiated yet
+/// struct EchoOnly;
+///
+/// impl OptionRegistry for EchoOnly {
+///     fn handles(&self, option: TelnetOption) -> bool {
+///         option == TelnetOption::ECHO
+///     }
+///
+///     fn is_negotiated(&self, _option: TelnetOption) -> bool {
+///         false
+///     }
+/// }
+///
+/// // IAC WILL ECHO
+/// assert!(audit_outgoing(&[255, 251, 1], &EchoOnly).is_ok());
+///
+/// // IAC WILL with the option byte missing
+/// assert!(matches!(
+///     audit_outgoing(&[255, 251], &EchoOnly),
+///     Err(AuditViolation::MissingOption { .. })
+/// ));
+///
+/// // IAC DO NAWS, which nothing here handles
+/// assert!(matches!(
+///     audit_outgoing(&[255, 253, 31], &EchoOnly),
+///     Err(AuditViolation::UnhandledOption { .. })
+/// ));
+/// ```
+pub fn audit_outgoing(bytes: &[u8], options: &dyn OptionRegistry) -> Result<(), AuditViolation> {
+    let mut i = 0;
+    // Offset of the currently open IAC SB, if any
+    let mut open_sb: Option<usize> = None;
+
+    while i < bytes.len() {
+        if bytes[i] != IAC {
+            i += 1;
+            continue;
+        }
+
+        let Some(&next) = bytes.get(i + 1) else {
+            return Err(match open_sb {
+                Some(offset) => AuditViolation::UnterminatedSubnegotiation { offset },
+                None => AuditViolation::TruncatedCommand { offset: i },
+            });
+        };
+
+        if next == IAC {
+            // Escaped data byte, valid both in data and in SB payloads
+            i += 2;
+            continue;
+        }
+
+        if open_sb.is_some() {
+            if next == TelnetCommand::SE.to_byte() {
+                open_sb = None;
+                i += 2;
+                continue;
+            }
+            return Err(AuditViolation::UnescapedIacInSubnegotiation { offset: i });
+        }
+
+        let command = TelnetCommand::from_byte(next).ok_or(AuditViolation::UnknownCommand {
+            offset: i,
+            byte: next,
+        })?;
+
+        match command {
+            TelnetCommand::SE => {
+                return Err(AuditViolation::StraySubnegotiationEnd { offset: i });
+            }
+            TelnetCommand::SB => {
+                if i + 2 >= bytes.len() {
+                    return Err(AuditViolation::MissingOption { offset: i, command });
+                }
+                let option = TelnetOption::new(bytes[i + 2]);
+                if !options.is_negotiated(option) {
+                    return Err(AuditViolation::NotNegotiated { offset: i, option });
+                }
+                open_sb = Some(i);
+                i += 3;
+            }
+            cmd if cmd.requires_option() => {
+                if i + 2 >= bytes.len() {
+                    return Err(AuditViolation::MissingOption { offset: i, command });
+                }
+                let option = TelnetOption::new(bytes[i + 2]);
+                if matches!(cmd, TelnetCommand::WILL | TelnetCommand::DO)
+                    && !options.handles(option)
+                {
+                    return Err(AuditViolation::UnhandledOption {
+                        offset: i,
+                        command,
+                        option,
+                    });
+                }
+                i += 3;
+            }
+            _ => i += 2,
+        }
+    }
+
+    match open_sb {
+        Some(offset) => Err(AuditViolation::UnterminatedSubnegotiation { offset }),
+        None => Ok(()),
+    }
+}
+
+/// Report a violation according to the build profile
+///
+/// Debug builds panic so the offending code path is caught in tests;
+/// release builds log and let the write proceed.
+#[cfg(feature = "rfc-audit")]
+pub(crate) fn enforce(context: &str, bytes: &[u8], options: &dyn OptionRegistry) {
+    if let Err(violation) = audit_outgoing(bytes, options) {
+        if cfg!(debug_assertions) {
+            panic!("[rfc-audit] {}: {} ({:?})", context, violation, bytes);
+        } else {
+            eprintln!("[rfc-audit] {}: {}", context, violation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::TelnetSequence;
+
+    /// Handles ECHO, TERMINAL-TYPE and NAWS, with the last two enabled
+    struct Connection;
+
+    impl OptionRegistry for Connection {
+        fn handles(&self, option: TelnetOption) -> bool {
+            matches!(
+                option,
+                TelnetOption::ECHO | TelnetOption::TERMINAL_TYPE | TelnetOption::NAWS
+            )
+        }
+
+        fn is_negotiated(&self, option: TelnetOption) -> bool {
+            matches!(option, TelnetOption::TERMINAL_TYPE | TelnetOption::NAWS)
+        }
+    }
+
+    fn audit(bytes: &[u8]) -> Result<(), AuditViolation> {
+        audit_outgoing(bytes, &Connection)
+    }
+
+    #[test]
+    fn test_valid_units() {
+        assert!(audit(b"plain text").is_ok());
+        assert!(audit(&[65, 255, 255, 66]).is_ok());
+        assert!(audit(&[255, 241]).is_ok()); // IAC NOP
+
+        let neg = TelnetSequence::Negotiation {
+            command: TelnetCommand::DO,
+            option: TelnetOption::NAWS,
+        };
+        assert!(audit(&neg.to_bytes()).is_ok());
+
+        let sb = TelnetSequence::SubNegotiation {
+            option: TelnetOption::TERMINAL_TYPE,
+            data: vec![1],
+        };
+        assert!(audit(&sb.to_bytes()).is_ok());
+
+        // Escaped 255 inside SB payload
+        assert!(audit(&[255, 250, 31, 0, 255, 255, 0, 24, 255, 240]).is_ok());
+    }
+
+    #[test]
+    fn test_violations() {
+        assert_eq!(
+            audit(&[255, 100]),
+            Err(AuditViolation::UnknownCommand {
+                offset: 0,
+                byte: 100
+            })
+        );
+        assert_eq!(
+            audit(&[65, 255]),
+            Err(AuditViolation::TruncatedCommand { offset: 1 })
+        );
+        assert_eq!(
+            audit(&[255, 253]),
+            Err(AuditViolation::MissingOption {
+                offset: 0,
+                command: TelnetCommand::DO
+            })
+        );
+        assert_eq!(
+            audit(&[255, 250, 24, 1]),
+            Err(AuditViolation::UnterminatedSubnegotiation { offset: 0 })
+        );
+        assert_eq!(
+            audit(&[255, 250, 31, 0, 255, 0, 255, 240]),
+            Err(AuditViolation::UnescapedIacInSubnegotiation { offset: 4 })
+        );
+        assert_eq!(
+            audit(&[255, 240]),
+            Err(AuditViolation::StraySubnegotiationEnd { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_will_and_do_need_a_handler() {
+        assert!(audit(&[255, 251, 1]).is_ok()); // IAC WILL ECHO
+        assert!(audit(&[255, 253, 31]).is_ok()); // IAC DO NAWS
+        assert_eq!(
+            audit(&[b'>', 255, 253, 201]),
+            Err(AuditViolation::UnhandledOption {
+                offset: 1,
+                command: TelnetCommand::DO,
+                option: TelnetOption::GMCP
+            })
+        );
+        assert_eq!(
+            audit(&[255, 251, 201]),
+            Err(AuditViolation::UnhandledOption {
+                offset: 0,
+                command: TelnetCommand::WILL,
+                option: TelnetOption::GMCP
+            })
+        );
+
+        // Refusing needs no handler
+        assert!(audit(&[255, 252, 201]).is_ok()); // IAC WONT GMCP
+        assert!(audit(&[255, 254, 201]).is_ok()); // IAC DONT GMCP
+    }
+
+    #[test]
+    fn test_subnegotiation_needs_a_negotiated_option() {
+        // ECHO is handled but not enabled on either side
+        assert_eq!(
+            audit(&[255, 250, 1, 0, 255, 240]),
+            Err(AuditViolation::NotNegotiated {
+                offset: 0,
+                option: TelnetOption::ECHO
+            })
+        );
+        assert_eq!(
+            audit(&[b'x', 255, 250, 201, b'{', b'}', 255, 240]),
+            Err(AuditViolation::NotNegotiated {
+                offset: 1,
+                option: TelnetOption::GMCP
+            })
+        );
+    }
+}
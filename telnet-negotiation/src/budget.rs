@@ -0,0 +1,139 @@
+//! # Per-connection memory budget
+//!
+//! A `TelnetStream` buffers what the peer sends until the application reads
+//! it: decoded data, raw bytes not yet parsed, a sub-negotiation waiting for
+//! its IAC SE, and the negotiation history. A well-behaved client never
+//! gets near a megabyte, but one that streams an endless sub-negotiation,
+//! or floods a session the application has stopped reading, would grow them
+//! without bound. On a server with hundreds of connections that adds up.
+//!
+//! [`MemoryBudget`] caps the total per connection and picks what happens
+//! when a peer goes over it; [`MemoryStats`] reports what is in use.
+//!
+//! ```rust
+//! use telnet_negotiation::{MemoryBudget, OverflowPolicy};
+//!
+//! // 64 KiB per connection, discarding whatever arrives past that
+//! let budget = MemoryBudget::new(64 * 1024, OverflowPolicy::Drop);
+//! assert_eq!(budget.limit(), Some(64 * 1024));
+//! ```
+
+/// Budget a stream starts with: 1 MiB, disconnecting peers that exceed it
+pub const DEFAULT_MEMORY_BUDGET: usize = 1024 * 1024;
+
+/// What a stream does when a peer pushes it over budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Discard everything buffered from the peer and carry on
+    Flush,
+    /// Keep what is buffered and discard the newest input that doesn't fit,
+    /// starting with any unfinished sub-negotiation
+    Drop,
+    /// Shut the connection down; reads fail with `ErrorKind::OutOfMemory`
+    #[default]
+    Disconnect,
+}
+
+/// Memory limit for one connection's receive-side buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    policy: OverflowPolicy,
+}
+
+impl MemoryBudget {
+    /// Allow at most `limit` bytes, applying `policy` beyond that
+    pub fn new(limit: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            limit: Some(limit),
+            policy,
+        }
+    }
+
+    /// No limit at all (usage is still reported)
+    pub fn unlimited() -> Self {
+        Self {
+            limit: None,
+            policy: OverflowPolicy::default(),
+        }
+    }
+
+    /// Bytes allowed, or `None` when unlimited
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// What happens past the limit
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    /// Whether `usage` bytes is over budget
+    pub fn is_exceeded(&self, usage: usize) -> bool {
+        self.limit.is_some_and(|limit| usage > limit)
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_BUDGET, OverflowPolicy::default())
+    }
+}
+
+/// Memory a connection is holding, by buffer, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryStats {
+    /// Decoded data waiting for the application to read it
+    pub data_buffer: usize,
+    /// Raw bytes received but not yet parsed
+    pub read_buffer: usize,
+    /// A sub-negotiation (or other sequence) still waiting for its end
+    pub subnegotiation: usize,
+    /// Negotiation history kept for diagnostics and client profiling
+    pub history: usize,
+    /// Highest total seen on this connection
+    pub peak: usize,
+    /// Times the peer has pushed the connection over budget
+    pub overflows: u64,
+}
+
+impl MemoryStats {
+    /// Bytes in use across all buffers right now
+    pub fn total(&self) -> usize {
+        self.data_buffer + self.read_buffer + self.subnegotiation + self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_budget() {
+        let budget = MemoryBudget::default();
+        assert_eq!(budget.limit(), Some(DEFAULT_MEMORY_BUDGET));
+        assert_eq!(budget.policy(), OverflowPolicy::Disconnect);
+        assert!(!budget.is_exceeded(DEFAULT_MEMORY_BUDGET));
+        assert!(budget.is_exceeded(DEFAULT_MEMORY_BUDGET + 1));
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let budget = MemoryBudget::unlimited();
+        assert_eq!(budget.limit(), None);
+        assert!(!budget.is_exceeded(usize::MAX));
+    }
+
+    #[test]
+    fn test_stats_total() {
+        let stats = MemoryStats {
+            data_buffer: 10,
+            read_buffer: 5,
+            subnegotiation: 3,
+            history: 2,
+            peak: 100,
+            overflows: 1,
+        };
+        assert_eq!(stats.total(), 20);
+    }
+}
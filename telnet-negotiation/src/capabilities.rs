@@ -0,0 +1,326 @@
+//! # Terminal capabilities
+//!
+//! A client describes its terminal in several places: the names it gives
+//! for TERMINAL-TYPE (RFC 1091) and the MTTS flags that may follow them,
+//! the variables it shares over NEW-ENVIRON (RFC 1572), and the window
+//! size it reports with NAWS (RFC 1073). [`TerminalCapabilities::merge`]
+//! fuses them into one answer, and a `TelnetStream` recomputes it whenever
+//! any of them changes.
+//!
+//! ## Precedence
+//!
+//! Where sources give a single value, the first one that has it wins:
+//!
+//! - **Window size**: NAWS, then the `COLUMNS` and `LINES` variables.
+//! - **Terminal type**: the TERMINAL-TYPE name, then the `TERM` variable.
+//! - **Character set**: the `CHARSET` variable (as MNES clients send it),
+//!   then the encoding in `LC_ALL`, `LC_CTYPE` or `LANG` (`en_US.UTF-8`),
+//!   then `UTF-8` if the MTTS flags claim it.
+//!
+//! ANSI, color and cursor support only ever go up. The terminal name sets a
+//! starting point from the names this crate knows; MTTS flags and a
+//! `COLORTERM` of `truecolor` or `24bit` raise it, since a client that
+//! states a capability outright knows better than a name lookup. The
+//! screen reader flag comes from MTTS alone.
+//!
+//! There is no CHARSET option (RFC 2066) handler yet, so the character set
+//! comes from the variables and flags above.
+//!
+//! ```rust
+//! use telnet_negotiation::{CapabilitySources, ColorDepth, TerminalCapabilities};
+//!
+//! let caps = TerminalCapabilities::merge(&CapabilitySources {
+//!     mtts: Some(1 | 4 | 8), // ANSI, UTF-8, 256 colors
+//!     ..CapabilitySources::default()
+//! });
+//! assert!(caps.supports_ansi);
+//! assert_eq!(caps.color_depth, ColorDepth::Extended256);
+//! assert_eq!(caps.charset.as_deref(), Some("UTF-8"));
+//! ```
+
+use crate::options::terminal_type::{
+    ColorSupport, MTTS_256_COLORS, MTTS_ANSI, MTTS_SCREEN_READER, MTTS_TRUECOLOR, MTTS_UTF8,
+    MTTS_VT100, TerminalInfo, TerminalTypeOption,
+};
+use crate::options::{Environment, WindowSize};
+
+/// Terminal capabilities detected from option negotiation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TerminalCapabilities {
+    /// Terminal width in characters
+    pub width: Option<u16>,
+    /// Terminal height in lines
+    pub height: Option<u16>,
+    /// Terminal type string (e.g., "XTERM", "VT100")
+    pub terminal_type: Option<String>,
+    /// Supports ANSI escape sequences
+    pub supports_ansi: bool,
+    /// Supports color output
+    pub supports_color: bool,
+    /// Color support depth
+    pub color_depth: ColorDepth,
+    /// Client reported a screen reader (MTTS)
+    pub screen_reader: bool,
+    /// The cursor can be moved and the screen cleared with escape sequences
+    pub cursor_positioning: bool,
+    /// Character set the client uses, as it named it (e.g. "UTF-8")
+    pub charset: Option<String>,
+    /// Whether that character set is UTF-8
+    pub utf8: bool,
+}
+
+/// Color support levels detected from terminal type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorDepth {
+    /// No color support (monochrome)
+    #[default]
+    Monochrome,
+    /// Basic 8-color support (3-bit)
+    Basic8,
+    /// 8 colors plus their bright variants (4-bit)
+    Basic16,
+    /// 256-color support (8-bit)
+    Extended256,
+    /// True color support (24-bit RGB)
+    TrueColor,
+}
+
+impl From<ColorSupport> for ColorDepth {
+    fn from(support: ColorSupport) -> Self {
+        match support {
+            ColorSupport::None => ColorDepth::Monochrome,
+            ColorSupport::Basic8 => ColorDepth::Basic8,
+            ColorSupport::Color16 => ColorDepth::Basic16,
+            ColorSupport::Color256 => ColorDepth::Extended256,
+            ColorSupport::TrueColor => ColorDepth::TrueColor,
+        }
+    }
+}
+
+/// What the client has said about its terminal so far, for
+/// [`TerminalCapabilities::merge`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapabilitySources<'a> {
+    /// The TERMINAL-TYPE name and what it implies
+    pub terminal: Option<&'a TerminalInfo>,
+    /// MTTS flags from the end of the TERMINAL-TYPE cycle
+    pub mtts: Option<u32>,
+    /// Variables received over NEW-ENVIRON
+    pub environment: Option<&'a Environment>,
+    /// The latest NAWS report
+    pub window: Option<WindowSize>,
+}
+
+impl TerminalCapabilities {
+    /// Fuse every source into one view, in the
+    /// [documented order](crate::capabilities#precedence)
+    pub fn merge(sources: &CapabilitySources<'_>) -> Self {
+        let var = |name: &str| {
+            sources
+                .environment
+                .and_then(|environment| environment.var(name).or(environment.user_var(name)))
+                .filter(|value| !value.is_empty())
+        };
+        let flag = |bit: u32| sources.mtts.is_some_and(|flags| flags & bit != 0);
+
+        let mut caps = TerminalCapabilities::default();
+
+        let size = sources.window.map(|size| (size.width, size.height));
+        let size = size.or_else(|| {
+            let number = |name| var(name).and_then(|value| value.trim().parse().ok());
+            Some((number("COLUMNS")?, number("LINES")?))
+        });
+        if let Some((width, height)) = size {
+            caps.width = Some(width);
+            caps.height = Some(height);
+        }
+
+        // The name sets a starting point
+        let named = match sources.terminal {
+            Some(info) => Some((info.terminal_type.clone(), info.capabilities.clone())),
+            None => var("TERM").map(|term| {
+                (
+                    term.to_string(),
+                    TerminalTypeOption::detect_capabilities(term),
+                )
+            }),
+        };
+        if let Some((name, named)) = named {
+            caps.terminal_type = Some(name);
+            caps.supports_ansi = named.ansi_support;
+            caps.color_depth = named.color_support.into();
+            caps.cursor_positioning = named.cursor_positioning;
+        }
+
+        // Stated capabilities only raise it
+        let mut raise = |depth: ColorDepth| {
+            caps.supports_ansi = true;
+            caps.color_depth = caps.color_depth.max(depth);
+        };
+        if flag(MTTS_ANSI) {
+            raise(ColorDepth::Basic8);
+        }
+        if flag(MTTS_256_COLORS) {
+            raise(ColorDepth::Extended256);
+        }
+        if flag(MTTS_TRUECOLOR)
+            || var("COLORTERM")
+                .is_some_and(|value| value.eq_ignore_ascii_case("truecolor") || value == "24bit")
+        {
+            raise(ColorDepth::TrueColor);
+        }
+        caps.supports_color = caps.color_depth != ColorDepth::Monochrome;
+        caps.screen_reader = flag(MTTS_SCREEN_READER);
+        caps.cursor_positioning |= flag(MTTS_VT100);
+
+        caps.charset = var("CHARSET")
+            .map(str::to_string)
+            .or_else(|| {
+                ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .into_iter()
+                    .find_map(var)
+                    .and_then(locale_charset)
+            })
+            .or_else(|| flag(MTTS_UTF8).then(|| "UTF-8".to_string()));
+        caps.utf8 = caps.charset.as_deref().is_some_and(|charset| {
+            charset.eq_ignore_ascii_case("UTF-8") || charset.eq_ignore_ascii_case("UTF8")
+        });
+
+        caps
+    }
+}
+
+/// The encoding part of a locale such as `en_US.UTF-8@euro`
+fn locale_charset(locale: &str) -> Option<String> {
+    let (_, encoding) = locale.split_once('.')?;
+    let encoding = encoding.split('@').next().unwrap_or(encoding);
+    (!encoding.is_empty()).then(|| encoding.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::NewEnvironOption;
+    use crate::options::TelnetOptionHandler;
+
+    fn environment(pairs: &[(&str, &str)]) -> NewEnvironOption {
+        let variables: Vec<_> = pairs
+            .iter()
+            .map(|&(name, value)| (crate::options::EnvironKind::Var, name, Some(value)))
+            .collect();
+        let mut option = NewEnvironOption::new();
+        option
+            .handle_subnegotiation(&NewEnvironOption::encode_variables(0, &variables))
+            .unwrap();
+        option
+    }
+
+    #[test]
+    fn test_nothing_known() {
+        let caps = TerminalCapabilities::merge(&CapabilitySources::default());
+        assert_eq!(caps, TerminalCapabilities::default());
+    }
+
+    #[test]
+    fn test_naws_beats_environment() {
+        let environ = environment(&[("COLUMNS", "132"), ("LINES", "50")]);
+        let mut sources = CapabilitySources {
+            environment: Some(environ.environment()),
+            ..CapabilitySources::default()
+        };
+        let caps = TerminalCapabilities::merge(&sources);
+        assert_eq!((caps.width, caps.height), (Some(132), Some(50)));
+
+        sources.window = Some(WindowSize {
+            width: 80,
+            height: 24,
+        });
+        let caps = TerminalCapabilities::merge(&sources);
+        assert_eq!((caps.width, caps.height), (Some(80), Some(24)));
+    }
+
+    #[test]
+    fn test_stated_colors_raise_the_name() {
+        let mut ttype = TerminalTypeOption::new();
+        ttype.set_terminal_type("VT100".to_string());
+        let environ = environment(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]);
+
+        let caps = TerminalCapabilities::merge(&CapabilitySources {
+            terminal: ttype.terminal_info(),
+            ..CapabilitySources::default()
+        });
+        assert_eq!(caps.terminal_type.as_deref(), Some("VT100"));
+        assert!(!caps.supports_color);
+
+        // TERM only counts without a TERMINAL-TYPE name; COLORTERM always does
+        let caps = TerminalCapabilities::merge(&CapabilitySources {
+            terminal: ttype.terminal_info(),
+            environment: Some(environ.environment()),
+            ..CapabilitySources::default()
+        });
+        assert_eq!(caps.terminal_type.as_deref(), Some("VT100"));
+        assert_eq!(caps.color_depth, ColorDepth::TrueColor);
+
+        let caps = TerminalCapabilities::merge(&CapabilitySources {
+            mtts: Some(MTTS_ANSI | MTTS_SCREEN_READER),
+            environment: Some(environ.environment()),
+            ..CapabilitySources::default()
+        });
+        assert_eq!(caps.terminal_type.as_deref(), Some("xterm-256color"));
+        assert!(caps.supports_ansi && caps.screen_reader);
+        assert_eq!(caps.color_depth, ColorDepth::TrueColor);
+    }
+
+    #[test]
+    fn test_charset_precedence() {
+        let charset = |pairs: &[(&str, &str)], mtts| {
+            let environ = environment(pairs);
+            let caps = TerminalCapabilities::merge(&CapabilitySources {
+                mtts,
+                environment: Some(environ.environment()),
+                ..CapabilitySources::default()
+            });
+            (caps.charset, caps.utf8)
+        };
+
+        assert_eq!(charset(&[], None), (None, false));
+        assert_eq!(
+            charset(&[], Some(MTTS_UTF8)),
+            (Some("UTF-8".to_string()), true)
+        );
+        assert_eq!(
+            charset(&[("LANG", "de_DE.ISO-8859-1@euro")], Some(MTTS_UTF8)),
+            (Some("ISO-8859-1".to_string()), false)
+        );
+        assert_eq!(
+            charset(&[("LANG", "C"), ("LC_ALL", "en_US.utf8")], None),
+            (Some("utf8".to_string()), true)
+        );
+        assert_eq!(
+            charset(&[("CHARSET", "CP437"), ("LANG", "en_US.UTF-8")], None),
+            (Some("CP437".to_string()), false)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let caps = TerminalCapabilities {
+            width: Some(80),
+            height: Some(24),
+            terminal_type: Some("XTERM-256COLOR".to_string()),
+            supports_ansi: true,
+            supports_color: true,
+            color_depth: ColorDepth::Extended256,
+            charset: Some("UTF-8".to_string()),
+            utf8: true,
+            ..TerminalCapabilities::default()
+        };
+        let json = serde_json::to_string(&caps).unwrap();
+        assert!(json.contains(r#""color_depth":"Extended256""#));
+        let back: TerminalCapabilities = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, caps);
+    }
+}
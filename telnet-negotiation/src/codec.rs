@@ -0,0 +1,361 @@
+//! # Tokio Codec
+//!
+//! [`TelnetCodec`] frames a telnet byte stream for `tokio_util::codec`, so
+//! servers built on `Framed` transports can speak telnet without the
+//! blocking [`TelnetStream`](crate::TelnetStream) wrapper. Decoding splits
+//! what arrives into [`TelnetFrame`]s with IAC IAC unescaped; encoding
+//! escapes and frames what goes out.
+//!
+//! The codec only frames. Nothing answers DO or WILL for you: the caller
+//! decides, for instance by handing negotiation frames to an
+//! `OptionNegotiator` (`negotiation` feature). Data passes through byte for
+//! byte, so NVT line endings (CR LF, CR NUL) are the application's business
+//! too.
+//!
+//! ```rust
+//! use bytes::BytesMut;
+//! use telnet_negotiation::codec::{TelnetCodec, TelnetFrame};
+//! use telnet_negotiation::{TelnetCommand, TelnetOption};
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = TelnetCodec::new();
+//!
+//! // "hi", then IAC DO ECHO
+//! let mut input = BytesMut::from(&b"hi\xff\xfd\x01"[..]);
+//! assert_eq!(
+//!     codec.decode(&mut input).unwrap(),
+//!     Some(TelnetFrame::Data("hi".into()))
+//! );
+//! assert_eq!(
+//!     codec.decode(&mut input).unwrap(),
+//!     Some(TelnetFrame::Negotiation {
+//!         command: TelnetCommand::DO,
+//!         option: TelnetOption::ECHO,
+//!     })
+//! );
+//! assert_eq!(codec.decode(&mut input).unwrap(), None);
+//!
+//! let mut output = BytesMut::new();
+//! let reply = TelnetFrame::Negotiation {
+//!     command: TelnetCommand::WILL,
+//!     option: TelnetOption::ECHO,
+//! };
+//! codec.encode(reply, &mut output).unwrap();
+//! assert_eq!(&output[..], b"\xff\xfb\x01");
+//! ```
+
+use crate::protocol::{IAC, TelnetCommand, TelnetOption};
+
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Longest sub-negotiation payload a codec accepts by default
+pub const DEFAULT_MAX_SUBNEGOTIATION: usize = 64 * 1024;
+
+/// One piece of a telnet byte stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelnetFrame {
+    /// Application data, with IAC IAC as a single 255
+    Data(Bytes),
+
+    /// A command without an option, such as IAC GA or IAC AYT
+    Command(TelnetCommand),
+
+    /// Option negotiation: IAC WILL/WONT/DO/DONT <option>
+    Negotiation {
+        command: TelnetCommand,
+        option: TelnetOption,
+    },
+
+    /// IAC SB <option> <data> IAC SE, with IAC IAC in the data as a single 255
+    Subnegotiation { option: TelnetOption, data: Bytes },
+}
+
+/// Decoder and encoder of [`TelnetFrame`]s
+#[derive(Debug, Clone)]
+pub struct TelnetCodec {
+    max_subnegotiation: usize,
+}
+
+impl Default for TelnetCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelnetCodec {
+    /// A codec accepting sub-negotiations up to [`DEFAULT_MAX_SUBNEGOTIATION`]
+    pub fn new() -> Self {
+        Self {
+            max_subnegotiation: DEFAULT_MAX_SUBNEGOTIATION,
+        }
+    }
+
+    /// A codec accepting sub-negotiations up to `max` bytes
+    ///
+    /// A peer that sends a longer one, finished or not, gets an
+    /// `InvalidData` error instead of growing the read buffer without bound.
+    pub fn with_max_subnegotiation(max: usize) -> Self {
+        Self {
+            max_subnegotiation: max,
+        }
+    }
+
+    /// Longest sub-negotiation payload this codec accepts
+    pub fn max_subnegotiation(&self) -> usize {
+        self.max_subnegotiation
+    }
+
+    /// Decode the IAC SB at the start of `src`
+    fn decode_subnegotiation(&self, src: &mut BytesMut) -> io::Result<Option<TelnetFrame>> {
+        let Some(&option) = src.get(2) else {
+            return Ok(None);
+        };
+
+        let mut data = Vec::new();
+        let mut pos = 3;
+        loop {
+            match (src.get(pos), src.get(pos + 1)) {
+                (Some(&IAC), Some(&IAC)) => {
+                    data.push(IAC);
+                    pos += 2;
+                }
+                (Some(&IAC), Some(&next)) => {
+                    // IAC SE ends it; any other command means the peer never
+                    // closed it, so end it here and decode the command next
+                    if next == TelnetCommand::SE.to_byte() {
+                        pos += 2;
+                    }
+                    break;
+                }
+                (Some(&IAC), None) | (None, _) => return Ok(None),
+                (Some(&byte), _) => {
+                    data.push(byte);
+                    pos += 1;
+                }
+            }
+            if data.len() > self.max_subnegotiation {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "sub-negotiation longer than {} bytes",
+                        self.max_subnegotiation
+                    ),
+                ));
+            }
+        }
+
+        src.advance(pos);
+        Ok(Some(TelnetFrame::Subnegotiation {
+            option: TelnetOption::new(option),
+            data: data.into(),
+        }))
+    }
+}
+
+impl Decoder for TelnetCodec {
+    type Item = TelnetFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<TelnetFrame>> {
+        loop {
+            // Data up to the next command
+            let mut data = Vec::new();
+            let mut pos = 0;
+            while pos < src.len() {
+                if src[pos] != IAC {
+                    data.push(src[pos]);
+                    pos += 1;
+                } else if src.get(pos + 1) == Some(&IAC) {
+                    data.push(IAC);
+                    pos += 2;
+                } else {
+                    break;
+                }
+            }
+            if pos > 0 {
+                src.advance(pos);
+                return Ok(Some(TelnetFrame::Data(data.into())));
+            }
+
+            // At an IAC, or out of input
+            let Some(&byte) = src.get(1) else {
+                return Ok(None);
+            };
+            match TelnetCommand::from_byte(byte) {
+                Some(TelnetCommand::SB) => return self.decode_subnegotiation(src),
+                Some(command) if command.is_negotiation_command() => {
+                    let Some(&option) = src.get(2) else {
+                        return Ok(None);
+                    };
+                    src.advance(3);
+                    return Ok(Some(TelnetFrame::Negotiation {
+                        command,
+                        option: TelnetOption::new(option),
+                    }));
+                }
+                Some(command) => {
+                    src.advance(2);
+                    return Ok(Some(TelnetFrame::Command(command)));
+                }
+                // Not a command this crate knows; drop it like the parser does
+                None => src.advance(2),
+            }
+        }
+    }
+}
+
+impl Encoder<TelnetFrame> for TelnetCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: TelnetFrame, dst: &mut BytesMut) -> io::Result<()> {
+        match frame {
+            TelnetFrame::Data(data) => put_escaped(dst, &data),
+            TelnetFrame::Command(command) => dst.extend_from_slice(&[IAC, command.to_byte()]),
+            TelnetFrame::Negotiation { command, option } => {
+                dst.extend_from_slice(&[IAC, command.to_byte(), option.to_byte()])
+            }
+            TelnetFrame::Subnegotiation { option, data } => {
+                dst.reserve(data.len() + 5);
+                dst.extend_from_slice(&[IAC, TelnetCommand::SB.to_byte(), option.to_byte()]);
+                put_escaped(dst, &data);
+                dst.extend_from_slice(&[IAC, TelnetCommand::SE.to_byte()]);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Append `data` with every IAC doubled
+fn put_escaped(dst: &mut BytesMut, data: &[u8]) {
+    dst.reserve(data.len());
+    for chunk in data.split_inclusive(|&byte| byte == IAC) {
+        dst.extend_from_slice(chunk);
+        if chunk.last() == Some(&IAC) {
+            dst.extend_from_slice(&[IAC]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut TelnetCodec, src: &mut BytesMut) -> Vec<TelnetFrame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = codec.decode(src).unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn sample_frames() -> Vec<TelnetFrame> {
+        vec![
+            TelnetFrame::Data(Bytes::from_static(b"caf\xff!")),
+            TelnetFrame::Negotiation {
+                command: TelnetCommand::WILL,
+                option: TelnetOption::TERMINAL_TYPE,
+            },
+            TelnetFrame::Subnegotiation {
+                option: TelnetOption::NAWS,
+                data: Bytes::from_static(&[0, 80, 0, 255]),
+            },
+            TelnetFrame::Command(TelnetCommand::GA),
+            TelnetFrame::Data(Bytes::from_static(b"bye")),
+        ]
+    }
+
+    #[test]
+    fn test_encode_then_decode_round_trips() {
+        let mut codec = TelnetCodec::new();
+        let mut wire = BytesMut::new();
+        for frame in sample_frames() {
+            codec.encode(frame, &mut wire).unwrap();
+        }
+        assert_eq!(
+            &wire[..],
+            b"caf\xff\xff!\xff\xfb\x18\xff\xfa\x1f\x00\x50\x00\xff\xff\xff\xf0\xff\xf9bye"
+        );
+
+        assert_eq!(decode_all(&mut codec, &mut wire), sample_frames());
+        assert!(wire.is_empty());
+    }
+
+    #[test]
+    fn test_frames_split_across_reads() {
+        let mut codec = TelnetCodec::new();
+        let mut wire = BytesMut::new();
+        for frame in sample_frames() {
+            codec.encode(frame, &mut wire).unwrap();
+        }
+
+        // One byte at a time: data comes out in pieces, nothing else does
+        let mut src = BytesMut::new();
+        let mut frames = Vec::new();
+        for &byte in wire.iter() {
+            src.extend_from_slice(&[byte]);
+            frames.extend(decode_all(&mut codec, &mut src));
+        }
+        assert!(src.is_empty());
+
+        let data: Vec<u8> = frames
+            .iter()
+            .filter_map(|frame| match frame {
+                TelnetFrame::Data(data) => Some(data.to_vec()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(data, b"caf\xff!bye");
+
+        let others: Vec<&TelnetFrame> = frames
+            .iter()
+            .filter(|frame| !matches!(frame, TelnetFrame::Data(_)))
+            .collect();
+        assert_eq!(others, sample_frames()[1..4].iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_unterminated_subnegotiation_ends_at_next_command() {
+        let mut codec = TelnetCodec::new();
+        let mut src = BytesMut::from(&b"\xff\xfa\x18\x00xterm\xff\xf9ok"[..]);
+        assert_eq!(
+            decode_all(&mut codec, &mut src),
+            vec![
+                TelnetFrame::Subnegotiation {
+                    option: TelnetOption::TERMINAL_TYPE,
+                    data: Bytes::from_static(b"\x00xterm"),
+                },
+                TelnetFrame::Command(TelnetCommand::GA),
+                TelnetFrame::Data(Bytes::from_static(b"ok")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_commands_are_dropped() {
+        let mut codec = TelnetCodec::new();
+        let mut src = BytesMut::from(&b"a\xff\x10b"[..]);
+        assert_eq!(
+            decode_all(&mut codec, &mut src),
+            vec![
+                TelnetFrame::Data(Bytes::from_static(b"a")),
+                TelnetFrame::Data(Bytes::from_static(b"b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oversized_subnegotiation_is_an_error() {
+        let mut codec = TelnetCodec::with_max_subnegotiation(4);
+        assert_eq!(codec.max_subnegotiation(), 4);
+
+        let mut src = BytesMut::from(&b"\xff\xfa\x18\x00abc"[..]);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        src.extend_from_slice(b"d");
+        let error = codec.decode(&mut src).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,472 @@
+//! # RFC 1143 Conformance
+//!
+//! Two sets of data-driven checks for option negotiation, shipped with the
+//! crate beside the [wire vectors](crate::vectors):
+//!
+//! - `vectors/rfc1143.txt` is the Q method's state table, every
+//!   transition for both sides of an option with the queue on and off.
+//!   [`check_transition`] runs one against a fresh [`OptionNegotiator`].
+//! - `vectors/handshakes.txt` holds the opening negotiation of common
+//!   clients (PuTTY, SyncTERM, Mudlet, NetRunner) and what a
+//!   [`TelnetStream`](crate::TelnetStream) server answers.
+//!   [`replay`] plays one back against a server on a mock socket.
+//!
+//! Each file's header describes its format.
+//!
+//! ```rust
+//! use telnet_negotiation::conformance;
+//!
+//! for transition in conformance::transitions() {
+//!     conformance::check_transition(&transition).unwrap();
+//! }
+//! #[cfg(feature = "stream")]
+//! for handshake in conformance::handshakes() {
+//!     conformance::replay(&handshake).unwrap();
+//! }
+//! ```
+
+use crate::negotiation::{
+    NegotiationResult, NegotiatorSnapshot, OptionNegotiator, OptionState, QueueState, Side,
+};
+use crate::protocol::{TelnetCommand, TelnetOption, TelnetSequence};
+use crate::vectors::{VectorError, parse_bytes, parse_option, parse_sequence};
+
+/// The RFC 1143 state table, in the format [`parse_transitions`] reads
+pub const RFC1143_TABLE: &str = include_str!("../vectors/rfc1143.txt");
+
+/// Client handshakes, in the format [`parse_handshakes`] reads
+pub const HANDSHAKES: &str = include_str!("../vectors/handshakes.txt");
+
+/// What happens to an option in a [`Transition`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The peer sent WILL, WONT, DO or DONT
+    Receive(TelnetCommand),
+    /// We ask to enable the option
+    Enable,
+    /// We ask to disable the option
+    Disable,
+}
+
+/// One row of the RFC 1143 state table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    /// 1-based line in the table, to name the row in failures
+    pub line: usize,
+    pub side: Side,
+    /// Whether the RFC 1143 queue system is on
+    pub queue: bool,
+    pub state: OptionState,
+    pub event: Event,
+    /// Whether we agree to enable; `None` when the answer mustn't matter
+    pub accept: Option<bool>,
+    pub next: OptionState,
+    /// The verb sent in answer
+    pub send: Option<TelnetCommand>,
+    /// Whether RFC 1143 calls this an error
+    pub error: bool,
+}
+
+/// A client's opening negotiation and what our server should answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    pub name: String,
+    /// What the server offers before the client says anything
+    pub offers: Vec<TelnetSequence>,
+    /// Everything the client sends
+    pub client: Vec<u8>,
+    /// Every sequence the server sends, offers included, in order
+    pub server: Vec<TelnetSequence>,
+    /// Options on at the end on the server's side, ascending
+    pub local: Vec<TelnetOption>,
+    /// Options on at the end on the client's side, ascending
+    pub remote: Vec<TelnetOption>,
+}
+
+/// The state table shipped with the crate
+pub fn transitions() -> Vec<Transition> {
+    parse_transitions(RFC1143_TABLE).expect("shipped state table is valid")
+}
+
+/// The handshakes shipped with the crate
+pub fn handshakes() -> Vec<Handshake> {
+    parse_handshakes(HANDSHAKES).expect("shipped handshakes are valid")
+}
+
+/// Read a state table in the `vectors/rfc1143.txt` format
+pub fn parse_transitions(text: &str) -> Result<Vec<Transition>, VectorError> {
+    let mut transitions = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| VectorError {
+            line: number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [side, queue, state, event, accept, next, send, flagged] = columns[..] else {
+            return Err(error(format!("expected 8 columns, got {}", columns.len())));
+        };
+        let side = match side {
+            "local" => Side::Local,
+            "remote" => Side::Remote,
+            _ => return Err(error(format!("unknown side '{}'", side))),
+        };
+        let queue = match queue {
+            "on" => true,
+            "off" => false,
+            _ => return Err(error(format!("queue is on or off, got '{}'", queue))),
+        };
+        let event = match event {
+            "enable" => Event::Enable,
+            "disable" => Event::Disable,
+            verb => Event::Receive(parse_verb(verb).map_err(error)?),
+        };
+        let accept = match accept {
+            "yes" => Some(true),
+            "no" => Some(false),
+            "-" => None,
+            _ => return Err(error(format!("accept is yes, no or -, got '{}'", accept))),
+        };
+        let send = match send {
+            "-" => None,
+            verb => Some(parse_verb(verb).map_err(error)?),
+        };
+        let flagged = match flagged {
+            "error" => true,
+            "-" => false,
+            _ => return Err(error(format!("error is error or -, got '{}'", flagged))),
+        };
+
+        transitions.push(Transition {
+            line: number,
+            side,
+            queue,
+            state: parse_state(state).map_err(error)?,
+            event,
+            accept,
+            next: parse_state(next).map_err(error)?,
+            send,
+            error: flagged,
+        });
+    }
+    Ok(transitions)
+}
+
+/// Read handshakes in the `vectors/handshakes.txt` format
+pub fn parse_handshakes(text: &str) -> Result<Vec<Handshake>, VectorError> {
+    let mut handshakes: Vec<Handshake> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| VectorError {
+            line: number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("handshake ") {
+            handshakes.push(Handshake {
+                name: name.trim().to_string(),
+                offers: Vec::new(),
+                client: Vec::new(),
+                server: Vec::new(),
+                local: Vec::new(),
+                remote: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!("expected key = value, got '{}'", line)));
+        };
+        let Some(handshake) = handshakes.last_mut() else {
+            return Err(error("key outside a handshake".to_string()));
+        };
+        let value = value.trim();
+        match key.trim() {
+            "offer" => handshake.offers.push(parse_sequence(value).map_err(error)?),
+            "client" => handshake.client.extend(parse_bytes(value).map_err(error)?),
+            "server" => handshake.server.push(parse_sequence(value).map_err(error)?),
+            "local" => handshake.local = parse_options(value).map_err(error)?,
+            "remote" => handshake.remote = parse_options(value).map_err(error)?,
+            key => return Err(error(format!("unexpected key '{}'", key))),
+        }
+    }
+    Ok(handshakes)
+}
+
+/// Run `transition` against a fresh [`OptionNegotiator`]
+///
+/// Rows whose `accept` mustn't matter are run once agreeing and once
+/// refusing. Returns what went wrong, naming the row's line.
+pub fn check_transition(transition: &Transition) -> Result<(), String> {
+    let accepts = match transition.accept {
+        Some(accept) => vec![accept],
+        None => vec![true, false],
+    };
+    for accept in accepts {
+        let mut negotiator = OptionNegotiator::new();
+        let option = TelnetOption::ECHO;
+        let start = vec![(option.to_byte(), transition.state)];
+        let (local, remote) = match transition.side {
+            Side::Local => (start, Vec::new()),
+            Side::Remote => (Vec::new(), start),
+        };
+        negotiator.restore(&NegotiatorSnapshot {
+            local,
+            remote,
+            queue_enabled: transition.queue,
+        });
+        negotiator.set_accept_policy(move |_, _| accept);
+
+        let result =
+            apply(&mut negotiator, transition.side, transition.event, option).ok_or_else(|| {
+                format!(
+                    "line {}: {:?} isn't received on the {:?} side",
+                    transition.line, transition.event, transition.side
+                )
+            })?;
+        let expected = transition
+            .send
+            .map(|command| TelnetSequence::Negotiation { command, option });
+        let state = negotiator.get_state(transition.side, option);
+        if state != transition.next
+            || result.new_state != state
+            || result.response != expected
+            || result.error.is_some() != transition.error
+        {
+            return Err(format!(
+                "line {}: {} {} with accept {}: expected {} sending {:?}{}, \
+                 got {} (reported {}) sending {:?}{}",
+                transition.line,
+                transition.state,
+                event_name(transition.event),
+                accept,
+                transition.next,
+                transition.send,
+                if transition.error {
+                    " with an error"
+                } else {
+                    ""
+                },
+                state,
+                result.new_state,
+                result.response,
+                result
+                    .error
+                    .map(|error| format!(" and error '{}'", error))
+                    .unwrap_or_default(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Play `handshake` back against a server on a
+/// [`MockStream`](crate::testing::MockStream)
+///
+/// The server makes the offers, then reads everything the client sent.
+/// Returns what differed from the expected answer and final options.
+#[cfg(feature = "stream")]
+pub fn replay(handshake: &Handshake) -> Result<(), String> {
+    use crate::stream::TelnetStream;
+    use crate::testing::{MockStream, SilentHandler};
+    use std::io::Read;
+
+    let peer = MockStream::with_input(&handshake.client);
+    let mut server = TelnetStream::new(peer.clone());
+    let failed = |e: std::io::Error| format!("{}: {}", handshake.name, e);
+    for offer in &handshake.offers {
+        match offer {
+            TelnetSequence::Negotiation {
+                command: TelnetCommand::WILL,
+                option,
+            } => {
+                // A server only offers what it can handle
+                if !server.can_request(*option) {
+                    server.register_option_handler(Box::new(SilentHandler(*option)));
+                }
+                server.enable_option(Side::Local, *option).map_err(failed)?
+            }
+            TelnetSequence::Negotiation {
+                command: TelnetCommand::DO,
+                option,
+            } => server
+                .enable_option(Side::Remote, *option)
+                .map_err(failed)?,
+            other => return Err(format!("{}: can't offer {:?}", handshake.name, other)),
+        };
+    }
+    let mut buf = [0; 256];
+    while server.read(&mut buf).map_err(failed)? > 0 {}
+
+    let sent: Vec<TelnetSequence> = peer
+        .written_sequences()
+        .into_iter()
+        .filter(|sequence| !matches!(sequence, TelnetSequence::EscapedData(_)))
+        .collect();
+    if sent != handshake.server {
+        return Err(format!(
+            "{}: expected the server to send\n{}\ngot\n{}",
+            handshake.name,
+            sequence_list(&handshake.server),
+            sequence_list(&sent)
+        ));
+    }
+    let enabled = |side| -> Vec<TelnetOption> {
+        (0..=u8::MAX)
+            .map(TelnetOption::new)
+            .filter(|&option| server.is_option_enabled(side, option))
+            .collect()
+    };
+    for (side, expected) in [
+        (Side::Local, &handshake.local),
+        (Side::Remote, &handshake.remote),
+    ] {
+        let actual = enabled(side);
+        if &actual != expected {
+            return Err(format!(
+                "{}: expected {:?} options {:?}, got {:?}",
+                handshake.name, side, expected, actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn apply(
+    negotiator: &mut OptionNegotiator,
+    side: Side,
+    event: Event,
+    option: TelnetOption,
+) -> Option<NegotiationResult> {
+    Some(match (side, event) {
+        (side, Event::Enable) => negotiator.request_enable(side, option),
+        (side, Event::Disable) => negotiator.request_disable(side, option),
+        (Side::Remote, Event::Receive(TelnetCommand::WILL)) => negotiator.handle_will(option),
+        (Side::Remote, Event::Receive(TelnetCommand::WONT)) => negotiator.handle_wont(option),
+        (Side::Local, Event::Receive(TelnetCommand::DO)) => negotiator.handle_do(option),
+        (Side::Local, Event::Receive(TelnetCommand::DONT)) => negotiator.handle_dont(option),
+        _ => return None,
+    })
+}
+
+fn event_name(event: Event) -> String {
+    match event {
+        Event::Receive(command) => format!("{:?}", command),
+        Event::Enable => "enable".to_string(),
+        Event::Disable => "disable".to_string(),
+    }
+}
+
+#[cfg(feature = "stream")]
+fn sequence_list(sequences: &[TelnetSequence]) -> String {
+    sequences
+        .iter()
+        .map(|sequence| format!("  {:?}", sequence))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `NO`, `YES`, `WANTNO`, `WANTYES OPPOSITE`, ...
+fn parse_state(value: &str) -> Result<OptionState, String> {
+    [QueueState::Empty, QueueState::Opposite]
+        .into_iter()
+        .flat_map(|queue| {
+            [
+                OptionState::No,
+                OptionState::Yes,
+                OptionState::WantNo { queue },
+                OptionState::WantYes { queue },
+            ]
+        })
+        .find(|state| state.to_string() == value)
+        .ok_or_else(|| format!("unknown state '{}'", value))
+}
+
+/// `WILL`, `WONT`, `DO` or `DONT`
+fn parse_verb(value: &str) -> Result<TelnetCommand, String> {
+    [
+        TelnetCommand::WILL,
+        TelnetCommand::WONT,
+        TelnetCommand::DO,
+        TelnetCommand::DONT,
+    ]
+    .into_iter()
+    .find(|command| format!("{:?}", command) == value)
+    .ok_or_else(|| format!("unknown verb '{}'", value))
+}
+
+/// Decimal option numbers separated by spaces, or `-` for none
+fn parse_options(value: &str) -> Result<Vec<TelnetOption>, String> {
+    if value == "-" {
+        return Ok(Vec::new());
+    }
+    value.split_whitespace().map(parse_option).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiator_follows_the_state_table() {
+        let transitions = transitions();
+        // Both sides: 25 rows with the queue on, 17 with it off
+        assert_eq!(transitions.len(), 84);
+        let failures: Vec<String> = transitions
+            .iter()
+            .filter_map(|transition| check_transition(transition).err())
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_check_transition_catches_a_wrong_row() {
+        let mut transition = transitions()
+            .into_iter()
+            .find(|t| t.event == Event::Receive(TelnetCommand::WONT) && t.state == OptionState::Yes)
+            .unwrap();
+        transition.send = None;
+        let failure = check_transition(&transition).unwrap_err();
+        assert!(failure.starts_with(&format!("line {}:", transition.line)));
+    }
+
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_client_handshakes() {
+        let handshakes = handshakes();
+        for name in ["putty", "syncterm", "mudlet", "netrunner"] {
+            assert!(handshakes.iter().any(|h| h.name == name), "{}", name);
+        }
+        for handshake in &handshakes {
+            if let Err(failure) = replay(handshake) {
+                panic!("{}", failure);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_bad_lines() {
+        let error = parse_transitions("remote | on | NO | WILL | yes | YES | DO\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert!(error.message.contains("8 columns"));
+
+        let error = parse_transitions("\nlocal | on | MAYBE | DO | - | YES | - | -\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("MAYBE"));
+
+        let error = parse_handshakes("handshake x\noffer = FROB 1\n").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = parse_handshakes("client = 00\n").unwrap_err();
+        assert!(error.message.contains("outside a handshake"));
+    }
+}
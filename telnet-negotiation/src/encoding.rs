@@ -0,0 +1,287 @@
+//! # Character set transcoding
+//!
+//! Telnet moves bytes; what they mean is up to the two ends. BBS art and
+//! door games are drawn in code page 437, the IBM PC character set, and
+//! some older clients speak ISO 8859-1 (Latin-1). Sent as is, CP437 box
+//! drawing turns to mojibake on a UTF-8 terminal, and UTF-8 text turns to
+//! pairs of accented letters on a CP437 one.
+//!
+//! [`Transcoder`] converts a byte stream between any two [`Encoding`]s,
+//! carrying a UTF-8 sequence split across two chunks over to the next.
+//! A `TelnetStream` can run one each way on its data; see
+//! [`TelnetStream::set_transcoding`](crate::TelnetStream::set_transcoding).
+//!
+//! ```rust
+//! use telnet_negotiation::encoding::{cp437_to_utf8, utf8_to_cp437};
+//!
+//! assert_eq!(cp437_to_utf8(b"\xc9\xcd\xbb"), "╔═╗");
+//! assert_eq!(utf8_to_cp437("╚═╝ ok ✓"), b"\xc8\xcd\xbc ok ?");
+//! ```
+//!
+//! ASCII, including the control characters that carry newlines and ANSI
+//! escapes, is the same in all three. Characters a single-byte set can't
+//! show become `?`, and malformed UTF-8 becomes U+FFFD.
+
+/// A character set on one end of the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// UTF-8
+    #[default]
+    Utf8,
+    /// Code page 437, the IBM PC character set
+    Cp437,
+    /// ISO 8859-1
+    Latin1,
+}
+
+impl Encoding {
+    /// The encoding a client means by `name`, as it might appear in a
+    /// CHARSET variable or a locale
+    ///
+    /// Case, `-` and `_` are ignored, so `utf8`, `UTF-8`, `IBM437`,
+    /// `CP437`, `ISO-8859-1` and `latin1` are all recognised.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name: String = name
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        match name.as_str() {
+            "UTF8" => Some(Encoding::Utf8),
+            "CP437" | "IBM437" | "437" | "PC437" => Some(Encoding::Cp437),
+            "ISO88591" | "LATIN1" | "L1" | "CP819" | "IBM819" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// The usual name of the encoding
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Cp437 => "CP437",
+            Encoding::Latin1 => "ISO-8859-1",
+        }
+    }
+
+    /// Append `c` in this encoding, as `?` if it has no such character
+    fn encode(self, c: char, output: &mut Vec<u8>) {
+        match self {
+            Encoding::Utf8 => {
+                output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            Encoding::Cp437 => output.push(cp437_byte(c).unwrap_or(b'?')),
+            Encoding::Latin1 => output.push(u8::try_from(c).unwrap_or(b'?')),
+        }
+    }
+}
+
+/// Converts a byte stream from one [`Encoding`] to another
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+    from: Encoding,
+    to: Encoding,
+    /// The start of a UTF-8 sequence whose end hasn't arrived yet
+    partial: Vec<u8>,
+}
+
+impl Transcoder {
+    /// Convert what is written in `from` to `to`
+    pub fn new(from: Encoding, to: Encoding) -> Self {
+        Self {
+            from,
+            to,
+            partial: Vec::new(),
+        }
+    }
+
+    /// The encoding input is taken to be in
+    pub fn from(&self) -> Encoding {
+        self.from
+    }
+
+    /// The encoding output is written in
+    pub fn to(&self) -> Encoding {
+        self.to
+    }
+
+    /// Convert `input`, appending the result to `output`
+    ///
+    /// An incomplete UTF-8 sequence at the end of `input` is held back
+    /// until the next call completes it.
+    pub fn push(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        let to = self.to;
+        match self.from {
+            Encoding::Cp437 => input
+                .iter()
+                .for_each(|&byte| to.encode(cp437_char(byte), output)),
+            Encoding::Latin1 => input
+                .iter()
+                .for_each(|&byte| to.encode(char::from(byte), output)),
+            Encoding::Utf8 if self.partial.is_empty() => {
+                self.decode_utf8(input, output);
+            }
+            Encoding::Utf8 => {
+                let mut joined = std::mem::take(&mut self.partial);
+                joined.extend_from_slice(input);
+                self.decode_utf8(&joined, output);
+            }
+        }
+    }
+
+    /// Write out a sequence still held back, as U+FFFD in `to`
+    pub fn finish(&mut self, output: &mut Vec<u8>) {
+        if !std::mem::take(&mut self.partial).is_empty() {
+            self.to.encode(char::REPLACEMENT_CHARACTER, output);
+        }
+    }
+
+    fn decode_utf8(&mut self, mut input: &[u8], output: &mut Vec<u8>) {
+        loop {
+            match std::str::from_utf8(input) {
+                Ok(text) => {
+                    self.encode_str(text, output);
+                    return;
+                }
+                Err(e) => {
+                    let (valid, rest) = input.split_at(e.valid_up_to());
+                    // Checked by from_utf8 already
+                    self.encode_str(std::str::from_utf8(valid).unwrap_or_default(), output);
+                    match e.error_len() {
+                        Some(len) => {
+                            self.to.encode(char::REPLACEMENT_CHARACTER, output);
+                            input = &rest[len..];
+                        }
+                        None => {
+                            self.partial.extend_from_slice(rest);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode_str(&self, text: &str, output: &mut Vec<u8>) {
+        if self.to == Encoding::Utf8 || text.is_ascii() {
+            output.extend_from_slice(text.as_bytes());
+        } else {
+            text.chars().for_each(|c| self.to.encode(c, output));
+        }
+    }
+}
+
+/// Decode CP437 bytes
+pub fn cp437_to_utf8(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| cp437_char(byte)).collect()
+}
+
+/// Encode `text` in CP437, with `?` for characters it lacks
+pub fn utf8_to_cp437(text: &str) -> Vec<u8> {
+    let mut output = Vec::with_capacity(text.len());
+    text.chars()
+        .for_each(|c| Encoding::Cp437.encode(c, &mut output));
+    output
+}
+
+/// Decode Latin-1 bytes
+pub fn latin1_to_utf8(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// Encode `text` in Latin-1, with `?` for characters it lacks
+pub fn utf8_to_latin1(text: &str) -> Vec<u8> {
+    let mut output = Vec::with_capacity(text.len());
+    text.chars()
+        .for_each(|c| Encoding::Latin1.encode(c, &mut output));
+    output
+}
+
+/// Code page 437 from 0x80 up; below that it is ASCII
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+fn cp437_char(byte: u8) -> char {
+    match byte {
+        0..=0x7f => char::from(byte),
+        _ => CP437_HIGH[usize::from(byte - 0x80)],
+    }
+}
+
+fn cp437_byte(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    let index = CP437_HIGH.iter().position(|&high| high == c)?;
+    Some(0x80 + index as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cp437_round_trip() {
+        let every: Vec<u8> = (0..=255).collect();
+        let text = cp437_to_utf8(&every);
+        assert_eq!(text.chars().count(), 256);
+        assert_eq!(utf8_to_cp437(&text), every);
+        assert_eq!(
+            utf8_to_cp437("\u{1b}[1m░▒▓\r\n"),
+            b"\x1b[1m\xb0\xb1\xb2\r\n"
+        );
+    }
+
+    #[test]
+    fn test_latin1_round_trip() {
+        let every: Vec<u8> = (0..=255).collect();
+        assert_eq!(utf8_to_latin1(&latin1_to_utf8(&every)), every);
+        assert_eq!(latin1_to_utf8(b"caf\xe9"), "café");
+        assert_eq!(utf8_to_latin1("na\u{ef}ve €5"), b"na\xefve ?5");
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Encoding::from_name("utf-8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("IBM437"), Some(Encoding::Cp437));
+        assert_eq!(Encoding::from_name("ISO_8859-1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("KOI8-R"), None);
+        assert_eq!(
+            Encoding::from_name(Encoding::Latin1.name()),
+            Some(Encoding::Latin1)
+        );
+    }
+
+    #[test]
+    fn test_split_utf8_waits_for_the_rest() {
+        let mut transcoder = Transcoder::new(Encoding::Utf8, Encoding::Cp437);
+        let mut output = Vec::new();
+        let text = "═╗".as_bytes();
+        transcoder.push(&text[..1], &mut output);
+        assert!(output.is_empty());
+        transcoder.push(&text[1..4], &mut output);
+        assert_eq!(output, b"\xcd");
+        transcoder.push(&text[4..], &mut output);
+        assert_eq!(output, b"\xcd\xbb");
+
+        // Bad bytes and a sequence cut off for good become replacements
+        transcoder.push(b"\xffa\xe2\x95", &mut output);
+        transcoder.finish(&mut output);
+        assert_eq!(output, b"\xcd\xbb?a?");
+    }
+
+    #[test]
+    fn test_single_byte_sets_to_each_other() {
+        let mut transcoder = Transcoder::new(Encoding::Cp437, Encoding::Latin1);
+        let mut output = Vec::new();
+        transcoder.push(b"\x82\xb0", &mut output);
+        assert_eq!(output, b"\xe9?");
+    }
+}
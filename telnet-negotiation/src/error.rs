@@ -0,0 +1,223 @@
+//! # Telnet Errors
+//!
+//! A `TelnetStream` is a `Read` and `Write`, so its methods fail with
+//! `io::Error` like any socket. That alone can't tell a dropped connection
+//! from a peer abusing the protocol, so the errors the stream raises itself
+//! carry a [`TelnetError`] inside. [`TelnetError::from`] gets it back out
+//! of an `io::Error` (anything else becomes [`TelnetError::Io`]).
+//! `TelnetError` needs the `std` feature.
+//!
+//! The parser doesn't fail on malformed input: it keeps going, as RFC 854
+//! asks, and reports what it passed through as data as
+//! [`ProtocolViolation`]s alongside the data.
+
+use crate::protocol::{TelnetCommand, TelnetOption};
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Something the peer sent that a well-behaved telnet never would
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolViolation {
+    /// IAC followed by a byte that is not a telnet command; both bytes were
+    /// passed on as data
+    UnknownCommand(u8),
+    /// IAC followed by something other than SE or IAC inside a
+    /// sub-negotiation; the sub-negotiation was abandoned and both bytes
+    /// passed on as data
+    UnterminatedSubnegotiation { option: TelnetOption, byte: u8 },
+    /// A WILL or DO repeated past the loop guard after we refused it
+    RepeatedRefusal {
+        command: TelnetCommand,
+        option: TelnetOption,
+    },
+}
+
+impl fmt::Display for ProtocolViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolViolation::UnknownCommand(byte) => {
+                write!(f, "IAC followed by unknown command {}", byte)
+            }
+            ProtocolViolation::UnterminatedSubnegotiation { option, byte } => write!(
+                f,
+                "sub-negotiation for {:?} interrupted by IAC {}",
+                option, byte
+            ),
+            ProtocolViolation::RepeatedRefusal { command, option } => {
+                write!(f, "peer repeated refused {:?} {:?}", command, option)
+            }
+        }
+    }
+}
+
+impl core::error::Error for ProtocolViolation {}
+
+/// Why a telnet operation failed
+///
+/// Raised inside an `io::Error`, and taken back out with `from`:
+///
+/// ```rust
+/// use std::io;
+/// use telnet_negotiation::{TelnetError, TelnetOption};
+///
+/// let error: io::Error = TelnetError::SubnegotiationTooLarge {
+///     option: TelnetOption::GMCP,
+///     limit: 1024,
+/// }
+/// .into();
+/// assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+///
+/// match TelnetError::from(error) {
+///     TelnetError::SubnegotiationTooLarge { option, .. } => assert_eq!(option, TelnetOption::GMCP),
+///     other => panic!("unexpected {}", other),
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum TelnetError {
+    /// The peer broke the protocol badly enough to end the connection
+    ProtocolViolation(ProtocolViolation),
+    /// A sub-negotiation went over the parser's limit under
+    /// [`LimitPolicy::Error`](crate::parser::LimitPolicy::Error)
+    SubnegotiationTooLarge { option: TelnetOption, limit: usize },
+    /// The peer didn't finish negotiating `option` in time
+    NegotiationTimeout { option: TelnetOption },
+    /// The transport failed
+    Io(io::Error),
+}
+
+#[cfg(feature = "std")]
+impl TelnetError {
+    /// The `io::ErrorKind` this error is raised as
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            TelnetError::ProtocolViolation(_) | TelnetError::SubnegotiationTooLarge { .. } => {
+                io::ErrorKind::InvalidData
+            }
+            TelnetError::NegotiationTimeout { .. } => io::ErrorKind::TimedOut,
+            TelnetError::Io(error) => error.kind(),
+        }
+    }
+
+    /// The telnet error inside `error`, if the stream raised it
+    pub fn from_io(error: &io::Error) -> Option<&TelnetError> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for TelnetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TelnetError::ProtocolViolation(violation) => {
+                write!(f, "telnet protocol violation: {}", violation)
+            }
+            TelnetError::SubnegotiationTooLarge { option, limit } => write!(
+                f,
+                "telnet sub-negotiation for {:?} longer than {} bytes",
+                option, limit
+            ),
+            TelnetError::NegotiationTimeout { option } => {
+                write!(f, "telnet negotiation of {:?} timed out", option)
+            }
+            TelnetError::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TelnetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TelnetError::ProtocolViolation(violation) => Some(violation),
+            TelnetError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ProtocolViolation> for TelnetError {
+    fn from(violation: ProtocolViolation) -> Self {
+        TelnetError::ProtocolViolation(violation)
+    }
+}
+
+/// Unwraps a telnet error the stream raised, and wraps any other
+#[cfg(feature = "std")]
+impl From<io::Error> for TelnetError {
+    fn from(error: io::Error) -> Self {
+        error.downcast().unwrap_or_else(TelnetError::Io)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<TelnetError> for io::Error {
+    fn from(error: TelnetError) -> Self {
+        match error {
+            TelnetError::Io(error) => error,
+            error => io::Error::new(error.kind(), error),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_through_io_error() {
+        let violation = ProtocolViolation::RepeatedRefusal {
+            command: TelnetCommand::WILL,
+            option: TelnetOption::new(99),
+        };
+        let error: io::Error = TelnetError::from(violation.clone()).into();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            TelnetError::from_io(&error),
+            Some(TelnetError::ProtocolViolation(_))
+        ));
+        match TelnetError::from(error) {
+            TelnetError::ProtocolViolation(v) => assert_eq!(v, violation),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let timeout: io::Error = TelnetError::NegotiationTimeout {
+            option: TelnetOption::START_TLS,
+        }
+        .into();
+        assert_eq!(timeout.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_plain_io_errors_pass_through() {
+        let error = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert!(TelnetError::from_io(&error).is_none());
+        let telnet = TelnetError::from(error);
+        assert!(matches!(telnet, TelnetError::Io(_)));
+        assert_eq!(telnet.kind(), io::ErrorKind::ConnectionReset);
+
+        // And come back out unchanged
+        let error: io::Error = telnet.into();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+        assert!(error.get_ref().is_some_and(|e| e.to_string() == "reset"));
+    }
+
+    #[test]
+    fn test_display() {
+        let error = TelnetError::SubnegotiationTooLarge {
+            option: TelnetOption::GMCP,
+            limit: 16,
+        };
+        assert_eq!(
+            error.to_string(),
+            "telnet sub-negotiation for GMCP longer than 16 bytes"
+        );
+        assert_eq!(
+            ProtocolViolation::UnknownCommand(99).to_string(),
+            "IAC followed by unknown command 99"
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! # Line and Prompt Events
+//!
+//! MUD clients match triggers against whole lines, and against prompts,
+//! which servers send without a trailing newline and mark with IAC EOR or
+//! IAC GA instead. This module turns the clean data stream (telnet
+//! commands already removed) plus those marks into a sequence of
+//! [`TextEvent`]s:
+//!
+//! ```text
+//! "hp 10\r\n> " IAC GA "look\r\n"   =>   Line("hp 10"), Prompt("> "), Line("look")
+//! ```
+//!
+//! [`LineSegmenter`] does the splitting and works with any source of data
+//! and mark offsets. With the `stream` feature, [`TelnetStream::text_events`]
+//! drives one straight from a connection.
+//!
+//! Lines end at LF, and a CR just before it is dropped. Text is kept as
+//! bytes since plenty of MUDs still send Latin-1; [`TextEvent::text`] gives
+//! a lossy UTF-8 view for the common case.
+//!
+//! [`TelnetStream::text_events`]: crate::TelnetStream::text_events
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+#[cfg(feature = "stream")]
+use crate::stream::TelnetStream;
+#[cfg(feature = "stream")]
+use crate::transport::Transport;
+#[cfg(feature = "stream")]
+use std::io::{self, Read};
+#[cfg(feature = "stream")]
+use std::net::TcpStream;
+
+/// A piece of the data stream, split at newlines and prompt marks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextEvent {
+    /// A line of text, without its line ending
+    Line(Vec<u8>),
+    /// Text the peer marked as a prompt with IAC EOR or IAC GA
+    Prompt(Vec<u8>),
+}
+
+impl TextEvent {
+    /// The raw bytes of the line or prompt
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            TextEvent::Line(bytes) | TextEvent::Prompt(bytes) => bytes,
+        }
+    }
+
+    /// The text as UTF-8, with invalid sequences replaced
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.bytes())
+    }
+
+    pub fn is_prompt(&self) -> bool {
+        matches!(self, TextEvent::Prompt(_))
+    }
+}
+
+/// Splits clean data into [`TextEvent`]s
+///
+/// Feed it data with [`push`](Self::push) and prompt boundaries with
+/// [`mark`](Self::mark), then drain events with
+/// [`next_event`](Self::next_event). Marks are given as data offsets
+/// counted from the start of the stream (as in
+/// [`PromptMark::offset`](crate::PromptMark)), so they can be registered
+/// before the data they fall in arrives, or just after it.
+///
+/// ```
+/// use telnet_negotiation::{LineSegmenter, TextEvent};
+///
+/// let mut segmenter = LineSegmenter::new();
+/// segmenter.mark(9); // IAC GA after "hp 10\r\n> "
+/// segmenter.push(b"hp 10\r\n> look\r\n");
+///
+/// assert_eq!(segmenter.next_event(), Some(TextEvent::Line(b"hp 10".to_vec())));
+/// assert_eq!(segmenter.next_event(), Some(TextEvent::Prompt(b"> ".to_vec())));
+/// assert_eq!(segmenter.next_event(), Some(TextEvent::Line(b"look".to_vec())));
+/// assert_eq!(segmenter.next_event(), None);
+/// ```
+#[derive(Debug, Default)]
+pub struct LineSegmenter {
+    /// Text after the last line ending or prompt
+    partial: Vec<u8>,
+    /// Data bytes pushed so far
+    consumed: u64,
+    /// Mark offsets not reached yet, in order
+    marks: VecDeque<u64>,
+    ready: VecDeque<TextEvent>,
+}
+
+impl LineSegmenter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a prompt boundary after `offset` data bytes
+    ///
+    /// A mark for data already pushed ends the current prompt right away.
+    pub fn mark(&mut self, offset: u64) {
+        if offset <= self.consumed {
+            self.end_prompt();
+            return;
+        }
+        // Keep marks ordered even if they come in out of order
+        let at = self.marks.partition_point(|&mark| mark <= offset);
+        self.marks.insert(at, offset);
+    }
+
+    /// Add the next chunk of data
+    pub fn push(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.reach_marks();
+            self.consumed += 1;
+            if byte == b'\n' {
+                if self.partial.last() == Some(&b'\r') {
+                    self.partial.pop();
+                }
+                self.ready
+                    .push_back(TextEvent::Line(std::mem::take(&mut self.partial)));
+            } else {
+                self.partial.push(byte);
+            }
+        }
+        self.reach_marks();
+    }
+
+    /// The next complete line or prompt, if any
+    pub fn next_event(&mut self) -> Option<TextEvent> {
+        self.ready.pop_front()
+    }
+
+    /// Text received since the last line or prompt ended
+    pub fn partial(&self) -> &[u8] {
+        &self.partial
+    }
+
+    /// End of stream: whatever is left over becomes a final line
+    pub fn finish(&mut self) -> Option<TextEvent> {
+        self.marks.clear();
+        if self.partial.is_empty() {
+            None
+        } else {
+            Some(TextEvent::Line(std::mem::take(&mut self.partial)))
+        }
+    }
+
+    fn reach_marks(&mut self) {
+        while self
+            .marks
+            .front()
+            .is_some_and(|&mark| mark <= self.consumed)
+        {
+            self.marks.pop_front();
+            self.end_prompt();
+        }
+    }
+
+    fn end_prompt(&mut self) {
+        // A mark straight after a newline has no prompt text to report
+        if !self.partial.is_empty() {
+            self.ready
+                .push_back(TextEvent::Prompt(std::mem::take(&mut self.partial)));
+        }
+    }
+}
+
+/// Blocking iterator over a stream's [`TextEvent`]s
+///
+/// Returned by [`TelnetStream::text_events`]. Ends when the peer closes
+/// the connection, after yielding any unterminated text as a last line.
+#[cfg(feature = "stream")]
+pub struct TextEvents<'a, S = TcpStream> {
+    stream: &'a mut TelnetStream<S>,
+    segmenter: LineSegmenter,
+    done: bool,
+}
+
+#[cfg(feature = "stream")]
+impl<'a, S: Transport> TextEvents<'a, S> {
+    pub(crate) fn new(stream: &'a mut TelnetStream<S>) -> Self {
+        Self {
+            stream,
+            segmenter: LineSegmenter::new(),
+            done: false,
+        }
+    }
+
+    /// The segmenter, e.g. to look at a partial line after a read timeout
+    pub fn segmenter(&self) -> &LineSegmenter {
+        &self.segmenter
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<S: Transport> Iterator for TextEvents<'_, S> {
+    type Item = io::Result<TextEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0u8; 1024];
+        loop {
+            if let Some(event) = self.segmenter.next_event() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+
+            let n = match self.stream.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) => return Some(Err(e)),
+            };
+            // Marks are recorded while parsing, so every mark inside the
+            // data just read is known by now
+            for mark in self.stream.take_prompt_marks() {
+                self.segmenter.mark(mark.offset);
+            }
+            if n == 0 {
+                self.done = true;
+                return self.segmenter.finish().map(Ok);
+            }
+            self.segmenter.push(&buf[..n]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(segmenter: &mut LineSegmenter) -> Vec<TextEvent> {
+        std::iter::from_fn(|| segmenter.next_event()).collect()
+    }
+
+    #[test]
+    fn test_lines_split_on_newline() {
+        let mut segmenter = LineSegmenter::new();
+        segmenter.push(b"one\r\ntwo\nthr");
+        assert_eq!(
+            drain(&mut segmenter),
+            vec![
+                TextEvent::Line(b"one".to_vec()),
+                TextEvent::Line(b"two".to_vec())
+            ]
+        );
+        assert_eq!(segmenter.partial(), b"thr");
+
+        segmenter.push(b"ee\r");
+        segmenter.push(b"\n");
+        assert_eq!(
+            drain(&mut segmenter),
+            vec![TextEvent::Line(b"three".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_marks_end_prompts() {
+        let mut segmenter = LineSegmenter::new();
+        segmenter.push(b"Name? ");
+        // The mark arrives after its data
+        segmenter.mark(6);
+        segmenter.mark(17); // "bob\n" and "hp 10> " still to come
+        segmenter.push(b"bob\n");
+        segmenter.push(b"hp 10> x");
+
+        let events = drain(&mut segmenter);
+        assert_eq!(
+            events,
+            vec![
+                TextEvent::Prompt(b"Name? ".to_vec()),
+                TextEvent::Line(b"bob".to_vec()),
+                TextEvent::Prompt(b"hp 10> ".to_vec()),
+            ]
+        );
+        assert!(events[0].is_prompt());
+        assert_eq!(events[1].text(), "bob");
+        assert_eq!(segmenter.finish(), Some(TextEvent::Line(b"x".to_vec())));
+        assert_eq!(segmenter.finish(), None);
+    }
+
+    #[test]
+    fn test_empty_prompts_are_skipped() {
+        let mut segmenter = LineSegmenter::new();
+        segmenter.mark(0);
+        segmenter.push(b"line\n");
+        segmenter.mark(5);
+        segmenter.mark(5);
+        assert_eq!(
+            drain(&mut segmenter),
+            vec![TextEvent::Line(b"line".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_non_utf8_text_is_kept() {
+        let mut segmenter = LineSegmenter::new();
+        segmenter.push(b"caf\xe9\n");
+        let event = segmenter.next_event().unwrap();
+        assert_eq!(event.bytes(), b"caf\xe9");
+        assert_eq!(event.text(), "caf\u{FFFD}");
+    }
+}
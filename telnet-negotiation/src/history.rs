@@ -0,0 +1,432 @@
+//! # Negotiation History
+//!
+//! Records the WILL/WONT/DO/DONT traffic of a connection so it can be
+//! inspected after the fact, e.g. to fingerprint a client or to diagnose a
+//! negotiation that never converged.
+//!
+//! ## Key Concepts
+//!
+//! ### Per-option Ring Buffers
+//! Each option keeps its own bounded ring of records. A chatty option (a
+//! client toggling ECHO on every password prompt) can only evict its own
+//! oldest entries, never the single DO TERMINAL-TYPE exchange that happened
+//! at connect time.
+//!
+//! ### Ordering
+//! Every record carries a connection-wide sequence number and the time since
+//! the history was created, so the per-option rings can be merged back into
+//! a single chronological view.
+//!
+//! ### Round-trip Statistics
+//! [`NegotiationStats`] pairs each option we asked for with the peer's
+//! first answer, giving per-option round-trip times. How quickly (and
+//! whether) a client answers the opening volley says a lot about the link
+//! and the client's age.
+
+use crate::negotiation::OptionState;
+use crate::protocol::{TelnetCommand, TelnetOption};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Default number of records kept per option
+pub const DEFAULT_HISTORY_PER_OPTION: usize = 16;
+
+/// Which way a negotiation command travelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Received from the peer
+    Received,
+    /// Sent by us
+    Sent,
+}
+
+/// A single negotiation command seen on the connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiationRecord {
+    /// Connection-wide sequence number (monotonic)
+    pub seq: u64,
+    /// Time since the history was created
+    pub elapsed: Duration,
+    /// Whether we sent or received the command
+    pub direction: Direction,
+    /// WILL, WONT, DO or DONT
+    pub command: TelnetCommand,
+    /// Option the command refers to
+    pub option: TelnetOption,
+    /// Local state of the option after the command was processed
+    pub local_state: OptionState,
+    /// Remote state of the option after the command was processed
+    pub remote_state: OptionState,
+}
+
+/// Bounded, per-option negotiation history for one connection
+#[derive(Debug, Clone)]
+pub struct NegotiationHistory {
+    started: Instant,
+    next_seq: u64,
+    capacity_per_option: usize,
+    records: HashMap<TelnetOption, VecDeque<NegotiationRecord>>,
+}
+
+impl Default for NegotiationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NegotiationHistory {
+    /// Create a history keeping `DEFAULT_HISTORY_PER_OPTION` records per option
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_HISTORY_PER_OPTION)
+    }
+
+    /// Create a history keeping at most `capacity_per_option` records per option
+    pub fn with_capacity(capacity_per_option: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            next_seq: 0,
+            capacity_per_option: capacity_per_option.max(1),
+            records: HashMap::new(),
+        }
+    }
+
+    /// Record a negotiation command and the option state it left behind
+    pub fn record(
+        &mut self,
+        direction: Direction,
+        command: TelnetCommand,
+        option: TelnetOption,
+        local_state: OptionState,
+        remote_state: OptionState,
+    ) {
+        let record = NegotiationRecord {
+            seq: self.next_seq,
+            elapsed: self.started.elapsed(),
+            direction,
+            command,
+            option,
+            local_state,
+            remote_state,
+        };
+        self.next_seq += 1;
+
+        let ring = self.records.entry(option).or_default();
+        if ring.len() == self.capacity_per_option {
+            ring.pop_front();
+        }
+        ring.push_back(record);
+    }
+
+    /// Records for a single option, oldest first
+    pub fn for_option(&self, option: TelnetOption) -> impl Iterator<Item = &NegotiationRecord> {
+        self.records.get(&option).into_iter().flatten()
+    }
+
+    /// All retained records across options, in the order they happened
+    pub fn chronological(&self) -> Vec<&NegotiationRecord> {
+        let mut all: Vec<&NegotiationRecord> = self.records.values().flatten().collect();
+        all.sort_by_key(|r| r.seq);
+        all
+    }
+
+    /// Options that have at least one record
+    pub fn options(&self) -> Vec<TelnetOption> {
+        let mut options: Vec<TelnetOption> = self.records.keys().copied().collect();
+        options.sort_by_key(|o| o.to_byte());
+        options
+    }
+
+    /// Total number of negotiation commands seen, including evicted ones
+    pub fn total_recorded(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Whether nothing has been recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.next_seq == 0
+    }
+
+    /// Number of records currently kept, across options
+    pub fn retained(&self) -> usize {
+        self.records.values().map(VecDeque::len).sum()
+    }
+
+    /// Approximate bytes the retained records take up
+    pub fn memory_used(&self) -> usize {
+        self.retained() * std::mem::size_of::<NegotiationRecord>()
+    }
+
+    /// Forget all records
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Round-trip statistics for the requests we made
+    pub fn stats(&self) -> NegotiationStats {
+        let records: Vec<NegotiationRecord> = self.chronological().into_iter().cloned().collect();
+        NegotiationStats::from_records(&records)
+    }
+}
+
+/// How the peer answered one of our option requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionTiming {
+    pub option: TelnetOption,
+    /// DO or WILL, as we sent it
+    pub requested: TelnetCommand,
+    /// The peer's first answer, if one arrived
+    pub answer: Option<TelnetCommand>,
+    /// Time from our request to that answer
+    pub round_trip: Option<Duration>,
+}
+
+impl OptionTiming {
+    /// Whether the peer agreed (WILL to our DO, DO to our WILL)
+    pub fn accepted(&self) -> bool {
+        matches!(
+            (self.requested, self.answer),
+            (TelnetCommand::DO, Some(TelnetCommand::WILL))
+                | (TelnetCommand::WILL, Some(TelnetCommand::DO))
+        )
+    }
+}
+
+/// Round-trip timing of the options we requested, one entry per option
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NegotiationStats {
+    /// In the order the requests were sent
+    pub timings: Vec<OptionTiming>,
+}
+
+impl NegotiationStats {
+    /// Pair the first DO or WILL we sent for each option with the peer's
+    /// first reply after it; `records` must be in chronological order
+    ///
+    /// Options the peer offered before we asked are not counted, since
+    /// there is no request to time them against.
+    pub fn from_records(records: &[NegotiationRecord]) -> Self {
+        let mut timings: Vec<OptionTiming> = Vec::new();
+        let mut sent_at: HashMap<TelnetOption, Duration> = HashMap::new();
+
+        for record in records {
+            match (record.direction, record.command) {
+                (Direction::Sent, TelnetCommand::DO | TelnetCommand::WILL)
+                    if !sent_at.contains_key(&record.option) =>
+                {
+                    sent_at.insert(record.option, record.elapsed);
+                    timings.push(OptionTiming {
+                        option: record.option,
+                        requested: record.command,
+                        answer: None,
+                        round_trip: None,
+                    });
+                }
+                (Direction::Received, command) => {
+                    let Some(timing) = timings
+                        .iter_mut()
+                        .find(|t| t.option == record.option && t.answer.is_none())
+                    else {
+                        continue;
+                    };
+                    let expected = match timing.requested {
+                        TelnetCommand::DO => [TelnetCommand::WILL, TelnetCommand::WONT],
+                        _ => [TelnetCommand::DO, TelnetCommand::DONT],
+                    };
+                    if expected.contains(&command) {
+                        timing.answer = Some(command);
+                        timing.round_trip =
+                            Some(record.elapsed.saturating_sub(sent_at[&record.option]));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self { timings }
+    }
+
+    /// Requests the peer answered, either way
+    pub fn answered(&self) -> usize {
+        self.timings.iter().filter(|t| t.answer.is_some()).count()
+    }
+
+    /// Requests the peer agreed to
+    pub fn accepted(&self) -> usize {
+        self.timings.iter().filter(|t| t.accepted()).count()
+    }
+
+    /// Requests still waiting for an answer
+    pub fn unanswered(&self) -> usize {
+        self.timings.len() - self.answered()
+    }
+
+    /// Middle round-trip time of the answered requests
+    pub fn median_round_trip(&self) -> Option<Duration> {
+        let mut times: Vec<Duration> = self.timings.iter().filter_map(|t| t.round_trip).collect();
+        times.sort();
+        times.get(times.len() / 2).copied()
+    }
+
+    /// Longest round-trip time of the answered requests
+    pub fn slowest_round_trip(&self) -> Option<Duration> {
+        self.timings.iter().filter_map(|t| t.round_trip).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(history: &mut NegotiationHistory, command: TelnetCommand, option: TelnetOption) {
+        history.record(
+            Direction::Received,
+            command,
+            option,
+            OptionState::No,
+            OptionState::Yes,
+        );
+    }
+
+    fn timed(
+        millis: u64,
+        direction: Direction,
+        command: TelnetCommand,
+        option: TelnetOption,
+    ) -> NegotiationRecord {
+        NegotiationRecord {
+            seq: millis,
+            elapsed: Duration::from_millis(millis),
+            direction,
+            command,
+            option,
+            local_state: OptionState::No,
+            remote_state: OptionState::No,
+        }
+    }
+
+    #[test]
+    fn test_stats_pair_requests_with_answers() {
+        let records = vec![
+            timed(
+                0,
+                Direction::Sent,
+                TelnetCommand::DO,
+                TelnetOption::TERMINAL_TYPE,
+            ),
+            timed(1, Direction::Sent, TelnetCommand::DO, TelnetOption::NAWS),
+            timed(2, Direction::Sent, TelnetCommand::WILL, TelnetOption::ECHO),
+            timed(
+                3,
+                Direction::Sent,
+                TelnetCommand::DO,
+                TelnetOption::NEW_ENVIRON,
+            ),
+            timed(
+                40,
+                Direction::Received,
+                TelnetCommand::WILL,
+                TelnetOption::TERMINAL_TYPE,
+            ),
+            timed(
+                41,
+                Direction::Received,
+                TelnetCommand::WONT,
+                TelnetOption::NAWS,
+            ),
+            // A repeated request is not a new measurement
+            timed(
+                50,
+                Direction::Sent,
+                TelnetCommand::DO,
+                TelnetOption::TERMINAL_TYPE,
+            ),
+            timed(
+                90,
+                Direction::Received,
+                TelnetCommand::DO,
+                TelnetOption::ECHO,
+            ),
+        ];
+        let stats = NegotiationStats::from_records(&records);
+
+        assert_eq!(stats.timings.len(), 4);
+        assert_eq!(stats.answered(), 3);
+        assert_eq!(stats.accepted(), 2);
+        assert_eq!(stats.unanswered(), 1);
+        assert_eq!(stats.timings[0].round_trip, Some(Duration::from_millis(40)));
+        assert!(!stats.timings[1].accepted());
+        assert_eq!(stats.median_round_trip(), Some(Duration::from_millis(40)));
+        assert_eq!(stats.slowest_round_trip(), Some(Duration::from_millis(88)));
+    }
+
+    #[test]
+    fn test_stats_ignore_unrequested_offers() {
+        let records = vec![
+            timed(
+                0,
+                Direction::Received,
+                TelnetCommand::WILL,
+                TelnetOption::NAWS,
+            ),
+            timed(1, Direction::Sent, TelnetCommand::DO, TelnetOption::NAWS),
+        ];
+        let stats = NegotiationStats::from_records(&records);
+
+        assert_eq!(stats.timings.len(), 1);
+        assert_eq!(stats.answered(), 0);
+        assert_eq!(stats.median_round_trip(), None);
+        assert!(NegotiationHistory::new().stats().timings.is_empty());
+    }
+
+    #[test]
+    fn test_per_option_ring_eviction() {
+        let mut history = NegotiationHistory::with_capacity(2);
+
+        record(&mut history, TelnetCommand::WILL, TelnetOption::NAWS);
+        for _ in 0..5 {
+            record(&mut history, TelnetCommand::DO, TelnetOption::ECHO);
+        }
+
+        // ECHO churn must not evict the NAWS record
+        assert_eq!(history.for_option(TelnetOption::NAWS).count(), 1);
+        assert_eq!(history.for_option(TelnetOption::ECHO).count(), 2);
+        assert_eq!(history.total_recorded(), 6);
+        assert_eq!(history.retained(), 3);
+        assert_eq!(
+            history.memory_used(),
+            3 * std::mem::size_of::<NegotiationRecord>()
+        );
+
+        let echo_seqs: Vec<u64> = history
+            .for_option(TelnetOption::ECHO)
+            .map(|r| r.seq)
+            .collect();
+        assert_eq!(echo_seqs, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_chronological_merge() {
+        let mut history = NegotiationHistory::new();
+        record(&mut history, TelnetCommand::DO, TelnetOption::TERMINAL_TYPE);
+        record(&mut history, TelnetCommand::WILL, TelnetOption::ECHO);
+        record(
+            &mut history,
+            TelnetCommand::WILL,
+            TelnetOption::TERMINAL_TYPE,
+        );
+
+        let order: Vec<TelnetOption> = history.chronological().iter().map(|r| r.option).collect();
+        assert_eq!(
+            order,
+            vec![
+                TelnetOption::TERMINAL_TYPE,
+                TelnetOption::ECHO,
+                TelnetOption::TERMINAL_TYPE
+            ]
+        );
+        assert_eq!(
+            history.options(),
+            vec![TelnetOption::ECHO, TelnetOption::TERMINAL_TYPE]
+        );
+    }
+}
@@ -15,7 +15,19 @@
 //! The library is organized into several modules:
 //! - `protocol`: Basic Telnet protocol constants and types (RFC 854)
 //! - `negotiation`: Core negotiation logic (RFC 1143 Q-method)
+//! - `simulation`: Runs two negotiators against each other to check convergence
 //! - `stream`: TelnetStream wrapper for transparent integration
+//! - `profile`: Which options to request at connect, and in what order
+//! - `server`: `TelnetServerBuilder`, the opening handshake for accepted connections
+//! - `split`: Read and write halves of a TelnetStream for separate threads
+//! - `vectors`: Byte-level wire-compatibility test vectors and their loader
+//! - `conformance`: The RFC 1143 state table and common clients' handshakes, as data, with checks
+//! - `transport`: The `Transport` trait for running TelnetStream over TCP, Unix sockets or test doubles
+//! - `codec`: `TelnetCodec` for tokio `Framed` transports (`codec` feature)
+//! - `tls`: `TelnetStream::new_tls` and a START_TLS upgrader over rustls (`tls` feature)
+//! - `websocket`: `WebSocketTransport`, telnet over WebSocket for browser clients (`websocket` feature)
+//! - `events`: Line and prompt events for MUD trigger engines
+//! - `line_reader`: `LineReader`, line editing and echo for character-mode clients
 //! - `options`: Individual option implementations (Echo, Terminal Type, etc.)
 //!
 //! ## Phase 6: Specific Telnet Options  
@@ -29,12 +41,18 @@
 //! - **Phase 6**: Echo Option (RFC 857) for secure password input
 //! - **Phase 6**: Terminal Type Option (RFC 1091) for capability detection
 //! - **Phase 6**: NAWS Option (RFC 1073) for window size negotiation
+//! - NEW-ENVIRON Option (RFC 1572) for client environment variables
+//! - TERMINAL-SPEED Option (RFC 1079) for pacing output to slow links
+//! - SEND-LOCATION Option (RFC 779) for where the caller says they are
 //! - **Phase 6**: Sub-negotiation framework with automatic routing
 //! - **Phase 6**: High-level API methods for common BBS operations
 //! - MUSH/MUD protocol extensions (MCCP, MXP, GMCP, etc.)
 //! - Command and option serialization/deserialization
 //! - IAC sequence detection and parsing from byte streams
 //! - Data/command separation with stateful parsing
+//! - Line and prompt events (split on EOR/GA) for MUD client triggers
+//! - Line discipline: backspace, Ctrl+U, server-side echo (masked for passwords) and a length cap
+//! - Pollable negotiation events: options switching, sub-negotiations, resizes, echo
 //! - Sub-negotiation sequence handling with option-specific routing
 //! - RFC 1143 compliant option negotiation state machine
 //! - Loop-free WILL/WONT/DO/DONT handling
@@ -42,28 +60,204 @@
 //! - Automatic response generation and transmission
 //! - TelnetStream wrapper for transparent operation
 //! - Drop-in replacement for TcpStream with automatic telnet handling
+//! - Runs over any `Transport`: TCP, Unix sockets, TLS or in-memory test doubles
+//! - WebSocket transport for web MUD clients and browser terminals (`websocket` feature)
+//! - `testing::MockStream`: scripted input and captured output for socket-free tests
+//! - Keepalives (IAC NOP or a timing mark) on idle connections
+//! - Per-connection protocol counters, with a hook for exporting them to metrics collectors
+//! - Per-connection memory budget with flush, drop or disconnect on overflow
+//! - Structured `TelnetError` telling protocol abuse and timeouts from I/O failures
+//! - Parser limits on sub-negotiation size: truncate, drop or fail on overflow
+//! - Loop guard against peers repeating requests we refused
 //! - Read/Write traits for backward compatibility
+//! - CP437 and Latin-1 transcoding, by hand or following the client's reported character set
+//! - ANSI downgrading: colors brought down to the terminal's depth, cursor control removed for dumb terminals
+//! - Split into read and write halves for a dedicated output thread
+//! - Vectored writes and write buffering with a flush policy (immediate, per line, manual)
 //! - RFC compliance checking and categorization
+//! - Optional strict audit of outgoing sequences (`rfc-audit` feature)
+//! - START_TLS upgrade to TLS on the same port, with a pluggable TLS backend
+//! - Implicit TLS ("telnets") and a ready-made START_TLS backend over rustls (`tls` feature)
+//! - Client mode for outbound connections to other telnet servers
+//! - Server handshake builder: offer options, wait until the client is quiet, get capabilities
+//! - Negotiation snapshots to hand a connection to another process or worker
+//!
+//! ## Cargo Features
+//!
+//! Everything is enabled by default. Consumers that only need part of the
+//! crate can turn defaults off and pick from:
+//! - `std` (on by default): everything that needs the standard library;
+//!   see below
+//! - `parser`: `TelnetParser` for splitting data from commands
+//! - `negotiation`: the RFC 1143 state machine and negotiation history
+//! - `stream`: `TelnetStream` (pulls in `parser`, `negotiation` and `options-core`)
+//! - `options-core`: Echo, Terminal Type, Terminal Speed, NAWS, LINEMODE, NEW-ENVIRON and
+//!   START_TLS handlers, the `TerminalCapabilities` merged from them, and
+//!   the `ansi` filter fitted to them
+//! - `options-mud`: MUD-specific option handlers (MSP, MSSP, MXP)
+//! - `compression`: reserved for MCCP
+//! - `codec` (off by default): `TelnetCodec`, a `tokio_util::codec` decoder
+//!   and encoder of telnet frames; adds the `tokio-util` and `bytes` dependencies
+//! - `serde` (off by default): `Serialize` and `Deserialize` for
+//!   protocol types, terminal capabilities and negotiation snapshots;
+//!   adds the `serde` dependency
+//! - `tls` (off by default, enables `stream`): implicit TLS with
+//!   `TelnetStream::new_tls`, and `RustlsUpgrader` for START_TLS; adds the
+//!   `rustls` dependency
+//! - `websocket` (off by default, enables `stream`): `WebSocketTransport`,
+//!   which answers a browser's WebSocket upgrade and carries telnet in
+//!   binary frames; no extra dependencies
+//!
+//! `protocol`, `error`, `audit`, `encoding`, `vectors` and the
+//! [`options::TelnetOptionHandler`] trait are always available with `std`.
+//!
+//! ### `no_std`
+//!
+//! With `std` off the crate is `no_std` and needs only `alloc`, for
+//! embedded gateways and WASM clients that want the state machines but
+//! bring their own I/O. What remains is `protocol`, [`ProtocolViolation`],
+//! and with their features `parser` and `negotiation` (less
+//! `OptionNegotiator::expire_stale` and the negotiation history, which
+//! need a clock) along with `simulation`:
+//!
+//! ```toml
+//! telnet-negotiation = { version = "0.6", default-features = false, features = ["parser", "negotiation"] }
+//! ```
+//!
+//! Every other feature turns `std` back on.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 // Re-export main types for convenience
-pub use negotiation::{NegotiationResult, OptionNegotiator, OptionState, QueueState, Side};
-pub use options::{EchoOption, EchoState, NawsOption, TerminalTypeOption, WindowSize};
-pub use parser::{ParseResult, TelnetParser};
+#[cfg(feature = "options-core")]
+pub use ansi::{AnsiFilter, AnsiProfile};
+#[cfg(feature = "stream")]
+pub use budget::{DEFAULT_MEMORY_BUDGET, MemoryBudget, MemoryStats, OverflowPolicy};
+#[cfg(feature = "options-core")]
+pub use capabilities::{CapabilitySources, ColorDepth, TerminalCapabilities};
+#[cfg(feature = "codec")]
+pub use codec::{TelnetCodec, TelnetFrame};
+#[cfg(feature = "std")]
+pub use encoding::{Encoding, Transcoder};
+pub use error::ProtocolViolation;
+#[cfg(feature = "std")]
+pub use error::TelnetError;
+#[cfg(feature = "stream")]
+pub use events::TextEvents;
+#[cfg(all(feature = "parser", feature = "std"))]
+pub use events::{LineSegmenter, TextEvent};
+#[cfg(all(feature = "negotiation", feature = "std"))]
+pub use history::{
+    Direction, NegotiationHistory, NegotiationRecord, NegotiationStats, OptionTiming,
+};
+#[cfg(feature = "stream")]
+pub use line_reader::{DEFAULT_MAX_LINE, LineEcho, LineReader};
+#[cfg(feature = "stream")]
+pub use loop_guard::{DEFAULT_LOOP_REFUSALS, DEFAULT_LOOP_WINDOW, LoopGuard, LoopPolicy};
+#[cfg(feature = "negotiation")]
+pub use negotiation::{
+    AcceptPolicy, NegotiationResult, NegotiatorDump, NegotiatorSnapshot, OptionNegotiator,
+    OptionPolicy, OptionState, QueueState, Side, StaleNegotiation, default_accept_policy,
+};
+#[cfg(feature = "options-core")]
+pub use options::{
+    EchoOption, EchoState, EnvironKind, Environment, LinemodeOption, NawsOption, NewEnvironOption,
+    SendLocationOption, StartTlsOption, StartTlsState, TerminalSpeed, TerminalSpeedOption,
+    TerminalTypeOption, TlsTransport, TlsUpgrader, WindowSize,
+};
+#[cfg(feature = "options-mud")]
+pub use options::{MspKind, MspOption, MspTrigger, MsspOption, MxpMode, MxpOption};
+#[cfg(feature = "parser")]
+pub use parser::{LimitPolicy, ParseEvent, ParseEvents, ParseResult, ParserLimits, TelnetParser};
+#[cfg(feature = "stream")]
+pub use profile::{BUILT_IN_REQUESTS, NegotiationProfile};
 pub use protocol::{IAC, TelnetCommand, TelnetOption, TelnetSequence};
-pub use stream::{ColorDepth, TelnetStream, TerminalCapabilities}; // Phase 6: ✅ Enhanced Stream + Options
+#[cfg(feature = "stream")]
+pub use proxy::{Flow, ProxyMode, TelnetProxy};
+#[cfg(feature = "stream")]
+pub use server::{DEFAULT_HANDSHAKE_TIMEOUT, Handshake, TelnetServerBuilder};
+#[cfg(feature = "stream")]
+pub use split::{ReuniteError, TelnetReadHalf, TelnetWriteHalf};
+#[cfg(feature = "stream")]
+pub use stats::ConnectionStats;
+#[cfg(feature = "stream")]
+pub use stream::{
+    AnsiFiltering, ControlEvent, FlushPolicy, KeepaliveProbe, NegotiationEvent, NewlineMode,
+    PromptMark, Role, StreamSnapshot, TelnetStream, Transcoding, WRITE_BUFFER_CAPACITY, WriteCheck,
+}; // Phase 6: ✅ Enhanced Stream + Options
+#[cfg(feature = "tls")]
+pub use tls::{RustlsUpgrader, TlsStream};
+#[cfg(feature = "stream")]
+pub use transport::Transport;
+#[cfg(feature = "websocket")]
+pub use websocket::{SUBPROTOCOLS, WebSocketRequest, WebSocketTransport};
 
 // Module declarations - implemented incrementally
+#[cfg(feature = "options-core")]
+pub mod ansi; // Fits outgoing escape sequences to the client's terminal
+#[cfg(feature = "std")]
+pub mod audit; // Outgoing RFC framing checks (enforced with the `rfc-audit` feature)
+#[cfg(feature = "stream")]
+pub mod budget; // Per-connection memory budget
+#[cfg(feature = "options-core")]
+pub mod capabilities; // Terminal capabilities fused from every option that describes them
+#[cfg(feature = "codec")]
+pub mod codec; // Telnet framing for tokio_util::codec
+#[cfg(all(feature = "negotiation", feature = "std"))]
+pub mod conformance; // RFC 1143 state table and client handshake checks
+#[cfg(feature = "std")]
+pub mod encoding; // CP437 and Latin-1 transcoding
+pub mod error; // TelnetError and the protocol violations behind it
+#[cfg(all(feature = "parser", feature = "std"))]
+pub mod events; // Line and prompt events above the parser
+#[cfg(all(feature = "negotiation", feature = "std"))]
+pub mod history; // Per-option negotiation history
+#[cfg(feature = "stream")]
+pub mod line_reader; // Line editing and echo for character-mode clients
+#[cfg(feature = "stream")]
+pub mod loop_guard; // Stops peers repeating refused requests
+#[cfg(feature = "negotiation")]
 pub mod negotiation; // Phase 4: ✅ Option negotiation state machine (RFC 1143)
+#[cfg(feature = "std")]
 pub mod options; // Phase 6: ✅ Individual option implementations
+#[cfg(feature = "parser")]
 pub mod parser; // Phase 3: ✅ Command detection and parsing
+#[cfg(feature = "stream")]
+pub mod profile; // Options requested at connect, in order
 pub mod protocol; // Phase 2: ✅ Protocol constants and types
+#[cfg(feature = "stream")]
+pub mod proxy; // Relays between two telnet connections
+#[cfg(feature = "stream")]
+mod ring; // Byte ring buffer behind TelnetStream reads
+#[cfg(feature = "stream")]
+pub mod server; // Opening handshake for accepted connections
+#[cfg(feature = "negotiation")]
+pub mod simulation; // Two negotiators run against each other, for tests and docs
+#[cfg(feature = "stream")]
+pub mod split; // Read and write halves sharing one stream
+#[cfg(feature = "stream")]
+pub mod stats; // Per-connection protocol counters and their export hook
+#[cfg(feature = "stream")]
 pub mod stream; // Phase 5: ✅ TelnetStream wrapper
+#[cfg(feature = "stream")]
+pub mod testing; // In-memory transport for end-to-end stream tests
+#[cfg(feature = "tls")]
+pub mod tls; // Implicit TLS and START_TLS over rustls
+#[cfg(feature = "stream")]
+pub mod transport; // What a TelnetStream can run over
+#[cfg(feature = "std")]
+pub mod vectors; // Wire-compatibility test vectors
+#[cfg(feature = "websocket")]
+pub mod websocket; // Telnet over WebSocket for browser clients
 
 /// Library version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Supported Telnet RFCs
 pub const SUPPORTED_RFCS: &[&str] = &[
+    "RFC 779 - Telnet Send-Location Option",
     "RFC 854 - Telnet Protocol Specification",
     "RFC 855 - Telnet Option Specifications",
     "RFC 856 - Telnet Binary Transmission",
@@ -71,6 +265,7 @@ pub const SUPPORTED_RFCS: &[&str] = &[
     "RFC 858 - Telnet Suppress Go Ahead Option",
     "RFC 859 - Telnet Status Option",
     "RFC 860 - Telnet Timing Mark Option",
+    "RFC 885 - Telnet End of Record Option",
     "RFC 1073 - Telnet Window Size Option",
     "RFC 1079 - Telnet Terminal Speed Option",
     "RFC 1091 - Telnet Terminal-Type Option",
@@ -0,0 +1,518 @@
+//! # Line Discipline
+//!
+//! A terminal in line mode edits each line itself and sends it whole. One
+//! in character mode, or any client once the server has said WILL ECHO,
+//! sends every key as it is pressed, backspaces and arrow keys included,
+//! and shows only what the server echoes back. [`LineReader`] does the
+//! server's half of that:
+//!
+//! - BS and DEL erase the last character, and Ctrl+U the whole line
+//! - Escape sequences (arrow keys, function keys) are dropped, as are
+//!   other control characters
+//! - CR LF, CR NUL, a bare CR and a bare LF all end a line
+//! - Lines are capped at [`LineReader::max_len`] characters; keys past
+//!   the cap ring the bell
+//! - Typed characters are echoed while we have agreed to WILL ECHO, or
+//!   always or never if set, optionally masked for passwords
+//!
+//! [`LineReader::read_secret`] (and [`TelnetStream::read_secret`]) also
+//! switch echo around a password prompt.
+//!
+//! ```rust
+//! use telnet_negotiation::testing::MockStream;
+//! use telnet_negotiation::{LineReader, TelnetStream};
+//!
+//! // "pasx" typed, a backspace, "s", then Enter
+//! let mut stream = TelnetStream::new(MockStream::with_input(b"pasx\x7fs\r\n"));
+//! let mut reader = LineReader::new();
+//! let mut line = String::new();
+//! reader.read_line(&mut stream, &mut line).unwrap();
+//! assert_eq!(line, "pass");
+//! ```
+//!
+//! Echoing erases with `BS SPACE BS`, one column per character, which
+//! suits everything but double-width characters.
+
+use crate::negotiation::{OptionState, Side};
+use crate::protocol::TelnetOption;
+use crate::stream::TelnetStream;
+use crate::transport::Transport;
+
+use std::io::{self, BufRead, Write};
+
+/// Longest line a [`LineReader`] takes by default, in characters
+pub const DEFAULT_MAX_LINE: usize = 1024;
+
+const BELL: u8 = 0x07;
+const BACKSPACE: u8 = 0x08;
+const ESCAPE: u8 = 0x1B;
+const DELETE: u8 = 0x7F;
+const KILL_LINE: u8 = 0x15; // Ctrl+U
+
+/// When a [`LineReader`] echoes what the client types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEcho {
+    /// While our side of ECHO is on, i.e. after we said WILL ECHO
+    #[default]
+    Auto,
+    /// Always; for clients that never echo themselves
+    Always,
+    /// Never
+    Never,
+}
+
+/// Where in an escape sequence the input is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// After ESC
+    Started,
+    /// After ESC [ or ESC O, up to the final byte
+    Sequence,
+}
+
+/// Reads edited lines from a [`TelnetStream`]
+///
+/// Keeps the line being typed between calls, so a read timeout or resize
+/// interrupt part way loses nothing. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct LineReader {
+    max_len: usize,
+    echo: LineEcho,
+    mask: Option<char>,
+    /// The line so far, as UTF-8 bytes
+    line: Vec<u8>,
+    /// Characters in `line`
+    chars: usize,
+    /// Bytes taken from the stream for `line`, up to its ending
+    taken: usize,
+    /// The last line ended with CR, so a following LF or NUL is its tail
+    after_cr: bool,
+    /// Dropping the rest of a character that went over the cap
+    overflowing: bool,
+    escape: Escape,
+    /// Reading a secret: echo only the mask, if any
+    secret: bool,
+}
+
+impl Default for LineReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineReader {
+    /// A reader with [`DEFAULT_MAX_LINE`], [`LineEcho::Auto`] and no mask
+    pub fn new() -> Self {
+        Self {
+            max_len: DEFAULT_MAX_LINE,
+            echo: LineEcho::Auto,
+            mask: None,
+            line: Vec::new(),
+            chars: 0,
+            taken: 0,
+            after_cr: false,
+            overflowing: false,
+            escape: Escape::None,
+            secret: false,
+        }
+    }
+
+    /// Longest line taken, in characters
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Cap lines at `max_len` characters (at least 1)
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len.max(1);
+    }
+
+    pub fn echo(&self) -> LineEcho {
+        self.echo
+    }
+
+    pub fn set_echo(&mut self, echo: LineEcho) {
+        self.echo = echo;
+    }
+
+    /// Echo `mask` in place of each typed character, e.g. `'*'` for a
+    /// password; `None` echoes what was typed
+    pub fn set_mask(&mut self, mask: Option<char>) {
+        self.mask = mask;
+    }
+
+    /// The line typed so far, before its ending arrives
+    pub fn partial(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Forget the line typed so far
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.chars = 0;
+        self.taken = 0;
+        self.overflowing = false;
+        self.escape = Escape::None;
+    }
+
+    /// Read an edited line, appending it to `line` without its ending
+    ///
+    /// Returns the number of bytes taken from the stream for it, 0 at end
+    /// of file; text after the last line ending is returned as a final
+    /// line. Invalid UTF-8 is replaced. Errors (`WouldBlock`, a read
+    /// timeout, a resize interrupt) keep the partial line for the next
+    /// call.
+    pub fn read_line<S: Transport>(
+        &mut self,
+        stream: &mut TelnetStream<S>,
+        line: &mut String,
+    ) -> io::Result<usize> {
+        loop {
+            let chunk = stream.fill_buf()?;
+            if chunk.is_empty() {
+                if self.taken == 0 {
+                    return Ok(0);
+                }
+                return Ok(self.finish(line));
+            }
+
+            let mut echo = Vec::new();
+            let mut used = 0;
+            let mut ended = false;
+            for &byte in chunk {
+                used += 1;
+                if self.edit(byte, &mut echo) {
+                    ended = true;
+                    break;
+                }
+            }
+            stream.consume(used);
+            self.taken += used;
+
+            // Asked after the read, which may have carried the DO ECHO
+            if self.echoing(stream) && !echo.is_empty() {
+                stream.write_all(&echo)?;
+                stream.flush()?;
+            }
+            if ended {
+                return Ok(self.finish(line));
+            }
+        }
+    }
+
+    /// Prompt for a password or other secret and read it
+    ///
+    /// Says WILL ECHO before the prompt and WONT ECHO after the line,
+    /// unless our side of ECHO was already on or on its way, so the
+    /// caller's terminal is left as it was found. Typed characters echo as
+    /// the [mask](Self::set_mask), or not at all without one, and the line
+    /// is always ended on screen. Returns `None` if the peer hung up.
+    ///
+    /// Resizes don't interrupt the read; they are still reported by
+    /// [`TelnetStream::take_resize`]. A signal (with
+    /// [`TelnetStream::set_interrupt_on_signal`]) or any other error ends
+    /// it, discarding what was typed and restoring echo first.
+    pub fn read_secret<S: Transport>(
+        &mut self,
+        stream: &mut TelnetStream<S>,
+        prompt: &str,
+    ) -> io::Result<Option<String>> {
+        let already = stream.option_state(Side::Local, TelnetOption::ECHO) != OptionState::No;
+        if !already {
+            stream.request_echo_off()?;
+        }
+        stream.write_all(prompt.as_bytes())?;
+        stream.flush()?;
+
+        let interrupt_on_resize = stream.interrupts_on_resize();
+        stream.set_interrupt_on_resize(false);
+        self.clear();
+        self.secret = true;
+        let mut line = String::new();
+        let result = self.read_line(stream, &mut line);
+        let echoed = self.echoing(stream);
+        self.secret = false;
+        stream.set_interrupt_on_resize(interrupt_on_resize);
+
+        // Restore echo even after an error, but report the error first
+        let restored = if already {
+            Ok(())
+        } else {
+            stream.request_echo_on().map(drop)
+        };
+        let taken = result.inspect_err(|_| self.clear())?;
+        restored?;
+        if taken == 0 {
+            return Ok(None);
+        }
+        if !echoed {
+            stream.write_all(b"\r\n")?;
+            stream.flush()?;
+        }
+        Ok(Some(line))
+    }
+
+    /// Whether typing is echoed right now
+    fn echoing<S: Transport>(&self, stream: &TelnetStream<S>) -> bool {
+        match self.echo {
+            LineEcho::Auto => stream.is_option_enabled(Side::Local, TelnetOption::ECHO),
+            LineEcho::Always => true,
+            LineEcho::Never => false,
+        }
+    }
+
+    /// Hand over the finished line, returning the bytes it took
+    fn finish(&mut self, line: &mut String) -> usize {
+        line.push_str(&String::from_utf8_lossy(&self.line));
+        let taken = self.taken;
+        self.clear();
+        taken
+    }
+
+    /// Apply one typed byte, adding what to echo; `true` at a line ending
+    fn edit(&mut self, byte: u8, echo: &mut Vec<u8>) -> bool {
+        if std::mem::take(&mut self.after_cr) && matches!(byte, b'\n' | 0) {
+            return false;
+        }
+        match self.escape {
+            Escape::Started => {
+                self.escape = if matches!(byte, b'[' | b'O') {
+                    Escape::Sequence
+                } else {
+                    Escape::None
+                };
+                return false;
+            }
+            Escape::Sequence => {
+                if (0x40..=0x7E).contains(&byte) {
+                    self.escape = Escape::None;
+                }
+                return false;
+            }
+            Escape::None => {}
+        }
+
+        match byte {
+            b'\r' | b'\n' => {
+                self.after_cr = byte == b'\r';
+                echo.extend_from_slice(b"\r\n");
+                return true;
+            }
+            BACKSPACE | DELETE => self.erase(echo),
+            KILL_LINE => {
+                while self.chars > 0 {
+                    self.erase(echo);
+                }
+            }
+            ESCAPE => self.escape = Escape::Started,
+            byte if byte < 0x20 => {}
+            byte => self.insert(byte, echo),
+        }
+        false
+    }
+
+    fn insert(&mut self, byte: u8, echo: &mut Vec<u8>) {
+        let starts_char = !is_continuation(byte);
+        if starts_char {
+            self.overflowing = self.chars >= self.max_len;
+            if self.overflowing {
+                echo.push(BELL);
+                return;
+            }
+            self.chars += 1;
+        } else if self.overflowing {
+            return;
+        }
+
+        self.line.push(byte);
+        match self.mask {
+            Some(mask) if starts_char => {
+                let mut encoded = [0; 4];
+                echo.extend_from_slice(mask.encode_utf8(&mut encoded).as_bytes());
+            }
+            Some(_) => {}
+            None if self.secret => {}
+            None => echo.push(byte),
+        }
+    }
+
+    /// Remove the last character, and rub it out on screen
+    fn erase(&mut self, echo: &mut Vec<u8>) {
+        if self.chars == 0 {
+            return;
+        }
+        while let Some(byte) = self.line.pop() {
+            if !is_continuation(byte) {
+                break;
+            }
+        }
+        self.chars -= 1;
+        if !self.secret || self.mask.is_some() {
+            echo.extend_from_slice(&[BACKSPACE, b' ', BACKSPACE]);
+        }
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{TelnetCommand, TelnetSequence};
+    use crate::testing::MockStream;
+
+    /// WILL and WONT ECHO sent to the peer, in order
+    fn echo_commands(peer: &MockStream) -> Vec<TelnetCommand> {
+        peer.written_sequences()
+            .into_iter()
+            .filter_map(|sequence| match sequence {
+                TelnetSequence::Negotiation { command, option } if option == TelnetOption::ECHO => {
+                    Some(command)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn read_all(reader: &mut LineReader, stream: &mut TelnetStream<MockStream>) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            match reader.read_line(stream, &mut line) {
+                Ok(0) => return lines,
+                Ok(_) => lines.push(line),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("unexpected {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_editing_and_endings() {
+        let peer = MockStream::new();
+        // Backspace, DEL, Ctrl+U, an arrow key, a stray control character
+        peer.push_input(b"helo\x08lo\r\n");
+        peer.push_input(b"oops\x15yes\r\0");
+        peer.push_input(b"le\x1b[Dft\x01\n");
+        peer.push_input("caf\u{e9}\x7f\u{e8}\r".as_bytes());
+        peer.push_would_block();
+        peer.push_input(b"\nlast");
+
+        let mut stream = TelnetStream::new(peer.clone());
+        let mut reader = LineReader::new();
+        assert_eq!(
+            read_all(&mut reader, &mut stream),
+            ["hello", "yes", "left", "caf\u{e8}", "last"]
+        );
+        // Nothing echoed without WILL ECHO
+        assert!(peer.written_data().is_empty());
+    }
+
+    #[test]
+    fn test_partial_line_survives_a_timeout() {
+        let peer = MockStream::new();
+        peer.push_input(b"ab");
+        peer.push_would_block();
+        peer.push_input(b"\x7fc\r\n");
+
+        let mut stream = TelnetStream::new(peer);
+        let mut reader = LineReader::new();
+        let mut line = String::new();
+        let error = reader.read_line(&mut stream, &mut line).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(reader.partial(), b"ab");
+
+        assert_eq!(reader.read_line(&mut stream, &mut line).unwrap(), 5);
+        assert_eq!(line, "ac");
+    }
+
+    #[test]
+    fn test_echo_after_will_echo() {
+        let peer = MockStream::new();
+        // IAC DO ECHO agrees to our WILL ECHO
+        peer.push_input(b"\xff\xfd\x01");
+        peer.push_input(b"pw\x7fx\r\n");
+
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.request_echo_off().unwrap();
+        let mut reader = LineReader::new();
+        reader.set_mask(Some('*'));
+        let mut line = String::new();
+        reader.read_line(&mut stream, &mut line).unwrap();
+        assert_eq!(line, "px");
+        assert_eq!(peer.written_data(), b"**\x08 \x08*\r\n");
+
+        // Unmasked, and always
+        let peer = MockStream::with_input(b"hi\r");
+        let mut stream = TelnetStream::new(peer.clone());
+        reader.set_mask(None);
+        reader.set_echo(LineEcho::Always);
+        line.clear();
+        reader.read_line(&mut stream, &mut line).unwrap();
+        assert_eq!(line, "hi");
+        assert_eq!(peer.written_data(), b"hi\r\n");
+    }
+
+    #[test]
+    fn test_read_secret_switches_echo_around_the_prompt() {
+        // The client agrees to WILL ECHO, then types with a backspace
+        let peer = MockStream::new();
+        peer.push_input(b"\xff\xfd\x01");
+        peer.push_input(b"s3cx\x7fret\r\n");
+        let mut stream = TelnetStream::new(peer.clone());
+        let secret = stream.read_secret("Password: ").unwrap();
+        assert_eq!(secret.as_deref(), Some("s3cret"));
+        assert_eq!(peer.written_data(), b"Password: \r\n");
+        assert_eq!(
+            echo_commands(&peer),
+            [TelnetCommand::WILL, TelnetCommand::WONT]
+        );
+
+        // A client that never answers still gets its line ended
+        let peer = MockStream::with_input(b"pw\r\n");
+        let mut stream = TelnetStream::new(peer.clone());
+        assert_eq!(stream.read_secret("PIN: ").unwrap().as_deref(), Some("pw"));
+        assert_eq!(peer.written_data(), b"PIN: \r\n");
+
+        // Hanging up mid-prompt
+        let mut stream = TelnetStream::new(MockStream::with_input(b"\xff\xfd\x01"));
+        assert_eq!(stream.read_secret("Password: ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_secret_masks_and_leaves_echo_on() {
+        let peer = MockStream::new();
+        peer.push_input(b"\xff\xfd\x01");
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.request_echo_off().unwrap();
+        stream.fill_buf().unwrap();
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+        peer.take_written();
+
+        // Already echoing, as in character mode: no WILL or WONT this time
+        peer.push_input(b"ab\r\n");
+        let mut reader = LineReader::new();
+        reader.set_mask(Some('*'));
+        let secret = reader.read_secret(&mut stream, "Password: ").unwrap();
+        assert_eq!(secret.as_deref(), Some("ab"));
+        assert_eq!(peer.written_data(), b"Password: **\r\n");
+        assert!(echo_commands(&peer).is_empty());
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+    }
+
+    #[test]
+    fn test_max_len_rings_the_bell() {
+        let peer = MockStream::with_input("abc\u{e9}d\x7fe\r\n".as_bytes());
+        let mut stream = TelnetStream::new(peer.clone());
+        let mut reader = LineReader::new();
+        reader.set_max_len(3);
+        reader.set_echo(LineEcho::Always);
+        let mut line = String::new();
+        reader.read_line(&mut stream, &mut line).unwrap();
+        assert_eq!(line, "abe");
+        assert_eq!(peer.written_data(), b"abc\x07\x07\x08 \x08e\r\n");
+    }
+}
@@ -0,0 +1,120 @@
+//! # Negotiation loop guard
+//!
+//! RFC 1143 keeps two well-behaved ends from looping, but it can't stop a
+//! peer that ignores our answers. A broken or hostile client that sends
+//! IAC WILL for an option we refuse, gets IAC DONT back, and sends the same
+//! WILL again will keep both ends busy forever, each refusal costing a
+//! write on a server with hundreds of connections.
+//!
+//! [`LoopGuard`] counts how often the peer repeats a WILL or DO we have
+//! refused and, once it has done so too often within a time window, either
+//! stops answering it or drops the connection.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use telnet_negotiation::{LoopGuard, LoopPolicy};
+//!
+//! // Three refusals a minute, then hang up
+//! let guard = LoopGuard::new(3, Duration::from_secs(60), LoopPolicy::Disconnect);
+//! assert_eq!(guard.refusals(), Some(3));
+//! ```
+
+use std::time::Duration;
+
+/// Refusals of one request a stream answers by default before acting
+pub const DEFAULT_LOOP_REFUSALS: u32 = 5;
+
+/// Window the default refusals are counted over
+pub const DEFAULT_LOOP_WINDOW: Duration = Duration::from_secs(10);
+
+/// What a stream does about a peer repeating a refused request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopPolicy {
+    /// Stop answering the request until the window has passed
+    #[default]
+    Ignore,
+    /// Shut the connection down; reads fail with `ErrorKind::InvalidData`
+    Disconnect,
+}
+
+/// Limit on how often a peer may repeat a WILL or DO we refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopGuard {
+    refusals: Option<u32>,
+    window: Duration,
+    policy: LoopPolicy,
+}
+
+impl LoopGuard {
+    /// Answer at most `refusals` repeats of one request within `window`,
+    /// applying `policy` to any more
+    pub fn new(refusals: u32, window: Duration, policy: LoopPolicy) -> Self {
+        Self {
+            refusals: Some(refusals),
+            window,
+            policy,
+        }
+    }
+
+    /// No guard: every request is answered, however often it comes
+    pub fn off() -> Self {
+        Self {
+            refusals: None,
+            window: Duration::ZERO,
+            policy: LoopPolicy::default(),
+        }
+    }
+
+    /// Refusals answered within the window, or `None` when off
+    pub fn refusals(&self) -> Option<u32> {
+        self.refusals
+    }
+
+    /// How far back refusals are counted
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// What happens past the limit
+    pub fn policy(&self) -> LoopPolicy {
+        self.policy
+    }
+
+    /// Whether `refused` refusals within the window is the limit reached
+    pub fn is_tripped(&self, refused: usize) -> bool {
+        self.refusals
+            .is_some_and(|refusals| refused >= refusals as usize)
+    }
+}
+
+impl Default for LoopGuard {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_LOOP_REFUSALS,
+            DEFAULT_LOOP_WINDOW,
+            LoopPolicy::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_guard() {
+        let guard = LoopGuard::default();
+        assert_eq!(guard.refusals(), Some(DEFAULT_LOOP_REFUSALS));
+        assert_eq!(guard.window(), DEFAULT_LOOP_WINDOW);
+        assert_eq!(guard.policy(), LoopPolicy::Ignore);
+        assert!(!guard.is_tripped(DEFAULT_LOOP_REFUSALS as usize - 1));
+        assert!(guard.is_tripped(DEFAULT_LOOP_REFUSALS as usize));
+    }
+
+    #[test]
+    fn test_off_never_trips() {
+        let guard = LoopGuard::off();
+        assert_eq!(guard.refusals(), None);
+        assert!(!guard.is_tripped(usize::MAX));
+    }
+}
@@ -32,14 +32,28 @@
 //! WANTNO + receive WONT/DONT -> NO
 //! ```
 //!
+//! ### Stale Negotiations
+//! RFC 1143 leaves a request waiting until the peer answers. A peer that
+//! never does would keep the option in WANTYES or WANTNO for good, so
+//! [`OptionNegotiator::expire_stale`] gives up on requests left unanswered
+//! too long, optionally after asking once more.
+//!
 //! ### RFC 1143 Reference Note
 //! The original RFC uses "us" and "him" terminology, but this implementation
 //! uses "local" and "remote" for more professional, gender-neutral terminology.
 
 use crate::protocol::{TelnetCommand, TelnetOption, TelnetSequence};
 
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
 /// Option negotiation state as defined by RFC 1143
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OptionState {
     /// Option is disabled
     No,
@@ -51,8 +65,26 @@ pub enum OptionState {
     WantYes { queue: QueueState },
 }
 
+/// RFC 1143's names: `NO`, `YES`, `WANTNO`, `WANTYES`, with ` OPPOSITE`
+/// after a queued reversal
+impl fmt::Display for OptionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, queue) = match self {
+            OptionState::No => return f.pad("NO"),
+            OptionState::Yes => return f.pad("YES"),
+            OptionState::WantNo { queue } => ("WANTNO", queue),
+            OptionState::WantYes { queue } => ("WANTYES", queue),
+        };
+        match queue {
+            QueueState::Empty => f.pad(name),
+            QueueState::Opposite => f.pad(&alloc::format!("{} OPPOSITE", name)),
+        }
+    }
+}
+
 /// Queue state for handling requests during negotiation (RFC 1143 Section 5)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QueueState {
     /// No queued request
     Empty,
@@ -82,6 +114,117 @@ pub struct NegotiationResult {
     pub error: Option<String>,
 }
 
+/// A negotiator's option states, saved to carry a connection across a
+/// process restart or over to another worker
+///
+/// Only options that aren't plainly off are listed. The accept policy is
+/// code rather than data, so it isn't saved: [`OptionNegotiator::restore`]
+/// keeps whichever policy the negotiator already has.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NegotiatorSnapshot {
+    /// Our options, by option byte
+    pub local: Vec<(u8, OptionState)>,
+    /// The peer's options, by option byte
+    pub remote: Vec<(u8, OptionState)>,
+    /// Whether the RFC 1143 queue system is on
+    pub queue_enabled: bool,
+}
+
+/// A negotiation the peer didn't answer in time, from
+/// [`OptionNegotiator::expire_stale`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleNegotiation {
+    /// Which side of the option was being negotiated
+    pub side: Side,
+    /// The option
+    pub option: TelnetOption,
+    /// The state it was stuck in
+    pub state: OptionState,
+    /// The request to send again, if this was a retry; `None` means the
+    /// option has been given up and is now off
+    pub retry: Option<TelnetSequence>,
+}
+
+/// A table of every option not plainly off, from
+/// [`OptionNegotiator::dump`]
+///
+/// ```text
+/// option                 local    remote
+/// ECHO (1)               YES      NO
+/// SUPPRESS_GO_AHEAD (3)  YES      YES
+/// NAWS (31)              NO       WANTYES
+/// ```
+///
+/// A last line notes when the RFC 1143 queue is off. With nothing
+/// negotiated the table is the single line `no options negotiated`.
+#[derive(Debug, Clone, Copy)]
+pub struct NegotiatorDump<'a> {
+    negotiator: &'a OptionNegotiator,
+}
+
+impl fmt::Display for NegotiatorDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let negotiator = self.negotiator;
+        let rows: Vec<(String, OptionState, OptionState)> = (0..=u8::MAX)
+            .map(TelnetOption::new)
+            .filter_map(|option| {
+                let local = negotiator.get_state(Side::Local, option);
+                let remote = negotiator.get_state(Side::Remote, option);
+                if local == OptionState::No && remote == OptionState::No {
+                    return None;
+                }
+                let label = match option.name() {
+                    Some(name) => alloc::format!("{} ({})", name, option.to_byte()),
+                    None => option.to_byte().to_string(),
+                };
+                Some((label, local, remote))
+            })
+            .collect();
+
+        if rows.is_empty() {
+            f.write_str("no options negotiated")?;
+        } else {
+            let width = rows
+                .iter()
+                .map(|(label, ..)| label.len())
+                .max()
+                .unwrap_or(0);
+            let state_width = rows
+                .iter()
+                .map(|(_, local, _)| local.to_string().len())
+                .max()
+                .unwrap_or(0)
+                .max("local".len());
+            write!(f, "{:<width$}  {:<state_width$}  remote", "option", "local")?;
+            for (label, local, remote) in &rows {
+                write!(
+                    f,
+                    "\n{:<width$}  {:<state_width$}  {}",
+                    label, local, remote
+                )?;
+            }
+        }
+        if !negotiator.queue_enabled {
+            f.write_str("\nRFC 1143 queue: off")?;
+        }
+        Ok(())
+    }
+}
+
+/// A request waiting on the peer's answer
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    side: Side,
+    option: TelnetOption,
+    /// True while waiting to enable (WANTYES), false to disable (WANTNO)
+    enabling: bool,
+    #[cfg(feature = "std")]
+    since: Instant,
+    #[cfg(feature = "std")]
+    retried: bool,
+}
+
 /// RFC 1143 compliant telnet option negotiator
 #[derive(Debug, Clone)]
 pub struct OptionNegotiator {
@@ -91,6 +234,12 @@ pub struct OptionNegotiator {
     remote: [OptionState; 256],
     /// Whether to support the RFC 1143 queue system
     queue_enabled: bool,
+    /// Which options to agree to when the peer asks
+    accept_policy: Arc<dyn OptionPolicy>,
+    /// Options in WANTYES or WANTNO, and since when
+    pending: Vec<Pending>,
+    /// Whether a stale request is sent once more before giving up
+    retry_stale: bool,
 }
 
 impl Default for OptionNegotiator {
@@ -106,6 +255,9 @@ impl OptionNegotiator {
             local: [OptionState::No; 256],
             remote: [OptionState::No; 256],
             queue_enabled: true, // RFC 1143: MUST default to enabled
+            accept_policy: Arc::new(default_accept_policy),
+            pending: Vec::new(),
+            retry_stale: false,
         }
     }
 
@@ -118,6 +270,22 @@ impl OptionNegotiator {
         self.queue_enabled = enabled;
     }
 
+    /// Decide which options to agree to with `policy` instead of
+    /// [`default_accept_policy`]
+    ///
+    /// Any `Fn(TelnetOption, Side) -> bool` will do, including for
+    /// options this crate has no name for.
+    pub fn set_accept_policy(&mut self, policy: impl OptionPolicy + 'static) {
+        self.accept_policy = Arc::new(policy);
+    }
+
+    /// Send a stale request once more before giving up on it
+    ///
+    /// Off by default. See [`OptionNegotiator::expire_stale`].
+    pub fn set_retry_stale(&mut self, retry: bool) {
+        self.retry_stale = retry;
+    }
+
     /// Check if an option is currently enabled on the specified side
     pub fn is_enabled(&self, side: Side, option: TelnetOption) -> bool {
         let state = match side {
@@ -141,7 +309,7 @@ impl OptionNegotiator {
     pub fn handle_will(&mut self, option: TelnetOption) -> NegotiationResult {
         let current_state = self.remote[option.to_byte() as usize];
 
-        match current_state {
+        let result = match current_state {
             OptionState::No => {
                 // Remote wants to enable option
                 if self.should_accept_option(option, Side::Remote) {
@@ -231,7 +399,9 @@ impl OptionNegotiator {
                     }
                 }
             }
-        }
+        };
+        self.track(Side::Remote, option);
+        result
     }
 
     /// Process a WONT command received from the remote side
@@ -240,7 +410,7 @@ impl OptionNegotiator {
     pub fn handle_wont(&mut self, option: TelnetOption) -> NegotiationResult {
         let current_state = self.remote[option.to_byte() as usize];
 
-        match current_state {
+        let result = match current_state {
             OptionState::No => {
                 // Already disabled, ignore
                 NegotiationResult {
@@ -319,7 +489,9 @@ impl OptionNegotiator {
                     }
                 }
             }
-        }
+        };
+        self.track(Side::Remote, option);
+        result
     }
 
     /// Process a DO command received from the remote side
@@ -329,7 +501,7 @@ impl OptionNegotiator {
     pub fn handle_do(&mut self, option: TelnetOption) -> NegotiationResult {
         let current_state = self.local[option.to_byte() as usize];
 
-        match current_state {
+        let result = match current_state {
             OptionState::No => {
                 // Remote wants local side to enable option
                 if self.should_accept_option(option, Side::Local) {
@@ -418,7 +590,9 @@ impl OptionNegotiator {
                     }
                 }
             }
-        }
+        };
+        self.track(Side::Local, option);
+        result
     }
 
     /// Process a DONT command received from the remote side
@@ -428,7 +602,7 @@ impl OptionNegotiator {
     pub fn handle_dont(&mut self, option: TelnetOption) -> NegotiationResult {
         let current_state = self.local[option.to_byte() as usize];
 
-        match current_state {
+        let result = match current_state {
             OptionState::No => {
                 // Already disabled, ignore
                 NegotiationResult {
@@ -506,7 +680,9 @@ impl OptionNegotiator {
                     }
                 }
             }
-        }
+        };
+        self.track(Side::Local, option);
+        result
     }
 
     /// Request to enable an option on the specified side
@@ -606,7 +782,7 @@ impl OptionNegotiator {
                 }
             }
         };
-
+        self.track(side, option);
         result
     }
 
@@ -707,34 +883,100 @@ impl OptionNegotiator {
                 }
             }
         };
-
+        self.track(side, option);
         result
     }
 
-    /// Determine if we should accept a request to enable an option
+    /// Give up on negotiations the peer hasn't answered within `timeout`
     ///
-    /// This is where application-specific policy is implemented. By default,
-    /// we accept common safe options and reject unknown ones.
+    /// Every option that has been in WANTYES or WANTNO that long is
+    /// reported and turned off, as if the peer had refused, so it can be
+    /// asked for again later. With [`OptionNegotiator::set_retry_stale`]
+    /// on, the first time out instead hands back the request to send again
+    /// and restarts the clock; only a second one gives up.
     ///
-    /// Applications should override this logic based on their capabilities.
-    fn should_accept_option(&self, option: TelnetOption, _side: Side) -> bool {
-        match option {
-            // Safe options that most telnet implementations support
-            TelnetOption::ECHO => true,
-            TelnetOption::SUPPRESS_GO_AHEAD => true,
-            TelnetOption::NAWS => true,
-            TelnetOption::TERMINAL_TYPE => true,
-            TelnetOption::BINARY => true,
-            TelnetOption::NEW_ENVIRON => true,
-
-            // MUD/MUSH extensions - accept if we support them
-            TelnetOption::GMCP => true,
-            TelnetOption::MCCP2 => false, // Compression requires special handling
-            TelnetOption::MXP => false,   // Markup requires parser
-
-            // Reject unknown or complex options by default
-            _ => false,
+    /// Needs the `std` feature, for the clock.
+    #[cfg(feature = "std")]
+    pub fn expire_stale(&mut self, timeout: Duration) -> Vec<StaleNegotiation> {
+        let now = Instant::now();
+        let mut stale = Vec::new();
+        for mut pending in core::mem::take(&mut self.pending) {
+            if now.duration_since(pending.since) < timeout {
+                self.pending.push(pending);
+                continue;
+            }
+
+            let Pending { side, option, .. } = pending;
+            let state = self.get_state(side, option);
+            let retry = (self.retry_stale && !pending.retried).then(|| {
+                let command = match (side, pending.enabling) {
+                    (Side::Remote, true) => TelnetCommand::DO,
+                    (Side::Remote, false) => TelnetCommand::DONT,
+                    (Side::Local, true) => TelnetCommand::WILL,
+                    (Side::Local, false) => TelnetCommand::WONT,
+                };
+                TelnetSequence::Negotiation { command, option }
+            });
+            if retry.is_some() {
+                pending.since = now;
+                pending.retried = true;
+                self.pending.push(pending);
+            } else {
+                self.states_mut(side)[option.to_byte() as usize] = OptionState::No;
+            }
+            stale.push(StaleNegotiation {
+                side,
+                option,
+                state,
+                retry,
+            });
         }
+        stale
+    }
+
+    /// Note when `option` on `side` starts or stops waiting on the peer
+    fn track(&mut self, side: Side, option: TelnetOption) {
+        let enabling = match self.get_state(side, option) {
+            OptionState::WantYes { .. } => Some(true),
+            OptionState::WantNo { .. } => Some(false),
+            OptionState::Yes | OptionState::No => None,
+        };
+        let index = self
+            .pending
+            .iter()
+            .position(|p| p.side == side && p.option == option);
+        match (index, enabling) {
+            // Still waiting on the same request
+            (Some(i), Some(enabling)) if self.pending[i].enabling == enabling => {}
+            (Some(i), _) => {
+                self.pending.swap_remove(i);
+                self.track(side, option);
+            }
+            (None, Some(enabling)) => self.pending.push(Pending {
+                side,
+                option,
+                enabling,
+                #[cfg(feature = "std")]
+                since: Instant::now(),
+                #[cfg(feature = "std")]
+                retried: false,
+            }),
+            (None, None) => {}
+        }
+    }
+
+    /// The states for one side
+    #[cfg(feature = "std")]
+    fn states_mut(&mut self, side: Side) -> &mut [OptionState; 256] {
+        match side {
+            Side::Local => &mut self.local,
+            Side::Remote => &mut self.remote,
+        }
+    }
+
+    /// Whether to agree when the peer asks to enable `option` on `side`
+    fn should_accept_option(&self, option: TelnetOption, side: Side) -> bool {
+        self.accept_policy.accepts(option, side)
     }
 
     /// Reset all option states to disabled
@@ -743,6 +985,46 @@ impl OptionNegotiator {
     pub fn reset(&mut self) {
         self.local = [OptionState::No; 256];
         self.remote = [OptionState::No; 256];
+        self.pending.clear();
+    }
+
+    /// Save the state of every option, for [`OptionNegotiator::restore`]
+    pub fn snapshot(&self) -> NegotiatorSnapshot {
+        let listed = |states: &[OptionState; 256]| {
+            (0..=u8::MAX)
+                .map(|byte| (byte, states[byte as usize]))
+                .filter(|&(_, state)| state != OptionState::No)
+                .collect()
+        };
+        NegotiatorSnapshot {
+            local: listed(&self.local),
+            remote: listed(&self.remote),
+            queue_enabled: self.queue_enabled,
+        }
+    }
+
+    /// Pick up where a saved negotiator left off
+    ///
+    /// Every option not in `snapshot` is off afterwards. Nothing is sent:
+    /// the peer is assumed to be the same one, still in the state it was.
+    /// Negotiations still pending start their wait afresh.
+    pub fn restore(&mut self, snapshot: &NegotiatorSnapshot) {
+        self.reset();
+        for &(byte, state) in &snapshot.local {
+            self.local[byte as usize] = state;
+            self.track(Side::Local, TelnetOption::new(byte));
+        }
+        for &(byte, state) in &snapshot.remote {
+            self.remote[byte as usize] = state;
+            self.track(Side::Remote, TelnetOption::new(byte));
+        }
+        self.queue_enabled = snapshot.queue_enabled;
+    }
+
+    /// A readable table of every option that isn't plainly off, on each
+    /// side, for logs and bug reports; see [`NegotiatorDump`]
+    pub fn dump(&self) -> NegotiatorDump<'_> {
+        NegotiatorDump { negotiator: self }
     }
 
     /// Get a summary of all currently enabled options
@@ -751,13 +1033,12 @@ impl OptionNegotiator {
         let mut remote_enabled = Vec::new();
 
         for i in 0..=255 {
-            if let Some(option) = TelnetOption::from_byte(i) {
-                if matches!(self.local[i as usize], OptionState::Yes) {
-                    local_enabled.push(option);
-                }
-                if matches!(self.remote[i as usize], OptionState::Yes) {
-                    remote_enabled.push(option);
-                }
+            let option = TelnetOption::new(i);
+            if matches!(self.local[i as usize], OptionState::Yes) {
+                local_enabled.push(option);
+            }
+            if matches!(self.remote[i as usize], OptionState::Yes) {
+                remote_enabled.push(option);
             }
         }
 
@@ -765,6 +1046,66 @@ impl OptionNegotiator {
     }
 }
 
+/// Decides whether to agree when the peer asks to enable an option
+///
+/// `side` is the side the option would be enabled on: `Side::Remote` for
+/// a WILL from the peer, `Side::Local` for a DO. Closures and plain
+/// functions taking `(TelnetOption, Side)` are policies already.
+pub trait OptionPolicy: Send + Sync {
+    /// Whether to agree to enable `option` on `side`
+    fn accepts(&self, option: TelnetOption, side: Side) -> bool;
+}
+
+impl<F> OptionPolicy for F
+where
+    F: Fn(TelnetOption, Side) -> bool + Send + Sync,
+{
+    fn accepts(&self, option: TelnetOption, side: Side) -> bool {
+        self(option, side)
+    }
+}
+
+impl fmt::Debug for dyn OptionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OptionPolicy")
+    }
+}
+
+/// A policy written as a plain function
+pub type AcceptPolicy = fn(TelnetOption, Side) -> bool;
+
+/// The options a negotiator agrees to unless given another policy
+///
+/// Common safe options are accepted and unknown ones refused.
+pub fn default_accept_policy(option: TelnetOption, side: Side) -> bool {
+    match option {
+        // Safe options that most telnet implementations support
+        TelnetOption::ECHO => true,
+        TelnetOption::SUPPRESS_GO_AHEAD => true,
+        TelnetOption::NAWS => true,
+        TelnetOption::TERMINAL_TYPE => true,
+        TelnetOption::BINARY => true,
+        TelnetOption::NEW_ENVIRON => true,
+        // We ask for the client's line speed but never report ours
+        TelnetOption::TERMINAL_SPEED => side == Side::Remote,
+        // Likewise we take the client's location and never send one
+        TelnetOption::SEND_LOCATION => side == Side::Remote,
+        TelnetOption::END_OF_RECORD => true,
+
+        // MUD/MUSH extensions - accept if we support them
+        TelnetOption::GMCP => true,
+        TelnetOption::MCCP2 => false, // Compression requires special handling
+        // We only ever send markup, and only with the MXP handlers built
+        TelnetOption::MXP => side == Side::Local && cfg!(feature = "options-mud"),
+        // Servers send sound triggers and clients play them; either way
+        // it takes the MSP handler
+        TelnetOption::MSP => cfg!(feature = "options-mud"),
+
+        // Reject unknown or complex options by default
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -816,6 +1157,102 @@ mod tests {
         assert!(!negotiator.is_enabled(Side::Remote, TelnetOption::LOGOUT));
     }
 
+    #[test]
+    fn test_accept_policy_override() {
+        fn local_only(_: TelnetOption, side: Side) -> bool {
+            side == Side::Local
+        }
+
+        let mut negotiator = OptionNegotiator::new();
+        negotiator.set_accept_policy(local_only);
+
+        // LOGOUT is refused by default but allowed by the policy
+        let result = negotiator.handle_do(TelnetOption::LOGOUT);
+        assert!(result.enabled);
+        assert!(negotiator.is_enabled(Side::Local, TelnetOption::LOGOUT));
+
+        let result = negotiator.handle_will(TelnetOption::ECHO);
+        assert!(!result.enabled);
+        assert_eq!(
+            result.response,
+            Some(TelnetSequence::Negotiation {
+                command: TelnetCommand::DONT,
+                option: TelnetOption::ECHO
+            })
+        );
+    }
+
+    #[test]
+    fn test_closure_policy_decided_at_runtime() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let allow_echo = Arc::new(AtomicBool::new(false));
+        let mut negotiator = OptionNegotiator::new();
+        negotiator.set_accept_policy({
+            let allow_echo = Arc::clone(&allow_echo);
+            move |option, _| option == TelnetOption::ECHO && allow_echo.load(Ordering::Relaxed)
+        });
+
+        assert!(!negotiator.handle_will(TelnetOption::ECHO).enabled);
+        allow_echo.store(true, Ordering::Relaxed);
+        assert!(negotiator.handle_will(TelnetOption::ECHO).enabled);
+    }
+
+    #[test]
+    fn test_unnamed_option_negotiation() {
+        let private = TelnetOption::new(120);
+        let reply = |command| {
+            Some(TelnetSequence::Negotiation {
+                command,
+                option: private,
+            })
+        };
+
+        // Refused by default
+        let mut negotiator = OptionNegotiator::new();
+        let result = negotiator.handle_do(private);
+        assert_eq!(result.response, reply(TelnetCommand::WONT));
+        assert!(!negotiator.is_enabled(Side::Local, private));
+
+        negotiator.set_accept_policy(move |option, _| option == private);
+        let result = negotiator.handle_will(private);
+        assert_eq!(result.response, reply(TelnetCommand::DO));
+        assert!(negotiator.is_enabled(Side::Remote, private));
+        assert_eq!(negotiator.get_enabled_options().1, vec![private]);
+
+        // Asked again once on: no reply, so no loop
+        assert_eq!(negotiator.handle_will(private).response, None);
+
+        let result = negotiator.handle_wont(private);
+        assert_eq!(result.response, reply(TelnetCommand::DONT));
+        assert!(!negotiator.is_enabled(Side::Remote, private));
+    }
+
+    #[cfg(feature = "options-mud")]
+    #[test]
+    fn test_msp_accepted_either_way() {
+        let mut negotiator = OptionNegotiator::new();
+        negotiator.handle_do(TelnetOption::MSP);
+        assert!(negotiator.is_enabled(Side::Local, TelnetOption::MSP));
+        negotiator.handle_will(TelnetOption::MSP);
+        assert!(negotiator.is_enabled(Side::Remote, TelnetOption::MSP));
+    }
+
+    #[cfg(feature = "options-mud")]
+    #[test]
+    fn test_mxp_only_sent_by_us() {
+        let mut negotiator = OptionNegotiator::new();
+
+        // We'll send markup when asked, but never parse the client's
+        let result = negotiator.handle_do(TelnetOption::MXP);
+        assert!(result.enabled);
+        assert!(negotiator.is_enabled(Side::Local, TelnetOption::MXP));
+
+        let result = negotiator.handle_will(TelnetOption::MXP);
+        assert!(!result.enabled);
+        assert!(!negotiator.is_enabled(Side::Remote, TelnetOption::MXP));
+    }
+
     #[test]
     fn test_request_enable() {
         let mut negotiator = OptionNegotiator::new();
@@ -923,4 +1360,111 @@ mod tests {
         assert!(result2.error.is_some());
         assert!(result2.error.unwrap().contains("DONT answered by WILL"));
     }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let mut negotiator = OptionNegotiator::new();
+        negotiator.handle_do(TelnetOption::ECHO);
+        negotiator.handle_will(TelnetOption::NAWS);
+        negotiator.request_enable(Side::Remote, TelnetOption::TERMINAL_TYPE);
+        negotiator.set_queue_enabled(false);
+
+        let snapshot = negotiator.snapshot();
+        assert_eq!(snapshot.local, [(1, OptionState::Yes)]);
+        assert_eq!(snapshot.remote.len(), 2);
+
+        let mut restored = OptionNegotiator::new();
+        restored.handle_will(TelnetOption::BINARY);
+        restored.restore(&snapshot);
+        assert!(restored.is_enabled(Side::Local, TelnetOption::ECHO));
+        assert!(restored.is_enabled(Side::Remote, TelnetOption::NAWS));
+        assert!(!restored.is_enabled(Side::Remote, TelnetOption::BINARY));
+        assert_eq!(restored.snapshot(), snapshot);
+
+        // The outstanding request is answered as if nothing happened
+        let result = restored.handle_will(TelnetOption::TERMINAL_TYPE);
+        assert!(result.enabled);
+        assert_eq!(result.response, None);
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut negotiator = OptionNegotiator::new();
+        assert_eq!(negotiator.dump().to_string(), "no options negotiated");
+
+        negotiator.handle_do(TelnetOption::ECHO);
+        negotiator.handle_will(TelnetOption::SUPPRESS_GO_AHEAD);
+        negotiator.handle_do(TelnetOption::SUPPRESS_GO_AHEAD);
+        negotiator.request_enable(Side::Remote, TelnetOption::NAWS);
+        negotiator.request_disable(Side::Remote, TelnetOption::NAWS);
+        negotiator.request_enable(Side::Local, TelnetOption::new(123));
+        assert_eq!(
+            negotiator.dump().to_string(),
+            "option                 local    remote\n\
+             ECHO (1)               YES      NO\n\
+             SUPPRESS_GO_AHEAD (3)  YES      YES\n\
+             NAWS (31)              NO       WANTYES OPPOSITE\n\
+             123                    WANTYES  NO"
+        );
+
+        negotiator.reset();
+        negotiator.set_queue_enabled(false);
+        assert_eq!(
+            negotiator.dump().to_string(),
+            "no options negotiated\nRFC 1143 queue: off"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_expire_stale() {
+        let mut negotiator = OptionNegotiator::new();
+        negotiator.request_enable(Side::Remote, TelnetOption::NAWS);
+        negotiator.request_enable(Side::Remote, TelnetOption::ECHO);
+        negotiator.handle_will(TelnetOption::ECHO);
+        assert!(negotiator.expire_stale(Duration::from_secs(60)).is_empty());
+
+        // Only the unanswered DO NAWS is given up
+        let stale = negotiator.expire_stale(Duration::ZERO);
+        assert_eq!(
+            stale,
+            [StaleNegotiation {
+                side: Side::Remote,
+                option: TelnetOption::NAWS,
+                state: OptionState::WantYes {
+                    queue: QueueState::Empty
+                },
+                retry: None,
+            }]
+        );
+        assert_eq!(
+            negotiator.get_state(Side::Remote, TelnetOption::NAWS),
+            OptionState::No
+        );
+        assert!(negotiator.is_enabled(Side::Remote, TelnetOption::ECHO));
+        assert!(negotiator.expire_stale(Duration::ZERO).is_empty());
+
+        // With retries, the first time out asks again and the second gives up
+        negotiator.set_retry_stale(true);
+        negotiator.request_disable(Side::Remote, TelnetOption::ECHO);
+        let stale = negotiator.expire_stale(Duration::ZERO);
+        assert_eq!(
+            stale[0].retry,
+            Some(TelnetSequence::Negotiation {
+                command: TelnetCommand::DONT,
+                option: TelnetOption::ECHO,
+            })
+        );
+        assert!(matches!(
+            negotiator.get_state(Side::Remote, TelnetOption::ECHO),
+            OptionState::WantNo { .. }
+        ));
+        let stale = negotiator.expire_stale(Duration::ZERO);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].retry, None);
+        assert_eq!(
+            negotiator.get_state(Side::Remote, TelnetOption::ECHO),
+            OptionState::No
+        );
+    }
 }
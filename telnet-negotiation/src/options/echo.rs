@@ -58,11 +58,8 @@ impl EchoOption {
     /// * `is_server` - True if this is the server side, false for client
     pub fn new(is_server: bool) -> Self {
         Self {
-            state: if is_server {
-                EchoState::LocalEcho // Default: client echoes locally
-            } else {
-                EchoState::LocalEcho // Default: client echoes locally
-            },
+            // Default on both sides: client echoes locally
+            state: EchoState::LocalEcho,
             is_server,
         }
     }
@@ -165,11 +162,33 @@ impl TelnetOptionHandler for EchoOption {
     }
 
     fn reset(&mut self) {
-        self.state = if self.is_server {
-            EchoState::LocalEcho // Server default: client echoes
-        } else {
-            EchoState::LocalEcho // Client default: local echo
+        // Server default: client echoes; client default: local echo
+        self.state = EchoState::LocalEcho;
+    }
+
+    /// One byte: 0 for local echo, 1 for remote, 2 for none
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        let state = match self.state {
+            EchoState::LocalEcho => 0,
+            EchoState::RemoteEcho => 1,
+            EchoState::NoEcho => 2,
+        };
+        Some(vec![state])
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), OptionError> {
+        self.state = match state {
+            [0] => EchoState::LocalEcho,
+            [1] => EchoState::RemoteEcho,
+            [2] => EchoState::NoEcho,
+            _ => {
+                return Err(OptionError::InvalidData(format!(
+                    "bad echo snapshot {:?}",
+                    state
+                )));
+            }
         };
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -0,0 +1,381 @@
+//! # LINEMODE Option Implementation (RFC 1184)
+//!
+//! LINEMODE lets the client edit a line locally and send it whole, instead
+//! of sending every keystroke for the server to echo. The server decides
+//! which mode the client runs in; this handler implements the server side.
+//!
+//! ## Negotiation
+//!
+//! The server sends `DO LINEMODE`; a client that agrees answers
+//! `WILL LINEMODE`. From then on the server sets the mode with:
+//!
+//! ```text
+//! IAC SB LINEMODE MODE <mask> IAC SE
+//! ```
+//!
+//! and the client confirms with the same mask plus `MODE_ACK`. A client
+//! that can't honour the mask answers with the mode it can do instead.
+//!
+//! ## Mode Bits
+//!
+//! - `EDIT`: the client edits lines locally and sends them when complete
+//! - `TRAPSIG`: the client turns interrupt keys into `IP`, `BRK`, ...
+//! - `SOFT_TAB`, `LIT_ECHO`: display hints for local editing
+//!
+//! ## Forward Mask
+//!
+//! While editing locally, the client sends the line early when it sees a
+//! character in the forward mask:
+//!
+//! ```text
+//! IAC SB LINEMODE DO FORWARDMASK <mask0> ... <mask31> IAC SE
+//! ```
+//!
+//! Bit 7 of `mask0` is character 0, bit 0 of `mask31` is character 255.
+//! The client answers `WILL FORWARDMASK` or `WONT FORWARDMASK`.
+//!
+//! SLC (special line character) lists from the client are accepted and
+//! left alone, so the client keeps its own editing keys.
+
+use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::protocol::{TelnetCommand, TelnetOption};
+
+/// Sub-negotiation command: set or confirm the mode
+pub const LM_MODE: u8 = 1;
+/// Sub-negotiation command: characters that end a line early
+pub const LM_FORWARDMASK: u8 = 2;
+/// Sub-negotiation command: special line characters
+pub const LM_SLC: u8 = 3;
+
+/// Mode bit: the client edits lines locally
+pub const MODE_EDIT: u8 = 0x01;
+/// Mode bit: the client turns signal keys into telnet commands
+pub const MODE_TRAPSIG: u8 = 0x02;
+/// Mode bit: set by the client to confirm a mode
+pub const MODE_ACK: u8 = 0x04;
+/// Mode bit: the client expands tabs to spaces
+pub const MODE_SOFT_TAB: u8 = 0x08;
+/// Mode bit: the client echoes non-printable characters literally
+pub const MODE_LIT_ECHO: u8 = 0x10;
+
+/// Longest forward mask, one bit for each of the 256 byte values
+pub const FORWARDMASK_LEN: usize = 32;
+
+/// Server-side LINEMODE handler
+#[derive(Debug, Clone, Default)]
+pub struct LinemodeOption {
+    /// Mode the client last reported, without `MODE_ACK`
+    mode: Option<u8>,
+    /// Mode we asked for and the client hasn't confirmed yet
+    pending_mode: Option<u8>,
+    /// The client's answer to our forward mask, if it has answered
+    forward_mask_accepted: Option<bool>,
+}
+
+impl LinemodeOption {
+    /// Create a new LINEMODE option handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mode the client reported, once it has answered
+    pub fn mode(&self) -> Option<u8> {
+        self.mode
+    }
+
+    /// Mode waiting to be confirmed by the client
+    pub fn pending_mode(&self) -> Option<u8> {
+        self.pending_mode
+    }
+
+    /// Whether the client edits lines locally
+    pub fn is_local_editing(&self) -> bool {
+        self.mode.is_some_and(|mode| mode & MODE_EDIT != 0)
+    }
+
+    /// Whether the client sends signal keys as telnet commands
+    pub fn traps_signals(&self) -> bool {
+        self.mode.is_some_and(|mode| mode & MODE_TRAPSIG != 0)
+    }
+
+    /// Whether the client agreed to our forward mask (`None` until it answers)
+    pub fn forward_mask_accepted(&self) -> Option<bool> {
+        self.forward_mask_accepted
+    }
+
+    /// Remember `mode` as wanted and return the `MODE` sub-negotiation data
+    ///
+    /// `MODE_ACK` is the client's to set and is stripped here.
+    pub fn request_mode(&mut self, mode: u8) -> Vec<u8> {
+        let mode = mode & !MODE_ACK;
+        self.pending_mode = Some(mode);
+        vec![LM_MODE, mode]
+    }
+
+    /// Sub-negotiation data for `DO FORWARDMASK`
+    ///
+    /// Masks longer than [`FORWARDMASK_LEN`] are rejected; shorter ones are
+    /// treated as zero-filled by the client. Like any payload, a mask byte
+    /// of 255 is left for [`TelnetSequence::to_bytes`] to double.
+    ///
+    /// [`TelnetSequence::to_bytes`]: crate::TelnetSequence::to_bytes
+    pub fn encode_forward_mask(mask: &[u8]) -> Result<Vec<u8>, OptionError> {
+        if mask.len() > FORWARDMASK_LEN {
+            return Err(OptionError::InvalidData(format!(
+                "forward mask is at most {} bytes, got {}",
+                FORWARDMASK_LEN,
+                mask.len()
+            )));
+        }
+
+        let mut data = vec![TelnetCommand::DO.to_byte(), LM_FORWARDMASK];
+        data.extend_from_slice(mask);
+        Ok(data)
+    }
+
+    /// Build a forward mask that ends a line on any of `characters`
+    pub fn forward_mask_for(characters: &[u8]) -> Vec<u8> {
+        let mut mask = vec![0u8; FORWARDMASK_LEN];
+        for &c in characters {
+            mask[(c / 8) as usize] |= 0x80 >> (c % 8);
+        }
+        // Trailing zero bytes may be left off
+        while mask.last() == Some(&0) {
+            mask.pop();
+        }
+        mask
+    }
+
+    /// Record the client's `MODE`
+    ///
+    /// With `MODE_ACK` the client confirms the mode it is now in; without
+    /// it, the client is telling us which mode it can do instead. Either
+    /// way that is the mode in effect, and the server never replies, so a
+    /// disagreement can't turn into a loop.
+    fn handle_mode(&mut self, mask: u8) {
+        let mode = mask & !MODE_ACK;
+        if mask & MODE_ACK != 0 && self.pending_mode == Some(mode) {
+            self.pending_mode = None;
+        }
+        self.mode = Some(mode);
+    }
+}
+
+impl TelnetOptionHandler for LinemodeOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::LINEMODE
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        let Some(&command) = data.first() else {
+            return Err(OptionError::InvalidData(
+                "LINEMODE sub-negotiation is empty".to_string(),
+            ));
+        };
+
+        match command {
+            LM_MODE => {
+                let Some(&mask) = data.get(1) else {
+                    return Err(OptionError::InvalidData(
+                        "LINEMODE MODE has no mask".to_string(),
+                    ));
+                };
+                self.handle_mode(mask);
+                Ok(vec![])
+            }
+            LM_SLC => Ok(vec![]),
+            // WILL/WONT/DO/DONT followed by FORWARDMASK (or SLC)
+            251..=254 => match (TelnetCommand::from_byte(command), data.get(1)) {
+                (Some(TelnetCommand::WILL), Some(&LM_FORWARDMASK)) => {
+                    self.forward_mask_accepted = Some(true);
+                    Ok(vec![])
+                }
+                (Some(TelnetCommand::WONT), Some(&LM_FORWARDMASK)) => {
+                    self.forward_mask_accepted = Some(false);
+                    Ok(vec![])
+                }
+                (_, Some(&LM_SLC)) => Ok(vec![]),
+                // DO/DONT FORWARDMASK only ever go from server to client
+                _ => Err(OptionError::InvalidData(format!(
+                    "unexpected LINEMODE sub-negotiation {:?}",
+                    data
+                ))),
+            },
+            other => Err(OptionError::UnsupportedCommand(other)),
+        }
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        // LINEMODE has no SEND/IS; use request_mode and encode_forward_mask
+        Err(OptionError::UnsupportedCommand(command as u8))
+    }
+
+    fn is_active(&self) -> bool {
+        self.mode.is_some()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::TelnetSequence;
+
+    #[test]
+    fn test_request_mode_strips_ack() {
+        let mut linemode = LinemodeOption::new();
+        let data = linemode.request_mode(MODE_EDIT | MODE_TRAPSIG | MODE_ACK);
+
+        assert_eq!(data, vec![LM_MODE, MODE_EDIT | MODE_TRAPSIG]);
+        assert_eq!(linemode.pending_mode(), Some(MODE_EDIT | MODE_TRAPSIG));
+        assert!(!linemode.is_active());
+        assert!(!linemode.is_local_editing());
+    }
+
+    #[test]
+    fn test_client_acknowledges_mode() {
+        let mut linemode = LinemodeOption::new();
+        linemode.request_mode(MODE_EDIT | MODE_TRAPSIG);
+
+        let response = linemode
+            .handle_subnegotiation(&[LM_MODE, MODE_EDIT | MODE_TRAPSIG | MODE_ACK])
+            .unwrap();
+
+        assert!(response.is_empty());
+        assert_eq!(linemode.mode(), Some(MODE_EDIT | MODE_TRAPSIG));
+        assert_eq!(linemode.pending_mode(), None);
+        assert!(linemode.is_local_editing());
+        assert!(linemode.traps_signals());
+    }
+
+    #[test]
+    fn test_client_counter_proposes_mode() {
+        let mut linemode = LinemodeOption::new();
+        linemode.request_mode(MODE_EDIT | MODE_TRAPSIG);
+
+        // Can't edit locally, but will trap signals
+        linemode
+            .handle_subnegotiation(&[LM_MODE, MODE_TRAPSIG])
+            .unwrap();
+
+        assert_eq!(linemode.mode(), Some(MODE_TRAPSIG));
+        assert_eq!(linemode.pending_mode(), Some(MODE_EDIT | MODE_TRAPSIG));
+        assert!(!linemode.is_local_editing());
+    }
+
+    #[test]
+    fn test_forward_mask_encoding() {
+        let mask = LinemodeOption::forward_mask_for(b"\r\n");
+        // CR is 13 and LF is 10, both in the second byte
+        assert_eq!(mask, vec![0x00, 0x24]);
+
+        let data = LinemodeOption::encode_forward_mask(&mask).unwrap();
+        assert_eq!(data, vec![253, LM_FORWARDMASK, 0x00, 0x24]);
+    }
+
+    #[test]
+    fn test_forward_mask_iac_is_doubled_once() {
+        let data = LinemodeOption::encode_forward_mask(&[0xFF, 0x01]).unwrap();
+        assert_eq!(data, vec![253, LM_FORWARDMASK, 0xFF, 0x01]);
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::LINEMODE,
+            data,
+        };
+        assert_eq!(
+            sequence.to_bytes(),
+            [
+                255,
+                250,
+                34,
+                253,
+                LM_FORWARDMASK,
+                0xFF,
+                0xFF,
+                0x01,
+                255,
+                240
+            ]
+        );
+
+        assert!(LinemodeOption::encode_forward_mask(&[0; 33]).is_err());
+    }
+
+    #[test]
+    fn test_forward_mask_answer() {
+        let mut linemode = LinemodeOption::new();
+        assert_eq!(linemode.forward_mask_accepted(), None);
+
+        linemode
+            .handle_subnegotiation(&[251, LM_FORWARDMASK])
+            .unwrap();
+        assert_eq!(linemode.forward_mask_accepted(), Some(true));
+
+        linemode
+            .handle_subnegotiation(&[252, LM_FORWARDMASK])
+            .unwrap();
+        assert_eq!(linemode.forward_mask_accepted(), Some(false));
+    }
+
+    #[test]
+    fn test_slc_is_ignored() {
+        let mut linemode = LinemodeOption::new();
+        // SLC_IP, SLC_DEFAULT, ^C
+        assert!(
+            linemode
+                .handle_subnegotiation(&[LM_SLC, 3, 3, 3])
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            linemode
+                .handle_subnegotiation(&[251, LM_SLC])
+                .unwrap()
+                .is_empty()
+        );
+        assert!(!linemode.is_active());
+    }
+
+    #[test]
+    fn test_invalid_subnegotiation() {
+        let mut linemode = LinemodeOption::new();
+        assert!(linemode.handle_subnegotiation(&[]).is_err());
+        assert!(linemode.handle_subnegotiation(&[LM_MODE]).is_err());
+        assert!(
+            linemode
+                .handle_subnegotiation(&[253, LM_FORWARDMASK])
+                .is_err()
+        );
+        assert_eq!(
+            linemode.handle_subnegotiation(&[9]),
+            Err(OptionError::UnsupportedCommand(9))
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut linemode = LinemodeOption::new();
+        linemode.request_mode(MODE_EDIT);
+        linemode
+            .handle_subnegotiation(&[LM_MODE, MODE_EDIT | MODE_ACK])
+            .unwrap();
+
+        linemode.reset();
+        assert_eq!(linemode.mode(), None);
+        assert_eq!(linemode.pending_mode(), None);
+        assert_eq!(linemode.option_code(), TelnetOption::LINEMODE);
+    }
+}
@@ -18,6 +18,32 @@
 //! Provides dynamic terminal window size information.
 //! Enables responsive layouts that adapt to client terminal dimensions.
 //!
+//! ### LINEMODE (RFC 1184)
+//! Lets the client edit lines locally and send them whole, under the
+//! server's control.
+//!
+//! ### TERMINAL-SPEED (RFC 1079)
+//! Asks the client for its line speed, so output can be paced for modems.
+//!
+//! ### SEND-LOCATION (RFC 779)
+//! Takes the free-text location a client volunteers, such as a city.
+//!
+//! ### NEW-ENVIRON (RFC 1572)
+//! Asks the client for environment variables such as `USER` and `DISPLAY`.
+//!
+//! ### START_TLS (draft-altman-telnet-starttls)
+//! Upgrades the connection to TLS in place, on the same port.
+//! The TLS library itself is supplied by the application.
+//!
+//! ### MSSP - MUD Server Status Protocol (`options-mud`)
+//! Advertises server facts such as name and player count to crawlers.
+//!
+//! ### MSP - MUD Sound Protocol (`options-mud`)
+//! Builds and parses the `!!SOUND(...)` and `!!MUSIC(...)` cues MSP clients play.
+//!
+//! ### MXP - MUD eXtension Protocol (`options-mud`)
+//! Turns on client-side markup and wraps output in escaped MXP tags.
+//!
 //! ## Architecture
 //!
 //! Each option implementation provides:
@@ -26,23 +52,97 @@
 //! - RFC-compliant message formatting
 //! - Integration with the core negotiation system
 
+#[cfg(feature = "options-core")]
 pub mod echo;
+#[cfg(feature = "options-core")]
+pub mod linemode;
+#[cfg(feature = "options-mud")]
+pub mod msp;
+#[cfg(feature = "options-mud")]
+pub mod mssp;
+#[cfg(feature = "options-mud")]
+pub mod mxp;
+#[cfg(feature = "options-core")]
 pub mod naws;
+#[cfg(feature = "options-core")]
+pub mod new_environ;
+#[cfg(feature = "options-core")]
+pub mod send_location;
+#[cfg(feature = "options-core")]
+pub mod start_tls;
+#[cfg(feature = "options-core")]
+pub mod terminal_speed;
+#[cfg(feature = "options-core")]
 pub mod terminal_type;
 
 // Re-export main types for convenience
+#[cfg(feature = "options-core")]
 pub use echo::{EchoOption, EchoState};
+#[cfg(feature = "options-core")]
+pub use linemode::LinemodeOption;
+#[cfg(feature = "options-mud")]
+pub use msp::{MspKind, MspOption, MspTrigger, split_triggers};
+#[cfg(feature = "options-mud")]
+pub use mssp::MsspOption;
+#[cfg(feature = "options-mud")]
+pub use mxp::{MxpMode, MxpOption};
+#[cfg(feature = "options-core")]
 pub use naws::{NawsOption, WindowSize};
+#[cfg(feature = "options-core")]
+pub use new_environ::{EnvironKind, Environment, NewEnvironOption};
+#[cfg(feature = "options-core")]
+pub use send_location::SendLocationOption;
+#[cfg(feature = "options-core")]
+pub use start_tls::{StartTlsOption, StartTlsState, TlsTransport, TlsUpgrader};
+#[cfg(feature = "options-core")]
+pub use terminal_speed::{TerminalSpeed, TerminalSpeedOption};
+#[cfg(feature = "options-core")]
 pub use terminal_type::{TerminalInfo, TerminalTypeOption};
 
+#[cfg(feature = "negotiation")]
+use crate::negotiation::Side;
+
 /// Common trait for telnet option implementations
-pub trait TelnetOptionHandler {
+///
+/// Handlers are `Send` so a stream can move to another thread, or be split
+/// across two.
+pub trait TelnetOptionHandler: Send {
     /// The telnet option code this handler manages
     fn option_code(&self) -> crate::TelnetOption;
 
     /// Handle incoming sub-negotiation data
     fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError>;
 
+    /// Take this option's payloads in pieces of this many bytes as they
+    /// arrive, rather than whole
+    ///
+    /// Asked once, when the handler is registered with a `TelnetStream`.
+    /// `Some` suits options whose payloads can be too big to hold, such as
+    /// bulk GMCP data: they go to
+    /// [`TelnetOptionHandler::handle_subnegotiation_chunk`] instead of
+    /// `handle_subnegotiation`, and the parser's sub-negotiation limit no
+    /// longer applies to them. The default, `None`, takes them whole.
+    fn subnegotiation_chunk_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Handle a piece of a payload starting `offset` bytes in; `last` is
+    /// set on the piece that ends it
+    ///
+    /// Only called when [`TelnetOptionHandler::subnegotiation_chunk_size`]
+    /// is set. A reply is sent as for `handle_subnegotiation`. A payload
+    /// cut off by a malformed sequence never gets its last piece, so a
+    /// piece at offset 0 always starts a new one. The default passes each
+    /// piece to `handle_subnegotiation` as it is.
+    fn handle_subnegotiation_chunk(
+        &mut self,
+        data: &[u8],
+        _offset: usize,
+        _last: bool,
+    ) -> Result<Vec<u8>, OptionError> {
+        self.handle_subnegotiation(data)
+    }
+
     /// Generate sub-negotiation data to send
     fn generate_subnegotiation(
         &self,
@@ -55,6 +155,37 @@ pub trait TelnetOptionHandler {
     /// Reset the option to initial state
     fn reset(&mut self);
 
+    /// What the handler has learned about the peer, saved as bytes for
+    /// [`TelnetOptionHandler::restore`]
+    ///
+    /// Used to carry a connection across a process restart. The format is
+    /// the handler's own. `None` (the default) means nothing worth keeping.
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Take back state saved by [`TelnetOptionHandler::snapshot`]
+    ///
+    /// The default ignores it, matching the default `snapshot`.
+    fn restore(&mut self, _state: &[u8]) -> Result<(), OptionError> {
+        Ok(())
+    }
+
+    /// Called once `side` of this option has come on
+    ///
+    /// [`Side::Remote`] means the peer now performs the option, so this is
+    /// the moment to ask it for something, such as a TERMINAL-TYPE SEND.
+    /// Returns sub-negotiation data to send right away; the default sends
+    /// nothing.
+    #[cfg(feature = "negotiation")]
+    fn on_enabled(&mut self, _side: Side) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Called once `side` of this option has gone off
+    #[cfg(feature = "negotiation")]
+    fn on_disabled(&mut self, _side: Side) {}
+
     /// Get a reference to Any for downcasting
     fn as_any(&self) -> &dyn std::any::Any;
 
@@ -63,12 +194,15 @@ pub trait TelnetOptionHandler {
 }
 
 /// Common sub-negotiation commands used across options
+///
+/// The byte values are those TERMINAL-TYPE (RFC 1091), TERMINAL-SPEED
+/// (RFC 1079) and NEW-ENVIRON (RFC 1572) put on the wire.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SubNegotiationCommand {
-    /// Send current value/state (0)
-    Send = 0,
-    /// Provide current value/state (1)
-    Is = 1,
+    /// Provide current value/state (0)
+    Is = 0,
+    /// Ask for the current value/state (1)
+    Send = 1,
 }
 
 /// Errors that can occur during option processing
@@ -97,14 +231,66 @@ impl std::fmt::Display for OptionError {
 
 impl std::error::Error for OptionError {}
 
+/// Option handlers indexed by option byte
+///
+/// A lookup is a single array index, with no hashing on the way to each
+/// sub-negotiation, and any byte can have a handler, named or not.
+#[cfg(feature = "stream")]
+pub(crate) struct HandlerTable {
+    handlers: [Option<Box<dyn TelnetOptionHandler>>; 256],
+}
+
+#[cfg(feature = "stream")]
+impl HandlerTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            handlers: std::array::from_fn(|_| None),
+        }
+    }
+
+    /// Put `handler` in charge of option `code`, returning the one it replaces
+    pub(crate) fn insert(
+        &mut self,
+        code: u8,
+        handler: Box<dyn TelnetOptionHandler>,
+    ) -> Option<Box<dyn TelnetOptionHandler>> {
+        self.handlers[code as usize].replace(handler)
+    }
+
+    pub(crate) fn get(&self, code: u8) -> Option<&dyn TelnetOptionHandler> {
+        self.handlers[code as usize].as_deref()
+    }
+
+    pub(crate) fn get_mut(&mut self, code: u8) -> Option<&mut Box<dyn TelnetOptionHandler>> {
+        self.handlers[code as usize].as_mut()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_subnegotiation_commands() {
-        assert_eq!(SubNegotiationCommand::Send as u8, 0);
-        assert_eq!(SubNegotiationCommand::Is as u8, 1);
+        assert_eq!(SubNegotiationCommand::Is as u8, 0);
+        assert_eq!(SubNegotiationCommand::Send as u8, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_handler_table_indexed_by_byte() {
+        let mut table = HandlerTable::new();
+        assert!(table.get(31).is_none());
+
+        assert!(table.insert(31, Box::new(NawsOption::new())).is_none());
+        assert!(table.insert(200, Box::new(EchoOption::new(true))).is_none());
+        assert_eq!(
+            table.get(31).map(|h| h.option_code()),
+            Some(crate::TelnetOption::NAWS)
+        );
+        assert!(table.get_mut(200).is_some());
+        assert!(table.insert(31, Box::new(NawsOption::new())).is_some());
+        assert!(table.get(32).is_none());
     }
 
     #[test]
@@ -0,0 +1,476 @@
+//! # MSP Option Implementation (MUD Sound Protocol)
+//!
+//! MSP lets a server ask the client to play sound effects and music. The
+//! telnet option (90) only says whether the client understands it; the
+//! cues themselves travel in-band, as triggers in ordinary output:
+//!
+//! ```text
+//! !!SOUND(weather/thunder.wav V=80 P=60 T=weather)
+//! !!MUSIC(tavern.mid L=-1 C=1)
+//! !!MUSIC(Off)
+//! ```
+//!
+//! A trigger names a file (relative to the client's sound directory, or to
+//! the `U=` download URL) followed by optional `KEY=value` parameters:
+//!
+//! | Key | Meaning                                  | Default | Applies to |
+//! |-----|------------------------------------------|---------|------------|
+//! | `V` | Volume, 0 to 100                         | 100     | both       |
+//! | `L` | Times to play, -1 to loop forever        | 1       | both       |
+//! | `P` | Priority, 0 to 100; higher interrupts    | 50      | sound      |
+//! | `C` | 1 keeps the track going if it's re-sent  | 1       | music      |
+//! | `T` | Category, such as `combat` or `weather`  | none    | both       |
+//! | `U` | URL to download the file from            | none    | both       |
+//!
+//! The file name `Off` stops whatever is playing.
+//!
+//! Servers build triggers with [`MspTrigger`] and send them through
+//! [`MspOption::cue`], which yields nothing for clients that haven't
+//! agreed to MSP. Clients (or proxies) pull triggers back out of received
+//! lines with [`split_triggers`].
+
+use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::protocol::TelnetOption;
+
+/// Volume a trigger plays at unless it says otherwise
+pub const DEFAULT_VOLUME: u8 = 100;
+/// Priority a sound has unless it says otherwise
+pub const DEFAULT_PRIORITY: u8 = 50;
+
+/// Whether a trigger is a sound effect or background music
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MspKind {
+    /// `!!SOUND(...)`: a sound effect, several may overlap
+    Sound,
+    /// `!!MUSIC(...)`: background music, one track at a time
+    Music,
+}
+
+impl MspKind {
+    /// The trigger keyword, `SOUND` or `MUSIC`
+    pub fn keyword(self) -> &'static str {
+        match self {
+            MspKind::Sound => "SOUND",
+            MspKind::Music => "MUSIC",
+        }
+    }
+}
+
+/// One sound or music cue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MspTrigger {
+    pub kind: MspKind,
+    /// File to play, or `None` to stop playing (`Off`)
+    pub file: Option<String>,
+    /// 0 to 100
+    pub volume: u8,
+    /// Times to play, -1 for forever
+    pub repeats: i32,
+    /// 0 to 100; a higher priority sound cuts off a lower one (sounds only)
+    pub priority: u8,
+    /// Keep playing if this track is already on, rather than restart it
+    /// (music only)
+    pub continue_playing: bool,
+    /// Category the client can use to filter cues (`T=`)
+    pub category: Option<String>,
+    /// Where to download the file if the client doesn't have it (`U=`)
+    pub url: Option<String>,
+}
+
+impl MspTrigger {
+    fn new(kind: MspKind, file: Option<String>) -> Self {
+        Self {
+            kind,
+            file,
+            volume: DEFAULT_VOLUME,
+            repeats: 1,
+            priority: DEFAULT_PRIORITY,
+            continue_playing: true,
+            category: None,
+            url: None,
+        }
+    }
+
+    /// Play a sound effect
+    pub fn sound(file: &str) -> Self {
+        Self::new(MspKind::Sound, Some(file.to_string()))
+    }
+
+    /// Play background music
+    pub fn music(file: &str) -> Self {
+        Self::new(MspKind::Music, Some(file.to_string()))
+    }
+
+    /// Stop sounds or music (`Off`)
+    pub fn stop(kind: MspKind) -> Self {
+        Self::new(kind, None)
+    }
+
+    /// Set the volume, capped at 100
+    pub fn with_volume(mut self, volume: u8) -> Self {
+        self.volume = volume.min(100);
+        self
+    }
+
+    /// Set how many times to play, -1 for forever
+    pub fn with_repeats(mut self, repeats: i32) -> Self {
+        self.repeats = repeats.max(-1);
+        self
+    }
+
+    /// Set the priority, capped at 100
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority.min(100);
+        self
+    }
+
+    /// Restart the track even if it's already playing
+    pub fn restart(mut self) -> Self {
+        self.continue_playing = false;
+        self
+    }
+
+    pub fn with_category(mut self, category: &str) -> Self {
+        self.category = Some(category.to_string());
+        self
+    }
+
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Parse one trigger, such as `!!SOUND(hit.wav V=50)`
+    ///
+    /// Keywords and keys are matched without regard to case. Unknown keys
+    /// are ignored, so triggers from newer protocol versions still play.
+    pub fn parse(text: &str) -> Result<Self, OptionError> {
+        let invalid = || OptionError::InvalidData(format!("Not an MSP trigger: {:?}", text));
+
+        let text = text.trim();
+        let body = text.strip_prefix("!!").ok_or_else(invalid)?;
+        let (keyword, rest) = body.split_once('(').ok_or_else(invalid)?;
+        let kind = if keyword.eq_ignore_ascii_case("SOUND") {
+            MspKind::Sound
+        } else if keyword.eq_ignore_ascii_case("MUSIC") {
+            MspKind::Music
+        } else {
+            return Err(invalid());
+        };
+        let args = rest.strip_suffix(')').ok_or_else(invalid)?;
+
+        let mut words = args.split_whitespace();
+        let file = words.next().ok_or_else(invalid)?;
+        let file = (!file.eq_ignore_ascii_case("Off")).then(|| file.to_string());
+        let mut trigger = Self::new(kind, file);
+
+        for word in words {
+            let (key, value) = word.split_once('=').ok_or_else(invalid)?;
+            let number = |max: i32| {
+                value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|n| (0..=max).contains(n))
+                    .ok_or_else(|| {
+                        OptionError::InvalidData(format!("Bad MSP value {:?} in {:?}", word, text))
+                    })
+            };
+            match key.to_ascii_uppercase().as_str() {
+                "V" => trigger.volume = number(100)? as u8,
+                "P" => trigger.priority = number(100)? as u8,
+                "C" => trigger.continue_playing = number(1)? == 1,
+                "L" => trigger.repeats = if value == "-1" { -1 } else { number(i32::MAX)? },
+                "T" => trigger.category = Some(value.to_string()),
+                "U" => trigger.url = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(trigger)
+    }
+}
+
+/// Writes the trigger, leaving out parameters at their defaults
+///
+/// Whitespace and parentheses can't appear inside a trigger, so they are
+/// dropped from the file name, category and URL.
+impl std::fmt::Display for MspTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "!!{}(", self.kind.keyword())?;
+        match &self.file {
+            Some(file) => write!(f, "{}", param(file))?,
+            None => write!(f, "Off")?,
+        }
+        if self.file.is_some() {
+            if self.volume != DEFAULT_VOLUME {
+                write!(f, " V={}", self.volume)?;
+            }
+            if self.repeats != 1 {
+                write!(f, " L={}", self.repeats)?;
+            }
+            match self.kind {
+                MspKind::Sound if self.priority != DEFAULT_PRIORITY => {
+                    write!(f, " P={}", self.priority)?
+                }
+                MspKind::Music if !self.continue_playing => write!(f, " C=0")?,
+                _ => {}
+            }
+            if let Some(category) = &self.category {
+                write!(f, " T={}", param(category))?;
+            }
+        }
+        if let Some(url) = &self.url {
+            write!(f, " U={}", param(url))?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn param(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+        .collect()
+}
+
+/// Pull the MSP triggers out of a received line
+///
+/// Returns the line with every valid trigger removed, and the triggers in
+/// the order they appeared. Anything that only looks like a trigger stays
+/// in the text.
+///
+/// ```
+/// use telnet_negotiation::options::{MspKind, split_triggers};
+///
+/// let (text, triggers) = split_triggers("!!SOUND(door.wav V=40)The door creaks open.");
+/// assert_eq!(text, "The door creaks open.");
+/// assert_eq!(triggers[0].kind, MspKind::Sound);
+/// assert_eq!(triggers[0].file.as_deref(), Some("door.wav"));
+/// assert_eq!(triggers[0].volume, 40);
+/// ```
+pub fn split_triggers(line: &str) -> (String, Vec<MspTrigger>) {
+    let mut text = String::with_capacity(line.len());
+    let mut triggers = Vec::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find("!!") {
+        text.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let parsed = candidate
+            .find(')')
+            .and_then(|end| Some((end, MspTrigger::parse(&candidate[..=end]).ok()?)));
+        match parsed {
+            Some((end, trigger)) => {
+                triggers.push(trigger);
+                rest = &candidate[end + 1..];
+            }
+            None => {
+                text.push_str("!!");
+                rest = &candidate[2..];
+            }
+        }
+    }
+    text.push_str(rest);
+    (text, triggers)
+}
+
+/// MSP option handler
+///
+/// MSP has no sub-negotiation; the handler only tracks whether the other
+/// side agreed to it.
+#[derive(Debug, Clone, Default)]
+pub struct MspOption {
+    active: bool,
+}
+
+impl MspOption {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record whether the other side has agreed to MSP
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// The trigger text to send, or an empty string when MSP isn't active
+    ///
+    /// Clients look for triggers at the start of a line, so send the cue
+    /// there.
+    ///
+    /// ```
+    /// use telnet_negotiation::options::{MspOption, MspTrigger};
+    ///
+    /// let mut msp = MspOption::new();
+    /// let thunder = MspTrigger::sound("thunder.wav").with_volume(80);
+    /// assert_eq!(msp.cue(&thunder), "");
+    ///
+    /// msp.set_active(true);
+    /// assert_eq!(msp.cue(&thunder), "!!SOUND(thunder.wav V=80)");
+    /// ```
+    pub fn cue(&self, trigger: &MspTrigger) -> String {
+        if self.active {
+            trigger.to_string()
+        } else {
+            String::new()
+        }
+    }
+}
+
+impl TelnetOptionHandler for MspOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::MSP
+    }
+
+    fn handle_subnegotiation(&mut self, _data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        Err(OptionError::InvalidData(
+            "MSP has no sub-negotiation".to_string(),
+        ))
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        Err(OptionError::UnsupportedCommand(command as u8))
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn reset(&mut self) {
+        self.active = false;
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msp_creation() {
+        let mut msp = MspOption::new();
+        assert_eq!(msp.option_code(), TelnetOption::MSP);
+        assert!(!msp.is_active());
+        msp.set_active(true);
+        assert!(msp.is_active());
+        msp.reset();
+        assert!(!msp.is_active());
+    }
+
+    #[test]
+    fn test_trigger_formatting() {
+        assert_eq!(MspTrigger::sound("hit.wav").to_string(), "!!SOUND(hit.wav)");
+        assert_eq!(
+            MspTrigger::sound("weather/rain.wav")
+                .with_volume(60)
+                .with_repeats(-5)
+                .with_priority(70)
+                .with_category("weather")
+                .to_string(),
+            "!!SOUND(weather/rain.wav V=60 L=-1 P=70 T=weather)"
+        );
+        assert_eq!(
+            MspTrigger::music("tavern.mid")
+                .restart()
+                .with_url("http://example.com/sounds/")
+                .to_string(),
+            "!!MUSIC(tavern.mid C=0 U=http://example.com/sounds/)"
+        );
+        assert_eq!(MspTrigger::stop(MspKind::Music).to_string(), "!!MUSIC(Off)");
+        assert_eq!(MspTrigger::sound("x").with_volume(150).volume, 100);
+        // Nothing in a name can end the trigger early
+        assert_eq!(
+            MspTrigger::sound("bad name).wav").to_string(),
+            "!!SOUND(badname.wav)"
+        );
+    }
+
+    #[test]
+    fn test_trigger_parsing() {
+        let trigger = MspTrigger::parse("!!SOUND(hit.wav V=30 L=2 P=90 T=combat)").unwrap();
+        assert_eq!(trigger.kind, MspKind::Sound);
+        assert_eq!(trigger.file.as_deref(), Some("hit.wav"));
+        assert_eq!(trigger.volume, 30);
+        assert_eq!(trigger.repeats, 2);
+        assert_eq!(trigger.priority, 90);
+        assert_eq!(trigger.category.as_deref(), Some("combat"));
+
+        let trigger = MspTrigger::parse("!!music(theme.mid l=-1 c=0 x=new)").unwrap();
+        assert_eq!(trigger.kind, MspKind::Music);
+        assert_eq!(trigger.repeats, -1);
+        assert!(!trigger.continue_playing);
+
+        let trigger = MspTrigger::parse("!!SOUND(off U=http://example.com/)").unwrap();
+        assert_eq!(trigger.file, None);
+        assert_eq!(trigger.url.as_deref(), Some("http://example.com/"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let trigger = MspTrigger::music("theme.mid")
+            .with_volume(10)
+            .with_repeats(3)
+            .restart()
+            .with_category("ambient");
+        assert_eq!(MspTrigger::parse(&trigger.to_string()).unwrap(), trigger);
+    }
+
+    #[test]
+    fn test_malformed_triggers_rejected() {
+        for bad in [
+            "SOUND(hit.wav)",
+            "!!NOISE(hit.wav)",
+            "!!SOUND()",
+            "!!SOUND(hit.wav",
+            "!!SOUND(hit.wav V=101)",
+            "!!SOUND(hit.wav V=loud)",
+            "!!SOUND(hit.wav L=-2)",
+            "!!MUSIC(theme.mid C=2)",
+            "!!SOUND(hit.wav stray)",
+        ] {
+            assert!(MspTrigger::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_split_triggers() {
+        let (text, triggers) =
+            split_triggers("!!MUSIC(Off)!!SOUND(bell.wav) Dinner is served!! !!SOUND(x V=999)");
+        assert_eq!(text, " Dinner is served!! !!SOUND(x V=999)");
+        assert_eq!(triggers.len(), 2);
+        assert_eq!(triggers[0], MspTrigger::stop(MspKind::Music));
+        assert_eq!(triggers[1], MspTrigger::sound("bell.wav"));
+
+        let (text, triggers) = split_triggers("no cues here");
+        assert_eq!(text, "no cues here");
+        assert!(triggers.is_empty());
+    }
+
+    #[test]
+    fn test_cue_only_when_active() {
+        let mut msp = MspOption::new();
+        let stop = MspTrigger::stop(MspKind::Sound);
+        assert_eq!(msp.cue(&stop), "");
+        msp.set_active(true);
+        assert_eq!(msp.cue(&stop), "!!SOUND(Off)");
+    }
+
+    #[test]
+    fn test_no_subnegotiation() {
+        let mut msp = MspOption::new();
+        assert!(msp.handle_subnegotiation(&[]).is_err());
+        assert!(
+            msp.generate_subnegotiation(SubNegotiationCommand::Send)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! # MSSP Option Implementation (MUD Server Status Protocol)
+//!
+//! MSSP lets a server advertise facts about itself (name, player count,
+//! uptime, codebase and so on) to MUD and BBS crawlers. The server offers
+//! it with `IAC WILL MSSP`; a crawler that answers `IAC DO MSSP` receives a
+//! single sub-negotiation holding every variable.
+//!
+//! ## Sub-negotiation Format
+//!
+//! ```text
+//! IAC SB MSSP MSSP_VAR "NAME" MSSP_VAL "Moonbase" MSSP_VAR "PLAYERS" MSSP_VAL "3" IAC SE
+//! ```
+//!
+//! A variable with several values repeats `MSSP_VAL`:
+//!
+//! ```text
+//! MSSP_VAR "PORT" MSSP_VAL "23" MSSP_VAL "2323"
+//! ```
+//!
+//! Names and values are plain text and may not contain NUL, `MSSP_VAR`,
+//! `MSSP_VAL` or IAC bytes.
+
+use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::protocol::{IAC, TelnetCommand, TelnetOption};
+
+use std::collections::HashMap;
+
+/// Marks the start of a variable name
+pub const MSSP_VAR: u8 = 1;
+/// Marks the start of a value
+pub const MSSP_VAL: u8 = 2;
+
+/// MSSP option handler
+///
+/// On a server it holds the variables to advertise; on a crawler it
+/// collects the variables a server sent.
+#[derive(Debug, Clone, Default)]
+pub struct MsspOption {
+    variables: HashMap<String, Vec<String>>,
+}
+
+impl MsspOption {
+    /// Create a handler advertising `variables`
+    pub fn new(variables: HashMap<String, Vec<String>>) -> Self {
+        Self { variables }
+    }
+
+    /// Variables currently held
+    pub fn variables(&self) -> &HashMap<String, Vec<String>> {
+        &self.variables
+    }
+
+    /// Set or replace one variable, e.g. `PLAYERS` as players come and go
+    pub fn set_variable(&mut self, name: &str, values: Vec<String>) {
+        self.variables.insert(name.to_string(), values);
+    }
+
+    /// Sub-negotiation payload: the bytes between `IAC SB MSSP` and `IAC SE`
+    ///
+    /// Variables are written in name order so the output is stable. Bytes
+    /// the format reserves are dropped from names and values, and a
+    /// variable with no values is sent with one empty value.
+    pub fn payload(&self) -> Vec<u8> {
+        let mut names: Vec<&String> = self.variables.keys().collect();
+        names.sort();
+
+        let mut data = Vec::new();
+        for name in names {
+            data.push(MSSP_VAR);
+            data.extend(sanitize(name));
+
+            let values = &self.variables[name];
+            if values.is_empty() {
+                data.push(MSSP_VAL);
+            }
+            for value in values {
+                data.push(MSSP_VAL);
+                data.extend(sanitize(value));
+            }
+        }
+        data
+    }
+
+    /// The complete `IAC SB MSSP ... IAC SE` sequence, ready to send
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            IAC,
+            TelnetCommand::SB.to_byte(),
+            TelnetOption::MSSP.to_byte(),
+        ];
+        bytes.extend(self.payload());
+        bytes.extend([IAC, TelnetCommand::SE.to_byte()]);
+        bytes
+    }
+
+    /// Parse a payload received from a server
+    pub fn parse_payload(data: &[u8]) -> Result<HashMap<String, Vec<String>>, OptionError> {
+        if data.first() != Some(&MSSP_VAR) {
+            return Err(OptionError::InvalidData(
+                "MSSP data must start with MSSP_VAR".to_string(),
+            ));
+        }
+
+        let mut variables: HashMap<String, Vec<String>> = HashMap::new();
+        let mut current: Option<String> = None;
+        let mut index = 0;
+
+        while index < data.len() {
+            let marker = data[index];
+            let end = data[index + 1..]
+                .iter()
+                .position(|&b| b == MSSP_VAR || b == MSSP_VAL)
+                .map_or(data.len(), |offset| index + 1 + offset);
+            let text = String::from_utf8_lossy(&data[index + 1..end]).to_string();
+
+            match marker {
+                MSSP_VAR => {
+                    if text.is_empty() {
+                        return Err(OptionError::InvalidData(
+                            "MSSP variable with an empty name".to_string(),
+                        ));
+                    }
+                    variables.entry(text.clone()).or_default();
+                    current = Some(text);
+                }
+                MSSP_VAL => match &current {
+                    Some(name) => variables.entry(name.clone()).or_default().push(text),
+                    None => {
+                        return Err(OptionError::InvalidData(
+                            "MSSP value before any variable".to_string(),
+                        ));
+                    }
+                },
+                _ => unreachable!("segments start at a marker"),
+            }
+            index = end;
+        }
+
+        Ok(variables)
+    }
+}
+
+/// Bytes of `text` with the ones MSSP reserves removed
+fn sanitize(text: &str) -> impl Iterator<Item = u8> + '_ {
+    text.bytes()
+        .filter(|&b| !matches!(b, 0 | MSSP_VAR | MSSP_VAL | IAC))
+}
+
+impl TelnetOptionHandler for MsspOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::MSSP
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        // Only servers send MSSP data; a crawler keeps what it was told
+        self.variables = Self::parse_payload(data)?;
+        Ok(vec![])
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        match command {
+            // MSSP has no command byte; the payload is the whole message
+            SubNegotiationCommand::Is => Ok(self.payload()),
+            SubNegotiationCommand::Send => Err(OptionError::UnsupportedCommand(
+                SubNegotiationCommand::Send as u8,
+            )),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        !self.variables.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.variables.clear();
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> MsspOption {
+        let mut variables = HashMap::new();
+        variables.insert("NAME".to_string(), vec!["Moonbase".to_string()]);
+        variables.insert("PLAYERS".to_string(), vec!["3".to_string()]);
+        MsspOption::new(variables)
+    }
+
+    #[test]
+    fn test_mssp_creation() {
+        let option = sample();
+        assert_eq!(option.option_code(), TelnetOption::MSSP);
+        assert!(option.is_active());
+        assert!(!MsspOption::default().is_active());
+    }
+
+    #[test]
+    fn test_payload_format() {
+        let mut expected = vec![MSSP_VAR];
+        expected.extend(b"NAME");
+        expected.push(MSSP_VAL);
+        expected.extend(b"Moonbase");
+        expected.push(MSSP_VAR);
+        expected.extend(b"PLAYERS");
+        expected.push(MSSP_VAL);
+        expected.extend(b"3");
+
+        assert_eq!(sample().payload(), expected);
+        assert_eq!(
+            sample().generate_subnegotiation(SubNegotiationCommand::Is),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn test_framed_bytes() {
+        let bytes = sample().to_bytes();
+        assert_eq!(bytes[..3], [IAC, 250, 70]); // IAC SB MSSP
+        assert_eq!(bytes[bytes.len() - 2..], [IAC, 240]); // IAC SE
+        assert_eq!(bytes[3..bytes.len() - 2], sample().payload());
+    }
+
+    #[test]
+    fn test_multiple_and_empty_values() {
+        let mut option = MsspOption::default();
+        option.set_variable("PORT", vec!["23".to_string(), "2323".to_string()]);
+        option.set_variable("CODEBASE", vec![]);
+
+        let mut expected = vec![MSSP_VAR];
+        expected.extend(b"CODEBASE");
+        expected.push(MSSP_VAL);
+        expected.push(MSSP_VAR);
+        expected.extend(b"PORT");
+        expected.push(MSSP_VAL);
+        expected.extend(b"23");
+        expected.push(MSSP_VAL);
+        expected.extend(b"2323");
+
+        assert_eq!(option.payload(), expected);
+    }
+
+    #[test]
+    fn test_reserved_bytes_are_dropped() {
+        let mut option = MsspOption::default();
+        option.set_variable("NA\u{1}ME", vec!["Moon\u{2}base\u{0}".to_string()]);
+
+        let payload = option.payload();
+        assert_eq!(
+            MsspOption::parse_payload(&payload).unwrap()["NAME"],
+            vec!["Moonbase".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let mut option = sample();
+        option.set_variable("PORT", vec!["23".to_string(), "2323".to_string()]);
+
+        let parsed = MsspOption::parse_payload(&option.payload()).unwrap();
+        assert_eq!(&parsed, option.variables());
+    }
+
+    #[test]
+    fn test_handle_subnegotiation_stores_variables() {
+        let mut crawler = MsspOption::default();
+        let response = crawler.handle_subnegotiation(&sample().payload()).unwrap();
+
+        assert!(response.is_empty());
+        assert_eq!(crawler.variables(), sample().variables());
+    }
+
+    #[test]
+    fn test_malformed_payload_rejected() {
+        assert!(MsspOption::parse_payload(&[]).is_err());
+        assert!(MsspOption::parse_payload(&[MSSP_VAL, b'x']).is_err());
+        assert!(MsspOption::parse_payload(&[MSSP_VAR, MSSP_VAL, b'x']).is_err());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut option = sample();
+        option.reset();
+        assert!(option.variables().is_empty());
+        assert!(!option.is_active());
+    }
+}
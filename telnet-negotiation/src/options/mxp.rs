@@ -0,0 +1,325 @@
+//! # MXP Option Implementation (MUD eXtension Protocol)
+//!
+//! MXP lets a server send HTML-like markup (`<send>`, `<color>`, `<a>`,
+//! ...) that MUD clients render as links, menus and styled text. The
+//! server offers it with `IAC WILL MXP`; once the client answers
+//! `IAC DO MXP`, the server switches markup on with an empty
+//! sub-negotiation, `IAC SB MXP IAC SE`.
+//!
+//! ## Line Modes
+//!
+//! Markup is only parsed where the current line mode allows it. Modes are
+//! set in-band with `ESC [ <n> z`:
+//!
+//! ```text
+//! ESC[0z  open line     only "open" formatting tags are parsed
+//! ESC[1z  secure line   all tags are parsed, until the next newline
+//! ESC[2z  locked line   nothing is parsed, until the next newline
+//! ESC[7z  lock locked   nothing is parsed from here on (default mode)
+//! ```
+//!
+//! This handler starts every MXP session in lock locked mode, so ordinary
+//! output (including anything users typed) is never taken for markup.
+//! [`MxpOption::tag`] opens a secure line just for the tag and locks the
+//! line again straight after it. Text inside tags is entity-escaped, and
+//! [`MxpOption::plain`] strips mode switches from untrusted text so it
+//! can't unlock the line itself.
+//!
+//! When MXP isn't active, both helpers pass text through unchanged, so
+//! callers can use them without checking.
+
+use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::protocol::{IAC, TelnetCommand, TelnetOption};
+
+/// MXP line modes (`ESC [ <n> z`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MxpMode {
+    /// Only open (formatting) tags are parsed on this line
+    OpenLine = 0,
+    /// Every tag is parsed on this line
+    SecureLine = 1,
+    /// Nothing is parsed on this line
+    LockedLine = 2,
+    /// Close open tags and return to the default mode
+    Reset = 3,
+    /// The next tag only is secure
+    TempSecure = 4,
+    /// Open mode until changed
+    LockOpen = 5,
+    /// Secure mode until changed
+    LockSecure = 6,
+    /// Locked mode until changed
+    LockLocked = 7,
+}
+
+impl MxpMode {
+    /// The `ESC [ <n> z` sequence that selects this mode
+    pub fn escape(self) -> String {
+        format!("\x1b[{}z", self as u8)
+    }
+}
+
+/// MXP option handler (server side)
+#[derive(Debug, Clone, Default)]
+pub struct MxpOption {
+    active: bool,
+}
+
+impl MxpOption {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes that turn MXP on once the client has agreed to it: the empty
+    /// sub-negotiation, then lock locked mode so plain output stays plain
+    pub fn start_sequence() -> Vec<u8> {
+        let mut bytes = vec![
+            IAC,
+            TelnetCommand::SB.to_byte(),
+            TelnetOption::MXP.to_byte(),
+            IAC,
+            TelnetCommand::SE.to_byte(),
+        ];
+        bytes.extend(MxpMode::LockLocked.escape().into_bytes());
+        bytes
+    }
+
+    /// Record whether markup is on for this connection
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// `text` wrapped in a tag when MXP is active, otherwise `text` as-is
+    ///
+    /// `attributes` are written as `name="value"`. Names are limited to
+    /// ASCII letters, digits, `-` and `_` (anything else is dropped), and
+    /// values and `text` are entity-escaped.
+    ///
+    /// ```
+    /// use telnet_negotiation::options::MxpOption;
+    ///
+    /// let mut mxp = MxpOption::new();
+    /// assert_eq!(mxp.tag("send", &[("href", "read 1")], "Read"), "Read");
+    ///
+    /// mxp.set_active(true);
+    /// assert_eq!(
+    ///     mxp.tag("send", &[("href", "read 1")], "Read"),
+    ///     "\x1b[1z<send href=\"read 1\">Read</send>\x1b[2z"
+    /// );
+    /// ```
+    pub fn tag(&self, name: &str, attributes: &[(&str, &str)], text: &str) -> String {
+        if !self.active {
+            return text.to_string();
+        }
+
+        let name = tag_name(name);
+        let mut out = MxpMode::SecureLine.escape();
+        out.push('<');
+        out.push_str(&name);
+        for (key, value) in attributes {
+            out.push_str(&format!(" {}=\"{}\"", tag_name(key), escape(value)));
+        }
+        out.push('>');
+        out.push_str(&escape(text));
+        out.push_str(&format!("</{}>", name));
+        out.push_str(&MxpMode::LockedLine.escape());
+        out
+    }
+
+    /// Untrusted text made safe to send: with MXP active, mode switches
+    /// (`ESC [ <n> z`) are removed; otherwise `text` as-is
+    pub fn plain(&self, text: &str) -> String {
+        if self.active {
+            strip_mode_switches(text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Replace the characters MXP treats as markup with entities
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in strip_mode_switches(text).chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `text` without any `ESC [ <digits> z` sequences, including ones that
+/// only appear once an inner one is removed
+fn strip_mode_switches(text: &str) -> String {
+    let mut text = text.to_string();
+    loop {
+        let stripped = strip_once(&text);
+        if stripped == text {
+            return text;
+        }
+        text = stripped;
+    }
+}
+
+fn strip_once(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if after[digits..].starts_with('z') {
+            rest = &after[digits + 1..];
+        } else {
+            // Some other escape sequence (colors, say); keep it
+            out.push_str("\x1b[");
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn tag_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect()
+}
+
+impl TelnetOptionHandler for MxpOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::MXP
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        // Clients have nothing to say in MXP sub-negotiations
+        if data.is_empty() {
+            Ok(vec![])
+        } else {
+            Err(OptionError::InvalidData(
+                "MXP sub-negotiation carries no data".to_string(),
+            ))
+        }
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        Err(OptionError::UnsupportedCommand(command as u8))
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn reset(&mut self) {
+        self.active = false;
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active() -> MxpOption {
+        let mut mxp = MxpOption::new();
+        mxp.set_active(true);
+        mxp
+    }
+
+    #[test]
+    fn test_mxp_creation() {
+        let mxp = MxpOption::new();
+        assert_eq!(mxp.option_code(), TelnetOption::MXP);
+        assert!(!mxp.is_active());
+        assert!(active().is_active());
+    }
+
+    #[test]
+    fn test_start_sequence() {
+        assert_eq!(
+            MxpOption::start_sequence(),
+            b"\xff\xfa\x5b\xff\xf0\x1b[7z".to_vec() // IAC SB MXP IAC SE, lock locked
+        );
+    }
+
+    #[test]
+    fn test_mode_escapes() {
+        assert_eq!(MxpMode::SecureLine.escape(), "\x1b[1z");
+        assert_eq!(MxpMode::LockLocked.escape(), "\x1b[7z");
+    }
+
+    #[test]
+    fn test_entity_escaping() {
+        assert_eq!(
+            escape(r#"<b>Tom & "Jerry"</b>"#),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+        );
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_tag_escapes_contents() {
+        let tag = active().tag("send", &[("hint", "a \"quote\"")], "<b>&");
+        assert_eq!(
+            tag,
+            "\x1b[1z<send hint=\"a &quot;quote&quot;\">&lt;b&gt;&amp;</send>\x1b[2z"
+        );
+    }
+
+    #[test]
+    fn test_tag_names_are_sanitized() {
+        let tag = active().tag("a href=x><b", &[("on click", "x")], "go");
+        assert_eq!(tag, "\x1b[1z<ahrefxb onclick=\"x\">go</ahrefxb>\x1b[2z");
+    }
+
+    #[test]
+    fn test_pass_through_when_inactive() {
+        let mxp = MxpOption::new();
+        assert_eq!(mxp.tag("b", &[], "<hi> & bye"), "<hi> & bye");
+        assert_eq!(mxp.plain("\x1b[1z<b>"), "\x1b[1z<b>");
+    }
+
+    #[test]
+    fn test_plain_strips_mode_switches() {
+        let mxp = active();
+        assert_eq!(mxp.plain("a\x1b[1z<send>b\x1b[z"), "a<send>b");
+        // Colors and other escapes are left alone
+        assert_eq!(mxp.plain("\x1b[31mred\x1b[0m"), "\x1b[31mred\x1b[0m");
+        assert_eq!(escape("\x1b[6z<b>"), "&lt;b&gt;");
+        // Removing one switch mustn't assemble another
+        assert_eq!(mxp.plain("\x1b\x1b[1z[1z<b>"), "<b>");
+    }
+
+    #[test]
+    fn test_subnegotiation() {
+        let mut mxp = MxpOption::new();
+        assert_eq!(mxp.handle_subnegotiation(&[]), Ok(vec![]));
+        assert!(mxp.handle_subnegotiation(&[1]).is_err());
+        assert!(
+            mxp.generate_subnegotiation(SubNegotiationCommand::Is)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut mxp = active();
+        mxp.reset();
+        assert!(!mxp.is_active());
+    }
+}
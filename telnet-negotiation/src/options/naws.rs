@@ -47,6 +47,7 @@ pub struct NawsOption {
 
 /// Terminal window size information
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WindowSize {
     /// Terminal width in characters
     pub width: u16,
@@ -113,7 +114,7 @@ impl NawsOption {
     /// Encode window size for NAWS sub-negotiation
     ///
     /// Returns 4-byte array: <width-high> <width-low> <height-high> <height-low>
-    fn encode_window_size(size: WindowSize) -> Vec<u8> {
+    pub(crate) fn encode_window_size(size: WindowSize) -> Vec<u8> {
         vec![
             (size.width >> 8) as u8,    // width high byte
             (size.width & 0xFF) as u8,  // width low byte
@@ -170,6 +171,17 @@ impl TelnetOptionHandler for NawsOption {
         self.has_data = false;
     }
 
+    /// The window size as NAWS sends it
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        self.window_size.map(Self::encode_window_size)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), OptionError> {
+        self.window_size = Some(Self::parse_window_size(state)?);
+        self.has_data = true;
+        Ok(())
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -342,7 +354,7 @@ mod tests {
         let data = vec![0x00, 0x50, 0x00, 0x18]; // 80x24
         let result = naws.handle_subnegotiation(&data);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), vec![]); // No response expected
+        assert_eq!(result.unwrap(), Vec::<u8>::new()); // No response expected
 
         assert!(naws.is_active());
         assert_eq!(naws.width(), Some(80));
@@ -0,0 +1,474 @@
+//! # NEW-ENVIRON Option Implementation (RFC 1572)
+//!
+//! NEW-ENVIRON lets a server ask the client for environment variables such
+//! as `USER` and `DISPLAY`. A BBS can use `USER` to prefill the login
+//! prompt; nothing the client sends should be trusted as authentication.
+//!
+//! ## Sub-negotiation Protocol
+//!
+//! ### Request Variables
+//! ```text
+//! IAC SB NEW-ENVIRON SEND VAR "USER" VAR "DISPLAY" USERVAR "COLUMNS" IAC SE
+//! ```
+//!
+//! An empty `SEND` asks for every variable the client is willing to share.
+//!
+//! ### Response
+//! ```text
+//! IAC SB NEW-ENVIRON IS VAR "USER" VALUE "alice" VAR "DISPLAY" IAC SE
+//! ```
+//!
+//! A `VAR` without a `VALUE` is defined but has no value. The client may
+//! later send `INFO` with the same layout when a variable changes.
+//!
+//! ## Escaping
+//!
+//! Inside names and values, the bytes `VAR`, `VALUE`, `ESC` and `USERVAR`
+//! are preceded by `ESC`.
+
+use super::{OptionError, TelnetOptionHandler};
+#[cfg(feature = "negotiation")]
+use crate::negotiation::Side;
+use crate::protocol::TelnetOption;
+
+use std::collections::HashMap;
+
+/// Sub-negotiation command: here is my environment
+pub const ENV_IS: u8 = 0;
+/// Sub-negotiation command: please send your environment
+pub const ENV_SEND: u8 = 1;
+/// Sub-negotiation command: part of my environment changed
+pub const ENV_INFO: u8 = 2;
+
+/// Marks a well-known variable name (`USER`, `DISPLAY`, ...)
+pub const ENV_VAR: u8 = 0;
+/// Marks the value of the preceding variable
+pub const ENV_VALUE: u8 = 1;
+/// Escapes the next byte inside a name or value
+pub const ENV_ESC: u8 = 2;
+/// Marks a user-defined variable name
+pub const ENV_USERVAR: u8 = 3;
+
+/// Whether a variable is one of RFC 1572's well-known names or user-defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EnvironKind {
+    /// Well-known variable, sent with `VAR`
+    Var,
+    /// User-defined variable, sent with `USERVAR`
+    UserVar,
+}
+
+impl EnvironKind {
+    fn marker(self) -> u8 {
+        match self {
+            EnvironKind::Var => ENV_VAR,
+            EnvironKind::UserVar => ENV_USERVAR,
+        }
+    }
+}
+
+/// Variables received from the client
+///
+/// A variable maps to `None` when the client says it exists but has no value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    variables: HashMap<(EnvironKind, String), Option<String>>,
+}
+
+impl Environment {
+    /// Value of a well-known variable
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.get(EnvironKind::Var, name)
+    }
+
+    /// Value of a user-defined variable
+    pub fn user_var(&self, name: &str) -> Option<&str> {
+        self.get(EnvironKind::UserVar, name)
+    }
+
+    /// Value of a variable of either kind
+    pub fn get(&self, kind: EnvironKind, name: &str) -> Option<&str> {
+        self.variables
+            .get(&(kind, name.to_string()))
+            .and_then(|value| value.as_deref())
+    }
+
+    /// The client's login name, if it shared one
+    pub fn user(&self) -> Option<&str> {
+        self.var("USER").filter(|user| !user.is_empty())
+    }
+
+    /// The client's X display, if it shared one
+    pub fn display(&self) -> Option<&str> {
+        self.var("DISPLAY").filter(|display| !display.is_empty())
+    }
+
+    /// Every variable received, with its kind and value
+    pub fn iter(&self) -> impl Iterator<Item = (EnvironKind, &str, Option<&str>)> {
+        self.variables
+            .iter()
+            .map(|((kind, name), value)| (*kind, name.as_str(), value.as_deref()))
+    }
+
+    /// Number of variables received
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    /// Whether no variables have been received
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    fn set(&mut self, kind: EnvironKind, name: String, value: Option<String>) {
+        self.variables.insert((kind, name), value);
+    }
+}
+
+/// NEW-ENVIRON option handler
+#[derive(Debug, Clone, Default)]
+pub struct NewEnvironOption {
+    /// Variables the client has reported
+    environment: Environment,
+    /// Whether an IS reply has arrived
+    has_data: bool,
+}
+
+impl NewEnvironOption {
+    /// Create a new NEW-ENVIRON option handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Variables received so far
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Whether the client has answered a SEND yet
+    pub fn has_data(&self) -> bool {
+        self.has_data
+    }
+
+    /// SEND payload asking for specific variables
+    ///
+    /// With both lists empty the client is asked for everything it will share.
+    pub fn send_request(vars: &[&str], user_vars: &[&str]) -> Vec<u8> {
+        let mut data = vec![ENV_SEND];
+        for (kind, names) in [(EnvironKind::Var, vars), (EnvironKind::UserVar, user_vars)] {
+            for name in names {
+                data.push(kind.marker());
+                encode_escaped(&mut data, name);
+            }
+        }
+        data
+    }
+
+    /// IS or INFO payload listing `variables` (what a client sends)
+    pub fn encode_variables(
+        command: u8,
+        variables: &[(EnvironKind, &str, Option<&str>)],
+    ) -> Vec<u8> {
+        let mut data = vec![command];
+        for (kind, name, value) in variables {
+            data.push(kind.marker());
+            encode_escaped(&mut data, name);
+            if let Some(value) = value {
+                data.push(ENV_VALUE);
+                encode_escaped(&mut data, value);
+            }
+        }
+        data
+    }
+
+    /// Parse the variable list that follows IS or INFO
+    pub fn parse_variables(
+        data: &[u8],
+    ) -> Result<Vec<(EnvironKind, String, Option<String>)>, OptionError> {
+        let mut variables = Vec::new();
+        let mut bytes = data.iter().copied().peekable();
+
+        while let Some(marker) = bytes.next() {
+            let kind = match marker {
+                ENV_VAR => EnvironKind::Var,
+                ENV_USERVAR => EnvironKind::UserVar,
+                other => {
+                    return Err(OptionError::InvalidData(format!(
+                        "expected VAR or USERVAR, found {}",
+                        other
+                    )));
+                }
+            };
+
+            let name = read_escaped(&mut bytes);
+            let value = if bytes.peek() == Some(&ENV_VALUE) {
+                bytes.next();
+                Some(read_escaped(&mut bytes))
+            } else {
+                None
+            };
+            variables.push((kind, name, value));
+        }
+
+        Ok(variables)
+    }
+}
+
+/// Append `text`, escaping the bytes NEW-ENVIRON reserves
+fn encode_escaped(data: &mut Vec<u8>, text: &str) {
+    for byte in text.bytes() {
+        if matches!(byte, ENV_VAR | ENV_VALUE | ENV_ESC | ENV_USERVAR) {
+            data.push(ENV_ESC);
+        }
+        data.push(byte);
+    }
+}
+
+/// Read a name or value up to the next unescaped marker
+fn read_escaped<I: Iterator<Item = u8>>(bytes: &mut std::iter::Peekable<I>) -> String {
+    let mut raw = Vec::new();
+    while let Some(&byte) = bytes.peek() {
+        match byte {
+            ENV_VAR | ENV_VALUE | ENV_USERVAR => break,
+            ENV_ESC => {
+                bytes.next();
+                // A trailing ESC escapes nothing
+                if let Some(escaped) = bytes.next() {
+                    raw.push(escaped);
+                }
+            }
+            _ => {
+                raw.push(byte);
+                bytes.next();
+            }
+        }
+    }
+    String::from_utf8_lossy(&raw).into_owned()
+}
+
+impl TelnetOptionHandler for NewEnvironOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::NEW_ENVIRON
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        let Some((&command, rest)) = data.split_first() else {
+            return Err(OptionError::InvalidData(
+                "Empty NEW-ENVIRON sub-negotiation".to_string(),
+            ));
+        };
+
+        match command {
+            ENV_IS | ENV_INFO => {
+                let variables = Self::parse_variables(rest)?;
+                // IS is the complete answer to a SEND; INFO only updates
+                if command == ENV_IS {
+                    self.environment = Environment::default();
+                    self.has_data = true;
+                }
+                for (kind, name, value) in variables {
+                    self.environment.set(kind, name, value);
+                }
+                Ok(vec![])
+            }
+            // We don't share our own environment, so the answer is empty
+            ENV_SEND => Ok(vec![ENV_IS]),
+            other => Err(OptionError::UnsupportedCommand(other)),
+        }
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: super::SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        match command {
+            super::SubNegotiationCommand::Send => Ok(Self::send_request(&[], &[])),
+            super::SubNegotiationCommand::Is => Ok(vec![ENV_IS]),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.has_data
+    }
+
+    fn reset(&mut self) {
+        self.environment = Environment::default();
+        self.has_data = false;
+    }
+
+    /// Ask for everything once the client agrees to send its environment
+    #[cfg(feature = "negotiation")]
+    fn on_enabled(&mut self, side: Side) -> Vec<u8> {
+        match side {
+            Side::Remote => Self::send_request(&[], &[]),
+            Side::Local => Vec::new(),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_environ_creation() {
+        let option = NewEnvironOption::new();
+        assert_eq!(option.option_code(), TelnetOption::NEW_ENVIRON);
+        assert!(!option.is_active());
+        assert!(option.environment().is_empty());
+    }
+
+    #[test]
+    fn test_send_request() {
+        assert_eq!(NewEnvironOption::send_request(&[], &[]), vec![ENV_SEND]);
+
+        let mut expected = vec![ENV_SEND, ENV_VAR];
+        expected.extend(b"USER");
+        expected.push(ENV_VAR);
+        expected.extend(b"DISPLAY");
+        expected.push(ENV_USERVAR);
+        expected.extend(b"COLUMNS");
+        assert_eq!(
+            NewEnvironOption::send_request(&["USER", "DISPLAY"], &["COLUMNS"]),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_handle_is_response() {
+        let mut option = NewEnvironOption::new();
+        let mut data = vec![ENV_IS, ENV_VAR];
+        data.extend(b"USER");
+        data.push(ENV_VALUE);
+        data.extend(b"alice");
+        data.push(ENV_VAR);
+        data.extend(b"DISPLAY");
+        data.push(ENV_VALUE);
+        data.extend(b"host:0");
+        data.push(ENV_USERVAR);
+        data.extend(b"EDITOR");
+
+        assert_eq!(option.handle_subnegotiation(&data), Ok(vec![]));
+        assert!(option.is_active());
+
+        let env = option.environment();
+        assert_eq!(env.user(), Some("alice"));
+        assert_eq!(env.display(), Some("host:0"));
+        assert_eq!(env.len(), 3);
+        // Defined but without a value
+        assert_eq!(env.user_var("EDITOR"), None);
+        assert!(env.iter().any(|(kind, name, value)| {
+            kind == EnvironKind::UserVar && name == "EDITOR" && value.is_none()
+        }));
+    }
+
+    #[test]
+    fn test_escaped_bytes_round_trip() {
+        let name = "ODD\u{1}NAME";
+        let value = "a\u{0}b\u{2}c\u{3}";
+        let data = NewEnvironOption::encode_variables(
+            ENV_IS,
+            &[(EnvironKind::UserVar, name, Some(value))],
+        );
+        // One ESC before each reserved byte, plus the value's own ESC byte
+        assert_eq!(data.iter().filter(|&&b| b == ENV_ESC).count(), 5);
+
+        let mut option = NewEnvironOption::new();
+        option.handle_subnegotiation(&data).unwrap();
+        assert_eq!(option.environment().user_var(name), Some(value));
+    }
+
+    #[test]
+    fn test_info_updates_environment() {
+        let mut option = NewEnvironOption::new();
+        option
+            .handle_subnegotiation(&NewEnvironOption::encode_variables(
+                ENV_IS,
+                &[
+                    (EnvironKind::Var, "USER", Some("alice")),
+                    (EnvironKind::Var, "DISPLAY", Some("host:0")),
+                ],
+            ))
+            .unwrap();
+        option
+            .handle_subnegotiation(&NewEnvironOption::encode_variables(
+                ENV_INFO,
+                &[(EnvironKind::Var, "DISPLAY", Some("host:1"))],
+            ))
+            .unwrap();
+
+        assert_eq!(option.environment().user(), Some("alice"));
+        assert_eq!(option.environment().display(), Some("host:1"));
+    }
+
+    #[test]
+    fn test_is_replaces_environment() {
+        let mut option = NewEnvironOption::new();
+        for user in ["alice", "bob"] {
+            option
+                .handle_subnegotiation(&NewEnvironOption::encode_variables(
+                    ENV_IS,
+                    &[(EnvironKind::Var, "USER", Some(user))],
+                ))
+                .unwrap();
+        }
+        assert_eq!(option.environment().user(), Some("bob"));
+        assert_eq!(option.environment().len(), 1);
+    }
+
+    #[test]
+    fn test_send_is_answered_with_empty_is() {
+        let mut option = NewEnvironOption::new();
+        assert_eq!(
+            option.handle_subnegotiation(&[ENV_SEND, ENV_VAR]),
+            Ok(vec![ENV_IS])
+        );
+        assert!(!option.is_active());
+    }
+
+    #[test]
+    fn test_malformed_data_rejected() {
+        let mut option = NewEnvironOption::new();
+        assert!(option.handle_subnegotiation(&[]).is_err());
+        assert!(option.handle_subnegotiation(&[9]).is_err());
+        assert!(
+            option
+                .handle_subnegotiation(&[ENV_IS, ENV_VALUE, b'x'])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_empty_user_is_ignored() {
+        let mut option = NewEnvironOption::new();
+        option
+            .handle_subnegotiation(&NewEnvironOption::encode_variables(
+                ENV_IS,
+                &[(EnvironKind::Var, "USER", Some(""))],
+            ))
+            .unwrap();
+        assert_eq!(option.environment().user(), None);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut option = NewEnvironOption::new();
+        option
+            .handle_subnegotiation(&NewEnvironOption::encode_variables(
+                ENV_IS,
+                &[(EnvironKind::Var, "USER", Some("alice"))],
+            ))
+            .unwrap();
+        option.reset();
+        assert!(!option.is_active());
+        assert!(option.environment().is_empty());
+    }
+}
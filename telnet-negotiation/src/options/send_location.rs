@@ -0,0 +1,183 @@
+//! # SEND-LOCATION Option Implementation (RFC 779)
+//!
+//! SEND-LOCATION lets a client say where its user is, as free text such as
+//! `"Building 4, Room 12"` or `"Portland, OR"`. Unlike TERMINAL-TYPE there
+//! is no request: once the server has sent DO SEND-LOCATION, the client
+//! sends its location whenever it likes, usually straight away.
+//!
+//! ## Sub-negotiation Protocol
+//!
+//! ```text
+//! IAC SB SEND-LOCATION "Portland, OR" IAC SE
+//! ```
+//!
+//! The data is the location itself, with no command byte in front.
+
+use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::protocol::TelnetOption;
+
+/// Longest location accepted, in bytes; anything longer is refused
+pub const MAX_LOCATION_LEN: usize = 256;
+
+/// SEND-LOCATION option handler
+///
+/// Only the server side is implemented: we take the client's location but
+/// never send our own.
+#[derive(Debug, Clone, Default)]
+pub struct SendLocationOption {
+    /// Location from the client's last sub-negotiation
+    location: Option<String>,
+}
+
+impl SendLocationOption {
+    /// Create a new SEND-LOCATION option handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The client's location, once it has sent one
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Sub-negotiation payload for `location` (what a client sends)
+    pub fn encode_location(location: &str) -> Vec<u8> {
+        location.as_bytes().to_vec()
+    }
+
+    /// Clean up a location for display: control characters are dropped and
+    /// surrounding whitespace trimmed
+    ///
+    /// The text goes straight onto other callers' screens, so nothing that
+    /// could move the cursor or change colors is kept.
+    pub fn parse(data: &[u8]) -> Result<String, OptionError> {
+        if data.len() > MAX_LOCATION_LEN {
+            return Err(OptionError::InvalidData(format!(
+                "SEND-LOCATION is {} bytes (max {})",
+                data.len(),
+                MAX_LOCATION_LEN
+            )));
+        }
+        let text: String = String::from_utf8_lossy(data)
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect();
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(OptionError::InvalidData(
+                "Empty SEND-LOCATION sub-negotiation".to_string(),
+            ));
+        }
+        Ok(text.to_string())
+    }
+}
+
+impl TelnetOptionHandler for SendLocationOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::SEND_LOCATION
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        self.location = Some(Self::parse(data)?);
+        Ok(vec![])
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        match command {
+            // The client volunteers its location; there is nothing to ask with
+            SubNegotiationCommand::Send => Err(OptionError::UnsupportedCommand(command as u8)),
+            SubNegotiationCommand::Is => self
+                .location
+                .as_deref()
+                .map(Self::encode_location)
+                .ok_or_else(|| OptionError::InvalidState("No location known".to_string())),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.location.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.location = None;
+    }
+
+    /// The location text
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        self.location.as_deref().map(Self::encode_location)
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), OptionError> {
+        self.location = Some(Self::parse(state)?);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_location_creation() {
+        let sloc = SendLocationOption::new();
+        assert_eq!(sloc.option_code(), TelnetOption::SEND_LOCATION);
+        assert!(sloc.location().is_none());
+        assert!(!sloc.is_active());
+    }
+
+    #[test]
+    fn test_location_received() {
+        let mut sloc = SendLocationOption::new();
+        let data = SendLocationOption::encode_location("Portland, OR");
+
+        assert_eq!(sloc.handle_subnegotiation(&data).unwrap(), Vec::<u8>::new());
+        assert_eq!(sloc.location(), Some("Portland, OR"));
+        assert!(sloc.is_active());
+        assert_eq!(
+            sloc.generate_subnegotiation(SubNegotiationCommand::Is)
+                .unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_control_characters_dropped() {
+        let mut sloc = SendLocationOption::new();
+        sloc.handle_subnegotiation(b"  \x1b[31mMars\r\n Base ")
+            .unwrap();
+        assert_eq!(sloc.location(), Some("[31mMars Base"));
+    }
+
+    #[test]
+    fn test_bad_location_rejected() {
+        let mut sloc = SendLocationOption::new();
+        sloc.handle_subnegotiation(b"Earth").unwrap();
+
+        assert!(sloc.handle_subnegotiation(b"").is_err());
+        assert!(sloc.handle_subnegotiation(b" \x07 ").is_err());
+        assert!(
+            sloc.handle_subnegotiation(&[b'x'; MAX_LOCATION_LEN + 1])
+                .is_err()
+        );
+        // A bad location leaves the previous one alone
+        assert_eq!(sloc.location(), Some("Earth"));
+
+        sloc.reset();
+        assert!(sloc.location().is_none());
+        assert!(
+            sloc.generate_subnegotiation(SubNegotiationCommand::Send)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! # START_TLS Option Implementation (draft-altman-telnet-starttls)
+//!
+//! START_TLS lets a plain telnet connection switch to TLS on the same port.
+//! After the switch every byte, telnet commands included, travels inside
+//! the TLS session.
+//!
+//! ## Exchange
+//!
+//! ```text
+//! Server: IAC DO START_TLS
+//! Client: IAC WILL START_TLS
+//! Server: IAC SB START_TLS FOLLOWS IAC SE
+//! Client: IAC SB START_TLS FOLLOWS IAC SE
+//! Client: <TLS ClientHello ...>
+//! ```
+//!
+//! Once the server has sent FOLLOWS it must not send anything else in the
+//! clear. The client's FOLLOWS marks the last plaintext byte; whatever comes
+//! after it belongs to the TLS handshake.
+//!
+//! ## TLS Backends
+//!
+//! This crate does not link a TLS library. Applications supply a
+//! [`TlsUpgrader`] that wraps the socket in whatever TLS implementation
+//! they use, and [`crate::TelnetStream`] swaps it in at the right moment.
+
+use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::protocol::TelnetOption;
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+/// Sub-negotiation byte announcing that TLS negotiation follows
+pub const FOLLOWS: u8 = 1;
+
+/// Byte-level TLS session that replaces the plain socket after an upgrade
+pub trait TlsTransport: Read + Write + Send {}
+
+impl<T: Read + Write + Send> TlsTransport for T {}
+
+/// Performs the server side of a TLS handshake on an upgraded connection
+///
+/// `S` is the transport the telnet stream runs over, a `TcpStream` unless
+/// the stream was built on something else.
+pub trait TlsUpgrader<S = TcpStream>: Send {
+    /// Wrap `socket` in a TLS session
+    ///
+    /// `socket` is a clone of the connection's transport. `buffered` holds
+    /// bytes the client sent after its FOLLOWS that were already read off
+    /// it; they are the start of the handshake and must be fed to the TLS
+    /// engine before reading from `socket`.
+    fn upgrade(&mut self, socket: S, buffered: Vec<u8>) -> io::Result<Box<dyn TlsTransport>>;
+}
+
+/// Where a connection is in the START_TLS exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartTlsState {
+    /// START_TLS has not been offered
+    Idle,
+    /// Sent DO START_TLS, waiting for the client's answer
+    Offered,
+    /// Client agreed and we sent FOLLOWS; plaintext output is held back
+    FollowsSent,
+    /// Client sent FOLLOWS; the next bytes are the TLS handshake
+    Ready,
+    /// TLS is in place
+    Active,
+    /// Client refused, or the offer was withdrawn
+    Declined,
+}
+
+/// START_TLS option handler
+#[derive(Debug, Clone)]
+pub struct StartTlsOption {
+    state: StartTlsState,
+}
+
+impl StartTlsOption {
+    /// Create a new handler with nothing offered yet
+    pub fn new() -> Self {
+        Self {
+            state: StartTlsState::Idle,
+        }
+    }
+
+    /// Current point in the exchange
+    pub fn state(&self) -> StartTlsState {
+        self.state
+    }
+
+    /// Move to a new point in the exchange
+    pub fn set_state(&mut self, state: StartTlsState) {
+        self.state = state;
+    }
+}
+
+impl TelnetOptionHandler for StartTlsOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::START_TLS
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        if data != [FOLLOWS] {
+            return Err(OptionError::InvalidData(format!(
+                "START_TLS expects a single FOLLOWS byte, got {:?}",
+                data
+            )));
+        }
+
+        if self.state != StartTlsState::FollowsSent {
+            return Err(OptionError::InvalidState(format!(
+                "Unexpected START_TLS FOLLOWS in state {:?}",
+                self.state
+            )));
+        }
+
+        // The client's FOLLOWS is the handshake trigger, never answered
+        self.state = StartTlsState::Ready;
+        Ok(vec![])
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        match command {
+            // FOLLOWS shares its byte value with SEND in other options
+            SubNegotiationCommand::Send => Ok(vec![FOLLOWS]),
+            SubNegotiationCommand::Is => Err(OptionError::UnsupportedCommand(
+                SubNegotiationCommand::Is as u8,
+            )),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.state == StartTlsState::Active
+    }
+
+    fn reset(&mut self) {
+        self.state = StartTlsState::Idle;
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+impl Default for StartTlsOption {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{TelnetOption, TelnetSequence};
+
+    #[test]
+    fn test_start_tls_creation() {
+        let option = StartTlsOption::new();
+        assert_eq!(option.state(), StartTlsState::Idle);
+        assert!(!option.is_active());
+        assert_eq!(option.option_code(), TelnetOption::START_TLS);
+    }
+
+    #[test]
+    fn test_follows_moves_to_ready() {
+        let mut option = StartTlsOption::new();
+        option.set_state(StartTlsState::FollowsSent);
+
+        let response = option.handle_subnegotiation(&[FOLLOWS]).unwrap();
+        assert!(response.is_empty());
+        assert_eq!(option.state(), StartTlsState::Ready);
+    }
+
+    #[test]
+    fn test_unsolicited_follows_rejected() {
+        let mut option = StartTlsOption::new();
+        assert!(option.handle_subnegotiation(&[FOLLOWS]).is_err());
+        assert_eq!(option.state(), StartTlsState::Idle);
+    }
+
+    #[test]
+    fn test_malformed_subnegotiation_rejected() {
+        let mut option = StartTlsOption::new();
+        option.set_state(StartTlsState::FollowsSent);
+
+        assert!(option.handle_subnegotiation(&[]).is_err());
+        assert!(option.handle_subnegotiation(&[FOLLOWS, 0]).is_err());
+        assert_eq!(option.state(), StartTlsState::FollowsSent);
+    }
+
+    #[test]
+    fn test_generate_follows() {
+        let option = StartTlsOption::new();
+        let follows = option
+            .generate_subnegotiation(SubNegotiationCommand::Send)
+            .unwrap();
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::START_TLS,
+            data: follows,
+        };
+        // IAC SB START_TLS FOLLOWS IAC SE
+        assert_eq!(sequence.to_bytes(), vec![255, 250, 46, 1, 255, 240]);
+        assert!(
+            option
+                .generate_subnegotiation(SubNegotiationCommand::Is)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut option = StartTlsOption::new();
+        option.set_state(StartTlsState::Active);
+        assert!(option.is_active());
+
+        option.reset();
+        assert_eq!(option.state(), StartTlsState::Idle);
+    }
+}
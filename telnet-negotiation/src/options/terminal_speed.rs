@@ -0,0 +1,276 @@
+//! # TERMINAL-SPEED Option Implementation (RFC 1079)
+//!
+//! TERMINAL-SPEED lets a server ask how fast the client's line is. Callers
+//! on real modems (or emulating one) report speeds like `2400,2400`, and a
+//! server can hold back animation or long ANSI screens that would take
+//! minutes to arrive at that rate.
+//!
+//! ## Sub-negotiation Protocol
+//!
+//! ### Request Speed
+//! ```text
+//! IAC SB TERMINAL-SPEED SEND IAC SE
+//! ```
+//!
+//! ### Response
+//! ```text
+//! IAC SB TERMINAL-SPEED IS "38400,19200" IAC SE
+//! ```
+//!
+//! The reply is ASCII: transmit speed, a comma, then receive speed, both in
+//! bits per second and both from the client's point of view.
+
+use super::{OptionError, TelnetOptionHandler};
+#[cfg(feature = "negotiation")]
+use crate::negotiation::Side;
+use crate::protocol::TelnetOption;
+
+/// Sub-negotiation command: here is my terminal speed
+pub const TSPEED_IS: u8 = 0;
+/// Sub-negotiation command: please send your terminal speed
+pub const TSPEED_SEND: u8 = 1;
+
+/// Line speed reported by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TerminalSpeed {
+    /// Bits per second the client sends at
+    pub transmit: u32,
+    /// Bits per second the client receives at
+    pub receive: u32,
+}
+
+impl TerminalSpeed {
+    /// Create a new terminal speed
+    pub fn new(transmit: u32, receive: u32) -> Self {
+        Self { transmit, receive }
+    }
+
+    /// Characters per second the client can take in
+    ///
+    /// Assumes the usual 10 bits on the wire per character (8N1), so a
+    /// 2400 baud caller gets 240. This is the rate to pace output to.
+    pub fn receive_cps(&self) -> u32 {
+        self.receive / 10
+    }
+
+    /// Parse the `"<transmit>,<receive>"` text of an IS reply
+    pub fn parse(data: &[u8]) -> Result<Self, OptionError> {
+        let text = std::str::from_utf8(data).map_err(|_| {
+            OptionError::InvalidData("TERMINAL-SPEED reply is not ASCII".to_string())
+        })?;
+        let speed = |field: Option<&str>| {
+            field
+                .and_then(|f| f.trim().parse::<u32>().ok())
+                .ok_or_else(|| {
+                    OptionError::InvalidData(format!(
+                        "TERMINAL-SPEED reply must be \"<tx>,<rx>\", got {:?}",
+                        text
+                    ))
+                })
+        };
+
+        let mut fields = text.split(',');
+        let transmit = speed(fields.next())?;
+        let receive = speed(fields.next())?;
+        if fields.next().is_some() {
+            return Err(OptionError::InvalidData(format!(
+                "TERMINAL-SPEED reply has too many fields: {:?}",
+                text
+            )));
+        }
+        Ok(Self { transmit, receive })
+    }
+}
+
+impl std::fmt::Display for TerminalSpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.transmit, self.receive)
+    }
+}
+
+/// TERMINAL-SPEED option handler
+///
+/// Only the server side is implemented: we ask for the client's speed but
+/// never report our own.
+#[derive(Debug, Clone, Default)]
+pub struct TerminalSpeedOption {
+    /// Speed from the client's last IS reply
+    speed: Option<TerminalSpeed>,
+}
+
+impl TerminalSpeedOption {
+    /// Create a new TERMINAL-SPEED option handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The client's line speed, once it has answered
+    pub fn speed(&self) -> Option<TerminalSpeed> {
+        self.speed
+    }
+
+    /// SEND payload asking for the client's speed
+    pub fn send_request() -> Vec<u8> {
+        vec![TSPEED_SEND]
+    }
+
+    /// IS payload reporting `speed` (what a client sends)
+    pub fn encode_speed(speed: TerminalSpeed) -> Vec<u8> {
+        let mut data = vec![TSPEED_IS];
+        data.extend_from_slice(speed.to_string().as_bytes());
+        data
+    }
+}
+
+impl TelnetOptionHandler for TerminalSpeedOption {
+    fn option_code(&self) -> TelnetOption {
+        TelnetOption::TERMINAL_SPEED
+    }
+
+    fn handle_subnegotiation(&mut self, data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        match data.split_first() {
+            Some((&TSPEED_IS, rest)) => {
+                self.speed = Some(TerminalSpeed::parse(rest)?);
+                Ok(vec![])
+            }
+            Some((&other, _)) => Err(OptionError::UnsupportedCommand(other)),
+            None => Err(OptionError::InvalidData(
+                "Empty TERMINAL-SPEED sub-negotiation".to_string(),
+            )),
+        }
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        command: super::SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        match command {
+            super::SubNegotiationCommand::Send => Ok(Self::send_request()),
+            super::SubNegotiationCommand::Is => self
+                .speed
+                .map(Self::encode_speed)
+                .ok_or_else(|| OptionError::InvalidState("No terminal speed known".to_string())),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.speed.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.speed = None;
+    }
+
+    /// Ask for the speed once the client agrees to send it
+    #[cfg(feature = "negotiation")]
+    fn on_enabled(&mut self, side: Side) -> Vec<u8> {
+        match side {
+            Side::Remote => Self::send_request(),
+            Side::Local => Vec::new(),
+        }
+    }
+
+    /// The speed as `"<transmit>,<receive>"`
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        self.speed.map(|speed| speed.to_string().into_bytes())
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), OptionError> {
+        self.speed = Some(TerminalSpeed::parse(state)?);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_speed_creation() {
+        let tspeed = TerminalSpeedOption::new();
+        assert_eq!(tspeed.option_code(), TelnetOption::TERMINAL_SPEED);
+        assert!(tspeed.speed().is_none());
+        assert!(!tspeed.is_active());
+    }
+
+    #[test]
+    fn test_handle_is_response() {
+        let mut tspeed = TerminalSpeedOption::new();
+        let mut data = vec![TSPEED_IS];
+        data.extend(b"38400,2400");
+
+        assert_eq!(
+            tspeed.handle_subnegotiation(&data).unwrap(),
+            Vec::<u8>::new()
+        );
+        let speed = tspeed.speed().unwrap();
+        assert_eq!(speed, TerminalSpeed::new(38400, 2400));
+        assert_eq!(speed.receive_cps(), 240);
+        assert!(tspeed.is_active());
+    }
+
+    #[test]
+    fn test_speed_round_trip() {
+        let speed = TerminalSpeed::new(9600, 14400);
+        let encoded = TerminalSpeedOption::encode_speed(speed);
+        assert_eq!(encoded[0], TSPEED_IS);
+        assert_eq!(&encoded[1..], b"9600,14400");
+        assert_eq!(TerminalSpeed::parse(&encoded[1..]).unwrap(), speed);
+    }
+
+    #[test]
+    fn test_malformed_speed_rejected() {
+        for bad in [
+            &b""[..],
+            b"9600",
+            b"9600,",
+            b"fast,slow",
+            b"9600,9600,9600",
+            b"-1,9600",
+            b"\xff,9600",
+        ] {
+            assert!(TerminalSpeed::parse(bad).is_err(), "{:?}", bad);
+        }
+
+        // A bad reply leaves the previous speed alone
+        let mut tspeed = TerminalSpeedOption::new();
+        tspeed
+            .handle_subnegotiation(&TerminalSpeedOption::encode_speed(TerminalSpeed::new(
+                1200, 1200,
+            )))
+            .unwrap();
+        assert!(tspeed.handle_subnegotiation(b"\x00oops").is_err());
+        assert_eq!(tspeed.speed(), Some(TerminalSpeed::new(1200, 1200)));
+    }
+
+    #[test]
+    fn test_send_is_not_answered() {
+        let mut tspeed = TerminalSpeedOption::new();
+        assert_eq!(
+            tspeed.handle_subnegotiation(&[TSPEED_SEND]),
+            Err(OptionError::UnsupportedCommand(TSPEED_SEND))
+        );
+        assert!(tspeed.handle_subnegotiation(&[]).is_err());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut tspeed = TerminalSpeedOption::new();
+        tspeed
+            .handle_subnegotiation(&TerminalSpeedOption::encode_speed(TerminalSpeed::new(
+                300, 300,
+            )))
+            .unwrap();
+        tspeed.reset();
+        assert!(tspeed.speed().is_none());
+    }
+}
@@ -32,10 +32,35 @@
 //! - **XTERM-256COLOR**: Xterm with 256-color support
 //! - **SCREEN**: GNU Screen terminal multiplexer
 //! - **TMUX**: Modern terminal multiplexer
+//!
+//! ## Cycling and MTTS
+//!
+//! Each SEND asks for the client's next name; a client signals the end of
+//! its list by repeating the last one. MUD clients following the MTTS
+//! convention report a bit field as their final entry (`MTTS 137`), which
+//! carries flags such as 256-color, true color and screen reader support.
 
 use super::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+#[cfg(feature = "negotiation")]
+use crate::negotiation::Side;
 use crate::protocol::TelnetOption;
 
+/// MTTS flag: client supports ANSI color codes
+pub const MTTS_ANSI: u32 = 1;
+/// MTTS flag: client supports VT100 cursor control
+pub const MTTS_VT100: u32 = 2;
+/// MTTS flag: client supports UTF-8
+pub const MTTS_UTF8: u32 = 4;
+/// MTTS flag: client supports 256 colors
+pub const MTTS_256_COLORS: u32 = 8;
+/// MTTS flag: client is using a screen reader
+pub const MTTS_SCREEN_READER: u32 = 64;
+/// MTTS flag: client supports 24-bit color
+pub const MTTS_TRUECOLOR: u32 = 256;
+
+/// Most SEND requests made while cycling through a client's names
+const MAX_CYCLE_REQUESTS: u8 = 3;
+
 /// Terminal Type option handler
 #[derive(Debug, Clone)]
 pub struct TerminalTypeOption {
@@ -43,10 +68,19 @@ pub struct TerminalTypeOption {
     terminal_info: Option<TerminalInfo>,
     /// Whether we've received terminal type data
     has_data: bool,
+    /// MTTS flags, if the client reported them
+    mtts: Option<u32>,
+    /// SEND requests made so far in this cycle
+    requests: u8,
+    /// Last name received, to spot the end of the client's list
+    last_reply: Option<String>,
+    /// A SEND is out that the client hasn't answered
+    awaiting: bool,
 }
 
 /// Terminal information and capabilities
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TerminalInfo {
     /// Terminal type string (e.g., "XTERM", "VT100")
     pub terminal_type: String,
@@ -56,6 +90,7 @@ pub struct TerminalInfo {
 
 /// Terminal capabilities derived from terminal type
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TerminalCapabilities {
     /// Supports ANSI escape sequences
     pub ansi_support: bool,
@@ -72,7 +107,8 @@ pub struct TerminalCapabilities {
 }
 
 /// Color support levels
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColorSupport {
     /// No color support (monochrome)
     None,
@@ -92,6 +128,67 @@ impl TerminalTypeOption {
         Self {
             terminal_info: None,
             has_data: false,
+            mtts: None,
+            requests: 0,
+            last_reply: None,
+            awaiting: false,
+        }
+    }
+
+    /// Begin asking for the client's terminal names, returning the SEND payload
+    ///
+    /// Later IS replies automatically ask for the next name until the client
+    /// repeats itself, reports MTTS flags, or the request limit is reached.
+    pub fn start_cycle(&mut self) -> Vec<u8> {
+        self.requests = 1;
+        self.last_reply = None;
+        self.awaiting = true;
+        vec![SubNegotiationCommand::Send as u8]
+    }
+
+    /// Whether a cycle is still waiting for the client's next name
+    pub fn awaiting_reply(&self) -> bool {
+        self.awaiting
+    }
+
+    /// MTTS flags reported by the client, if any
+    pub fn mtts_flags(&self) -> Option<u32> {
+        self.mtts
+    }
+
+    /// Whether the client reported that a screen reader is in use
+    pub fn screen_reader(&self) -> bool {
+        self.mtts
+            .is_some_and(|flags| flags & MTTS_SCREEN_READER != 0)
+    }
+
+    /// Parse an `MTTS <flags>` entry
+    fn parse_mtts(value: &str) -> Option<u32> {
+        value.strip_prefix("MTTS ")?.trim().parse().ok()
+    }
+
+    /// Record MTTS flags and raise the detected capabilities to match
+    fn apply_mtts(&mut self, flags: u32) {
+        self.mtts = Some(flags);
+
+        let Some(info) = self.terminal_info.as_mut() else {
+            return;
+        };
+        let caps = &mut info.capabilities;
+
+        if flags & MTTS_ANSI != 0 {
+            caps.ansi_support = true;
+            caps.color_support = caps.color_support.max(ColorSupport::Basic8);
+        }
+        if flags & MTTS_VT100 != 0 {
+            caps.cursor_positioning = true;
+            caps.screen_clearing = true;
+        }
+        if flags & MTTS_256_COLORS != 0 {
+            caps.color_support = caps.color_support.max(ColorSupport::Color256);
+        }
+        if flags & MTTS_TRUECOLOR != 0 {
+            caps.color_support = ColorSupport::TrueColor;
         }
     }
 
@@ -144,7 +241,7 @@ impl TerminalTypeOption {
     }
 
     /// Detect terminal capabilities from terminal type string
-    fn detect_capabilities(terminal_type: &str) -> TerminalCapabilities {
+    pub(crate) fn detect_capabilities(terminal_type: &str) -> TerminalCapabilities {
         let type_lower = terminal_type.to_lowercase();
 
         match type_lower.as_str() {
@@ -170,6 +267,26 @@ impl TerminalTypeOption {
                 alternate_screen: true,
             },
 
+            // Mudlet renders 24-bit color in its own widget, without cursor control
+            t if t.starts_with("mudlet") => TerminalCapabilities {
+                ansi_support: true,
+                color_support: ColorSupport::TrueColor,
+                cursor_positioning: false,
+                screen_clearing: false,
+                character_attributes: true,
+                alternate_screen: false,
+            },
+
+            // TinTin++ runs inside a terminal and passes 256 colors through
+            t if t.starts_with("tintin") => TerminalCapabilities {
+                ansi_support: true,
+                color_support: ColorSupport::Color256,
+                cursor_positioning: true,
+                screen_clearing: true,
+                character_attributes: true,
+                alternate_screen: false,
+            },
+
             // Standard xterm/modern terminals
             t if t.contains("xterm") || t.contains("screen") || t.contains("tmux") => {
                 TerminalCapabilities {
@@ -236,9 +353,11 @@ impl TelnetOptionHandler for TerminalTypeOption {
             ));
         }
 
+        const IS: u8 = SubNegotiationCommand::Is as u8;
+        const SEND: u8 = SubNegotiationCommand::Send as u8;
         match data[0] {
-            // IS (1) - Terminal type response
-            1 => {
+            // IS (0) - Terminal type response
+            IS => {
                 if data.len() < 2 {
                     return Err(OptionError::InvalidData(
                         "Terminal type IS without data".to_string(),
@@ -246,14 +365,33 @@ impl TelnetOptionHandler for TerminalTypeOption {
                 }
 
                 let terminal_type = String::from_utf8_lossy(&data[1..]).to_string();
-                self.set_terminal_type(terminal_type);
+                let repeated = self.last_reply.as_deref() == Some(terminal_type.as_str());
+
+                if let Some(flags) = Self::parse_mtts(&terminal_type) {
+                    self.apply_mtts(flags);
+                } else if self.terminal_info.is_none() || self.requests == 0 {
+                    // The first name is the most specific one
+                    self.set_terminal_type(terminal_type.clone());
+                }
+                self.last_reply = Some(terminal_type);
+
+                // Keep cycling while the client has more names to give
+                if self.requests > 0
+                    && self.requests < MAX_CYCLE_REQUESTS
+                    && !repeated
+                    && self.mtts.is_none()
+                {
+                    self.requests += 1;
+                    return Ok(vec![SubNegotiationCommand::Send as u8]);
+                }
 
                 // No response needed for IS
+                self.awaiting = false;
                 Ok(vec![])
             }
 
-            // SEND (0) - Request for terminal type
-            0 => {
+            // SEND (1) - Request for terminal type
+            SEND => {
                 // This would be handled by the client side
                 // Server side doesn't typically respond to SEND
                 Err(OptionError::InvalidState(
@@ -272,18 +410,18 @@ impl TelnetOptionHandler for TerminalTypeOption {
         match command {
             SubNegotiationCommand::Send => {
                 // Generate SEND request
-                Ok(vec![0]) // SEND command
+                Ok(vec![SubNegotiationCommand::Send as u8])
             }
 
             SubNegotiationCommand::Is => {
                 // Generate IS response with terminal type
                 if let Some(info) = &self.terminal_info {
-                    let mut data = vec![1]; // IS command
+                    let mut data = vec![SubNegotiationCommand::Is as u8];
                     data.extend_from_slice(info.terminal_type.as_bytes());
                     Ok(data)
                 } else {
                     // Default terminal type if none set
-                    let mut data = vec![1]; // IS command  
+                    let mut data = vec![SubNegotiationCommand::Is as u8];
                     data.extend_from_slice(b"UNKNOWN");
                     Ok(data)
                 }
@@ -298,6 +436,53 @@ impl TelnetOptionHandler for TerminalTypeOption {
     fn reset(&mut self) {
         self.terminal_info = None;
         self.has_data = false;
+        self.mtts = None;
+        self.requests = 0;
+        self.last_reply = None;
+        self.awaiting = false;
+    }
+
+    /// Start the name cycle once the client agrees to send its type
+    #[cfg(feature = "negotiation")]
+    fn on_enabled(&mut self, side: Side) -> Vec<u8> {
+        match side {
+            Side::Remote => self.start_cycle(),
+            Side::Local => Vec::new(),
+        }
+    }
+
+    /// A client that stops sending its type won't answer a cycle in progress
+    #[cfg(feature = "negotiation")]
+    fn on_disabled(&mut self, side: Side) {
+        if side == Side::Remote {
+            self.awaiting = false;
+        }
+    }
+
+    /// The terminal type, then `"\nMTTS <flags>"` if the client sent them
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        let mut state = self.terminal_type()?.to_string();
+        if let Some(flags) = self.mtts {
+            state.push_str(&format!("\nMTTS {}", flags));
+        }
+        Some(state.into_bytes())
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), OptionError> {
+        let state = String::from_utf8_lossy(state);
+        let mut lines = state.lines();
+        let terminal_type = lines
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| {
+                OptionError::InvalidData("terminal type snapshot has no name".to_string())
+            })?;
+        self.reset();
+        self.set_terminal_type(terminal_type.to_string());
+        if let Some(flags) = lines.next().and_then(Self::parse_mtts) {
+            self.apply_mtts(flags);
+        }
+        Ok(())
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -366,18 +551,76 @@ mod tests {
         assert_eq!(caps.color_support, ColorSupport::Basic8);
     }
 
+    #[test]
+    fn test_mud_client_detection() {
+        let mut term_type = TerminalTypeOption::new();
+
+        term_type.set_terminal_type("MUDLET".to_string());
+        let caps = term_type.capabilities().unwrap();
+        assert_eq!(caps.color_support, ColorSupport::TrueColor);
+        assert!(!caps.cursor_positioning);
+
+        term_type.set_terminal_type("TINTIN++".to_string());
+        assert_eq!(term_type.color_support(), ColorSupport::Color256);
+    }
+
+    #[test]
+    fn test_cycle_collects_mtts() {
+        let mut term_type = TerminalTypeOption::new();
+        assert_eq!(term_type.start_cycle(), vec![1]);
+        assert!(term_type.awaiting_reply());
+
+        // Each new name asks for the next one
+        assert_eq!(term_type.handle_subnegotiation(b"\x00MUDLET"), Ok(vec![1]));
+        assert!(term_type.awaiting_reply());
+        assert_eq!(
+            term_type.handle_subnegotiation(b"\x00ANSI-TRUECOLOR"),
+            Ok(vec![1])
+        );
+        // MTTS ends the cycle: ANSI | UTF-8 | 256 colors | screen reader
+        assert_eq!(term_type.handle_subnegotiation(b"\x00MTTS 77"), Ok(vec![]));
+        assert!(!term_type.awaiting_reply());
+
+        assert_eq!(term_type.terminal_type(), Some("MUDLET"));
+        assert_eq!(term_type.mtts_flags(), Some(77));
+        assert!(term_type.screen_reader());
+    }
+
+    #[test]
+    fn test_cycle_stops_on_repeat() {
+        let mut term_type = TerminalTypeOption::new();
+        term_type.start_cycle();
+
+        assert_eq!(term_type.handle_subnegotiation(b"\x00VT100"), Ok(vec![1]));
+        assert_eq!(term_type.handle_subnegotiation(b"\x00VT100"), Ok(vec![]));
+        assert_eq!(term_type.mtts_flags(), None);
+        assert!(!term_type.screen_reader());
+    }
+
+    #[test]
+    fn test_mtts_raises_color_support() {
+        let mut term_type = TerminalTypeOption::new();
+        term_type.start_cycle();
+
+        term_type.handle_subnegotiation(b"\x00VT100").unwrap();
+        term_type.handle_subnegotiation(b"\x00MTTS 9").unwrap();
+
+        assert_eq!(term_type.color_support(), ColorSupport::Color256);
+        assert!(term_type.supports_ansi());
+    }
+
     #[test]
     fn test_subnegotiation_handling() {
         let mut term_type = TerminalTypeOption::new();
 
         // Test IS command
-        let data = vec![1, 88, 84, 69, 82, 77]; // IS "XTERM"
+        let data = vec![0, 88, 84, 69, 82, 77]; // IS "XTERM"
         let result = term_type.handle_subnegotiation(&data);
         assert!(result.is_ok());
         assert_eq!(term_type.terminal_type(), Some("XTERM"));
 
         // Test invalid SEND (server side shouldn't handle)
-        let data = vec![0];
+        let data = vec![1];
         let result = term_type.handle_subnegotiation(&data);
         assert!(result.is_err());
     }
@@ -389,14 +632,14 @@ mod tests {
         // Test SEND generation
         let result = term_type.generate_subnegotiation(SubNegotiationCommand::Send);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), vec![0]);
+        assert_eq!(result.unwrap(), vec![1]);
 
         // Test IS generation with terminal type
         term_type.set_terminal_type("XTERM".to_string());
         let result = term_type.generate_subnegotiation(SubNegotiationCommand::Is);
         assert!(result.is_ok());
         let data = result.unwrap();
-        assert_eq!(data[0], 1); // IS command
+        assert_eq!(data[0], 0); // IS command
         assert_eq!(&data[1..], b"XTERM");
     }
 
@@ -17,24 +17,161 @@
 //! - With option: `IAC <command> <option>` (e.g., IAC WILL ECHO)
 //! - Sub-negotiation: `IAC SB <option> <data...> IAC SE`
 //! - Escaped data: `IAC IAC` (represents data byte 255)
+//!
+//! ### Resource Limits
+//! A sub-negotiation is held until its IAC SE arrives, so a peer that sends
+//! IAC SB and never finishes could make the parser buffer without end.
+//! [`ParserLimits`] caps the payload of one sub-negotiation and the bytes
+//! held for unfinished sequences (64 KiB each by default), and its
+//! [`LimitPolicy`] decides what becomes of a sequence that goes over:
+//! truncated, dropped, or dropped and reported as an error. Overflows are
+//! listed in [`ParseResult::overflows`] whatever the policy.
+//!
+//! ```rust
+//! use telnet_negotiation::parser::{LimitPolicy, ParserLimits, TelnetParser};
+//!
+//! let mut parser = TelnetParser::with_limits(ParserLimits::new(4, LimitPolicy::Truncate));
+//! // IAC SB 99 "abcdef" IAC SE
+//! let result = parser.parse(b"\xff\xfa\x63abcdef\xff\xf0");
+//! assert_eq!(result.overflows.len(), 1);
+//! ```
+//!
+//! ### Chunked Sub-negotiations
+//! Some payloads are big by design: a GMCP room dump or a long MSSP list
+//! can run past any limit worth setting. An option set with
+//! [`TelnetParser::set_chunk_size`] is handed over in pieces as it
+//! arrives rather than held whole, so the parser never holds more than one
+//! chunk of it and the sub-negotiation limit doesn't apply.
+//!
+//! ```rust
+//! use telnet_negotiation::TelnetOption;
+//! use telnet_negotiation::parser::TelnetParser;
+//!
+//! let mut parser = TelnetParser::new();
+//! parser.set_chunk_size(TelnetOption::GMCP, Some(4));
+//! // IAC SB GMCP "abcdef" IAC SE
+//! let result = parser.parse(b"\xff\xfa\xc9abcdef\xff\xf0");
+//! let chunks: Vec<_> = result.chunks.iter().map(|c| (&c.data[..], c.last)).collect();
+//! assert_eq!(chunks, [(&b"abcd"[..], false), (&b"ef"[..], true)]);
+//! ```
 
+use crate::error::ProtocolViolation;
 use crate::protocol::{IAC, TelnetCommand, TelnetOption, TelnetSequence};
 
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Longest sub-negotiation payload a parser keeps by default
+pub const DEFAULT_MAX_SUBNEGOTIATION: usize = 64 * 1024;
+
+/// Bytes a parser holds for unfinished sequences by default
+pub const DEFAULT_MAX_PENDING: usize = 64 * 1024;
+
+/// What a parser does with a sub-negotiation that goes over its limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Keep the payload up to the limit, skip the rest to IAC SE and report
+    /// what was kept
+    Truncate,
+    /// Skip the whole sequence to IAC SE and report nothing
+    #[default]
+    Drop,
+    /// Skip it as for `Drop`; callers treat the overflow as a protocol
+    /// error (a `TelnetStream` read fails with `ErrorKind::InvalidData`)
+    Error,
+}
+
+/// Caps on what a peer can make a parser buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserLimits {
+    max_subnegotiation: Option<usize>,
+    max_pending: Option<usize>,
+    policy: LimitPolicy,
+}
+
+impl ParserLimits {
+    /// Keep at most `max_subnegotiation` bytes of any one sub-negotiation,
+    /// applying `policy` beyond that, with the default pending limit
+    pub fn new(max_subnegotiation: usize, policy: LimitPolicy) -> Self {
+        Self {
+            max_subnegotiation: Some(max_subnegotiation),
+            max_pending: Some(DEFAULT_MAX_PENDING),
+            policy,
+        }
+    }
+
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        Self {
+            max_subnegotiation: None,
+            max_pending: None,
+            policy: LimitPolicy::default(),
+        }
+    }
+
+    /// Hold at most `max_pending` bytes for unfinished sequences
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = Some(max_pending);
+        self
+    }
+
+    /// Longest sub-negotiation payload kept, or `None` when unlimited
+    pub fn max_subnegotiation(&self) -> Option<usize> {
+        self.max_subnegotiation
+    }
+
+    /// Most bytes held for unfinished sequences, or `None` when unlimited
+    pub fn max_pending(&self) -> Option<usize> {
+        self.max_pending
+    }
+
+    /// What happens past a limit
+    pub fn policy(&self) -> LimitPolicy {
+        self.policy
+    }
+
+    /// Payload bytes a sub-negotiation may hold with `pending` bytes
+    /// already held elsewhere
+    pub(crate) fn payload_room(&self, pending: usize) -> usize {
+        let subnegotiation = self.max_subnegotiation.unwrap_or(usize::MAX);
+        let pending = self
+            .max_pending
+            .map_or(usize::MAX, |max| max.saturating_sub(pending));
+        subnegotiation.min(pending)
+    }
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SUBNEGOTIATION, LimitPolicy::default())
+    }
+}
+
 /// Parser state for IAC sequence detection
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ParserState {
     /// Expecting normal data or IAC byte
     Data,
     /// Found IAC (255), expecting command byte
-    IAC,
+    Iac,
     /// Found command that requires option parameter
     Command(TelnetCommand),
     /// Processing sub-negotiation data until IAC SE
     SubNegotiation {
-        option: TelnetOption,
+        option: u8,
         data: Vec<u8>,
         /// True if we've seen IAC and expecting SE
         expecting_se: bool,
+        /// The payload was thrown away; skip to IAC SE and report nothing
+        discarding: bool,
+        /// The payload hit its limit; skip to IAC SE and report what was kept
+        truncated: bool,
+        /// Handed over in chunks of this size rather than held whole
+        chunk_size: Option<usize>,
+        /// Payload bytes already handed over in earlier chunks
+        offset: usize,
     },
 }
 
@@ -45,8 +182,31 @@ pub struct ParseResult {
     pub data: Vec<u8>,
     /// Telnet command sequences found in the stream
     pub sequences: Vec<TelnetSequence>,
+    /// For each sequence, how many bytes of `data` came before it, so the
+    /// two can be replayed in stream order
+    pub sequence_offsets: Vec<usize>,
     /// Number of bytes consumed from the input
     pub bytes_consumed: usize,
+    /// Options whose sub-negotiation went over the parser's limits in this
+    /// chunk, whichever [`LimitPolicy`] was applied
+    pub overflows: Vec<TelnetOption>,
+    /// Malformed sequences in this chunk, passed on as data rather than
+    /// rejected
+    pub violations: Vec<ProtocolViolation>,
+    /// Pieces of sub-negotiations for options set to arrive in chunks
+    pub chunks: Vec<SubnegotiationChunk>,
+}
+
+/// A piece of a sub-negotiation payload, for an option set with
+/// [`TelnetParser::set_chunk_size`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubnegotiationChunk {
+    pub option: TelnetOption,
+    pub data: Vec<u8>,
+    /// Where in the payload `data` starts; 0 for the first piece
+    pub offset: usize,
+    /// Whether IAC SE followed, so this is the end of the payload
+    pub last: bool,
 }
 
 /// Telnet command parser with stateful IAC sequence detection
@@ -55,6 +215,10 @@ pub struct TelnetParser {
     state: ParserState,
     /// Buffer for incomplete sequences that span multiple parse calls
     sequence_buffer: Vec<u8>,
+    limits: ParserLimits,
+    /// Options whose sub-negotiations are handed over in chunks, and the
+    /// size of each
+    chunk_sizes: Vec<(u8, usize)>,
 }
 
 impl Default for TelnetParser {
@@ -69,9 +233,55 @@ impl TelnetParser {
         Self {
             state: ParserState::Data,
             sequence_buffer: Vec::new(),
+            limits: ParserLimits::default(),
+            chunk_sizes: Vec::new(),
+        }
+    }
+
+    /// Create a parser that applies `limits` rather than the defaults
+    pub fn with_limits(limits: ParserLimits) -> Self {
+        Self {
+            limits,
+            ..Self::new()
         }
     }
 
+    /// Change the limits, from the next byte parsed on
+    pub fn set_limits(&mut self, limits: ParserLimits) {
+        self.limits = limits;
+    }
+
+    /// The limits in force
+    pub fn limits(&self) -> ParserLimits {
+        self.limits
+    }
+
+    /// Hand `option`'s sub-negotiations over `chunk_size` bytes at a time
+    /// as they arrive, or whole again with `None`
+    ///
+    /// Each piece comes as a [`ParseEvent::SubnegotiationChunk`], the last
+    /// one (possibly empty) once IAC SE arrives. The parser's
+    /// sub-negotiation limit doesn't apply to these payloads, since no
+    /// more than `chunk_size` bytes of one are ever held. Takes effect
+    /// from the next IAC SB; a chunk size of 0 is taken as 1.
+    pub fn set_chunk_size(&mut self, option: TelnetOption, chunk_size: Option<usize>) {
+        let code = option.to_byte();
+        self.chunk_sizes.retain(|&(chunked, _)| chunked != code);
+        if let Some(size) = chunk_size {
+            self.chunk_sizes.push((code, size.max(1)));
+        }
+    }
+
+    /// The chunk size set for `option`, if its sub-negotiations are
+    /// handed over in pieces
+    pub fn chunk_size(&self, option: TelnetOption) -> Option<usize> {
+        let code = option.to_byte();
+        self.chunk_sizes
+            .iter()
+            .find(|&&(chunked, _)| chunked == code)
+            .map(|&(_, size)| size)
+    }
+
     /// Parse a chunk of bytes, returning data and command sequences
     ///
     /// This method can be called repeatedly with chunks of data from a TCP stream.
@@ -98,165 +308,498 @@ impl TelnetParser {
     /// assert_eq!(result.sequences.len(), 1);
     /// ```
     pub fn parse(&mut self, input: &[u8]) -> ParseResult {
-        let mut data = Vec::new();
-        let mut sequences = Vec::new();
-        let mut pos = 0;
+        let mut result = ParseResult {
+            data: Vec::with_capacity(input.len()),
+            sequences: Vec::new(),
+            sequence_offsets: Vec::new(),
+            bytes_consumed: 0,
+            overflows: Vec::new(),
+            violations: Vec::new(),
+            chunks: Vec::new(),
+        };
+
+        let mut events = self.parse_borrowed(input);
+        for event in events.by_ref() {
+            match event {
+                ParseEvent::Data(bytes) => result.data.extend_from_slice(bytes),
+                ParseEvent::Sequence(sequence) => {
+                    result.sequence_offsets.push(result.data.len());
+                    result.sequences.push(sequence);
+                }
+                ParseEvent::Subnegotiation { option, data } => {
+                    result.sequence_offsets.push(result.data.len());
+                    result.sequences.push(TelnetSequence::SubNegotiation {
+                        option,
+                        data: data.into_owned(),
+                    });
+                }
+                ParseEvent::SubnegotiationChunk {
+                    option,
+                    data,
+                    offset,
+                    last,
+                } => result.chunks.push(SubnegotiationChunk {
+                    option,
+                    data: data.into_owned(),
+                    offset,
+                    last,
+                }),
+                ParseEvent::Overflow(option) => result.overflows.push(option),
+                ParseEvent::Violation(violation) => result.violations.push(violation),
+            }
+        }
+        result.bytes_consumed = events.bytes_consumed();
+        result
+    }
 
-        while pos < input.len() {
+    /// Parse a chunk of bytes without copying it, as a series of events
+    ///
+    /// Data comes back as slices of `input` and a sub-negotiation that
+    /// starts and ends within `input` with no IAC IAC in it is borrowed
+    /// too; only one that spans chunks or needs unescaping is copied. What
+    /// [`TelnetParser::parse`] collects into a [`ParseResult`], this hands
+    /// over one piece at a time, in stream order.
+    ///
+    /// Dropping the iterator early leaves the parser as if only
+    /// [`ParseEvents::bytes_consumed`] bytes had been given to it.
+    ///
+    /// # Example
+    /// ```rust
+    /// use telnet_negotiation::parser::{ParseEvent, TelnetParser};
+    ///
+    /// let mut parser = TelnetParser::new();
+    /// // "hi" IAC SB 201 "x" IAC SE "!"
+    /// let input = b"hi\xff\xfa\xc9x\xff\xf0!";
+    /// let events: Vec<ParseEvent> = parser.parse_borrowed(input).collect();
+    ///
+    /// assert_eq!(events[0], ParseEvent::Data(b"hi"));
+    /// assert!(matches!(
+    ///     &events[1],
+    ///     ParseEvent::Subnegotiation { data: std::borrow::Cow::Borrowed(b"x"), .. }
+    /// ));
+    /// assert_eq!(events[2], ParseEvent::Data(b"!"));
+    /// ```
+    pub fn parse_borrowed<'p, 'a>(&'p mut self, input: &'a [u8]) -> ParseEvents<'p, 'a> {
+        let room = self.limits.payload_room(self.sequence_buffer.len());
+        let policy = self.limits.policy();
+        ParseEvents {
+            parser: self,
+            input,
+            pos: 0,
+            room,
+            policy,
+            borrowed: None,
+            queued: None,
+        }
+    }
+
+    /// Get the current parser state (for debugging/testing)
+    pub fn state(&self) -> String {
+        format!("{:?}", self.state)
+    }
+
+    /// Reset parser to initial state (useful for new connections)
+    pub fn reset(&mut self) {
+        self.state = ParserState::Data;
+        self.sequence_buffer.clear();
+    }
+
+    /// Check if parser has buffered data from incomplete sequences
+    pub fn has_buffered_data(&self) -> bool {
+        // Parser is in the middle of a sequence if not in Data state
+        !matches!(self.state, ParserState::Data)
+    }
+
+    /// Bytes held for a sequence that hasn't ended yet, mostly the payload
+    /// of a sub-negotiation still waiting for IAC SE
+    pub fn buffered_len(&self) -> usize {
+        let subnegotiation = match &self.state {
+            ParserState::SubNegotiation { data, .. } => data.len(),
+            _ => 0,
+        };
+        subnegotiation + self.sequence_buffer.len()
+    }
+
+    /// Throw away the payload of an unfinished sub-negotiation
+    ///
+    /// The rest of it is skipped up to IAC SE and the sequence is never
+    /// reported, so no handler sees a truncated payload and none of it
+    /// leaks into the data. Returns false if no sub-negotiation was open.
+    pub fn discard_subnegotiation(&mut self) -> bool {
+        match &mut self.state {
+            ParserState::SubNegotiation {
+                data, discarding, ..
+            } => {
+                *data = Vec::new();
+                *discarding = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Each byte after an IAC, for passing unknown commands on as data
+/// without copying: `IAC_PAIRS[b]` is `[IAC, b]`
+static IAC_PAIRS: [[u8; 2]; 256] = {
+    let mut pairs = [[IAC, 0]; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        pairs[byte][1] = byte as u8;
+        byte += 1;
+    }
+    pairs
+};
+
+/// One piece of parsed input, borrowed from it where possible
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseEvent<'a> {
+    /// Data bytes for the application
+    Data(&'a [u8]),
+    /// A command, negotiation or IAC IAC
+    ///
+    /// IAC IAC comes as [`TelnetSequence::EscapedData`] followed by a
+    /// `Data` event holding the 255, so callers that only want the data
+    /// can skip every `Sequence`.
+    Sequence(TelnetSequence),
+    /// A complete sub-negotiation, borrowed when it lay within one chunk
+    /// and needed no unescaping
+    Subnegotiation {
+        option: TelnetOption,
+        data: Cow<'a, [u8]>,
+    },
+    /// A piece of a sub-negotiation for an option set with
+    /// [`TelnetParser::set_chunk_size`], starting `offset` bytes into the
+    /// payload
+    ///
+    /// `last` is set on the piece that IAC SE ended. A payload cut off by
+    /// a malformed sequence, or thrown away with
+    /// [`TelnetParser::discard_subnegotiation`], never gets one; the next
+    /// payload starts again at offset 0.
+    SubnegotiationChunk {
+        option: TelnetOption,
+        data: Cow<'a, [u8]>,
+        offset: usize,
+        last: bool,
+    },
+    /// A sub-negotiation for this option went over the parser's limits
+    Overflow(TelnetOption),
+    /// A malformed sequence, passed on as the data that follows
+    Violation(ProtocolViolation),
+}
+
+/// Iterator over the [`ParseEvent`]s in one chunk, from
+/// [`TelnetParser::parse_borrowed`]
+#[derive(Debug)]
+pub struct ParseEvents<'p, 'a> {
+    parser: &'p mut TelnetParser,
+    input: &'a [u8],
+    pos: usize,
+    room: usize,
+    policy: LimitPolicy,
+    /// Payload of an open sub-negotiation kept as `input[start..end]`
+    /// rather than copied
+    borrowed: Option<(usize, usize)>,
+    /// An event that follows the one just returned
+    queued: Option<ParseEvent<'a>>,
+}
+
+impl<'a> ParseEvents<'_, 'a> {
+    /// Bytes of the chunk parsed so far; all of it once the iterator is
+    /// exhausted
+    pub fn bytes_consumed(&self) -> usize {
+        self.pos
+    }
+
+    /// Copy a borrowed sub-negotiation payload into the parser, which
+    /// must happen before it can outlive the chunk or be added to
+    fn spill(&mut self) {
+        if let Some((start, end)) = self.borrowed.take()
+            && let ParserState::SubNegotiation { data, .. } = &mut self.parser.state
+        {
+            data.extend_from_slice(&self.input[start..end]);
+        }
+    }
+
+    /// Add `input[at..at + len]` to the open sub-negotiation's payload
+    /// unless it is already skipped, applying the policy if it doesn't
+    /// all fit
+    ///
+    /// Returns true on overflow.
+    fn push_payload(&mut self, at: usize, len: usize) -> bool {
+        let ParserState::SubNegotiation {
+            data,
+            discarding,
+            truncated,
+            chunk_size,
+            ..
+        } = &mut self.parser.state
+        else {
+            return false;
+        };
+        if *discarding || *truncated {
+            return false;
+        }
+
+        // A chunked payload is bounded by its chunk size instead
+        let room = if chunk_size.is_some() {
+            usize::MAX
+        } else {
+            self.room
+        };
+        let held = data.len() + self.borrowed.map_or(0, |(start, end)| end - start);
+        let fit = room.saturating_sub(held).min(len);
+        match &mut self.borrowed {
+            Some((_, end)) if *end == at => *end += fit,
+            _ => {
+                if let Some((start, end)) = self.borrowed.take() {
+                    data.extend_from_slice(&self.input[start..end]);
+                }
+                data.extend_from_slice(&self.input[at..at + fit]);
+            }
+        }
+        if fit == len {
+            return false;
+        }
+
+        match self.policy {
+            LimitPolicy::Truncate => {
+                *truncated = true;
+                self.spill();
+            }
+            LimitPolicy::Drop | LimitPolicy::Error => {
+                *data = Vec::new();
+                *discarding = true;
+                self.borrowed = None;
+            }
+        }
+        true
+    }
+
+    /// Take the open sub-negotiation's payload so far, borrowed if it all
+    /// lies in this chunk
+    fn take_payload(&mut self) -> Cow<'a, [u8]> {
+        let owned = match &mut self.parser.state {
+            ParserState::SubNegotiation { data, .. } => core::mem::take(data),
+            _ => Vec::new(),
+        };
+        match self.borrowed.take() {
+            Some((start, end)) if owned.is_empty() => Cow::Borrowed(&self.input[start..end]),
+            Some((start, end)) => {
+                let mut owned = owned;
+                owned.extend_from_slice(&self.input[start..end]);
+                Cow::Owned(owned)
+            }
+            None => Cow::Owned(owned),
+        }
+    }
+
+    /// Hand over the open sub-negotiation's payload so far if it is
+    /// chunked and has filled a chunk
+    fn take_full_chunk(&mut self, option: TelnetOption) -> Option<ParseEvent<'a>> {
+        let ParserState::SubNegotiation {
+            data,
+            discarding: false,
+            chunk_size: Some(size),
+            ..
+        } = &self.parser.state
+        else {
+            return None;
+        };
+        let held = data.len() + self.borrowed.map_or(0, |(start, end)| end - start);
+        if held < *size {
+            return None;
+        }
+
+        let data = self.take_payload();
+        let ParserState::SubNegotiation { offset, .. } = &mut self.parser.state else {
+            return None;
+        };
+        let start = *offset;
+        *offset += data.len();
+        self.borrowed = Some((self.pos, self.pos));
+        Some(ParseEvent::SubnegotiationChunk {
+            option,
+            data,
+            offset: start,
+            last: false,
+        })
+    }
+}
+
+impl<'a> Iterator for ParseEvents<'_, 'a> {
+    type Item = ParseEvent<'a>;
+
+    fn next(&mut self) -> Option<ParseEvent<'a>> {
+        if let Some(event) = self.queued.take() {
+            return Some(event);
+        }
+
+        let input = self.input;
+        while self.pos < input.len() {
+            let pos = self.pos;
             let byte = input[pos];
 
-            match &mut self.state {
+            match &mut self.parser.state {
                 ParserState::Data => {
-                    if byte == IAC {
-                        self.state = ParserState::IAC;
-                        pos += 1;
+                    // Everything up to the next IAC is data
+                    let end = input[pos..]
+                        .iter()
+                        .position(|&b| b == IAC)
+                        .map_or(input.len(), |i| pos + i);
+                    if end < input.len() {
+                        self.parser.state = ParserState::Iac;
+                        self.pos = end + 1;
                     } else {
-                        // Regular data byte
-                        data.push(byte);
-                        pos += 1;
+                        self.pos = end;
                     }
-                }
-
-                ParserState::IAC => {
-                    if pos + 1 > input.len() {
-                        // Not enough data for command, wait for more
-                        break;
+                    if end > pos {
+                        return Some(ParseEvent::Data(&input[pos..end]));
                     }
+                }
 
+                ParserState::Iac => {
+                    self.pos += 1;
+                    self.parser.state = ParserState::Data;
                     if byte == IAC {
                         // IAC IAC = escaped data byte 255
-                        sequences.push(TelnetSequence::EscapedData(255));
-                        data.push(255);
-                        self.state = ParserState::Data;
-                        pos += 1;
+                        self.queued = Some(ParseEvent::Data(&input[pos..=pos]));
+                        return Some(ParseEvent::Sequence(TelnetSequence::EscapedData(IAC)));
                     } else if let Some(command) = TelnetCommand::from_byte(byte) {
                         if command.requires_option() {
                             // Command needs option parameter
-                            self.state = ParserState::Command(command);
-                            pos += 1;
+                            self.parser.state = ParserState::Command(command);
                         } else {
                             // Simple command
-                            sequences.push(TelnetSequence::Command(command));
-                            self.state = ParserState::Data;
-                            pos += 1;
+                            return Some(ParseEvent::Sequence(TelnetSequence::Command(command)));
                         }
                     } else {
                         // Unknown command byte - treat as data and continue
-                        data.push(IAC);
-                        data.push(byte);
-                        self.state = ParserState::Data;
-                        pos += 1;
+                        self.queued = Some(ParseEvent::Data(&IAC_PAIRS[byte as usize]));
+                        return Some(ParseEvent::Violation(ProtocolViolation::UnknownCommand(
+                            byte,
+                        )));
                     }
                 }
 
                 ParserState::Command(command) => {
-                    if pos + 1 > input.len() {
-                        // Not enough data for option, wait for more
-                        break;
-                    }
-
-                    if *command == TelnetCommand::SB {
+                    let command = *command;
+                    self.pos += 1;
+                    if command == TelnetCommand::SB {
                         // Starting sub-negotiation - need option byte
-                        if let Some(option) = TelnetOption::from_byte(byte) {
-                            self.state = ParserState::SubNegotiation {
-                                option,
-                                data: Vec::new(),
-                                expecting_se: false,
-                            };
-                            pos += 1;
-                        } else {
-                            // Invalid option for SB - treat as data
-                            data.push(IAC);
-                            data.push(TelnetCommand::SB.to_byte());
-                            data.push(byte);
-                            self.state = ParserState::Data;
-                            pos += 1;
-                        }
+                        self.parser.state = ParserState::SubNegotiation {
+                            option: byte,
+                            data: Vec::new(),
+                            expecting_se: false,
+                            discarding: false,
+                            truncated: false,
+                            chunk_size: self.parser.chunk_size(TelnetOption::new(byte)),
+                            offset: 0,
+                        };
+                        self.borrowed = Some((self.pos, self.pos));
                     } else if command.is_negotiation_command() {
                         // Negotiation command needs option
-                        if let Some(option) = TelnetOption::from_byte(byte) {
-                            sequences.push(TelnetSequence::Negotiation {
-                                command: *command,
-                                option,
-                            });
-                            self.state = ParserState::Data;
-                            pos += 1;
-                        } else {
-                            // Invalid option - treat as data
-                            data.push(IAC);
-                            data.push(command.to_byte());
-                            data.push(byte);
-                            self.state = ParserState::Data;
-                            pos += 1;
-                        }
+                        self.parser.state = ParserState::Data;
+                        return Some(ParseEvent::Sequence(TelnetSequence::Negotiation {
+                            command,
+                            option: TelnetOption::new(byte),
+                        }));
                     } else {
                         // Command that requires option but isn't negotiation or SB
                         // This shouldn't happen with current command set, but handle gracefully
-                        data.push(IAC);
-                        data.push(command.to_byte());
-                        data.push(byte);
-                        self.state = ParserState::Data;
-                        pos += 1;
+                        self.parser.state = ParserState::Data;
+                        self.queued = Some(ParseEvent::Data(&input[pos..=pos]));
+                        return Some(ParseEvent::Data(&IAC_PAIRS[command.to_byte() as usize]));
                     }
                 }
 
                 ParserState::SubNegotiation {
                     option,
-                    data: sub_data,
+                    data,
                     expecting_se,
+                    discarding,
+                    chunk_size,
+                    offset,
+                    ..
                 } => {
+                    let option = TelnetOption::new(*option);
+                    let chunk_size = *chunk_size;
                     if *expecting_se {
+                        *expecting_se = false;
+                        self.pos += 1;
                         if byte == TelnetCommand::SE.to_byte() {
                             // Complete sub-negotiation sequence
-                            sequences.push(TelnetSequence::SubNegotiation {
-                                option: *option,
-                                data: sub_data.clone(),
-                            });
-                            self.state = ParserState::Data;
-                            pos += 1;
+                            let discarded = *discarding;
+                            let offset = *offset;
+                            let data = self.take_payload();
+                            self.parser.state = ParserState::Data;
+                            if discarded {
+                                continue;
+                            }
+                            if chunk_size.is_some() {
+                                return Some(ParseEvent::SubnegotiationChunk {
+                                    option,
+                                    data,
+                                    offset,
+                                    last: true,
+                                });
+                            }
+                            return Some(ParseEvent::Subnegotiation { option, data });
+                        } else if byte == IAC {
+                            // IAC IAC inside a sub-negotiation is a data byte 255
+                            if self.push_payload(pos, 1) {
+                                return Some(ParseEvent::Overflow(option));
+                            }
+                            if let Some(chunk) = self.take_full_chunk(option) {
+                                return Some(chunk);
+                            }
                         } else {
                             // Expected SE but got something else - malformed
                             // Add IAC and the byte as data, continue parsing
-                            data.push(IAC);
-                            data.push(byte);
-                            self.state = ParserState::Data;
-                            pos += 1;
+                            self.parser.state = ParserState::Data;
+                            self.borrowed = None;
+                            self.queued = Some(ParseEvent::Data(&IAC_PAIRS[byte as usize]));
+                            return Some(ParseEvent::Violation(
+                                ProtocolViolation::UnterminatedSubnegotiation { option, byte },
+                            ));
                         }
                     } else if byte == IAC {
                         // Might be end of sub-negotiation
                         *expecting_se = true;
-                        pos += 1;
+                        self.pos += 1;
                     } else {
-                        // Sub-negotiation data
-                        sub_data.push(byte);
-                        pos += 1;
+                        // Sub-negotiation data, up to the next IAC or the
+                        // end of the chunk being filled
+                        let mut len = input[pos..]
+                            .iter()
+                            .position(|&b| b == IAC)
+                            .unwrap_or(input.len() - pos);
+                        if let Some(size) = chunk_size.filter(|_| !*discarding) {
+                            let held =
+                                data.len() + self.borrowed.map_or(0, |(start, end)| end - start);
+                            len = len.min(size - held);
+                        }
+                        self.pos += len;
+                        if self.push_payload(pos, len) {
+                            return Some(ParseEvent::Overflow(option));
+                        }
+                        if let Some(chunk) = self.take_full_chunk(option) {
+                            return Some(chunk);
+                        }
                     }
                 }
             }
         }
 
-        ParseResult {
-            data,
-            sequences,
-            bytes_consumed: pos,
-        }
-    }
-
-    /// Get the current parser state (for debugging/testing)
-    pub fn state(&self) -> String {
-        format!("{:?}", self.state)
-    }
-
-    /// Reset parser to initial state (useful for new connections)
-    pub fn reset(&mut self) {
-        self.state = ParserState::Data;
-        self.sequence_buffer.clear();
+        self.spill();
+        None
     }
+}
 
-    /// Check if parser has buffered data from incomplete sequences
-    pub fn has_buffered_data(&self) -> bool {
-        // Parser is in the middle of a sequence if not in Data state
-        !matches!(self.state, ParserState::Data)
+impl Drop for ParseEvents<'_, '_> {
+    fn drop(&mut self) {
+        // The parser outlives the chunk, so it keeps its own copy
+        self.spill();
     }
 }
 
@@ -292,6 +835,19 @@ mod tests {
         assert_eq!(result.bytes_consumed, 2);
     }
 
+    #[test]
+    fn test_end_of_record_command() {
+        let mut parser = TelnetParser::new();
+        let result = parser.parse(&[b'>', 255, 239]); // ">" IAC EOR
+
+        assert_eq!(result.data, vec![b'>']);
+        assert_eq!(
+            result.sequences,
+            vec![TelnetSequence::Command(TelnetCommand::EOR)]
+        );
+        assert_eq!(result.sequence_offsets, vec![1]);
+    }
+
     #[test]
     fn test_negotiation_command() {
         let mut parser = TelnetParser::new();
@@ -311,6 +867,19 @@ mod tests {
         assert_eq!(result.bytes_consumed, 3);
     }
 
+    #[test]
+    fn test_sequence_offsets() {
+        let mut parser = TelnetParser::new();
+        // "ab" IAC WILL BINARY "c" IAC NOP
+        let input = vec![b'a', b'b', 255, 251, 0, b'c', 255, 241];
+
+        let result = parser.parse(&input);
+
+        assert_eq!(result.data, b"abc");
+        assert_eq!(result.sequences.len(), 2);
+        assert_eq!(result.sequence_offsets, vec![2, 3]);
+    }
+
     #[test]
     fn test_escaped_iac() {
         let mut parser = TelnetParser::new();
@@ -399,21 +968,48 @@ mod tests {
 
         let result = parser.parse(&input);
 
-        // Should treat as regular data
+        // Should treat as regular data, but say so
         assert_eq!(result.data, vec![255, 99]);
         assert_eq!(result.sequences.len(), 0);
+        assert_eq!(
+            result.violations,
+            vec![ProtocolViolation::UnknownCommand(99)]
+        );
     }
 
     #[test]
     fn test_invalid_option() {
         let mut parser = TelnetParser::new();
-        let input = vec![255, 251, 99]; // IAC WILL + invalid option
+        let input = vec![255, 251, 99]; // IAC WILL + unnamed option
 
         let result = parser.parse(&input);
 
-        // Should treat as regular data
-        assert_eq!(result.data, vec![255, 251, 99]);
-        assert_eq!(result.sequences.len(), 0);
+        // Reported like any other so the negotiator can answer it
+        assert!(result.data.is_empty());
+        assert_eq!(
+            result.sequences,
+            vec![TelnetSequence::Negotiation {
+                command: TelnetCommand::WILL,
+                option: TelnetOption::new(99)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unknown_subnegotiation() {
+        let mut parser = TelnetParser::new();
+        let input = vec![b'a', 255, 250, 99, 1, 2, 255, 240, b'b'];
+
+        let result = parser.parse(&input);
+
+        assert_eq!(result.data, b"ab");
+        assert_eq!(
+            result.sequences,
+            vec![TelnetSequence::SubNegotiation {
+                option: TelnetOption::new(99),
+                data: vec![1, 2]
+            }]
+        );
     }
 
     #[test]
@@ -474,4 +1070,260 @@ mod tests {
         let result = parser.parse(&[104, 101, 108, 108, 111]); // "hello"
         assert_eq!(result.data, b"hello");
     }
+
+    #[test]
+    fn test_buffered_len_counts_open_subnegotiation() {
+        let mut parser = TelnetParser::new();
+        assert_eq!(parser.buffered_len(), 0);
+
+        // IAC SB TTYPE IS "AN", no IAC SE yet
+        parser.parse(&[255, 250, 24, 0, 65, 78]);
+        assert_eq!(parser.buffered_len(), 3);
+        parser.parse(&[83, 73]);
+        assert_eq!(parser.buffered_len(), 5);
+
+        parser.parse(&[255, 240]);
+        assert_eq!(parser.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_discarded_subnegotiation_is_skipped() {
+        let mut parser = TelnetParser::new();
+        assert!(!parser.discard_subnegotiation());
+
+        parser.parse(&[255, 250, 24, 0, 65, 78]);
+        assert!(parser.discard_subnegotiation());
+        assert_eq!(parser.buffered_len(), 0);
+
+        // The rest of the payload is neither buffered nor passed on as data
+        let result = parser.parse(&[83, 73, 255, 240, 104, 105]);
+        assert_eq!(parser.buffered_len(), 0);
+        assert!(result.sequences.is_empty());
+        assert_eq!(result.data, b"hi");
+    }
+
+    #[test]
+    fn test_subnegotiation_limit_policies() {
+        // IAC SB 99 "abcdef" IAC SE "hi", split across two chunks
+        let chunks: [&[u8]; 2] = [&[255, 250, 99, b'a', b'b', b'c'], b"def\xff\xf0hi"];
+        let parse = |policy| {
+            let mut parser = TelnetParser::with_limits(ParserLimits::new(4, policy));
+            let results: Vec<ParseResult> = chunks.iter().map(|c| parser.parse(c)).collect();
+            assert!(parser.buffered_len() <= 4);
+            results
+        };
+
+        let results = parse(LimitPolicy::Truncate);
+        assert!(results[0].overflows.is_empty());
+        assert_eq!(results[1].overflows, vec![TelnetOption::new(99)]);
+        assert_eq!(
+            results[1].sequences,
+            vec![TelnetSequence::SubNegotiation {
+                option: TelnetOption::new(99),
+                data: b"abcd".to_vec(),
+            }]
+        );
+        assert_eq!(results[1].data, b"hi");
+
+        for policy in [LimitPolicy::Drop, LimitPolicy::Error] {
+            let results = parse(policy);
+            assert_eq!(results[1].overflows, vec![TelnetOption::new(99)]);
+            assert!(results[1].sequences.is_empty());
+            assert_eq!(results[1].data, b"hi");
+        }
+
+        // Exactly at the limit is fine
+        let mut parser = TelnetParser::with_limits(ParserLimits::new(6, LimitPolicy::Error));
+        let result = parser.parse(&[chunks[0], chunks[1]].concat());
+        assert!(result.overflows.is_empty());
+        assert_eq!(result.sequences.len(), 1);
+    }
+
+    #[test]
+    fn test_pending_limit_and_unlimited() {
+        let limits = ParserLimits::new(100, LimitPolicy::Drop).with_max_pending(2);
+        let mut parser = TelnetParser::with_limits(limits);
+        let result = parser.parse(&[255, 250, 24, 0, 65, 78]);
+        assert_eq!(result.overflows, vec![TelnetOption::TERMINAL_TYPE]);
+        assert_eq!(parser.buffered_len(), 0);
+
+        let mut parser = TelnetParser::with_limits(ParserLimits::unlimited());
+        let mut input = vec![255, 250, 201];
+        input.extend(std::iter::repeat_n(b'x', DEFAULT_MAX_SUBNEGOTIATION + 1));
+        let result = parser.parse(&input);
+        assert!(result.overflows.is_empty());
+        assert_eq!(parser.buffered_len(), DEFAULT_MAX_SUBNEGOTIATION + 1);
+
+        // The default stops at DEFAULT_MAX_SUBNEGOTIATION
+        let mut parser = TelnetParser::new();
+        assert_eq!(parser.parse(&input).overflows, vec![TelnetOption::new(201)]);
+        assert_eq!(parser.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_interrupted_subnegotiation_is_reported() {
+        let mut parser = TelnetParser::new();
+        // IAC SB NAWS 0 80 IAC 'x' - the peer never sends IAC SE
+        let result = parser.parse(&[255, 250, 31, 0, 80, 255, b'x', b'y']);
+
+        assert!(result.sequences.is_empty());
+        assert_eq!(result.data, vec![255, b'x', b'y']);
+        assert_eq!(
+            result.violations,
+            vec![ProtocolViolation::UnterminatedSubnegotiation {
+                option: TelnetOption::NAWS,
+                byte: b'x',
+            }]
+        );
+
+        // Well-formed input reports nothing
+        assert!(parser.parse(&[255, 251, 1, b'a']).violations.is_empty());
+    }
+
+    #[test]
+    fn test_parse_borrowed_borrows_what_it_can() {
+        let mut parser = TelnetParser::new();
+        // "ab" IAC IAC "c" IAC SB NAWS 0 80 0 24 IAC SE IAC 99 "d"
+        let input = [
+            b'a', b'b', 255, 255, b'c', 255, 250, 31, 0, 80, 0, 24, 255, 240, 255, 99, b'd',
+        ];
+        let events: Vec<ParseEvent> = parser.parse_borrowed(&input).collect();
+        assert_eq!(
+            events,
+            vec![
+                ParseEvent::Data(b"ab"),
+                ParseEvent::Sequence(TelnetSequence::EscapedData(255)),
+                ParseEvent::Data(&[255]),
+                ParseEvent::Data(b"c"),
+                ParseEvent::Subnegotiation {
+                    option: TelnetOption::NAWS,
+                    data: Cow::Borrowed(&[0, 80, 0, 24]),
+                },
+                ParseEvent::Violation(ProtocolViolation::UnknownCommand(99)),
+                ParseEvent::Data(&[255, 99]),
+                ParseEvent::Data(b"d"),
+            ]
+        );
+        assert!(matches!(
+            &events[4],
+            ParseEvent::Subnegotiation {
+                data: Cow::Borrowed(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_borrowed_copies_split_and_escaped_payloads() {
+        let mut parser = TelnetParser::new();
+        // IAC SB 201 "ab" | "c" IAC IAC "d" IAC SE
+        assert_eq!(
+            parser.parse_borrowed(&[255, 250, 201, b'a', b'b']).count(),
+            0
+        );
+        assert_eq!(parser.buffered_len(), 2);
+
+        let events: Vec<ParseEvent> = parser
+            .parse_borrowed(&[b'c', 255, 255, b'd', 255, 240])
+            .collect();
+        match &events[..] {
+            [ParseEvent::Subnegotiation { option, data }] => {
+                assert_eq!(*option, TelnetOption::new(201));
+                assert!(matches!(data, Cow::Owned(_)));
+                assert_eq!(&data[..], &[b'a', b'b', b'c', 255, b'd']);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Stopping early keeps what was borrowed so far
+        let input = [b'x', 255, 250, 201, b'e', b'f', 255, 240];
+        let mut events = parser.parse_borrowed(&input);
+        assert_eq!(events.next(), Some(ParseEvent::Data(b"x")));
+        assert_eq!(events.bytes_consumed(), 2);
+        drop(events);
+        let result = parser.parse(&input[2..]);
+        assert_eq!(
+            result.sequences,
+            vec![TelnetSequence::SubNegotiation {
+                option: TelnetOption::new(201),
+                data: b"ef".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_subnegotiation_iac_round_trip() {
+        // GMCP with 255 at the start, the end and twice in a row
+        let mut payload = vec![255];
+        payload.extend_from_slice(b"Char.Vitals {\"hp\":");
+        payload.extend_from_slice(&[255, 255]);
+        payload.extend_from_slice(b"}");
+        payload.push(255);
+        let sent = TelnetSequence::SubNegotiation {
+            option: TelnetOption::GMCP,
+            data: payload,
+        };
+        let mut wire = sent.to_bytes();
+        wire.extend_from_slice(b"ok");
+        assert_eq!(wire.iter().filter(|&&b| b == IAC).count(), 2 * 4 + 2);
+
+        let mut parser = TelnetParser::new();
+        let result = parser.parse(&wire);
+        assert_eq!(result.sequences, vec![sent.clone()]);
+        assert_eq!(result.data, b"ok");
+
+        // The same, split after every byte
+        let mut parser = TelnetParser::new();
+        let mut sequences = Vec::new();
+        let mut data = Vec::new();
+        for byte in &wire {
+            let result = parser.parse(std::slice::from_ref(byte));
+            sequences.extend(result.sequences);
+            data.extend(result.data);
+        }
+        assert_eq!(sequences, vec![sent]);
+        assert_eq!(data, b"ok");
+    }
+
+    #[test]
+    fn test_chunked_subnegotiation() {
+        let mut parser = TelnetParser::with_limits(ParserLimits::new(4, LimitPolicy::Error));
+        parser.set_chunk_size(TelnetOption::GMCP, Some(3));
+        assert_eq!(parser.chunk_size(TelnetOption::GMCP), Some(3));
+
+        // IAC SB GMCP "ab" IAC IAC "cdefg" | "h" IAC SE "!", split mid-payload
+        let result = parser.parse(&[255, 250, 201, b'a', b'b', 255, 255, b'c', b'd', b'e', b'f']);
+        assert!(result.overflows.is_empty());
+        assert_eq!(parser.buffered_len(), 1);
+        let result2 = parser.parse(&[b'g', b'h', 255, 240, b'!']);
+        assert_eq!(result2.data, b"!");
+
+        let chunks: Vec<(&[u8], usize, bool)> = result
+            .chunks
+            .iter()
+            .chain(&result2.chunks)
+            .map(|chunk| (&chunk.data[..], chunk.offset, chunk.last))
+            .collect();
+        assert_eq!(
+            chunks,
+            [
+                (&[b'a', b'b', 255][..], 0, false),
+                (&b"cde"[..], 3, false),
+                (&b"fgh"[..], 6, false),
+                (&b""[..], 9, true),
+            ]
+        );
+
+        // Thrown away part way, the rest is skipped without a last chunk
+        let events: Vec<ParseEvent> = parser.parse_borrowed(&[255, 250, 201, b'x']).collect();
+        assert!(events.is_empty());
+        assert!(parser.discard_subnegotiation());
+        assert!(parser.parse(b"yz\xff\xf0").chunks.is_empty());
+
+        // Other options are still held whole and limited
+        parser.set_chunk_size(TelnetOption::GMCP, None);
+        let result = parser.parse(b"\xff\xfa\xc9abcdef\xff\xf0");
+        assert!(result.chunks.is_empty());
+        assert_eq!(result.overflows, vec![TelnetOption::GMCP]);
+    }
 }
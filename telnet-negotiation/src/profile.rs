@@ -0,0 +1,161 @@
+//! # Negotiation Profiles
+//!
+//! A [`NegotiationProfile`] lists the options a server asks for when a
+//! connection opens, in the order it asks. Servers with different
+//! audiences want different greetings: a board for dumb terminals has no
+//! use for TERMINAL-TYPE, while one for MUD clients wants MXP and MSP too.
+//!
+//! ```rust
+//! use telnet_negotiation::{NegotiationProfile, TelnetOption};
+//!
+//! let profile: NegotiationProfile = "naws, suppress-go-ahead, mxp".parse().unwrap();
+//! assert_eq!(profile.options()[0], TelnetOption::NAWS);
+//! assert_eq!(profile.to_string(), "NAWS, SUPPRESS_GO_AHEAD, MXP");
+//! ```
+//!
+//! [`TelnetStream::apply_profile`](crate::TelnetStream::apply_profile)
+//! sends the requests. Only options the stream knows how to ask for can be
+//! in a profile: those with a `request_*` method, and any option with a
+//! handler registered through
+//! [`TelnetStream::register_option_handler`](crate::TelnetStream::register_option_handler),
+//! which is asked for with IAC DO. [`NegotiationProfile::check`] tests a
+//! profile against the built-in set before any stream exists.
+
+use crate::protocol::TelnetOption;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Options a stream can ask for without a custom handler
+pub const BUILT_IN_REQUESTS: &[TelnetOption] = &[
+    TelnetOption::BINARY,
+    TelnetOption::SUPPRESS_GO_AHEAD,
+    TelnetOption::END_OF_RECORD,
+    TelnetOption::TERMINAL_TYPE,
+    TelnetOption::NAWS,
+    TelnetOption::TERMINAL_SPEED,
+    TelnetOption::SEND_LOCATION,
+    TelnetOption::NEW_ENVIRON,
+    #[cfg(feature = "options-mud")]
+    TelnetOption::MXP,
+    #[cfg(feature = "options-mud")]
+    TelnetOption::MSP,
+];
+
+/// Options to request at connect, in order
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NegotiationProfile {
+    options: Vec<TelnetOption>,
+}
+
+impl NegotiationProfile {
+    /// A profile asking for `options` in the order given, each once
+    pub fn new(options: impl IntoIterator<Item = TelnetOption>) -> Self {
+        let mut profile = Self::default();
+        for option in options {
+            if !profile.options.contains(&option) {
+                profile.options.push(option);
+            }
+        }
+        profile
+    }
+
+    /// The options, in the order they are requested
+    pub fn options(&self) -> &[TelnetOption] {
+        &self.options
+    }
+
+    /// Whether `option` is in the profile
+    pub fn contains(&self, option: TelnetOption) -> bool {
+        self.options.contains(&option)
+    }
+
+    /// Check every option is one a stream can ask for without a custom
+    /// handler, returning the first that isn't
+    pub fn check(&self) -> Result<(), TelnetOption> {
+        match self
+            .options
+            .iter()
+            .find(|option| !BUILT_IN_REQUESTS.contains(option))
+        {
+            Some(&option) => Err(option),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reads a comma-separated list of option names (`NAWS`, `new-environ`,
+/// any case) or decimal option numbers
+impl FromStr for NegotiationProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = Vec::new();
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let option = match item.parse::<u8>() {
+                Ok(byte) => TelnetOption::new(byte),
+                Err(_) => TelnetOption::from_name(item)
+                    .ok_or_else(|| format!("unknown telnet option '{}'", item))?,
+            };
+            options.push(option);
+        }
+        Ok(Self::new(options))
+    }
+}
+
+impl fmt::Display for NegotiationProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, option) in self.options.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            match option.name() {
+                Some(name) => f.write_str(name)?,
+                None => write!(f, "{}", option.to_byte())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_names_numbers_and_duplicates() {
+        let profile: NegotiationProfile =
+            "terminal-type, NAWS, 24, new_environ, 150".parse().unwrap();
+        assert_eq!(
+            profile.options(),
+            &[
+                TelnetOption::TERMINAL_TYPE,
+                TelnetOption::NAWS,
+                TelnetOption::NEW_ENVIRON,
+                TelnetOption::new(150),
+            ]
+        );
+        assert_eq!(profile.to_string(), "TERMINAL_TYPE, NAWS, NEW_ENVIRON, 150");
+        assert_eq!(
+            profile.to_string().parse::<NegotiationProfile>(),
+            Ok(profile)
+        );
+
+        assert!("naws, frobnicate".parse::<NegotiationProfile>().is_err());
+        assert!(
+            "".parse::<NegotiationProfile>()
+                .unwrap()
+                .options()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_check_against_built_in_requests() {
+        let profile = NegotiationProfile::new([TelnetOption::NAWS, TelnetOption::BINARY]);
+        assert_eq!(profile.check(), Ok(()));
+
+        let profile = NegotiationProfile::new([TelnetOption::NAWS, TelnetOption::GMCP]);
+        assert_eq!(profile.check(), Err(TelnetOption::GMCP));
+    }
+}
@@ -20,6 +20,10 @@
 //! Sub-options use: `IAC SB <option> <parameters...> IAC SE`
 //! This is crucial for MUSH/MUD protocols that send complex data.
 
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
 /// IAC - Interpret As Command (RFC 854, Section 4)
 ///
 /// The IAC byte (255/0xFF) indicates that the next byte(s) should be interpreted
@@ -34,8 +38,14 @@ pub const IAC: u8 = 255;
 /// These commands follow the IAC byte to indicate specific protocol operations.
 /// Each command has a specific purpose and may require additional parameters.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum TelnetCommand {
+    /// End of Record (RFC 885) - marks the end of a record, such as a prompt
+    /// Only sent once END_OF_RECORD is enabled
+    /// Format: IAC EOR
+    EOR = 239,
+
     /// End of subnegotiation parameters (RFC 855)
     /// Used with SB to encapsulate option-specific parameters
     /// Format: IAC SB <option> <data...> IAC SE
@@ -121,6 +131,7 @@ impl TelnetCommand {
     /// ```
     pub fn from_byte(byte: u8) -> Option<Self> {
         match byte {
+            239 => Some(TelnetCommand::EOR),
             240 => Some(TelnetCommand::SE),
             241 => Some(TelnetCommand::NOP),
             242 => Some(TelnetCommand::DM),
@@ -171,278 +182,319 @@ impl TelnetCommand {
     }
 }
 
-/// Standard Telnet Options (RFC assignments and common extensions)
+/// Telnet option code
 ///
-/// These options can be negotiated between client and server to enable
-/// various protocol features. Each option has specific behavior defined
-/// in its respective RFC.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[repr(u8)]
-#[allow(non_camel_case_types)] // Protocol constants traditionally use SCREAMING_SNAKE_CASE
-pub enum TelnetOption {
+/// Any byte is an option: the RFC assignments and common extensions have
+/// names below, and [`TelnetOption::new`] makes one for anything else, such
+/// as a MUD's private option. Each option has specific behavior defined in
+/// its respective RFC.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TelnetOption(u8);
+
+#[allow(non_upper_case_globals)]
+impl TelnetOption {
     /// Binary Transmission (RFC 856)
     /// Allows 8-bit binary data transmission instead of 7-bit ASCII
     /// Essential for file transfers and binary protocols
-    BINARY = 0,
+    pub const BINARY: TelnetOption = TelnetOption(0);
 
     /// Echo (RFC 857)
     /// Controls which side echoes typed characters
     /// Critical for password input and line editing
-    ECHO = 1,
+    pub const ECHO: TelnetOption = TelnetOption(1);
 
     /// Reconnection (RFC 671)
     /// Rarely implemented - allows reconnection after connection loss
-    RECONNECTION = 2,
+    pub const RECONNECTION: TelnetOption = TelnetOption(2);
 
     /// Suppress Go Ahead (RFC 858)
     /// Disables the Go Ahead signal for full-duplex operation
     /// Most modern telnet sessions negotiate this
-    SUPPRESS_GO_AHEAD = 3,
+    pub const SUPPRESS_GO_AHEAD: TelnetOption = TelnetOption(3);
 
     /// Approximate Message Size Negotiation (RFC 1043)
     /// Allows negotiation of maximum message sizes
-    APPROX_MESSAGE_SIZE = 4,
+    pub const APPROX_MESSAGE_SIZE: TelnetOption = TelnetOption(4);
 
     /// Status (RFC 859)
     /// Allows querying the status of telnet options
-    STATUS = 5,
+    pub const STATUS: TelnetOption = TelnetOption(5);
 
     /// Timing Mark (RFC 860)
     /// Provides a timing reference in the data stream
-    TIMING_MARK = 6,
+    pub const TIMING_MARK: TelnetOption = TelnetOption(6);
 
     /// Remote Controlled Trans and Echo (RFC 726)
     /// Legacy option for remote echo control
-    RCTE = 7,
+    pub const RCTE: TelnetOption = TelnetOption(7);
 
     /// Output Line Width (RFC 20)
     /// Negotiates output line width - largely obsolete
-    OUTPUT_LINE_WIDTH = 8,
+    pub const OUTPUT_LINE_WIDTH: TelnetOption = TelnetOption(8);
 
     /// Output Page Size (RFC 20)
     /// Negotiates output page height - largely obsolete
-    OUTPUT_PAGE_SIZE = 9,
+    pub const OUTPUT_PAGE_SIZE: TelnetOption = TelnetOption(9);
 
     /// Output Carriage-Return Disposition (RFC 652)
     /// Controls how carriage returns are handled
-    NAOCRD = 10,
+    pub const NAOCRD: TelnetOption = TelnetOption(10);
 
     /// Output Horizontal Tab Stops (RFC 653)
     /// Negotiates horizontal tab positions
-    NAOHTS = 11,
+    pub const NAOHTS: TelnetOption = TelnetOption(11);
 
     /// Output Horizontal Tab Disposition (RFC 654)
     /// Controls how horizontal tabs are handled
-    NAOHTD = 12,
+    pub const NAOHTD: TelnetOption = TelnetOption(12);
 
     /// Output Form Feed Disposition (RFC 655)
     /// Controls how form feed characters are handled
-    NAOFFD = 13,
+    pub const NAOFFD: TelnetOption = TelnetOption(13);
 
     /// Output Vertical Tab Stops (RFC 656)
     /// Negotiates vertical tab positions
-    NAOVTS = 14,
+    pub const NAOVTS: TelnetOption = TelnetOption(14);
 
     /// Output Vertical Tab Disposition (RFC 657)
     /// Controls how vertical tabs are handled
-    NAOVTD = 15,
+    pub const NAOVTD: TelnetOption = TelnetOption(15);
 
     /// Output Linefeed Disposition (RFC 658)
     /// Controls how line feeds are handled
-    NAOLFD = 16,
+    pub const NAOLFD: TelnetOption = TelnetOption(16);
 
     /// Extended ASCII (RFC 698)
     /// Allows extended ASCII character set usage
-    EXTEND_ASCII = 17,
+    pub const EXTEND_ASCII: TelnetOption = TelnetOption(17);
 
     /// Logout (RFC 727)
     /// Provides graceful logout mechanism
-    LOGOUT = 18,
+    pub const LOGOUT: TelnetOption = TelnetOption(18);
 
     /// Byte Macro (RFC 735)
     /// Allows definition of byte sequences as macros
-    BYTE_MACRO = 19,
+    pub const BYTE_MACRO: TelnetOption = TelnetOption(19);
 
     /// Data Entry Terminal (RFC 1043)
     /// DET option for forms-based applications
-    DATA_ENTRY_TERMINAL = 20,
+    pub const DATA_ENTRY_TERMINAL: TelnetOption = TelnetOption(20);
 
     /// SUPDUP (RFC 736)
     /// Support for SUPDUP protocol
-    SUPDUP = 21,
+    pub const SUPDUP: TelnetOption = TelnetOption(21);
 
     /// SUPDUP Output (RFC 749)
     /// SUPDUP output control
-    SUPDUP_OUTPUT = 22,
+    pub const SUPDUP_OUTPUT: TelnetOption = TelnetOption(22);
 
     /// Send Location (RFC 779)
     /// Allows client to send its location
-    SEND_LOCATION = 23,
+    pub const SEND_LOCATION: TelnetOption = TelnetOption(23);
 
     /// Terminal Type (RFC 1091)
     /// Negotiates client terminal type - very commonly used
     /// Essential for proper screen formatting and capabilities
-    TERMINAL_TYPE = 24,
+    pub const TERMINAL_TYPE: TelnetOption = TelnetOption(24);
 
     /// End of Record (RFC 885)
     /// Marks record boundaries in data stream
-    END_OF_RECORD = 25,
+    pub const END_OF_RECORD: TelnetOption = TelnetOption(25);
 
     /// TACACS User Identification (RFC 927)
     /// User identification for TACACS
-    TACACS_USER_ID = 26,
+    pub const TACACS_USER_ID: TelnetOption = TelnetOption(26);
 
     /// Output Marking (RFC 933)
     /// Provides output marking capabilities
-    OUTPUT_MARKING = 27,
+    pub const OUTPUT_MARKING: TelnetOption = TelnetOption(27);
 
     /// Terminal Location Number (RFC 946)
     /// Negotiates terminal location number
-    TERMINAL_LOCATION = 28,
+    pub const TERMINAL_LOCATION: TelnetOption = TelnetOption(28);
 
     /// Telnet 3270 Regime (RFC 1041)
     /// Support for IBM 3270 terminal emulation
-    TELNET_3270 = 29,
+    pub const TELNET_3270: TelnetOption = TelnetOption(29);
 
     /// X.3 PAD (RFC 1053)
     /// Support for X.3 PAD functionality
-    X3_PAD = 30,
+    pub const X3_PAD: TelnetOption = TelnetOption(30);
 
     /// Negotiate About Window Size (RFC 1073)
     /// Negotiates terminal window dimensions
     /// Very commonly used for responsive display formatting
-    NAWS = 31,
+    pub const NAWS: TelnetOption = TelnetOption(31);
 
     /// Terminal Speed (RFC 1079)
     /// Negotiates terminal/connection speed
-    TERMINAL_SPEED = 32,
+    pub const TERMINAL_SPEED: TelnetOption = TelnetOption(32);
 
     /// Remote Flow Control (RFC 1372)
     /// Negotiates flow control mechanisms
-    TOGGLE_FLOW_CONTROL = 33,
+    pub const TOGGLE_FLOW_CONTROL: TelnetOption = TelnetOption(33);
 
     /// Linemode (RFC 1184)
     /// Enables line-at-a-time editing mode
-    LINEMODE = 34,
+    pub const LINEMODE: TelnetOption = TelnetOption(34);
 
     /// X Display Location (RFC 1096)
     /// Negotiates X11 display location
-    X_DISPLAY_LOCATION = 35,
+    pub const X_DISPLAY_LOCATION: TelnetOption = TelnetOption(35);
 
     /// Environment Option (RFC 1408, obsoleted by RFC 1571)
     /// Legacy environment variable passing
-    OLD_ENVIRON = 36,
+    pub const OLD_ENVIRON: TelnetOption = TelnetOption(36);
 
     /// Authentication (RFC 2941)
     /// Provides authentication mechanisms
-    AUTHENTICATION = 37,
+    pub const AUTHENTICATION: TelnetOption = TelnetOption(37);
 
     /// Encryption (RFC 2946)
     /// Provides data encryption capabilities
-    ENCRYPT = 38,
+    pub const ENCRYPT: TelnetOption = TelnetOption(38);
 
     /// New Environment (RFC 1571)
     /// Modern environment variable negotiation
-    NEW_ENVIRON = 39,
+    pub const NEW_ENVIRON: TelnetOption = TelnetOption(39);
+
+    /// START_TLS (draft-altman-telnet-starttls)
+    /// Upgrades the connection to TLS without changing ports
+    pub const START_TLS: TelnetOption = TelnetOption(46);
 
     // Common MUD/MUSH Extensions (non-RFC, but widely used)
     /// MUD Client Compression Protocol v1
     /// Compresses data stream to reduce bandwidth
     /// Widely supported in MUD clients
-    MCCP1 = 85,
+    pub const MCCP1: TelnetOption = TelnetOption(85);
 
     /// MUD Client Compression Protocol v2  
     /// Improved compression protocol
     /// More widely adopted than MCCP1
-    MCCP2 = 86,
+    pub const MCCP2: TelnetOption = TelnetOption(86);
+
+    /// MUD Sound Protocol
+    /// Sound and music cues sent in-band as !!SOUND(...) triggers
+    pub const MSP: TelnetOption = TelnetOption(90);
 
     /// MUD eXtension Protocol
     /// Allows HTML-like markup in MUD text
     /// Enables rich formatting, links, images
-    MXP = 91,
+    pub const MXP: TelnetOption = TelnetOption(91);
 
     /// MUD Server Status Protocol
     /// Provides server status information
-    MSSP = 70,
+    pub const MSSP: TelnetOption = TelnetOption(70);
 
     /// Achaea Telnet Client Protocol
     /// Game-specific protocol for Achaea MUD
-    ATCP = 200,
+    pub const ATCP: TelnetOption = TelnetOption(200);
 
     /// Generic MUD Communication Protocol
     /// JSON-based out-of-band communication
     /// Very popular in modern MUD development
-    GMCP = 201,
+    pub const GMCP: TelnetOption = TelnetOption(201);
 
     /// MUD Server Data Protocol  
     /// Key-value based out-of-band data
     /// Alternative to GMCP
-    MSDP = 69,
+    pub const MSDP: TelnetOption = TelnetOption(69);
 }
 
 impl TelnetOption {
+    /// The option for `byte`, named or not
+    pub const fn new(byte: u8) -> Self {
+        TelnetOption(byte)
+    }
+
     /// Convert a byte to a TelnetOption if it represents a known option
+    ///
+    /// Use [`TelnetOption::new`] to take any byte.
     pub fn from_byte(byte: u8) -> Option<Self> {
-        match byte {
-            0 => Some(TelnetOption::BINARY),
-            1 => Some(TelnetOption::ECHO),
-            2 => Some(TelnetOption::RECONNECTION),
-            3 => Some(TelnetOption::SUPPRESS_GO_AHEAD),
-            4 => Some(TelnetOption::APPROX_MESSAGE_SIZE),
-            5 => Some(TelnetOption::STATUS),
-            6 => Some(TelnetOption::TIMING_MARK),
-            7 => Some(TelnetOption::RCTE),
-            8 => Some(TelnetOption::OUTPUT_LINE_WIDTH),
-            9 => Some(TelnetOption::OUTPUT_PAGE_SIZE),
-            10 => Some(TelnetOption::NAOCRD),
-            11 => Some(TelnetOption::NAOHTS),
-            12 => Some(TelnetOption::NAOHTD),
-            13 => Some(TelnetOption::NAOFFD),
-            14 => Some(TelnetOption::NAOVTS),
-            15 => Some(TelnetOption::NAOVTD),
-            16 => Some(TelnetOption::NAOLFD),
-            17 => Some(TelnetOption::EXTEND_ASCII),
-            18 => Some(TelnetOption::LOGOUT),
-            19 => Some(TelnetOption::BYTE_MACRO),
-            20 => Some(TelnetOption::DATA_ENTRY_TERMINAL),
-            21 => Some(TelnetOption::SUPDUP),
-            22 => Some(TelnetOption::SUPDUP_OUTPUT),
-            23 => Some(TelnetOption::SEND_LOCATION),
-            24 => Some(TelnetOption::TERMINAL_TYPE),
-            25 => Some(TelnetOption::END_OF_RECORD),
-            26 => Some(TelnetOption::TACACS_USER_ID),
-            27 => Some(TelnetOption::OUTPUT_MARKING),
-            28 => Some(TelnetOption::TERMINAL_LOCATION),
-            29 => Some(TelnetOption::TELNET_3270),
-            30 => Some(TelnetOption::X3_PAD),
-            31 => Some(TelnetOption::NAWS),
-            32 => Some(TelnetOption::TERMINAL_SPEED),
-            33 => Some(TelnetOption::TOGGLE_FLOW_CONTROL),
-            34 => Some(TelnetOption::LINEMODE),
-            35 => Some(TelnetOption::X_DISPLAY_LOCATION),
-            36 => Some(TelnetOption::OLD_ENVIRON),
-            37 => Some(TelnetOption::AUTHENTICATION),
-            38 => Some(TelnetOption::ENCRYPT),
-            39 => Some(TelnetOption::NEW_ENVIRON),
-            69 => Some(TelnetOption::MSDP),
-            70 => Some(TelnetOption::MSSP),
-            85 => Some(TelnetOption::MCCP1),
-            86 => Some(TelnetOption::MCCP2),
-            91 => Some(TelnetOption::MXP),
-            200 => Some(TelnetOption::ATCP),
-            201 => Some(TelnetOption::GMCP),
-            _ => None,
-        }
+        let option = TelnetOption(byte);
+        option.name().map(|_| option)
+    }
+
+    /// Look up a named option, ignoring case and accepting `-` for `_`
+    ///
+    /// `"naws"`, `"Terminal-Type"` and `"GMCP"` all resolve; a name this
+    /// crate doesn't know gives `None`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX).map(TelnetOption).find(|option| {
+            option.name().is_some_and(|known| {
+                known.len() == name.len()
+                    && known
+                        .bytes()
+                        .zip(name.bytes())
+                        .all(|(k, n)| k == n.to_ascii_uppercase() || (k == b'_' && n == b'-'))
+            })
+        })
     }
 
     /// Convert option to its byte representation
     pub fn to_byte(self) -> u8 {
-        self as u8
+        self.0
+    }
+
+    /// The constant's name for a known option, such as `"ECHO"`
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            TelnetOption::BINARY => Some("BINARY"),
+            TelnetOption::ECHO => Some("ECHO"),
+            TelnetOption::RECONNECTION => Some("RECONNECTION"),
+            TelnetOption::SUPPRESS_GO_AHEAD => Some("SUPPRESS_GO_AHEAD"),
+            TelnetOption::APPROX_MESSAGE_SIZE => Some("APPROX_MESSAGE_SIZE"),
+            TelnetOption::STATUS => Some("STATUS"),
+            TelnetOption::TIMING_MARK => Some("TIMING_MARK"),
+            TelnetOption::RCTE => Some("RCTE"),
+            TelnetOption::OUTPUT_LINE_WIDTH => Some("OUTPUT_LINE_WIDTH"),
+            TelnetOption::OUTPUT_PAGE_SIZE => Some("OUTPUT_PAGE_SIZE"),
+            TelnetOption::NAOCRD => Some("NAOCRD"),
+            TelnetOption::NAOHTS => Some("NAOHTS"),
+            TelnetOption::NAOHTD => Some("NAOHTD"),
+            TelnetOption::NAOFFD => Some("NAOFFD"),
+            TelnetOption::NAOVTS => Some("NAOVTS"),
+            TelnetOption::NAOVTD => Some("NAOVTD"),
+            TelnetOption::NAOLFD => Some("NAOLFD"),
+            TelnetOption::EXTEND_ASCII => Some("EXTEND_ASCII"),
+            TelnetOption::LOGOUT => Some("LOGOUT"),
+            TelnetOption::BYTE_MACRO => Some("BYTE_MACRO"),
+            TelnetOption::DATA_ENTRY_TERMINAL => Some("DATA_ENTRY_TERMINAL"),
+            TelnetOption::SUPDUP => Some("SUPDUP"),
+            TelnetOption::SUPDUP_OUTPUT => Some("SUPDUP_OUTPUT"),
+            TelnetOption::SEND_LOCATION => Some("SEND_LOCATION"),
+            TelnetOption::TERMINAL_TYPE => Some("TERMINAL_TYPE"),
+            TelnetOption::END_OF_RECORD => Some("END_OF_RECORD"),
+            TelnetOption::TACACS_USER_ID => Some("TACACS_USER_ID"),
+            TelnetOption::OUTPUT_MARKING => Some("OUTPUT_MARKING"),
+            TelnetOption::TERMINAL_LOCATION => Some("TERMINAL_LOCATION"),
+            TelnetOption::TELNET_3270 => Some("TELNET_3270"),
+            TelnetOption::X3_PAD => Some("X3_PAD"),
+            TelnetOption::NAWS => Some("NAWS"),
+            TelnetOption::TERMINAL_SPEED => Some("TERMINAL_SPEED"),
+            TelnetOption::TOGGLE_FLOW_CONTROL => Some("TOGGLE_FLOW_CONTROL"),
+            TelnetOption::LINEMODE => Some("LINEMODE"),
+            TelnetOption::X_DISPLAY_LOCATION => Some("X_DISPLAY_LOCATION"),
+            TelnetOption::OLD_ENVIRON => Some("OLD_ENVIRON"),
+            TelnetOption::AUTHENTICATION => Some("AUTHENTICATION"),
+            TelnetOption::ENCRYPT => Some("ENCRYPT"),
+            TelnetOption::NEW_ENVIRON => Some("NEW_ENVIRON"),
+            TelnetOption::START_TLS => Some("START_TLS"),
+            TelnetOption::MSDP => Some("MSDP"),
+            TelnetOption::MSSP => Some("MSSP"),
+            TelnetOption::MCCP1 => Some("MCCP1"),
+            TelnetOption::MCCP2 => Some("MCCP2"),
+            TelnetOption::MSP => Some("MSP"),
+            TelnetOption::MXP => Some("MXP"),
+            TelnetOption::ATCP => Some("ATCP"),
+            TelnetOption::GMCP => Some("GMCP"),
+            _ => None,
+        }
     }
 
     /// Check if this is a standard RFC option
     pub fn is_rfc_standard(self) -> bool {
-        matches!(self as u8, 0..=39)
+        matches!(self.0, 0..=39)
     }
 
     /// Check if this is a MUD/MUSH extension option
@@ -451,6 +503,7 @@ impl TelnetOption {
             self,
             TelnetOption::MCCP1
                 | TelnetOption::MCCP2
+                | TelnetOption::MSP
                 | TelnetOption::MXP
                 | TelnetOption::MSSP
                 | TelnetOption::ATCP
@@ -468,6 +521,7 @@ impl TelnetOption {
             TelnetOption::SUPPRESS_GO_AHEAD => Some(858),
             TelnetOption::STATUS => Some(859),
             TelnetOption::TIMING_MARK => Some(860),
+            TelnetOption::SEND_LOCATION => Some(779),
             TelnetOption::TERMINAL_TYPE => Some(1091),
             TelnetOption::END_OF_RECORD => Some(885),
             TelnetOption::NAWS => Some(1073),
@@ -503,6 +557,10 @@ impl TelnetOption {
                 ("IS", 0, "Here is my terminal type"),
                 ("SEND", 1, "Please tell me your terminal type"),
             ],
+            TelnetOption::TERMINAL_SPEED => &[
+                ("IS", 0, "Here is my terminal speed"),
+                ("SEND", 1, "Please tell me your terminal speed"),
+            ],
             TelnetOption::NAWS => &[(
                 "SIZE",
                 0,
@@ -513,6 +571,7 @@ impl TelnetOption {
                 ("SEND", 1, "Please send your environment variables"),
                 ("INFO", 2, "Information about environment variables"),
             ],
+            TelnetOption::START_TLS => &[("FOLLOWS", 1, "TLS negotiation follows this message")],
             // MUD/MUSH protocols have their own sub-negotiation formats
             TelnetOption::GMCP => &[(
                 "JSON",
@@ -524,6 +583,73 @@ impl TelnetOption {
     }
 }
 
+impl fmt::Debug for TelnetOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "TelnetOption({})", self.0),
+        }
+    }
+}
+
+impl From<u8> for TelnetOption {
+    fn from(byte: u8) -> Self {
+        TelnetOption(byte)
+    }
+}
+
+impl From<TelnetOption> for u8 {
+    fn from(option: TelnetOption) -> Self {
+        option.0
+    }
+}
+
+/// Named options serialize as their name (`"NAWS"`), others as the byte
+#[cfg(feature = "serde")]
+impl serde::Serialize for TelnetOption {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.name() {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_u8(self.0),
+        }
+    }
+}
+
+/// Accepts a name, as [`TelnetOption::from_name`] reads it, or a byte
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TelnetOption {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OptionVisitor;
+
+        impl serde::de::Visitor<'_> for OptionVisitor {
+            type Value = TelnetOption;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a telnet option name or byte")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<TelnetOption, E> {
+                u8::try_from(value)
+                    .map(TelnetOption)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<TelnetOption, E> {
+                u8::try_from(value)
+                    .map(TelnetOption)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(value), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<TelnetOption, E> {
+                TelnetOption::from_name(value)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(OptionVisitor)
+    }
+}
+
 /// Represents a complete Telnet command sequence
 ///
 /// This type captures the various forms of Telnet commands:
@@ -531,6 +657,7 @@ impl TelnetOption {
 /// - Option negotiation: IAC <command> <option>  
 /// - Sub-negotiation: IAC SB <option> <data> IAC SE
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TelnetSequence {
     /// Simple command without parameters (e.g., IAC NOP)
     Command(TelnetCommand),
@@ -564,7 +691,13 @@ impl TelnetSequence {
                 bytes.push(IAC);
                 bytes.push(TelnetCommand::SB.to_byte());
                 bytes.push(option.to_byte());
-                bytes.extend_from_slice(data);
+                for &byte in data {
+                    // IAC in the payload is doubled (RFC 855)
+                    bytes.push(byte);
+                    if byte == IAC {
+                        bytes.push(IAC);
+                    }
+                }
                 bytes.push(IAC);
                 bytes.push(TelnetCommand::SE.to_byte());
                 bytes
@@ -592,6 +725,7 @@ mod tests {
         assert_eq!(TelnetCommand::from_byte(252), Some(TelnetCommand::WONT));
         assert_eq!(TelnetCommand::from_byte(253), Some(TelnetCommand::DO));
         assert_eq!(TelnetCommand::from_byte(254), Some(TelnetCommand::DONT));
+        assert_eq!(TelnetCommand::from_byte(239), Some(TelnetCommand::EOR));
         assert_eq!(TelnetCommand::from_byte(100), None);
 
         assert_eq!(TelnetCommand::WILL.to_byte(), 251);
@@ -615,6 +749,24 @@ mod tests {
         assert_eq!(TelnetOption::GMCP.to_byte(), 201);
     }
 
+    #[test]
+    fn test_unnamed_options() {
+        // Aardwolf's private channel option
+        let option = TelnetOption::new(102);
+        assert_eq!(option.to_byte(), 102);
+        assert_eq!(option.name(), None);
+        assert_eq!(format!("{:?}", option), "TelnetOption(102)");
+        assert_eq!(format!("{:?}", TelnetOption::new(1)), "ECHO");
+        assert_eq!(TelnetOption::new(1), TelnetOption::ECHO);
+        assert_eq!(TelnetOption::from(102), option);
+
+        let neg = TelnetSequence::Negotiation {
+            command: TelnetCommand::DO,
+            option,
+        };
+        assert_eq!(neg.to_bytes(), vec![255, 253, 102]);
+    }
+
     #[test]
     fn test_negotiation_commands() {
         assert!(TelnetCommand::WILL.is_negotiation_command());
@@ -642,6 +794,8 @@ mod tests {
 
         assert!(TelnetOption::MCCP2.is_mud_extension());
         assert!(TelnetOption::MXP.is_mud_extension());
+        assert!(TelnetOption::MSP.is_mud_extension());
+        assert_eq!(TelnetOption::from_byte(90), Some(TelnetOption::MSP));
     }
 
     #[test]
@@ -698,4 +852,47 @@ mod tests {
         let escaped = TelnetSequence::EscapedData(255);
         assert_eq!(escaped.to_bytes(), vec![255, 255]);
     }
+
+    #[test]
+    fn test_option_from_name() {
+        assert_eq!(TelnetOption::from_name("NAWS"), Some(TelnetOption::NAWS));
+        assert_eq!(
+            TelnetOption::from_name("terminal-type"),
+            Some(TelnetOption::TERMINAL_TYPE)
+        );
+        assert_eq!(TelnetOption::from_name("Gmcp"), Some(TelnetOption::GMCP));
+        assert_eq!(TelnetOption::from_name("NAW"), None);
+        assert_eq!(TelnetOption::from_name(""), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_sequences() {
+        let sequences = vec![
+            TelnetSequence::Command(TelnetCommand::NOP),
+            TelnetSequence::Negotiation {
+                command: TelnetCommand::WILL,
+                option: TelnetOption::ECHO,
+            },
+            TelnetSequence::SubNegotiation {
+                option: TelnetOption::new(102),
+                data: vec![1, 2],
+            },
+            TelnetSequence::EscapedData(255),
+        ];
+        let json = serde_json::to_string(&sequences).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"Command":"NOP"},{"Negotiation":{"command":"WILL","option":"ECHO"}},"#.to_string()
+                + r#"{"SubNegotiation":{"option":102,"data":[1,2]}},{"EscapedData":255}]"#
+        );
+        let back: Vec<TelnetSequence> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, sequences);
+
+        // Hand-written scripts can use either spelling of a name
+        let option: TelnetOption = serde_json::from_str(r#""terminal-type""#).unwrap();
+        assert_eq!(option, TelnetOption::TERMINAL_TYPE);
+        assert!(serde_json::from_str::<TelnetOption>(r#""BOGUS""#).is_err());
+        assert!(serde_json::from_str::<TelnetOption>("256").is_err());
+    }
 }
@@ -0,0 +1,398 @@
+//! # Telnet Proxy
+//!
+//! [`TelnetProxy`] relays between two telnet connections: the client that
+//! called in and the server it is passed on to. Each side is an ordinary
+//! [`TelnetStream`], so everything the crate does for one connection (RFC
+//! 1143 negotiation, escaping, option handlers, limits) happens on both
+//! legs, and what crosses is clean data. That is the groundwork for MUD
+//! proxies, sniffers and reverse proxies.
+//!
+//! ```rust,no_run
+//! use std::net::{TcpListener, TcpStream};
+//! use telnet_negotiation::{Flow, ProxyMode, TelnetProxy, TelnetStream};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let (caller, _) = TcpListener::bind("127.0.0.1:4000")?.accept()?;
+//!     let upstream = TcpStream::connect("mud.example.com:4000")?;
+//!
+//!     TelnetProxy::new(TelnetStream::new(caller), TelnetStream::client(upstream))
+//!         .mode(ProxyMode::Forward)
+//!         .on_data(|flow, data| {
+//!             if flow == Flow::ClientToServer {
+//!                 println!("> {}", String::from_utf8_lossy(data));
+//!             }
+//!         })
+//!         .run()
+//! }
+//! ```
+//!
+//! Options are handled one of two ways, picked with [`ProxyMode`]. By
+//! default each leg negotiates for itself, so the caller and the server can
+//! end up with different options, and only data is passed along. In
+//! [`ProxyMode::Forward`] an option switched on or off on one leg is asked
+//! for on the other too, and sub-negotiations are passed across verbatim,
+//! so the two ends in effect negotiate with each other. Each leg still
+//! answers by its own accept policy first; set a permissive one with
+//! [`TelnetStream::set_accept_policy`] before handing the legs over for a
+//! fully transparent proxy. Negotiation from one read is passed on before
+//! the data that came with it.
+//!
+//! The proxy runs each direction on its own thread over split halves (see
+//! [`crate::split`]), so both transports must be cloneable. When either
+//! end hangs up, both connections are shut down.
+
+use crate::negotiation::Side;
+use crate::split::{TelnetReadHalf, TelnetWriteHalf};
+#[cfg(doc)]
+use crate::stream::FlushPolicy;
+use crate::stream::{NegotiationEvent, TelnetStream};
+use crate::transport::Transport;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// Size of each relayed read
+const RELAY_CHUNK: usize = 4096;
+
+/// Which way traffic is going through the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flow {
+    /// From the caller to the server
+    ClientToServer,
+    /// From the server back to the caller
+    ServerToClient,
+}
+
+/// How a [`TelnetProxy`] treats option negotiation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProxyMode {
+    /// Each leg negotiates for itself; only data crosses
+    #[default]
+    Renegotiate,
+    /// Option changes on one leg are asked for on the other, and
+    /// sub-negotiations are passed across
+    Forward,
+}
+
+type DataHook = Box<dyn FnMut(Flow, &mut Vec<u8>) + Send>;
+type EventHook = Box<dyn FnMut(Flow, &NegotiationEvent) + Send>;
+
+#[derive(Default)]
+struct Hooks {
+    data: Option<DataHook>,
+    event: Option<EventHook>,
+}
+
+/// Relays between a caller's connection and a server's
+pub struct TelnetProxy<C = TcpStream, S = TcpStream> {
+    client: TelnetStream<C>,
+    server: TelnetStream<S>,
+    mode: ProxyMode,
+    hooks: Hooks,
+}
+
+impl<C: Transport, S: Transport> TelnetProxy<C, S> {
+    /// A proxy between the `client` that called in and the `server` it is
+    /// passed on to
+    ///
+    /// Configure each leg (accept policy, limits, client terminal type for
+    /// the server leg) before handing it over. Relayed data goes out as it
+    /// is written, so leave the legs on [`FlushPolicy::Immediate`].
+    pub fn new(client: TelnetStream<C>, server: TelnetStream<S>) -> Self {
+        Self {
+            client,
+            server,
+            mode: ProxyMode::default(),
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// How options are negotiated across the proxy
+    pub fn mode(mut self, mode: ProxyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Look at or change data on its way through
+    ///
+    /// The hook sees each chunk as it was read; it may rewrite it, or
+    /// clear it to pass nothing on.
+    pub fn on_data(mut self, hook: impl FnMut(Flow, &mut Vec<u8>) + Send + 'static) -> Self {
+        self.hooks.data = Some(Box::new(hook));
+        self
+    }
+
+    /// Watch negotiation on both legs, with the flow it arrived on
+    pub fn on_event(mut self, hook: impl FnMut(Flow, &NegotiationEvent) + Send + 'static) -> Self {
+        self.hooks.event = Some(Box::new(hook));
+        self
+    }
+
+    /// Relay until either end hangs up
+    ///
+    /// Returns the error that ended the relay, if it didn't end with a
+    /// clean hang-up; errors the other direction sees as the connections
+    /// are shut down are not reported.
+    pub fn run(self) -> io::Result<()>
+    where
+        C: Send + 'static,
+        S: Send + 'static,
+    {
+        let Self {
+            mut client,
+            mut server,
+            mode,
+            hooks,
+        } = self;
+        client.set_negotiation_events(true);
+        server.set_negotiation_events(true);
+        let (client_reader, client_writer) = client.split()?;
+        let (server_reader, server_writer) = server.split()?;
+
+        let hooks = Arc::new(Mutex::new(hooks));
+        let ended = Arc::new(AtomicBool::new(false));
+        let upstream = {
+            let relay = Relay {
+                flow: Flow::ClientToServer,
+                mode,
+                hooks: Arc::clone(&hooks),
+                ended: Arc::clone(&ended),
+            };
+            thread::spawn(move || relay.run(client_reader, server_writer))
+        };
+        let relay = Relay {
+            flow: Flow::ServerToClient,
+            mode,
+            hooks,
+            ended,
+        };
+        let downstream = relay.run(server_reader, client_writer);
+
+        let upstream = upstream
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("proxy relay thread panicked")));
+        downstream.and(upstream)
+    }
+}
+
+impl<C, S> fmt::Debug for TelnetProxy<C, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TelnetProxy")
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One direction of a running proxy
+struct Relay {
+    flow: Flow,
+    mode: ProxyMode,
+    hooks: Arc<Mutex<Hooks>>,
+    /// Set by whichever direction finishes first
+    ended: Arc<AtomicBool>,
+}
+
+impl Relay {
+    fn run<R: Transport, W: Transport>(
+        &self,
+        mut from: TelnetReadHalf<R>,
+        mut to: TelnetWriteHalf<W>,
+    ) -> io::Result<()> {
+        let outcome = self.relay(&mut from, &mut to);
+        if self.ended.swap(true, Ordering::SeqCst) {
+            // The other direction finished first and shut everything down
+            return Ok(());
+        }
+        let _ = from.lock().shutdown(Shutdown::Both);
+        let _ = to.lock().shutdown(Shutdown::Both);
+        outcome
+    }
+
+    fn relay<R: Transport, W: Transport>(
+        &self,
+        from: &mut TelnetReadHalf<R>,
+        to: &mut TelnetWriteHalf<W>,
+    ) -> io::Result<()> {
+        let mut buf = [0; RELAY_CHUNK];
+        loop {
+            let n = match from.read_once(&mut buf) {
+                Ok(Some(n)) => n,
+                Ok(None) => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => 0,
+                Err(e) => return Err(e),
+            };
+
+            let events = from.lock().take_negotiation_events();
+            for event in &events {
+                if let Some(hook) = &mut self.hooks().event {
+                    hook(self.flow, event);
+                }
+                if self.mode == ProxyMode::Forward {
+                    forward(&mut to.lock(), event)?;
+                }
+            }
+
+            if n > 0 {
+                let mut data = buf[..n].to_vec();
+                if let Some(hook) = &mut self.hooks().data {
+                    hook(self.flow, &mut data);
+                }
+                // No flush: that would end the turn with IAC GA
+                if !data.is_empty() {
+                    to.write_all(&data)?;
+                }
+            }
+        }
+    }
+
+    fn hooks(&self) -> std::sync::MutexGuard<'_, Hooks> {
+        self.hooks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Repeat on the other leg what `event` did on this one
+///
+/// The sides swap: an option the caller turned on for itself is one the
+/// proxy turns on for itself towards the server, and the other way round.
+fn forward<S: Transport>(to: &mut TelnetStream<S>, event: &NegotiationEvent) -> io::Result<()> {
+    let across = |side| match side {
+        Side::Local => Side::Remote,
+        Side::Remote => Side::Local,
+    };
+    match event {
+        NegotiationEvent::OptionEnabled { side, option } => {
+            to.enable_option(across(*side), *option).map(drop)
+        }
+        NegotiationEvent::OptionDisabled { side, option } => {
+            to.disable_option(across(*side), *option).map(drop)
+        }
+        NegotiationEvent::Subnegotiation { option, data } => to.send_subnegotiation(*option, data),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ParserLimits, TelnetParser};
+    use crate::protocol::{IAC, TelnetCommand, TelnetOption, TelnetSequence};
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    /// A connected pair of sockets
+    fn socket_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let near = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (far, _) = listener.accept().unwrap();
+        (near, far)
+    }
+
+    fn parse(bytes: &[u8]) -> (Vec<TelnetSequence>, Vec<u8>) {
+        let result = TelnetParser::with_limits(ParserLimits::unlimited()).parse(bytes);
+        (result.sequences, result.data)
+    }
+
+    #[test]
+    fn test_forward_mode_relays_data_and_negotiation() {
+        let (mut caller, client_leg) = socket_pair();
+        let (server_leg, mut server) = socket_pair();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let proxy = {
+            let seen = Arc::clone(&seen);
+            TelnetProxy::new(
+                TelnetStream::new(client_leg),
+                TelnetStream::client(server_leg),
+            )
+            .mode(ProxyMode::Forward)
+            .on_data(|flow, data| {
+                if flow == Flow::ClientToServer {
+                    data.make_ascii_uppercase();
+                }
+            })
+            .on_event(move |flow, event| seen.lock().unwrap().push((flow, event.clone())))
+        };
+        let proxy = thread::spawn(move || proxy.run());
+
+        // The caller offers NAWS and reports its size along with some text
+        caller
+            .write_all(&[IAC, 251, 31, IAC, 250, 31, 0, 80, 0, 24, IAC, 240])
+            .unwrap();
+        caller.write_all(b"hi").unwrap();
+
+        // The server is offered NAWS in turn and gets the report as sent
+        let mut upstream = [0; 14];
+        server.read_exact(&mut upstream).unwrap();
+        let (sequences, data) = parse(&upstream);
+        assert_eq!(
+            sequences,
+            [
+                TelnetSequence::Negotiation {
+                    command: TelnetCommand::WILL,
+                    option: TelnetOption::NAWS,
+                },
+                TelnetSequence::SubNegotiation {
+                    option: TelnetOption::NAWS,
+                    data: vec![0, 80, 0, 24],
+                },
+            ]
+        );
+        assert_eq!(data, b"HI");
+
+        // The caller got its DO from the proxy, then the server's reply
+        server.write_all(b"ok").unwrap();
+        let mut downstream = [0; 5];
+        caller.read_exact(&mut downstream).unwrap();
+        let (sequences, data) = parse(&downstream);
+        assert_eq!(
+            sequences,
+            [TelnetSequence::Negotiation {
+                command: TelnetCommand::DO,
+                option: TelnetOption::NAWS,
+            }]
+        );
+        assert_eq!(data, b"ok");
+
+        // Hanging up on one side closes the other
+        drop(caller);
+        assert_eq!(server.read(&mut [0; 8]).unwrap(), 0);
+        proxy.join().unwrap().unwrap();
+
+        assert!(seen.lock().unwrap().contains(&(
+            Flow::ClientToServer,
+            NegotiationEvent::OptionEnabled {
+                side: Side::Remote,
+                option: TelnetOption::NAWS,
+            }
+        )));
+    }
+
+    #[test]
+    fn test_renegotiate_mode_passes_only_data() {
+        let (mut caller, client_leg) = socket_pair();
+        let (server_leg, mut server) = socket_pair();
+        let proxy = thread::spawn(move || {
+            TelnetProxy::new(
+                TelnetStream::new(client_leg),
+                TelnetStream::client(server_leg),
+            )
+            .run()
+        });
+
+        caller
+            .write_all(&[IAC, 251, 31, IAC, 250, 31, 0, 80, 0, 24, IAC, 240])
+            .unwrap();
+        caller.write_all(b"a\xffb").unwrap();
+        drop(caller);
+
+        let mut upstream = Vec::new();
+        server.read_to_end(&mut upstream).unwrap();
+        // Nothing about NAWS, and the 255 escaped again on the way out
+        assert_eq!(upstream, b"a\xff\xffb");
+        proxy.join().unwrap().unwrap();
+    }
+}
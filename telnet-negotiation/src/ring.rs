@@ -0,0 +1,205 @@
+//! # Ring Buffer
+//!
+//! Application data waits in a [`RingBuffer`] between parsing and `read`.
+//! Bytes go in and come out a slice at a time with `copy_from_slice`, in
+//! at most two copies each way (either side of the wrap), so a large ANSI
+//! screen costs a couple of `memcpy`s rather than a step per byte.
+
+/// Smallest allocation once anything is stored
+const MIN_CAPACITY: usize = 64;
+
+/// A growable FIFO of bytes in one contiguous allocation
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RingBuffer {
+    /// Storage; its length is the capacity, always zero or a power of two
+    buf: Vec<u8>,
+    /// Index of the oldest byte
+    head: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bytes in order, as the run up to the wrap and the run after it
+    pub(crate) fn as_slices(&self) -> (&[u8], &[u8]) {
+        let first = (self.buf.len() - self.head).min(self.len);
+        (
+            &self.buf[self.head..self.head + first],
+            &self.buf[..self.len - first],
+        )
+    }
+
+    /// The byte `index` places from the front
+    pub(crate) fn get(&self, index: usize) -> Option<u8> {
+        (index < self.len).then(|| self.buf[(self.head + index) & (self.buf.len() - 1)])
+    }
+
+    /// Position of the first byte matching `pred`, counted from the front
+    pub(crate) fn position(&self, pred: impl Fn(u8) -> bool) -> Option<usize> {
+        let (first, second) = self.as_slices();
+        first.iter().chain(second).position(|&byte| pred(byte))
+    }
+
+    /// Append `bytes`, growing if they don't fit
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.reserve(bytes.len());
+
+        let capacity = self.buf.len();
+        let tail = (self.head + self.len) & (capacity - 1);
+        let first = (capacity - tail).min(bytes.len());
+        self.buf[tail..tail + first].copy_from_slice(&bytes[..first]);
+        self.buf[..bytes.len() - first].copy_from_slice(&bytes[first..]);
+        self.len += bytes.len();
+    }
+
+    /// Move bytes from the front into `out`, returning how many
+    pub(crate) fn read_into(&mut self, out: &mut [u8]) -> usize {
+        let (first, second) = self.as_slices();
+        let from_first = first.len().min(out.len());
+        let from_second = second.len().min(out.len() - from_first);
+        out[..from_first].copy_from_slice(&first[..from_first]);
+        out[from_first..from_first + from_second].copy_from_slice(&second[..from_second]);
+
+        let taken = from_first + from_second;
+        self.consume(taken);
+        taken
+    }
+
+    /// Take `count` bytes from the front as a `Vec`
+    pub(crate) fn take_vec(&mut self, count: usize) -> Vec<u8> {
+        let mut bytes = vec![0; count.min(self.len)];
+        self.read_into(&mut bytes);
+        bytes
+    }
+
+    /// Drop up to `count` bytes from the front
+    pub(crate) fn consume(&mut self, count: usize) {
+        let count = count.min(self.len);
+        self.len -= count;
+        self.head = if self.len == 0 {
+            0
+        } else {
+            (self.head + count) & (self.buf.len() - 1)
+        };
+    }
+
+    /// Drop everything, keeping the memory
+    pub(crate) fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Keep the oldest `len` bytes, dropping the newest
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+        if self.len == 0 {
+            self.head = 0;
+        }
+    }
+
+    /// Empty the buffer and give its memory back
+    pub(crate) fn release(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Make room for `additional` more bytes
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.len + additional;
+        if needed <= self.buf.len() {
+            return;
+        }
+
+        let mut grown = vec![0; needed.next_power_of_two().max(MIN_CAPACITY)];
+        let (first, second) = self.as_slices();
+        grown[..first.len()].copy_from_slice(first);
+        grown[first.len()..self.len].copy_from_slice(second);
+        self.buf = grown;
+        self.head = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(ring: &RingBuffer) -> Vec<u8> {
+        let (first, second) = ring.as_slices();
+        [first, second].concat()
+    }
+
+    #[test]
+    fn test_wraps_and_grows_in_order() {
+        let mut ring = RingBuffer::new();
+        let data: Vec<u8> = (0..60).collect();
+        ring.extend_from_slice(&data);
+        ring.consume(50);
+
+        // Wraps around the end of the 64 byte storage
+        ring.extend_from_slice(&[100; 20]);
+        assert_eq!(ring.len(), 30);
+        let (first, second) = ring.as_slices();
+        assert_eq!(first.len(), 14);
+        assert_eq!(second.len(), 16);
+        assert_eq!(ring.get(0), Some(50));
+        assert_eq!(ring.get(29), Some(100));
+        assert_eq!(ring.get(30), None);
+        assert_eq!(ring.position(|b| b == 100), Some(10));
+
+        // Growing keeps the order
+        ring.extend_from_slice(&[200; 100]);
+        let expected: Vec<u8> = (50..60)
+            .chain(std::iter::repeat_n(100, 20))
+            .chain(std::iter::repeat_n(200, 100))
+            .collect();
+        assert_eq!(contents(&ring), expected);
+    }
+
+    #[test]
+    fn test_read_into_copies_across_the_wrap() {
+        let mut ring = RingBuffer::new();
+        ring.extend_from_slice(&[1; 60]);
+        ring.consume(58);
+        ring.extend_from_slice(&[2, 3, 4, 5, 6]);
+
+        let mut out = [0; 4];
+        assert_eq!(ring.read_into(&mut out), 4);
+        assert_eq!(out, [1, 1, 2, 3]);
+        assert_eq!(ring.take_vec(10), vec![4, 5, 6]);
+        assert!(ring.is_empty());
+        assert_eq!(ring.read_into(&mut out), 0);
+    }
+
+    #[test]
+    fn test_truncate_and_release() {
+        let mut ring = RingBuffer::new();
+        ring.extend_from_slice(&[0; 62]);
+        ring.consume(61);
+        ring.extend_from_slice(b"abcde");
+        assert_eq!(ring.as_slices(), (&b"\0ab"[..], &b"cde"[..]));
+        ring.consume(1);
+
+        ring.truncate(3);
+        assert_eq!(contents(&ring), b"abc");
+        ring.clear();
+        assert!(ring.is_empty());
+        ring.extend_from_slice(b"xy");
+        assert_eq!(contents(&ring), b"xy");
+        ring.release();
+        assert!(ring.is_empty());
+        assert_eq!(ring.as_slices(), (&[][..], &[][..]));
+    }
+}
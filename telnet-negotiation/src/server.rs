@@ -0,0 +1,294 @@
+//! # Server Handshakes
+//!
+//! Every telnet server opens the same way: offer and request a handful of
+//! options, wait for the client to answer them and send what follows (its
+//! terminal type, window size), then start talking. [`TelnetServerBuilder`]
+//! does that once, configured with the options to ask for and how long to
+//! wait, and hands back the stream with the [`TerminalCapabilities`] it
+//! found.
+//!
+//! ```rust,no_run
+//! use std::net::TcpListener;
+//! use std::time::Duration;
+//! use telnet_negotiation::{TelnetOption, TelnetServerBuilder};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let listener = TcpListener::bind("127.0.0.1:2323")?;
+//!     let server = TelnetServerBuilder::new()
+//!         .request(TelnetOption::NEW_ENVIRON)
+//!         .offer_echo(true)
+//!         .timeout(Duration::from_millis(500));
+//!
+//!     let handshake = server.accept(&listener)?;
+//!     println!(
+//!         "{:?} at {:?} columns",
+//!         handshake.capabilities.terminal_type, handshake.capabilities.width
+//!     );
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The handshake ends as soon as it is quiet: every option asked for has
+//! been answered, and those the client agreed to have sent what they carry
+//! (the whole terminal type cycle, a window size, the environment, a line
+//! speed or a location). A client that never answers costs the timeout and
+//! no more. Application data that arrives meanwhile is kept for the first
+//! `read`.
+
+use crate::history::Direction;
+use crate::negotiation::Side;
+use crate::profile::NegotiationProfile;
+use crate::protocol::TelnetOption;
+use crate::stream::{TelnetStream, TerminalCapabilities};
+use crate::transport::Transport;
+
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// How long a handshake waits for a quiet client by default
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Opens server connections with a configurable option handshake
+#[derive(Debug, Clone)]
+pub struct TelnetServerBuilder {
+    profile: NegotiationProfile,
+    echo: bool,
+    timeout: Duration,
+    debug: bool,
+}
+
+/// A connection whose opening handshake has finished
+pub struct Handshake<S = TcpStream> {
+    pub stream: TelnetStream<S>,
+    /// What the handshake found out about the client's terminal
+    pub capabilities: TerminalCapabilities,
+    /// Whether the client answered everything before the timeout
+    pub settled: bool,
+}
+
+impl Default for TelnetServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelnetServerBuilder {
+    /// A builder asking for SUPPRESS-GO-AHEAD, NAWS and TERMINAL-TYPE,
+    /// without offering to echo, waiting up to
+    /// [`DEFAULT_HANDSHAKE_TIMEOUT`]
+    pub fn new() -> Self {
+        Self {
+            profile: NegotiationProfile::new([
+                TelnetOption::SUPPRESS_GO_AHEAD,
+                TelnetOption::NAWS,
+                TelnetOption::TERMINAL_TYPE,
+            ]),
+            echo: false,
+            timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            debug: false,
+        }
+    }
+
+    /// Ask for the options in `profile`, in its order, instead
+    pub fn profile(mut self, profile: NegotiationProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Ask for `option` too, after those already listed
+    pub fn request(mut self, option: TelnetOption) -> Self {
+        let options = self.profile.options().iter().copied().chain([option]);
+        self.profile = NegotiationProfile::new(options);
+        self
+    }
+
+    /// Offer to echo (WILL ECHO), for servers that echo what callers type
+    pub fn offer_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Wait at most `timeout` for the client to finish answering
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Log negotiation to stderr (see [`TelnetStream::with_debug`])
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// The options asked for, in order
+    pub fn requested(&self) -> &NegotiationProfile {
+        &self.profile
+    }
+
+    /// Accept the next connection on `listener` and run the handshake
+    pub fn accept(&self, listener: &TcpListener) -> io::Result<Handshake> {
+        let (socket, _) = listener.accept()?;
+        self.handshake(socket)
+    }
+
+    /// Wrap a freshly accepted connection and run the handshake
+    ///
+    /// Fails with `InvalidInput`, before anything is sent, if an option
+    /// can't be requested (see [`TelnetStream::can_request`]).
+    pub fn handshake<S: Transport>(&self, transport: S) -> io::Result<Handshake<S>> {
+        let mut stream = if self.debug {
+            TelnetStream::with_debug(transport)
+        } else {
+            TelnetStream::new(transport)
+        };
+        let settled = self.negotiate(&mut stream)?;
+        Ok(Handshake {
+            capabilities: stream.get_terminal_capabilities(),
+            stream,
+            settled,
+        })
+    }
+
+    /// Run the handshake on a stream that is already open
+    ///
+    /// For servers that set the stream up first (a memory budget, extra
+    /// option handlers). Returns whether the client answered everything
+    /// before the timeout.
+    pub fn negotiate<S: Transport>(&self, stream: &mut TelnetStream<S>) -> io::Result<bool> {
+        let since = stream.negotiation_history().total_recorded();
+        stream.apply_profile(&self.profile)?;
+        let mut asked = self.profile.options().to_vec();
+        if self.echo {
+            stream.request_echo_off()?;
+            asked.push(TelnetOption::ECHO);
+        }
+
+        stream.read_until(self.timeout, |stream| {
+            asked
+                .iter()
+                .all(|&option| answered(stream, option, since) && !awaiting_data(stream, option))
+        })
+    }
+}
+
+/// Whether the peer has answered a request for `option` made at history
+/// entry `since`, or had already agreed to it
+fn answered<S: Transport>(stream: &TelnetStream<S>, option: TelnetOption, since: u64) -> bool {
+    stream.is_option_enabled(Side::Remote, option)
+        || stream.is_option_enabled(Side::Local, option)
+        || stream
+            .negotiation_history()
+            .for_option(option)
+            .any(|record| record.seq >= since && record.direction == Direction::Received)
+}
+
+/// Whether the peer agreed to `option` but hasn't yet sent what it carries
+fn awaiting_data<S: Transport>(stream: &TelnetStream<S>, option: TelnetOption) -> bool {
+    if !stream.is_option_enabled(Side::Remote, option) {
+        return false;
+    }
+    match option {
+        TelnetOption::TERMINAL_TYPE => stream
+            .terminal_type_handler()
+            .is_some_and(|handler| handler.awaiting_reply() || handler.terminal_type().is_none()),
+        TelnetOption::NAWS => stream.naws_size().is_none(),
+        TelnetOption::NEW_ENVIRON => stream.environment().is_none(),
+        TelnetOption::TERMINAL_SPEED => stream.terminal_speed().is_none(),
+        TelnetOption::SEND_LOCATION => stream.location().is_none(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::thread;
+    use std::time::Instant;
+
+    fn connected_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn test_handshake_settles_once_the_client_has_answered() {
+        let (server, client) = connected_pair();
+        let answering = thread::spawn(move || {
+            let mut client = TelnetStream::client(client);
+            client.set_client_terminal_type("XTERM-256COLOR");
+            client.set_client_window_size(100, 40).unwrap();
+            let mut buf = [0; 64];
+            let mut text = Vec::new();
+            while let Ok(n @ 1..) = client.read(&mut buf) {
+                text.extend_from_slice(&buf[..n]);
+            }
+            text
+        });
+
+        let started = Instant::now();
+        let Handshake {
+            mut stream,
+            capabilities,
+            settled,
+        } = TelnetServerBuilder::new()
+            .offer_echo(true)
+            .timeout(Duration::from_secs(5))
+            .handshake(server)
+            .unwrap();
+        assert!(settled);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(capabilities.width, Some(100));
+        assert_eq!(capabilities.height, Some(40));
+        assert_eq!(
+            capabilities.terminal_type.as_deref(),
+            Some("XTERM-256COLOR")
+        );
+        assert!(stream.is_full_duplex());
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+
+        std::io::Write::write_all(&mut stream, b"welcome").unwrap();
+        drop(stream);
+        assert_eq!(answering.join().unwrap(), b"welcome");
+    }
+
+    #[test]
+    fn test_silent_client_costs_the_timeout() {
+        let (server, _client) = connected_pair();
+        let started = Instant::now();
+        let handshake = TelnetServerBuilder::new()
+            .timeout(Duration::from_millis(100))
+            .handshake(server)
+            .unwrap();
+        assert!(!handshake.settled);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(handshake.capabilities.width, None);
+    }
+
+    #[test]
+    fn test_request_appends_and_unrequestable_options_fail() {
+        let builder = TelnetServerBuilder::new()
+            .request(TelnetOption::NEW_ENVIRON)
+            .request(TelnetOption::NAWS);
+        assert_eq!(
+            builder.requested().options(),
+            &[
+                TelnetOption::SUPPRESS_GO_AHEAD,
+                TelnetOption::NAWS,
+                TelnetOption::TERMINAL_TYPE,
+                TelnetOption::NEW_ENVIRON,
+            ]
+        );
+
+        let (server, _client) = connected_pair();
+        let error = builder
+            .request(TelnetOption::GMCP)
+            .handshake(server)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,497 @@
+//! # Negotiation Simulator (RFC 1143)
+//!
+//! Runs two [`OptionNegotiator`]s against each other over an in-memory
+//! wire, so negotiation behaviour can be checked without sockets or timing.
+//!
+//! ## How It Works
+//!
+//! Each peer has its own accept policy and a queue of commands in flight
+//! to the other. A script of [`Step`]s says when each peer decides to
+//! enable or disable an option and when commands arrive, so requests can
+//! cross on the wire in any order. Once the script ends, everything still
+//! in flight is delivered, a command from each peer in turn, until the
+//! wire goes quiet.
+//!
+//! The Q method promises that this always happens, and that both ends
+//! then agree: whatever A thinks of its own side of an option, B thinks of
+//! the remote side, and no option is left half negotiated. [`simulate`]
+//! checks all of that and reports the first thing that doesn't hold.
+//!
+//! ## Example
+//!
+//! Both ends offer to echo at the same moment. Each WILL arrives while the
+//! receiver is waiting on its own offer; neither peer loops, and each ends
+//! up echoing for the other:
+//!
+//! ```rust
+//! use telnet_negotiation::simulation::{Peer, Step, simulate};
+//! use telnet_negotiation::{Side, TelnetCommand, TelnetOption, default_accept_policy};
+//!
+//! let echo = TelnetOption::ECHO;
+//! let result = simulate(
+//!     default_accept_policy,
+//!     default_accept_policy,
+//!     &[
+//!         Step::Enable(Peer::A, Side::Local, echo),
+//!         Step::Enable(Peer::B, Side::Local, echo),
+//!     ],
+//! )
+//! .unwrap();
+//!
+//! assert!(result.a.is_enabled(Side::Local, echo));
+//! assert!(result.b.is_enabled(Side::Remote, echo));
+//! // Two offers, two answers, then quiet
+//! let commands: Vec<_> = result.transcript.iter().map(|t| t.command).collect();
+//! assert_eq!(
+//!     commands,
+//!     [TelnetCommand::WILL, TelnetCommand::WILL, TelnetCommand::DO, TelnetCommand::DO]
+//! );
+//! ```
+
+use crate::negotiation::{NegotiationResult, OptionNegotiator, OptionPolicy, OptionState, Side};
+use crate::protocol::{TelnetCommand, TelnetOption, TelnetSequence};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Commands delivered before a simulation is declared stuck in a loop
+pub const MAX_DELIVERIES: usize = 1000;
+
+/// One end of the simulated connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    A,
+    B,
+}
+
+impl Peer {
+    /// The end this one talks to
+    pub fn other(self) -> Peer {
+        match self {
+            Peer::A => Peer::B,
+            Peer::B => Peer::A,
+        }
+    }
+}
+
+/// One step of a simulation script
+///
+/// Sides are from the acting peer's point of view: `Side::Local` is its
+/// own WILL/WONT, `Side::Remote` its DO/DONT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// The peer decides it wants the option enabled
+    Enable(Peer, Side, TelnetOption),
+    /// The peer decides it wants the option disabled
+    Disable(Peer, Side, TelnetOption),
+    /// The oldest command the peer has in flight arrives at the other end;
+    /// nothing happens if it has none
+    Deliver(Peer),
+}
+
+/// A command put on the wire during a simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transmission {
+    pub from: Peer,
+    pub command: TelnetCommand,
+    pub option: TelnetOption,
+}
+
+/// Both ends once a simulation has settled
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub a: OptionNegotiator,
+    pub b: OptionNegotiator,
+    /// Every command sent, in the order it was sent
+    pub transcript: Vec<Transmission>,
+}
+
+impl Simulation {
+    fn negotiator(&mut self, peer: Peer) -> &mut OptionNegotiator {
+        match peer {
+            Peer::A => &mut self.a,
+            Peer::B => &mut self.b,
+        }
+    }
+}
+
+/// Why a simulation didn't converge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulationError {
+    /// Commands were still in flight after [`MAX_DELIVERIES`]
+    NoConvergence { deliveries: usize },
+    /// A peer received a reply no Q method peer should send
+    ProtocolError {
+        peer: Peer,
+        command: TelnetCommand,
+        option: TelnetOption,
+        message: String,
+    },
+    /// An option was still being negotiated once the wire went quiet
+    Unsettled {
+        peer: Peer,
+        side: Side,
+        option: TelnetOption,
+        state: OptionState,
+    },
+    /// The ends disagree about an option; `side` is from A's point of view
+    Disagreement {
+        side: Side,
+        option: TelnetOption,
+        a: OptionState,
+        b: OptionState,
+    },
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::NoConvergence { deliveries } => {
+                write!(f, "still negotiating after {} deliveries", deliveries)
+            }
+            SimulationError::ProtocolError {
+                peer,
+                command,
+                option,
+                message,
+            } => write!(
+                f,
+                "peer {:?} received {:?} {:?}: {}",
+                peer, command, option, message
+            ),
+            SimulationError::Unsettled {
+                peer,
+                side,
+                option,
+                state,
+            } => write!(
+                f,
+                "peer {:?} left {:?} {:?} in {:?}",
+                peer, side, option, state
+            ),
+            SimulationError::Disagreement { side, option, a, b } => write!(
+                f,
+                "A has {:?} {:?} as {:?} but B has it as {:?}",
+                side, option, a, b
+            ),
+        }
+    }
+}
+
+impl core::error::Error for SimulationError {}
+
+/// Run `script` between two negotiators and check that they converge
+///
+/// Peer A agrees to options according to `a_policy` and peer B according
+/// to `b_policy`. Commands left in flight after the script are delivered
+/// in turns until none remain.
+pub fn simulate(
+    a_policy: impl OptionPolicy + 'static,
+    b_policy: impl OptionPolicy + 'static,
+    script: &[Step],
+) -> Result<Simulation, SimulationError> {
+    let mut a = OptionNegotiator::new();
+    a.set_accept_policy(a_policy);
+    let mut b = OptionNegotiator::new();
+    b.set_accept_policy(b_policy);
+    let mut wire = Wire {
+        simulation: Simulation {
+            a,
+            b,
+            transcript: Vec::new(),
+        },
+        in_flight: [VecDeque::new(), VecDeque::new()],
+        deliveries: 0,
+    };
+
+    for step in script {
+        match *step {
+            Step::Enable(peer, side, option) => {
+                let result = wire
+                    .simulation
+                    .negotiator(peer)
+                    .request_enable(side, option);
+                wire.send(peer, result);
+            }
+            Step::Disable(peer, side, option) => {
+                let result = wire
+                    .simulation
+                    .negotiator(peer)
+                    .request_disable(side, option);
+                wire.send(peer, result);
+            }
+            Step::Deliver(peer) => wire.deliver(peer)?,
+        }
+    }
+
+    let mut turn = Peer::A;
+    while !wire.in_flight.iter().all(VecDeque::is_empty) {
+        wire.deliver(turn)?;
+        turn = turn.other();
+    }
+
+    let options = touched_options(script, &wire.simulation.transcript);
+    check_settled(&wire.simulation, &options)?;
+    Ok(wire.simulation)
+}
+
+/// The two negotiators and the commands between them
+struct Wire {
+    simulation: Simulation,
+    /// Commands sent by A, then by B, oldest first
+    in_flight: [VecDeque<(TelnetCommand, TelnetOption)>; 2],
+    deliveries: usize,
+}
+
+impl Wire {
+    fn queue(&mut self, peer: Peer) -> &mut VecDeque<(TelnetCommand, TelnetOption)> {
+        &mut self.in_flight[peer as usize]
+    }
+
+    /// Put `peer`'s reply, if it made one, on the wire
+    fn send(&mut self, peer: Peer, result: NegotiationResult) {
+        if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+            self.queue(peer).push_back((command, option));
+            self.simulation.transcript.push(Transmission {
+                from: peer,
+                command,
+                option,
+            });
+        }
+    }
+
+    /// Hand `peer`'s oldest command in flight to the other end
+    fn deliver(&mut self, peer: Peer) -> Result<(), SimulationError> {
+        let Some((command, option)) = self.queue(peer).pop_front() else {
+            return Ok(());
+        };
+        self.deliveries += 1;
+        if self.deliveries > MAX_DELIVERIES {
+            return Err(SimulationError::NoConvergence {
+                deliveries: MAX_DELIVERIES,
+            });
+        }
+
+        let receiver = peer.other();
+        let negotiator = self.simulation.negotiator(receiver);
+        let result = match command {
+            TelnetCommand::WILL => negotiator.handle_will(option),
+            TelnetCommand::WONT => negotiator.handle_wont(option),
+            TelnetCommand::DO => negotiator.handle_do(option),
+            TelnetCommand::DONT => negotiator.handle_dont(option),
+            _ => return Ok(()),
+        };
+        if let Some(message) = result.error.clone() {
+            return Err(SimulationError::ProtocolError {
+                peer: receiver,
+                command,
+                option,
+                message,
+            });
+        }
+        self.send(receiver, result);
+        Ok(())
+    }
+}
+
+/// Every option the script or the wire mentioned, in order of appearance
+fn touched_options(script: &[Step], transcript: &[Transmission]) -> Vec<TelnetOption> {
+    let mut options = Vec::new();
+    let scripted = script.iter().filter_map(|step| match *step {
+        Step::Enable(_, _, option) | Step::Disable(_, _, option) => Some(option),
+        Step::Deliver(_) => None,
+    });
+    for option in scripted.chain(transcript.iter().map(|t| t.option)) {
+        if !options.contains(&option) {
+            options.push(option);
+        }
+    }
+    options
+}
+
+fn check_settled(simulation: &Simulation, options: &[TelnetOption]) -> Result<(), SimulationError> {
+    for &option in options {
+        for side in [Side::Local, Side::Remote] {
+            let mirror = match side {
+                Side::Local => Side::Remote,
+                Side::Remote => Side::Local,
+            };
+            let a = simulation.a.get_state(side, option);
+            let b = simulation.b.get_state(mirror, option);
+
+            for (peer, side, state) in [(Peer::A, side, a), (Peer::B, mirror, b)] {
+                if !matches!(state, OptionState::Yes | OptionState::No) {
+                    return Err(SimulationError::Unsettled {
+                        peer,
+                        side,
+                        option,
+                        state,
+                    });
+                }
+            }
+            if a != b {
+                return Err(SimulationError::Disagreement { side, option, a, b });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::negotiation::{AcceptPolicy, default_accept_policy};
+
+    fn accept_all(_: TelnetOption, _: Side) -> bool {
+        true
+    }
+
+    fn refuse_all(_: TelnetOption, _: Side) -> bool {
+        false
+    }
+
+    #[test]
+    fn test_request_is_answered() {
+        let naws = TelnetOption::NAWS;
+        let result = simulate(
+            default_accept_policy,
+            accept_all,
+            &[Step::Enable(Peer::A, Side::Remote, naws)],
+        )
+        .unwrap();
+
+        assert!(result.a.is_enabled(Side::Remote, naws));
+        assert!(result.b.is_enabled(Side::Local, naws));
+        assert_eq!(
+            result.transcript,
+            [
+                Transmission {
+                    from: Peer::A,
+                    command: TelnetCommand::DO,
+                    option: naws,
+                },
+                Transmission {
+                    from: Peer::B,
+                    command: TelnetCommand::WILL,
+                    option: naws,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refusal_converges_to_disabled() {
+        let echo = TelnetOption::ECHO;
+        let result = simulate(
+            accept_all,
+            refuse_all,
+            &[
+                Step::Enable(Peer::A, Side::Remote, echo),
+                Step::Enable(Peer::B, Side::Remote, echo),
+            ],
+        )
+        .unwrap();
+
+        // A agreed to echo for B; B refused to echo for A
+        assert!(!result.a.is_enabled(Side::Remote, echo));
+        assert!(result.a.is_enabled(Side::Local, echo));
+        assert!(result.b.is_enabled(Side::Remote, echo));
+    }
+
+    #[test]
+    fn test_queued_change_of_mind_is_carried_out() {
+        let binary = TelnetOption::BINARY;
+        let result = simulate(
+            accept_all,
+            accept_all,
+            &[
+                // A asks, changes its mind twice before hearing back
+                Step::Enable(Peer::A, Side::Local, binary),
+                Step::Disable(Peer::A, Side::Local, binary),
+                Step::Enable(Peer::A, Side::Local, binary),
+                Step::Disable(Peer::A, Side::Local, binary),
+            ],
+        )
+        .unwrap();
+
+        assert!(!result.a.is_enabled(Side::Local, binary));
+        // Offer accepted, then withdrawn: WILL, DO, WONT, DONT
+        assert_eq!(result.transcript.len(), 4);
+    }
+
+    #[test]
+    fn test_crossing_disables_converge() {
+        let sga = TelnetOption::SUPPRESS_GO_AHEAD;
+        let result = simulate(
+            default_accept_policy,
+            default_accept_policy,
+            &[
+                Step::Enable(Peer::A, Side::Local, sga),
+                Step::Deliver(Peer::A),
+                Step::Deliver(Peer::B),
+                Step::Disable(Peer::A, Side::Local, sga),
+                Step::Disable(Peer::B, Side::Remote, sga),
+            ],
+        );
+
+        // Both ends ask to disable at once; each request answers the other
+        let result = result.unwrap();
+        assert!(!result.a.is_enabled(Side::Local, sga));
+        assert!(!result.b.is_enabled(Side::Remote, sga));
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = SimulationError::Disagreement {
+            side: Side::Local,
+            option: TelnetOption::ECHO,
+            a: OptionState::Yes,
+            b: OptionState::No,
+        };
+        assert_eq!(
+            error.to_string(),
+            "A has Local ECHO as Yes but B has it as No"
+        );
+    }
+
+    /// Deterministic xorshift, so every run checks the same scripts
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn test_random_interleavings_converge() {
+        let options = [TelnetOption::ECHO, TelnetOption::BINARY];
+        let policies: [AcceptPolicy; 3] = [accept_all, refuse_all, default_accept_policy];
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..2000 {
+            let script: Vec<Step> = (0..rng.next(16) + 1)
+                .map(|_| {
+                    let peer = [Peer::A, Peer::B][rng.next(2)];
+                    let side = [Side::Local, Side::Remote][rng.next(2)];
+                    let option = options[rng.next(options.len())];
+                    match rng.next(3) {
+                        0 => Step::Enable(peer, side, option),
+                        1 => Step::Disable(peer, side, option),
+                        _ => Step::Deliver(peer),
+                    }
+                })
+                .collect();
+            let a_policy = policies[rng.next(policies.len())];
+            let b_policy = policies[rng.next(policies.len())];
+
+            if let Err(e) = simulate(a_policy, b_policy, &script) {
+                panic!("{} for {:?}", e, script);
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! # Split Streams
+//!
+//! [`TelnetStream::split`] turns a stream into a [`TelnetReadHalf`] and a
+//! [`TelnetWriteHalf`] that can live on different threads, so a server can
+//! push output (pages, chat lines) while another thread waits for input.
+//!
+//! The halves share one stream behind a mutex: its negotiation state,
+//! option handlers and output queue. The read half waits for the peer on
+//! its own clone of the transport without holding the lock, then takes
+//! the lock just long enough to parse what arrived and queue any replies.
+//! Replies and the write half's output go through the same queue, so the
+//! [output ordering](crate::stream#output-ordering) rules still hold.
+//!
+//! ```rust,no_run
+//! use std::io::{Read, Write};
+//! use std::net::TcpListener;
+//! use std::thread;
+//! use telnet_negotiation::TelnetStream;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let (socket, _) = TcpListener::bind("127.0.0.1:2323")?.accept()?;
+//!     let (mut reader, mut writer) = TelnetStream::new(socket).split()?;
+//!
+//!     // Output from its own thread
+//!     let pusher = thread::spawn(move || writer.write_all(b"Page from the SysOp!\r\n"));
+//!
+//!     let mut line = [0; 256];
+//!     let n = reader.read(&mut line)?;
+//!     println!("Caller typed {:?}", &line[..n]);
+//!     pusher.join().unwrap()
+//! }
+//! ```
+//!
+//! Negotiation answers only arrive through the read half. Calls that wait
+//! for one by reading the stream themselves (`request_terminal_type`,
+//! `poll_negotiation`, `measure_round_trip` and the like) would compete
+//! with it, so make those before splitting. Afterwards, send requests and
+//! collect results through [`TelnetReadHalf::lock`] or
+//! [`TelnetWriteHalf::lock`], for example `send_timing_mark` then
+//! `round_trip_time`. START_TLS needs the whole stream; see
+//! [`TelnetReadHalf::reunite`].
+
+use crate::options::StartTlsState;
+use crate::stream::TelnetStream;
+use crate::transport::Transport;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Size of each read from the transport
+const READ_CHUNK: usize = 4096;
+
+impl<S: Transport> TelnetStream<S> {
+    /// Split into a read half and a write half that share this stream
+    ///
+    /// The read half gets a clone of the transport (see
+    /// [`Transport::try_clone`]), so transports that can't be cloned can't
+    /// be split; nor can a stream that has started START_TLS. On error the
+    /// stream is dropped.
+    pub fn split(mut self) -> io::Result<(TelnetReadHalf<S>, TelnetWriteHalf<S>)> {
+        if self.start_tls_state() != StartTlsState::Idle {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "a stream using START_TLS can't be split",
+            ));
+        }
+        let reader = self.get_ref().try_clone()?;
+
+        self.set_split(true);
+        let shared = Arc::new(Mutex::new(self));
+        Ok((
+            TelnetReadHalf {
+                reader,
+                shared: Arc::clone(&shared),
+            },
+            TelnetWriteHalf { shared },
+        ))
+    }
+}
+
+/// The reading half of a split [`TelnetStream`]
+pub struct TelnetReadHalf<S = TcpStream> {
+    /// Second handle on the connection, read without holding the lock
+    reader: S,
+    shared: Arc<Mutex<TelnetStream<S>>>,
+}
+
+/// The writing half of a split [`TelnetStream`]
+pub struct TelnetWriteHalf<S = TcpStream> {
+    shared: Arc<Mutex<TelnetStream<S>>>,
+}
+
+impl<S: Transport> TelnetReadHalf<S> {
+    /// The shared stream, for negotiation state and requests
+    ///
+    /// Hold the guard briefly: the read half needs the lock to handle
+    /// whatever the peer sends, and the write half to write. Don't read
+    /// through it; that is the read half's job.
+    pub fn lock(&self) -> MutexGuard<'_, TelnetStream<S>> {
+        lock(&self.shared)
+    }
+
+    /// Make reads give up with `WouldBlock` or `TimedOut` after `dur`
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.reader.set_read_timeout(dur)
+    }
+
+    /// Current read timeout; `None` means reads block
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.reader.read_timeout()
+    }
+
+    /// One read from the peer, returning whatever data it brought, none if
+    /// only telnet commands came, or `None` at end of file
+    pub(crate) fn read_once(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        let n = self.lock().read_buffered(buf);
+        if n > 0 {
+            return Ok(Some(n));
+        }
+
+        let mut raw = [0; READ_CHUNK];
+        let n = self.reader.read(&mut raw)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        let mut stream = self.lock();
+        stream.feed(&raw[..n])?;
+        Ok(Some(stream.read_buffered(buf)))
+    }
+
+    /// Put the halves back together into the stream they came from
+    ///
+    /// Fails, handing both back, if they came from different streams.
+    pub fn reunite(self, writer: TelnetWriteHalf<S>) -> Result<TelnetStream<S>, ReuniteError<S>> {
+        if !Arc::ptr_eq(&self.shared, &writer.shared) {
+            return Err(ReuniteError(self, writer));
+        }
+
+        drop(writer);
+        let shared = Arc::into_inner(self.shared).expect("both halves were given");
+        let mut stream = shared.into_inner().unwrap_or_else(PoisonError::into_inner);
+        stream.set_split(false);
+        Ok(stream)
+    }
+}
+
+impl<S: Transport> TelnetWriteHalf<S> {
+    /// The shared stream, for negotiation state and requests
+    ///
+    /// See [`TelnetReadHalf::lock`].
+    pub fn lock(&self) -> MutexGuard<'_, TelnetStream<S>> {
+        lock(&self.shared)
+    }
+}
+
+/// Reads clean application data, as [`TelnetStream`]'s `Read` does
+///
+/// Window resizes and signals interrupt a waiting read once each if the
+/// stream was set to; see [`TelnetStream::set_interrupt_on_resize`] and
+/// [`TelnetStream::set_interrupt_on_signal`].
+impl<S: Transport> Read for TelnetReadHalf<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut raw = [0; READ_CHUNK];
+        loop {
+            {
+                let mut stream = self.lock();
+                let n = stream.read_buffered(buf);
+                if n > 0 {
+                    return Ok(n);
+                }
+                stream.interrupt_if_due()?;
+            }
+
+            // Wait without the lock so the write half carries on meanwhile
+            let n = self.reader.read(&mut raw)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.lock().feed(&raw[..n])?;
+        }
+    }
+}
+
+/// Writes through the shared stream, as [`TelnetStream`]'s `Write` does
+impl<S: Transport> Write for TelnetWriteHalf<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.lock().write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+/// Halves from two different streams, given back by
+/// [`TelnetReadHalf::reunite`]
+pub struct ReuniteError<S = TcpStream>(pub TelnetReadHalf<S>, pub TelnetWriteHalf<S>);
+
+impl<S> fmt::Debug for ReuniteError<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReuniteError(..)")
+    }
+}
+
+impl<S> fmt::Display for ReuniteError<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite halves of different streams")
+    }
+}
+
+impl<S> std::error::Error for ReuniteError<S> {}
+
+/// Lock the shared stream, carrying on if another thread panicked with it
+fn lock<S>(shared: &Mutex<TelnetStream<S>>) -> MutexGuard<'_, TelnetStream<S>> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{IAC, TelnetCommand, TelnetOption};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    fn connected_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn test_halves_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<TelnetReadHalf>();
+        assert_send::<TelnetWriteHalf>();
+    }
+
+    #[test]
+    fn test_split_reads_and_writes_from_two_threads() {
+        let (server, mut client) = connected_pair();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let (mut reader, mut writer) = TelnetStream::new(server).split().unwrap();
+
+        // Output pushed while the read half is blocked waiting
+        let pusher = thread::spawn(move || {
+            writer.write_all(b"page").unwrap();
+            writer.flush().unwrap();
+            writer
+        });
+
+        // The reply to DO TIMING-MARK comes from the read half's thread
+        client
+            .write_all(&[
+                IAC,
+                TelnetCommand::DO.to_byte(),
+                TelnetOption::TIMING_MARK.to_byte(),
+            ])
+            .unwrap();
+        client.write_all(b"hi").unwrap();
+
+        let mut buf = [0; 16];
+        let n = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"hi");
+        let writer = pusher.join().unwrap();
+
+        let will_tm = [
+            IAC,
+            TelnetCommand::WILL.to_byte(),
+            TelnetOption::TIMING_MARK.to_byte(),
+        ];
+        let go_ahead = [IAC, TelnetCommand::GA.to_byte()];
+        let mut received = Vec::new();
+        while received.len() < b"page".len() + will_tm.len() + go_ahead.len() {
+            let n = client.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed early");
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert!(received.windows(4).any(|w| w == b"page"));
+        assert!(received.windows(3).any(|w| w == will_tm));
+
+        let stream = reader.reunite(writer).unwrap();
+        assert_eq!(stream.pending_output_len(), 0);
+    }
+
+    #[test]
+    fn test_reunite_refuses_halves_of_different_streams() {
+        let (first, _client_one) = connected_pair();
+        let (second, _client_two) = connected_pair();
+        let (reader, _) = TelnetStream::new(first).split().unwrap();
+        let (_, writer) = TelnetStream::new(second).split().unwrap();
+
+        let Err(ReuniteError(reader, writer)) = reader.reunite(writer) else {
+            panic!("halves of different streams were reunited");
+        };
+        assert!(!Arc::ptr_eq(&reader.shared, &writer.shared));
+    }
+
+    #[test]
+    fn test_start_tls_waits_for_reunite() {
+        struct Refuse;
+        impl crate::options::TlsUpgrader for Refuse {
+            fn upgrade(
+                &mut self,
+                _socket: TcpStream,
+                _buffered: Vec<u8>,
+            ) -> io::Result<Box<dyn crate::options::TlsTransport>> {
+                Err(io::ErrorKind::Unsupported.into())
+            }
+        }
+
+        let (server, _client) = connected_pair();
+        let (reader, writer) = TelnetStream::new(server).split().unwrap();
+        let error = writer.lock().offer_start_tls(Box::new(Refuse)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+
+        let mut stream = reader.reunite(writer).unwrap();
+        stream.offer_start_tls(Box::new(Refuse)).unwrap();
+        assert_eq!(stream.start_tls_state(), StartTlsState::Offered);
+    }
+}
@@ -0,0 +1,207 @@
+//! # Connection statistics
+//!
+//! A [`TelnetStream`](crate::TelnetStream) counts what crosses it: raw
+//! bytes each way, the application data inside them, and the telnet
+//! sequences parsed and sent. [`TelnetStream::stats`] returns the totals
+//! so far.
+//!
+//! For metrics systems that aggregate many connections, such as a
+//! Prometheus collector, [`TelnetStream::set_stats_hook`] calls back with
+//! the counts added since the previous call, which can be summed across
+//! connections as they are:
+//!
+//! ```rust,no_run
+//! use std::sync::{Arc, Mutex};
+//! use std::time::Duration;
+//! use telnet_negotiation::{ConnectionStats, TelnetStream};
+//!
+//! fn watch(stream: &mut TelnetStream, totals: Arc<Mutex<ConnectionStats>>) {
+//!     stream.set_stats_hook(Duration::from_secs(10), move |added| {
+//!         *totals.lock().unwrap() += *added;
+//!     });
+//! }
+//! ```
+//!
+//! There is no MCCP support yet (the `compression` feature is reserved for
+//! it), so there are no compression counters either.
+//!
+//! [`TelnetStream::stats`]: crate::TelnetStream::stats
+//! [`TelnetStream::set_stats_hook`]: crate::TelnetStream::set_stats_hook
+
+use crate::protocol::{IAC, TelnetCommand};
+
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+/// Protocol counters for one connection, or added across several
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionStats {
+    /// Bytes read from the transport, telnet sequences included
+    pub bytes_received: u64,
+    /// Bytes the transport took from us
+    pub bytes_sent: u64,
+    /// Application data found among the bytes received
+    pub data_received: u64,
+    /// Application data accepted by `write`, before any encoding
+    pub data_sent: u64,
+    /// Telnet sequences parsed: commands, negotiations and sub-negotiations
+    pub sequences_received: u64,
+    /// WILL, WONT, DO and DONT received
+    pub negotiations_received: u64,
+    /// WILL, WONT, DO and DONT sent
+    pub negotiations_sent: u64,
+    /// Sub-negotiations received
+    pub subnegotiations_received: u64,
+    /// Sub-negotiations sent
+    pub subnegotiations_sent: u64,
+    /// Payload bytes of the sub-negotiations received
+    pub subnegotiation_bytes_received: u64,
+    /// Largest sub-negotiation payload received so far
+    pub largest_subnegotiation: usize,
+    /// Other commands (NOP, GA, AYT and the like) sent
+    pub commands_sent: u64,
+}
+
+impl ConnectionStats {
+    /// The counts added since `earlier`, an older copy of the same
+    /// connection's stats
+    ///
+    /// `largest_subnegotiation` isn't a count and is kept as it is.
+    pub fn since(&self, earlier: &ConnectionStats) -> ConnectionStats {
+        ConnectionStats {
+            bytes_received: self.bytes_received - earlier.bytes_received,
+            bytes_sent: self.bytes_sent - earlier.bytes_sent,
+            data_received: self.data_received - earlier.data_received,
+            data_sent: self.data_sent - earlier.data_sent,
+            sequences_received: self.sequences_received - earlier.sequences_received,
+            negotiations_received: self.negotiations_received - earlier.negotiations_received,
+            negotiations_sent: self.negotiations_sent - earlier.negotiations_sent,
+            subnegotiations_received: self.subnegotiations_received
+                - earlier.subnegotiations_received,
+            subnegotiations_sent: self.subnegotiations_sent - earlier.subnegotiations_sent,
+            subnegotiation_bytes_received: self.subnegotiation_bytes_received
+                - earlier.subnegotiation_bytes_received,
+            largest_subnegotiation: self.largest_subnegotiation,
+            commands_sent: self.commands_sent - earlier.commands_sent,
+        }
+    }
+
+    /// Count one protocol unit we queued to send
+    pub(crate) fn count_sent(&mut self, unit: &[u8]) {
+        let &[IAC, command, ..] = unit else {
+            return;
+        };
+        match TelnetCommand::from_byte(command) {
+            Some(TelnetCommand::SB) => self.subnegotiations_sent += 1,
+            Some(
+                TelnetCommand::WILL | TelnetCommand::WONT | TelnetCommand::DO | TelnetCommand::DONT,
+            ) => self.negotiations_sent += 1,
+            _ => self.commands_sent += 1,
+        }
+    }
+}
+
+/// Totals across connections; `largest_subnegotiation` takes the larger
+impl AddAssign for ConnectionStats {
+    fn add_assign(&mut self, other: ConnectionStats) {
+        self.bytes_received += other.bytes_received;
+        self.bytes_sent += other.bytes_sent;
+        self.data_received += other.data_received;
+        self.data_sent += other.data_sent;
+        self.sequences_received += other.sequences_received;
+        self.negotiations_received += other.negotiations_received;
+        self.negotiations_sent += other.negotiations_sent;
+        self.subnegotiations_received += other.subnegotiations_received;
+        self.subnegotiations_sent += other.subnegotiations_sent;
+        self.subnegotiation_bytes_received += other.subnegotiation_bytes_received;
+        self.largest_subnegotiation = self
+            .largest_subnegotiation
+            .max(other.largest_subnegotiation);
+        self.commands_sent += other.commands_sent;
+    }
+}
+
+/// A callback set with [`TelnetStream::set_stats_hook`](crate::TelnetStream::set_stats_hook)
+pub(crate) struct StatsHook {
+    every: Duration,
+    last_called: Instant,
+    /// What the hook has been told about so far
+    reported: ConnectionStats,
+    hook: Box<dyn FnMut(&ConnectionStats) + Send>,
+}
+
+impl StatsHook {
+    pub(crate) fn new(every: Duration, hook: Box<dyn FnMut(&ConnectionStats) + Send>) -> Self {
+        Self {
+            every,
+            last_called: Instant::now(),
+            reported: ConnectionStats::default(),
+            hook,
+        }
+    }
+
+    /// Pass on what changed since the last call, if `every` has passed
+    pub(crate) fn call_if_due(&mut self, stats: &ConnectionStats) {
+        if self.last_called.elapsed() >= self.every {
+            self.call(stats);
+        }
+    }
+
+    /// Pass on what changed since the last call, if anything did
+    pub(crate) fn call(&mut self, stats: &ConnectionStats) {
+        self.last_called = Instant::now();
+        if *stats == self.reported {
+            return;
+        }
+        let added = stats.since(&self.reported);
+        self.reported = *stats;
+        (self.hook)(&added);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_since_and_totals() {
+        let earlier = ConnectionStats {
+            bytes_received: 10,
+            subnegotiations_received: 1,
+            largest_subnegotiation: 4,
+            ..ConnectionStats::default()
+        };
+        let later = ConnectionStats {
+            bytes_received: 25,
+            subnegotiations_received: 3,
+            largest_subnegotiation: 9,
+            ..ConnectionStats::default()
+        };
+        let added = later.since(&earlier);
+        assert_eq!(added.bytes_received, 15);
+        assert_eq!(added.subnegotiations_received, 2);
+        assert_eq!(added.largest_subnegotiation, 9);
+
+        let mut totals = earlier;
+        totals += added;
+        assert_eq!(totals, later);
+    }
+
+    #[test]
+    fn test_count_sent() {
+        let mut stats = ConnectionStats::default();
+        stats.count_sent(&[IAC, 251, 1]);
+        stats.count_sent(&[IAC, 250, 24, 1, IAC, 240]);
+        stats.count_sent(&[IAC, 241]);
+        stats.count_sent(b"\r\n[Yes]\r\n");
+        assert_eq!(
+            (
+                stats.negotiations_sent,
+                stats.subnegotiations_sent,
+                stats.commands_sent
+            ),
+            (1, 1, 1)
+        );
+    }
+}
@@ -1,7 +1,8 @@
 //! # TelnetStream - Transparent Telnet Protocol Wrapper
 //!
-//! This module provides `TelnetStream`, a transparent wrapper around `TcpStream`
-//! that automatically handles Telnet protocol negotiation and command processing.
+//! This module provides `TelnetStream`, a transparent wrapper around a `TcpStream`
+//! (or any other [`Transport`]) that automatically handles Telnet protocol
+//! negotiation and command processing.
 //!
 //! ## Key Features:
 //!
@@ -41,56 +42,368 @@
 //! - `OptionNegotiator`: Handles RFC 1143 option negotiation
 //! - Internal buffers for clean data separation
 //! - Automatic response generation and transmission
+//!
+//! ### Transports
+//! `TelnetStream<S>` runs over any [`Transport`] and defaults to
+//! `TcpStream`. Socket options such as `set_nodelay`, `ttl` and `peer_addr`
+//! are only on `TelnetStream<TcpStream>`; other transports are reached
+//! through [`TelnetStream::get_ref`].
+//!
+//! ### Client Mode
+//! [`TelnetStream::client`] wraps a connection to a remote server instead,
+//! for gateways, MUD clients and other outbound sessions; or pick the
+//! [`Role`] at run time with [`TelnetStream::with_role`]. A client answers
+//! DO TERMINAL-TYPE with WILL and each SEND with IS, reports the window
+//! size it was given once the server agrees to NAWS, and never offers to
+//! echo. [`TelnetStream::is_echo_remote`] follows the server's WILL and
+//! WONT ECHO, so the application knows when to stop echoing typed text
+//! itself.
+//!
+//! ### Newlines and BINARY (RFC 856)
+//! Until BINARY is enabled for a direction, data follows the RFC 854 NVT
+//! rules: a bare CR is sent as CR NUL and a bare LF as CR LF, and CR NUL
+//! arriving from the peer is read as a plain CR. With BINARY on, bytes in
+//! that direction pass through untouched (apart from IAC doubling).
+//! Sending is governed by our side of the option, receiving by the peer's.
+//! [`TelnetStream::set_newline_mode`] can also read every line ending the
+//! peer sends as a plain `\n`, or switch newline translation off.
+//!
+//! ### Output Ordering
+//! Everything the stream sends goes through one queue, in the order it was
+//! produced: application data, negotiation replies, sub-negotiations, GA.
+//! Replies to what the peer sent are queued while a batch of input is
+//! parsed, behind any output already waiting, and sent once the batch is
+//! done. A reply the transport can't take yet stays queued and goes out
+//! before the next write, and a failed send never fails the read that
+//! produced it. Output is only ever split between whole protocol units
+//! and whole data bytes (an escaped IAC counts as one), so a reply can
+//! land between two writes but never inside one.
+//!
+//! ### Write Buffering
+//! Writes go straight to the transport by default. Under
+//! [`FlushPolicy::OnNewline`] or [`FlushPolicy::Manual`] they are encoded
+//! into the output queue instead and sent at the end of a line or on
+//! `flush` (or sooner, once [`WRITE_BUFFER_CAPACITY`] bytes are waiting);
+//! see [`TelnetStream::set_flush_policy`]. `write_vectored` encodes all
+//! its slices for one transport write.
+//!
+//! ### Split Halves
+//! [`TelnetStream::split`] gives a read half and a write half that share
+//! the stream, for servers that push output from another thread; see
+//! [`crate::split`].
+//!
+//! ### Memory Budget
+//! Everything buffered from the peer counts against a per-connection
+//! [`MemoryBudget`] (1 MiB by default). A peer that goes over it is
+//! flushed, trimmed or disconnected according to the budget's
+//! [`OverflowPolicy`]; see [`TelnetStream::set_memory_budget`] and
+//! [`TelnetStream::memory_stats`].
+//!
+//! ### Parser Limits
+//! The parser keeps at most 64 KiB of any one sub-negotiation and drops
+//! longer ones whole, whatever the memory budget. [`ParserLimits`] can
+//! raise or lower that, and truncate the payload or fail the connection
+//! instead; see [`TelnetStream::set_parser_limits`].
+//!
+//! ### Loop Guard
+//! A peer that keeps repeating an IAC WILL or IAC DO we refused is stopped
+//! by a [`LoopGuard`]: by default, after five refusals of the same request
+//! within ten seconds the stream stops answering it. The guard can instead
+//! drop the connection; see [`TelnetStream::set_loop_guard`].
+//!
+//! ### Errors
+//! Methods fail with `io::Error`, as a socket would. Errors the stream
+//! raises over the peer's behaviour (a sub-negotiation over the limit, a
+//! request repeated past the loop guard, a START_TLS handshake left
+//! unfinished) carry a [`TelnetError`] that [`TelnetError::from`] gets
+//! back, so an application can tell protocol abuse from a dropped line.
+//! Malformed sequences that are passed on as data are reported as
+//! [`NegotiationEvent::ProtocolViolation`].
+//!
+//! ### Prompts (RFC 885)
+//! [`TelnetStream::send_prompt`] ends a prompt with IAC EOR once
+//! END_OF_RECORD is enabled, or IAC GA while Go Ahead isn't suppressed,
+//! so MUD clients can tell a prompt from a line still being written.
+//! Marks arriving from the peer are collected as [`PromptMark`]s.
+//!
+//! ### Go Ahead (RFC 858)
+//! Until Go Ahead is suppressed the connection is half duplex: each
+//! `flush` that follows new data ends our turn with IAC GA, so line-at-a-
+//! time clients know when to let the user type. Once we have agreed to
+//! WILL SUPPRESS-GO-AHEAD no GA is sent again; ask for that in both
+//! directions with [`TelnetStream::request_suppress_go_ahead`] and check
+//! [`TelnetStream::is_full_duplex`].
+//!
+//! ### Simple Commands
+//! IAC AYT is answered with a short status line (see
+//! [`TelnetStream::set_ayt_reply`]) and IAC NOP is ignored. IP, BRK, AO,
+//! EC and EL are kept for the application to act on with
+//! [`TelnetStream::take_control_events`], each with its place in the data.
+//! With [`TelnetStream::set_interrupt_on_signal`] on, an IP, BRK or AO also
+//! makes a `read` waiting for input return `ErrorKind::Interrupted`, so a
+//! caller's Ctrl+C can stop a listing without waiting for Enter.
+//!
+//! ### Synch (RFC 854)
+//! A client flushes its typeahead by sending IAC DM as TCP urgent data.
+//! The standard library can't see the urgent flag, so the stream acts when
+//! the DM turns up in the normal data: data received before it that the
+//! application hasn't read yet is dropped, and the DM is kept as a control
+//! event. Turn that off with [`TelnetStream::set_discard_on_synch`].
+//!
+//! ### Window Resizes
+//! Each NAWS report that changes the client's window size is kept for
+//! [`TelnetStream::take_resize`]. With
+//! [`TelnetStream::set_interrupt_on_resize`] on, a `read` waiting for input
+//! also returns `ErrorKind::Interrupted` once per change, so an application
+//! can redraw for the new size instead of waiting for the next keypress.
+//! Dragging a window edge sends a report for every step; with
+//! [`TelnetStream::set_resize_debounce`] a size only counts once the client
+//! has stopped changing it, so a storm of reports costs one redraw.
+//!
+//! ### Negotiation Events
+//! With [`TelnetStream::set_negotiation_events`] on, the stream keeps a
+//! [`NegotiationEvent`] for each option that switches on or off on either
+//! side, each sub-negotiation the peer sends (handled or not), each window
+//! resize, each echo change and each malformed sequence, in the order
+//! they happened. Collect them with
+//! [`TelnetStream::take_negotiation_events`] instead of downcasting
+//! handlers to see what changed.
+//!
+//! ### Session Handoff
+//! [`TelnetStream::snapshot`] saves what negotiation has settled (option
+//! states and what each handler learned about the peer) so another
+//! process or worker holding the same socket can
+//! [`restore`](TelnetStream::restore) it and carry on without negotiating
+//! again. With the `serde` feature the snapshot can be serialized.
+//!
+//! ### Timing Mark (RFC 860)
+//! [`TelnetStream::send_timing_mark`] sends IAC DO TIMING-MARK, which the
+//! peer answers only once it has dealt with everything sent before it.
+//! The time until the answer arrives is kept as
+//! [`TelnetStream::round_trip_time`], making the mark a cheap latency probe
+//! and keepalive. Marks never change option state, so they can be sent any
+//! number of times, and a peer's DO TIMING-MARK is always answered WILL.
+//!
+//! ### Keepalive
+//! With [`TelnetStream::set_keepalive`] on, an IAC NOP (or a timing mark,
+//! see [`KeepaliveProbe`]) goes out whenever nothing else has been sent
+//! for the interval, so NAT gateways and firewalls don't drop a caller
+//! who is reading a long message. A blocking `read` sends them while it
+//! waits; applications with their own loop call [`TelnetStream::tick`].
+//!
+//! ### START_TLS
+//! [`TelnetStream::offer_start_tls`] upgrades the connection to TLS mid-session.
+//! Once the client agrees, all reads and writes go through the TLS session
+//! supplied by the application's [`TlsUpgrader`].
+
+use crate::ansi::{AnsiFilter, AnsiProfile};
+use crate::audit::OptionRegistry;
+use crate::budget::{MemoryBudget, MemoryStats, OverflowPolicy};
+use crate::capabilities::CapabilitySources;
+use crate::encoding::{Encoding, Transcoder};
+// Lived here before the capabilities module; the old paths still work
+pub use crate::capabilities::{ColorDepth, TerminalCapabilities};
+use crate::error::{ProtocolViolation, TelnetError};
+use crate::events::TextEvents;
+use crate::history::{Direction, NegotiationHistory, NegotiationStats};
+use crate::loop_guard::{LoopGuard, LoopPolicy};
+use crate::negotiation::{
+    NegotiationResult, NegotiatorDump, NegotiatorSnapshot, OptionNegotiator, OptionPolicy,
+    OptionState, Side, StaleNegotiation,
+};
+use crate::options::start_tls::FOLLOWS;
+use crate::options::{
+    EchoOption, Environment, HandlerTable, LinemodeOption, NawsOption, NewEnvironOption,
+    SendLocationOption, StartTlsOption, StartTlsState, SubNegotiationCommand, TelnetOptionHandler,
+    TerminalSpeed, TerminalSpeedOption, TerminalTypeOption, TlsTransport, TlsUpgrader, WindowSize,
+};
+use crate::parser::{LimitPolicy, ParseEvent, ParserLimits, TelnetParser};
+use crate::profile::{BUILT_IN_REQUESTS, NegotiationProfile};
+use crate::protocol::{IAC, TelnetCommand, TelnetOption, TelnetSequence};
+use crate::ring::RingBuffer;
+use crate::stats::{ConnectionStats, StatsHook};
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IoSlice, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Terminal type a client-mode stream reports until told otherwise
+const DEFAULT_CLIENT_TERMINAL_TYPE: &str = "ANSI";
+
+/// Answer to IAC AYT until told otherwise, as BSD telnetd sends it
+const DEFAULT_AYT_REPLY: &str = "\r\n[Yes]\r\n";
+
+/// Negotiation events kept before the oldest are dropped
+const MAX_NEGOTIATION_EVENTS: usize = 256;
 
-use crate::negotiation::{OptionNegotiator, Side};
-use crate::options::{EchoOption, NawsOption, TelnetOptionHandler, TerminalTypeOption, WindowSize};
-use crate::parser::TelnetParser;
-use crate::protocol::{TelnetCommand, TelnetOption, TelnetSequence};
-use std::collections::{HashMap, VecDeque};
-use std::io::{self, Read, Write};
-use std::net::{SocketAddr, TcpStream};
-use std::time::Duration;
-
-/// Terminal capabilities detected from option negotiation
-#[derive(Debug, Clone, Default)]
-pub struct TerminalCapabilities {
-    /// Terminal width in characters
-    pub width: Option<u16>,
-    /// Terminal height in lines
-    pub height: Option<u16>,
-    /// Terminal type string (e.g., "XTERM", "VT100")
-    pub terminal_type: Option<String>,
-    /// Supports ANSI escape sequences
-    pub supports_ansi: bool,
-    /// Supports color output
-    pub supports_color: bool,
-    /// Color support depth
-    pub color_depth: ColorDepth,
+/// Encoded output gathered under a buffering [`FlushPolicy`] before it is
+/// sent regardless
+pub const WRITE_BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// A prompt or record boundary the peer marked with IAC EOR or IAC GA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptMark {
+    /// `TelnetCommand::EOR` or `TelnetCommand::GA`
+    pub command: TelnetCommand,
+    /// Data bytes received before the mark, counted over the whole
+    /// connection; the prompt ends once that many bytes have been read
+    pub offset: u64,
 }
 
-/// Color support levels detected from terminal type
+/// A control command from the peer, as collected by
+/// [`TelnetStream::take_control_events`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ColorDepth {
-    /// No color support (monochrome)
-    Monochrome,
-    /// Basic 8-color support (3-bit)
-    Basic8,
-    /// 256-color support (8-bit)  
-    Extended256,
-    /// True color support (24-bit RGB)
-    TrueColor,
+pub struct ControlEvent {
+    /// `TelnetCommand::IP`, `BRK`, `AO`, `EC`, `EL` or `DM`
+    pub command: TelnetCommand,
+    /// Data bytes received before the command, counted over the whole
+    /// connection, as for [`PromptMark::offset`]
+    pub offset: u64,
 }
 
-impl Default for ColorDepth {
-    fn default() -> Self {
-        ColorDepth::Monochrome
+impl ControlEvent {
+    /// Whether this asks the application to stop what it is doing: IP,
+    /// BRK or AO rather than a line editing command
+    pub fn is_signal(&self) -> bool {
+        matches!(
+            self.command,
+            TelnetCommand::IP | TelnetCommand::BRK | TelnetCommand::AO
+        )
     }
 }
 
+/// Something that changed in negotiation, as collected by
+/// [`TelnetStream::take_negotiation_events`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NegotiationEvent {
+    /// An option was switched on for one side
+    OptionEnabled { side: Side, option: TelnetOption },
+    /// An option was switched off for one side
+    OptionDisabled { side: Side, option: TelnetOption },
+    /// The peer sent a sub-negotiation, already passed to the option's
+    /// handler if it has one
+    Subnegotiation { option: TelnetOption, data: Vec<u8> },
+    /// The client's window size changed, as for [`TelnetStream::take_resize`]
+    WindowResized(WindowSize),
+    /// Echoing moved between the sides; `remote` as in
+    /// [`TelnetStream::is_echo_remote`]
+    EchoChanged { remote: bool },
+    /// The peer sent a malformed sequence, passed on as data
+    ProtocolViolation(ProtocolViolation),
+}
+
+/// A connection's telnet state, saved by [`TelnetStream::snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamSnapshot {
+    pub negotiator: NegotiatorSnapshot,
+    /// What each option handler learned, by option byte; see
+    /// [`TelnetOptionHandler::snapshot`]
+    pub handlers: Vec<(u8, Vec<u8>)>,
+}
+
+/// What [`TelnetStream::tick`] sends to keep an idle connection alive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepaliveProbe {
+    /// IAC NOP, which the peer ignores (the default)
+    #[default]
+    Nop,
+    /// IAC DO TIMING-MARK, which the peer answers, so each keepalive also
+    /// updates [`TelnetStream::round_trip_time`]
+    TimingMark,
+}
+
+/// Which end of a telnet connection a [`TelnetStream`] plays
+///
+/// Option negotiation is symmetric, but a few answers are not: a server
+/// asks for the terminal type and offers to echo, while a client reports
+/// its terminal type and window size and lets the server echo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Role {
+    /// We accepted the connection (the default)
+    #[default]
+    Server,
+    /// We opened the connection to a remote server
+    Client,
+}
+
+/// When written data is handed to the transport; see
+/// [`TelnetStream::set_flush_policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Every write goes to the transport (the default)
+    #[default]
+    Immediate,
+    /// Writes are held until one ends a line, or until `flush`
+    OnNewline,
+    /// Writes are held until `flush`
+    Manual,
+}
+
+/// How a stream translates newlines outside BINARY; see
+/// [`TelnetStream::set_newline_mode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewlineMode {
+    /// RFC 854 NVT rules: LF is sent as CR LF and a bare CR as CR NUL, and
+    /// CR NUL from the peer is read as CR (the default)
+    #[default]
+    Nvt,
+    /// As [`NewlineMode::Nvt`] when sending, and every line ending from the
+    /// peer (CR LF, CR NUL, a bare CR or LF) is read as a single `\n`
+    Normalize,
+    /// No translation either way; only IAC is escaped
+    Raw,
+}
+
+/// How closely a stream checks written data before it goes out; see
+/// [`TelnetStream::set_write_check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteCheck {
+    /// Data is escaped and sent without further checks (the default)
+    #[default]
+    Off,
+    /// A write holding IAC followed by a command byte is refused rather
+    /// than escaped, and [`TelnetStream::write_raw`] only sends
+    /// well-formed telnet
+    Strict,
+}
+
+/// Whether a stream converts data between the application's character set
+/// and the client's; see [`TelnetStream::set_transcoding`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transcoding {
+    /// Bytes pass through as they are (the default)
+    #[default]
+    Off,
+    /// The application reads and writes `app`; the client gets the
+    /// character set it reported (see [`TerminalCapabilities::charset`]),
+    /// and nothing is converted until it reports one this crate knows
+    Negotiated { app: Encoding },
+    /// The application reads and writes `app`, and the client `peer`
+    Manual { app: Encoding, peer: Encoding },
+}
+
+/// Whether a stream fits outgoing escape sequences to the client's
+/// terminal; see [`TelnetStream::set_ansi_filtering`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnsiFiltering {
+    /// Escape sequences pass through as they are (the default)
+    #[default]
+    Off,
+    /// Follow [`TelnetStream::get_terminal_capabilities`], once the client
+    /// has named its terminal
+    Negotiated,
+    /// Fit output to this profile whatever the client reports
+    Manual(AnsiProfile),
+}
+
 /// A transparent wrapper around TcpStream that handles Telnet protocol automatically
 ///
 /// `TelnetStream` provides the same interface as `TcpStream` while transparently
 /// handling all telnet option negotiation and command processing in the background.
+/// `S` is the underlying [`Transport`]; anything other than the default
+/// `TcpStream` works too, such as a `UnixStream` or an in-memory test double.
 ///
 /// ## Example Usage
 /// ```rust,no_run
@@ -112,9 +425,9 @@ impl Default for ColorDepth {
 ///     Ok(())
 /// }
 /// ```
-pub struct TelnetStream {
-    /// Underlying TCP stream
-    inner: TcpStream,
+pub struct TelnetStream<S = TcpStream> {
+    /// Underlying transport
+    inner: S,
 
     /// Telnet command parser for incoming data
     parser: TelnetParser,
@@ -123,27 +436,187 @@ pub struct TelnetStream {
     negotiator: OptionNegotiator,
 
     /// Option handlers for sub-negotiation processing
-    option_handlers: HashMap<TelnetOption, Box<dyn TelnetOptionHandler>>,
+    option_handlers: HandlerTable,
 
     /// Buffer for clean application data (telnet commands filtered out)
-    data_buffer: VecDeque<u8>,
+    data_buffer: RingBuffer,
+
+    /// Last data byte received was a CR, so a following NUL is padding
+    read_after_cr: bool,
+
+    /// The last line read ended at a CR, so an LF straight after it
+    /// belongs to the same line ending
+    line_after_cr: bool,
+
+    /// Last data byte sent was a CR, so the next one decides between CR
+    /// LF and CR NUL
+    write_after_cr: bool,
+
+    /// Data was written since our turn last ended, so the next flush owes
+    /// an IAC GA unless Go Ahead is suppressed
+    owes_go_ahead: bool,
 
     /// Buffer for incomplete reads from the underlying stream
     read_buffer: Vec<u8>,
 
+    /// Data bytes received so far, for placing prompt marks
+    data_received: u64,
+
+    /// EOR and GA marks not yet collected by the application
+    prompt_marks: VecDeque<PromptMark>,
+
+    /// IP, BRK, AO, EC, EL and DM commands not yet collected by the
+    /// application
+    signals: VecDeque<ControlEvent>,
+
+    /// Whether a DM throws away the unread data before it
+    discard_on_synch: bool,
+
+    /// Whether an IP, BRK or AO should cut a blocking `read` short
+    interrupt_on_signal: bool,
+
+    /// A signal hasn't interrupted a `read` yet
+    signal_interrupt_due: bool,
+
+    /// What to send back for IAC AYT, if anything
+    ayt_reply: Option<String>,
+
     /// Whether to log telnet activity for debugging
     debug_logging: bool,
+
+    /// Recent negotiation commands, kept per option
+    history: NegotiationHistory,
+
+    /// TLS session that replaced the plain socket after START_TLS
+    tls: Option<Box<dyn TlsTransport>>,
+
+    /// Backend for an outstanding START_TLS offer
+    tls_upgrader: Option<Box<dyn TlsUpgrader<S>>>,
+
+    /// Output held back between our FOLLOWS and the TLS handshake
+    held_output: Vec<u8>,
+
+    /// Encoded output the transport hasn't taken yet (the tail of a
+    /// protocol unit or half of an escaped IAC); always sent before
+    /// anything newer
+    pending_output: Vec<u8>,
+
+    /// Input is being parsed, so replies are queued rather than written
+    /// until the batch is done
+    replying: bool,
+
+    /// When written data goes to the transport
+    flush_policy: FlushPolicy,
+
+    /// Newline translation outside BINARY
+    newline_mode: NewlineMode,
+
+    /// Checks made on written data before it's sent
+    write_check: WriteCheck,
+
+    /// Character set conversion asked for
+    transcoding: Transcoding,
+
+    /// Converters for the data path while the two character sets differ
+    incoming: Option<Transcoder>,
+    outgoing: Option<Transcoder>,
+
+    /// Escape sequence downgrading asked for, and the filter doing it
+    ansi_filtering: AnsiFiltering,
+    ansi_filter: Option<AnsiFilter>,
+
+    /// Scratch space for encoding writes, kept to save allocating per call
+    encoded: Vec<u8>,
+
+    /// Whether we accepted this connection or opened it
+    role: Role,
+
+    /// Terminal type to report when acting as a client
+    client_terminal_type: String,
+
+    /// Window size to report when acting as a client
+    client_window_size: Option<WindowSize>,
+
+    /// Cap on what the peer can make us buffer
+    budget: MemoryBudget,
+
+    /// Most memory this connection has held at once
+    memory_peak: usize,
+
+    /// Times the peer went over budget
+    budget_overflows: u64,
+
+    /// Cap on how often the peer may repeat a request we refused
+    loop_guard: LoopGuard,
+
+    /// When we refused each recent WILL or DO, oldest first
+    refusals: VecDeque<(Instant, TelnetCommand, TelnetOption)>,
+
+    /// A request the peer repeated past the guard, to disconnect over
+    negotiation_loop: Option<(TelnetCommand, TelnetOption)>,
+
+    /// A sub-negotiation that went over the parser's limits under
+    /// [`LimitPolicy::Error`], to disconnect over
+    parser_overflow: Option<TelnetOption>,
+
+    /// When each unanswered IAC DO TIMING-MARK went out, oldest first
+    timing_marks: VecDeque<Instant>,
+
+    /// Round trip of the most recently answered timing mark
+    round_trip: Option<Duration>,
+
+    /// How long output may stay idle before a keepalive goes out
+    keepalive: Option<Duration>,
+    keepalive_probe: KeepaliveProbe,
+
+    /// When the transport last took any of our output
+    last_sent: Instant,
+
+    /// Window size the client switched to, not yet collected
+    resize: Option<WindowSize>,
+
+    /// Last window size reported as a resize
+    reported_size: Option<WindowSize>,
+
+    /// Quiet period a new window size must last before it counts
+    resize_debounce: Option<Duration>,
+
+    /// Newest window size still inside the quiet period, and when it came
+    pending_resize: Option<(WindowSize, Instant)>,
+
+    /// Whether a resize should cut a blocking `read` short
+    interrupt_on_resize: bool,
+
+    /// A resize hasn't interrupted a `read` yet
+    resize_interrupt_due: bool,
+
+    /// Split into halves, with reads coming from another handle
+    split: bool,
+
+    /// Whether to keep negotiation events
+    keep_events: bool,
+
+    /// Negotiation events not yet collected by the application
+    negotiation_events: VecDeque<NegotiationEvent>,
+
+    /// Everything known about the client's terminal, merged from the
+    /// option handlers whenever one of them learns something
+    capabilities: TerminalCapabilities,
+
+    /// Protocol counters, and the callback exporting them
+    stats: ConnectionStats,
+    stats_hook: Option<StatsHook>,
 }
 
-impl TelnetStream {
-    /// Create a new TelnetStream wrapping the provided TcpStream
+impl<S: Transport> TelnetStream<S> {
+    /// Create a new TelnetStream wrapping the provided transport
     ///
     /// The stream will immediately begin transparent telnet protocol handling.
     /// All telnet option negotiation will be handled automatically according
     /// to RFC 1143 specifications.
     ///
     /// # Arguments
-    /// * `stream` - The underlying TcpStream to wrap
+    /// * `stream` - The underlying transport to wrap
     ///
     /// # Returns
     /// A new TelnetStream ready for transparent telnet operation
@@ -159,36 +632,114 @@ impl TelnetStream {
     ///     Ok(())
     /// }
     /// ```
-    pub fn new(stream: TcpStream) -> Self {
-        let mut telnet_stream = Self {
-            inner: stream,
-            parser: TelnetParser::new(),
-            negotiator: OptionNegotiator::new(),
-            option_handlers: HashMap::new(),
-            data_buffer: VecDeque::new(),
-            read_buffer: Vec::new(),
-            debug_logging: false,
-        };
-
-        // Register core telnet option handlers
-        telnet_stream.register_core_options();
-
-        telnet_stream
+    pub fn new(stream: S) -> Self {
+        Self::build(stream, Role::Server, false)
     }
 
     /// Create a new TelnetStream with debug logging enabled
     ///
     /// This will log all telnet negotiation activity to stderr, useful for
     /// debugging telnet protocol interactions.
-    pub fn with_debug(stream: TcpStream) -> Self {
+    pub fn with_debug(stream: S) -> Self {
+        Self::build(stream, Role::Server, true)
+    }
+
+    /// Wrap a connection we opened to a remote telnet server
+    ///
+    /// The stream answers as a client: it agrees to send its terminal type
+    /// and window size when the server asks, lets the server echo, and
+    /// never offers to echo itself. Set what it reports with
+    /// [`TelnetStream::set_client_terminal_type`] and
+    /// [`TelnetStream::set_client_window_size`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use std::net::TcpStream;
+    /// use telnet_negotiation::TelnetStream;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let socket = TcpStream::connect("bbs.example.com:23")?;
+    ///     let mut remote = TelnetStream::client(socket);
+    ///     remote.set_client_terminal_type("ANSI");
+    ///     remote.set_client_window_size(80, 24)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn client(stream: S) -> Self {
+        Self::build(stream, Role::Client, false)
+    }
+
+    /// Wrap a connection as the given end of it
+    ///
+    /// The same as [`TelnetStream::new`] for [`Role::Server`] and
+    /// [`TelnetStream::client`] for [`Role::Client`], for code that
+    /// decides at run time.
+    pub fn with_role(stream: S, role: Role) -> Self {
+        Self::build(stream, role, false)
+    }
+
+    fn build(stream: S, role: Role, debug_logging: bool) -> Self {
         let mut telnet_stream = Self {
             inner: stream,
             parser: TelnetParser::new(),
             negotiator: OptionNegotiator::new(),
-            option_handlers: HashMap::new(),
-            data_buffer: VecDeque::new(),
+            option_handlers: HandlerTable::new(),
+            data_buffer: RingBuffer::new(),
+            read_after_cr: false,
+            line_after_cr: false,
+            write_after_cr: false,
+            owes_go_ahead: false,
             read_buffer: Vec::new(),
-            debug_logging: true,
+            data_received: 0,
+            prompt_marks: VecDeque::new(),
+            signals: VecDeque::new(),
+            discard_on_synch: true,
+            interrupt_on_signal: false,
+            signal_interrupt_due: false,
+            ayt_reply: Some(DEFAULT_AYT_REPLY.to_string()),
+            debug_logging,
+            history: NegotiationHistory::new(),
+            tls: None,
+            tls_upgrader: None,
+            held_output: Vec::new(),
+            pending_output: Vec::new(),
+            replying: false,
+            flush_policy: FlushPolicy::default(),
+            newline_mode: NewlineMode::default(),
+            write_check: WriteCheck::default(),
+            transcoding: Transcoding::default(),
+            incoming: None,
+            outgoing: None,
+            ansi_filtering: AnsiFiltering::default(),
+            ansi_filter: None,
+            encoded: Vec::new(),
+            role,
+            client_terminal_type: DEFAULT_CLIENT_TERMINAL_TYPE.to_string(),
+            client_window_size: None,
+            budget: MemoryBudget::default(),
+            memory_peak: 0,
+            budget_overflows: 0,
+            loop_guard: LoopGuard::default(),
+            refusals: VecDeque::new(),
+            negotiation_loop: None,
+            parser_overflow: None,
+            timing_marks: VecDeque::new(),
+            round_trip: None,
+            keepalive: None,
+            keepalive_probe: KeepaliveProbe::default(),
+            last_sent: Instant::now(),
+            resize: None,
+            reported_size: None,
+            resize_debounce: None,
+            pending_resize: None,
+            interrupt_on_resize: false,
+            resize_interrupt_due: false,
+            split: false,
+            keep_events: false,
+            negotiation_events: VecDeque::new(),
+            capabilities: TerminalCapabilities::default(),
+            stats: ConnectionStats::default(),
+            stats_hook: None,
         };
 
         // Register core telnet option handlers
@@ -197,647 +748,6040 @@ impl TelnetStream {
         telnet_stream
     }
 
-    /// Enable or disable RFC 1143 queue system
-    ///
-    /// The queue system allows handling rapid option enable/disable requests
-    /// without causing negotiation loops. This is enabled by default per RFC 1143.
-    pub fn set_queue_enabled(&mut self, enabled: bool) {
-        self.negotiator.set_queue_enabled(enabled);
+    /// Which end of the connection this stream plays
+    pub fn role(&self) -> Role {
+        self.role
     }
 
-    /// Check if a telnet option is currently enabled on the specified side
-    pub fn is_option_enabled(&self, side: Side, option: crate::TelnetOption) -> bool {
-        self.negotiator.is_enabled(side, option)
+    /// Terminal type reported to the server in client mode
+    pub fn set_client_terminal_type(&mut self, terminal_type: &str) {
+        self.client_terminal_type = terminal_type.to_string();
     }
 
-    /// Register core telnet option handlers
-    fn register_core_options(&mut self) {
-        // Register Echo option handler
-        self.option_handlers.insert(
-            TelnetOption::ECHO,
-            Box::new(EchoOption::new(true)), // Assume server-side behavior
-        );
-
-        // Register Terminal Type option handler
-        self.option_handlers.insert(
-            TelnetOption::TERMINAL_TYPE,
-            Box::new(TerminalTypeOption::new()),
-        );
-
-        // Register NAWS option handler
-        self.option_handlers
-            .insert(TelnetOption::NAWS, Box::new(NawsOption::new()));
+    /// Window size reported to the server in client mode
+    ///
+    /// Sent right away if NAWS is already on, otherwise as soon as the
+    /// server asks for it.
+    pub fn set_client_window_size(&mut self, width: u16, height: u16) -> io::Result<()> {
+        self.client_window_size = Some(WindowSize { width, height });
+        if self.role == Role::Client && self.negotiator.is_enabled(Side::Local, TelnetOption::NAWS)
+        {
+            self.send_client_window_size()?;
+        }
+        Ok(())
     }
 
-    /// Register a custom option handler
+    /// Limit what the peer can make this connection buffer
     ///
-    /// This allows extending telnet support with custom options beyond
-    /// the core RFC options (Echo, Terminal Type, NAWS).
-    pub fn register_option_handler(&mut self, handler: Box<dyn TelnetOptionHandler>) {
-        let option = handler.option_code();
-        self.option_handlers.insert(option, handler);
+    /// Checked every time input is parsed. Setting a budget doesn't act on
+    /// what is already buffered until more input arrives.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.budget = budget;
+    }
 
-        if self.debug_logging {
-            eprintln!("[TelnetStream] Registered handler for option {:?}", option);
-        }
+    /// The budget in force for this connection
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.budget
     }
 
-    /// Get a reference to an option handler
-    fn get_option_handler(&self, option: TelnetOption) -> Option<&dyn TelnetOptionHandler> {
-        self.option_handlers.get(&option).map(|h| h.as_ref())
+    /// Memory held for this connection right now, with its peak and the
+    /// number of times it went over budget
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            data_buffer: self.data_buffer.len(),
+            read_buffer: self.read_buffer.len(),
+            subnegotiation: self.parser.buffered_len(),
+            history: self.history.memory_used(),
+            peak: self.memory_peak,
+            overflows: self.budget_overflows,
+        }
     }
 
-    /// Process sub-negotiation for a specific option
-    fn process_option_subnegotiation(
-        &mut self,
-        option: TelnetOption,
-        data: &[u8],
-    ) -> io::Result<()> {
-        if let Some(handler) = self.option_handlers.get_mut(&option) {
-            match handler.handle_subnegotiation(data) {
-                Ok(response_data) => {
-                    // Send response if handler generated one
-                    if !response_data.is_empty() {
-                        let mut response = Vec::new();
-                        response.extend_from_slice(&[255, 250]); // IAC SB
-                        response.push(option as u8);
-                        response.extend_from_slice(&response_data);
-                        response.extend_from_slice(&[255, 240]); // IAC SE
+    /// Apply the budget's policy if the peer has pushed us over it
+    fn enforce_memory_budget(&mut self) -> io::Result<()> {
+        let usage = self.memory_stats().total();
+        self.memory_peak = self.memory_peak.max(usage);
+        if !self.budget.is_exceeded(usage) {
+            return Ok(());
+        }
+        self.budget_overflows += 1;
 
-                        self.inner.write_all(&response)?;
+        if self.debug_logging {
+            eprintln!(
+                "[TelnetStream] Memory budget exceeded ({} bytes), policy {:?}",
+                usage,
+                self.budget.policy()
+            );
+        }
 
-                        if self.debug_logging {
-                            eprintln!(
-                                "[TelnetStream] Sent sub-negotiation response for {:?}: {} bytes",
-                                option,
-                                response_data.len()
-                            );
-                        }
-                    }
-                    Ok(())
-                }
-                Err(e) => {
-                    if self.debug_logging {
-                        eprintln!(
-                            "[TelnetStream] Sub-negotiation error for {:?}: {}",
-                            option, e
-                        );
-                    }
-                    Ok(()) // Don't fail the entire operation for sub-negotiation errors
-                }
+        match self.budget.policy() {
+            OverflowPolicy::Flush => {
+                self.data_received -= self.data_buffer.len() as u64;
+                self.data_buffer.release();
+                self.read_buffer = Vec::new();
+                self.parser.discard_subnegotiation();
+                self.history.clear();
             }
-        } else {
-            if self.debug_logging {
-                eprintln!(
-                    "[TelnetStream] No handler registered for option {:?}",
-                    option
-                );
+            OverflowPolicy::Drop => {
+                // An unfinished sub-negotiation goes first, then the newest
+                // input: unparsed bytes, then the end of the unread data
+                self.parser.discard_subnegotiation();
+                let limit = self.budget.limit().unwrap_or(usize::MAX);
+                let mut excess = self.memory_stats().total().saturating_sub(limit);
+
+                let unparsed = excess.min(self.read_buffer.len());
+                self.read_buffer.truncate(self.read_buffer.len() - unparsed);
+                excess -= unparsed;
+
+                let unread = excess.min(self.data_buffer.len());
+                self.data_buffer.truncate(self.data_buffer.len() - unread);
+                self.data_received -= unread as u64;
+            }
+            OverflowPolicy::Disconnect => {
+                let _ = self.inner.shutdown(Shutdown::Both);
+                return Err(io::Error::new(
+                    io::ErrorKind::OutOfMemory,
+                    "telnet memory budget exceeded",
+                ));
             }
-            Ok(())
         }
+        Ok(())
     }
 
-    /// Get the peer address of the underlying TcpStream
-    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.inner.peer_addr()
+    /// Limit how long a sub-negotiation the parser will hold
+    ///
+    /// The default keeps 64 KiB of any one sub-negotiation and drops
+    /// longer ones whole. Under [`LimitPolicy::Error`] an overflow shuts
+    /// the connection down and the read fails with `ErrorKind::InvalidData`.
+    /// The memory budget still applies on top.
+    pub fn set_parser_limits(&mut self, limits: ParserLimits) {
+        self.parser.set_limits(limits);
     }
 
-    /// Get the local address of the underlying TcpStream  
-    pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.inner.local_addr()
+    /// The parser limits in force for this connection
+    pub fn parser_limits(&self) -> ParserLimits {
+        self.parser.limits()
     }
 
-    /// Set the read timeout for the underlying TcpStream
-    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
-        self.inner.set_read_timeout(dur)
+    /// Drop the connection if the peer overflowed a parser limit set to
+    /// fail
+    fn enforce_parser_limits(&mut self) -> io::Result<()> {
+        let Some(option) = self.parser_overflow else {
+            return Ok(());
+        };
+        let _ = self.inner.shutdown(Shutdown::Both);
+        let limit = self.parser.limits().payload_room(0);
+        Err(TelnetError::SubnegotiationTooLarge { option, limit }.into())
     }
 
-    /// Set the write timeout for the underlying TcpStream
-    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
-        self.inner.set_write_timeout(dur)
+    /// Limit how often the peer may repeat a WILL or DO we refused
+    ///
+    /// Refusals already counted stay counted against the new guard.
+    pub fn set_loop_guard(&mut self, guard: LoopGuard) {
+        self.loop_guard = guard;
     }
 
-    /// Get the read timeout of the underlying TcpStream
-    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
-        self.inner.read_timeout()
+    /// The loop guard in force for this connection
+    pub fn loop_guard(&self) -> LoopGuard {
+        self.loop_guard
     }
 
-    /// Get the write timeout of the underlying TcpStream
-    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
-        self.inner.write_timeout()
+    /// Count a refusal of the peer's `command` for `option`, returning
+    /// whether to send it
+    ///
+    /// Past the guard's limit the refusal is held back, and under
+    /// [`LoopPolicy::Disconnect`] the connection is marked for closing.
+    fn guard_refusal(&mut self, command: TelnetCommand, option: TelnetOption) -> bool {
+        let now = Instant::now();
+        let window = self.loop_guard.window();
+        while let Some(&(at, _, _)) = self.refusals.front() {
+            if now.duration_since(at) < window {
+                break;
+            }
+            self.refusals.pop_front();
+        }
+
+        let refused = self
+            .refusals
+            .iter()
+            .filter(|&&(_, c, o)| c == command && o == option)
+            .count();
+        if !self.loop_guard.is_tripped(refused) {
+            self.refusals.push_back((now, command, option));
+            return true;
+        }
+
+        if self.debug_logging {
+            eprintln!(
+                "[TelnetStream] Peer repeated refused {:?} {:?}, policy {:?}",
+                command,
+                option,
+                self.loop_guard.policy()
+            );
+        }
+        if self.loop_guard.policy() == LoopPolicy::Disconnect {
+            self.negotiation_loop = Some((command, option));
+        }
+        false
     }
 
-    /// Set the TTL for the underlying TcpStream
-    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
-        self.inner.set_ttl(ttl)
+    /// Drop the connection if the peer looped past a disconnecting guard
+    fn enforce_loop_guard(&mut self) -> io::Result<()> {
+        let Some((command, option)) = self.negotiation_loop else {
+            return Ok(());
+        };
+        let _ = self.inner.shutdown(Shutdown::Both);
+        Err(TelnetError::from(ProtocolViolation::RepeatedRefusal { command, option }).into())
     }
 
-    /// Get the TTL of the underlying TcpStream
-    pub fn ttl(&self) -> io::Result<u32> {
-        self.inner.ttl()
+    /// Enable or disable RFC 1143 queue system
+    ///
+    /// The queue system allows handling rapid option enable/disable requests
+    /// without causing negotiation loops. This is enabled by default per RFC 1143.
+    pub fn set_queue_enabled(&mut self, enabled: bool) {
+        self.negotiator.set_queue_enabled(enabled);
     }
 
-    /// Enable or disable Nagle's algorithm for the underlying TcpStream
-    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
-        self.inner.set_nodelay(nodelay)
+    /// Decide which options to agree to when the peer asks
+    ///
+    /// See [`OptionNegotiator::set_accept_policy`]. Options already
+    /// negotiated stay as they are.
+    pub fn set_accept_policy(&mut self, policy: impl OptionPolicy + 'static) {
+        self.negotiator.set_accept_policy(policy);
     }
 
-    /// Check if Nagle's algorithm is disabled for the underlying TcpStream
-    pub fn nodelay(&self) -> io::Result<bool> {
-        self.inner.nodelay()
+    /// Check if a telnet option is currently enabled on the specified side
+    pub fn is_option_enabled(&self, side: Side, option: crate::TelnetOption) -> bool {
+        self.negotiator.is_enabled(side, option)
     }
 
-    /// Shutdown the underlying TcpStream
-    pub fn shutdown(&self, how: std::net::Shutdown) -> io::Result<()> {
-        self.inner.shutdown(how)
+    /// Where negotiation of `option` on `side` stands (RFC 1143)
+    pub fn option_state(&self, side: Side, option: TelnetOption) -> OptionState {
+        self.negotiator.get_state(side, option)
     }
 
-    /// Try to clone the underlying TcpStream
-    pub fn try_clone(&self) -> io::Result<TcpStream> {
-        self.inner.try_clone()
+    /// A readable table of where every negotiated option stands, for logs
+    /// and bug reports
+    ///
+    /// ```rust,no_run
+    /// # use telnet_negotiation::TelnetStream;
+    /// # let stream = TelnetStream::new(std::net::TcpStream::connect("localhost:23").unwrap());
+    /// eprintln!("{}", stream.dump_options());
+    /// ```
+    pub fn dump_options(&self) -> NegotiatorDump<'_> {
+        self.negotiator.dump()
     }
 
-    /// Process incoming data and handle telnet commands automatically
+    /// Ask to turn `option` on: WILL for [`Side::Local`], DO for
+    /// [`Side::Remote`]
     ///
-    /// This is the core method that:
-    /// 1. Reads raw data from the underlying TcpStream
-    /// 2. Parses telnet command sequences
-    /// 3. Handles option negotiation automatically
-    /// 4. Buffers clean application data
-    /// 5. Sends appropriate telnet responses
+    /// Goes through the RFC 1143 state machine, so nothing is sent if the
+    /// option is already on or a request for it is outstanding, and a
+    /// pending request to turn it off is queued to be reversed. Returns the
+    /// option's state afterwards; it is on once the peer agrees.
+    pub fn enable_option(&mut self, side: Side, option: TelnetOption) -> io::Result<OptionState> {
+        let was = self.enabled_sides(option);
+        let result = self.negotiator.request_enable(side, option);
+        self.send_request(option, was, result)
+    }
+
+    /// Ask to turn `option` off: WONT for [`Side::Local`], DONT for
+    /// [`Side::Remote`]
     ///
-    /// Returns the number of clean data bytes available for application use.
-    fn process_incoming_data(&mut self) -> io::Result<usize> {
-        // Read raw data from underlying stream
-        let mut temp_buffer = [0; 4096];
-        let bytes_read = match self.inner.read(&mut temp_buffer) {
-            Ok(0) => return Ok(0), // EOF
-            Ok(n) => n,
-            Err(e) => return Err(e),
+    /// The counterpart of [`TelnetStream::enable_option`]. The option is
+    /// off as far as we are concerned straight away; the peer's reply
+    /// completes the exchange.
+    pub fn disable_option(&mut self, side: Side, option: TelnetOption) -> io::Result<OptionState> {
+        let was = self.enabled_sides(option);
+        let result = self.negotiator.request_disable(side, option);
+        self.send_request(option, was, result)
+    }
+
+    /// Give up on negotiations the peer hasn't answered within `timeout`,
+    /// or ask once more
+    ///
+    /// See [`OptionNegotiator::expire_stale`]; retries are sent here. For
+    /// a client that never answers, say, DO NAWS, so the option can be
+    /// asked for again instead of waiting forever.
+    pub fn expire_stale_negotiations(
+        &mut self,
+        timeout: Duration,
+    ) -> io::Result<Vec<StaleNegotiation>> {
+        let stale = self.negotiator.expire_stale(timeout);
+        let mut sent = false;
+        for negotiation in &stale {
+            if let Some(TelnetSequence::Negotiation { command, option }) = negotiation.retry {
+                self.send_negotiation(command, option)?;
+                sent = true;
+            }
+        }
+        if sent {
+            self.transport_flush()?;
+        }
+        Ok(stale)
+    }
+
+    /// Ask once more before giving up on a stale negotiation
+    ///
+    /// See [`TelnetStream::expire_stale_negotiations`]. Off by default.
+    pub fn set_retry_stale(&mut self, retry: bool) {
+        self.negotiator.set_retry_stale(retry);
+    }
+
+    /// Send IAC SB `option` `data` IAC SE, doubling any IAC in `data`
+    ///
+    /// For options the stream has no handler of its own for, or payloads
+    /// relayed from another connection (see [`crate::proxy`]).
+    pub fn send_subnegotiation(&mut self, option: TelnetOption, data: &[u8]) -> io::Result<()> {
+        let sequence = TelnetSequence::SubNegotiation {
+            option,
+            data: data.to_vec(),
         };
+        self.send_raw(&sequence.to_bytes())?;
+        self.transport_flush()
+    }
 
-        // Add to read buffer for processing
-        self.read_buffer
-            .extend_from_slice(&temp_buffer[..bytes_read]);
+    /// Whether `option` is enabled on each side, local first
+    fn enabled_sides(&self, option: TelnetOption) -> (bool, bool) {
+        (
+            self.is_option_enabled(Side::Local, option),
+            self.is_option_enabled(Side::Remote, option),
+        )
+    }
 
-        // Parse telnet commands from buffered data
-        let parse_result = self.parser.parse(&self.read_buffer);
+    /// Send whatever a request to the negotiator produced, given which
+    /// sides of `option` were enabled beforehand
+    fn send_request(
+        &mut self,
+        option: TelnetOption,
+        (was_local, was_remote): (bool, bool),
+        result: NegotiationResult,
+    ) -> io::Result<OptionState> {
+        if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+            self.send_negotiation(command, option)?;
+            self.transport_flush()?;
+        }
+        if let Some(error) = &result.error
+            && self.debug_logging
+        {
+            eprintln!("[TelnetStream] Request for {:?}: {}", option, error);
+        }
+        self.push_option_events(option, was_local, was_remote);
+        self.notify_option_handler(option, was_local, was_remote)?;
+        Ok(result.new_state)
+    }
 
-        // Remove processed bytes from read buffer
-        if parse_result.bytes_consumed > 0 {
-            self.read_buffer.drain(0..parse_result.bytes_consumed);
+    /// Whether [`TelnetStream::request_option`] knows how to ask for `option`
+    ///
+    /// True for the options in [`BUILT_IN_REQUESTS`] and for any option
+    /// with a registered handler.
+    pub fn can_request(&self, option: TelnetOption) -> bool {
+        BUILT_IN_REQUESTS.contains(&option) || self.get_option_handler(option).is_some()
+    }
+
+    /// Ask the peer for `option` the way that option is asked for
+    ///
+    /// Options with a `request_*` method go through it (SUPPRESS-GO-AHEAD
+    /// and BINARY in both directions, END-OF-RECORD as WILL); others with
+    /// a registered handler are asked for with IAC DO. Fails with
+    /// `InvalidInput` if [`TelnetStream::can_request`] is false.
+    pub fn request_option(&mut self, option: TelnetOption) -> io::Result<()> {
+        match option {
+            TelnetOption::BINARY => self.request_binary(),
+            TelnetOption::SUPPRESS_GO_AHEAD => self.request_suppress_go_ahead(),
+            TelnetOption::END_OF_RECORD => self.request_end_of_record(),
+            TelnetOption::TERMINAL_TYPE => self.request_terminal_type().map(drop),
+            TelnetOption::NAWS => self.request_window_size().map(drop),
+            TelnetOption::TERMINAL_SPEED => self.request_terminal_speed(),
+            TelnetOption::SEND_LOCATION => self.request_location(),
+            TelnetOption::NEW_ENVIRON => self.request_environment(),
+            #[cfg(feature = "options-mud")]
+            TelnetOption::MXP => self.request_mxp(),
+            #[cfg(feature = "options-mud")]
+            TelnetOption::MSP => self.request_msp(),
+            _ if self.can_request(option) => self.enable_option(Side::Remote, option).map(drop),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no way to request telnet option {:?}", option),
+            )),
         }
+    }
 
-        // Add clean data to application buffer
-        let data_bytes_added = parse_result.data.len();
-        for byte in parse_result.data {
-            self.data_buffer.push_back(byte);
+    /// Ask for every option in `profile`, in order
+    ///
+    /// Nothing is sent if any option can't be requested; the error names
+    /// the first. Answers arrive as data is read, or during
+    /// [`TelnetStream::poll_negotiation`].
+    pub fn apply_profile(&mut self, profile: &NegotiationProfile) -> io::Result<()> {
+        if let Some(option) = profile
+            .options()
+            .iter()
+            .find(|&&option| !self.can_request(option))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no way to request telnet option {:?}", option),
+            ));
         }
+        for &option in profile.options() {
+            self.request_option(option)?;
+        }
+        Ok(())
+    }
 
-        // Process any telnet sequences found
-        for sequence in parse_result.sequences {
-            if let Err(e) = self.handle_telnet_sequence(&sequence) {
-                if self.debug_logging {
-                    eprintln!(
-                        "[TelnetStream] Error handling sequence {:?}: {}",
-                        sequence, e
-                    );
-                }
-                // Don't fail the entire operation for telnet processing errors
+    /// Ask for BINARY transmission in both directions (WILL and DO BINARY)
+    ///
+    /// Each direction switches over when the peer agrees to it; check with
+    /// [`TelnetStream::is_option_enabled`] (`Side::Local` for what we send,
+    /// `Side::Remote` for what we receive).
+    pub fn request_binary(&mut self) -> io::Result<()> {
+        for side in [Side::Local, Side::Remote] {
+            let result = self.negotiator.request_enable(side, TelnetOption::BINARY);
+            if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+                self.send_negotiation(command, option)?;
             }
         }
-
-        Ok(data_bytes_added)
+        self.transport_flush()
     }
 
-    /// Handle a single telnet sequence and send appropriate responses
-    fn handle_telnet_sequence(&mut self, sequence: &TelnetSequence) -> io::Result<()> {
-        match sequence {
-            TelnetSequence::Negotiation { command, option } => {
-                if self.debug_logging {
-                    eprintln!("[TelnetStream] Processing: {:?} {:?}", command, option);
-                }
-
-                let result = match command {
-                    TelnetCommand::WILL => self.negotiator.handle_will(*option),
-                    TelnetCommand::WONT => self.negotiator.handle_wont(*option),
-                    TelnetCommand::DO => self.negotiator.handle_do(*option),
-                    TelnetCommand::DONT => self.negotiator.handle_dont(*option),
-                    _ => {
-                        if self.debug_logging {
-                            eprintln!(
-                                "[TelnetStream] Non-negotiation command in negotiation sequence: {:?}",
-                                command
-                            );
-                        }
-                        return Ok(());
-                    }
-                };
+    /// Ask to suppress Go Ahead in both directions (WILL and DO
+    /// SUPPRESS-GO-AHEAD), for a full duplex connection
+    ///
+    /// Flushes stop ending with IAC GA as soon as the peer agrees to our
+    /// side; see [`TelnetStream::is_full_duplex`].
+    pub fn request_suppress_go_ahead(&mut self) -> io::Result<()> {
+        for side in [Side::Local, Side::Remote] {
+            let result = self
+                .negotiator
+                .request_enable(side, TelnetOption::SUPPRESS_GO_AHEAD);
+            if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+                self.send_negotiation(command, option)?;
+            }
+        }
+        self.transport_flush()
+    }
 
-                // Send response if needed
-                if let Some(response) = result.response {
-                    let response_bytes = response.to_bytes();
-                    self.inner.write_all(&response_bytes)?;
-                    self.inner.flush()?;
+    /// Whether Go Ahead is suppressed in both directions
+    pub fn is_full_duplex(&self) -> bool {
+        [Side::Local, Side::Remote]
+            .into_iter()
+            .all(|side| self.is_option_enabled(side, TelnetOption::SUPPRESS_GO_AHEAD))
+    }
 
-                    if self.debug_logging {
-                        if let TelnetSequence::Negotiation { command, option } = response {
-                            eprintln!("[TelnetStream] Sent response: {:?} {:?}", command, option);
-                        }
-                    }
-                }
+    /// Send IAC DO TIMING-MARK to measure the round trip to the peer
+    ///
+    /// The answer is picked up by later reads; see
+    /// [`TelnetStream::round_trip_time`]. Answers are matched to marks in
+    /// the order they were sent, so several may be outstanding at once.
+    pub fn send_timing_mark(&mut self) -> io::Result<()> {
+        self.timing_marks.push_back(Instant::now());
+        self.send_negotiation(TelnetCommand::DO, TelnetOption::TIMING_MARK)?;
+        self.transport_flush()
+    }
 
-                // Log any negotiation errors
-                if let Some(error) = result.error {
-                    if self.debug_logging {
-                        eprintln!(
-                            "[TelnetStream] Negotiation error for {:?}: {}",
-                            option, error
-                        );
-                    }
-                }
+    /// Send a timing mark and read until it is answered or `timeout` passes
+    ///
+    /// Returns the round trip, or `None` if the peer didn't answer in time.
+    /// Application data read meanwhile is kept for later `read` calls.
+    pub fn measure_round_trip(&mut self, timeout: Duration) -> io::Result<Option<Duration>> {
+        self.send_timing_mark()?;
+        let previous_timeout = self.inner.read_timeout()?;
+        let deadline = Instant::now() + timeout;
 
-                if self.debug_logging {
-                    eprintln!(
-                        "[TelnetStream] Option {:?} now enabled: Local={}, Remote={}",
-                        option,
-                        self.negotiator.is_enabled(Side::Local, *option),
-                        self.negotiator.is_enabled(Side::Remote, *option)
-                    );
-                }
+        let outcome = loop {
+            // Ours is the newest mark, so it is answered last
+            if self.timing_marks.is_empty() {
+                break Ok(self.round_trip);
             }
 
-            TelnetSequence::SubNegotiation { option, data } => {
-                if self.debug_logging {
-                    eprintln!(
-                        "[TelnetStream] Sub-negotiation for {:?}: {} bytes",
-                        option,
-                        data.len()
-                    );
-                }
-
-                // Route sub-negotiation to appropriate option handler
-                self.process_option_subnegotiation(*option, data)?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Ok(None);
             }
 
-            TelnetSequence::Command(cmd) => {
-                if self.debug_logging {
-                    eprintln!("[TelnetStream] Simple command: {:?}", cmd);
+            self.inner.set_read_timeout(Some(remaining))?;
+            let mut temp_buffer = [0; 4096];
+            match self.transport_read(&mut temp_buffer) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Connection closed before timing mark was answered",
+                    ));
                 }
-                // Simple commands like NOP, AYT etc. - mostly just log for now
-                // Specific handling can be added later if needed
-            }
-
-            TelnetSequence::EscapedData(byte) => {
-                if self.debug_logging {
-                    eprintln!("[TelnetStream] Escaped data byte: {}", byte);
+                Ok(n) => {
+                    self.read_buffer.extend_from_slice(&temp_buffer[..n]);
+                    if let Err(e) = self.process_read_buffer() {
+                        break Err(e);
+                    }
                 }
-                // Escaped data is already added to the data buffer by the parser
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => break Err(e),
             }
-        }
+        };
 
-        Ok(())
+        self.inner.set_read_timeout(previous_timeout)?;
+        outcome
     }
 
-    /// Get access to the underlying TcpStream for advanced operations
+    /// The client's new window size, if it has changed since last asked
     ///
-    /// This provides access to the wrapped TcpStream for operations that
-    /// aren't available through the TelnetStream interface.
-    ///
-    /// **Warning**: Direct access bypasses telnet processing. Use with caution.
-    pub fn get_ref(&self) -> &TcpStream {
-        &self.inner
+    /// The first size a client reports counts as a change too.
+    pub fn take_resize(&mut self) -> Option<WindowSize> {
+        self.settle_resize();
+        self.resize_interrupt_due = false;
+        self.resize.take()
     }
 
-    /// Get mutable access to the underlying TcpStream
+    /// Only count a new window size once the client has kept it for
+    /// `quiet`, or every size as it arrives with `None` (the default)
     ///
-    /// **Warning**: Direct access bypasses telnet processing. Use with caution.
-    pub fn get_mut(&mut self) -> &mut TcpStream {
-        &mut self.inner
+    /// Sizes that come faster than that replace each other, and only the
+    /// last one is reported: to [`TelnetStream::take_resize`], as a
+    /// [`NegotiationEvent::WindowResized`], and as an interrupt. A `read`
+    /// waiting for input with [`TelnetStream::set_interrupt_on_resize`] on
+    /// wakes when the quiet period ends. A storm that ends on the size
+    /// already reported isn't reported again.
+    pub fn set_resize_debounce(&mut self, quiet: Option<Duration>) {
+        self.resize_debounce = quiet;
+        self.settle_resize();
     }
 
-    /// Extract the underlying TcpStream, consuming the TelnetStream
-    ///
-    /// This returns the wrapped TcpStream and destroys the TelnetStream.
-    /// Any buffered data will be lost.
-    pub fn into_inner(self) -> TcpStream {
-        self.inner
+    /// The quiet period set by [`TelnetStream::set_resize_debounce`]
+    pub fn resize_debounce(&self) -> Option<Duration> {
+        self.resize_debounce
     }
 
-    // ========================================
-    // Phase 6: High-level Option API Methods
-    // ========================================
+    /// Report `size` as a resize, unless it is what was last reported
+    fn report_resize(&mut self, size: WindowSize) {
+        if self.reported_size == Some(size) {
+            return;
+        }
+        self.reported_size = Some(size);
+        self.resize = Some(size);
+        self.resize_interrupt_due = true;
+        self.push_event(NegotiationEvent::WindowResized(size));
+    }
 
-    /// Request echo to be disabled for secure password input
-    ///
-    /// This negotiates with the client to disable local echoing,
-    /// which is essential for secure password input where characters
-    /// should not be displayed as they are typed.
+    /// Report a debounced size whose quiet period has passed
+    fn settle_resize(&mut self) {
+        if self.resize_settles_in() == Some(Duration::ZERO)
+            && let Some((size, _)) = self.pending_resize.take()
+        {
+            self.report_resize(size);
+        }
+    }
+
+    /// Time left before a debounced size counts
+    fn resize_settles_in(&self) -> Option<Duration> {
+        let (_, arrived) = self.pending_resize?;
+        Some(
+            self.resize_debounce
+                .unwrap_or_default()
+                .saturating_sub(arrived.elapsed()),
+        )
+    }
+
+    /// Have a `read` waiting for input return `ErrorKind::Interrupted` when
+    /// the client's window size changes
     ///
-    /// Returns true if echo was successfully disabled.
-    pub fn request_echo_off(&mut self) -> io::Result<bool> {
-        // Send IAC WILL ECHO (server will handle echoing)
-        let command = TelnetSequence::Negotiation {
-            command: TelnetCommand::WILL,
-            option: TelnetOption::ECHO,
-        };
+    /// Each change interrupts at most one `read`, so callers that simply
+    /// retry on `Interrupted` (as `read_exact` does) carry on unaffected.
+    /// Off by default.
+    pub fn set_interrupt_on_resize(&mut self, enabled: bool) {
+        self.interrupt_on_resize = enabled;
+    }
 
-        let command_bytes = command.to_bytes();
-        self.inner.write_all(&command_bytes)?;
-        self.inner.flush()?;
+    pub(crate) fn interrupts_on_resize(&self) -> bool {
+        self.interrupt_on_resize
+    }
 
-        if self.debug_logging {
-            eprintln!("[TelnetStream] Requested echo OFF (sent WILL ECHO)");
+    /// Fail a `read` with `Interrupted` if a resize or signal hasn't
+    /// interrupted one yet
+    pub(crate) fn interrupt_if_due(&mut self) -> io::Result<()> {
+        self.settle_resize();
+        if self.interrupt_on_resize && std::mem::take(&mut self.resize_interrupt_due) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Window resized"));
         }
-
-        // Update our echo option handler state
-        if let Some(handler) = self.option_handlers.get_mut(&TelnetOption::ECHO) {
-            if let Some(echo_handler) = handler.as_any_mut().downcast_mut::<EchoOption>() {
-                echo_handler.enable_remote_echo();
-            }
+        if self.interrupt_on_signal && std::mem::take(&mut self.signal_interrupt_due) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Interrupted by peer",
+            ));
         }
-
-        // For now, assume success - proper negotiation tracking would require
-        // waiting for DO/DONT response, which is complex for synchronous API
-        Ok(true)
+        Ok(())
     }
 
-    /// Request echo to be enabled for normal input
+    /// Keep a [`NegotiationEvent`] for each change, for
+    /// [`TelnetStream::take_negotiation_events`]
     ///
-    /// This restores normal local echoing after password input is complete.
-    /// Characters will be displayed as they are typed.
-    ///
-    /// Returns true if echo was successfully enabled.
-    pub fn request_echo_on(&mut self) -> io::Result<bool> {
-        // Send IAC WONT ECHO (server won't handle echoing)
-        let command = TelnetSequence::Negotiation {
-            command: TelnetCommand::WONT,
-            option: TelnetOption::ECHO,
-        };
+    /// Off by default. Only the newest 256 uncollected events are kept.
+    /// Turning it off discards any not yet collected.
+    pub fn set_negotiation_events(&mut self, enabled: bool) {
+        self.keep_events = enabled;
+        if !enabled {
+            self.negotiation_events.clear();
+        }
+    }
 
-        let command_bytes = command.to_bytes();
-        self.inner.write_all(&command_bytes)?;
-        self.inner.flush()?;
+    /// Negotiation events since last asked, oldest first
+    pub fn take_negotiation_events(&mut self) -> Vec<NegotiationEvent> {
+        self.settle_resize();
+        self.negotiation_events.drain(..).collect()
+    }
 
-        if self.debug_logging {
-            eprintln!("[TelnetStream] Requested echo ON (sent WONT ECHO)");
+    /// Queue an event if the application wants them
+    fn push_event(&mut self, event: NegotiationEvent) {
+        if !self.keep_events {
+            return;
+        }
+        if self.negotiation_events.len() == MAX_NEGOTIATION_EVENTS {
+            self.negotiation_events.pop_front();
         }
+        self.negotiation_events.push_back(event);
+    }
 
-        // Update our echo option handler state
-        if let Some(handler) = self.option_handlers.get_mut(&TelnetOption::ECHO) {
-            if let Some(echo_handler) = handler.as_any_mut().downcast_mut::<EchoOption>() {
-                echo_handler.enable_local_echo();
+    /// Queue events for whichever sides of `option` changed
+    fn push_option_events(&mut self, option: TelnetOption, was_local: bool, was_remote: bool) {
+        for (side, was) in [(Side::Local, was_local), (Side::Remote, was_remote)] {
+            match (was, self.negotiator.is_enabled(side, option)) {
+                (false, true) => self.push_event(NegotiationEvent::OptionEnabled { side, option }),
+                (true, false) => self.push_event(NegotiationEvent::OptionDisabled { side, option }),
+                _ => {}
             }
         }
-
-        Ok(true)
     }
 
-    /// Request the client's terminal type information
-    ///
-    /// This initiates terminal type negotiation to determine client
-    /// capabilities for adaptive rendering (ANSI support, colors, etc.).
-    ///
-    /// Returns the terminal type string if available, or None if not supported.
-    pub fn request_terminal_type(&mut self) -> io::Result<Option<String>> {
-        // First, request the client supports terminal type negotiation
-        let do_command = TelnetSequence::Negotiation {
-            command: TelnetCommand::DO,
-            option: TelnetOption::TERMINAL_TYPE,
+    /// Tell `option`'s handler which of its sides came on or went off, and
+    /// send whatever it asks to on the way up
+    fn notify_option_handler(
+        &mut self,
+        option: TelnetOption,
+        was_local: bool,
+        was_remote: bool,
+    ) -> io::Result<()> {
+        let Some(handler) = self.option_handlers.get_mut(option.to_byte()) else {
+            return Ok(());
         };
 
-        let command_bytes = do_command.to_bytes();
-        self.inner.write_all(&command_bytes)?;
-        self.inner.flush()?;
+        let mut requests = Vec::new();
+        for (side, was) in [(Side::Local, was_local), (Side::Remote, was_remote)] {
+            match (was, self.negotiator.is_enabled(side, option)) {
+                (false, true) => requests.push(handler.on_enabled(side)),
+                (true, false) => handler.on_disabled(side),
+                _ => {}
+            }
+        }
 
-        if self.debug_logging {
-            eprintln!("[TelnetStream] Requested terminal type negotiation (sent DO TERMINAL_TYPE)");
+        let mut sent = false;
+        for data in requests.into_iter().filter(|data| !data.is_empty()) {
+            let sequence = TelnetSequence::SubNegotiation { option, data };
+            self.send_raw(&sequence.to_bytes())?;
+            sent = true;
+        }
+        if sent {
+            self.transport_flush()?;
         }
+        Ok(())
+    }
 
-        // Note: In a real implementation, we would need to:
-        // 1. Wait for WILL TERMINAL_TYPE response
-        // 2. Send IAC SB TERMINAL_TYPE SEND IAC SE
-        // 3. Wait for IAC SB TERMINAL_TYPE IS <type> IAC SE response
-        //
-        // For this synchronous API, we return what we have if available
-        if let Some(handler) = self.get_option_handler(TelnetOption::TERMINAL_TYPE) {
-            if let Some(term_handler) = handler.as_any().downcast_ref::<TerminalTypeOption>() {
-                return Ok(term_handler.terminal_type().map(|s| s.to_string()));
-            }
+    /// Queue an event if echoing has moved since `was_remote`
+    fn push_echo_event(&mut self, was_remote: bool) {
+        let remote = self.is_echo_remote();
+        if remote != was_remote {
+            self.push_event(NegotiationEvent::EchoChanged { remote });
         }
+    }
 
-        Ok(None)
+    /// Round trip of the most recently answered timing mark, if any
+    pub fn round_trip_time(&self) -> Option<Duration> {
+        self.round_trip
     }
 
-    /// Request the client's window size information
-    ///
-    /// This initiates NAWS negotiation to determine the client's terminal
-    /// dimensions for responsive layout rendering.
+    /// Timing marks sent that the peer hasn't answered yet
+    pub fn pending_timing_marks(&self) -> usize {
+        self.timing_marks.len()
+    }
+
+    /// Send a keepalive whenever nothing has been sent for `interval`,
+    /// so NAT gateways and firewalls don't drop a quiet session
     ///
-    /// Returns the window size if available, or None if not supported.
-    pub fn request_window_size(&mut self) -> io::Result<Option<WindowSize>> {
-        // Request NAWS negotiation
-        let do_command = TelnetSequence::Negotiation {
-            command: TelnetCommand::DO,
-            option: TelnetOption::NAWS,
-        };
+    /// Keepalives go out from [`TelnetStream::tick`], and from a `read`
+    /// while it waits for input; the read still times out when its own
+    /// read timeout says so. A split stream's read half waits without the
+    /// stream, so call `tick` through a half's `lock` instead. `None`
+    /// (the default) turns keepalives off.
+    pub fn set_keepalive(&mut self, interval: Option<Duration>) {
+        self.keepalive = interval;
+    }
 
-        let command_bytes = do_command.to_bytes();
-        self.inner.write_all(&command_bytes)?;
-        self.inner.flush()?;
+    /// The interval set by [`TelnetStream::set_keepalive`]
+    pub fn keepalive(&self) -> Option<Duration> {
+        self.keepalive
+    }
 
-        if self.debug_logging {
-            eprintln!("[TelnetStream] Requested window size negotiation (sent DO NAWS)");
-        }
+    /// Choose what a keepalive sends; IAC NOP by default
+    pub fn set_keepalive_probe(&mut self, probe: KeepaliveProbe) {
+        self.keepalive_probe = probe;
+    }
 
-        // Return current window size if available
-        if let Some(handler) = self.get_option_handler(TelnetOption::NAWS) {
-            if let Some(naws_handler) = handler.as_any().downcast_ref::<NawsOption>() {
-                return Ok(naws_handler.window_size());
+    /// Time left before the next keepalive is due, if keepalives are on
+    pub fn keepalive_due_in(&self) -> Option<Duration> {
+        let interval = self.keepalive?;
+        Some(interval.saturating_sub(self.last_sent.elapsed()))
+    }
+
+    /// Send a keepalive if one is due, returning whether it did
+    ///
+    /// For applications driving the stream from their own loop; call it
+    /// as often as convenient, at least once per interval.
+    pub fn tick(&mut self) -> io::Result<bool> {
+        if self.keepalive_due_in() != Some(Duration::ZERO) {
+            return Ok(false);
+        }
+        match self.keepalive_probe {
+            KeepaliveProbe::Nop => {
+                self.send_raw(&TelnetSequence::Command(TelnetCommand::NOP).to_bytes())?;
+                self.transport_flush()?;
             }
+            KeepaliveProbe::TimingMark => self.send_timing_mark()?,
         }
+        self.report_stats_if_due();
+        // Held output (mid START_TLS) didn't reach the peer; don't retry
+        // until the next interval either way
+        self.last_sent = Instant::now();
+        Ok(true)
+    }
 
-        Ok(None)
+    /// Protocol counters for this connection so far; see [`crate::stats`]
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats
     }
 
-    /// Get current terminal information and capabilities
+    /// Call `hook` with the counts added since its previous call, at most
+    /// once per `every`, for exporting to a metrics collector
     ///
-    /// This returns a comprehensive view of the client's terminal capabilities
-    /// based on negotiated options. Used for adaptive rendering decisions.
-    pub fn get_terminal_capabilities(&self) -> TerminalCapabilities {
-        let mut caps = TerminalCapabilities::default();
-
-        // Get terminal type information
-        if let Some(handler) = self.get_option_handler(TelnetOption::TERMINAL_TYPE) {
-            if let Some(term_handler) = handler.as_any().downcast_ref::<TerminalTypeOption>() {
-                if let Some(terminal_info) = term_handler.terminal_info() {
-                    caps.terminal_type = Some(terminal_info.terminal_type.clone());
-                    caps.supports_ansi = terminal_info.capabilities.ansi_support;
-                    caps.supports_color = !matches!(
-                        terminal_info.capabilities.color_support,
-                        crate::options::terminal_type::ColorSupport::None
-                    );
-                    caps.color_depth = match terminal_info.capabilities.color_support {
-                        crate::options::terminal_type::ColorSupport::None => ColorDepth::Monochrome,
-                        crate::options::terminal_type::ColorSupport::Basic8 => ColorDepth::Basic8,
-                        crate::options::terminal_type::ColorSupport::Color16 => ColorDepth::Basic8, // Map to Basic8
-                        crate::options::terminal_type::ColorSupport::Color256 => {
-                            ColorDepth::Extended256
-                        }
-                        crate::options::terminal_type::ColorSupport::TrueColor => {
-                            ColorDepth::TrueColor
-                        }
-                    };
-                }
-            }
-        }
+    /// The first call covers the connection from the start. Calls come
+    /// from reads, writes and [`TelnetStream::tick`], and are skipped
+    /// while nothing has changed; use [`TelnetStream::report_stats`] for
+    /// the last counts before closing. Replaces any hook already set.
+    pub fn set_stats_hook(
+        &mut self,
+        every: Duration,
+        hook: impl FnMut(&ConnectionStats) + Send + 'static,
+    ) {
+        self.stats_hook = Some(StatsHook::new(every, Box::new(hook)));
+    }
 
-        // Get window size information
-        if let Some(handler) = self.get_option_handler(TelnetOption::NAWS) {
-            if let Some(naws_handler) = handler.as_any().downcast_ref::<NawsOption>() {
-                if let Some(size) = naws_handler.window_size() {
-                    caps.width = Some(size.width);
-                    caps.height = Some(size.height);
-                }
-            }
+    /// Stop calling the hook set by [`TelnetStream::set_stats_hook`]
+    pub fn clear_stats_hook(&mut self) {
+        self.stats_hook = None;
+    }
+
+    /// Call the stats hook now with anything it hasn't been told yet
+    pub fn report_stats(&mut self) {
+        if let Some(hook) = self.stats_hook.as_mut() {
+            hook.call(&self.stats);
         }
+    }
 
-        caps
+    fn report_stats_if_due(&mut self) {
+        if let Some(hook) = self.stats_hook.as_mut() {
+            hook.call_if_due(&self.stats);
+        }
     }
 
-    /// Get the current echo state
-    ///
-    /// Returns true if remote echo is active (server handles echoing),
-    /// false if local echo is active (client handles echoing).
-    pub fn is_echo_remote(&self) -> bool {
-        if let Some(handler) = self.get_option_handler(TelnetOption::ECHO) {
-            if let Some(echo_handler) = handler.as_any().downcast_ref::<EchoOption>() {
-                return echo_handler.is_remote_echo();
+    /// Count a sequence the parser found
+    fn count_received(&mut self, sequence: &TelnetSequence) {
+        let stats = &mut self.stats;
+        match sequence {
+            // The 255 follows as data, and is counted there
+            TelnetSequence::EscapedData(_) => return,
+            TelnetSequence::Negotiation { .. } => stats.negotiations_received += 1,
+            TelnetSequence::SubNegotiation { data, .. } => {
+                stats.subnegotiations_received += 1;
+                stats.subnegotiation_bytes_received += data.len() as u64;
+                stats.largest_subnegotiation = stats.largest_subnegotiation.max(data.len());
             }
+            TelnetSequence::Command(_) => {}
         }
-        false // Default to local echo
+        stats.sequences_received += 1;
     }
-}
 
-/// Implement Read trait for transparent telnet operation
-///
-/// The Read implementation automatically processes incoming telnet commands
-/// and returns only clean application data to the caller.
-impl Read for TelnetStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        // If we have buffered data, return it first
-        if !self.data_buffer.is_empty() {
-            let bytes_to_copy = std::cmp::min(buf.len(), self.data_buffer.len());
-            for i in 0..bytes_to_copy {
-                buf[i] = self.data_buffer.pop_front().unwrap();
+    /// Answer or resolve a TIMING-MARK command outside the option state
+    /// machine, which would otherwise leave it enabled after the first mark
+    fn handle_timing_mark(&mut self, command: TelnetCommand) -> io::Result<()> {
+        self.record_negotiation(Direction::Received, command, TelnetOption::TIMING_MARK);
+        match command {
+            TelnetCommand::WILL | TelnetCommand::WONT => {
+                // Either answer means the peer has caught up with us
+                if let Some(sent) = self.timing_marks.pop_front() {
+                    self.round_trip = Some(sent.elapsed());
+                }
+                Ok(())
             }
-            return Ok(bytes_to_copy);
+            TelnetCommand::DO => {
+                self.send_negotiation(TelnetCommand::WILL, TelnetOption::TIMING_MARK)?;
+                self.transport_flush()
+            }
+            _ => Ok(()),
         }
+    }
 
-        // No buffered data, need to read from underlying stream
-        loop {
-            let data_added = self.process_incoming_data()?;
-
-            if data_added == 0 {
-                // No data was added - either EOF or only telnet commands
-                if self.data_buffer.is_empty() {
-                    // Check if we hit EOF
-                    let mut temp = [0; 1];
-                    match self.inner.read(&mut temp) {
-                        Ok(0) => return Ok(0), // Confirmed EOF
-                        Ok(n) => {
-                            // Got data, put it back in read buffer for processing
-                            for i in 0..n {
-                                self.read_buffer.push(temp[i]);
-                            }
-                            continue;
-                        }
-                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                            // Non-blocking read, no data available
-                            return Err(io::Error::new(io::ErrorKind::WouldBlock, "Would block"));
+    /// Register core telnet option handlers
+    fn register_core_options(&mut self) {
+        // Register Echo option handler
+        self.option_handlers.insert(
+            TelnetOption::ECHO.to_byte(),
+            Box::new(EchoOption::new(self.role == Role::Server)),
+        );
+
+        // Register Terminal Type option handler
+        self.option_handlers.insert(
+            TelnetOption::TERMINAL_TYPE.to_byte(),
+            Box::new(TerminalTypeOption::new()),
+        );
+
+        // Register NAWS option handler
+        self.option_handlers
+            .insert(TelnetOption::NAWS.to_byte(), Box::new(NawsOption::new()));
+
+        // Register LINEMODE option handler (idle until requested)
+        self.option_handlers.insert(
+            TelnetOption::LINEMODE.to_byte(),
+            Box::new(LinemodeOption::new()),
+        );
+
+        // Register TERMINAL-SPEED option handler (idle until requested)
+        self.option_handlers.insert(
+            TelnetOption::TERMINAL_SPEED.to_byte(),
+            Box::new(TerminalSpeedOption::new()),
+        );
+
+        // Register SEND-LOCATION option handler (idle until requested)
+        self.option_handlers.insert(
+            TelnetOption::SEND_LOCATION.to_byte(),
+            Box::new(SendLocationOption::new()),
+        );
+
+        // Register NEW-ENVIRON option handler
+        self.option_handlers.insert(
+            TelnetOption::NEW_ENVIRON.to_byte(),
+            Box::new(NewEnvironOption::new()),
+        );
+
+        // Register START_TLS option handler (idle until offered)
+        self.option_handlers.insert(
+            TelnetOption::START_TLS.to_byte(),
+            Box::new(StartTlsOption::new()),
+        );
+
+        // Register MXP option handler (idle until offered)
+        #[cfg(feature = "options-mud")]
+        self.option_handlers.insert(
+            TelnetOption::MXP.to_byte(),
+            Box::new(crate::options::MxpOption::new()),
+        );
+
+        // Register MSP option handler (idle until offered)
+        #[cfg(feature = "options-mud")]
+        self.option_handlers.insert(
+            TelnetOption::MSP.to_byte(),
+            Box::new(crate::options::MspOption::new()),
+        );
+    }
+
+    /// Register a custom option handler
+    ///
+    /// This allows extending telnet support with custom options beyond
+    /// the core RFC options (Echo, Terminal Type, NAWS).
+    ///
+    /// A handler that sets
+    /// [`TelnetOptionHandler::subnegotiation_chunk_size`] gets its
+    /// payloads in pieces as they arrive.
+    pub fn register_option_handler(&mut self, handler: Box<dyn TelnetOptionHandler>) {
+        let option = handler.option_code();
+        self.parser
+            .set_chunk_size(option, handler.subnegotiation_chunk_size());
+        self.option_handlers.insert(option.to_byte(), handler);
+        self.recompute_capabilities();
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Registered handler for option {:?}", option);
+        }
+    }
+
+    /// Save the connection's negotiated state, to hand the socket to
+    /// another process or worker
+    ///
+    /// Buffered data, queued output and the negotiation history aren't
+    /// included, so take the snapshot between reads with output flushed.
+    pub fn snapshot(&self) -> StreamSnapshot {
+        StreamSnapshot {
+            negotiator: self.negotiator.snapshot(),
+            handlers: (0..=u8::MAX)
+                .filter_map(|code| {
+                    let state = self.option_handlers.get(code)?.snapshot()?;
+                    Some((code, state))
+                })
+                .collect(),
+        }
+    }
+
+    /// Carry on from a [`StreamSnapshot`] of the same connection
+    ///
+    /// Nothing is sent to the peer. Register any custom handlers first;
+    /// state for options without a handler is ignored. Fails with
+    /// `InvalidData` if a handler can't read its state, leaving the
+    /// handlers before it restored.
+    pub fn restore(&mut self, snapshot: &StreamSnapshot) -> io::Result<()> {
+        self.negotiator.restore(&snapshot.negotiator);
+        let restored = snapshot.handlers.iter().try_for_each(|(code, state)| {
+            match self.option_handlers.get_mut(*code) {
+                Some(handler) => handler
+                    .restore(state)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                None => Ok(()),
+            }
+        });
+        self.recompute_capabilities();
+        restored
+    }
+
+    /// Get a reference to an option handler
+    fn get_option_handler(&self, option: TelnetOption) -> Option<&dyn TelnetOptionHandler> {
+        self.option_handlers.get(option.to_byte())
+    }
+
+    /// The window size from the client's latest NAWS report
+    pub(crate) fn naws_size(&self) -> Option<WindowSize> {
+        self.get_option_handler(TelnetOption::NAWS)
+            .and_then(|handler| handler.as_any().downcast_ref::<NawsOption>())
+            .and_then(|naws| naws.window_size())
+    }
+
+    /// Process sub-negotiation for a specific option
+    fn process_option_subnegotiation(
+        &mut self,
+        option: TelnetOption,
+        data: &[u8],
+    ) -> io::Result<()> {
+        if option == TelnetOption::NAWS {
+            let before = self.naws_size();
+            self.dispatch_subnegotiation(option, data)?;
+            let after = self.naws_size();
+            if let Some(size) = after
+                && after != before
+            {
+                if self.resize_debounce.is_some() {
+                    // A size that outlasted its quiet period still counts
+                    self.settle_resize();
+                    self.pending_resize = Some((size, Instant::now()));
+                } else {
+                    self.report_resize(size);
+                }
+            }
+            self.recompute_capabilities();
+            return Ok(());
+        }
+        self.dispatch_subnegotiation(option, data)?;
+        self.recompute_capabilities();
+        Ok(())
+    }
+
+    /// Hand sub-negotiation data to the option's handler and send its reply
+    fn dispatch_subnegotiation(&mut self, option: TelnetOption, data: &[u8]) -> io::Result<()> {
+        self.call_option_handler(option, |handler| handler.handle_subnegotiation(data))
+    }
+
+    /// Hand a piece of a chunked sub-negotiation to the option's handler,
+    /// logging rather than failing if that goes wrong
+    ///
+    /// Chunked payloads aren't reported as events, since that would mean
+    /// holding them whole after all.
+    fn handle_subnegotiation_chunk(
+        &mut self,
+        option: TelnetOption,
+        data: &[u8],
+        offset: usize,
+        last: bool,
+    ) {
+        let stats = &mut self.stats;
+        stats.subnegotiation_bytes_received += data.len() as u64;
+        if last {
+            stats.sequences_received += 1;
+            stats.subnegotiations_received += 1;
+            stats.largest_subnegotiation = stats.largest_subnegotiation.max(offset + data.len());
+        }
+        if self.debug_logging {
+            eprintln!(
+                "[TelnetStream] Sub-negotiation chunk for {:?}: {} bytes at {}{}",
+                option,
+                data.len(),
+                offset,
+                if last { ", last" } else { "" }
+            );
+        }
+
+        let result = self.call_option_handler(option, |handler| {
+            handler.handle_subnegotiation_chunk(data, offset, last)
+        });
+        if let Err(e) = result
+            && self.debug_logging
+        {
+            eprintln!(
+                "[TelnetStream] Error handling sub-negotiation chunk for {:?}: {}",
+                option, e
+            );
+        }
+        if last {
+            self.recompute_capabilities();
+        }
+    }
+
+    /// Call the option's handler with `call` and send the sub-negotiation
+    /// it returns, if any
+    fn call_option_handler(
+        &mut self,
+        option: TelnetOption,
+        call: impl FnOnce(&mut dyn TelnetOptionHandler) -> Result<Vec<u8>, crate::options::OptionError>,
+    ) -> io::Result<()> {
+        if let Some(handler) = self.option_handlers.get_mut(option.to_byte()) {
+            match call(handler.as_mut()) {
+                Ok(response_data) => {
+                    // Send response if handler generated one
+                    if !response_data.is_empty() {
+                        let len = response_data.len();
+                        let response = TelnetSequence::SubNegotiation {
+                            option,
+                            data: response_data,
+                        };
+                        self.send_raw(&response.to_bytes())?;
+
+                        if self.debug_logging {
+                            eprintln!(
+                                "[TelnetStream] Sent sub-negotiation response for {:?}: {} bytes",
+                                option, len
+                            );
                         }
-                        Err(e) => return Err(e),
                     }
-                } else {
-                    // We have some buffered data, return it
-                    let bytes_to_copy = std::cmp::min(buf.len(), self.data_buffer.len());
-                    for i in 0..bytes_to_copy {
-                        buf[i] = self.data_buffer.pop_front().unwrap();
+                    Ok(())
+                }
+                Err(e) => {
+                    if self.debug_logging {
+                        eprintln!(
+                            "[TelnetStream] Sub-negotiation error for {:?}: {}",
+                            option, e
+                        );
                     }
-                    return Ok(bytes_to_copy);
+                    Ok(()) // Don't fail the entire operation for sub-negotiation errors
                 }
-            } else {
-                // Data was added to buffer, return what we can
-                let bytes_to_copy = std::cmp::min(buf.len(), self.data_buffer.len());
-                for i in 0..bytes_to_copy {
-                    buf[i] = self.data_buffer.pop_front().unwrap();
+            }
+        } else {
+            if self.debug_logging {
+                eprintln!("[TelnetStream] No handler registered for {:?}", option);
+            }
+            Ok(())
+        }
+    }
+
+    /// Write a complete protocol unit (command, negotiation or sub-negotiation)
+    ///
+    /// The unit is queued whole before any of it is written, so an error
+    /// part way (a write timeout, `WouldBlock`) leaves the rest queued
+    /// rather than lost or torn; it goes out ahead of the next write or
+    /// flush. With the `rfc-audit` feature, the bytes are checked for
+    /// RFC 854/855 framing before they are sent.
+    fn send_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        #[cfg(feature = "rfc-audit")]
+        crate::audit::enforce("protocol write", bytes, &self.audited_options());
+
+        self.stats.count_sent(bytes);
+        self.pending_output.extend_from_slice(bytes);
+        if self.replying {
+            return Ok(());
+        }
+        self.send_pending()
+    }
+
+    /// Send the replies queued while parsing a batch of input
+    ///
+    /// Errors are left for the writer: whatever the transport doesn't take
+    /// stays queued and goes out ahead of the next write or flush.
+    fn send_replies(&mut self) {
+        if self.pending_output.is_empty() {
+            return;
+        }
+        if let Err(e) = self.write_out()
+            && self.debug_logging
+        {
+            eprintln!("[TelnetStream] Replies left queued: {}", e);
+        }
+    }
+
+    /// Write out everything in `pending_output`
+    ///
+    /// Whatever the transport doesn't take stays queued, so this can be
+    /// retried after any error.
+    fn send_pending(&mut self) -> io::Result<()> {
+        while !self.pending_output.is_empty() {
+            let pending = std::mem::take(&mut self.pending_output);
+            let result = self.transport_write(&pending);
+            let written = match &result {
+                Ok(n) => *n,
+                Err(_) => 0,
+            };
+            self.pending_output = pending;
+            self.pending_output.drain(..written);
+
+            match result {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of encoded bytes accepted but not yet handed to the transport
+    ///
+    /// Non-zero after a short or failed write, or while a buffering
+    /// [`FlushPolicy`] holds data back; [`Write::flush`] (or the next
+    /// write) sends them.
+    pub fn pending_output_len(&self) -> usize {
+        self.pending_output.len()
+    }
+
+    /// Choose when written data goes to the transport
+    ///
+    /// With [`FlushPolicy::Immediate`] (the default) each write makes one
+    /// transport write, as described under [`Write`]. The other policies
+    /// accept whole writes into the output queue and send it at a newline
+    /// ([`FlushPolicy::OnNewline`]) or on `flush` ([`FlushPolicy::Manual`]),
+    /// and whenever [`WRITE_BUFFER_CAPACITY`] would be passed. Protocol
+    /// output, such as a negotiation reply, takes queued data with it so
+    /// the order holds. Data already held back waits for the next send.
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
+    }
+
+    /// The policy in force for written data
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+
+    /// Choose how newlines are translated outside BINARY
+    ///
+    /// [`NewlineMode::Normalize`] suits applications that read lines with
+    /// `BufRead`: whatever the client sends at Enter arrives as `\n`.
+    /// [`NewlineMode::Raw`] suits peers that don't follow the NVT rules.
+    /// Data already read or queued is left as it is.
+    pub fn set_newline_mode(&mut self, mode: NewlineMode) {
+        self.newline_mode = mode;
+    }
+
+    /// The newline translation in force
+    pub fn newline_mode(&self) -> NewlineMode {
+        self.newline_mode
+    }
+
+    /// Choose how written data is checked before it's sent
+    ///
+    /// Data is always escaped, so a 255 250 in a file being sent goes out
+    /// as data rather than opening a sub-negotiation. Under
+    /// [`WriteCheck::Strict`] such a write is refused instead, with
+    /// `InvalidInput` and none of it taken, since in text it's usually a
+    /// sequence meant for [`TelnetStream::write_raw`]; the check looks
+    /// within each write, across its slices. Strict also has `write_raw`
+    /// refuse bytes that aren't well-formed telnet, such as an IAC SB
+    /// with no IAC SE.
+    pub fn set_write_check(&mut self, check: WriteCheck) {
+        self.write_check = check;
+    }
+
+    /// The write check in force
+    pub fn write_check(&self) -> WriteCheck {
+        self.write_check
+    }
+
+    /// What the audit checks outgoing WILL, DO and SB against
+    fn audited_options(&self) -> AuditedOptions<'_> {
+        AuditedOptions {
+            handlers: &self.option_handlers,
+            negotiator: &self.negotiator,
+        }
+    }
+
+    /// Send bytes exactly as given, without data escaping
+    ///
+    /// For sequences the stream has no method for, such as a command
+    /// built with [`TelnetSequence::to_bytes`]. The bytes are queued whole
+    /// ahead of any later data and sent like a negotiation reply. Under
+    /// [`WriteCheck::Strict`] they're checked with
+    /// [`audit_outgoing`](crate::audit::audit_outgoing) first, and a
+    /// malformed sequence, a WILL or DO for an option with no handler, or
+    /// a sub-negotiation for an option not enabled is refused with
+    /// `InvalidInput`; with the `rfc-audit` feature they must pass the
+    /// audit in any case.
+    /// Nothing is counted as data, and the stream's view of negotiated
+    /// options isn't changed, so use [`TelnetStream::enable_option`] and
+    /// its kin for negotiation.
+    pub fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if self.write_check == WriteCheck::Strict {
+            crate::audit::audit_outgoing(bytes, &self.audited_options())
+                .map_err(|violation| io::Error::new(io::ErrorKind::InvalidInput, violation))?;
+        }
+        self.send_raw(bytes)
+    }
+
+    /// Convert data between the application's character set and the
+    /// client's, such as CP437 door art for a UTF-8 terminal
+    ///
+    /// Applies to data read and written, in BINARY or not; telnet
+    /// commands are untouched. Under [`Transcoding::Negotiated`] the
+    /// client's set follows what it reports, so conversion may start or
+    /// change partway through. Writes are then taken whole, as under a
+    /// buffering [`FlushPolicy`]: what the transport doesn't take at once
+    /// is queued, and a failed send is left for the next write or flush to
+    /// report.
+    pub fn set_transcoding(&mut self, transcoding: Transcoding) {
+        self.transcoding = transcoding;
+        self.update_transcoders();
+    }
+
+    /// The character set conversion asked for
+    pub fn transcoding(&self) -> Transcoding {
+        self.transcoding
+    }
+
+    /// The application's and the client's character sets, if data is
+    /// being converted between them
+    pub fn transcoding_between(&self) -> Option<(Encoding, Encoding)> {
+        self.outgoing
+            .as_ref()
+            .map(|transcoder| (transcoder.from(), transcoder.to()))
+    }
+
+    /// Set up the converters for the current setting and client
+    ///
+    /// Left alone if nothing changed, so a character split between two
+    /// reads or writes still comes out whole.
+    fn update_transcoders(&mut self) {
+        let between = match self.transcoding {
+            Transcoding::Off => None,
+            Transcoding::Negotiated { app } => self
+                .capabilities
+                .charset
+                .as_deref()
+                .and_then(Encoding::from_name)
+                .map(|peer| (app, peer)),
+            Transcoding::Manual { app, peer } => Some((app, peer)),
+        }
+        .filter(|(app, peer)| app != peer);
+        if between == self.transcoding_between() {
+            return;
+        }
+        self.outgoing = between.map(|(app, peer)| Transcoder::new(app, peer));
+        self.incoming = between.map(|(app, peer)| Transcoder::new(peer, app));
+    }
+
+    /// Strip or downgrade escape sequences the client's terminal can't
+    /// handle, so render code can use any it likes
+    ///
+    /// See [`crate::ansi`] for what is changed. Under
+    /// [`AnsiFiltering::Negotiated`] the profile follows the terminal
+    /// capabilities, and nothing is changed until the client names its
+    /// terminal. Writes are then taken whole, as with
+    /// [`TelnetStream::set_transcoding`]. A sequence split between writes
+    /// is held until its end arrives.
+    pub fn set_ansi_filtering(&mut self, filtering: AnsiFiltering) {
+        self.ansi_filtering = filtering;
+        self.update_ansi_filter();
+    }
+
+    /// The escape sequence downgrading asked for
+    pub fn ansi_filtering(&self) -> AnsiFiltering {
+        self.ansi_filtering
+    }
+
+    /// The profile output is being fitted to, if any
+    pub fn ansi_profile(&self) -> Option<AnsiProfile> {
+        self.ansi_filter.as_ref().map(AnsiFilter::profile)
+    }
+
+    /// Set up the filter for the current setting and client, keeping any
+    /// sequence it holds
+    fn update_ansi_filter(&mut self) {
+        let profile = match self.ansi_filtering {
+            AnsiFiltering::Off => None,
+            AnsiFiltering::Negotiated => self
+                .capabilities
+                .terminal_type
+                .is_some()
+                .then(|| AnsiProfile::from(&self.capabilities)),
+            AnsiFiltering::Manual(profile) => Some(profile),
+        };
+        match (profile, self.ansi_filter.as_mut()) {
+            (Some(profile), Some(filter)) => filter.set_profile(profile),
+            (profile, _) => self.ansi_filter = profile.map(AnsiFilter::new),
+        }
+    }
+
+    /// Filter, convert and queue a write whole; see
+    /// [`TelnetStream::set_transcoding`] and
+    /// [`TelnetStream::set_ansi_filtering`]
+    fn write_converted(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        if self.flush_policy == FlushPolicy::Immediate {
+            // Older output goes first; until it's out none of `bufs` is taken
+            self.send_pending()?;
+        }
+        let mut converted = std::mem::take(&mut self.encoded);
+        converted.clear();
+        for buf in bufs {
+            match (self.ansi_filter.as_mut(), self.outgoing.as_mut()) {
+                (Some(filter), Some(transcoder)) => {
+                    transcoder.push(&filter.filter(buf), &mut converted)
                 }
-                return Ok(bytes_to_copy);
+                (Some(filter), None) => filter.push(buf, &mut converted),
+                (None, Some(transcoder)) => transcoder.push(buf, &mut converted),
+                (None, None) => converted.extend_from_slice(buf),
             }
         }
+
+        let binary = self.sends_raw();
+        let start = self.pending_output.len();
+        encode_data(
+            converted.iter(),
+            binary,
+            &mut self.write_after_cr,
+            &mut self.pending_output,
+        );
+        self.encoded = converted;
+
+        #[cfg(feature = "rfc-audit")]
+        crate::audit::enforce(
+            "data write",
+            &self.pending_output[start..],
+            &self.audited_options(),
+        );
+
+        let added = &self.pending_output[start..];
+        let send = match self.flush_policy {
+            FlushPolicy::Immediate => true,
+            FlushPolicy::OnNewline => added.contains(&b'\n'),
+            FlushPolicy::Manual => self.pending_output.len() > WRITE_BUFFER_CAPACITY,
+        };
+        if !added.is_empty() {
+            self.owes_go_ahead = true;
+        }
+        if send
+            && let Err(e) = self.send_pending()
+            && self.debug_logging
+        {
+            eprintln!("[TelnetStream] Write left queued: {}", e);
+        }
+        Ok(bufs.iter().map(|buf| buf.len()).sum())
     }
-}
 
-/// Implement Write trait for transparent telnet operation
-///
-/// The Write implementation passes application data through to the underlying
-/// TcpStream while ensuring proper telnet protocol handling for any embedded
-/// IAC bytes (RFC 854 escaping).
-impl Write for TelnetStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        // Check if we need to escape any IAC bytes (255) in the data
-        // Per RFC 854, data byte 255 must be sent as IAC IAC (255 255)
+    /// Whether written data goes out without newline translation
+    fn sends_raw(&self) -> bool {
+        self.newline_mode == NewlineMode::Raw
+            || self
+                .negotiator
+                .is_enabled(Side::Local, TelnetOption::BINARY)
+    }
 
-        let mut escaped_data = Vec::new();
-        let mut needs_escaping = false;
+    /// Write `bufs` with a single transport write, returning how many of
+    /// their bytes went out
+    fn write_now(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let binary = self.sends_raw();
+        let bytes = || bufs.iter().flat_map(|buf| buf.iter());
+        if bufs.iter().all(|buf| buf.is_empty()) {
+            return Ok(0);
+        }
 
-        for &byte in buf {
-            if byte == 255 {
-                // IAC byte
-                escaped_data.push(255); // First IAC
-                escaped_data.push(255); // Second IAC (escaped)
-                needs_escaping = true;
-            } else {
-                escaped_data.push(byte);
+        // Text without IAC, CR or LF goes out as it is, without a copy
+        let mut encoded = std::mem::take(&mut self.encoded);
+        encoded.clear();
+        let wire: &[u8] = match bufs {
+            [buf] if is_wire_ready(buf, binary, self.write_after_cr) => buf,
+            _ => {
+                let mut after_cr = self.write_after_cr;
+                encode_data(bytes(), binary, &mut after_cr, &mut encoded);
+                &encoded
+            }
+        };
+
+        #[cfg(feature = "rfc-audit")]
+        crate::audit::enforce("data write", wire, &self.audited_options());
+
+        let written = loop {
+            match self.transport_write(wire) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.encoded = encoded;
+                    return Err(e);
+                }
+                Ok(written) => break written,
+            }
+        };
+
+        // Map encoded bytes back to data bytes; a byte counts once its
+        // encoding has started, and the rest of it is queued
+        let mut consumed = 0;
+        let mut end = 0;
+        let mut after_cr = self.write_after_cr;
+        for &byte in bytes() {
+            if end >= written {
+                break;
             }
+            end += encoded_len(byte, binary, after_cr);
+            after_cr = !binary && byte == b'\r';
+            consumed += 1;
+        }
+        if end > written {
+            self.pending_output.extend_from_slice(&wire[written..end]);
         }
+        self.encoded = encoded;
 
-        if needs_escaping {
-            // Send escaped data
-            self.inner.write_all(&escaped_data)?;
-            // Return original buffer length to caller
-            Ok(buf.len())
-        } else {
-            // No escaping needed, pass through directly
-            self.inner.write(buf)
+        if consumed > 0 {
+            self.write_after_cr = after_cr;
+            self.owes_go_ahead = true;
         }
+        Ok(consumed)
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        self.inner.flush()
+    /// Accept all of `bufs` into the output queue under a buffering
+    /// [`FlushPolicy`]
+    ///
+    /// Queued output is sent first if `bufs` would take it past
+    /// [`WRITE_BUFFER_CAPACITY`]; an error then means none of `bufs` was
+    /// taken. Once taken, a failed send at a newline is left for the next
+    /// write or flush to report.
+    fn write_buffered(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if self.pending_output.len() + len > WRITE_BUFFER_CAPACITY {
+            self.send_pending()?;
+            if len > WRITE_BUFFER_CAPACITY {
+                return self.write_now(bufs);
+            }
+        }
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let binary = self.sends_raw();
+        let start = self.pending_output.len();
+        encode_data(
+            bufs.iter().flat_map(|buf| buf.iter()),
+            binary,
+            &mut self.write_after_cr,
+            &mut self.pending_output,
+        );
+
+        #[cfg(feature = "rfc-audit")]
+        crate::audit::enforce(
+            "data write",
+            &self.pending_output[start..],
+            &self.audited_options(),
+        );
+        let ends_line = self.pending_output[start..].contains(&b'\n');
+
+        self.owes_go_ahead = true;
+        if self.flush_policy == FlushPolicy::OnNewline
+            && ends_line
+            && let Err(e) = self.send_pending()
+            && self.debug_logging
+        {
+            eprintln!("[TelnetStream] Line left queued: {}", e);
+        }
+        Ok(len)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::protocol::{TelnetCommand, TelnetOption};
+    /// Read from the TLS session if one is active, otherwise the plain socket
+    fn transport_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.tls.as_mut() {
+            Some(tls) => tls.read(buf),
+            None => self.inner.read(buf),
+        }
+    }
+
+    /// Write to the TLS session if one is active, otherwise the plain socket
+    ///
+    /// A single write, which may take only part of `bytes`. Nothing may go
+    /// out in the clear after our START_TLS FOLLOWS, so output is held until
+    /// the handshake has replaced the transport.
+    fn transport_write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if self.start_tls_state() == StartTlsState::FollowsSent {
+            self.held_output.extend_from_slice(bytes);
+            return Ok(bytes.len());
+        }
 
-    // Mock TcpStream for testing using Cursor<Vec<u8>>
-    // This isn't a complete mock but sufficient for basic testing
+        let written = match self.tls.as_mut() {
+            Some(tls) => tls.write(bytes),
+            None => self.inner.write(bytes),
+        }?;
+        if written > 0 {
+            self.last_sent = Instant::now();
+            self.stats.bytes_sent += written as u64;
+        }
+        Ok(written)
+    }
 
-    #[test]
-    fn test_telnet_stream_creation() {
-        // We can't easily test with real TcpStream in unit tests
-        // This test just verifies the struct can be created with proper field initialization
-        // Integration tests with real network streams would go in examples/
+    /// Send anything pending, then flush the transport
+    ///
+    /// While input is being parsed this waits for the end of the batch.
+    fn transport_flush(&mut self) -> io::Result<()> {
+        if self.replying {
+            return Ok(());
+        }
+        self.write_out()
+    }
 
-        // Test that the struct fields are properly initialized
-        let _parser = TelnetParser::new();
-        let negotiator = OptionNegotiator::new();
+    /// Send anything pending and flush the transport, even mid-batch
+    fn write_out(&mut self) -> io::Result<()> {
+        self.send_pending()?;
+        match self.tls.as_mut() {
+            Some(tls) => tls.flush(),
+            None => self.inner.flush(),
+        }
+    }
+
+    // ========================================
+    // START_TLS
+    // ========================================
+
+    /// Offer to upgrade this connection to TLS (IAC DO START_TLS)
+    ///
+    /// The exchange then completes as data is read: when the client agrees
+    /// we send FOLLOWS, and once its own FOLLOWS arrives `upgrader` is handed
+    /// the socket and every later read and write goes through TLS. Use
+    /// [`TelnetStream::await_start_tls`] to block until that has happened.
+    ///
+    /// Fails with `Unsupported` while the stream is split into halves.
+    pub fn offer_start_tls(&mut self, upgrader: Box<dyn TlsUpgrader<S>>) -> io::Result<()> {
+        if self.split {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "START_TLS needs the whole stream; reunite the halves first",
+            ));
+        }
+        if self.start_tls_state() != StartTlsState::Idle {
+            return Ok(());
+        }
+
+        self.tls_upgrader = Some(upgrader);
+        self.set_start_tls_state(StartTlsState::Offered);
+
+        let result = self
+            .negotiator
+            .request_enable(Side::Remote, TelnetOption::START_TLS);
+        if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+            self.send_negotiation(command, option)?;
+        }
+        self.transport_flush()?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Offered START_TLS (sent DO START_TLS)");
+        }
+
+        Ok(())
+    }
+
+    /// Read until an outstanding START_TLS offer is settled or `timeout` passes
+    ///
+    /// Returns true if the connection is now encrypted. A client that does
+    /// not answer in time has the offer withdrawn and stays in plaintext.
+    /// Application data read meanwhile is kept for later `read` calls.
+    pub fn await_start_tls(&mut self, timeout: Duration) -> io::Result<bool> {
+        let previous_timeout = self.inner.read_timeout()?;
+        let deadline = std::time::Instant::now() + timeout;
+
+        let outcome = loop {
+            match self.start_tls_state() {
+                StartTlsState::Active => break Ok(true),
+                StartTlsState::Idle | StartTlsState::Declined => break Ok(false),
+                StartTlsState::Offered | StartTlsState::FollowsSent | StartTlsState::Ready => {}
+            }
+
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break self.withdraw_start_tls().map(|_| false);
+            }
+
+            self.inner.set_read_timeout(Some(remaining))?;
+            let mut temp_buffer = [0; 4096];
+            match self.transport_read(&mut temp_buffer) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Connection closed during START_TLS",
+                    ));
+                }
+                Ok(n) => {
+                    self.read_buffer.extend_from_slice(&temp_buffer[..n]);
+                    if let Err(e) = self.process_read_buffer() {
+                        break Err(e);
+                    }
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.inner.set_read_timeout(previous_timeout)?;
+        outcome
+    }
+
+    /// Current point in the START_TLS exchange
+    pub fn start_tls_state(&self) -> StartTlsState {
+        self.get_option_handler(TelnetOption::START_TLS)
+            .and_then(|handler| handler.as_any().downcast_ref::<StartTlsOption>())
+            .map(|handler| handler.state())
+            .unwrap_or(StartTlsState::Idle)
+    }
+
+    /// Whether reads and writes currently go through TLS
+    pub fn is_tls_active(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    fn set_start_tls_state(&mut self, state: StartTlsState) {
+        if let Some(handler) = self
+            .option_handlers
+            .get_mut(TelnetOption::START_TLS.to_byte())
+            && let Some(start_tls) = handler.as_any_mut().downcast_mut::<StartTlsOption>()
+        {
+            start_tls.set_state(state);
+        }
+    }
+
+    /// Drive START_TLS forward after the client answers our DO
+    fn handle_start_tls_negotiation(&mut self, command: TelnetCommand) -> io::Result<()> {
+        match (command, self.start_tls_state()) {
+            (TelnetCommand::WILL, StartTlsState::Offered)
+                if self
+                    .negotiator
+                    .is_enabled(Side::Remote, TelnetOption::START_TLS) =>
+            {
+                let sequence = TelnetSequence::SubNegotiation {
+                    option: TelnetOption::START_TLS,
+                    data: vec![FOLLOWS],
+                };
+                self.send_raw(&sequence.to_bytes())?;
+                // FOLLOWS is the last thing sent in the clear, so it can't
+                // wait for the end of the batch
+                self.write_out()?;
+                self.set_start_tls_state(StartTlsState::FollowsSent);
+
+                if self.debug_logging {
+                    eprintln!("[TelnetStream] Client accepted START_TLS, sent FOLLOWS");
+                }
+            }
+            (TelnetCommand::WONT, StartTlsState::Offered) => {
+                self.tls_upgrader = None;
+                self.set_start_tls_state(StartTlsState::Declined);
+
+                if self.debug_logging {
+                    eprintln!("[TelnetStream] Client declined START_TLS");
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Give up on a START_TLS offer the client has not answered
+    fn withdraw_start_tls(&mut self) -> io::Result<()> {
+        match self.start_tls_state() {
+            StartTlsState::Offered => {
+                // RFC 1143 queues the DONT, so a late WILL is refused cleanly
+                let result = self
+                    .negotiator
+                    .request_disable(Side::Remote, TelnetOption::START_TLS);
+                if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+                    self.send_negotiation(command, option)?;
+                    self.transport_flush()?;
+                }
+                self.tls_upgrader = None;
+                self.set_start_tls_state(StartTlsState::Declined);
+                Ok(())
+            }
+            // FOLLOWS is already out, so plaintext can no longer be trusted
+            StartTlsState::FollowsSent | StartTlsState::Ready => {
+                Err(TelnetError::NegotiationTimeout {
+                    option: TelnetOption::START_TLS,
+                }
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Hand the socket to the TLS backend once the client's FOLLOWS is in
+    fn complete_start_tls(&mut self) -> io::Result<()> {
+        let Some(mut upgrader) = self.tls_upgrader.take() else {
+            return Err(io::Error::other("START_TLS ready without a TLS backend"));
+        };
+
+        // Anything read past FOLLOWS is the start of the TLS handshake
+        let buffered = std::mem::take(&mut self.read_buffer);
+        let tls = upgrader.upgrade(self.inner.try_clone()?, buffered)?;
+        self.tls = Some(tls);
+        self.set_start_tls_state(StartTlsState::Active);
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] START_TLS complete, connection encrypted");
+        }
+
+        let held = std::mem::take(&mut self.held_output);
+        if !held.is_empty() {
+            self.pending_output.extend_from_slice(&held);
+            self.transport_flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a negotiation command and record it in the history
+    fn send_negotiation(&mut self, command: TelnetCommand, option: TelnetOption) -> io::Result<()> {
+        let sequence = TelnetSequence::Negotiation { command, option };
+        // Record even on error: the command is queued and will still go out
+        let result = self.send_raw(&sequence.to_bytes());
+        self.record_negotiation(Direction::Sent, command, option);
+        result
+    }
+
+    /// Add a negotiation command to the history along with the resulting state
+    fn record_negotiation(
+        &mut self,
+        direction: Direction,
+        command: TelnetCommand,
+        option: TelnetOption,
+    ) {
+        self.history.record(
+            direction,
+            command,
+            option,
+            self.negotiator.get_state(Side::Local, option),
+            self.negotiator.get_state(Side::Remote, option),
+        );
+    }
+
+    /// Get the recorded negotiation history for this connection
+    ///
+    /// Useful for fingerprinting clients and diagnosing negotiations
+    /// that did not converge.
+    pub fn negotiation_history(&self) -> &NegotiationHistory {
+        &self.history
+    }
+
+    /// How quickly the peer answered each option we requested
+    ///
+    /// Only answers processed so far count, so call this after
+    /// [`TelnetStream::poll_negotiation`] has given the peer time to reply.
+    pub fn negotiation_stats(&self) -> NegotiationStats {
+        self.history.stats()
+    }
+
+    /// Set the read timeout for the underlying transport
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(dur)
+    }
+
+    /// Get the read timeout of the underlying transport
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.inner.read_timeout()
+    }
+
+    /// Shutdown the underlying transport
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// Process incoming data and handle telnet commands automatically
+    ///
+    /// This is the core method that:
+    /// 1. Reads raw data from the underlying transport
+    /// 2. Parses telnet command sequences
+    /// 3. Handles option negotiation automatically
+    /// 4. Buffers clean application data
+    /// 5. Sends appropriate telnet responses
+    ///
+    /// Makes exactly one transport read. Returns the number of clean data
+    /// bytes it added, which may be none if only telnet commands or part of
+    /// a sequence arrived, or `None` at end of file. Errors from the read
+    /// (`WouldBlock`, `TimedOut`) are passed on untouched; bytes already
+    /// read stay buffered, and a sequence split across reads is finished
+    /// by the next one.
+    fn process_incoming_data(&mut self) -> io::Result<Option<usize>> {
+        let mut temp_buffer = [0; 4096];
+        match self.transport_read(&mut temp_buffer)? {
+            0 => Ok(None),
+            n => self.feed(&temp_buffer[..n]).map(Some),
+        }
+    }
+
+    /// When a `read` waiting on input should wake before its own timeout:
+    /// a debounced resize settling that should interrupt it, or a
+    /// keepalive falling due
+    fn next_wake(&self) -> Option<Duration> {
+        let resize = self
+            .resize_settles_in()
+            .filter(|_| self.interrupt_on_resize);
+        resize.into_iter().chain(self.keepalive_due_in()).min()
+    }
+
+    /// [`TelnetStream::process_incoming_data`] for a `read` waiting on
+    /// input, woken when a debounced resize settles and should interrupt it
+    /// and to send keepalives
+    ///
+    /// The resize wake-up is reported as a read that added no data. The
+    /// caller's read timeout still counts from the start.
+    fn read_incoming(&mut self) -> io::Result<Option<usize>> {
+        if self.next_wake().is_none() {
+            return self.process_incoming_data();
+        }
+
+        let previous_timeout = self.inner.read_timeout()?;
+        let deadline = previous_timeout.map(|timeout| Instant::now() + timeout);
+        let outcome = loop {
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let wait = self.next_wake().into_iter().chain(remaining).min();
+            // A zero timeout means none at all to a TcpStream
+            self.inner
+                .set_read_timeout(wait.map(|wait| wait.max(Duration::from_millis(1))))?;
+            let started = Instant::now();
+
+            match self.process_incoming_data() {
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    self.settle_resize();
+                    if self.interrupt_on_resize && self.resize_interrupt_due {
+                        break Ok(Some(0));
+                    }
+                    if let Err(e) = self.tick() {
+                        break Err(e);
+                    }
+                    // Out of time, or a nonblocking transport giving up at once
+                    let expired = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                    if expired || wait.is_none_or(|wait| started.elapsed() < wait / 2) {
+                        break Err(e);
+                    }
+                }
+                outcome => break outcome,
+            }
+        };
+        self.inner.set_read_timeout(previous_timeout)?;
+        outcome
+    }
+
+    /// Process bytes read from the peer, as if the stream had read them
+    ///
+    /// Returns the number of clean data bytes added to the application buffer.
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.read_buffer.extend_from_slice(bytes);
+        self.process_read_buffer()
+    }
+
+    /// Read until there is application data buffered, returning false
+    /// at end of file
+    fn fill_data_buffer(&mut self) -> io::Result<bool> {
+        while self.data_buffer.is_empty() {
+            // A resize may be all that arrived
+            self.interrupt_if_due()?;
+            if self.read_incoming()?.is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read a line of input as telnet clients send it, appending it to
+    /// `line` without its ending
+    ///
+    /// Clients end a line with CR LF, CR NUL or a bare LF depending on
+    /// their mode, so a line ends at CR or LF, and an LF just after a CR
+    /// ending is skipped even if it arrives later. The line is returned as
+    /// soon as its ending arrives, however the input was split into
+    /// packets, and invalid UTF-8 is replaced rather than failing the read.
+    /// Returns the number of bytes taken from the stream, 0 at end of file;
+    /// text after the last line ending is returned as a final line.
+    ///
+    /// Errors (`WouldBlock`, a read timeout, a resize interrupt) leave a
+    /// partial line buffered for the next call.
+    pub fn read_telnet_line(&mut self, line: &mut String) -> io::Result<usize> {
+        let mut skipped = 0;
+        loop {
+            if self.line_after_cr && !self.data_buffer.is_empty() {
+                self.line_after_cr = false;
+                if self.data_buffer.get(0) == Some(b'\n') {
+                    self.data_buffer.consume(1);
+                    skipped += 1;
+                }
+            }
+
+            let ending = self
+                .data_buffer
+                .position(|byte| byte == b'\r' || byte == b'\n');
+            let taken = match ending {
+                Some(end) => {
+                    self.line_after_cr = self.data_buffer.get(end) == Some(b'\r');
+                    end + 1
+                }
+                None => {
+                    self.interrupt_if_due()?;
+                    if self.read_incoming()?.is_some() {
+                        continue;
+                    }
+                    if self.data_buffer.is_empty() {
+                        return Ok(0);
+                    }
+                    self.data_buffer.len()
+                }
+            };
+
+            let bytes = self.data_buffer.take_vec(taken);
+            let text = match ending {
+                Some(_) => &bytes[..taken - 1],
+                None => &bytes[..],
+            };
+            line.push_str(&String::from_utf8_lossy(text));
+            return Ok(skipped + taken);
+        }
+    }
+
+    /// Move buffered application data into `buf`, returning how much
+    pub(crate) fn read_buffered(&mut self, buf: &mut [u8]) -> usize {
+        self.data_buffer.read_into(buf)
+    }
+
+    /// Note whether the stream is split into halves
+    pub(crate) fn set_split(&mut self, split: bool) {
+        self.split = split;
+    }
+
+    /// Parse everything buffered so far, switching to TLS at the right byte
+    ///
+    /// Returns the number of clean data bytes added to the application buffer.
+    fn process_read_buffer(&mut self) -> io::Result<usize> {
+        let mut data_bytes_added = 0;
+        loop {
+            // While START_TLS is pending, parse a byte at a time so parsing
+            // stops exactly at the client's FOLLOWS; what follows is TLS
+            let pending_tls = self.start_tls_state() == StartTlsState::FollowsSent;
+            let parse_len = if pending_tls {
+                self.read_buffer.len().min(1)
+            } else {
+                self.read_buffer.len()
+            };
+            if parse_len == 0 {
+                break;
+            }
+
+            data_bytes_added += self.parse_buffered(parse_len);
+            self.send_replies();
+
+            if self.start_tls_state() == StartTlsState::Ready {
+                self.complete_start_tls()?;
+                break;
+            }
+            if !pending_tls {
+                break;
+            }
+        }
+
+        self.enforce_parser_limits()?;
+        self.enforce_loop_guard()?;
+        self.enforce_memory_budget()?;
+        self.report_stats_if_due();
+        // Some of what was added may have been thrown away
+        Ok(data_bytes_added.min(self.data_buffer.len()))
+    }
+
+    /// Parse the first `len` buffered bytes and act on any telnet sequences
+    ///
+    /// Returns the number of clean data bytes added to the application buffer.
+    fn parse_buffered(&mut self, len: usize) -> usize {
+        // The events borrow the input and the parser, so both are set
+        // aside while the sequences among them are handled
+        let input = std::mem::take(&mut self.read_buffer);
+        self.stats.bytes_received += len as u64;
+        let mut parser = std::mem::take(&mut self.parser);
+        let mut events = parser.parse_borrowed(&input[..len]);
+
+        // Data and sequences are handled in stream order, since a sequence
+        // (BINARY, say) can change how the data after it is read
+        let mut data_bytes_added = 0;
+        let mut overflow = None;
+        self.replying = true;
+        for event in events.by_ref() {
+            match event {
+                ParseEvent::Data(bytes) => {
+                    self.stats.data_received += bytes.len() as u64;
+                    data_bytes_added += self.push_data(bytes);
+                }
+                ParseEvent::Sequence(sequence) => self.handle_parsed_sequence(&sequence),
+                ParseEvent::Subnegotiation { option, data } => {
+                    self.handle_parsed_sequence(&TelnetSequence::SubNegotiation {
+                        option,
+                        data: data.into_owned(),
+                    })
+                }
+                ParseEvent::SubnegotiationChunk {
+                    option,
+                    data,
+                    offset,
+                    last,
+                } => self.handle_subnegotiation_chunk(option, &data, offset, last),
+                ParseEvent::Overflow(option) => {
+                    overflow.get_or_insert(option);
+                }
+                ParseEvent::Violation(violation) => {
+                    if self.debug_logging {
+                        eprintln!("[TelnetStream] Protocol violation: {}", violation);
+                    }
+                    self.push_event(NegotiationEvent::ProtocolViolation(violation));
+                }
+            }
+        }
+        self.replying = false;
+
+        // Remove processed bytes from read buffer
+        let consumed = events.bytes_consumed();
+        drop(events);
+        self.parser = parser;
+        self.read_buffer = input;
+        self.read_buffer.drain(..consumed);
+
+        if let Some(option) = overflow {
+            if self.debug_logging {
+                eprintln!(
+                    "[TelnetStream] Sub-negotiation for {:?} over the parser limit, policy {:?}",
+                    option,
+                    self.parser.limits().policy()
+                );
+            }
+            if self.parser.limits().policy() == LimitPolicy::Error {
+                self.parser_overflow = Some(option);
+            }
+        }
+
+        data_bytes_added
+    }
+
+    /// Act on a sequence from the peer, logging rather than failing if
+    /// that goes wrong
+    fn handle_parsed_sequence(&mut self, sequence: &TelnetSequence) {
+        self.count_received(sequence);
+        // Don't fail the entire operation for telnet processing errors
+        if let Err(e) = self.handle_telnet_sequence(sequence)
+            && self.debug_logging
+        {
+            eprintln!(
+                "[TelnetStream] Error handling sequence {:?}: {}",
+                sequence, e
+            );
+        }
+    }
+
+    /// Add received data to the application buffer, returning how many
+    /// bytes were added
+    ///
+    /// Outside BINARY, the NUL of a CR NUL pair is dropped (RFC 854), and
+    /// under [`NewlineMode::Normalize`] a CR becomes `\n` and an LF after
+    /// it is dropped too. The runs between are copied in whole. Data is
+    /// converted to the application's character set first if
+    /// [`TelnetStream::set_transcoding`] asks for it.
+    fn push_data(&mut self, data: &[u8]) -> usize {
+        if let Some(transcoder) = self.incoming.as_mut() {
+            let mut converted = Vec::with_capacity(data.len());
+            transcoder.push(data, &mut converted);
+            return self.buffer_data(&converted);
+        }
+        self.buffer_data(data)
+    }
+
+    /// [`TelnetStream::push_data`] after any conversion
+    fn buffer_data(&mut self, data: &[u8]) -> usize {
+        let Some(&last) = data.last() else {
+            return 0;
+        };
+        let mut added = data.len();
+        if self.newline_mode == NewlineMode::Raw
+            || self
+                .negotiator
+                .is_enabled(Side::Remote, TelnetOption::BINARY)
+        {
+            self.data_buffer.extend_from_slice(data);
+        } else {
+            let normalize = self.newline_mode == NewlineMode::Normalize;
+            let mut run_start = 0;
+            let mut after_cr = self.read_after_cr;
+            for (i, &byte) in data.iter().enumerate() {
+                let padding = after_cr && (byte == 0 || (normalize && byte == b'\n'));
+                let ending = normalize && byte == b'\r';
+                if padding || ending {
+                    self.data_buffer.extend_from_slice(&data[run_start..i]);
+                    run_start = i + 1;
+                    if ending {
+                        self.data_buffer.extend_from_slice(b"\n");
+                    } else {
+                        added -= 1;
+                    }
+                }
+                after_cr = byte == b'\r';
+            }
+            self.data_buffer.extend_from_slice(&data[run_start..]);
+        }
+        self.read_after_cr = last == b'\r';
+        self.data_received += added as u64;
+        added
+    }
+
+    /// Handle a single telnet sequence and send appropriate responses
+    fn handle_telnet_sequence(&mut self, sequence: &TelnetSequence) -> io::Result<()> {
+        match sequence {
+            TelnetSequence::Negotiation { command, option } => {
+                if self.debug_logging {
+                    eprintln!("[TelnetStream] Processing: {:?} {:?}", command, option);
+                }
+
+                if *option == TelnetOption::TIMING_MARK {
+                    return self.handle_timing_mark(*command);
+                }
+
+                // A client never echoes for the server
+                if self.role == Role::Client
+                    && *command == TelnetCommand::DO
+                    && *option == TelnetOption::ECHO
+                {
+                    self.record_negotiation(Direction::Received, *command, *option);
+                    if !self.guard_refusal(*command, *option) {
+                        return Ok(());
+                    }
+                    self.send_negotiation(TelnetCommand::WONT, *option)?;
+                    return self.transport_flush();
+                }
+
+                let was_enabled = self.negotiator.is_enabled(Side::Remote, *option);
+                let was_local = self.negotiator.is_enabled(Side::Local, *option);
+                let result = match command {
+                    TelnetCommand::WILL => self.negotiator.handle_will(*option),
+                    TelnetCommand::WONT => self.negotiator.handle_wont(*option),
+                    TelnetCommand::DO => self.negotiator.handle_do(*option),
+                    TelnetCommand::DONT => self.negotiator.handle_dont(*option),
+                    _ => {
+                        if self.debug_logging {
+                            eprintln!(
+                                "[TelnetStream] Non-negotiation command in negotiation sequence: {:?}",
+                                command
+                            );
+                        }
+                        return Ok(());
+                    }
+                };
+
+                self.record_negotiation(Direction::Received, *command, *option);
+                self.push_option_events(*option, was_local, was_enabled);
+
+                // A refusal of a request the peer keeps repeating may be
+                // held back
+                let refusal = matches!(
+                    (command, &result.response),
+                    (
+                        TelnetCommand::WILL,
+                        Some(TelnetSequence::Negotiation {
+                            command: TelnetCommand::DONT,
+                            ..
+                        }),
+                    ) | (
+                        TelnetCommand::DO,
+                        Some(TelnetSequence::Negotiation {
+                            command: TelnetCommand::WONT,
+                            ..
+                        }),
+                    )
+                );
+                let answer = !refusal || self.guard_refusal(*command, *option);
+
+                // Send response if needed
+                if let Some(response) = result.response.filter(|_| answer) {
+                    if let TelnetSequence::Negotiation { command, option } = response {
+                        self.send_negotiation(command, option)?;
+
+                        if self.debug_logging {
+                            eprintln!("[TelnetStream] Sent response: {:?} {:?}", command, option);
+                        }
+                    } else {
+                        self.send_raw(&response.to_bytes())?;
+                    }
+                    self.transport_flush()?;
+                }
+
+                if *option == TelnetOption::START_TLS {
+                    self.handle_start_tls_negotiation(*command)?;
+                }
+
+                // The handler may have something to ask now the option is
+                // on, such as the client's terminal type
+                self.notify_option_handler(*option, was_local, was_enabled)?;
+
+                // A client entering LINEMODE needs to be told which mode
+                if self.role == Role::Server
+                    && *option == TelnetOption::LINEMODE
+                    && !was_enabled
+                    && self.negotiator.is_enabled(Side::Remote, *option)
+                    && let Some(mode) = self.linemode().and_then(|l| l.pending_mode())
+                {
+                    self.send_linemode_mode(mode)?;
+                }
+
+                // Turn markup on once the client takes up our MXP offer
+                #[cfg(feature = "options-mud")]
+                if self.role == Role::Server && *option == TelnetOption::MXP {
+                    let enabled = self.negotiator.is_enabled(Side::Local, *option);
+                    if enabled && !was_local {
+                        self.send_raw(&crate::options::MxpOption::start_sequence())?;
+                        self.transport_flush()?;
+                    }
+                    if let Some(mxp) = self.mxp_mut() {
+                        mxp.set_active(enabled);
+                    }
+                }
+
+                // Servers send sound cues and clients receive them
+                #[cfg(feature = "options-mud")]
+                if *option == TelnetOption::MSP {
+                    let side = match self.role {
+                        Role::Server => Side::Local,
+                        Role::Client => Side::Remote,
+                    };
+                    let enabled = self.negotiator.is_enabled(side, *option);
+                    if let Some(msp) = self.msp_mut() {
+                        msp.set_active(enabled);
+                    }
+                }
+
+                // A client echoes locally unless the server has taken it on
+                if self.role == Role::Client && *option == TelnetOption::ECHO {
+                    let was_remote = self.is_echo_remote();
+                    let remote = self.negotiator.is_enabled(Side::Remote, *option);
+                    if let Some(handler) = self.option_handlers.get_mut(option.to_byte())
+                        && let Some(echo_handler) =
+                            handler.as_any_mut().downcast_mut::<EchoOption>()
+                    {
+                        if remote {
+                            echo_handler.enable_remote_echo();
+                        } else {
+                            echo_handler.enable_local_echo();
+                        }
+                    }
+                    self.push_echo_event(was_remote);
+                }
+
+                // Once the server accepts our window size, send it
+                if self.role == Role::Client
+                    && *option == TelnetOption::NAWS
+                    && !was_local
+                    && self.negotiator.is_enabled(Side::Local, *option)
+                    && self.client_window_size.is_some()
+                {
+                    self.send_client_window_size()?;
+                }
+
+                // Log any negotiation errors
+                if let Some(error) = result.error
+                    && self.debug_logging
+                {
+                    eprintln!(
+                        "[TelnetStream] Negotiation error for {:?}: {}",
+                        option, error
+                    );
+                }
+
+                if self.debug_logging {
+                    eprintln!(
+                        "[TelnetStream] Option {:?} now enabled: Local={}, Remote={}",
+                        option,
+                        self.negotiator.is_enabled(Side::Local, *option),
+                        self.negotiator.is_enabled(Side::Remote, *option)
+                    );
+                }
+            }
+
+            TelnetSequence::SubNegotiation { option, data } => {
+                if self.debug_logging {
+                    eprintln!(
+                        "[TelnetStream] Sub-negotiation for {:?}: {} bytes",
+                        option,
+                        data.len()
+                    );
+                }
+
+                self.push_event(NegotiationEvent::Subnegotiation {
+                    option: *option,
+                    data: data.clone(),
+                });
+
+                // A server asking for our terminal type
+                if self.role == Role::Client
+                    && *option == TelnetOption::TERMINAL_TYPE
+                    && data.first() == Some(&(SubNegotiationCommand::Send as u8))
+                {
+                    return self.send_client_terminal_type();
+                }
+
+                // Route sub-negotiation to appropriate option handler
+                self.process_option_subnegotiation(*option, data)?;
+            }
+
+            TelnetSequence::Command(cmd) => {
+                if self.debug_logging {
+                    eprintln!("[TelnetStream] Simple command: {:?}", cmd);
+                }
+
+                match cmd {
+                    TelnetCommand::EOR | TelnetCommand::GA => {
+                        self.prompt_marks.push_back(PromptMark {
+                            command: *cmd,
+                            offset: self.data_received,
+                        });
+                    }
+                    TelnetCommand::IP
+                    | TelnetCommand::BRK
+                    | TelnetCommand::AO
+                    | TelnetCommand::EC
+                    | TelnetCommand::EL => {
+                        let event = ControlEvent {
+                            command: *cmd,
+                            offset: self.data_received,
+                        };
+                        self.signal_interrupt_due |= event.is_signal();
+                        self.signals.push_back(event);
+                    }
+                    TelnetCommand::DM => {
+                        // Synch: the typeahead before the mark goes
+                        if self.discard_on_synch {
+                            let discarded = self.data_buffer.len();
+                            self.data_buffer.clear();
+                            self.data_received -= discarded as u64;
+                            if self.debug_logging && discarded > 0 {
+                                eprintln!(
+                                    "[TelnetStream] Synch discarded {} bytes of input",
+                                    discarded
+                                );
+                            }
+                        }
+                        self.signals.push_back(ControlEvent {
+                            command: *cmd,
+                            offset: self.data_received,
+                        });
+                    }
+                    TelnetCommand::AYT => {
+                        if let Some(reply) = &self.ayt_reply {
+                            let reply = reply.clone().into_bytes();
+                            self.send_raw(&reply)?;
+                            self.transport_flush()?;
+                        }
+                    }
+                    // NOP is a keepalive
+                    _ => {}
+                }
+            }
+
+            TelnetSequence::EscapedData(byte) => {
+                if self.debug_logging {
+                    eprintln!("[TelnetStream] Escaped data byte: {}", byte);
+                }
+                // Escaped data is already added to the data buffer by the parser
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get access to the underlying transport for advanced operations
+    ///
+    /// This provides access to the wrapped transport for operations that
+    /// aren't available through the TelnetStream interface.
+    ///
+    /// **Warning**: Direct access bypasses telnet processing. Use with caution.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get mutable access to the underlying transport
+    ///
+    /// **Warning**: Direct access bypasses telnet processing. Use with caution.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Extract the underlying transport, consuming the TelnetStream
+    ///
+    /// This returns the wrapped transport and destroys the TelnetStream.
+    /// Any buffered data will be lost.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    // ========================================
+    // Phase 6: High-level Option API Methods
+    // ========================================
+
+    /// Request echo to be disabled for secure password input
+    ///
+    /// This negotiates with the client to disable local echoing,
+    /// which is essential for secure password input where characters
+    /// should not be displayed as they are typed.
+    ///
+    /// Returns true if echo was successfully disabled.
+    ///
+    /// In client mode this asks the server to echo instead (DO ECHO) and
+    /// returns whether it already does; [`TelnetStream::is_echo_remote`]
+    /// turns true once it agrees.
+    pub fn request_echo_off(&mut self) -> io::Result<bool> {
+        if self.role == Role::Client {
+            return self.request_server_echo(true);
+        }
+
+        // Offer WILL ECHO (server will handle echoing), unless already agreed
+        self.enable_option(Side::Local, TelnetOption::ECHO)?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested echo OFF (sent WILL ECHO)");
+        }
+
+        // Update our echo option handler state
+        let was_remote = self.is_echo_remote();
+        if let Some(handler) = self.option_handlers.get_mut(TelnetOption::ECHO.to_byte())
+            && let Some(echo_handler) = handler.as_any_mut().downcast_mut::<EchoOption>()
+        {
+            echo_handler.enable_remote_echo();
+        }
+        self.push_echo_event(was_remote);
+
+        // For now, assume success - proper negotiation tracking would require
+        // waiting for DO/DONT response, which is complex for synchronous API
+        Ok(true)
+    }
+
+    /// Prompt for a password or other secret and read it without showing it
+    ///
+    /// Says WILL ECHO and never echoes what is typed, then withdraws the
+    /// echo again, unless we were already echoing, and ends the prompt's
+    /// line. Returns `None` if the peer hung up. A resize doesn't cut the
+    /// read short; a signal does, with `Interrupted`, echo restored. Use
+    /// [`LineReader::read_secret`](crate::LineReader::read_secret) to echo
+    /// a mask character instead.
+    pub fn read_secret(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        crate::LineReader::new().read_secret(self, prompt)
+    }
+
+    /// Request echo to be enabled for normal input
+    ///
+    /// This restores normal local echoing after password input is complete.
+    /// Characters will be displayed as they are typed.
+    ///
+    /// Returns true if echo was successfully enabled.
+    ///
+    /// In client mode this asks the server to stop echoing (DONT ECHO)
+    /// and returns whether it already has.
+    pub fn request_echo_on(&mut self) -> io::Result<bool> {
+        if self.role == Role::Client {
+            return self.request_server_echo(false);
+        }
+
+        // Withdraw with WONT ECHO (server won't handle echoing)
+        self.disable_option(Side::Local, TelnetOption::ECHO)?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested echo ON (sent WONT ECHO)");
+        }
+
+        // Update our echo option handler state
+        let was_remote = self.is_echo_remote();
+        if let Some(handler) = self.option_handlers.get_mut(TelnetOption::ECHO.to_byte())
+            && let Some(echo_handler) = handler.as_any_mut().downcast_mut::<EchoOption>()
+        {
+            echo_handler.enable_local_echo();
+        }
+        self.push_echo_event(was_remote);
+
+        Ok(true)
+    }
+
+    /// Ask the server to start or stop echoing (client mode), returning
+    /// whether it already has
+    fn request_server_echo(&mut self, echo: bool) -> io::Result<bool> {
+        if echo {
+            self.enable_option(Side::Remote, TelnetOption::ECHO)?;
+        } else {
+            self.disable_option(Side::Remote, TelnetOption::ECHO)?;
+        }
+        Ok(self.is_echo_remote() == echo)
+    }
+
+    /// Request the client's terminal type information
+    ///
+    /// This initiates terminal type negotiation to determine client
+    /// capabilities for adaptive rendering (ANSI support, colors, etc.).
+    ///
+    /// Returns the terminal type string if already known, or None; to wait
+    /// for the answer use [`TelnetStream::negotiate_terminal_type`].
+    pub fn request_terminal_type(&mut self) -> io::Result<Option<String>> {
+        // First, request the client supports terminal type negotiation
+        self.enable_option(Side::Remote, TelnetOption::TERMINAL_TYPE)?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested terminal type negotiation (sent DO TERMINAL_TYPE)");
+        }
+
+        // Note: In a real implementation, we would need to:
+        // 1. Wait for WILL TERMINAL_TYPE response
+        // 2. Send IAC SB TERMINAL_TYPE SEND IAC SE
+        // 3. Wait for IAC SB TERMINAL_TYPE IS <type> IAC SE response
+        //
+        // For this synchronous API, we return what we have if available
+        if let Some(handler) = self.get_option_handler(TelnetOption::TERMINAL_TYPE)
+            && let Some(term_handler) = handler.as_any().downcast_ref::<TerminalTypeOption>()
+        {
+            return Ok(term_handler.terminal_type().map(|s| s.to_string()));
+        }
+
+        Ok(None)
+    }
+
+    /// Ask for the client's terminal type and read until it arrives
+    ///
+    /// Unlike [`TelnetStream::request_terminal_type`], this waits: until
+    /// the client has given all the names it will (see
+    /// [`TerminalTypeOption::start_cycle`]), refuses with WONT, or
+    /// `timeout` passes. A request already on its way isn't sent again,
+    /// and a name already known is returned straight away. Returns the
+    /// most specific name, or `None` if none came in time. Application
+    /// data read meanwhile is kept for later `read` calls.
+    pub fn negotiate_terminal_type(&mut self, timeout: Duration) -> io::Result<Option<String>> {
+        let option = TelnetOption::TERMINAL_TYPE;
+        let cycling = self
+            .terminal_type_handler()
+            .is_some_and(TerminalTypeOption::awaiting_reply);
+        if !cycling && let Some(name) = self.known_terminal_type() {
+            return Ok(Some(name));
+        }
+
+        let asked = match self.outstanding_request(option) {
+            Some(seq) => seq,
+            None if cycling => self.history.total_recorded(),
+            None => {
+                let seq = self.history.total_recorded();
+                self.request_terminal_type()?;
+                seq
+            }
+        };
+        self.read_until(timeout, |stream| {
+            let answered = stream.terminal_type_handler().is_some_and(|handler| {
+                handler.terminal_type().is_some() && !handler.awaiting_reply()
+            });
+            answered || stream.refused_since(option, asked)
+        })?;
+        Ok(self.known_terminal_type())
+    }
+
+    /// The terminal type handler, if one is registered
+    pub(crate) fn terminal_type_handler(&self) -> Option<&TerminalTypeOption> {
+        self.get_option_handler(TelnetOption::TERMINAL_TYPE)
+            .and_then(|handler| handler.as_any().downcast_ref::<TerminalTypeOption>())
+    }
+
+    /// The client's most specific terminal name, if it has given one
+    fn known_terminal_type(&self) -> Option<String> {
+        self.terminal_type_handler()
+            .and_then(|handler| handler.terminal_type())
+            .map(str::to_string)
+    }
+
+    /// Sequence number of our DO for `option` if the peer hasn't answered it
+    fn outstanding_request(&self, option: TelnetOption) -> Option<u64> {
+        self.history
+            .for_option(option)
+            .last()
+            .filter(|record| {
+                record.direction == Direction::Sent && record.command == TelnetCommand::DO
+            })
+            .map(|record| record.seq)
+    }
+
+    /// Whether the peer has sent WONT `option` since history entry `seq`
+    fn refused_since(&self, option: TelnetOption, seq: u64) -> bool {
+        self.history.for_option(option).any(|record| {
+            record.seq >= seq
+                && record.direction == Direction::Received
+                && record.command == TelnetCommand::WONT
+        })
+    }
+
+    /// Read until `done` holds, `timeout` passes or the connection closes,
+    /// returning whether `done` held
+    ///
+    /// Application data read meanwhile is kept for later `read` calls.
+    pub(crate) fn read_until(
+        &mut self,
+        timeout: Duration,
+        done: impl Fn(&Self) -> bool,
+    ) -> io::Result<bool> {
+        let previous_timeout = self.inner.read_timeout()?;
+        let deadline = Instant::now() + timeout;
+
+        let outcome = loop {
+            if done(self) {
+                break Ok(true);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Ok(false);
+            }
+
+            self.inner.set_read_timeout(Some(remaining))?;
+            let mut temp_buffer = [0; 4096];
+            match self.transport_read(&mut temp_buffer) {
+                Ok(0) => break Ok(false),
+                Ok(n) => {
+                    self.read_buffer.extend_from_slice(&temp_buffer[..n]);
+                    if let Err(e) = self.process_read_buffer() {
+                        break Err(e);
+                    }
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.inner.set_read_timeout(previous_timeout)?;
+        outcome
+    }
+
+    /// Answer a TERMINAL_TYPE SEND with our terminal type (client mode)
+    fn send_client_terminal_type(&mut self) -> io::Result<()> {
+        let mut data = vec![SubNegotiationCommand::Is as u8];
+        data.extend_from_slice(self.client_terminal_type.as_bytes());
+
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::TERMINAL_TYPE,
+            data,
+        };
+        self.send_raw(&sequence.to_bytes())?;
+        self.transport_flush()
+    }
+
+    /// Report our window size to the server (client mode)
+    fn send_client_window_size(&mut self) -> io::Result<()> {
+        let Some(size) = self.client_window_size else {
+            return Ok(());
+        };
+
+        // to_bytes doubles a size byte of 255
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::NAWS,
+            data: NawsOption::encode_window_size(size),
+        };
+        self.send_raw(&sequence.to_bytes())?;
+        self.transport_flush()
+    }
+
+    /// Read for up to `wait`, handling negotiation replies as they arrive
+    ///
+    /// Options requested at connect time (terminal type, window size) are
+    /// answered asynchronously; call this before relying on
+    /// [`TelnetStream::get_terminal_capabilities`]. Returns once `wait` has
+    /// passed or the connection closes. Application data read meanwhile is
+    /// kept for later `read` calls.
+    pub fn poll_negotiation(&mut self, wait: Duration) -> io::Result<()> {
+        let previous_timeout = self.inner.read_timeout()?;
+        let deadline = std::time::Instant::now() + wait;
+
+        let outcome = loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break Ok(());
+            }
+
+            self.inner.set_read_timeout(Some(remaining))?;
+            let mut temp_buffer = [0; 4096];
+            match self.transport_read(&mut temp_buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => {
+                    self.read_buffer.extend_from_slice(&temp_buffer[..n]);
+                    if let Err(e) = self.process_read_buffer() {
+                        break Err(e);
+                    }
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    break Ok(());
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.inner.set_read_timeout(previous_timeout)?;
+        outcome
+    }
+
+    /// Request the client's window size information
+    ///
+    /// This initiates NAWS negotiation to determine the client's terminal
+    /// dimensions for responsive layout rendering.
+    ///
+    /// Returns the window size if already known, or None; to wait for the
+    /// answer use [`TelnetStream::negotiate_window_size`].
+    pub fn request_window_size(&mut self) -> io::Result<Option<WindowSize>> {
+        // Request NAWS negotiation
+        self.enable_option(Side::Remote, TelnetOption::NAWS)?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested window size negotiation (sent DO NAWS)");
+        }
+
+        // Return current window size if available
+        if let Some(handler) = self.get_option_handler(TelnetOption::NAWS)
+            && let Some(naws_handler) = handler.as_any().downcast_ref::<NawsOption>()
+        {
+            return Ok(naws_handler.window_size());
+        }
+
+        Ok(None)
+    }
+
+    /// Ask for the client's window size and read until it arrives
+    ///
+    /// Unlike [`TelnetStream::request_window_size`], this waits: until the
+    /// client reports its size, refuses with WONT, or `timeout` passes. A
+    /// request already on its way isn't sent again, and a size already
+    /// known is returned straight away. Application data read meanwhile is
+    /// kept for later `read` calls.
+    pub fn negotiate_window_size(&mut self, timeout: Duration) -> io::Result<Option<WindowSize>> {
+        let option = TelnetOption::NAWS;
+        if let Some(size) = self.naws_size() {
+            return Ok(Some(size));
+        }
+
+        let asked = match self.outstanding_request(option) {
+            Some(seq) => seq,
+            None => {
+                let seq = self.history.total_recorded();
+                self.request_window_size()?;
+                seq
+            }
+        };
+        self.read_until(timeout, |stream| {
+            stream.naws_size().is_some() || stream.refused_since(option, asked)
+        })?;
+        Ok(self.naws_size())
+    }
+
+    /// Ask the client for its environment variables (sends DO NEW-ENVIRON)
+    ///
+    /// The variables arrive asynchronously once the client agrees; read
+    /// them with [`TelnetStream::environment`] after a
+    /// [`TelnetStream::poll_negotiation`].
+    pub fn request_environment(&mut self) -> io::Result<()> {
+        // Go through the negotiator so the client's WILL is taken as the
+        // answer rather than a fresh offer
+        let result = self
+            .negotiator
+            .request_enable(Side::Remote, TelnetOption::NEW_ENVIRON);
+        let Some(TelnetSequence::Negotiation { command, option }) = result.response else {
+            return Ok(());
+        };
+        self.send_negotiation(command, option)?;
+        self.transport_flush()?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested environment (sent DO NEW_ENVIRON)");
+        }
+        Ok(())
+    }
+
+    /// Environment variables the client sent, once it has answered
+    pub fn environment(&self) -> Option<&Environment> {
+        self.get_option_handler(TelnetOption::NEW_ENVIRON)
+            .and_then(|handler| handler.as_any().downcast_ref::<NewEnvironOption>())
+            .filter(|environ| environ.has_data())
+            .map(|environ| environ.environment())
+    }
+
+    /// Ask the client for its line speed (sends DO TERMINAL-SPEED)
+    ///
+    /// The speed arrives asynchronously once the client agrees; read it
+    /// with [`TelnetStream::terminal_speed`] after a
+    /// [`TelnetStream::poll_negotiation`]. Most clients on fast links
+    /// refuse, so no answer means no reason to slow down.
+    pub fn request_terminal_speed(&mut self) -> io::Result<()> {
+        let result = self
+            .negotiator
+            .request_enable(Side::Remote, TelnetOption::TERMINAL_SPEED);
+        let Some(TelnetSequence::Negotiation { command, option }) = result.response else {
+            return Ok(());
+        };
+        self.send_negotiation(command, option)?;
+        self.transport_flush()?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested terminal speed (sent DO TERMINAL_SPEED)");
+        }
+        Ok(())
+    }
+
+    /// The client's transmit and receive speeds, once it has answered
+    pub fn terminal_speed(&self) -> Option<TerminalSpeed> {
+        self.get_option_handler(TelnetOption::TERMINAL_SPEED)
+            .and_then(|handler| handler.as_any().downcast_ref::<TerminalSpeedOption>())
+            .and_then(|tspeed| tspeed.speed())
+    }
+
+    /// Invite the client to say where its user is (sends DO SEND-LOCATION)
+    ///
+    /// A client that agrees sends its location unasked, so it turns up with
+    /// later reads; see [`TelnetStream::location`].
+    pub fn request_location(&mut self) -> io::Result<()> {
+        let result = self
+            .negotiator
+            .request_enable(Side::Remote, TelnetOption::SEND_LOCATION);
+        let Some(TelnetSequence::Negotiation { command, option }) = result.response else {
+            return Ok(());
+        };
+        self.send_negotiation(command, option)?;
+        self.transport_flush()
+    }
+
+    /// The location the client sent, if any
+    ///
+    /// This is whatever the user typed into their client, with control
+    /// characters removed; show it, but don't rely on it.
+    pub fn location(&self) -> Option<&str> {
+        self.get_option_handler(TelnetOption::SEND_LOCATION)
+            .and_then(|handler| handler.as_any().downcast_ref::<SendLocationOption>())
+            .and_then(|sloc| sloc.location())
+    }
+
+    /// Ask the client to use LINEMODE with `mode` (`MODE_EDIT`, `MODE_TRAPSIG`, ...)
+    ///
+    /// Sends DO LINEMODE the first time; the mode follows once the client
+    /// agrees. Later calls change the mode of a client already in LINEMODE.
+    pub fn request_linemode(&mut self, mode: u8) -> io::Result<()> {
+        if self
+            .negotiator
+            .is_enabled(Side::Remote, TelnetOption::LINEMODE)
+        {
+            return self.send_linemode_mode(mode);
+        }
+
+        if let Some(linemode) = self.linemode_mut() {
+            linemode.request_mode(mode);
+        }
+        let result = self
+            .negotiator
+            .request_enable(Side::Remote, TelnetOption::LINEMODE);
+        let Some(TelnetSequence::Negotiation { command, option }) = result.response else {
+            return Ok(());
+        };
+        self.send_negotiation(command, option)?;
+        self.transport_flush()?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Requested LINEMODE (sent DO LINEMODE)");
+        }
+        Ok(())
+    }
+
+    /// Send the LINEMODE forward mask, if the client is in LINEMODE
+    ///
+    /// Returns false when LINEMODE isn't enabled and nothing was sent.
+    pub fn set_forward_mask(&mut self, mask: &[u8]) -> io::Result<bool> {
+        if !self
+            .negotiator
+            .is_enabled(Side::Remote, TelnetOption::LINEMODE)
+        {
+            return Ok(false);
+        }
+        let data = LinemodeOption::encode_forward_mask(mask)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::LINEMODE,
+            data,
+        };
+        self.send_raw(&sequence.to_bytes())?;
+        self.transport_flush()?;
+        Ok(true)
+    }
+
+    /// Whether the client is in LINEMODE and editing lines locally
+    pub fn is_local_line_editing(&self) -> bool {
+        self.negotiator
+            .is_enabled(Side::Remote, TelnetOption::LINEMODE)
+            && self.linemode().is_some_and(|l| l.is_local_editing())
+    }
+
+    /// The LINEMODE handler, for the mode and forward mask answer
+    pub fn linemode(&self) -> Option<&LinemodeOption> {
+        self.get_option_handler(TelnetOption::LINEMODE)
+            .and_then(|handler| handler.as_any().downcast_ref::<LinemodeOption>())
+    }
+
+    fn linemode_mut(&mut self) -> Option<&mut LinemodeOption> {
+        self.option_handlers
+            .get_mut(TelnetOption::LINEMODE.to_byte())
+            .and_then(|handler| handler.as_any_mut().downcast_mut::<LinemodeOption>())
+    }
+
+    fn send_linemode_mode(&mut self, mode: u8) -> io::Result<()> {
+        let Some(data) = self.linemode_mut().map(|l| l.request_mode(mode)) else {
+            return Ok(());
+        };
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::LINEMODE,
+            data,
+        };
+        self.send_raw(&sequence.to_bytes())?;
+        self.transport_flush()?;
+
+        if self.debug_logging {
+            eprintln!("[TelnetStream] Sent LINEMODE MODE {:#04x}", mode);
+        }
+        Ok(())
+    }
+
+    /// Offer MXP markup to the client (WILL MXP)
+    ///
+    /// When the client agrees, markup is switched on and
+    /// [`TelnetStream::mxp`] starts wrapping output in tags.
+    #[cfg(feature = "options-mud")]
+    pub fn request_mxp(&mut self) -> io::Result<()> {
+        let result = self
+            .negotiator
+            .request_enable(Side::Local, TelnetOption::MXP);
+        if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+            self.send_negotiation(command, option)?;
+        }
+        self.transport_flush()
+    }
+
+    /// The MXP handler, for wrapping output in tags (or passing it through
+    /// untouched when the client hasn't enabled MXP)
+    #[cfg(feature = "options-mud")]
+    pub fn mxp(&self) -> Option<&crate::options::MxpOption> {
+        self.get_option_handler(TelnetOption::MXP)
+            .and_then(|handler| handler.as_any().downcast_ref::<crate::options::MxpOption>())
+    }
+
+    #[cfg(feature = "options-mud")]
+    fn mxp_mut(&mut self) -> Option<&mut crate::options::MxpOption> {
+        self.option_handlers
+            .get_mut(TelnetOption::MXP.to_byte())
+            .and_then(|handler| {
+                handler
+                    .as_any_mut()
+                    .downcast_mut::<crate::options::MxpOption>()
+            })
+    }
+
+    /// Offer MUD Sound Protocol cues to the client (WILL MSP)
+    ///
+    /// Once the client agrees, [`TelnetStream::msp`] passes triggers
+    /// through instead of dropping them.
+    #[cfg(feature = "options-mud")]
+    pub fn request_msp(&mut self) -> io::Result<()> {
+        let result = self
+            .negotiator
+            .request_enable(Side::Local, TelnetOption::MSP);
+        if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+            self.send_negotiation(command, option)?;
+        }
+        self.transport_flush()
+    }
+
+    /// The MSP handler, for sending sound cues (or nothing, when the peer
+    /// hasn't enabled MSP)
+    #[cfg(feature = "options-mud")]
+    pub fn msp(&self) -> Option<&crate::options::MspOption> {
+        self.get_option_handler(TelnetOption::MSP)
+            .and_then(|handler| handler.as_any().downcast_ref::<crate::options::MspOption>())
+    }
+
+    #[cfg(feature = "options-mud")]
+    fn msp_mut(&mut self) -> Option<&mut crate::options::MspOption> {
+        self.option_handlers
+            .get_mut(TelnetOption::MSP.to_byte())
+            .and_then(|handler| {
+                handler
+                    .as_any_mut()
+                    .downcast_mut::<crate::options::MspOption>()
+            })
+    }
+
+    /// Offer to mark the end of each prompt (WILL END-OF-RECORD)
+    ///
+    /// Once the client agrees, [`TelnetStream::send_prompt`] ends prompts
+    /// with IAC EOR instead of IAC GA.
+    pub fn request_end_of_record(&mut self) -> io::Result<()> {
+        let result = self
+            .negotiator
+            .request_enable(Side::Local, TelnetOption::END_OF_RECORD);
+        if let Some(TelnetSequence::Negotiation { command, option }) = result.response {
+            self.send_negotiation(command, option)?;
+        }
+        self.transport_flush()
+    }
+
+    /// Write a prompt and mark where it ends
+    ///
+    /// The mark is IAC EOR when END-OF-RECORD is enabled on our side, else
+    /// IAC GA, unless we have agreed to suppress Go Ahead (RFC 858), in
+    /// which case the prompt goes out unmarked. Flushes either way.
+    pub fn send_prompt(&mut self, prompt: &[u8]) -> io::Result<()> {
+        self.write_all(prompt)?;
+
+        let mark = if self
+            .negotiator
+            .is_enabled(Side::Local, TelnetOption::END_OF_RECORD)
+        {
+            Some(TelnetCommand::EOR)
+        } else if !self
+            .negotiator
+            .is_enabled(Side::Local, TelnetOption::SUPPRESS_GO_AHEAD)
+        {
+            Some(TelnetCommand::GA)
+        } else {
+            None
+        };
+
+        self.owes_go_ahead = false;
+        if let Some(command) = mark {
+            self.end_turn(command)?;
+        }
+        self.transport_flush()
+    }
+
+    /// Mark the end of what we have written with `command`
+    fn end_turn(&mut self, command: TelnetCommand) -> io::Result<()> {
+        // Output ending in a bare CR still owes its NUL
+        if self.write_after_cr {
+            self.pending_output.push(0);
+            self.write_after_cr = false;
+        }
+        self.send_raw(&TelnetSequence::Command(command).to_bytes())
+    }
+
+    /// Take the EOR and GA marks received since the last call, oldest first
+    pub fn take_prompt_marks(&mut self) -> Vec<PromptMark> {
+        self.prompt_marks.drain(..).collect()
+    }
+
+    /// Set what to send back when the peer asks IAC AYT (Are You There)
+    ///
+    /// `None` leaves AYT unanswered. The reply is sent as is, so write
+    /// newlines as CR LF; being a `str` it can never contain IAC. Defaults
+    /// to `"\r\n[Yes]\r\n"`.
+    pub fn set_ayt_reply(&mut self, reply: Option<&str>) {
+        self.ayt_reply = reply.map(str::to_string);
+    }
+
+    /// Take the IP, BRK, AO, EC, EL and DM commands received since the
+    /// last call, oldest first
+    ///
+    /// These ask the application to interrupt what it is doing (IP),
+    /// get its attention (BRK), stop sending the output in progress (AO),
+    /// or erase the last character (EC) or line (EL) typed; what that
+    /// means is up to the application. Each comes with how much data came
+    /// before it, so erases can be applied to the right input. A DM says
+    /// the peer sent a Synch, and the typeahead before it has already been
+    /// dropped (see [`TelnetStream::set_discard_on_synch`]).
+    pub fn take_control_events(&mut self) -> Vec<ControlEvent> {
+        self.signal_interrupt_due = false;
+        self.signals.drain(..).collect()
+    }
+
+    /// The commands of [`TelnetStream::take_control_events`], without
+    /// their places in the data
+    pub fn take_signals(&mut self) -> Vec<TelnetCommand> {
+        self.take_control_events()
+            .into_iter()
+            .map(|event| event.command)
+            .collect()
+    }
+
+    /// Whether a DM from the peer drops the data before it that hasn't been
+    /// read yet, as RFC 854 Synch asks
+    ///
+    /// On by default. Off, the DM is still reported by
+    /// [`TelnetStream::take_control_events`] and the data kept.
+    pub fn set_discard_on_synch(&mut self, enabled: bool) {
+        self.discard_on_synch = enabled;
+    }
+
+    /// Have a `read` waiting for input return `ErrorKind::Interrupted` when
+    /// the peer sends IP, BRK or AO
+    ///
+    /// Each signal interrupts at most one `read`; collect it with
+    /// [`TelnetStream::take_control_events`]. Off by default.
+    pub fn set_interrupt_on_signal(&mut self, enabled: bool) {
+        self.interrupt_on_signal = enabled;
+    }
+
+    /// Read from the stream as lines and prompts, for trigger matching
+    ///
+    /// The iterator blocks on reads and takes over the prompt marks, so
+    /// don't mix it with [`take_prompt_marks`](Self::take_prompt_marks).
+    pub fn text_events(&mut self) -> TextEvents<'_, S> {
+        TextEvents::new(self)
+    }
+
+    /// Get current terminal information and capabilities
+    ///
+    /// This returns a comprehensive view of the client's terminal capabilities
+    /// based on negotiated options. Used for adaptive rendering decisions.
+    /// See [`crate::capabilities`] for where each part comes from.
+    pub fn get_terminal_capabilities(&self) -> TerminalCapabilities {
+        self.capabilities.clone()
+    }
+
+    /// Merge the handlers' view of the terminal again after any of them
+    /// changes; see [`crate::capabilities`]
+    fn recompute_capabilities(&mut self) {
+        let terminal = self.terminal_type_handler();
+        let sources = CapabilitySources {
+            terminal: terminal.and_then(TerminalTypeOption::terminal_info),
+            mtts: terminal.and_then(TerminalTypeOption::mtts_flags),
+            environment: self.environment(),
+            window: self.naws_size(),
+        };
+        self.capabilities = TerminalCapabilities::merge(&sources);
+        self.update_transcoders();
+        self.update_ansi_filter();
+    }
+
+    /// Get the current echo state
+    ///
+    /// Returns true if remote echo is active (server handles echoing),
+    /// false if local echo is active (client handles echoing).
+    pub fn is_echo_remote(&self) -> bool {
+        if let Some(handler) = self.get_option_handler(TelnetOption::ECHO)
+            && let Some(echo_handler) = handler.as_any().downcast_ref::<EchoOption>()
+        {
+            return echo_handler.is_remote_echo();
+        }
+        false // Default to local echo
+    }
+}
+
+/// Socket controls that only make sense over TCP
+impl TelnetStream<TcpStream> {
+    /// Connect to a telnet server and wrap the connection as a client
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self::client(TcpStream::connect(addr)?))
+    }
+
+    /// Get the peer address of the underlying TcpStream
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// Get the local address of the underlying TcpStream
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Set the write timeout for the underlying TcpStream
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_write_timeout(dur)
+    }
+
+    /// Get the write timeout of the underlying TcpStream
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.inner.write_timeout()
+    }
+
+    /// Set the TTL for the underlying TcpStream
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    /// Get the TTL of the underlying TcpStream
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.inner.ttl()
+    }
+
+    /// Enable or disable Nagle's algorithm for the underlying TcpStream
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    /// Check if Nagle's algorithm is disabled for the underlying TcpStream
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.inner.nodelay()
+    }
+
+    /// Try to clone the underlying TcpStream
+    pub fn try_clone(&self) -> io::Result<TcpStream> {
+        self.inner.try_clone()
+    }
+}
+
+/// Implement Read trait for transparent telnet operation
+///
+/// The Read implementation automatically processes incoming telnet commands
+/// and returns only clean application data to the caller.
+///
+/// ## Nonblocking Sockets and Timeouts
+///
+/// A read keeps reading the transport until application data turns up, so
+/// a burst of negotiation alone doesn't look like end of file. When the
+/// transport gives up instead (`WouldBlock` on a nonblocking socket,
+/// `TimedOut` or `WouldBlock` after a read timeout) the error is returned
+/// as is, and everything read so far stays buffered: commands already
+/// seen have been answered, and a sequence cut off part way is completed
+/// by a later read. That makes the stream safe to drive from a poll loop
+/// (mio and the like): read until `WouldBlock`, then wait for readiness.
+/// `Ok(0)` means the peer closed the connection.
+impl<S: Transport> Read for TelnetStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill_data_buffer()?;
+        Ok(self.read_buffered(buf))
+    }
+}
+
+/// Buffered reading of clean application data, for `read_line` and
+/// `lines()`
+///
+/// The buffer is the stream's own, so mixing these with `read` loses
+/// nothing. Lines end at LF as usual; for what telnet clients actually
+/// send at Enter, see [`TelnetStream::read_telnet_line`].
+impl<S: Transport> BufRead for TelnetStream<S> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.fill_data_buffer()?;
+        // Up to the wrap; the rest comes on the next call
+        Ok(self.data_buffer.as_slices().0)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.data_buffer.consume(amt);
+    }
+}
+
+/// Whether `buf` can be sent as it is: no IAC to double and, outside
+/// BINARY, no newline to put in NVT form or CR to finish
+fn is_wire_ready(buf: &[u8], binary: bool, after_cr: bool) -> bool {
+    if binary {
+        !buf.contains(&IAC)
+    } else {
+        !after_cr && !buf.iter().any(|&byte| matches!(byte, IAC | b'\r' | b'\n'))
+    }
+}
+
+/// Append the wire form of `bytes` to `out`: IAC doubled (RFC 854) and,
+/// outside BINARY, a bare CR padded with NUL and a bare LF sent as CR LF
+///
+/// `after_cr` says whether the byte before was a CR, and is left saying
+/// the same of the last byte.
+fn encode_data<'a>(
+    bytes: impl IntoIterator<Item = &'a u8>,
+    binary: bool,
+    after_cr: &mut bool,
+    out: &mut Vec<u8>,
+) {
+    for &byte in bytes {
+        if !binary {
+            if *after_cr && byte != b'\n' {
+                out.push(0);
+            }
+            if !*after_cr && byte == b'\n' {
+                out.push(b'\r');
+            }
+            *after_cr = byte == b'\r';
+        }
+        out.push(byte);
+        if byte == IAC {
+            out.push(IAC);
+        }
+    }
+}
+
+/// Find IAC followed by a command byte in data about to be written,
+/// reading `bufs` as one buffer
+fn find_bare_command(bufs: &[IoSlice<'_>]) -> Option<TelnetCommand> {
+    let mut bytes = bufs.iter().flat_map(|buf| buf.iter()).peekable();
+    while let Some(&byte) = bytes.next() {
+        if byte == IAC
+            && let Some(&&next) = bytes.peek()
+            && let Some(command) = TelnetCommand::from_byte(next)
+        {
+            return Some(command);
+        }
+    }
+    None
+}
+
+/// How many bytes [`encode_data`] turns `byte` into
+fn encoded_len(byte: u8, binary: bool, after_cr: bool) -> usize {
+    let newline = !binary
+        && if after_cr {
+            byte != b'\n'
+        } else {
+            byte == b'\n'
+        };
+    1 + usize::from(newline) + usize::from(byte == IAC)
+}
+
+/// A stream's option handlers and RFC 1143 state, as the audit sees them
+struct AuditedOptions<'a> {
+    handlers: &'a HandlerTable,
+    negotiator: &'a OptionNegotiator,
+}
+
+impl OptionRegistry for AuditedOptions<'_> {
+    fn handles(&self, option: TelnetOption) -> bool {
+        // BINARY, SUPPRESS-GO-AHEAD, END-OF-RECORD and TIMING-MARK are
+        // handled by the stream itself
+        BUILT_IN_REQUESTS.contains(&option)
+            || option == TelnetOption::TIMING_MARK
+            || self.handlers.get(option.to_byte()).is_some()
+    }
+
+    fn is_negotiated(&self, option: TelnetOption) -> bool {
+        [Side::Local, Side::Remote]
+            .into_iter()
+            .any(|side| self.negotiator.get_state(side, option) != OptionState::No)
+    }
+}
+
+/// Implement Write trait for transparent telnet operation
+///
+/// The Write implementation passes application data through to the underlying
+/// transport while ensuring proper telnet protocol handling for any embedded
+/// IAC bytes (RFC 854 escaping).
+///
+/// ## Partial Writes
+///
+/// `write` makes one write to the transport and returns how many bytes of
+/// `buf` went out, which may be fewer than `buf.len()`; call it again with
+/// the rest (or use `write_all`). A byte counts as written once its
+/// encoding is committed: if the transport takes only the first half of an
+/// escaped IAC, the second half is queued and the byte is counted. Queued
+/// output, including protocol replies generated while reading, is always
+/// sent before new data, and nothing from `buf` is taken until it has
+/// gone. An error therefore means none of `buf` was written, so retrying
+/// the same buffer never duplicates or tears output. `flush` sends
+/// anything still queued, ending the turn with IAC GA if data was written
+/// and Go Ahead isn't suppressed; see [`TelnetStream::pending_output_len`].
+///
+/// `write_vectored` treats its slices as one buffer, encoded for a single
+/// transport write. Under a buffering [`FlushPolicy`] writes are taken
+/// whole into the queue instead; see [`TelnetStream::set_flush_policy`].
+impl<S: Transport> Write for TelnetStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        if self.write_check == WriteCheck::Strict
+            && let Some(command) = find_bare_command(bufs)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "data holds IAC {:?}; send sequences with write_raw",
+                    command
+                ),
+            ));
+        }
+        let written = if self.outgoing.is_some() || self.ansi_filter.is_some() {
+            self.write_converted(bufs)?
+        } else {
+            match self.flush_policy {
+                FlushPolicy::Immediate => {
+                    // Older output goes first; until it's out none of `bufs` is taken
+                    self.send_pending()?;
+                    self.write_now(bufs)?
+                }
+                FlushPolicy::OnNewline | FlushPolicy::Manual => self.write_buffered(bufs)?,
+            }
+        };
+        self.stats.data_sent += written as u64;
+        self.report_stats_if_due();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Taken first: once queued, the GA goes out with any retry
+        if std::mem::take(&mut self.owes_go_ahead)
+            && !self
+                .negotiator
+                .is_enabled(Side::Local, TelnetOption::SUPPRESS_GO_AHEAD)
+        {
+            self.end_turn(TelnetCommand::GA)?;
+        }
+        self.transport_flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::TextEvent;
+    use crate::protocol::{TelnetCommand, TelnetOption};
+    use crate::testing::{MockStream, SilentHandler};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Stand-in for a TLS session: XORs every byte so plaintext leaks show up
+    struct XorTransport {
+        socket: TcpStream,
+        buffered: VecDeque<u8>,
+    }
+
+    impl Read for XorTransport {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = if self.buffered.is_empty() {
+                self.socket.read(buf)?
+            } else {
+                let n = buf.len().min(self.buffered.len());
+                for (slot, byte) in buf.iter_mut().zip(self.buffered.drain(..n)) {
+                    *slot = byte;
+                }
+                n
+            };
+            buf[..n].iter_mut().for_each(|b| *b ^= 0x5A);
+            Ok(n)
+        }
+    }
+
+    impl Write for XorTransport {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let scrambled: Vec<u8> = buf.iter().map(|b| b ^ 0x5A).collect();
+            self.socket.write_all(&scrambled)?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.socket.flush()
+        }
+    }
+
+    /// Transport that takes at most the scripted number of bytes per write
+    /// (`None` means `WouldBlock`), then everything once the script runs out
+    struct ShortWriteTransport {
+        script: VecDeque<Option<usize>>,
+        written: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl Read for ShortWriteTransport {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    impl Write for ShortWriteTransport {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = match self.script.pop_front() {
+                Some(Some(limit)) => buf.len().min(limit),
+                Some(None) => return Err(io::ErrorKind::WouldBlock.into()),
+                None => buf.len(),
+            };
+            self.written.lock().unwrap().extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A server stream whose writes go to a [`ShortWriteTransport`]
+    fn short_write_stream(
+        script: &[Option<usize>],
+    ) -> (TelnetStream, std::sync::Arc<std::sync::Mutex<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (socket, _) = listener.accept().unwrap();
+
+        let written = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut stream = TelnetStream::new(socket);
+        stream.tls = Some(Box::new(ShortWriteTransport {
+            script: script.iter().copied().collect(),
+            written: std::sync::Arc::clone(&written),
+        }));
+        (stream, written)
+    }
+
+    #[test]
+    fn test_short_write_reports_progress() {
+        let (mut stream, written) = short_write_stream(&[Some(3)]);
+
+        assert_eq!(stream.write(b"hello").unwrap(), 3);
+        assert_eq!(stream.pending_output_len(), 0);
+        assert_eq!(stream.write(b"lo").unwrap(), 2);
+        assert_eq!(written.lock().unwrap().as_slice(), b"hello");
+    }
+
+    #[test]
+    fn test_short_write_never_tears_iac() {
+        // The transport takes "a" and the first IAC of the escaped pair
+        let (mut stream, written) = short_write_stream(&[Some(2), None]);
+
+        assert_eq!(stream.write(&[b'a', 255, b'b']).unwrap(), 2);
+        assert_eq!(stream.pending_output_len(), 1);
+
+        // The queued IAC must go before anything else, so this fails whole
+        let error = stream.write(b"b").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(stream.pending_output_len(), 1);
+
+        stream.write_all(b"b").unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), &[b'a', 255, 255, b'b']);
+    }
+
+    #[test]
+    fn test_vectored_write_maps_progress_across_slices() {
+        // The transport takes "a" and the first IAC of the escaped pair
+        let (mut stream, written) = short_write_stream(&[Some(2)]);
+
+        let bufs = [IoSlice::new(&[b'a', 255]), IoSlice::new(b"b")];
+        assert_eq!(stream.write_vectored(&bufs).unwrap(), 2);
+        assert_eq!(stream.pending_output_len(), 1);
+
+        stream.write_all(b"b").unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), &[b'a', 255, 255, b'b']);
+    }
+
+    #[test]
+    fn test_flush_policies_hold_writes_back() {
+        let (mut stream, written) = short_write_stream(&[]);
+        stream.set_flush_policy(FlushPolicy::Manual);
+
+        assert_eq!(stream.write(b"menu").unwrap(), 4);
+        let bufs = [IoSlice::new(b"\n"), IoSlice::new(&[255])];
+        assert_eq!(stream.write_vectored(&bufs).unwrap(), 2);
+        assert!(written.lock().unwrap().is_empty());
+        assert_eq!(stream.pending_output_len(), 8);
+
+        stream.flush().unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            b"menu\r\n\xff\xff\xff\xf9" // ...then IAC GA
+        );
+
+        // Per line: held until a write ends one, sent without a GA
+        written.lock().unwrap().clear();
+        stream.set_flush_policy(FlushPolicy::OnNewline);
+        stream.write_all(b"Name: ").unwrap();
+        assert!(written.lock().unwrap().is_empty());
+        stream.write_all(b"joe\n").unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"Name: joe\r\n");
+        assert_eq!(stream.pending_output_len(), 0);
+
+        // A write that would overflow the buffer sends what is held first
+        written.lock().unwrap().clear();
+        stream.write_all(b"x").unwrap();
+        stream
+            .write_all(&vec![b'y'; WRITE_BUFFER_CAPACITY])
+            .unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"x");
+        assert_eq!(stream.pending_output_len(), WRITE_BUFFER_CAPACITY);
+    }
+
+    #[test]
+    fn test_interrupted_protocol_write_is_resumed() {
+        // DO NAWS gets one byte out before the transport stalls
+        let (mut stream, written) = short_write_stream(&[Some(1), None]);
+
+        let error = stream.request_window_size().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(stream.pending_output_len(), 2);
+
+        stream.write_all(b"hi").unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[255, 253, 31, b'h', b'i']
+        );
+        assert_eq!(stream.pending_output_len(), 0);
+    }
+
+    #[test]
+    fn test_nvt_newlines_on_write() {
+        let (mut stream, written) = short_write_stream(&[]);
+
+        stream.write_all(b"one\ntwo\r\n").unwrap();
+        // A CR at the end of a write waits to see what follows
+        stream.write_all(b"three\r").unwrap();
+        stream.write_all(b"\n\rover").unwrap();
+
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            b"one\r\ntwo\r\nthree\r\n\r\0over"
+        );
+    }
+
+    #[test]
+    fn test_newline_modes() {
+        // Line endings split across reads still come out as one LF each
+        let peer = MockStream::new();
+        for chunk in [&b"a\r\nb\r"[..], b"\0c\r", b"\nd\ne\r"] {
+            peer.push_input(chunk);
+        }
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.set_newline_mode(NewlineMode::Normalize);
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "a\nb\nc\nd\ne\n");
+
+        stream.write_all(b"out\n").unwrap();
+        assert_eq!(peer.take_written(), b"out\r\n");
+
+        // Raw passes newlines through both ways
+        let peer = MockStream::with_input(b"p\r\0q\r\n");
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.set_newline_mode(NewlineMode::Raw);
+        let mut read = Vec::new();
+        stream.read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"p\r\0q\r\n");
+
+        stream.write_all(b"x\ny\r").unwrap();
+        assert_eq!(peer.written(), b"x\ny\r");
+    }
+
+    #[test]
+    fn test_write_check() {
+        let (mut stream, written) = short_write_stream(&[]);
+        assert_eq!(stream.write_check(), WriteCheck::Off);
+
+        // File data that happens to hold IAC SB goes out escaped
+        stream.write_all(&[b'a', 255, 250, 24]).unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[b'a', 255, 255, 250, 24]
+        );
+
+        // Strict refuses what looks like a sequence, across slices,
+        // leaving nothing queued
+        written.lock().unwrap().clear();
+        stream.set_write_check(WriteCheck::Strict);
+        let bufs = [
+            IoSlice::new(b"ok "),
+            IoSlice::new(&[255]),
+            IoSlice::new(&[249]),
+        ];
+        let error = stream.write_vectored(&bufs).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(stream.pending_output_len(), 0);
+        stream.write_all(&[b'x', 255, b'y']).unwrap();
+
+        // The bypass sends a sequence as it is, but not a torn one
+        let error = stream.write_raw(&[255, 250, 24, 0]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        stream.write_raw(&[255, 249]).unwrap();
+        stream.flush().unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[b'x', 255, 255, b'y', 255, 249, 255, 249]
+        );
+    }
+
+    #[test]
+    fn test_short_write_inside_nvt_newline() {
+        // Takes "a" and CR; the NUL padding the CR goes out with "b"
+        let (mut stream, written) = short_write_stream(&[Some(2), Some(1)]);
+
+        assert_eq!(stream.write(b"a\rb").unwrap(), 2);
+        // Takes the NUL but not "b", which is queued
+        assert_eq!(stream.write(b"bc").unwrap(), 1);
+        assert_eq!(stream.pending_output_len(), 1);
+
+        stream.write_all(b"c").unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"a\r\0bc");
+    }
+
+    #[test]
+    fn test_binary_write_is_untouched() {
+        let (mut stream, written) = short_write_stream(&[]);
+        stream.negotiator.handle_do(TelnetOption::BINARY);
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::BINARY));
+        written.lock().unwrap().clear();
+
+        stream.write_all(&[b'\r', 0x80, b'\n', 0, 255]).unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[b'\r', 0x80, b'\n', 0, 255, 255]
+        );
+    }
+
+    #[test]
+    fn test_nvt_and_binary_reads() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            socket.write_all(b"a\r\0b\r\n").unwrap();
+
+            let mut ready = [0u8; 1];
+            socket.read_exact(&mut ready).unwrap();
+            // IAC WILL BINARY, then the same bytes again
+            socket.write_all(&[255, 251, 0]).unwrap();
+            socket.write_all(b"a\r\0b").unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+
+        let mut nvt = [0u8; 5];
+        stream.read_exact(&mut nvt).unwrap();
+        assert_eq!(&nvt, b"a\rb\r\n");
+
+        stream.write_all(b"!").unwrap();
+        let mut binary = [0u8; 4];
+        stream.read_exact(&mut binary).unwrap();
+        assert_eq!(&binary, b"a\r\0b");
+        assert!(stream.is_option_enabled(Side::Remote, TelnetOption::BINARY));
+
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_prompt_falls_back_to_go_ahead() {
+        let (mut stream, written) = short_write_stream(&[]);
+
+        // A bare CR keeps its NUL ahead of the mark
+        stream.send_prompt(b"Name?\r").unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"Name?\r\0\xff\xf9");
+    }
+
+    #[test]
+    fn test_flush_ends_turn_with_go_ahead() {
+        let (mut stream, written) = short_write_stream(&[]);
+
+        stream.write_all(b"Ready\r").unwrap();
+        stream.flush().unwrap();
+        // Nothing new since, so no second GA
+        stream.flush().unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"Ready\r\0\xff\xf9");
+
+        // A prompt's own mark ends the turn
+        written.lock().unwrap().clear();
+        stream.send_prompt(b"> ").unwrap();
+        stream.flush().unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"> \xff\xf9");
+    }
+
+    #[test]
+    fn test_go_ahead_stops_once_suppressed() {
+        let (mut stream, written) = short_write_stream(&[]);
+        stream.request_suppress_go_ahead().unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[255, 251, 3, 255, 253, 3] // IAC WILL SGA, IAC DO SGA
+        );
+        assert!(!stream.is_full_duplex());
+
+        // Our side alone is enough to stop sending GA
+        stream.negotiator.handle_do(TelnetOption::SUPPRESS_GO_AHEAD);
+        written.lock().unwrap().clear();
+        stream.write_all(b"hi").unwrap();
+        stream.flush().unwrap();
+        assert_eq!(written.lock().unwrap().as_slice(), b"hi");
+
+        stream
+            .negotiator
+            .handle_will(TelnetOption::SUPPRESS_GO_AHEAD);
+        assert!(stream.is_full_duplex());
+    }
+
+    #[test]
+    fn test_timing_mark_resolves_round_trip() {
+        let (mut stream, written) = short_write_stream(&[]);
+        assert_eq!(stream.round_trip_time(), None);
+
+        stream.send_timing_mark().unwrap();
+        stream.send_timing_mark().unwrap();
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[255, 253, 6, 255, 253, 6] // IAC DO TIMING-MARK, twice
+        );
+        assert_eq!(stream.pending_timing_marks(), 2);
+
+        // WONT counts as an answer too
+        for command in [TelnetCommand::WILL, TelnetCommand::WONT] {
+            stream
+                .handle_telnet_sequence(&TelnetSequence::Negotiation {
+                    command,
+                    option: TelnetOption::TIMING_MARK,
+                })
+                .unwrap();
+        }
+        assert_eq!(stream.pending_timing_marks(), 0);
+        assert!(stream.round_trip_time().is_some());
+        assert!(!stream.is_option_enabled(Side::Remote, TelnetOption::TIMING_MARK));
+        // Nothing is sent back, so the peer can't be drawn into a loop
+        assert_eq!(written.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_timing_mark_from_peer_is_always_answered() {
+        let (mut stream, written) = short_write_stream(&[]);
+        for _ in 0..2 {
+            stream
+                .handle_telnet_sequence(&TelnetSequence::Negotiation {
+                    command: TelnetCommand::DO,
+                    option: TelnetOption::TIMING_MARK,
+                })
+                .unwrap();
+        }
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            &[255, 251, 6, 255, 251, 6] // IAC WILL TIMING-MARK, twice
+        );
+        assert!(!stream.is_option_enabled(Side::Local, TelnetOption::TIMING_MARK));
+    }
+
+    #[test]
+    fn test_measure_round_trip_keeps_data() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let mut mark = [0; 3];
+            socket.read_exact(&mut mark).unwrap();
+            assert_eq!(mark, [255, 253, 6]);
+            socket.write_all(b"typed ahead").unwrap();
+            socket.write_all(&[255, 251, 6]).unwrap(); // IAC WILL TIMING-MARK
+            socket
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        let round_trip = stream.measure_round_trip(Duration::from_secs(5)).unwrap();
+        assert!(round_trip.is_some());
+        assert_eq!(stream.round_trip_time(), round_trip);
+
+        let mut buffer = [0; 32];
+        let n = stream.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..n], b"typed ahead");
+        drop(client.join().unwrap());
+    }
+
+    #[test]
+    fn test_measure_round_trip_times_out() {
+        let (mut stream, _written) = short_write_stream(&[]);
+        let round_trip = stream
+            .measure_round_trip(Duration::from_millis(20))
+            .unwrap();
+        assert_eq!(round_trip, None);
+        assert_eq!(stream.pending_timing_marks(), 1);
+    }
+
+    #[test]
+    fn test_text_events_split_lines_and_prompts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            socket.write_all(b"hp 10\r\n> ").unwrap();
+            socket.write_all(&[255, 249]).unwrap(); // IAC GA
+            socket.write_all(b"look\r").unwrap();
+            socket.write_all(b"\nbye").unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        client.join().unwrap();
+
+        let events: Vec<TextEvent> = stream.text_events().map(|e| e.unwrap()).collect();
+        assert_eq!(
+            events,
+            vec![
+                TextEvent::Line(b"hp 10".to_vec()),
+                TextEvent::Prompt(b"> ".to_vec()),
+                TextEvent::Line(b"look".to_vec()),
+                TextEvent::Line(b"bye".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simple_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            // NOP, AYT, IP, BRK, AO, then some data
+            socket
+                .write_all(&[255, 241, 255, 246, 255, 244, 255, 243, 255, 245, b'x'])
+                .unwrap();
+            let mut reply = [0u8; 9];
+            socket.read_exact(&mut reply).unwrap();
+            reply
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+
+        let mut buf = [0u8; 1];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"x");
+        assert_eq!(&client.join().unwrap(), b"\r\n[Yes]\r\n");
+        assert_eq!(
+            stream.take_signals(),
+            vec![TelnetCommand::IP, TelnetCommand::BRK, TelnetCommand::AO]
+        );
+        assert!(stream.take_signals().is_empty());
+    }
+
+    #[test]
+    fn test_synch_discards_typeahead() {
+        // Typeahead, IAC IP, IAC DM, then what comes after the Synch
+        let input = b"dir\r\nquit\r\n\xff\xf4\xff\xf2menu\r\n";
+        let mut stream = TelnetStream::new(MockStream::with_input(input));
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "menu\r\n");
+        assert_eq!(
+            stream.take_signals(),
+            [TelnetCommand::IP, TelnetCommand::DM]
+        );
+
+        // Data already read can't be taken back, and off keeps the rest
+        let peer = MockStream::new();
+        peer.push_input(b"read ");
+        peer.push_input(b"kept\xff\xf2!");
+        let mut stream = TelnetStream::new(peer);
+        stream.set_discard_on_synch(false);
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "read kept!");
+        assert_eq!(
+            stream.take_control_events(),
+            [ControlEvent {
+                command: TelnetCommand::DM,
+                offset: 9
+            }]
+        );
+    }
+
+    #[test]
+    fn test_signal_interrupts_waiting_read() {
+        // "a", IAC EL, "b", IAC IP
+        let peer = MockStream::with_input(b"a\xff\xf8b\xff\xf4");
+        peer.hold_open(true);
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.set_interrupt_on_signal(true);
+
+        // The data comes first; the read after it is the one interrupted
+        let mut buf = [0u8; 8];
+        assert_eq!(stream.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"ab");
+        let error = stream.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+
+        let events = stream.take_control_events();
+        assert_eq!(
+            events,
+            [
+                ControlEvent {
+                    command: TelnetCommand::EL,
+                    offset: 1
+                },
+                ControlEvent {
+                    command: TelnetCommand::IP,
+                    offset: 2
+                },
+            ]
+        );
+        assert!(!events[0].is_signal() && events[1].is_signal());
+
+        // Spent: the next read waits for input as usual
+        let error = stream.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn test_ayt_reply_can_be_turned_off() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            socket.write_all(&[255, 246, b'x']).unwrap();
+            let mut echoed = [0u8; 1];
+            socket.read_exact(&mut echoed).unwrap();
+            echoed
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.set_ayt_reply(None);
+
+        let mut buf = [0u8; 1];
+        stream.read_exact(&mut buf).unwrap();
+        // Nothing went out for the AYT, so the first byte back is ours
+        stream.write_all(b"y").unwrap();
+        stream.flush().unwrap();
+        assert_eq!(&client.join().unwrap(), b"y");
+    }
+
+    #[test]
+    fn test_prompts_with_end_of_record() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut offer = [0u8; 3];
+            socket.read_exact(&mut offer).unwrap();
+            assert_eq!(offer, [255, 251, 25]); // IAC WILL END-OF-RECORD
+            socket.write_all(&[255, 253, 25]).unwrap(); // IAC DO END-OF-RECORD
+
+            let mut prompt = [0u8; 4];
+            socket.read_exact(&mut prompt).unwrap();
+            assert_eq!(prompt, [b'>', b' ', 255, 239]);
+
+            // Two marked records of our own, then some data
+            socket.write_all(b"hp 10").unwrap();
+            socket.write_all(&[255, 239]).unwrap();
+            socket.write_all(b"> ").unwrap();
+            socket.write_all(&[255, 249]).unwrap();
+            socket.write_all(b"!").unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.request_end_of_record().unwrap();
+        while !stream.is_option_enabled(Side::Local, TelnetOption::END_OF_RECORD) {
+            stream.poll_negotiation(Duration::from_millis(50)).unwrap();
+        }
+        stream.send_prompt(b"> ").unwrap();
+
+        let mut data = [0u8; 8];
+        stream.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hp 10> !");
+        assert_eq!(
+            stream.take_prompt_marks(),
+            vec![
+                PromptMark {
+                    command: TelnetCommand::EOR,
+                    offset: 5,
+                },
+                PromptMark {
+                    command: TelnetCommand::GA,
+                    offset: 7,
+                },
+            ]
+        );
+        assert!(stream.take_prompt_marks().is_empty());
+
+        client.join().unwrap();
+    }
+
+    #[cfg(feature = "options-mud")]
+    #[test]
+    fn test_mxp_switched_on_when_accepted() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut offer = [0u8; 3];
+            socket.read_exact(&mut offer).unwrap();
+            assert_eq!(offer, [255, 251, 91]); // IAC WILL MXP
+            socket.write_all(&[255, 253, 91]).unwrap(); // IAC DO MXP
+
+            let mut start = [0u8; 9];
+            socket.read_exact(&mut start).unwrap();
+            assert_eq!(&start, b"\xff\xfa\x5b\xff\xf0\x1b[7z");
+            socket.write_all(b"!").unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        assert_eq!(stream.mxp().unwrap().tag("b", &[], "<hi>"), "<hi>");
+
+        stream.request_mxp().unwrap();
+        let mut bang = [0u8; 1];
+        stream.read_exact(&mut bang).unwrap();
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::MXP));
+        assert_eq!(
+            stream.mxp().unwrap().tag("b", &[], "<hi>"),
+            "\x1b[1z<b>&lt;hi&gt;</b>\x1b[2z"
+        );
+
+        client.join().unwrap();
+    }
+
+    #[cfg(feature = "options-mud")]
+    #[test]
+    fn test_msp_cues_once_accepted() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut offer = [0u8; 3];
+            socket.read_exact(&mut offer).unwrap();
+            assert_eq!(offer, [255, 251, 90]); // IAC WILL MSP
+            socket.write_all(&[255, 253, 90]).unwrap(); // IAC DO MSP
+            socket.write_all(b"!").unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        let bell = crate::options::MspTrigger::sound("bell.wav");
+        assert_eq!(stream.msp().unwrap().cue(&bell), "");
+
+        stream.request_msp().unwrap();
+        let mut bang = [0u8; 1];
+        stream.read_exact(&mut bang).unwrap();
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::MSP));
+        assert_eq!(stream.msp().unwrap().cue(&bell), "!!SOUND(bell.wav)");
+
+        client.join().unwrap();
+    }
+
+    struct XorUpgrader;
+
+    impl TlsUpgrader for XorUpgrader {
+        fn upgrade(
+            &mut self,
+            socket: TcpStream,
+            buffered: Vec<u8>,
+        ) -> io::Result<Box<dyn TlsTransport>> {
+            Ok(Box::new(XorTransport {
+                socket,
+                buffered: buffered.into(),
+            }))
+        }
+    }
+
+    fn xor(bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().map(|b| b ^ 0x5A).collect()
+    }
+
+    #[test]
+    fn test_start_tls_upgrade() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let mut buf = [0u8; 6];
+
+            socket.read_exact(&mut buf[..3]).unwrap();
+            assert_eq!(buf[..3], [255, 253, 46]); // IAC DO START_TLS
+            socket.write_all(&[255, 251, 46]).unwrap(); // IAC WILL START_TLS
+
+            socket.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, [255, 250, 46, 1, 255, 240]); // IAC SB START_TLS FOLLOWS IAC SE
+
+            // FOLLOWS and the first "handshake" bytes arrive in one segment
+            let mut reply = vec![255, 250, 46, FOLLOWS, 255, 240];
+            reply.extend(xor(b"hello"));
+            socket.write_all(&reply).unwrap();
+
+            let mut welcome = [0u8; 7];
+            socket.read_exact(&mut welcome).unwrap();
+            assert_eq!(welcome.to_vec(), xor(b"welcome"));
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.offer_start_tls(Box::new(XorUpgrader)).unwrap();
+        assert_eq!(stream.start_tls_state(), StartTlsState::Offered);
+
+        assert!(stream.await_start_tls(Duration::from_secs(5)).unwrap());
+        assert!(stream.is_tls_active());
+
+        stream.write_all(b"welcome").unwrap();
+        stream.flush().unwrap();
+
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_start_tls_declined() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let mut buf = [0u8; 3];
+            socket.read_exact(&mut buf).unwrap();
+            socket.write_all(&[255, 252, 46]).unwrap(); // IAC WONT START_TLS
+
+            let mut plain = [0u8; 5];
+            socket.read_exact(&mut plain).unwrap();
+            assert_eq!(&plain, b"plain");
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.offer_start_tls(Box::new(XorUpgrader)).unwrap();
+
+        assert!(!stream.await_start_tls(Duration::from_secs(5)).unwrap());
+        assert_eq!(stream.start_tls_state(), StartTlsState::Declined);
+        assert!(!stream.is_tls_active());
+
+        stream.write_all(b"plain").unwrap();
+        stream.flush().unwrap();
+
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_answers_server_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            // IAC DO TTYPE, IAC DO NAWS, IAC WILL ECHO, IAC DO ECHO
+            socket
+                .write_all(&[255, 253, 24, 255, 253, 31, 255, 251, 1, 255, 253, 1])
+                .unwrap();
+
+            let mut buf = [0u8; 21];
+            socket.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..3], [255, 251, 24]); // IAC WILL TTYPE
+            assert_eq!(buf[3..6], [255, 251, 31]); // IAC WILL NAWS
+            assert_eq!(buf[6..15], [255, 250, 31, 0, 132, 0, 25, 255, 240]); // 132x25
+            assert_eq!(buf[15..18], [255, 253, 1]); // IAC DO ECHO
+            assert_eq!(buf[18..], [255, 252, 1]); // IAC WONT ECHO
+
+            // IAC SB TTYPE SEND IAC SE, then some text
+            socket.write_all(&[255, 250, 24, 1, 255, 240]).unwrap();
+            socket.write_all(b"hi").unwrap();
+
+            let mut reply = [0u8; 12];
+            socket.read_exact(&mut reply).unwrap();
+            assert_eq!(reply[..4], [255, 250, 24, 0]);
+            assert_eq!(&reply[4..10], b"XTERM-");
+        });
+
+        let mut stream = TelnetStream::connect(addr).unwrap();
+        assert_eq!(stream.role(), Role::Client);
+        stream.set_client_terminal_type("XTERM-");
+        stream.set_client_window_size(132, 25).unwrap();
+
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+        assert!(stream.is_option_enabled(Side::Remote, TelnetOption::ECHO));
+        assert!(!stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_window_size_doubles_iac_once() {
+        let naws = TelnetOption::NAWS.to_byte();
+        let mut stream = TelnetStream::with_role(
+            MemoryTransport {
+                input: io::Cursor::new(vec![IAC, TelnetCommand::DO.to_byte(), naws]),
+                output: Vec::new(),
+                write_script: VecDeque::new(),
+            },
+            Role::Client,
+        );
+        stream.set_client_window_size(255, 24).unwrap();
+
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+
+        // IAC SB NAWS 0 255 0 24 IAC SE, with the 255 doubled once
+        assert!(
+            stream
+                .get_ref()
+                .output
+                .ends_with(&[IAC, 250, naws, 0, 255, 255, 0, 24, IAC, 240])
+        );
+    }
+
+    #[test]
+    fn test_client_follows_server_echo() {
+        let echo = TelnetOption::ECHO.to_byte();
+        let mut input = vec![IAC, TelnetCommand::WILL.to_byte(), echo];
+        input.push(b'a');
+        input.extend_from_slice(&[IAC, TelnetCommand::WONT.to_byte(), echo]);
+        input.push(b'b');
+
+        let mut stream = TelnetStream::with_role(
+            MemoryTransport {
+                input: io::Cursor::new(input),
+                output: Vec::new(),
+                write_script: VecDeque::new(),
+            },
+            Role::Client,
+        );
+        assert_eq!(stream.role(), Role::Client);
+        stream.set_negotiation_events(true);
+
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+        assert_eq!(text, b"ab");
+        assert!(!stream.is_echo_remote());
+
+        let echo_events: Vec<NegotiationEvent> = stream
+            .take_negotiation_events()
+            .into_iter()
+            .filter(|event| matches!(event, NegotiationEvent::EchoChanged { .. }))
+            .collect();
+        assert_eq!(
+            echo_events,
+            vec![
+                NegotiationEvent::EchoChanged { remote: true },
+                NegotiationEvent::EchoChanged { remote: false },
+            ]
+        );
+
+        // Asking for echo off means asking the server to echo
+        stream.get_mut().output.clear();
+        assert!(!stream.request_echo_off().unwrap());
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::DO.to_byte(), echo]
+        );
+        assert!(!stream.is_echo_remote());
+    }
+
+    #[test]
+    fn test_loop_guard_stops_answering_repeated_requests() {
+        use crate::loop_guard::{LoopGuard, LoopPolicy};
+
+        let option = 99;
+        let looping = |policy| {
+            let mut input = [IAC, TelnetCommand::WILL.to_byte(), option].repeat(8);
+            input.extend(b"hi");
+            let mut stream = TelnetStream::new(MemoryTransport {
+                input: io::Cursor::new(input),
+                output: Vec::new(),
+                write_script: VecDeque::new(),
+            });
+            stream.set_loop_guard(LoopGuard::new(3, Duration::from_secs(60), policy));
+            stream
+        };
+
+        // Three refusals, then silence; the data still gets through
+        let mut stream = looping(LoopPolicy::Ignore);
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+        assert_eq!(text, b"hi");
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::DONT.to_byte(), option].repeat(3)
+        );
+        // Each request still shows up in the history
+        assert_eq!(
+            stream
+                .negotiation_history()
+                .for_option(TelnetOption::new(option))
+                .filter(|record| record.direction == Direction::Received)
+                .count(),
+            8
+        );
+
+        let mut stream = looping(LoopPolicy::Disconnect);
+        let err = stream.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            TelnetError::from(err),
+            TelnetError::ProtocolViolation(ProtocolViolation::RepeatedRefusal { .. })
+        ));
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::DONT.to_byte(), option].repeat(3)
+        );
+
+        // With the guard off every request is answered
+        let mut stream = looping(LoopPolicy::Ignore);
+        stream.set_loop_guard(LoopGuard::off());
+        stream.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::DONT.to_byte(), option].repeat(8)
+        );
+    }
+
+    #[test]
+    fn test_echo_requests_track_negotiator_state() {
+        use crate::negotiation::QueueState;
+
+        let echo = TelnetOption::ECHO.to_byte();
+        let mut stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(vec![IAC, TelnetCommand::DO.to_byte(), echo]),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+
+        assert!(stream.request_echo_off().unwrap());
+        assert_eq!(
+            stream.option_state(Side::Local, TelnetOption::ECHO),
+            OptionState::WantYes {
+                queue: QueueState::Empty
+            }
+        );
+
+        // The client's DO answers our WILL rather than asking afresh
+        stream.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(
+            stream.option_state(Side::Local, TelnetOption::ECHO),
+            OptionState::Yes
+        );
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::WILL.to_byte(), echo]
+        );
+
+        // Already agreed, so nothing more to send
+        stream.get_mut().output.clear();
+        stream.request_echo_off().unwrap();
+        assert!(stream.get_ref().output.is_empty());
+
+        stream.request_echo_on().unwrap();
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::WONT.to_byte(), echo]
+        );
+        assert!(!stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+        assert!(!stream.is_echo_remote());
+
+        stream.get_mut().output.clear();
+        let state = stream
+            .enable_option(Side::Remote, TelnetOption::NAWS)
+            .unwrap();
+        assert!(matches!(state, OptionState::WantYes { .. }));
+        assert_eq!(
+            stream.get_ref().output,
+            [
+                IAC,
+                TelnetCommand::DO.to_byte(),
+                TelnetOption::NAWS.to_byte()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stale_negotiation_is_retried_then_dropped() {
+        let do_naws = [
+            IAC,
+            TelnetCommand::DO.to_byte(),
+            TelnetOption::NAWS.to_byte(),
+        ];
+        let mut stream = TelnetStream::new(MockStream::new());
+        stream.set_retry_stale(true);
+        stream
+            .enable_option(Side::Remote, TelnetOption::NAWS)
+            .unwrap();
+        assert_eq!(stream.get_mut().take_written(), do_naws);
+
+        // The client never answers: ask again, then give up
+        let stale = stream.expire_stale_negotiations(Duration::ZERO).unwrap();
+        assert!(stale[0].retry.is_some());
+        assert_eq!(stream.get_mut().take_written(), do_naws);
+
+        let stale = stream.expire_stale_negotiations(Duration::ZERO).unwrap();
+        assert_eq!(stale[0].retry, None);
+        assert!(stream.get_mut().take_written().is_empty());
+        assert_eq!(
+            stream.option_state(Side::Remote, TelnetOption::NAWS),
+            OptionState::No
+        );
+
+        // So it can be asked for afresh
+        stream
+            .enable_option(Side::Remote, TelnetOption::NAWS)
+            .unwrap();
+        assert_eq!(stream.get_mut().take_written(), do_naws);
+    }
+
+    #[test]
+    fn test_capabilities_follow_each_report() {
+        let mut environ = vec![IAC, 250, TelnetOption::NEW_ENVIRON.to_byte()];
+        environ.extend(NewEnvironOption::encode_variables(
+            0,
+            &[
+                (
+                    crate::options::EnvironKind::Var,
+                    "LANG",
+                    Some("en_US.UTF-8"),
+                ),
+                (
+                    crate::options::EnvironKind::UserVar,
+                    "COLORTERM",
+                    Some("truecolor"),
+                ),
+            ],
+        ));
+        environ.extend([IAC, 240]);
+
+        let peer = MockStream::new();
+        peer.push_input(&[IAC, 250, 31, 0, 100, 0, 40, IAC, 240]);
+        peer.push_input(&environ);
+        let mut stream = TelnetStream::new(peer);
+        assert_eq!(
+            stream.get_terminal_capabilities(),
+            TerminalCapabilities::default()
+        );
+
+        assert_eq!(stream.read(&mut [0; 8]).unwrap(), 0);
+        let caps = stream.get_terminal_capabilities();
+        assert_eq!((caps.width, caps.height), (Some(100), Some(40)));
+        assert_eq!(caps.charset.as_deref(), Some("UTF-8"));
+        assert!(caps.utf8 && caps.supports_color);
+        assert_eq!(caps.color_depth, ColorDepth::TrueColor);
+    }
+
+    #[test]
+    fn test_transcoding_for_a_cp437_client() {
+        let peer = MockStream::with_input(b"\xc9\xcd\xbb\r\n");
+        let mut stream = TelnetStream::new(peer);
+        stream.set_transcoding(Transcoding::Manual {
+            app: Encoding::Utf8,
+            peer: Encoding::Cp437,
+        });
+
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "╔═╗\r\n");
+
+        // A character split between writes still arrives whole
+        let art = "╚═╝\n".as_bytes();
+        stream.write_all(&art[..4]).unwrap();
+        stream.write_all(&art[4..]).unwrap();
+        assert_eq!(stream.get_mut().take_written(), b"\xc8\xcd\xbc\r\n");
+    }
+
+    #[test]
+    fn test_transcoding_follows_reported_charset() {
+        let mut environ = vec![IAC, 250, TelnetOption::NEW_ENVIRON.to_byte()];
+        environ.extend(NewEnvironOption::encode_variables(
+            0,
+            &[(
+                crate::options::EnvironKind::UserVar,
+                "CHARSET",
+                Some("CP437"),
+            )],
+        ));
+        environ.extend([IAC, 240]);
+        environ.extend(b"\xb0");
+
+        let mut stream = TelnetStream::new(MockStream::with_input(&environ));
+        stream.set_transcoding(Transcoding::Negotiated {
+            app: Encoding::Utf8,
+        });
+        assert_eq!(stream.transcoding_between(), None);
+
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "░");
+        assert_eq!(
+            stream.transcoding_between(),
+            Some((Encoding::Utf8, Encoding::Cp437))
+        );
+
+        stream.set_transcoding(Transcoding::Off);
+        stream.write_all("░".as_bytes()).unwrap();
+        assert_eq!(stream.get_mut().take_written(), "░".as_bytes());
+    }
+
+    #[test]
+    fn test_ansi_filtering_follows_reported_terminal() {
+        let mut environ = vec![IAC, 250, TelnetOption::NEW_ENVIRON.to_byte()];
+        environ.extend(NewEnvironOption::encode_variables(
+            0,
+            &[(crate::options::EnvironKind::Var, "TERM", Some("vt100"))],
+        ));
+        environ.extend([IAC, 240]);
+
+        let mut stream = TelnetStream::new(MockStream::new());
+        stream.set_ansi_filtering(AnsiFiltering::Negotiated);
+        assert_eq!(stream.ansi_profile(), None);
+        stream.write_all(b"\x1b[31mred").unwrap();
+        assert_eq!(stream.get_mut().take_written(), b"\x1b[31mred");
+
+        stream.get_mut().push_input(&environ);
+        assert_eq!(stream.read(&mut [0; 8]).unwrap(), 0);
+        assert_eq!(
+            stream.ansi_profile(),
+            Some(AnsiProfile {
+                attributes: false,
+                color_depth: ColorDepth::Monochrome,
+                cursor_positioning: true,
+            })
+        );
+
+        // Colors go, cursor addressing stays, and a split sequence waits
+        stream.write_all(b"\x1b[2J\x1b[1;3").unwrap();
+        stream.write_all(b"1mhi\x1b[0m").unwrap();
+        assert_eq!(stream.get_mut().take_written(), b"\x1b[2Jhi");
+
+        stream.set_ansi_filtering(AnsiFiltering::Manual(AnsiProfile::DUMB));
+        stream.write_all(b"\x1b[H\xb0").unwrap();
+        assert_eq!(stream.get_mut().take_written(), b"\xb0");
+    }
+
+    #[test]
+    fn test_server_collects_environment() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut request = [0u8; 3];
+            socket.read_exact(&mut request).unwrap();
+            assert_eq!(request, [255, 253, 39]); // IAC DO NEW-ENVIRON
+            socket.write_all(&[255, 251, 39]).unwrap(); // IAC WILL NEW-ENVIRON
+
+            let mut send = [0u8; 6];
+            socket.read_exact(&mut send).unwrap();
+            assert_eq!(send, [255, 250, 39, 1, 255, 240]); // IAC SB NEW-ENVIRON SEND IAC SE
+
+            // IS VAR "USER" VALUE "alice", then some text
+            let mut reply = vec![255, 250, 39, 0, 0];
+            reply.extend(b"USER");
+            reply.push(1);
+            reply.extend(b"alice");
+            reply.extend([255, 240]);
+            reply.extend(b"hi");
+            socket.write_all(&reply).unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        assert!(stream.environment().is_none());
+        stream.request_environment().unwrap();
+
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+        assert_eq!(
+            stream.environment().and_then(|env| env.user()),
+            Some("alice")
+        );
+
+        client.join().unwrap();
+    }
+
+    /// Accept one connection, with `client` writing `input` and hanging up
+    fn stream_fed(input: Vec<u8>, budget: MemoryBudget) -> (TelnetStream, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            socket.write_all(&input).unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.set_memory_budget(budget);
+        (stream, client)
+    }
+
+    /// IAC SB TERMINAL-TYPE IS, `len` bytes of name, IAC SE, then "hi"
+    fn oversized_subnegotiation(len: usize) -> Vec<u8> {
+        let mut input = vec![255, 250, 24, 0];
+        input.extend(std::iter::repeat_n(b'X', len));
+        input.extend([255, 240]);
+        input.extend(b"hi");
+        input
+    }
+
+    #[test]
+    fn test_memory_budget_disconnects_by_default() {
+        let budget = MemoryBudget::new(1024, OverflowPolicy::default());
+        let (mut stream, client) = stream_fed(oversized_subnegotiation(8192), budget);
+
+        let mut buf = [0u8; 2];
+        let err = stream.read_exact(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert_eq!(stream.memory_stats().overflows, 1);
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_memory_budget_flush_discards_subnegotiation() {
+        let budget = MemoryBudget::new(1024, OverflowPolicy::Flush);
+        let (mut stream, client) = stream_fed(oversized_subnegotiation(8192), budget);
+
+        // The runaway TERMINAL-TYPE is skipped, the data after it survives
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+
+        let stats = stream.memory_stats();
+        assert!(stats.overflows >= 1);
+        assert_eq!(stats.subnegotiation, 0);
+        assert!(stats.peak > 1024);
+        assert!(stream.get_terminal_capabilities().terminal_type.is_none());
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_memory_budget_drop_keeps_oldest_data() {
+        let input: Vec<u8> = (0..3000).map(|i| b'a' + (i % 26) as u8).collect();
+        let budget = MemoryBudget::new(1000, OverflowPolicy::Drop);
+        let (mut stream, client) = stream_fed(input.clone(), budget);
+
+        // Read everything without handing it to the application
+        stream.poll_negotiation(Duration::from_secs(5)).unwrap();
+        client.join().unwrap();
+        let stats = stream.memory_stats();
+        assert_eq!(stats.total(), 1000);
+        assert!(stats.overflows >= 1);
+
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        assert_eq!(received, input[..1000]);
+    }
+
+    #[test]
+    fn test_parser_limits_drop_or_fail_long_subnegotiations() {
+        use crate::parser::{LimitPolicy, ParserLimits};
+
+        let fed = |limits| {
+            let mut stream = TelnetStream::new(MemoryTransport {
+                input: io::Cursor::new(oversized_subnegotiation(100)),
+                output: Vec::new(),
+                write_script: VecDeque::new(),
+            });
+            stream.set_parser_limits(limits);
+            stream
+        };
+
+        // Dropped whole by default; the data after it survives
+        let mut stream = fed(ParserLimits::new(16, LimitPolicy::Drop));
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+        assert_eq!(text, b"hi");
+        assert_eq!(stream.memory_stats().overflows, 0);
+
+        let mut stream = fed(ParserLimits::new(16, LimitPolicy::Error));
+        let err = stream.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        match TelnetError::from(err) {
+            TelnetError::SubnegotiationTooLarge { option, limit } => {
+                assert_eq!(option, TelnetOption::TERMINAL_TYPE);
+                assert_eq!(limit, 16);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(stream.parser_limits().max_subnegotiation(), Some(16));
+    }
+
+    #[test]
+    fn test_malformed_sequences_become_events() {
+        let mut stream = TelnetStream::new(MemoryTransport {
+            // "a" IAC 99 "b"
+            input: io::Cursor::new(vec![b'a', IAC, 99, b'b']),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+        stream.set_negotiation_events(true);
+
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+        assert_eq!(text, [b'a', IAC, 99, b'b']);
+        assert_eq!(
+            stream.take_negotiation_events(),
+            [NegotiationEvent::ProtocolViolation(
+                ProtocolViolation::UnknownCommand(99)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unlimited_budget_still_reports_usage() {
+        let (mut stream, client) = stream_fed(b"hello".to_vec(), MemoryBudget::unlimited());
+        stream.poll_negotiation(Duration::from_secs(5)).unwrap();
+        client.join().unwrap();
+
+        let stats = stream.memory_stats();
+        assert_eq!(stats.data_buffer, 5);
+        assert_eq!(stats.peak, 5);
+        assert_eq!(stats.overflows, 0);
+    }
+
+    #[test]
+    fn test_server_collects_terminal_speed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut request = [0u8; 3];
+            socket.read_exact(&mut request).unwrap();
+            assert_eq!(request, [255, 253, 32]); // IAC DO TERMINAL-SPEED
+            socket.write_all(&[255, 251, 32]).unwrap(); // IAC WILL TERMINAL-SPEED
+
+            let mut send = [0u8; 6];
+            socket.read_exact(&mut send).unwrap();
+            assert_eq!(send, [255, 250, 32, 1, 255, 240]); // IAC SB TERMINAL-SPEED SEND IAC SE
+
+            // IS "2400,1200", then some text
+            let mut reply = vec![255, 250, 32, 0];
+            reply.extend(b"2400,1200");
+            reply.extend([255, 240]);
+            reply.extend(b"hi");
+            socket.write_all(&reply).unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        assert!(stream.terminal_speed().is_none());
+        stream.request_terminal_speed().unwrap();
+
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+        assert_eq!(
+            stream.terminal_speed(),
+            Some(TerminalSpeed::new(2400, 1200))
+        );
+
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_unknown_options_follow_the_policy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            socket
+                .write_all(&[255, 251, 99, 255, 253, 99, 255, 251, 98, b'x'])
+                .unwrap();
+            let mut replies = [0u8; 9];
+            socket.read_exact(&mut replies).unwrap();
+            replies
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.register_option_handler(Box::new(SilentHandler(TelnetOption::new(99))));
+        stream.set_accept_policy(|option: TelnetOption, side| {
+            option.to_byte() == 99 && side == Side::Remote
+        });
+
+        let mut buf = [0u8; 8];
+        assert_eq!(stream.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'x');
+        assert_eq!(
+            client.join().unwrap(),
+            [255, 253, 99, 255, 252, 99, 255, 254, 98]
+        );
+        assert!(stream.is_option_enabled(Side::Remote, TelnetOption::new(99)));
+        assert!(!stream.is_option_enabled(Side::Local, TelnetOption::new(99)));
+        assert!(!stream.is_option_enabled(Side::Remote, TelnetOption::new(98)));
+    }
+
+    #[test]
+    fn test_unnamed_option_handler_gets_subnegotiations() {
+        /// Answers every payload with the same bytes backwards
+        struct Reverse;
+        impl TelnetOptionHandler for Reverse {
+            fn option_code(&self) -> TelnetOption {
+                TelnetOption::new(120)
+            }
+            fn handle_subnegotiation(
+                &mut self,
+                data: &[u8],
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                Ok(data.iter().rev().copied().collect())
+            }
+            fn generate_subnegotiation(
+                &self,
+                _: SubNegotiationCommand,
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                Ok(Vec::new())
+            }
+            fn is_active(&self) -> bool {
+                true
+            }
+            fn reset(&mut self) {}
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            socket
+                .write_all(&[
+                    255, 251, 120, 255, 250, 120, b'h', 255, 255, b'i', 255, 240, b'x',
+                ])
+                .unwrap();
+            let mut replies = [0u8; 12];
+            socket.read_exact(&mut replies).unwrap();
+            replies
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.set_accept_policy(|option, _| option == TelnetOption::new(120));
+        stream.register_option_handler(Box::new(Reverse));
+
+        let mut buf = [0u8; 8];
+        assert_eq!(stream.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'x');
+        assert_eq!(
+            client.join().unwrap(),
+            [255, 253, 120, 255, 250, 120, b'i', 255, 255, b'h', 255, 240]
+        );
+    }
+
+    #[test]
+    fn test_chunked_handler_gets_payload_past_the_limit() {
+        /// Adds up a payload as it arrives and answers with its size
+        #[derive(Default)]
+        struct Tally {
+            received: usize,
+            largest_piece: usize,
+            escapes: usize,
+        }
+        impl TelnetOptionHandler for Tally {
+            fn option_code(&self) -> TelnetOption {
+                TelnetOption::new(122)
+            }
+            fn handle_subnegotiation(
+                &mut self,
+                _: &[u8],
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                panic!("payload should arrive in chunks");
+            }
+            fn subnegotiation_chunk_size(&self) -> Option<usize> {
+                Some(4096)
+            }
+            fn handle_subnegotiation_chunk(
+                &mut self,
+                data: &[u8],
+                offset: usize,
+                last: bool,
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                assert_eq!(offset, self.received);
+                self.received += data.len();
+                self.largest_piece = self.largest_piece.max(data.len());
+                self.escapes += data.iter().filter(|&&b| b == IAC).count();
+                Ok(if last {
+                    self.received.to_string().into_bytes()
+                } else {
+                    Vec::new()
+                })
+            }
+            fn generate_subnegotiation(
+                &self,
+                _: SubNegotiationCommand,
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                Ok(Vec::new())
+            }
+            fn is_active(&self) -> bool {
+                true
+            }
+            fn reset(&mut self) {}
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+
+        // 100 KB with a 255 every KB, past the 64 KiB default limit
+        let payload: Vec<u8> = (0..100_000)
+            .map(|i| if i % 1000 == 0 { IAC } else { b'x' })
+            .collect();
+        let sequence = TelnetSequence::SubNegotiation {
+            option: TelnetOption::new(122),
+            data: payload,
+        };
+        // IAC WILL 122 first, so the option is on when the reply goes out
+        let mut input = vec![IAC, TelnetCommand::WILL.to_byte(), 122];
+        input.extend_from_slice(&sequence.to_bytes());
+        input.extend_from_slice(b"ok");
+
+        let peer = MockStream::with_input(&input);
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.set_parser_limits(ParserLimits::new(1024, LimitPolicy::Error));
+        stream.set_accept_policy(|option, _| option == TelnetOption::new(122));
+        stream.register_option_handler(Box::new(Tally::default()));
+
+        let mut read = Vec::new();
+        stream.read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"ok");
+        assert_eq!(peer.written(), b"\xff\xfdz\xff\xfaz100000\xff\xf0");
+        let tally = stream
+            .get_option_handler(TelnetOption::new(122))
+            .and_then(|handler| handler.as_any().downcast_ref::<Tally>())
+            .unwrap();
+        assert_eq!((tally.received, tally.escapes), (100_000, 100));
+        assert!(tally.largest_piece <= 4096);
+        assert_eq!(stream.stats().largest_subnegotiation, 100_000);
+        assert_eq!(stream.stats().subnegotiations_received, 1);
+    }
+
+    #[test]
+    fn test_option_handler_hears_option_come_and_go() {
+        /// Asks for a greeting once the peer takes the option on
+        #[derive(Default)]
+        struct Greeter {
+            changes: Vec<(Side, bool)>,
+        }
+        impl TelnetOptionHandler for Greeter {
+            fn option_code(&self) -> TelnetOption {
+                TelnetOption::new(121)
+            }
+            fn handle_subnegotiation(
+                &mut self,
+                _: &[u8],
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                Ok(Vec::new())
+            }
+            fn generate_subnegotiation(
+                &self,
+                _: SubNegotiationCommand,
+            ) -> Result<Vec<u8>, crate::options::OptionError> {
+                Ok(Vec::new())
+            }
+            fn is_active(&self) -> bool {
+                true
+            }
+            fn reset(&mut self) {}
+            fn on_enabled(&mut self, side: Side) -> Vec<u8> {
+                self.changes.push((side, true));
+                b"hello?".to_vec()
+            }
+            fn on_disabled(&mut self, side: Side) {
+                self.changes.push((side, false));
+            }
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+
+        // WILL 121, then WONT 121
+        let input = [255, 251, 121, b'a', 255, 252, 121];
+        let mut stream = TelnetStream::new(MockStream::with_input(&input));
+        stream.set_accept_policy(|option, _| option == TelnetOption::new(121));
+        stream.register_option_handler(Box::new(Greeter::default()));
+
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"a");
+
+        let mut expected = vec![255, 253, 121, 255, 250, 121];
+        expected.extend_from_slice(b"hello?");
+        expected.extend_from_slice(&[255, 240, 255, 254, 121]);
+        assert_eq!(stream.get_mut().take_written(), expected);
+
+        let greeter = stream
+            .get_option_handler(TelnetOption::new(121))
+            .and_then(|handler| handler.as_any().downcast_ref::<Greeter>())
+            .unwrap();
+        assert_eq!(
+            greeter.changes,
+            [(Side::Remote, true), (Side::Remote, false)]
+        );
+    }
+
+    #[test]
+    fn test_resize_interrupts_read_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let naws = |width: u8, height: u8| [255, 250, 31, 0, width, 0, height, 255, 240];
+            // 80x24, the same again, then 100x40
+            let mut reports = Vec::new();
+            for (width, height) in [(80, 24), (80, 24), (100, 40)] {
+                reports.extend(naws(width, height));
+            }
+            socket.write_all(&reports).unwrap();
+
+            let mut redrawn = [0u8; 1];
+            socket.read_exact(&mut redrawn).unwrap();
+            socket.write_all(b"x").unwrap();
+            socket
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.set_interrupt_on_resize(true);
+
+        let mut buf = [0u8; 8];
+        let error = stream.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(
+            stream.take_resize(),
+            Some(WindowSize {
+                width: 100,
+                height: 40
+            })
+        );
+        assert_eq!(stream.get_terminal_capabilities().width, Some(100));
+
+        // The resize is spent; the next read waits for input as usual
+        stream.write_all(b"!").unwrap();
+        stream.flush().unwrap();
+        assert_eq!(stream.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'x');
+        assert_eq!(stream.take_resize(), None);
+        drop(client.join().unwrap());
+    }
+
+    #[test]
+    fn test_stats_count_both_directions() {
+        use std::sync::{Arc, Mutex};
+
+        let mut input = b"hi".to_vec();
+        input.extend([IAC, 251, 31]); // IAC WILL NAWS
+        input.extend([IAC, 250, 31, 0, 80, 0, 24, IAC, 240]);
+        input.extend([IAC, 241, IAC, IAC]); // NOP, then an escaped 255
+
+        let mut stream = TelnetStream::new(MockStream::with_input(&input));
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let hook_added = Arc::clone(&added);
+        stream.set_stats_hook(Duration::ZERO, move |stats| {
+            hook_added.lock().unwrap().push(*stats)
+        });
+
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hi\xff");
+        stream.write_all(b"hello").unwrap();
+        stream
+            .send_subnegotiation(TelnetOption::NAWS, &[1])
+            .unwrap();
+
+        let stats = stream.stats();
+        assert_eq!(stats.bytes_received, input.len() as u64);
+        assert_eq!(stats.data_received, 3);
+        assert_eq!(stats.sequences_received, 3);
+        assert_eq!(stats.negotiations_received, 1);
+        assert_eq!(stats.subnegotiations_received, 1);
+        assert_eq!(stats.subnegotiation_bytes_received, 4);
+        assert_eq!(stats.largest_subnegotiation, 4);
+        assert_eq!(stats.data_sent, 5);
+        assert_eq!(stats.subnegotiations_sent, 1);
+        assert_eq!(
+            stats.bytes_sent,
+            stream.get_mut().take_written().len() as u64
+        );
+
+        // The sub-negotiation went out after the last write; the hook
+        // hears about it when asked
+        stream.report_stats();
+        stream.report_stats();
+        let mut total = ConnectionStats::default();
+        for added in added.lock().unwrap().iter() {
+            total += *added;
+        }
+        assert_eq!(total, stats);
+    }
+
+    #[test]
+    fn test_keepalive_only_when_idle() {
+        let mut stream = TelnetStream::new(MockStream::new());
+        assert!(!stream.tick().unwrap());
+        stream.set_keepalive(Some(Duration::from_millis(30)));
+        assert!(!stream.tick().unwrap());
+
+        thread::sleep(Duration::from_millis(20));
+        stream.write_all(b"hi").unwrap();
+        thread::sleep(Duration::from_millis(20));
+        // The write put the next keepalive off
+        assert!(!stream.tick().unwrap());
+        assert_eq!(stream.get_mut().take_written(), b"hi");
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(stream.tick().unwrap());
+        assert!(!stream.tick().unwrap());
+        assert_eq!(
+            stream.get_mut().take_written(),
+            [IAC, TelnetCommand::NOP.to_byte()]
+        );
+
+        stream.set_keepalive_probe(KeepaliveProbe::TimingMark);
+        thread::sleep(Duration::from_millis(35));
+        assert!(stream.tick().unwrap());
+        assert_eq!(
+            stream.get_mut().take_written(),
+            [
+                IAC,
+                TelnetCommand::DO.to_byte(),
+                TelnetOption::TIMING_MARK.to_byte()
+            ]
+        );
+        assert_eq!(stream.pending_timing_marks(), 1);
+    }
+
+    #[test]
+    fn test_blocking_read_sends_keepalives() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let mut nops = [0u8; 6];
+            socket.read_exact(&mut nops).unwrap();
+            socket.write_all(b"x").unwrap();
+            (nops, socket)
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.set_keepalive(Some(Duration::from_millis(20)));
+
+        // The caller stays quiet until it has had three keepalives
+        let mut buf = [0u8; 8];
+        assert_eq!(stream.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'x');
+        let nop = [IAC, TelnetCommand::NOP.to_byte()];
+        let (nops, _socket) = client.join().unwrap();
+        assert_eq!(nops, [nop, nop, nop].concat().as_slice());
+
+        // Keepalives don't stretch the read timeout
+        stream
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let started = Instant::now();
+        let error = stream.read(&mut buf).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(100) && waited < Duration::from_secs(2));
+        assert_eq!(
+            stream.read_timeout().unwrap(),
+            Some(Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn test_debounced_resize_storm_wakes_read_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let mut reports = Vec::new();
+            for (width, height) in [(80, 24), (90, 30), (100, 40)] {
+                reports.extend([255, 250, 31, 0, width, 0, height, 255, 240]);
+            }
+            socket.write_all(&reports).unwrap();
+
+            let mut redrawn = [0u8; 1];
+            socket.read_exact(&mut redrawn).unwrap();
+            socket.write_all(b"x").unwrap();
+            socket
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        stream.set_resize_debounce(Some(Duration::from_millis(50)));
+        stream.set_interrupt_on_resize(true);
+        stream.set_negotiation_events(true);
+
+        // Nothing but the reports arrives, so the quiet period ending is
+        // what wakes the read
+        let started = Instant::now();
+        let mut buf = [0u8; 8];
+        let error = stream.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(stream.take_resize(), Some(WindowSize::new(100, 40)));
+        let resizes: Vec<_> = stream
+            .take_negotiation_events()
+            .into_iter()
+            .filter(|event| matches!(event, NegotiationEvent::WindowResized(_)))
+            .collect();
+        assert_eq!(
+            resizes,
+            [NegotiationEvent::WindowResized(WindowSize::new(100, 40))]
+        );
+
+        // The user's own timeout is put back
+        assert_eq!(stream.read_timeout().unwrap(), None);
+        stream.write_all(b"!").unwrap();
+        stream.flush().unwrap();
+        assert_eq!(stream.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'x');
+        drop(client.join().unwrap());
+    }
+
+    #[test]
+    fn test_debounced_resize_back_to_reported_size() {
+        let naws = |width: u8, height: u8| [255, 250, 31, 0, width, 0, height, 255, 240];
+        let peer = MockStream::with_input(&naws(80, 24));
+        peer.hold_open(true);
+        let mut stream = TelnetStream::new(peer.clone());
+        stream.set_resize_debounce(Some(Duration::from_millis(20)));
+
+        stream.poll_negotiation(Duration::from_millis(1)).unwrap();
+        assert_eq!(stream.take_resize(), None);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(stream.take_resize(), Some(WindowSize::new(80, 24)));
+
+        // A storm that ends where it started changes nothing
+        peer.push_input(&[naws(90, 30), naws(80, 24)].concat());
+        stream.poll_negotiation(Duration::from_millis(1)).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(stream.take_resize(), None);
+
+        // Turning debouncing off reports a pending size straight away
+        peer.push_input(&naws(120, 50));
+        stream.poll_negotiation(Duration::from_millis(1)).unwrap();
+        stream.set_resize_debounce(None);
+        assert_eq!(stream.take_resize(), Some(WindowSize::new(120, 50)));
+    }
+
+    #[test]
+    fn test_server_collects_location() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut request = [0u8; 3];
+            socket.read_exact(&mut request).unwrap();
+            assert_eq!(request, [255, 253, 23]); // IAC DO SEND-LOCATION
+
+            // Agree and send the location straight away, then some text
+            let mut reply = vec![255, 251, 23, 255, 250, 23];
+            reply.extend(b"Portland, OR");
+            reply.extend([255, 240]);
+            reply.extend(b"hi");
+            socket.write_all(&reply).unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        assert!(stream.location().is_none());
+        stream.request_location().unwrap();
+
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+        assert_eq!(stream.location(), Some("Portland, OR"));
+        assert!(stream.is_option_enabled(Side::Remote, TelnetOption::SEND_LOCATION));
+
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_server_negotiates_linemode() {
+        use crate::options::linemode::{LM_FORWARDMASK, LM_MODE, MODE_ACK, MODE_EDIT};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+
+            let mut request = [0u8; 3];
+            socket.read_exact(&mut request).unwrap();
+            assert_eq!(request, [255, 253, 34]); // IAC DO LINEMODE
+            socket.write_all(&[255, 251, 34]).unwrap(); // IAC WILL LINEMODE
+
+            let mut mode = [0u8; 7];
+            socket.read_exact(&mut mode).unwrap();
+            assert_eq!(mode, [255, 250, 34, LM_MODE, MODE_EDIT, 255, 240]);
+
+            // Acknowledge, then a whole edited line
+            let mut reply = vec![255, 250, 34, LM_MODE, MODE_EDIT | MODE_ACK, 255, 240];
+            reply.extend(b"hello\r\n");
+            socket.write_all(&reply).unwrap();
+
+            let mut mask = [0u8; 9];
+            socket.read_exact(&mut mask).unwrap();
+            assert_eq!(
+                mask,
+                [255, 250, 34, 253, LM_FORWARDMASK, 0x00, 0x40, 255, 240]
+            );
+            socket
+                .write_all(&[255, 250, 34, 251, LM_FORWARDMASK, 255, 240, b'!'])
+                .unwrap();
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        assert!(!stream.set_forward_mask(&[0x01]).unwrap());
+        stream.request_linemode(MODE_EDIT).unwrap();
+
+        let mut line = [0u8; 7];
+        stream.read_exact(&mut line).unwrap();
+        assert_eq!(&line, b"hello\r\n");
+        assert!(stream.is_local_line_editing());
+
+        // Tab (9) ends a line early
+        let mask = LinemodeOption::forward_mask_for(b"\t");
+        assert!(stream.set_forward_mask(&mask).unwrap());
+        let mut bang = [0u8; 1];
+        stream.read_exact(&mut bang).unwrap();
+        assert_eq!(
+            stream.linemode().and_then(|l| l.forward_mask_accepted()),
+            Some(true)
+        );
+
+        client.join().unwrap();
+    }
+
+    /// In-memory transport: reads come from a script, writes are kept
+    ///
+    /// Each write takes at most the next scripted number of bytes (`None`
+    /// means `WouldBlock`), then everything once the script runs out.
+    struct MemoryTransport {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+        write_script: VecDeque<Option<usize>>,
+    }
+
+    impl Read for MemoryTransport {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MemoryTransport {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = match self.write_script.pop_front() {
+                Some(Some(limit)) => buf.len().min(limit),
+                Some(None) => return Err(io::ErrorKind::WouldBlock.into()),
+                None => buf.len(),
+            };
+            self.output.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for MemoryTransport {}
+
+    /// Transport whose reads return scripted chunks (`None` means
+    /// `WouldBlock`), then end of file; writes are kept
+    struct ChunkedTransport {
+        reads: VecDeque<Option<Vec<u8>>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for ChunkedTransport {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.reads.pop_front() {
+                Some(Some(chunk)) => {
+                    assert!(chunk.len() <= buf.len());
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                Some(None) => Err(io::ErrorKind::WouldBlock.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Write for ChunkedTransport {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for ChunkedTransport {}
+
+    #[test]
+    fn test_read_telnet_line_across_packets() {
+        let mut stream = TelnetStream::new(ChunkedTransport {
+            reads: VecDeque::from([
+                Some(b"hel".to_vec()),
+                None,
+                Some(b"lo\r".to_vec()),
+                Some(b"\n".to_vec()),
+                Some(vec![b'b', IAC, TelnetCommand::NOP.to_byte(), b'y']),
+                Some(b"e\r\0x\n".to_vec()),
+                Some(b"tail".to_vec()),
+            ]),
+            output: Vec::new(),
+        });
+
+        let mut line = String::new();
+        let error = stream.read_telnet_line(&mut line).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert!(line.is_empty());
+
+        // CR LF, CR NUL and LF all end a line
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if stream.read_telnet_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            lines.push(line);
+        }
+        assert_eq!(lines, ["hello", "bye", "x", "tail"]);
+    }
+
+    #[test]
+    fn test_buf_read_lines() {
+        let mut input = b"one\r\n".to_vec();
+        input.extend_from_slice(&[IAC, TelnetCommand::NOP.to_byte()]);
+        input.extend_from_slice(b"two\nthree");
+        let stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+
+        let lines: Vec<String> = stream.lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn test_snapshot_carries_negotiation_to_a_new_stream() {
+        let mut input = vec![IAC, TelnetCommand::WILL.to_byte(), 31];
+        input.extend_from_slice(&[IAC, 250, 31, 0, 100, 0, 40, IAC, 240]);
+        input.extend_from_slice(&[IAC, TelnetCommand::WILL.to_byte(), 24]);
+        input.extend_from_slice(&[IAC, 250, 24, 0]);
+        input.extend_from_slice(b"XTERM-256COLOR");
+        input.extend_from_slice(&[IAC, 240]);
+        let mut stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+        stream.request_echo_off().unwrap();
+        stream.read_to_end(&mut Vec::new()).unwrap();
+        let before = stream.get_terminal_capabilities();
+        assert_eq!(before.width, Some(100));
+
+        let snapshot = stream.snapshot();
+        let mut restored = silent_stream();
+        restored.restore(&snapshot).unwrap();
+        assert!(restored.get_ref().output.is_empty());
+        assert!(restored.is_option_enabled(Side::Remote, TelnetOption::NAWS));
+        assert!(restored.is_echo_remote());
+        let after = restored.get_terminal_capabilities();
+        assert_eq!(after.width, Some(100));
+        assert_eq!(after.height, Some(40));
+        assert_eq!(after.terminal_type, before.terminal_type);
+        assert_eq!(after.color_depth, before.color_depth);
+        assert_eq!(restored.snapshot(), snapshot);
+
+        // A state a handler can't read is refused
+        let mut broken = snapshot.clone();
+        broken
+            .handlers
+            .push((TelnetOption::NAWS.to_byte(), vec![1]));
+        let error = silent_stream().restore(&broken).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_serializes() {
+        let mut stream = silent_stream();
+        stream.request_echo_off().unwrap();
+        let snapshot = stream.snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let back: StreamSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, snapshot);
+    }
+
+    #[test]
+    fn test_nonblocking_reads_keep_partial_sequences() {
+        let sga = TelnetOption::SUPPRESS_GO_AHEAD.to_byte();
+        let mut stream = TelnetStream::new(ChunkedTransport {
+            reads: VecDeque::from([
+                Some(vec![b'o', b'k', IAC]),
+                None,
+                Some(vec![TelnetCommand::WILL.to_byte()]),
+                None,
+                Some(vec![sga, b'h', b'i']),
+                Some(vec![IAC, TelnetCommand::NOP.to_byte()]),
+                None,
+            ]),
+            output: Vec::new(),
+        });
+
+        let mut buf = [0u8; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"ok");
+
+        // Only part of IAC WILL SUPPRESS-GO-AHEAD so far: nothing to
+        // return and nothing lost
+        for _ in 0..2 {
+            let error = stream.read(&mut buf).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        }
+        assert!(!stream.is_option_enabled(Side::Remote, TelnetOption::SUPPRESS_GO_AHEAD));
+
+        assert_eq!(stream.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"hi");
+        assert!(stream.is_option_enabled(Side::Remote, TelnetOption::SUPPRESS_GO_AHEAD));
+        assert_eq!(
+            stream.get_ref().output,
+            [IAC, TelnetCommand::DO.to_byte(), sga]
+        );
+
+        // A read of commands alone carries on to the next, which blocks
+        let error = stream.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        assert!(stream.get_ref().reads.is_empty());
+    }
+
+    fn silent_stream() -> TelnetStream<MemoryTransport> {
+        TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(Vec::new()),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        })
+    }
+
+    #[test]
+    fn test_apply_profile_requests_in_order() {
+        let mut stream = silent_stream();
+        let profile: NegotiationProfile = "naws, end-of-record, terminal-type".parse().unwrap();
+        stream.apply_profile(&profile).unwrap();
+        assert_eq!(
+            stream.get_ref().output,
+            [
+                IAC,
+                TelnetCommand::DO.to_byte(),
+                TelnetOption::NAWS.to_byte(),
+                IAC,
+                TelnetCommand::WILL.to_byte(),
+                TelnetOption::END_OF_RECORD.to_byte(),
+                IAC,
+                TelnetCommand::DO.to_byte(),
+                TelnetOption::TERMINAL_TYPE.to_byte(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_profile_refuses_unknown_options() {
+        let mut stream = silent_stream();
+        let profile = NegotiationProfile::new([TelnetOption::NAWS, TelnetOption::GMCP]);
+        let error = stream.apply_profile(&profile).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(stream.get_ref().output.is_empty());
+        assert!(!stream.can_request(TelnetOption::GMCP));
+    }
+
+    #[test]
+    fn test_negotiation_events_in_order() {
+        let mut input = vec![
+            IAC,
+            TelnetCommand::WILL.to_byte(),
+            TelnetOption::NAWS.to_byte(),
+        ];
+        input.extend_from_slice(&[
+            IAC,
+            TelnetCommand::SB.to_byte(),
+            TelnetOption::NAWS.to_byte(),
+        ]);
+        input.extend_from_slice(&[0, 80, 0, 24, IAC, TelnetCommand::SE.to_byte()]);
+        // GMCP has no handler, but still shows up
+        input.extend_from_slice(&[
+            IAC,
+            TelnetCommand::SB.to_byte(),
+            TelnetOption::GMCP.to_byte(),
+        ]);
+        input.extend_from_slice(b"Core.Hello {}");
+        input.extend_from_slice(&[IAC, TelnetCommand::SE.to_byte()]);
+        input.extend_from_slice(&[
+            IAC,
+            TelnetCommand::WONT.to_byte(),
+            TelnetOption::NAWS.to_byte(),
+        ]);
+        input.push(b'x');
+
+        let mut stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+        stream.set_negotiation_events(true);
+        stream.request_echo_off().unwrap();
+        let mut buf = [0u8; 1];
+        stream.read_exact(&mut buf).unwrap();
+
+        let naws = TelnetOption::NAWS;
+        assert_eq!(
+            stream.take_negotiation_events(),
+            vec![
+                NegotiationEvent::EchoChanged { remote: true },
+                NegotiationEvent::OptionEnabled {
+                    side: Side::Remote,
+                    option: naws,
+                },
+                NegotiationEvent::Subnegotiation {
+                    option: naws,
+                    data: vec![0, 80, 0, 24],
+                },
+                NegotiationEvent::WindowResized(WindowSize::new(80, 24)),
+                NegotiationEvent::Subnegotiation {
+                    option: TelnetOption::GMCP,
+                    data: b"Core.Hello {}".to_vec(),
+                },
+                NegotiationEvent::OptionDisabled {
+                    side: Side::Remote,
+                    option: naws,
+                },
+            ]
+        );
+        assert!(stream.take_negotiation_events().is_empty());
+
+        // Off again, nothing is kept
+        stream.set_negotiation_events(false);
+        stream.request_echo_on().unwrap();
+        assert!(stream.take_negotiation_events().is_empty());
+    }
+
+    #[test]
+    fn test_negotiate_terminal_type_waits_for_the_cycle() {
+        let ttype = TelnetOption::TERMINAL_TYPE.to_byte();
+        let mut input = vec![IAC, TelnetCommand::WILL.to_byte(), ttype];
+        for name in [&b"MUDLET"[..], b"XTERM", b"XTERM"] {
+            input.extend_from_slice(&[IAC, TelnetCommand::SB.to_byte(), ttype, 0]);
+            input.extend_from_slice(name);
+            input.extend_from_slice(&[IAC, TelnetCommand::SE.to_byte()]);
+        }
+        input.extend_from_slice(b"hi");
+
+        let mut stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+        let name = stream
+            .negotiate_terminal_type(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("MUDLET"));
+        assert!(!stream.terminal_type_handler().unwrap().awaiting_reply());
+
+        // Known now, so nothing more is sent
+        let sent = stream.get_ref().output.len();
+        assert_eq!(
+            stream.negotiate_terminal_type(Duration::ZERO).unwrap(),
+            Some("MUDLET".to_string())
+        );
+        assert_eq!(stream.get_ref().output.len(), sent);
+
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+        assert_eq!(text, b"hi");
+    }
+
+    #[test]
+    fn test_negotiate_window_size_returns_early() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (socket, _) = listener.accept().unwrap();
+        let mut stream = TelnetStream::new(socket);
+        let naws = TelnetOption::NAWS.to_byte();
+
+        // A refusal ends the wait with the connection still open
+        client
+            .write_all(&[IAC, TelnetCommand::WONT.to_byte(), naws])
+            .unwrap();
+        let started = Instant::now();
+        assert_eq!(
+            stream
+                .negotiate_window_size(Duration::from_secs(5))
+                .unwrap(),
+            None
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // As does a report, even one arriving in pieces
+        let report = thread::spawn(move || {
+            client
+                .write_all(&[IAC, TelnetCommand::WILL.to_byte(), naws, IAC])
+                .unwrap();
+            thread::sleep(Duration::from_millis(20));
+            client
+                .write_all(&[TelnetCommand::SB.to_byte(), naws, 0, 100, 0, 30, IAC])
+                .unwrap();
+            client.write_all(&[TelnetCommand::SE.to_byte()]).unwrap();
+            client
+        });
+        let started = Instant::now();
+        assert_eq!(
+            stream
+                .negotiate_window_size(Duration::from_secs(5))
+                .unwrap(),
+            Some(WindowSize::new(100, 30))
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        report.join().unwrap();
+    }
+
+    #[test]
+    fn test_stream_over_memory_transport() {
+        let mut input = b"hi".to_vec();
+        input.extend_from_slice(&[IAC, TelnetCommand::AYT.to_byte()]);
+        input.extend_from_slice(&[IAC, IAC, b'!']);
+
+        let mut stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+            write_script: VecDeque::new(),
+        });
+        stream.poll_negotiation(Duration::from_millis(10)).unwrap();
+        let mut text = Vec::new();
+        stream.read_to_end(&mut text).unwrap();
+        assert_eq!(text, b"hi\xff!");
+
+        stream.write_all(b"ok\xff").unwrap();
+        let output = &stream.get_ref().output;
+        assert!(output.ends_with(b"ok\xff\xff"));
+        assert!(
+            output
+                .windows(DEFAULT_AYT_REPLY.len())
+                .any(|w| w == DEFAULT_AYT_REPLY.as_bytes())
+        );
+
+        // START_TLS needs a second handle on the socket
+        let transport = stream.into_inner();
+        assert_eq!(
+            transport.try_clone().err().unwrap().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_replies_queue_behind_earlier_output() {
+        let mut input = vec![IAC, TelnetCommand::WILL.to_byte(), 24];
+        input.extend_from_slice(b"hi");
+
+        // "ab" and the first IAC of the pair go out, then the socket fills
+        let mut stream = TelnetStream::new(MemoryTransport {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+            write_script: [Some(3), None].into(),
+        });
+        assert_eq!(stream.write(b"ab\xff").unwrap(), 3);
+        assert_eq!(stream.pending_output_len(), 1);
+
+        // The reply can't be sent, but the read still succeeds, and the
+        // follow-up request for the terminal type isn't lost
+        let mut buf = [0u8; 8];
+        let n = stream.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"hi");
+        assert!(stream.is_option_enabled(Side::Remote, TelnetOption::TERMINAL_TYPE));
+        assert_eq!(stream.pending_output_len(), 1 + 3 + 6);
+
+        stream.write_all(b"c").unwrap();
+        assert_eq!(
+            stream.get_ref().output,
+            [
+                b'a',
+                b'b',
+                IAC,
+                IAC, // the earlier write, IAC still whole
+                IAC,
+                253,
+                24, // DO TERMINAL-TYPE
+                IAC,
+                250,
+                24,
+                SubNegotiationCommand::Send as u8,
+                IAC,
+                240, // TERMINAL-TYPE SEND
+                b'c',
+            ]
+        );
+        assert_eq!(stream.pending_output_len(), 0);
+    }
+
+    #[test]
+    fn test_heavy_writes_interleaved_with_negotiation() {
+        const MARKS: usize = 200;
+        // Every byte value, IAC included, but no newlines to translate
+        let payload: Vec<u8> = (0..256 * 1024)
+            .map(|i| (i % 256) as u8)
+            .map(|b| if b == b'\r' || b == b'\n' { b'.' } else { b })
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The client asks for a timing mark over and over while the server
+        // writes, then reads everything that came back
+        let client = thread::spawn(move || {
+            let mut socket = TcpStream::connect(addr).unwrap();
+            let mut sender = socket.try_clone().unwrap();
+            let marks = thread::spawn(move || {
+                for _ in 0..MARKS {
+                    sender
+                        .write_all(&[IAC, TelnetCommand::DO.to_byte(), 6])
+                        .unwrap();
+                    thread::sleep(Duration::from_micros(200));
+                }
+                sender.shutdown(Shutdown::Write).unwrap();
+            });
+
+            let mut received = Vec::new();
+            socket.read_to_end(&mut received).unwrap();
+            marks.join().unwrap();
+            received
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let mut stream = TelnetStream::new(socket);
+        let mut buf = [0u8; 64];
+        let mut peer_done = false;
+        let mut read_some =
+            |stream: &mut TelnetStream, peer_done: &mut bool| match stream.read(&mut buf) {
+                Ok(0) => *peer_done = true,
+                Ok(_) => panic!("peer sent data"),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("{}", e),
+            };
+
+        let mut rest = &payload[..];
+        while !rest.is_empty() {
+            match stream.write(&rest[..rest.len().min(1000)]) {
+                Ok(n) => rest = &rest[n..],
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_micros(100))
+                }
+                Err(e) => panic!("{}", e),
+            }
+            if !peer_done {
+                read_some(&mut stream, &mut peer_done);
+            }
+        }
+        while !peer_done || stream.pending_output_len() > 0 {
+            if !peer_done {
+                read_some(&mut stream, &mut peer_done);
+            }
+            match stream.flush() {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("{}", e),
+            }
+            thread::sleep(Duration::from_micros(100));
+        }
+        stream.shutdown(Shutdown::Both).unwrap();
+
+        let received = client.join().unwrap();
+        let result = TelnetParser::new().parse(&received);
+        assert_eq!(result.bytes_consumed, received.len());
+        assert!(result.data == payload, "data was torn or reordered");
+        let answers = result
+            .sequences
+            .iter()
+            .filter(|sequence| {
+                **sequence
+                    == TelnetSequence::Negotiation {
+                        command: TelnetCommand::WILL,
+                        option: TelnetOption::TIMING_MARK,
+                    }
+            })
+            .count();
+        assert_eq!(answers, MARKS);
+        // Nothing else but GAs and the payload's escaped IACs
+        assert!(result.sequences.iter().all(|sequence| matches!(
+            sequence,
+            TelnetSequence::Negotiation { .. }
+                | TelnetSequence::Command(TelnetCommand::GA)
+                | TelnetSequence::EscapedData(IAC)
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stream_over_unix_socket() {
+        let (server_end, mut client_end) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut stream = TelnetStream::new(server_end);
+
+        // Read timeouts reach the socket, so waits end on time
+        let started = Instant::now();
+        stream.poll_negotiation(Duration::from_millis(50)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(stream.read_timeout().unwrap(), None);
+
+        client_end
+            .write_all(&[b'o', IAC, TelnetCommand::NOP.to_byte(), b'k'])
+            .unwrap();
+        let mut buf = [0u8; 8];
+        let mut text = Vec::new();
+        while text.len() < 2 {
+            let n = stream.read(&mut buf).unwrap();
+            text.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(text, b"ok");
+
+        stream.shutdown(Shutdown::Both).unwrap();
+        let mut rest = Vec::new();
+        client_end.read_to_end(&mut rest).unwrap();
+    }
+
+    #[test]
+    fn test_telnet_stream_creation() {
+        let peer = MockStream::new();
+        let stream = TelnetStream::new(peer.clone());
 
-        assert!(!negotiator.is_enabled(Side::Local, TelnetOption::ECHO));
-        assert!(!negotiator.is_enabled(Side::Remote, TelnetOption::ECHO));
+        // Nothing is negotiated or sent until someone asks
+        assert!(!stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+        assert!(!stream.is_option_enabled(Side::Remote, TelnetOption::ECHO));
+        assert_eq!(stream.role(), Role::Server);
+        assert!(peer.written().is_empty());
     }
 
     #[test]
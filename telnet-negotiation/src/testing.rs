@@ -0,0 +1,343 @@
+//! # Testing Without Sockets
+//!
+//! [`MockStream`] is an in-memory [`Transport`] for driving a
+//! [`TelnetStream`](crate::TelnetStream) through whole negotiations in a
+//! unit test: script what the peer sends, run the stream, then look at what
+//! it wrote back. No ports, threads or timing involved.
+//!
+//! ```rust
+//! use std::io::Read;
+//! use telnet_negotiation::testing::MockStream;
+//! use telnet_negotiation::{Side, TelnetCommand, TelnetOption, TelnetSequence, TelnetStream};
+//!
+//! let peer = MockStream::new();
+//! // IAC DO SUPPRESS-GO-AHEAD, then some text
+//! peer.push_input(b"\xff\xfd\x03hello");
+//!
+//! let mut stream = TelnetStream::new(peer.clone());
+//! let mut text = String::new();
+//! stream.read_to_string(&mut text).unwrap();
+//! assert_eq!(text, "hello");
+//!
+//! assert!(stream.is_option_enabled(Side::Local, TelnetOption::SUPPRESS_GO_AHEAD));
+//! assert_eq!(
+//!     peer.written_sequences(),
+//!     [TelnetSequence::Negotiation {
+//!         command: TelnetCommand::WILL,
+//!         option: TelnetOption::SUPPRESS_GO_AHEAD,
+//!     }]
+//! );
+//! ```
+//!
+//! A stream only offers or accepts an option it has a handler for, which
+//! the `rfc-audit` feature checks. [`SilentHandler`] stands in for one in a
+//! test that doesn't care what the option does.
+//!
+//! Clones share one connection, so the test keeps a handle while the stream
+//! owns another. No `read` returns bytes from two pushed chunks, so a
+//! sequence split across chunks arrives split, as it might from a socket.
+//! Once the script runs out reads return end of file, or `WouldBlock` if
+//! the stream is [held open](MockStream::hold_open) for more input later.
+
+use crate::options::{OptionError, SubNegotiationCommand, TelnetOptionHandler};
+use crate::parser::{ParserLimits, TelnetParser};
+use crate::protocol::{TelnetOption, TelnetSequence};
+use crate::transport::Transport;
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// One step of what the peer sends
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Incoming {
+    Bytes(Vec<u8>),
+    WouldBlock,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    incoming: VecDeque<Incoming>,
+    written: Vec<u8>,
+    hold_open: bool,
+    read_timeout: Option<Duration>,
+    read_closed: bool,
+    write_closed: bool,
+}
+
+/// An in-memory connection with scripted input and captured output
+#[derive(Debug, Clone, Default)]
+pub struct MockStream {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockStream {
+    /// A connection with nothing to read yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A connection whose peer sends `input` in one chunk, then hangs up
+    pub fn with_input(input: &[u8]) -> Self {
+        let stream = Self::new();
+        stream.push_input(input);
+        stream
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        // A test that panicked mid-read has already failed
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue `bytes` as the next chunk the peer sends
+    pub fn push_input(&self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.state()
+                .incoming
+                .push_back(Incoming::Bytes(bytes.to_vec()));
+        }
+    }
+
+    /// Queue a read that fails with `WouldBlock`, as a nonblocking socket
+    /// or one whose read timeout ran out would
+    pub fn push_would_block(&self) {
+        self.state().incoming.push_back(Incoming::WouldBlock);
+    }
+
+    /// Whether reads past the end of the script wait for more input
+    /// (`WouldBlock`) rather than return end of file
+    pub fn hold_open(&self, open: bool) {
+        self.state().hold_open = open;
+    }
+
+    /// Scripted input not read yet, in bytes
+    pub fn unread_len(&self) -> usize {
+        self.state()
+            .incoming
+            .iter()
+            .map(|step| match step {
+                Incoming::Bytes(bytes) => bytes.len(),
+                Incoming::WouldBlock => 0,
+            })
+            .sum()
+    }
+
+    /// Everything written so far
+    pub fn written(&self) -> Vec<u8> {
+        self.state().written.clone()
+    }
+
+    /// Everything written since last taken, clearing it
+    pub fn take_written(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state().written)
+    }
+
+    /// The telnet sequences among what was written, in order
+    pub fn written_sequences(&self) -> Vec<TelnetSequence> {
+        self.parse_written().sequences
+    }
+
+    /// The application data among what was written, unescaped
+    pub fn written_data(&self) -> Vec<u8> {
+        self.parse_written().data
+    }
+
+    fn parse_written(&self) -> crate::parser::ParseResult {
+        TelnetParser::with_limits(ParserLimits::unlimited()).parse(&self.written())
+    }
+
+    /// Whether the stream shut down the reading side
+    pub fn is_read_shut_down(&self) -> bool {
+        self.state().read_closed
+    }
+
+    /// Whether the stream shut down the writing side
+    pub fn is_write_shut_down(&self) -> bool {
+        self.state().write_closed
+    }
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state();
+        if state.read_closed || buf.is_empty() {
+            return Ok(0);
+        }
+        match state.incoming.pop_front() {
+            Some(Incoming::Bytes(mut bytes)) => {
+                let n = bytes.len().min(buf.len());
+                buf[..n].copy_from_slice(&bytes[..n]);
+                if n < bytes.len() {
+                    bytes.drain(..n);
+                    state.incoming.push_front(Incoming::Bytes(bytes));
+                }
+                Ok(n)
+            }
+            Some(Incoming::WouldBlock) => Err(io::ErrorKind::WouldBlock.into()),
+            None if state.hold_open => Err(io::ErrorKind::WouldBlock.into()),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state();
+        if state.write_closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        state.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MockStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.state().read_timeout)
+    }
+
+    /// Recorded for [`Transport::read_timeout`]; reads never wait
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.state().read_timeout = dur;
+        Ok(())
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        let mut state = self.state();
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            state.read_closed = true;
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            state.write_closed = true;
+        }
+        Ok(())
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::negotiation::Side;
+    use crate::protocol::{IAC, TelnetCommand, TelnetOption};
+    use crate::stream::TelnetStream;
+
+    #[test]
+    fn test_chunks_come_back_in_order_and_split_to_fit() {
+        let mut mock = MockStream::new();
+        mock.push_input(b"abc");
+        mock.push_would_block();
+        mock.push_input(b"d");
+        assert_eq!(mock.unread_len(), 4);
+
+        let mut buf = [0; 2];
+        assert_eq!(mock.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf, b"ab");
+        assert_eq!(mock.read(&mut buf).unwrap(), 1);
+        assert_eq!(
+            mock.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(mock.read(&mut buf).unwrap(), 1);
+        assert_eq!(mock.read(&mut buf).unwrap(), 0);
+
+        mock.hold_open(true);
+        assert_eq!(
+            mock.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn test_negotiation_round_trip() {
+        let peer = MockStream::new();
+        peer.hold_open(true);
+        let mut stream = TelnetStream::new(peer.clone());
+
+        stream.request_echo_off().unwrap();
+        assert_eq!(
+            peer.take_written(),
+            [
+                IAC,
+                TelnetCommand::WILL.to_byte(),
+                TelnetOption::ECHO.to_byte()
+            ]
+        );
+
+        peer.push_input(&[
+            IAC,
+            TelnetCommand::DO.to_byte(),
+            TelnetOption::ECHO.to_byte(),
+        ]);
+        stream.poll_negotiation(Duration::from_secs(1)).unwrap();
+        assert!(stream.is_option_enabled(Side::Local, TelnetOption::ECHO));
+        // Our WILL was answered, so nothing more goes out
+        assert!(peer.written().is_empty());
+
+        stream.write_all(b"x\xff").unwrap();
+        assert_eq!(peer.written_data(), b"x\xff");
+        assert!(
+            peer.written_sequences()
+                .iter()
+                .all(|sequence| matches!(sequence, TelnetSequence::EscapedData(_)))
+        );
+    }
+
+    #[test]
+    fn test_shutdown_is_recorded() {
+        let mut mock = MockStream::with_input(b"unread");
+        mock.shutdown(Shutdown::Both).unwrap();
+        assert!(mock.is_read_shut_down());
+        assert!(mock.is_write_shut_down());
+        assert_eq!(mock.read(&mut [0; 8]).unwrap(), 0);
+        assert_eq!(
+            mock.write(b"x").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
+
+/// A handler for an option that takes its sub-negotiations and answers
+/// nothing
+#[derive(Debug, Clone, Copy)]
+pub struct SilentHandler(pub TelnetOption);
+
+impl TelnetOptionHandler for SilentHandler {
+    fn option_code(&self) -> TelnetOption {
+        self.0
+    }
+
+    fn handle_subnegotiation(&mut self, _data: &[u8]) -> Result<Vec<u8>, OptionError> {
+        Ok(Vec::new())
+    }
+
+    fn generate_subnegotiation(
+        &self,
+        _command: SubNegotiationCommand,
+    ) -> Result<Vec<u8>, OptionError> {
+        Ok(Vec::new())
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {}
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,121 @@
+//! # Transports
+//!
+//! [`crate::TelnetStream`] runs over anything that implements [`Transport`]:
+//! a byte stream (`Read + Write`) plus the few socket controls the stream
+//! needs for itself. It uses read timeouts to wait a bounded time for
+//! negotiation answers, shutdown to drop a peer that goes over its memory
+//! budget, and a clone of the socket to hand to the TLS backend on
+//! START_TLS.
+//!
+//! Every control has a default, so for transports that have none of them
+//! (pipes, in-memory test doubles) an empty impl is enough:
+//!
+//! ```rust
+//! use std::io::{self, Cursor, Read, Write};
+//! use telnet_negotiation::{TelnetStream, Transport};
+//!
+//! struct Loopback(Cursor<Vec<u8>>);
+//!
+//! impl Read for Loopback {
+//!     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+//!         self.0.read(buf)
+//!     }
+//! }
+//!
+//! impl Write for Loopback {
+//!     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+//!         Ok(buf.len())
+//!     }
+//!     fn flush(&mut self) -> io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! impl Transport for Loopback {}
+//!
+//! let mut stream = TelnetStream::new(Loopback(Cursor::new(b"hi\xff\xf1!".to_vec())));
+//! let mut text = String::new();
+//! stream.read_to_string(&mut text).unwrap();
+//! assert_eq!(text, "hi!");
+//! ```
+//!
+//! `TcpStream` and, on Unix, `UnixStream` implement it already.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+/// A byte stream a [`crate::TelnetStream`] can run over
+pub trait Transport: Read + Write {
+    /// Current read timeout; `None` means reads block
+    ///
+    /// Transports without timeouts always block, and say so.
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// Make reads give up with `WouldBlock` or `TimedOut` after `dur`
+    ///
+    /// Transports without timeouts ignore this. Waits such as
+    /// [`crate::TelnetStream::poll_negotiation`] then last until the peer
+    /// sends something or the transport runs dry.
+    fn set_read_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Close one or both directions of the connection
+    fn shutdown(&self, _how: Shutdown) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// A second handle on the same connection
+    ///
+    /// START_TLS gives this to the TLS backend; transports that can't be
+    /// cloned can't be upgraded.
+    fn try_clone(&self) -> io::Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "transport can't be cloned",
+        ))
+    }
+}
+
+impl Transport for TcpStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, dur)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl Transport for std::os::unix::net::UnixStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        std::os::unix::net::UnixStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, dur)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        std::os::unix::net::UnixStream::shutdown(self, how)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        std::os::unix::net::UnixStream::try_clone(self)
+    }
+}
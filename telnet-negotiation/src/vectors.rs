@@ -0,0 +1,350 @@
+//! # Wire-Compatibility Vectors
+//!
+//! Canonical byte-level test vectors for the telnet wire format: bytes a
+//! peer sends and the data and sequences they decode to, and sequences
+//! and the bytes they encode to. They ship with the crate as
+//! `vectors/wire.txt` so that other implementations, and rewrites of this
+//! one, can check they stay byte-exact. The file's header describes the
+//! format; [`parse`] reads files written in it.
+//!
+//! Decoding needs the `parser` feature:
+//!
+//! ```rust
+//! # #[cfg(feature = "parser")]
+//! # {
+//! use telnet_negotiation::parser::TelnetParser;
+//! use telnet_negotiation::vectors;
+//!
+//! for vector in vectors::load().decode {
+//!     let result = TelnetParser::new().parse(&vector.input);
+//!     assert_eq!(vectors::events(&result), vector.expected, "{}", vector.name);
+//! }
+//! for vector in vectors::load().encode {
+//!     assert_eq!(vector.sequence.to_bytes(), vector.output, "{}", vector.name);
+//! }
+//! # }
+//! ```
+
+use crate::protocol::{TelnetCommand, TelnetOption, TelnetSequence};
+
+use std::fmt;
+
+/// The vectors shipped with the crate, in the format [`parse`] reads
+pub const WIRE_VECTORS: &str = include_str!("../vectors/wire.txt");
+
+/// What decoding produces, in stream order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireEvent {
+    /// Application data
+    Data(Vec<u8>),
+    /// A telnet sequence
+    Sequence(TelnetSequence),
+}
+
+/// Bytes from a peer and what they should decode to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeVector {
+    pub name: String,
+    pub input: Vec<u8>,
+    /// Data and sequences in order, adjacent data joined into one event
+    pub expected: Vec<WireEvent>,
+}
+
+/// A sequence and the bytes it should encode to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeVector {
+    pub name: String,
+    pub sequence: TelnetSequence,
+    pub output: Vec<u8>,
+}
+
+/// Every vector in a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VectorSet {
+    pub decode: Vec<DecodeVector>,
+    pub encode: Vec<EncodeVector>,
+}
+
+/// A line of a vector file that couldn't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorError {
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+/// The vectors shipped with the crate
+pub fn load() -> VectorSet {
+    parse(WIRE_VECTORS).expect("shipped vectors are valid")
+}
+
+/// Read vectors in the `vectors/wire.txt` format
+pub fn parse(text: &str) -> Result<VectorSet, VectorError> {
+    enum Current {
+        None,
+        Decode(DecodeVector),
+        Encode {
+            name: String,
+            sequence: Option<TelnetSequence>,
+            output: Option<Vec<u8>>,
+        },
+    }
+
+    fn finish(current: Current, set: &mut VectorSet, line: usize) -> Result<(), VectorError> {
+        match current {
+            Current::None => {}
+            Current::Decode(vector) => set.decode.push(vector),
+            Current::Encode {
+                name,
+                sequence: Some(sequence),
+                output: Some(output),
+            } => set.encode.push(EncodeVector {
+                name,
+                sequence,
+                output,
+            }),
+            Current::Encode { name, .. } => {
+                return Err(VectorError {
+                    line,
+                    message: format!("encode {} needs one seq and one out", name),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut set = VectorSet::default();
+    let mut current = Current::None;
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| VectorError {
+            line: number,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("decode ") {
+            finish(current, &mut set, number)?;
+            current = Current::Decode(DecodeVector {
+                name: name.trim().to_string(),
+                input: Vec::new(),
+                expected: Vec::new(),
+            });
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("encode ") {
+            finish(current, &mut set, number)?;
+            current = Current::Encode {
+                name: name.trim().to_string(),
+                sequence: None,
+                output: None,
+            };
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(format!("expected key = value, got '{}'", line)));
+        };
+        let value = value.trim();
+        match (&mut current, key.trim()) {
+            (Current::Decode(vector), "in") => {
+                vector.input.extend(parse_bytes(value).map_err(error)?)
+            }
+            (Current::Decode(vector), "data") => {
+                let bytes = parse_bytes(value).map_err(error)?;
+                push_data(&mut vector.expected, &bytes);
+            }
+            (Current::Decode(vector), "seq") => vector
+                .expected
+                .push(WireEvent::Sequence(parse_sequence(value).map_err(error)?)),
+            (Current::Encode { sequence, .. }, "seq") if sequence.is_none() => {
+                *sequence = Some(parse_sequence(value).map_err(error)?);
+            }
+            (Current::Encode { output, .. }, "out") if output.is_none() => {
+                *output = Some(parse_bytes(value).map_err(error)?);
+            }
+            (Current::None, _) => return Err(error("key outside a vector".to_string())),
+            (_, key) => return Err(error(format!("unexpected key '{}'", key))),
+        }
+    }
+    finish(current, &mut set, text.lines().count())?;
+
+    Ok(set)
+}
+
+/// A parse result as [`WireEvent`]s, for comparing with a [`DecodeVector`]
+///
+/// IAC IAC appears as data only, as the vectors expect it.
+#[cfg(feature = "parser")]
+pub fn events(result: &crate::parser::ParseResult) -> Vec<WireEvent> {
+    let mut events = Vec::new();
+    let mut taken = 0;
+    for (sequence, &offset) in result.sequences.iter().zip(&result.sequence_offsets) {
+        push_data(&mut events, &result.data[taken..offset]);
+        taken = offset;
+        if !matches!(sequence, TelnetSequence::EscapedData(_)) {
+            events.push(WireEvent::Sequence(sequence.clone()));
+        }
+    }
+    push_data(&mut events, &result.data[taken..]);
+    events
+}
+
+/// Add data to `events`, joining it onto data just before it
+pub fn push_data(events: &mut Vec<WireEvent>, data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    match events.last_mut() {
+        Some(WireEvent::Data(last)) => last.extend_from_slice(data),
+        _ => events.push(WireEvent::Data(data.to_vec())),
+    }
+}
+
+/// Hex bytes and "quoted" text
+pub(crate) fn parse_bytes(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut rest = value.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| format!("unclosed quote in '{}'", value))?;
+            bytes.extend_from_slice(&quoted.as_bytes()[..end]);
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let token = &rest[..end];
+            if token.len() != 2 {
+                return Err(format!("'{}' is not a two-digit hex byte", token));
+            }
+            let byte = u8::from_str_radix(token, 16)
+                .map_err(|_| format!("'{}' is not a two-digit hex byte", token))?;
+            bytes.push(byte);
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(bytes)
+}
+
+/// `GA`, `WILL 1`, `SB 31 00 50 00 18` or `ESCAPED ff`
+pub(crate) fn parse_sequence(value: &str) -> Result<TelnetSequence, String> {
+    let (name, rest) = value.split_once(' ').unwrap_or((value, ""));
+    let rest = rest.trim();
+
+    if name == "ESCAPED" {
+        let byte = parse_bytes(rest)?;
+        return match byte[..] {
+            [byte] => Ok(TelnetSequence::EscapedData(byte)),
+            _ => Err(format!("ESCAPED takes one byte, got '{}'", rest)),
+        };
+    }
+
+    let command = (0..=u8::MAX)
+        .filter_map(TelnetCommand::from_byte)
+        .find(|command| format!("{:?}", command) == name)
+        .ok_or_else(|| format!("unknown command '{}'", name))?;
+
+    if command == TelnetCommand::SB {
+        let (option, data) = rest.split_once(' ').unwrap_or((rest, ""));
+        return Ok(TelnetSequence::SubNegotiation {
+            option: parse_option(option)?,
+            data: parse_bytes(data)?,
+        });
+    }
+    if command.is_negotiation_command() {
+        return Ok(TelnetSequence::Negotiation {
+            command,
+            option: parse_option(rest)?,
+        });
+    }
+    if !rest.is_empty() {
+        return Err(format!("{} takes no arguments", name));
+    }
+    Ok(TelnetSequence::Command(command))
+}
+
+/// A decimal option number
+pub(crate) fn parse_option(value: &str) -> Result<TelnetOption, String> {
+    value
+        .parse::<u8>()
+        .map(TelnetOption::new)
+        .map_err(|_| format!("'{}' is not a decimal option number", value))
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::parser::TelnetParser;
+
+    #[test]
+    fn test_shipped_vectors_load() {
+        let set = load();
+        assert!(set.decode.len() >= 10);
+        assert!(set.encode.len() >= 5);
+        assert!(set.decode.iter().any(|v| v.name == "mixed"));
+    }
+
+    #[test]
+    fn test_parser_matches_decode_vectors() {
+        for vector in load().decode {
+            let result = TelnetParser::new().parse(&vector.input);
+            assert_eq!(result.bytes_consumed, vector.input.len(), "{}", vector.name);
+            assert_eq!(events(&result), vector.expected, "{}", vector.name);
+        }
+    }
+
+    #[test]
+    fn test_parser_matches_decode_vectors_byte_by_byte() {
+        for vector in load().decode {
+            let mut parser = TelnetParser::new();
+            let mut actual = Vec::new();
+            for byte in &vector.input {
+                for event in events(&parser.parse(std::slice::from_ref(byte))) {
+                    match event {
+                        WireEvent::Data(data) => push_data(&mut actual, &data),
+                        sequence => actual.push(sequence),
+                    }
+                }
+            }
+            assert_eq!(actual, vector.expected, "{}", vector.name);
+        }
+    }
+
+    #[test]
+    fn test_sequences_match_encode_vectors() {
+        for vector in load().encode {
+            assert_eq!(vector.sequence.to_bytes(), vector.output, "{}", vector.name);
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_bad_lines() {
+        let error = parse("decode x\nin = fff\n").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = parse("encode x\nseq = FROB\nout = 00\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("FROB"));
+
+        let error = parse("encode x\nseq = GA\n").unwrap_err();
+        assert!(error.message.contains("needs one seq and one out"));
+
+        assert_eq!(
+            parse("decode x\nin = \"a b\" 0a\n").unwrap().decode[0].input,
+            b"a b\n"
+        );
+    }
+}
@@ -0,0 +1,792 @@
+//! # Telnet over WebSocket
+//!
+//! Browser clients can't open a raw TCP socket, so web MUD clients and web
+//! BBS terminals speak telnet over a WebSocket instead: the telnet byte
+//! stream, IAC sequences and all, carried in binary frames. Wrapping an
+//! accepted connection in [`WebSocketTransport`] answers the HTTP upgrade
+//! and then hides the framing, so a [`TelnetStream`](crate::TelnetStream)
+//! runs over it like over any other [`Transport`]:
+//!
+//! ```rust,no_run
+//! use std::io::Write;
+//! use std::net::TcpListener;
+//! use telnet_negotiation::{TelnetStream, WebSocketTransport};
+//!
+//! let listener = TcpListener::bind("0.0.0.0:8080")?;
+//! let (socket, _) = listener.accept()?;
+//! let transport = WebSocketTransport::accept(socket)?;
+//! println!("browser caller on {}", transport.request().path);
+//! let mut stream = TelnetStream::new(transport);
+//! stream.write_all(b"Welcome!\r\n")?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! Each write goes out as one binary frame. Binary and text frames from the
+//! client are both read as telnet bytes, since some clients send text.
+//! Pings are answered, and a close from the client is answered and then
+//! read as end of file. Dropping the transport sends a close of its own.
+//!
+//! Only plain `ws://` is handled; put a TLS-terminating proxy in front for
+//! `wss://`. The transport can't be cloned, so START_TLS is not offered over it.
+
+use crate::transport::Transport;
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+/// Subprotocols [`WebSocketTransport::accept`] agrees to, in order of preference
+pub const SUBPROTOCOLS: &[&str] = &["telnet", "binary"];
+
+/// Longest upgrade request accepted, headers included
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// Most data sent in one frame; longer writes are split across frames
+const MAX_FRAME: usize = 64 * 1024;
+
+/// Appended to the client's key before hashing (RFC 6455 section 1.3)
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// What the client asked for when it upgraded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebSocketRequest {
+    /// The request path, such as `/telnet`
+    pub path: String,
+    /// The page the browser connected from, if it said
+    pub origin: Option<String>,
+    /// The subprotocol both sides agreed on, if any
+    pub protocol: Option<String>,
+}
+
+/// Where reading the client's frames is up to
+#[derive(Debug)]
+enum ReadState {
+    /// Collecting a frame header; `len` bytes of it so far
+    Header { bytes: [u8; 14], len: usize },
+    /// Inside a frame's payload
+    Payload {
+        opcode: u8,
+        mask: [u8; 4],
+        offset: u64,
+        remaining: u64,
+    },
+    /// The close handshake is done, or the client broke the protocol
+    Closed,
+}
+
+/// A WebSocket connection carrying a telnet byte stream
+///
+/// Made by [`WebSocketTransport::accept`], which answers the client's
+/// upgrade request. See the [module documentation](self).
+pub struct WebSocketTransport<T: Transport = TcpStream> {
+    inner: T,
+    request: WebSocketRequest,
+    /// Bytes read past the end of the upgrade request
+    early: Vec<u8>,
+    state: ReadState,
+    /// A fragmented data message is under way
+    fragmented: bool,
+    /// The payload of the control frame being read
+    control: Vec<u8>,
+    /// Framed bytes the transport hasn't taken yet
+    outgoing: Vec<u8>,
+    close_sent: bool,
+}
+
+impl<T: Transport> WebSocketTransport<T> {
+    /// Answer the upgrade request waiting on `inner`, agreeing to one of
+    /// [`SUBPROTOCOLS`] if the client offers any
+    ///
+    /// Blocks until the whole request has arrived, so set a read timeout
+    /// on `inner` first to bound the wait. A request that isn't a valid
+    /// WebSocket upgrade is answered with `400 Bad Request` and fails with
+    /// `InvalidData`.
+    pub fn accept(inner: T) -> io::Result<Self> {
+        Self::accept_with(inner, SUBPROTOCOLS)
+    }
+
+    /// Like [`WebSocketTransport::accept`], agreeing to the first of
+    /// `protocols` the client offers
+    pub fn accept_with(mut inner: T, protocols: &[&str]) -> io::Result<Self> {
+        let (head, early) = read_request(&mut inner)?;
+        let (request, key) = match parse_request(&head, protocols) {
+            Ok(parsed) => parsed,
+            Err(reason) => {
+                let _ = inner.write_all(
+                    b"HTTP/1.1 400 Bad Request\r\n\
+                      Sec-WebSocket-Version: 13\r\n\
+                      Content-Length: 0\r\n\
+                      Connection: close\r\n\r\n",
+                );
+                let _ = inner.flush();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bad WebSocket upgrade: {}", reason),
+                ));
+            }
+        };
+
+        let mut hashed = key.into_bytes();
+        hashed.extend_from_slice(ACCEPT_GUID.as_bytes());
+        let mut response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n",
+            base64(&sha1(&hashed))
+        );
+        if let Some(protocol) = &request.protocol {
+            response.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", protocol));
+        }
+        response.push_str("\r\n");
+        inner.write_all(response.as_bytes())?;
+        inner.flush()?;
+
+        Ok(Self {
+            inner,
+            request,
+            early,
+            state: ReadState::Header {
+                bytes: [0; 14],
+                len: 0,
+            },
+            fragmented: false,
+            control: Vec::new(),
+            outgoing: Vec::new(),
+            close_sent: false,
+        })
+    }
+
+    /// The upgrade request the connection was opened with
+    pub fn request(&self) -> &WebSocketRequest {
+        &self.request
+    }
+
+    /// The underlying transport
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// The underlying transport, mutably
+    ///
+    /// Reading or writing it directly will corrupt the framing.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Read from the bytes left over from the handshake, then the transport
+    fn read_raw(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.early.is_empty() {
+            return self.inner.read(buf);
+        }
+        let n = buf.len().min(self.early.len());
+        buf[..n].copy_from_slice(&self.early[..n]);
+        self.early.drain(..n);
+        Ok(n)
+    }
+
+    /// Write out as much of the queued frames as the transport takes
+    fn drain(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.inner.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue a frame and send what the transport takes now
+    ///
+    /// Control frames go out between reads, so a transport that is busy
+    /// just leaves them queued for the next write or flush.
+    fn send_control(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        push_frame(&mut self.outgoing, opcode, payload);
+        match self.drain() {
+            Err(e) if !is_retry(&e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn send_close(&mut self, code: u16) -> io::Result<()> {
+        if self.close_sent {
+            return Ok(());
+        }
+        self.close_sent = true;
+        self.send_control(OP_CLOSE, &code.to_be_bytes())
+    }
+
+    /// Close with a protocol error and fail the read
+    fn protocol_error(&mut self, reason: &str) -> io::Error {
+        self.state = ReadState::Closed;
+        let _ = self.send_close(CLOSE_PROTOCOL_ERROR);
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("WebSocket protocol error: {}", reason),
+        )
+    }
+
+    /// Check a frame's first two bytes
+    fn check_header(&mut self, first: u8, second: u8) -> io::Result<()> {
+        let opcode = first & 0x0F;
+        let fin = first & 0x80 != 0;
+        if first & 0x70 != 0 {
+            return Err(self.protocol_error("reserved bits set"));
+        }
+        if second & 0x80 == 0 {
+            return Err(self.protocol_error("client frame not masked"));
+        }
+        match opcode {
+            OP_CONTINUATION if !self.fragmented => {
+                Err(self.protocol_error("continuation without a message"))
+            }
+            OP_TEXT | OP_BINARY if self.fragmented => {
+                Err(self.protocol_error("new message inside a fragmented one"))
+            }
+            OP_CONTINUATION | OP_TEXT | OP_BINARY => Ok(()),
+            OP_CLOSE | OP_PING | OP_PONG if !fin || second & 0x7F > 125 => {
+                Err(self.protocol_error("fragmented or oversized control frame"))
+            }
+            OP_CLOSE | OP_PING | OP_PONG => Ok(()),
+            _ => Err(self.protocol_error("unknown opcode")),
+        }
+    }
+
+    /// Act on a complete control frame; `true` once the connection is closed
+    fn handle_control(&mut self, opcode: u8) -> io::Result<bool> {
+        let payload = std::mem::take(&mut self.control);
+        match opcode {
+            OP_PING => self.send_control(OP_PONG, &payload)?,
+            OP_CLOSE => {
+                self.state = ReadState::Closed;
+                let code = match payload.get(..2) {
+                    Some(&[high, low]) => u16::from_be_bytes([high, low]),
+                    _ => CLOSE_NORMAL,
+                };
+                self.send_close(code)?;
+                return Ok(true);
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+impl<T: Transport> Read for WebSocketTransport<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.state {
+                ReadState::Closed => return Ok(0),
+                ReadState::Header { mut bytes, len } => {
+                    let needed = header_len(&bytes[..len]);
+                    if len < needed {
+                        let n = self.read_raw(&mut bytes[len..needed])?;
+                        if n == 0 {
+                            return Ok(0);
+                        }
+                        self.state = ReadState::Header {
+                            bytes,
+                            len: len + n,
+                        };
+                        if len < 2 && len + n >= 2 {
+                            self.check_header(bytes[0], bytes[1])?;
+                        }
+                        continue;
+                    }
+
+                    let opcode = bytes[0] & 0x0F;
+                    let (length, at) = match bytes[1] & 0x7F {
+                        126 => (u16::from_be_bytes([bytes[2], bytes[3]]) as u64, 4),
+                        127 => {
+                            let mut wide = [0; 8];
+                            wide.copy_from_slice(&bytes[2..10]);
+                            (u64::from_be_bytes(wide), 10)
+                        }
+                        short => (short as u64, 2),
+                    };
+                    let mut mask = [0; 4];
+                    mask.copy_from_slice(&bytes[at..at + 4]);
+                    if opcode < OP_CLOSE {
+                        self.fragmented = bytes[0] & 0x80 == 0;
+                    }
+                    self.state = ReadState::Payload {
+                        opcode,
+                        mask,
+                        offset: 0,
+                        remaining: length,
+                    };
+                }
+                ReadState::Payload {
+                    opcode,
+                    mask,
+                    offset,
+                    remaining,
+                } => {
+                    if remaining == 0 {
+                        self.state = ReadState::Header {
+                            bytes: [0; 14],
+                            len: 0,
+                        };
+                        if opcode >= OP_CLOSE && self.handle_control(opcode)? {
+                            return Ok(0);
+                        }
+                        continue;
+                    }
+
+                    let n = if opcode >= OP_CLOSE {
+                        let mut piece = [0; 125];
+                        let n = self.read_raw(&mut piece[..remaining as usize])?;
+                        self.control.extend_from_slice(&piece[..n]);
+                        n
+                    } else {
+                        let want = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+                        self.read_raw(&mut buf[..want])?
+                    };
+                    if n == 0 {
+                        return Ok(0);
+                    }
+                    self.state = ReadState::Payload {
+                        opcode,
+                        mask,
+                        offset: offset + n as u64,
+                        remaining: remaining - n as u64,
+                    };
+
+                    if opcode < OP_CLOSE {
+                        for (i, byte) in buf[..n].iter_mut().enumerate() {
+                            *byte ^= mask[((offset + i as u64) % 4) as usize];
+                        }
+                        return Ok(n);
+                    }
+                    let start = self.control.len() - n;
+                    for (i, byte) in self.control[start..].iter_mut().enumerate() {
+                        *byte ^= mask[((offset + i as u64) % 4) as usize];
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Transport> Write for WebSocketTransport<T> {
+    /// Send up to 64 KiB of `buf` as one binary frame
+    ///
+    /// Once a frame is queued the write counts, even if the transport
+    /// only takes part of it now; the rest goes out on the next write or
+    /// flush.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.close_sent {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "WebSocket connection closed",
+            ));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.drain()?;
+        let n = buf.len().min(MAX_FRAME);
+        push_frame(&mut self.outgoing, OP_BINARY, &buf[..n]);
+        match self.drain() {
+            Err(e) if !is_retry(&e) => Err(e),
+            _ => Ok(n),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.inner.flush()
+    }
+}
+
+impl<T: Transport> Transport for WebSocketTransport<T> {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.inner.read_timeout()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(dur)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+}
+
+impl<T: Transport> Drop for WebSocketTransport<T> {
+    fn drop(&mut self) {
+        let _ = self.send_close(CLOSE_NORMAL);
+        let _ = self.inner.flush();
+    }
+}
+
+impl<T: Transport> std::fmt::Debug for WebSocketTransport<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocketTransport")
+            .field("request", &self.request)
+            .field("state", &self.state)
+            .field("close_sent", &self.close_sent)
+            .finish_non_exhaustive()
+    }
+}
+
+fn is_retry(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+    )
+}
+
+/// How long the header starting with `bytes` is, once its first two bytes are known
+fn header_len(bytes: &[u8]) -> usize {
+    match bytes {
+        [_, second, ..] => {
+            let extended = match second & 0x7F {
+                126 => 2,
+                127 => 8,
+                _ => 0,
+            };
+            2 + extended + 4
+        }
+        _ => 2,
+    }
+}
+
+/// Append a final, unmasked server frame
+fn push_frame(out: &mut Vec<u8>, opcode: u8, payload: &[u8]) {
+    out.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => out.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            out.push(126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(payload);
+}
+
+/// Read up to the blank line ending the request's headers, returning the
+/// headers and whatever came after them
+fn read_request<T: Read>(inner: &mut T) -> io::Result<(String, Vec<u8>)> {
+    let mut request = Vec::new();
+    let mut chunk = [0; 1024];
+    loop {
+        let n = match inner.read(&mut chunk) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let searched = request.len().saturating_sub(3);
+        request.extend_from_slice(&chunk[..n]);
+        if let Some(end) = request[searched..]
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+        {
+            let early = request.split_off(searched + end + 4);
+            let head = String::from_utf8(request)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "request isn't UTF-8"))?;
+            return Ok((head, early));
+        }
+        if request.len() > MAX_REQUEST {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "WebSocket upgrade request too long",
+            ));
+        }
+    }
+}
+
+/// Check the upgrade request, returning what it asked for and its key
+fn parse_request(
+    head: &str,
+    protocols: &[&str],
+) -> Result<(WebSocketRequest, String), &'static str> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some("GET"), Some(path), Some(version)) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return Err("not a GET request");
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err("not HTTP/1.1");
+    }
+
+    let mut request = WebSocketRequest {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    let mut upgrade = false;
+    let mut connection = false;
+    let mut version = None;
+    let mut key = None;
+    let mut offered = Vec::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let tokens = || value.split(',').map(str::trim);
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => upgrade = tokens().any(|t| t.eq_ignore_ascii_case("websocket")),
+            "connection" => connection = tokens().any(|t| t.eq_ignore_ascii_case("upgrade")),
+            "sec-websocket-version" => version = Some(value),
+            "sec-websocket-key" => key = Some(value),
+            "sec-websocket-protocol" => offered.extend(tokens()),
+            "origin" => request.origin = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    if !upgrade || !connection {
+        return Err("not a WebSocket upgrade");
+    }
+    if version != Some("13") {
+        return Err("unsupported WebSocket version");
+    }
+    let key = key
+        .filter(|k| !k.is_empty())
+        .ok_or("no Sec-WebSocket-Key")?;
+    request.protocol = protocols
+        .iter()
+        .find(|p| offered.iter().any(|o| o.eq_ignore_ascii_case(p)))
+        .map(|p| p.to_string());
+    Ok((request, key.to_string()))
+}
+
+/// SHA-1, needed only for the handshake's accept key
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (out, word) in digest.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard padded base64
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let bits = group
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TelnetStream;
+    use crate::testing::MockStream;
+
+    const UPGRADE: &[u8] = b"GET /telnet HTTP/1.1\r\n\
+        Host: bbs.example\r\n\
+        Upgrade: websocket\r\n\
+        Connection: keep-alive, Upgrade\r\n\
+        Origin: http://bbs.example\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Protocol: binary, telnet\r\n\
+        Sec-WebSocket-Version: 13\r\n\r\n";
+
+    /// A masked client frame
+    fn client_frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xFA, 0x21, 0x3D];
+        let mut frame = vec![first, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_accept_key_and_digests() {
+        assert_eq!(
+            base64(&sha1(
+                b"dGhlIHNhbXBsZSBub25jZQ==258EAFA5-E914-47DA-95CA-C5AB0DC85B11"
+            )),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(
+            sha1(b"abc")[..4],
+            [0xa9, 0x99, 0x3e, 0x36],
+            "FIPS 180 example"
+        );
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+    }
+
+    #[test]
+    fn test_telnet_stream_over_websocket() {
+        let peer = MockStream::new();
+        // The first frame arrives with the request, the rest in pieces
+        let mut first = UPGRADE.to_vec();
+        first.extend(client_frame(0x82, b"hel"));
+        peer.push_input(&first);
+        let ping = client_frame(0x89, b"hi");
+        peer.push_input(&ping[..3]);
+        peer.push_would_block();
+        peer.push_input(&ping[3..]);
+        // "lo" sent as a fragmented text message, then IAC NOP
+        peer.push_input(&client_frame(0x01, b"l"));
+        peer.push_input(&client_frame(0x80, b"o\xff\xf1"));
+        peer.push_input(&client_frame(0x88, &1001u16.to_be_bytes()));
+
+        let transport = WebSocketTransport::accept(peer.clone()).unwrap();
+        assert_eq!(transport.request().path, "/telnet");
+        assert_eq!(
+            transport.request().origin.as_deref(),
+            Some("http://bbs.example")
+        );
+        assert_eq!(transport.request().protocol.as_deref(), Some("telnet"));
+
+        let mut stream = TelnetStream::new(transport);
+        let mut text = Vec::new();
+        let mut buf = [0; 64];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => text.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("unexpected {}", e),
+            }
+        }
+        assert_eq!(text, b"hello");
+
+        let written = peer.take_written();
+        let response = String::from_utf8_lossy(&written);
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(response.contains("Sec-WebSocket-Protocol: telnet\r\n"));
+        let frames = &written[response.find("\r\n\r\n").unwrap() + 4..];
+        // The pong, then the client's close echoed
+        assert_eq!(frames, b"\x8a\x02hi\x88\x02\x03\xe9");
+
+        // Writes after the close fail, and dropping sends nothing more
+        assert!(stream.write_all(b"late").is_err());
+        drop(stream);
+        assert!(peer.written().is_empty());
+    }
+
+    #[test]
+    fn test_writes_become_binary_frames() {
+        let peer = MockStream::with_input(UPGRADE);
+        let mut transport = WebSocketTransport::accept(peer.clone()).unwrap();
+        peer.take_written();
+
+        transport.write_all(b"\xff\xfb\x01").unwrap();
+        let long = vec![b'x'; 300];
+        transport.write_all(&long).unwrap();
+        transport.flush().unwrap();
+
+        let mut expected = b"\x82\x03\xff\xfb\x01\x82\x7e\x01\x2c".to_vec();
+        expected.extend_from_slice(&long);
+        assert_eq!(peer.take_written(), expected);
+
+        drop(transport);
+        assert_eq!(peer.written(), b"\x88\x02\x03\xe8");
+    }
+
+    #[test]
+    fn test_rejects_plain_http() {
+        let peer = MockStream::with_input(b"GET / HTTP/1.1\r\nHost: bbs.example\r\n\r\n");
+        let error = WebSocketTransport::accept(peer.clone()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(peer.written().starts_with(b"HTTP/1.1 400 Bad Request\r\n"));
+
+        let old = String::from_utf8(UPGRADE.to_vec())
+            .unwrap()
+            .replace("Version: 13", "Version: 8");
+        let error = WebSocketTransport::accept(MockStream::with_input(old.as_bytes())).unwrap_err();
+        assert!(error.to_string().contains("version"));
+    }
+
+    #[test]
+    fn test_unmasked_frame_closes_with_protocol_error() {
+        let peer = MockStream::new();
+        peer.push_input(UPGRADE);
+        peer.push_input(b"\x82\x02hi");
+        let mut transport = WebSocketTransport::accept(peer.clone()).unwrap();
+        peer.take_written();
+
+        let error = transport.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(peer.take_written(), b"\x88\x02\x03\xea");
+        assert_eq!(transport.read(&mut [0; 16]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use moonbase::attention::{AttentionEvent, AttentionProfile, BEL, attention_bytes};
+
+fn profile(quiet: bool, visual_flash: bool, supports_ansi: bool) -> AttentionProfile {
+    AttentionProfile {
+        quiet,
+        visual_flash,
+        supports_ansi,
+    }
+}
+
+#[test]
+fn test_bell_only() {
+    let event = AttentionEvent::NewMessage { count: 1 };
+    assert_eq!(
+        attention_bytes(&event, profile(false, false, true)),
+        vec![BEL]
+    );
+}
+
+#[test]
+fn test_quiet_mode_keeps_visual_line() {
+    let event = AttentionEvent::NewMessage { count: 2 };
+    let bytes = attention_bytes(&event, profile(true, true, false));
+
+    assert!(!bytes.contains(&BEL));
+    let text = String::from_utf8(bytes).unwrap();
+    assert!(text.contains("*** You have 2 new private messages ***"));
+}
+
+#[test]
+fn test_quiet_without_flash_sends_nothing() {
+    let event = AttentionEvent::Page {
+        from: "sysop".to_string(),
+    };
+    assert!(attention_bytes(&event, profile(true, false, true)).is_empty());
+}
+
+#[test]
+fn test_flash_honors_ansi_capability() {
+    let event = AttentionEvent::ChatMention {
+        from: "alice".to_string(),
+    };
+
+    let ansi = String::from_utf8(attention_bytes(&event, profile(false, true, true))).unwrap();
+    assert!(ansi.starts_with('\u{7}'));
+    assert!(ansi.contains("\x1b["));
+    assert!(ansi.contains("alice mentioned you in chat"));
+
+    let plain = String::from_utf8(attention_bytes(&event, profile(false, true, false))).unwrap();
+    assert!(!plain.contains('\x1b'));
+    assert!(plain.contains("*** alice mentioned you in chat ***"));
+}
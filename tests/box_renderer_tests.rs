@@ -0,0 +1,74 @@
+mod common;
+
+use crossterm::style::Color;
+use moonbase::box_renderer::{BoxRenderer, BoxStyle, MenuItem};
+use moonbase::text_width::display_width;
+
+fn rendered(renderer: &BoxRenderer, items: &[MenuItem]) -> String {
+    let mut out = Vec::new();
+    renderer
+        .render_menu(&mut out, "MAIN MENU", items, 40, Some(Color::Cyan))
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn menu_items() -> Vec<MenuItem> {
+    vec![
+        MenuItem::info("Welcome back"),
+        MenuItem::blank(),
+        MenuItem::separator(),
+        MenuItem::option("B", "Bulletins"),
+        MenuItem::disabled_option("F", "Files"),
+    ]
+}
+
+#[test]
+fn test_boxed_menu_has_borders() {
+    let output = rendered(&BoxRenderer::new(BoxStyle::Ascii, false), &menu_items());
+
+    assert!(output.starts_with('+'));
+    assert!(output.contains("| [B] Bulletins"));
+    assert!(output.contains("[F] Files (disabled)"));
+}
+
+#[test]
+fn test_linear_menu_is_plain_text() {
+    let renderer = BoxRenderer::new(BoxStyle::Ascii, true).with_linear(true);
+    let output = rendered(&renderer, &menu_items());
+
+    assert_eq!(
+        output,
+        "MAIN MENU\nWelcome back\nB: Bulletins\nF: Files (unavailable)\n"
+    );
+}
+
+#[test]
+fn test_linear_message_box_keeps_lines_unwrapped() {
+    let renderer = BoxRenderer::new(BoxStyle::Ascii, false).with_linear(true);
+    let message = "A line that is much longer than the box would allow it to be\n\nDone";
+
+    let mut out = Vec::new();
+    renderer
+        .render_message_box(&mut out, "NOTICE", message, 20, None)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "NOTICE\nA line that is much longer than the box would allow it to be\nDone\n"
+    );
+}
+
+#[test]
+fn test_wide_characters_keep_borders_aligned() {
+    let renderer = BoxRenderer::new(BoxStyle::Ascii, false);
+    let items = vec![
+        MenuItem::info("ユーザー: 月の基地"),
+        MenuItem::info("🚀 launch day"),
+        MenuItem::info("これはとても長いお知らせなので箱に収まりません"),
+    ];
+
+    let output = rendered(&renderer, &items);
+    for line in output.lines() {
+        assert_eq!(display_width(line), 40, "misaligned: {:?}", line);
+    }
+}
@@ -1,9 +1,10 @@
 mod common;
 
 use moonbase::bulletin_repository::{BulletinStorage, JsonBulletinStorage};
-use moonbase::bulletins::BulletinRequest;
+use moonbase::bulletins::{Bulletin, BulletinReadState, BulletinRequest};
 use moonbase::config::BbsConfig;
 use moonbase::errors::BbsResult;
+use moonbase::text_width::display_width;
 use tempfile::TempDir;
 
 #[test]
@@ -42,17 +43,15 @@ fn test_bulletin_posting_and_reading() -> BbsResult<()> {
     assert_eq!(bulletin.author, "TestUser");
     assert_eq!(bulletin.id, 1);
     assert!(!bulletin.is_sticky);
-    assert!(bulletin.read_by.is_empty());
 
     // Test marking as read
-    storage.mark_read(bulletin_id, "TestUser")?;
-
-    // Verify it's marked as read
-    let updated_bulletin = storage.load_bulletin(bulletin_id)?.unwrap();
-    assert!(updated_bulletin.is_read_by("TestUser"));
+    let mut reads = BulletinReadState::default();
+    assert!(!reads.is_read(bulletin_id));
+    reads.mark_read(bulletin_id);
+    assert!(reads.is_read(bulletin_id));
 
     // Test statistics
-    let stats = storage.get_stats(Some("TestUser"));
+    let stats = storage.get_stats(Some(&reads));
     assert_eq!(stats.total_bulletins, 1);
     assert_eq!(stats.unread_count, 0); // Read by TestUser
     assert_eq!(stats.recent_bulletins.len(), 1);
@@ -64,3 +63,137 @@ fn test_bulletin_posting_and_reading() -> BbsResult<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_read_state_high_water_mark() {
+    let mut reads = BulletinReadState::default();
+
+    // Reading out of order keeps exceptions above the mark
+    reads.mark_read(3);
+    reads.mark_read(5);
+    assert_eq!(reads.high_water, 0);
+    assert!(reads.is_read(3) && reads.is_read(5));
+    assert!(!reads.is_read(1) && !reads.is_read(4));
+
+    // Filling the gaps folds contiguous IDs into the mark
+    reads.mark_read(1);
+    reads.mark_read(2);
+    assert_eq!(reads.high_water, 3);
+    assert_eq!(
+        reads.read_above.iter().copied().collect::<Vec<_>>(),
+        vec![5]
+    );
+
+    reads.mark_read(4);
+    assert_eq!(reads.high_water, 5);
+    assert!(reads.read_above.is_empty());
+}
+
+#[test]
+fn test_unread_count_from_read_state() -> BbsResult<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let mut storage = JsonBulletinStorage::new(temp_dir.path())?;
+    let config = BbsConfig::default();
+
+    for i in 1..=4 {
+        let request = BulletinRequest::new(
+            format!("Bulletin {}", i),
+            "Content".to_string(),
+            "TestUser".to_string(),
+        );
+        storage.post_bulletin(&request, &config)?;
+    }
+
+    let mut reads = BulletinReadState::default();
+    reads.mark_read(1);
+    reads.mark_read(3);
+
+    assert_eq!(storage.get_stats(Some(&reads)).unread_count, 2);
+    // Anonymous users see everything as unread
+    assert_eq!(storage.get_stats(None).unread_count, 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_content_wraps_by_display_width() {
+    let bulletin = Bulletin::new(
+        1,
+        "Wide".to_string(),
+        "月の基地へようこそ ok".to_string(),
+        "sysop".to_string(),
+    );
+
+    // Nine ideographs are eighteen columns, so they split into 8-column chunks
+    let lines = bulletin.get_content_lines(8);
+    assert_eq!(lines, vec!["月の基地", "へようこ", "そ", "ok"]);
+    assert!(lines.iter().all(|l| display_width(l) <= 8));
+}
+
+#[test]
+fn test_bulletins_since_last_call() -> BbsResult<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let mut storage = JsonBulletinStorage::new(temp_dir.path())?;
+    let config = BbsConfig::default();
+
+    let post = |storage: &mut JsonBulletinStorage, title: &str| {
+        let request = BulletinRequest::new(
+            title.to_string(),
+            "Content for the bulletin.".to_string(),
+            "TestUser".to_string(),
+        );
+        storage.post_bulletin(&request, &config)
+    };
+
+    post(&mut storage, "Before")?;
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let last_call = jiff::Timestamp::now();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    post(&mut storage, "After One")?;
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    post(&mut storage, "After Two")?;
+
+    let titles: Vec<String> = storage
+        .get_bulletins_since(last_call)?
+        .into_iter()
+        .map(|b| b.title)
+        .collect();
+    assert_eq!(titles, ["After Two", "After One"]);
+
+    assert!(
+        storage
+            .get_bulletins_since(jiff::Timestamp::now())?
+            .is_empty()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_display_name_shown_on_bulletins() -> BbsResult<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let mut storage = JsonBulletinStorage::new(temp_dir.path())?;
+    let config = BbsConfig::default();
+
+    let request = BulletinRequest::new(
+        "Hello".to_string(),
+        "First post".to_string(),
+        "alice".to_string(),
+    )
+    .with_author_display(Some("Alice Liddell".to_string()));
+    let id = storage.post_bulletin(&request, &config)?;
+
+    // The login name stays the author of record
+    let bulletin = storage.load_bulletin(id)?.unwrap();
+    assert_eq!(bulletin.author, "alice");
+    assert_eq!(bulletin.byline(), "Alice Liddell");
+    assert_eq!(
+        storage.get_stats(None).recent_bulletins[0].author,
+        "Alice Liddell"
+    );
+
+    // Bulletins saved before display names existed still load
+    let plain = Bulletin::new(2, "Old".to_string(), "Post".to_string(), "bob".to_string());
+    assert_eq!(plain.byline(), "bob");
+
+    Ok(())
+}
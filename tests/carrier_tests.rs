@@ -0,0 +1,213 @@
+mod common;
+
+use moonbase::bulletin_repository::JsonBulletinStorage;
+use moonbase::club_repository::JsonClubStorage;
+use moonbase::config::BbsConfig;
+use moonbase::connection::Connection;
+use moonbase::errors::BbsError;
+use moonbase::message_repository::JsonMessageStorage;
+use moonbase::services::{CoreServices, Draft, DraftService, PresenceService};
+use moonbase::session::BbsSession;
+use moonbase::user_repository::JsonUserStorage;
+use moonbase::users::RegistrationRequest;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use telnet_negotiation::TelnetStream;
+
+/// Run a session against a client that calls `client` on its socket
+fn run_session<F>(config: BbsConfig, services: Arc<CoreServices>, client: F) -> Result<(), BbsError>
+where
+    F: FnOnce(TcpStream) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let caller = thread::spawn(move || client(TcpStream::connect(addr).unwrap()));
+
+    let (socket, _) = listener.accept().unwrap();
+    let mut session = BbsSession::new(Arc::new(config), services);
+    let result = session.run(TelnetStream::new(Connection::from(socket)));
+    caller.join().unwrap();
+    result
+}
+
+#[test]
+fn test_hangups_and_timeouts_are_told_apart() {
+    for kind in [
+        ErrorKind::UnexpectedEof,
+        ErrorKind::ConnectionReset,
+        ErrorKind::BrokenPipe,
+    ] {
+        let err = BbsError::from(io::Error::from(kind));
+        assert!(matches!(err, BbsError::ClientDisconnected), "{:?}", kind);
+    }
+    for kind in [ErrorKind::WouldBlock, ErrorKind::TimedOut] {
+        let err = BbsError::from(io::Error::from(kind));
+        assert!(matches!(err, BbsError::Timeout), "{:?}", kind);
+    }
+    let err = BbsError::from(io::Error::from(ErrorKind::PermissionDenied));
+    assert!(matches!(err, BbsError::Io(_)));
+}
+
+#[test]
+fn test_presence_registry() {
+    let presence = PresenceService::new();
+    let first = presence.connect("10.0.0.1:4000");
+    let second = presence.connect("10.0.0.2:4000");
+    assert_ne!(first, second);
+
+    presence.set_user(second, Some("alice"));
+    let online = presence.online();
+    assert_eq!(online.len(), 2);
+    assert_eq!(online[0].username, None);
+    assert_eq!(online[1].username.as_deref(), Some("alice"));
+
+    presence.set_location(second, Some("Portland, OR"));
+    assert_eq!(
+        presence.online()[1].location.as_deref(),
+        Some("Portland, OR")
+    );
+
+    let gone = presence.disconnect(second).unwrap();
+    assert_eq!(gone.peer, "10.0.0.2:4000");
+    assert!(presence.disconnect(second).is_none());
+    assert_eq!(presence.count(), 1);
+}
+
+#[test]
+fn test_drafts_are_kept_until_taken() {
+    let drafts = DraftService::new();
+    drafts.save(
+        "Alice",
+        Draft::Bulletin {
+            title: "First".to_string(),
+            area: None,
+        },
+    );
+    drafts.save(
+        "alice",
+        Draft::Message {
+            recipient: "bob".to_string(),
+            subject: "Hi".to_string(),
+        },
+    );
+
+    // Latest draft wins, and it's handed back only once
+    assert_eq!(
+        drafts.take("ALICE"),
+        Some(Draft::Message {
+            recipient: "bob".to_string(),
+            subject: "Hi".to_string(),
+        })
+    );
+    assert_eq!(drafts.take("alice"), None);
+}
+
+#[test]
+fn test_services_survive_a_session_dying_with_the_lock_held() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let users = Arc::new(Mutex::new(JsonUserStorage::new(temp_dir.path()).unwrap()));
+    let services = CoreServices::new(
+        &config.features,
+        users.clone(),
+        Arc::new(Mutex::new(
+            JsonBulletinStorage::new(temp_dir.path()).unwrap(),
+        )),
+        Arc::new(Mutex::new(
+            JsonMessageStorage::new(temp_dir.path()).unwrap(),
+        )),
+        Arc::new(Mutex::new(JsonClubStorage::new(temp_dir.path()).unwrap())),
+    );
+
+    // A session thread panics while holding the user storage
+    let held = users.clone();
+    let _ = thread::spawn(move || {
+        let _guard = held.lock().unwrap();
+        panic!("session went away mid-operation");
+    })
+    .join();
+    assert!(users.is_poisoned());
+
+    let request = RegistrationRequest::new("alice".to_string(), None, "password123".to_string());
+    services.users.register(request, &config).unwrap();
+    assert_eq!(services.users.get_stats().unwrap().total_users, 1);
+}
+
+#[test]
+fn test_session_cleaned_up_after_carrier_drop() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = Arc::new(common::services(temp_dir.path(), &config));
+
+    let result = run_session(config, Arc::clone(&services), |mut socket| {
+        // Wait for the first screen, then hang up
+        let mut buf = [0u8; 256];
+        assert!(socket.read(&mut buf).unwrap() > 0);
+    });
+
+    assert!(matches!(result, Err(BbsError::ClientDisconnected)));
+    assert_eq!(services.presence.count(), 0);
+}
+
+#[test]
+fn test_browser_caller_hanging_up_is_a_carrier_drop() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = Arc::new(common::services(temp_dir.path(), &config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let caller = thread::spawn(move || {
+        let mut socket = TcpStream::connect(addr).unwrap();
+        socket
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: bbs\r\nUpgrade: websocket\r\n\
+                  Connection: Upgrade\r\nSec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .unwrap();
+        // Wait for the upgrade and the first binary frame, then hang up
+        let mut received = Vec::new();
+        let mut buf = [0u8; 256];
+        while !received
+            .windows(5)
+            .any(|w| w[..4] == *b"\r\n\r\n" && w[4] == 0x82)
+        {
+            let n = socket.read(&mut buf).unwrap();
+            assert!(n > 0);
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert!(received.starts_with(b"HTTP/1.1 101 "));
+    });
+
+    let (socket, _) = listener.accept().unwrap();
+    let connection = Connection::accept_websocket(socket).unwrap();
+    assert!(!connection.can_start_tls());
+    let mut session = BbsSession::new(Arc::new(config), Arc::clone(&services));
+    let result = session.run(TelnetStream::new(connection));
+    caller.join().unwrap();
+
+    assert!(matches!(result, Err(BbsError::ClientDisconnected)));
+    assert_eq!(services.presence.count(), 0);
+}
+
+#[test]
+fn test_idle_session_times_out() {
+    let temp_dir = common::create_temp_dir();
+    let mut config = BbsConfig::default();
+    config.timeouts.connection_timeout = Duration::from_millis(500);
+    let services = Arc::new(common::services(temp_dir.path(), &config));
+
+    let result = run_session(config, Arc::clone(&services), |mut socket| {
+        // Say nothing, and read until the server closes the line
+        let mut output = Vec::new();
+        let _ = socket.read_to_end(&mut output);
+        assert!(String::from_utf8_lossy(&output).contains("Idle too long"));
+    });
+
+    assert!(matches!(result, Err(BbsError::Timeout)));
+    assert_eq!(services.presence.count(), 0);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use moonbase::client_profile::ClientClass;
+use std::time::Duration;
+use telnet_negotiation::{NegotiationStats, OptionTiming, TelnetCommand, TelnetOption};
+
+fn timing(option: TelnetOption, answer: Option<TelnetCommand>, millis: u64) -> OptionTiming {
+    OptionTiming {
+        option,
+        requested: TelnetCommand::DO,
+        answer,
+        round_trip: answer.map(|_| Duration::from_millis(millis)),
+    }
+}
+
+fn stats(timings: Vec<OptionTiming>) -> NegotiationStats {
+    NegotiationStats { timings }
+}
+
+#[test]
+fn test_prompt_client_is_modern() {
+    let stats = stats(vec![
+        timing(TelnetOption::TERMINAL_TYPE, Some(TelnetCommand::WILL), 12),
+        timing(TelnetOption::NAWS, Some(TelnetCommand::WILL), 14),
+        timing(TelnetOption::NEW_ENVIRON, Some(TelnetCommand::WONT), 15),
+    ]);
+
+    let class = ClientClass::from_stats(&stats);
+    assert_eq!(class, ClientClass::Modern);
+    assert!(class.redraws_screen());
+}
+
+#[test]
+fn test_slow_client_is_laggy() {
+    let stats = stats(vec![
+        timing(TelnetOption::TERMINAL_TYPE, Some(TelnetCommand::WILL), 180),
+        timing(TelnetOption::NAWS, Some(TelnetCommand::WILL), 200),
+        // Answered too late to be seen
+        timing(TelnetOption::NEW_ENVIRON, None, 0),
+    ]);
+
+    let class = ClientClass::from_stats(&stats);
+    assert_eq!(class, ClientClass::Laggy);
+    assert!(!class.redraws_screen());
+}
+
+#[test]
+fn test_client_refusing_everything_is_minimal() {
+    let refused = stats(vec![
+        timing(TelnetOption::TERMINAL_TYPE, Some(TelnetCommand::WONT), 5),
+        timing(TelnetOption::NAWS, Some(TelnetCommand::WONT), 5),
+    ]);
+    assert_eq!(ClientClass::from_stats(&refused), ClientClass::Minimal);
+
+    // A raw socket never answers at all
+    let silent = stats(vec![timing(TelnetOption::NAWS, None, 0)]);
+    assert_eq!(ClientClass::from_stats(&silent), ClientClass::Minimal);
+}
+
+#[test]
+fn test_no_requests_assumes_modern() {
+    assert_eq!(
+        ClientClass::from_stats(&NegotiationStats::default()),
+        ClientClass::Modern
+    );
+}
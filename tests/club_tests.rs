@@ -0,0 +1,397 @@
+mod common;
+
+use jiff::Timestamp;
+use moonbase::bulletins::BulletinRequest;
+use moonbase::club_repository::{ClubStorage, JsonClubStorage};
+use moonbase::clubs::ClubRequest;
+use moonbase::config::{BbsConfig, SecurityLevel};
+use moonbase::errors::BbsError;
+use moonbase::services::permission_service::Membership;
+use moonbase::services::{CoreServices, ExportFormat};
+use moonbase::users::RegistrationRequest;
+use std::fs;
+
+fn create(services: &CoreServices, name: &str, password: Option<&str>) {
+    let request = ClubRequest::new(
+        name.to_string(),
+        "Members only".to_string(),
+        password.map(str::to_string),
+    );
+    services
+        .clubs
+        .create_club(request, "sysop", SecurityLevel::Sysop)
+        .unwrap();
+}
+
+fn post(
+    services: &CoreServices,
+    config: &BbsConfig,
+    author: &str,
+    area: Option<&str>,
+    level: SecurityLevel,
+) -> Result<u32, BbsError> {
+    let request = BulletinRequest::new("Title".to_string(), "Body".to_string(), author.to_string())
+        .with_area(area.map(str::to_string));
+    services.bulletins.post_bulletin(request, config, level)
+}
+
+#[test]
+fn test_only_sysop_creates_clubs() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+
+    let request = ClubRequest::new("Hams".to_string(), String::new(), None);
+    let result = services
+        .clubs
+        .create_club(request, "alice", SecurityLevel::User);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+
+    create(&services, "Hams", None);
+    let club = services.clubs.get_club("hams").unwrap().unwrap();
+    assert!(club.is_member("SysOp"));
+
+    // Names ignore case
+    let request = ClubRequest::new("HAMS".to_string(), String::new(), None);
+    let result = services
+        .clubs
+        .create_club(request, "sysop", SecurityLevel::Sysop);
+    assert!(matches!(result, Err(BbsError::InvalidInput(_))));
+}
+
+#[test]
+fn test_club_request_validation() {
+    let bad = [
+        ClubRequest::new("  ".to_string(), String::new(), None),
+        ClubRequest::new("x".repeat(31), String::new(), None),
+        ClubRequest::new("Hams/Radio".to_string(), String::new(), None),
+        ClubRequest::new("Hams".to_string(), String::new(), Some("abc".to_string())),
+    ];
+    for request in bad {
+        assert!(request.validate().is_err(), "{:?}", request);
+    }
+    assert!(
+        ClubRequest::new("Ham Radio-2".to_string(), String::new(), None)
+            .validate()
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_password_club_join() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    create(&services, "Hams", Some("qrz73"));
+
+    let result = services.clubs.join_with_password("hams", "alice", "wrong");
+    assert!(matches!(result, Err(BbsError::AuthenticationFailed(_))));
+    assert_eq!(
+        services
+            .clubs
+            .membership(Some("hams"), Some("alice"))
+            .unwrap(),
+        Membership::Outsider
+    );
+
+    services
+        .clubs
+        .join_with_password("hams", "Alice", "qrz73")
+        .unwrap();
+    assert_eq!(
+        services
+            .clubs
+            .membership(Some("Hams"), Some("alice"))
+            .unwrap(),
+        Membership::Member
+    );
+
+    // Password clubs don't take requests
+    assert!(services.clubs.request_membership("hams", "bob").is_err());
+
+    services.clubs.leave("hams", "alice").unwrap();
+    assert_eq!(
+        services
+            .clubs
+            .membership(Some("hams"), Some("alice"))
+            .unwrap(),
+        Membership::Outsider
+    );
+}
+
+#[test]
+fn test_approval_club_join() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    create(&services, "Inner Circle", None);
+
+    assert!(
+        services
+            .clubs
+            .join_with_password("inner circle", "alice", "anything")
+            .is_err()
+    );
+
+    services
+        .clubs
+        .request_membership("inner circle", "alice")
+        .unwrap();
+    services
+        .clubs
+        .request_membership("inner circle", "bob")
+        .unwrap();
+    let club = services.clubs.get_club("inner circle").unwrap().unwrap();
+    assert!(club.is_pending("alice") && !club.is_member("alice"));
+
+    // Only the SysOp decides
+    let result = services
+        .clubs
+        .approve("inner circle", "alice", SecurityLevel::User);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+
+    services
+        .clubs
+        .approve("inner circle", "alice", SecurityLevel::Sysop)
+        .unwrap();
+    services
+        .clubs
+        .decline("inner circle", "bob", SecurityLevel::Sysop)
+        .unwrap();
+
+    let club = services.clubs.get_club("inner circle").unwrap().unwrap();
+    assert!(club.is_member("alice"));
+    assert!(!club.is_member("bob") && !club.is_pending("bob"));
+
+    // Nobody left to approve
+    assert!(
+        services
+            .clubs
+            .approve("inner circle", "bob", SecurityLevel::Sysop)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_club_bulletins_hidden_from_outsiders() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    create(&services, "Hams", Some("qrz73"));
+    services
+        .clubs
+        .join_with_password("hams", "alice", "qrz73")
+        .unwrap();
+
+    let user = SecurityLevel::User;
+    let club_post = post(&services, &config, "alice", Some("hams"), user).unwrap();
+    let main_post = post(&services, &config, "bob", None, user).unwrap();
+
+    // Outsiders can neither post, list nor read
+    let result = post(&services, &config, "bob", Some("hams"), user);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+    let result = services
+        .bulletins
+        .list_bulletins(Some("hams"), Some("bob"), user);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+    let result = services
+        .bulletins
+        .get_bulletin(club_post, Some("bob"), user);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+    let result = services
+        .bulletins
+        .get_bulletin(club_post, None, SecurityLevel::Guest);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+    let result =
+        services
+            .bulletins
+            .get_recent_bulletins(10, Some("hams"), None, SecurityLevel::Guest);
+    assert!(matches!(result, Err(BbsError::PermissionDenied(_))));
+
+    // Members see the club, and the main board doesn't include it
+    let listed = services
+        .bulletins
+        .list_bulletins(Some("hams"), Some("alice"), user)
+        .unwrap();
+    assert_eq!(listed.iter().map(|b| b.id).collect::<Vec<_>>(), [club_post]);
+    let listed = services
+        .bulletins
+        .list_bulletins(None, Some("alice"), user)
+        .unwrap();
+    assert_eq!(listed.iter().map(|b| b.id).collect::<Vec<_>>(), [main_post]);
+    let recent = services
+        .bulletins
+        .get_recent_bulletins(10, Some("hams"), Some("alice"), user)
+        .unwrap();
+    assert_eq!(recent.iter().map(|b| b.id).collect::<Vec<_>>(), [club_post]);
+
+    // The SysOp may look into every club
+    assert!(
+        services
+            .bulletins
+            .get_bulletin(club_post, Some("sysop2"), SecurityLevel::Sysop)
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_club_posts_dont_hold_back_outsiders_reads() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    create(&services, "Hams", Some("qrz73"));
+    services
+        .clubs
+        .join_with_password("hams", "alice", "qrz73")
+        .unwrap();
+    let request = RegistrationRequest::new("bob".to_string(), None, "password123".to_string());
+    services.users.register(request, &config).unwrap();
+
+    // Club and main-board posts take turns with the IDs
+    let user = SecurityLevel::User;
+    let mut main_posts = Vec::new();
+    for _ in 0..3 {
+        post(&services, &config, "alice", Some("hams"), user).unwrap();
+        main_posts.push(post(&services, &config, "alice", None, user).unwrap());
+    }
+
+    // Bob reads the whole main board, as the session marks it
+    services
+        .users
+        .mark_bulletins_read("bob", &main_posts)
+        .unwrap();
+    let readable = services
+        .bulletins
+        .readable_after(0, Some("bob"), user)
+        .unwrap();
+    assert_eq!(readable.iter().copied().collect::<Vec<_>>(), main_posts);
+    let bob = services
+        .users
+        .skip_unreadable_bulletins("bob", &readable)
+        .unwrap();
+    assert_eq!(bob.bulletin_reads.high_water, main_posts[2]);
+    assert!(bob.bulletin_reads.read_above.is_empty());
+
+    // A main-board post he hasn't read still holds the mark
+    let unread = post(&services, &config, "alice", None, user).unwrap();
+    post(&services, &config, "alice", Some("hams"), user).unwrap();
+    let last = post(&services, &config, "alice", None, user).unwrap();
+    services.users.mark_bulletin_read("bob", last).unwrap();
+    let readable = services
+        .bulletins
+        .readable_after(main_posts[2], Some("bob"), user)
+        .unwrap();
+    let bob = services
+        .users
+        .skip_unreadable_bulletins("bob", &readable)
+        .unwrap();
+    assert_eq!(bob.bulletin_reads.high_water, main_posts[2]);
+    assert!(!bob.bulletin_reads.is_read(unread));
+    assert!(bob.bulletin_reads.is_read(last));
+}
+
+#[test]
+fn test_club_stats_and_new_bulletins_are_scoped() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    create(&services, "Hams", Some("qrz73"));
+    services
+        .clubs
+        .join_with_password("hams", "alice", "qrz73")
+        .unwrap();
+
+    let since = Timestamp::now();
+    let user = SecurityLevel::User;
+    post(&services, &config, "alice", Some("hams"), user).unwrap();
+    post(&services, &config, "alice", Some("hams"), user).unwrap();
+    post(&services, &config, "bob", None, user).unwrap();
+
+    assert_eq!(
+        services.bulletins.get_stats(None).unwrap().total_bulletins,
+        1
+    );
+    let stats = services
+        .bulletins
+        .get_area_stats(None, Some("hams"), Some("alice"), user)
+        .unwrap();
+    assert_eq!(stats.total_bulletins, 2);
+
+    let new_for = |username| {
+        services
+            .bulletins
+            .get_bulletins_since(since, Some(username), user)
+            .unwrap()
+            .len()
+    };
+    assert_eq!(new_for("alice"), 3);
+    assert_eq!(new_for("bob"), 1);
+
+    // Batch lookups leave out club posts too
+    let fetched_for = |username| {
+        services
+            .bulletins
+            .get_many(&[3, 1, 2, 99], Some(username), user)
+            .unwrap()
+            .iter()
+            .map(|b| b.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(fetched_for("alice"), [3, 1, 2]);
+    assert_eq!(fetched_for("bob"), [3]);
+}
+
+#[test]
+fn test_clubs_persist() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    {
+        let services = common::services(temp_dir.path(), &config);
+        create(&services, "Hams", Some("qrz73"));
+        services
+            .clubs
+            .join_with_password("hams", "alice", "qrz73")
+            .unwrap();
+    }
+
+    let storage = JsonClubStorage::new(temp_dir.path()).unwrap();
+    let club = storage.load_club("HAMS").unwrap().unwrap();
+    assert_eq!(club.name, "Hams");
+    assert!(club.is_member("alice"));
+    assert!(club.check_password("qrz73").unwrap());
+    assert_eq!(storage.list_clubs().unwrap().len(), 1);
+}
+
+#[test]
+fn test_export_leaves_out_clubs_unless_sysop() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    create(&services, "Hams", None);
+
+    post(
+        &services,
+        &config,
+        "sysop",
+        Some("hams"),
+        SecurityLevel::Sysop,
+    )
+    .unwrap();
+    post(&services, &config, "sysop", None, SecurityLevel::Sysop).unwrap();
+
+    let user_mbox = temp_dir.path().join("user.mbox");
+    let report = services
+        .export
+        .export(ExportFormat::Mbox, &user_mbox, SecurityLevel::User)
+        .unwrap();
+    assert_eq!(report.exported, 1);
+
+    let sysop_mbox = temp_dir.path().join("sysop.mbox");
+    let report = services
+        .export
+        .export(ExportFormat::Mbox, &sysop_mbox, SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(report.exported, 2);
+    assert!(!fs::read_to_string(&sysop_mbox).unwrap().is_empty());
+}
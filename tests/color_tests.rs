@@ -0,0 +1,85 @@
+mod common;
+
+use crossterm::style::{Attribute, Color, SetAttribute, SetForegroundColor};
+use moonbase::color::ColorFilter;
+use telnet_negotiation::ColorDepth;
+
+fn fg(color: Color) -> String {
+    SetForegroundColor(color).to_string()
+}
+
+fn filtered(depth: ColorDepth, input: &str) -> String {
+    String::from_utf8(ColorFilter::new(depth).filter(input.as_bytes())).unwrap()
+}
+
+#[test]
+fn test_true_color_passes_through() {
+    let input = format!(
+        "{}hi",
+        fg(Color::Rgb {
+            r: 10,
+            g: 20,
+            b: 30
+        })
+    );
+    assert_eq!(filtered(ColorDepth::TrueColor, &input), input);
+}
+
+#[test]
+fn test_rgb_downgrades_to_palette() {
+    let red = fg(Color::Rgb { r: 250, g: 5, b: 5 });
+
+    assert_eq!(filtered(ColorDepth::Extended256, &red), "\x1b[38;5;196m");
+    assert_eq!(filtered(ColorDepth::Basic16, &red), "\x1b[91m");
+    assert_eq!(filtered(ColorDepth::Basic8, &red), "\x1b[31m");
+}
+
+#[test]
+fn test_named_colors_fold_to_eight() {
+    // crossterm sends named colors as 256-color indices
+    let bright_green = fg(Color::Green);
+
+    assert_eq!(
+        filtered(ColorDepth::Extended256, &bright_green),
+        bright_green
+    );
+    assert_eq!(filtered(ColorDepth::Basic16, &bright_green), "\x1b[92m");
+    assert_eq!(filtered(ColorDepth::Basic8, &bright_green), "\x1b[32m");
+}
+
+#[test]
+fn test_monochrome_strips_color_only() {
+    let input = format!(
+        "{}{}bold{}",
+        fg(Color::Red),
+        SetAttribute(Attribute::Bold),
+        SetAttribute(Attribute::Reset)
+    );
+
+    assert_eq!(
+        filtered(ColorDepth::Monochrome, &input),
+        format!(
+            "{}bold{}",
+            SetAttribute(Attribute::Bold),
+            SetAttribute(Attribute::Reset)
+        )
+    );
+    // Mixed sequences keep their attributes
+    assert_eq!(filtered(ColorDepth::Monochrome, "\x1b[1;31;44m"), "\x1b[1m");
+}
+
+#[test]
+fn test_sequence_split_across_writes() {
+    let mut filter = ColorFilter::new(ColorDepth::Basic8);
+
+    let mut out = filter.filter(b"a\x1b[38;2;0;0");
+    assert_eq!(out, b"a");
+    out.extend(filter.filter(b";255mb"));
+    assert_eq!(out, b"a\x1b[34mb");
+}
+
+#[test]
+fn test_other_escapes_untouched() {
+    let input = "\x1b[2J\x1b[1;1H\x1b7text";
+    assert_eq!(filtered(ColorDepth::Monochrome, input), input);
+}
@@ -1,5 +1,26 @@
+use moonbase::bulletin_repository::JsonBulletinStorage;
+use moonbase::club_repository::JsonClubStorage;
+use moonbase::config::BbsConfig;
+use moonbase::message_repository::JsonMessageStorage;
+use moonbase::services::CoreServices;
+use moonbase::user_repository::JsonUserStorage;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 
+#[allow(dead_code)]
 pub fn create_temp_dir() -> TempDir {
     TempDir::new().unwrap()
 }
+
+/// Services over JSON storage in `data_dir`
+#[allow(dead_code)]
+pub fn services(data_dir: &Path, config: &BbsConfig) -> CoreServices {
+    CoreServices::new(
+        &config.features,
+        Arc::new(Mutex::new(JsonUserStorage::new(data_dir).unwrap())),
+        Arc::new(Mutex::new(JsonBulletinStorage::new(data_dir).unwrap())),
+        Arc::new(Mutex::new(JsonMessageStorage::new(data_dir).unwrap())),
+        Arc::new(Mutex::new(JsonClubStorage::new(data_dir).unwrap())),
+    )
+}
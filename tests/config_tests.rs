@@ -0,0 +1,354 @@
+mod common;
+
+use moonbase::config::{BbsConfig, FeatureAccess, GatewayBoard, SecurityLevel};
+use moonbase::motd::{AddressRange, ConnectionNotice};
+use std::time::Duration;
+use telnet_negotiation::TelnetOption;
+use tempfile::TempDir;
+
+fn load_config(content: &str) -> BbsConfig {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    std::fs::write(&path, content).unwrap();
+    BbsConfig::load_from_file(path.to_str().unwrap()).unwrap()
+}
+
+#[test]
+fn test_retro_options_default_off() {
+    let config = BbsConfig::default();
+
+    assert!(!config.ui.retro_modem_banner);
+    assert_eq!(config.ui.retro_modem_speed, 38400);
+    assert!(!config.ui.retro_login_matrix);
+    assert!(!config.ui.retro_last_callers);
+    assert!(config.ui.attention_flash);
+}
+
+#[test]
+fn test_retro_options_parsing() {
+    let config = load_config(
+        r#"
+[ui]
+retro_modem_banner = true
+retro_modem_speed = 2400
+retro_login_matrix = false
+retro_last_callers = true
+"#,
+    );
+
+    assert!(config.ui.retro_modem_banner);
+    assert_eq!(config.ui.retro_modem_speed, 2400);
+    assert!(!config.ui.retro_login_matrix);
+    assert!(config.ui.retro_last_callers);
+}
+
+#[test]
+fn test_retro_options_invalid_value() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    std::fs::write(&path, "[ui]\nretro_modem_speed = fast\n").unwrap();
+
+    assert!(BbsConfig::load_from_file(path.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_feature_access_parsing() {
+    let config = load_config(
+        r#"
+[features]
+bulletins = "guest/user"
+files = "read-only"
+messages = "user"
+chat = "off"
+doors = "sysop"
+"#,
+    );
+
+    assert_eq!(
+        config.features.bulletins,
+        FeatureAccess::Gated {
+            read: SecurityLevel::Guest,
+            write: SecurityLevel::User
+        }
+    );
+    assert_eq!(config.features.files, FeatureAccess::ReadOnly);
+    assert_eq!(
+        config.features.messages,
+        FeatureAccess::level(SecurityLevel::User)
+    );
+    assert_eq!(config.features.chat, FeatureAccess::Off);
+    assert_eq!(
+        config.features.doors,
+        FeatureAccess::level(SecurityLevel::Sysop)
+    );
+}
+
+#[test]
+fn test_legacy_feature_toggles() {
+    let config = load_config(
+        "[features]
+bulletins_enabled = false
+file_uploads_enabled = true
+",
+    );
+
+    assert_eq!(config.features.bulletins, FeatureAccess::Off);
+    assert_eq!(config.features.files, BbsConfig::default().features.files);
+}
+
+#[test]
+fn test_feature_access_round_trip() {
+    for text in ["off", "read-only", "guest", "guest/user", "user/sysop"] {
+        let access: FeatureAccess = text.parse().unwrap();
+        assert_eq!(access.to_string(), text);
+    }
+    assert!("admin".parse::<FeatureAccess>().is_err());
+}
+
+#[test]
+fn test_start_tls_option() {
+    assert!(!BbsConfig::default().server.start_tls);
+
+    let config = load_config("[server]\nstart_tls = true\n");
+    assert!(config.server.start_tls);
+}
+
+#[test]
+fn test_websocket_port_option() {
+    assert_eq!(BbsConfig::default().server.websocket_port, None);
+
+    let config = load_config("[server]\nwebsocket_port = 8023\n");
+    assert_eq!(config.server.websocket_port, Some(8023));
+    let config = load_config("[server]\nwebsocket_port = none\n");
+    assert_eq!(config.server.websocket_port, None);
+}
+
+#[test]
+fn test_tls_options() {
+    let defaults = BbsConfig::default();
+    assert_eq!(defaults.server.tls_port, None);
+    assert!(defaults.server.tls_certificate.is_empty());
+
+    let config = load_config(
+        "[server]\ntls_port = 992\ntls_certificate = \"tls/cert.pem\"\ntls_private_key = \"tls/key.pem\"\n",
+    );
+    assert_eq!(config.server.tls_port, Some(992));
+    assert_eq!(config.server.tls_certificate, "tls/cert.pem");
+    assert_eq!(config.server.tls_private_key, "tls/key.pem");
+}
+
+#[test]
+fn test_telnet_options_order() {
+    assert_eq!(
+        BbsConfig::default().server.telnet_options.options(),
+        &[
+            TelnetOption::TERMINAL_TYPE,
+            TelnetOption::NAWS,
+            TelnetOption::NEW_ENVIRON,
+            TelnetOption::SEND_LOCATION,
+            TelnetOption::SUPPRESS_GO_AHEAD,
+        ]
+    );
+
+    let config = load_config("[server]\ntelnet_options = \"naws, suppress-go-ahead, mxp\"\n");
+    assert_eq!(
+        config.server.telnet_options.options(),
+        &[
+            TelnetOption::NAWS,
+            TelnetOption::SUPPRESS_GO_AHEAD,
+            TelnetOption::MXP,
+        ]
+    );
+}
+
+#[test]
+fn test_telnet_options_rejects_unrequestable() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    for value in ["naws, frobnicate", "naws, gmcp", "naws, 150"] {
+        std::fs::write(&path, format!("[server]\ntelnet_options = \"{}\"\n", value)).unwrap();
+        assert!(
+            BbsConfig::load_from_file(path.to_str().unwrap()).is_err(),
+            "{}",
+            value
+        );
+    }
+}
+
+#[test]
+fn test_scripting_options() {
+    let config = BbsConfig::default();
+    assert!(!config.scripting.enabled);
+    assert_eq!(config.scripting.directory, "scripts");
+
+    let config = load_config("[scripting]\nenabled = true\ndirectory = \"/srv/bbs/scripts\"\n");
+    assert!(config.scripting.enabled);
+    assert_eq!(config.scripting.directory, "/srv/bbs/scripts");
+}
+
+#[test]
+fn test_gateway_options() {
+    let config = BbsConfig::default();
+    assert!(!config.gateway.enabled);
+    assert_eq!(config.gateway.level, SecurityLevel::User);
+    assert_eq!(config.gateway.max_minutes, 30);
+    assert!(config.gateway.boards.is_empty());
+
+    let config = load_config(
+        r#"
+[gateway]
+enabled = true
+level = "guest"
+max_minutes = 15
+board = "Level 29|bbs.example.com:2323"
+board = "Local Test | 127.0.0.1:23"
+"#,
+    );
+    assert!(config.gateway.enabled);
+    assert_eq!(config.gateway.level, SecurityLevel::Guest);
+    assert_eq!(config.gateway.max_minutes, 15);
+    assert_eq!(config.gateway.boards.len(), 2);
+    assert_eq!(config.gateway.boards[0].name, "Level 29");
+    assert_eq!(config.gateway.boards[0].address, "bbs.example.com:2323");
+    assert_eq!(config.gateway.boards[1].name, "Local Test");
+    assert_eq!(config.gateway.boards[1].address, "127.0.0.1:23");
+}
+
+#[test]
+fn test_gateway_board_rejects_bad_entries() {
+    for entry in [
+        "No Address",
+        "|host:23",
+        "Name|host",
+        "Name|host:port",
+        "Name|:23",
+    ] {
+        assert!(entry.parse::<GatewayBoard>().is_err(), "{}", entry);
+    }
+}
+
+#[test]
+fn test_feed_options() {
+    let config = BbsConfig::default();
+    assert!(!config.feed.enabled);
+    assert_eq!(config.feed.http_port, 8080);
+    assert_eq!(config.feed.max_items, 20);
+    assert_eq!(config.feed.base_url("127.0.0.1"), "http://127.0.0.1:8080");
+    assert_eq!(config.feed.areas, vec!["main"]);
+
+    let config = load_config(
+        r#"
+[feed]
+enabled = true
+http_port = 8888
+max_items = 5
+base_url = "https://bbs.example.com/"
+areas = "main, Hams,  Retro Games"
+"#,
+    );
+    assert!(config.feed.enabled);
+    assert_eq!(config.feed.http_port, 8888);
+    assert_eq!(config.feed.max_items, 5);
+    assert_eq!(config.feed.base_url("127.0.0.1"), "https://bbs.example.com");
+    assert_eq!(config.feed.areas, vec!["main", "Hams", "Retro Games"]);
+}
+
+#[test]
+fn test_metrics_options() {
+    let config = BbsConfig::default();
+    assert!(!config.metrics.enabled);
+    assert_eq!(config.metrics.slow_render, Duration::from_millis(250));
+
+    let config = load_config(
+        r#"
+[metrics]
+enabled = true
+slow_render_ms = 40
+"#,
+    );
+    assert!(config.metrics.enabled);
+    assert_eq!(config.metrics.slow_render, Duration::from_millis(40));
+}
+
+#[test]
+fn test_motd_options() {
+    assert!(BbsConfig::default().motd.notices.is_empty());
+
+    let config = load_config(
+        r#"
+[motd]
+notice = "!192.168.0.0/16|Outside the LAN.\nCalls are logged."
+notice = "partner.example.com|Hello from Partner BBS"
+"#,
+    );
+    let notices = &config.motd.notices;
+    assert_eq!(notices.len(), 2);
+    assert!(notices[0].outside);
+    assert_eq!(notices[0].range.to_string(), "192.168.0.0/16");
+    assert_eq!(notices[0].text, "Outside the LAN.\nCalls are logged.");
+    assert_eq!(
+        notices[1].range,
+        AddressRange::Host("partner.example.com".to_string())
+    );
+
+    // Written back the way it was read
+    let reparsed: ConnectionNotice = notices[0].to_string().parse().unwrap();
+    assert_eq!(&reparsed, &notices[0]);
+}
+
+#[test]
+fn test_motd_rejects_bad_notices() {
+    for entry in [
+        "No range",
+        "10.0.0.0/8|",
+        "10.0.0.0/33|Text",
+        "::1/129|Text",
+        "10.0.0.0/x|Text",
+        "bad host!|Text",
+        "|Text",
+    ] {
+        assert!(entry.parse::<ConnectionNotice>().is_err(), "{}", entry);
+    }
+}
+
+#[test]
+fn test_feed_rejects_zero_items() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    std::fs::write(&path, "[feed]\nmax_items = 0\n").unwrap();
+    assert!(BbsConfig::load_from_file(path.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_username_rules_options() {
+    let config = BbsConfig::default();
+    assert!(config.features.usernames.allow_unicode);
+    assert!(!config.features.usernames.case_insensitive);
+    assert!(config.features.usernames.is_reserved("Admin"));
+
+    let config = load_config(
+        r#"
+[features]
+username_unicode = false
+username_digits = false
+username_symbols = "_-"
+username_case_insensitive = true
+reserved_usernames = "root, guest"
+"#,
+    );
+    let rules = &config.features.usernames;
+    assert!(!rules.allows_char('é'));
+    assert!(!rules.allows_char('7'));
+    assert!(rules.allows_char('-'));
+    assert!(rules.case_insensitive);
+    assert!(rules.is_reserved("ROOT"));
+    assert!(!rules.is_reserved("admin"));
+}
+
+#[test]
+fn test_username_symbols_reject_letters() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    std::fs::write(&path, "[features]\nusername_symbols = \"_a\"\n").unwrap();
+    assert!(BbsConfig::load_from_file(path.to_str().unwrap()).is_err());
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use moonbase::confusables::{confusable, skeleton};
+
+#[test]
+fn test_skeleton_folds_case_and_separators() {
+    assert_eq!(skeleton("Sys_Op"), "sysop");
+    assert_eq!(skeleton("  sys op "), "sysop");
+    assert!(confusable("SysOp", "sysop"));
+}
+
+#[test]
+fn test_lookalike_letters_collide() {
+    // Cyrillic а and о, Greek Ο, digits for letters
+    assert!(confusable("\u{0430}lice", "alice"));
+    assert!(confusable("b\u{043E}b", "bob"));
+    assert!(confusable("\u{039F}scar", "oscar"));
+    assert!(confusable("Sys0p", "SysOp"));
+    assert!(confusable("paul", "pau1"));
+    assert!(confusable("modem", "rnodern"));
+    // Accents and fullwidth forms
+    assert!(confusable("José", "jose"));
+    assert!(confusable("\u{FF41}dmin", "admin"));
+}
+
+#[test]
+fn test_invisible_characters_ignored() {
+    assert!(confusable("al\u{200B}ice", "alice"));
+    assert!(confusable("e\u{0301}ve", "eve"));
+}
+
+#[test]
+fn test_different_names_stay_apart() {
+    assert!(!confusable("alice", "alicia"));
+    assert!(!confusable("bob", "rob"));
+    assert!(!confusable("", ""));
+    assert!(!confusable("---", "alice"));
+}
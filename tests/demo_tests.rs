@@ -0,0 +1,95 @@
+mod common;
+
+use moonbase::config::{BbsConfig, FeatureAccess, SecurityLevel};
+use moonbase::demo::{self, DEMO_PASSWORD, DemoReport};
+use moonbase::users::RegistrationRequest;
+use tempfile::TempDir;
+
+#[test]
+fn test_seed_fills_an_empty_board() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+
+    let report = demo::seed_demo(&config, &services).unwrap();
+    assert_eq!(
+        report,
+        DemoReport {
+            users: 5,
+            bulletins: 9,
+            clubs: 1,
+            messages: 4,
+        }
+    );
+    assert!(!demo::is_empty(&services).unwrap());
+
+    let ada = services
+        .users
+        .authenticate("ada", DEMO_PASSWORD)
+        .unwrap()
+        .unwrap();
+    assert_eq!(ada.display_name.as_deref(), Some("Ada Vance"));
+
+    let main_board = services
+        .bulletins
+        .list_bulletins(None, None, SecurityLevel::Guest)
+        .unwrap();
+    assert_eq!(main_board.len(), 7);
+    let club = services.clubs.get_club("Retro Lab").unwrap().unwrap();
+    assert!(club.is_member("modemkid"));
+    assert!(!club.is_member("ada"));
+
+    let inbox = services
+        .messages
+        .get_inbox("nightowl", SecurityLevel::User)
+        .unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].subject, "Welcome aboard");
+}
+
+#[test]
+fn test_seed_refuses_a_board_in_use() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    let request = RegistrationRequest::new("alice".to_string(), None, "secret".to_string());
+    services.users.register(request, &config).unwrap();
+
+    assert!(demo::seed_demo(&config, &services).is_err());
+    assert_eq!(services.users.get_stats().unwrap().total_users, 1);
+}
+
+#[test]
+fn test_seed_skips_features_callers_cannot_write() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.features.bulletins = FeatureAccess::ReadOnly;
+    config.features.messages = FeatureAccess::Off;
+
+    let report = demo::seed_demo(&config, &common::services(temp_dir.path(), &config)).unwrap();
+    assert_eq!(
+        report,
+        DemoReport {
+            users: 5,
+            ..DemoReport::default()
+        }
+    );
+}
+
+#[test]
+fn test_wipe_moves_data_aside() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    demo::seed_demo(&config, &common::services(temp_dir.path(), &config)).unwrap();
+
+    let backup_dir = demo::wipe(temp_dir.path()).unwrap();
+    assert!(backup_dir.starts_with(temp_dir.path().join("backups")));
+    assert!(backup_dir.join("users.json").exists());
+    assert!(backup_dir.join("bulletins.json").exists());
+    assert!(!temp_dir.path().join("users.json").exists());
+
+    // Seeding again starts from nothing
+    let services = common::services(temp_dir.path(), &config);
+    assert!(demo::is_empty(&services).unwrap());
+    assert_eq!(demo::seed_demo(&config, &services).unwrap().users, 5);
+}
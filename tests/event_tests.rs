@@ -0,0 +1,119 @@
+mod common;
+
+use moonbase::config::{BbsConfig, SecurityLevel};
+use moonbase::events::{BbsEvent, EventBus};
+use moonbase::messages::MessageRequest;
+use tempfile::TempDir;
+
+fn login(username: &str) -> BbsEvent {
+    BbsEvent::Login {
+        username: username.to_string(),
+    }
+}
+
+#[test]
+fn test_every_subscriber_receives_events() {
+    let bus = EventBus::default();
+    let first = bus.subscribe();
+    let second = bus.subscribe();
+
+    assert_eq!(bus.publish(login("alice")), 2);
+
+    assert_eq!(first.drain(), vec![login("alice")]);
+    assert_eq!(second.drain(), vec![login("alice")]);
+    assert!(first.drain().is_empty());
+}
+
+#[test]
+fn test_events_before_subscribing_are_not_seen() {
+    let bus = EventBus::default();
+    bus.publish(login("alice"));
+
+    let late = bus.subscribe();
+    bus.publish(login("bob"));
+
+    assert_eq!(late.drain(), vec![login("bob")]);
+}
+
+#[test]
+fn test_full_queue_drops_and_counts() {
+    let bus = EventBus::new(2);
+    let slow = bus.subscribe();
+    let fast = bus.subscribe();
+
+    for name in ["alice", "bob"] {
+        bus.publish(login(name));
+    }
+    assert_eq!(fast.drain().len(), 2);
+
+    // Only the slow subscriber's queue is full; publishing does not block
+    assert_eq!(bus.publish(login("carol")), 1);
+    assert_eq!(fast.drain(), vec![login("carol")]);
+
+    assert_eq!(slow.drain(), vec![login("alice"), login("bob")]);
+    assert_eq!(slow.take_missed(), 1);
+    assert_eq!(slow.take_missed(), 0);
+}
+
+#[test]
+fn test_dropping_subscription_unsubscribes() {
+    let bus = EventBus::default();
+    let subscription = bus.subscribe();
+    let clone = bus.clone();
+    assert_eq!(clone.subscriber_count(), 1);
+
+    drop(subscription);
+    assert_eq!(bus.subscriber_count(), 0);
+    assert_eq!(clone.publish(login("alice")), 0);
+}
+
+#[test]
+fn test_sending_mail_publishes_new_message() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    let subscription = services.events.subscribe();
+
+    let request = MessageRequest::new(
+        "bob".to_string(),
+        "Hello".to_string(),
+        "Are you coming to the meet?".to_string(),
+        "alice".to_string(),
+    );
+    let message_id = services
+        .messages
+        .send_message(request, &config, SecurityLevel::User)
+        .unwrap();
+
+    assert_eq!(
+        subscription.drain(),
+        vec![BbsEvent::NewMessage {
+            to: "bob".to_string(),
+            from: "alice".to_string(),
+            message_id,
+        }]
+    );
+}
+
+#[test]
+fn test_failed_send_publishes_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    let subscription = services.events.subscribe();
+
+    // Empty subject fails validation
+    let request = MessageRequest::new(
+        "bob".to_string(),
+        String::new(),
+        "No subject".to_string(),
+        "alice".to_string(),
+    );
+    assert!(
+        services
+            .messages
+            .send_message(request, &config, SecurityLevel::User)
+            .is_err()
+    );
+    assert!(subscription.drain().is_empty());
+}
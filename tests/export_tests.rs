@@ -0,0 +1,141 @@
+mod common;
+
+use moonbase::bulletins::BulletinRequest;
+use moonbase::config::{BbsConfig, FeatureAccess, SecurityLevel};
+use moonbase::errors::BbsError;
+use moonbase::services::{CoreServices, ExportFormat};
+use std::fs;
+use tempfile::TempDir;
+
+fn post(services: &CoreServices, config: &BbsConfig, title: &str, content: &str) -> u32 {
+    let request = BulletinRequest::new(title.to_string(), content.to_string(), "sysop".to_string());
+    services
+        .bulletins
+        .post_bulletin(request, config, SecurityLevel::Sysop)
+        .unwrap()
+}
+
+#[test]
+fn test_mbox_export_is_incremental() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    let mbox = temp_dir.path().join("bulletins.mbox");
+
+    post(&services, &config, "First", "Hello");
+    let second = post(&services, &config, "Second", "World");
+
+    let report = services
+        .export
+        .export(ExportFormat::Mbox, &mbox, SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(report.exported, 2);
+    assert_eq!(report.last_id, second);
+
+    // Nothing new: nothing written
+    let report = services
+        .export
+        .export(ExportFormat::Mbox, &mbox, SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(report.exported, 0);
+    assert_eq!(report.last_id, second);
+
+    let third = post(&services, &config, "Third", "Again");
+    let report = services
+        .export
+        .export(ExportFormat::Mbox, &mbox, SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(report.exported, 1);
+    assert_eq!(report.last_id, third);
+
+    let content = fs::read_to_string(&mbox).unwrap();
+    assert_eq!(content.matches("\nSubject: ").count(), 3);
+    assert!(content.starts_with("From sysop@moonbase.invalid "));
+    assert!(content.contains(&format!(
+        "Message-ID: <bulletin-{}@moonbase.invalid>",
+        third
+    )));
+    let first = content.find("Subject: First").unwrap();
+    assert!(first < content.find("Subject: Third").unwrap());
+}
+
+#[test]
+fn test_mbox_quotes_from_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    let mbox = temp_dir.path().join("bulletins.mbox");
+
+    post(
+        &services,
+        &config,
+        "Quoting",
+        "Line one\\nFrom the sysop\\n>From before",
+    );
+    services
+        .export
+        .export(ExportFormat::Mbox, &mbox, SecurityLevel::Sysop)
+        .unwrap();
+
+    let content = fs::read_to_string(&mbox).unwrap();
+    assert!(content.contains("\nLine one\n>From the sysop\n>>From before\n"));
+    assert_eq!(content.matches("\nFrom ").count(), 0);
+}
+
+#[test]
+fn test_maildir_export() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(temp_dir.path(), &config);
+    let maildir = temp_dir.path().join("Maildir");
+
+    post(&services, &config, "Café ☕ night", "Bring mugs");
+    services
+        .export
+        .export(ExportFormat::Maildir, &maildir, SecurityLevel::Sysop)
+        .unwrap();
+    post(&services, &config, "Second", "More");
+    services
+        .export
+        .export(ExportFormat::Maildir, &maildir, SecurityLevel::Sysop)
+        .unwrap();
+
+    assert!(maildir.join("cur").is_dir());
+    assert_eq!(fs::read_dir(maildir.join("tmp")).unwrap().count(), 0);
+    let mut messages: Vec<String> = fs::read_dir(maildir.join("new"))
+        .unwrap()
+        .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect();
+    messages.sort();
+    assert_eq!(messages.len(), 2);
+
+    let cafe = messages.iter().find(|m| m.contains("Bring mugs")).unwrap();
+    // Non-ASCII subjects are RFC 2047 encoded
+    assert!(cafe.contains("Subject: =?UTF-8?B?Q2Fmw6kg4piVIG5pZ2h0?=\n"));
+    assert!(cafe.contains("Content-Type: text/plain; charset=utf-8\n"));
+}
+
+#[test]
+fn test_export_requires_bulletins_enabled() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.features.bulletins = FeatureAccess::Off;
+    let services = common::services(temp_dir.path(), &config);
+
+    let result = services.export.export(
+        ExportFormat::Mbox,
+        &temp_dir.path().join("bulletins.mbox"),
+        SecurityLevel::Sysop,
+    );
+    assert!(matches!(result, Err(BbsError::FeatureDisabled(_))));
+}
+
+#[test]
+fn test_export_format_parsing() {
+    assert_eq!("mbox".parse::<ExportFormat>().unwrap(), ExportFormat::Mbox);
+    assert_eq!(
+        "Maildir".parse::<ExportFormat>().unwrap(),
+        ExportFormat::Maildir
+    );
+    assert!("pst".parse::<ExportFormat>().is_err());
+}
@@ -0,0 +1,165 @@
+#![cfg(unix)]
+
+mod common;
+
+use moonbase::config::{BbsConfig, CommandsConfig, ExternalCommand, SecurityLevel};
+use moonbase::external::{self, CommandCaller, MAX_OUTPUT_BYTES};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const RESERVED: &[&str] = &["1", "q"];
+
+fn commands(allowed: &[&str], lines: &[&str]) -> CommandsConfig {
+    CommandsConfig {
+        allowed: allowed.iter().map(|p| p.to_string()).collect(),
+        timeout: Duration::from_secs(5),
+        commands: lines.iter().map(|line| line.parse().unwrap()).collect(),
+    }
+}
+
+fn caller(username: &str) -> CommandCaller<'_> {
+    CommandCaller {
+        username: Some(username),
+        level: SecurityLevel::User,
+        node: 3,
+        bbs_name: "Moonbase",
+    }
+}
+
+#[test]
+fn test_command_lines_parse_and_round_trip() {
+    let command: ExternalCommand = "U|System Uptime|guest|/usr/bin/uptime -p".parse().unwrap();
+    assert_eq!(command.key, "u");
+    assert_eq!(command.description, "System Uptime");
+    assert_eq!(command.level, SecurityLevel::Guest);
+    assert_eq!(command.program, "/usr/bin/uptime");
+    assert_eq!(command.args, vec!["-p"]);
+    assert_eq!(
+        command.to_string().parse::<ExternalCommand>().unwrap(),
+        command
+    );
+
+    for bad in [
+        "U|Uptime|guest",
+        "U|Uptime|guest|",
+        "U|Uptime|wizard|/usr/bin/uptime",
+        "!|Uptime|guest|/usr/bin/uptime",
+    ] {
+        assert!(bad.parse::<ExternalCommand>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_commands_section_in_config_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    std::fs::write(
+        &path,
+        r#"
+[commands]
+allowed = "/usr/games/fortune, /usr/bin/uptime"
+timeout_seconds = 3
+command = "K|Fortune Cookie|guest|/usr/games/fortune -s"
+command = "U|Uptime|user|/usr/bin/uptime"
+"#,
+    )
+    .unwrap();
+    let config = BbsConfig::load_from_file(path.to_str().unwrap()).unwrap();
+
+    assert_eq!(
+        config.commands.allowed,
+        vec!["/usr/games/fortune", "/usr/bin/uptime"]
+    );
+    assert_eq!(config.commands.timeout, Duration::from_secs(3));
+    assert_eq!(config.commands.commands.len(), 2);
+    assert_eq!(config.commands.commands[1].level, SecurityLevel::User);
+    assert!(external::check(&config.commands, RESERVED).is_ok());
+}
+
+#[test]
+fn test_check_rejects_unsafe_commands() {
+    // Not on the allowlist
+    let config = commands(&["/bin/echo"], &["E|Echo|guest|/bin/sh -c reboot"]);
+    assert!(external::check(&config, RESERVED).is_err());
+
+    // Shadows a built-in key, or another command
+    let config = commands(&["/bin/echo"], &["Q|Echo|guest|/bin/echo"]);
+    assert!(external::check(&config, RESERVED).is_err());
+    let config = commands(
+        &["/bin/echo"],
+        &["E|Echo|guest|/bin/echo", "E|Again|guest|/bin/echo"],
+    );
+    assert!(external::check(&config, RESERVED).is_err());
+
+    // Unknown variable
+    let config = commands(&["/bin/echo"], &["E|Echo|guest|/bin/echo {password}"]);
+    assert!(external::check(&config, RESERVED).is_err());
+}
+
+#[test]
+fn test_run_fills_in_arguments() {
+    let config = commands(
+        &["/bin/echo"],
+        &["E|Echo|guest|/bin/echo {user} on node {node} of {bbs_name} ({level})"],
+    );
+    let output = external::run(&config.commands[0], &config, caller("alice")).unwrap();
+
+    assert_eq!(output.text, "alice on node 3 of Moonbase (user)\n");
+    assert!(output.success);
+    assert!(!output.timed_out);
+    assert!(!output.truncated);
+
+    // Substituted values are never expanded again
+    let output = external::run(&config.commands[0], &config, caller("{node}")).unwrap();
+    assert_eq!(output.text, "{node} on node 3 of Moonbase (user)\n");
+}
+
+#[test]
+fn test_run_refuses_programs_not_allowed() {
+    let config = commands(&[], &["E|Echo|guest|/bin/echo hi"]);
+    let error = external::run(&config.commands[0], &config, caller("alice")).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn test_slow_programs_are_killed() {
+    let mut config = commands(&["/bin/sleep"], &["S|Sleep|guest|/bin/sleep 30"]);
+    config.timeout = Duration::from_millis(200);
+
+    let started = Instant::now();
+    let output = external::run(&config.commands[0], &config, caller("alice")).unwrap();
+    assert!(output.timed_out);
+    assert!(!output.success);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_output_is_capped() {
+    let mut config = commands(&["/usr/bin/yes"], &["Y|Yes|guest|/usr/bin/yes"]);
+    config.timeout = Duration::from_millis(300);
+
+    let output = external::run(&config.commands[0], &config, caller("alice")).unwrap();
+    assert!(output.truncated);
+    assert_eq!(output.text.len(), MAX_OUTPUT_BYTES);
+}
+
+#[test]
+fn test_output_after_exit_is_cut_off_not_lost() {
+    // The script exits at once, leaving a loop writing to its output
+    let temp_dir = TempDir::new().unwrap();
+    let script = temp_dir.path().join("ticker.sh");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\n(for i in $(seq 200); do echo tick; sleep 0.005; done) &\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let script = script.to_str().unwrap();
+    let config = commands(&[script], &[&format!("T|Ticker|guest|{}", script)]);
+
+    let output = external::run(&config.commands[0], &config, caller("alice")).unwrap();
+    assert!(output.success);
+    assert!(output.truncated);
+    assert!(output.text.starts_with("tick\n"));
+    assert!(output.text.lines().all(|line| line == "tick"));
+}
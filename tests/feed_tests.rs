@@ -0,0 +1,240 @@
+mod common;
+
+use moonbase::bulletins::{Bulletin, BulletinRequest};
+use moonbase::clubs::ClubRequest;
+use moonbase::config::{BbsConfig, FeatureAccess, SecurityLevel};
+use moonbase::feed::{FEED_PATH, atom_feed, feed_path, serve};
+use moonbase::guide::GUIDE_PATH;
+use moonbase::services::CoreServices;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+
+fn post(
+    services: &CoreServices,
+    config: &BbsConfig,
+    area: Option<&str>,
+    title: &str,
+    content: &str,
+) {
+    let request = BulletinRequest::new(title.to_string(), content.to_string(), "sysop".to_string())
+        .with_area(area.map(str::to_string));
+    services
+        .bulletins
+        .post_bulletin(request, config, SecurityLevel::Sysop)
+        .unwrap();
+}
+
+/// Start a feed listener on a free port and return its address
+fn start_feed(config: BbsConfig, services: CoreServices) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || serve(listener, Arc::new(config), Arc::new(services)));
+    address
+}
+
+fn request(address: &str, request_line: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "{}\r\nHost: test\r\n\r\n", request_line).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_atom_feed_lists_entries() {
+    let mut config = BbsConfig::default();
+    config.feed.base_url = "https://bbs.example.com".to_string();
+    let bulletins = vec![Bulletin::new(
+        7,
+        "Fish & Chips <Friday>".to_string(),
+        "Line one\\nLine two\u{1b}[31m".to_string(),
+        "sysop".to_string(),
+    )];
+
+    let xml = atom_feed(&config, None, &bulletins);
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>"));
+    assert!(xml.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+    assert!(xml.contains("<id>https://bbs.example.com/bulletins.atom</id>"));
+    assert!(xml.contains("<id>https://bbs.example.com/bulletins.atom#7</id>"));
+    assert!(xml.contains("<title>Fish &amp; Chips &lt;Friday&gt;</title>"));
+    // Stored "\n" becomes a real line break and the escape byte is dropped
+    assert!(xml.contains("Line one\nLine two[31m</content>"));
+    assert!(xml.contains(&format!("<updated>{}</updated>", bulletins[0].posted_at)));
+    assert_eq!(xml.matches("<entry>").count(), 1);
+}
+
+#[test]
+fn test_empty_feed_is_valid() {
+    let xml = atom_feed(&BbsConfig::default(), None, &[]);
+    assert!(xml.contains("<updated>"));
+    assert!(!xml.contains("<entry>"));
+    assert!(xml.trim_end().ends_with("</feed>"));
+}
+
+#[test]
+fn test_feed_paths() {
+    assert_eq!(feed_path(None), FEED_PATH);
+    assert_eq!(feed_path(Some("Hams")), "/areas/hams.atom");
+    assert_eq!(feed_path(Some("Retro Games!")), "/areas/retro-games.atom");
+    assert_eq!(feed_path(Some("Ham Radio-2")), "/areas/ham-radio-2.atom");
+}
+
+#[test]
+fn test_club_feed_names_the_club() {
+    let mut config = BbsConfig::default();
+    config.feed.base_url = "https://bbs.example.com".to_string();
+
+    let xml = atom_feed(&config, Some("Hams"), &[]);
+
+    assert!(xml.contains("<title>Rust BBS Bulletins: Hams</title>"));
+    assert!(xml.contains("<id>https://bbs.example.com/areas/hams.atom</id>"));
+}
+
+#[test]
+fn test_feed_served_over_http() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.feed.enabled = true;
+    config.feed.max_items = 2;
+    let services = common::services(temp_dir.path(), &config);
+    for title in ["First", "Second", "Third"] {
+        post(&services, &config, None, title, "Hello");
+    }
+    let address = start_feed(config, services);
+
+    let response = request(&address, &format!("GET {} HTTP/1.1", FEED_PATH));
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: application/atom+xml"));
+    // max_items keeps only the newest
+    assert_eq!(response.matches("<entry>").count(), 2);
+
+    let head = request(&address, &format!("HEAD {}?since=1 HTTP/1.1", FEED_PATH));
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.ends_with("\r\n\r\n"));
+
+    let missing = request(&address, "GET /users HTTP/1.1");
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let post = request(&address, &format!("POST {} HTTP/1.1", FEED_PATH));
+    assert!(post.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+}
+
+#[test]
+fn test_members_only_bulletins_are_not_published() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.feed.enabled = true;
+    config.features.bulletins = FeatureAccess::level(SecurityLevel::User);
+    let services = common::services(temp_dir.path(), &config);
+    post(&services, &config, None, "Members only", "Secret");
+    let address = start_feed(config, services);
+
+    let response = request(&address, &format!("GET {} HTTP/1.1", FEED_PATH));
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(!response.contains("Secret"));
+}
+
+#[test]
+fn test_board_guide_served_with_the_feed() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.metrics.enabled = true;
+    let address = start_feed(config.clone(), common::services(temp_dir.path(), &config));
+
+    // Only published alongside the feed
+    let response = request(&address, &format!("GET {} HTTP/1.1", GUIDE_PATH));
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    config.feed.enabled = true;
+    let address = start_feed(config.clone(), common::services(temp_dir.path(), &config));
+    let response = request(&address, &format!("GET {} HTTP/1.1", GUIDE_PATH));
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Content-Type: text/plain; charset=utf-8"));
+    assert!(response.contains("Rust BBS - BOARD GUIDE"));
+}
+
+fn create_club(services: &CoreServices, name: &str) {
+    let request = ClubRequest::new(name.to_string(), String::new(), None);
+    services
+        .clubs
+        .create_club(request, "sysop", SecurityLevel::Sysop)
+        .unwrap();
+}
+
+#[test]
+fn test_listed_clubs_keep_their_bulletins() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.feed.enabled = true;
+    config.feed.areas = vec!["main".to_string(), "hams".to_string()];
+    let services = common::services(temp_dir.path(), &config);
+    create_club(&services, "Hams");
+    create_club(&services, "Secret Society");
+    post(&services, &config, None, "Open day", "Everyone welcome");
+    post(
+        &services,
+        &config,
+        Some("Hams"),
+        "Field day",
+        "Bring antennas",
+    );
+    post(
+        &services,
+        &config,
+        Some("Secret Society"),
+        "Meeting",
+        "Password is swordfish",
+    );
+    let address = start_feed(config.clone(), services);
+
+    let main = request(&address, &format!("GET {} HTTP/1.1", FEED_PATH));
+    assert!(main.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(main.contains("Open day"));
+    assert!(!main.contains("Field day"));
+
+    // Listing a club doesn't open it to guests
+    let hams = request(
+        &address,
+        &format!("GET {} HTTP/1.1", feed_path(Some("Hams"))),
+    );
+    assert!(hams.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(!hams.contains("Field day"));
+
+    let secret = request(
+        &address,
+        &format!("GET {} HTTP/1.1", feed_path(Some("Secret Society"))),
+    );
+    assert!(secret.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(!secret.contains("swordfish"));
+
+    // The board guide lists just the feeds guests can read
+    let guide = request(&address, &format!("GET {} HTTP/1.1", GUIDE_PATH));
+    assert!(guide.contains(FEED_PATH));
+    assert!(!guide.contains("/areas/hams.atom"));
+}
+
+#[test]
+fn test_main_board_feed_can_be_turned_off() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.feed.enabled = true;
+    config.feed.areas = vec!["Gone".to_string()];
+    let services = common::services(temp_dir.path(), &config);
+    post(&services, &config, None, "Open day", "Everyone welcome");
+    let address = start_feed(config, services);
+
+    let main = request(&address, &format!("GET {} HTTP/1.1", FEED_PATH));
+    assert!(main.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(!main.contains("Open day"));
+
+    // Listing a club that doesn't exist publishes nothing
+    let gone = request(
+        &address,
+        &format!("GET {} HTTP/1.1", feed_path(Some("Gone"))),
+    );
+    assert!(gone.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
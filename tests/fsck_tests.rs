@@ -0,0 +1,160 @@
+mod common;
+
+use moonbase::bulletin_repository::{BulletinStorage, JsonBulletinStorage};
+use moonbase::bulletins::BulletinRequest;
+use moonbase::club_repository::{ClubStorage, JsonClubStorage};
+use moonbase::clubs::ClubRequest;
+use moonbase::config::BbsConfig;
+use moonbase::fsck::{DataChecker, FsckIssue};
+use moonbase::message_repository::{JsonMessageStorage, MessageStorage};
+use moonbase::messages::MessageRequest;
+use moonbase::user_repository::{JsonUserStorage, UserStorage};
+use moonbase::users::RegistrationRequest;
+use tempfile::TempDir;
+
+/// Two users, two bulletins and one message between the users
+fn seed(dir: &TempDir) {
+    let config = BbsConfig::default();
+
+    let mut users = JsonUserStorage::new(dir.path()).unwrap();
+    for name in ["alice", "bob"] {
+        let request = RegistrationRequest::new(name.to_string(), None, "password123".to_string());
+        users.register_user(&request, &config).unwrap();
+    }
+
+    let mut bulletins = JsonBulletinStorage::new(dir.path()).unwrap();
+    for title in ["First", "Second"] {
+        let request = BulletinRequest::new(
+            title.to_string(),
+            "Content".to_string(),
+            "alice".to_string(),
+        );
+        bulletins.post_bulletin(&request, &config).unwrap();
+    }
+
+    let mut messages = JsonMessageStorage::new(dir.path()).unwrap();
+    let request = MessageRequest::new(
+        "bob".to_string(),
+        "Hello".to_string(),
+        "Hi bob".to_string(),
+        "alice".to_string(),
+    );
+    messages.send_message(&request, &config).unwrap();
+}
+
+fn edit_json(dir: &TempDir, file: &str, edit: impl FnOnce(&mut serde_json::Value)) {
+    let path = dir.path().join(file);
+    let mut value: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    edit(&mut value);
+    std::fs::write(&path, serde_json::to_string_pretty(&value).unwrap()).unwrap();
+}
+
+#[test]
+fn test_clean_data_passes() {
+    let dir = TempDir::new().unwrap();
+    seed(&dir);
+
+    let report = DataChecker::load(dir.path()).unwrap().check().unwrap();
+    assert!(report.is_clean(), "{:?}", report.issues);
+}
+
+#[test]
+fn test_detects_dangling_references() {
+    let dir = TempDir::new().unwrap();
+    seed(&dir);
+
+    // Delete bob, mark a missing bulletin read, and make bulletin 2 claim ID 1
+    edit_json(&dir, "users.json", |users| {
+        users.as_object_mut().unwrap().remove("bob");
+        users["alice"]["bulletin_reads"] = serde_json::json!({
+            "high_water": 0,
+            "read_above": [2, 9]
+        });
+    });
+    edit_json(&dir, "bulletins.json", |data| {
+        data["bulletins"]["2"]["id"] = serde_json::json!(1);
+        data["bulletins"]["1"]["area"] = serde_json::json!("Gone");
+    });
+
+    let report = DataChecker::load(dir.path()).unwrap().check().unwrap();
+    assert!(report.issues.contains(&FsckIssue::MessageUserMissing {
+        id: 1,
+        username: "bob".to_string()
+    }));
+    assert!(
+        report
+            .issues
+            .contains(&FsckIssue::DuplicateBulletinId { id: 1, key: 2 })
+    );
+    assert!(report.issues.contains(&FsckIssue::DanglingBulletinRead {
+        username: "alice".to_string(),
+        bulletin_id: 9
+    }));
+    assert!(report.issues.contains(&FsckIssue::BulletinClubMissing {
+        id: 1,
+        club: "Gone".to_string()
+    }));
+}
+
+#[test]
+fn test_bulletins_in_existing_clubs_pass() {
+    let dir = TempDir::new().unwrap();
+    seed(&dir);
+    let mut clubs = JsonClubStorage::new(dir.path()).unwrap();
+    let club = ClubRequest::new("Hams".to_string(), String::new(), None)
+        .into_club("alice")
+        .unwrap();
+    clubs.save_club(&club).unwrap();
+    edit_json(&dir, "bulletins.json", |data| {
+        data["bulletins"]["1"]["area"] = serde_json::json!("hams");
+    });
+
+    let report = DataChecker::load(dir.path()).unwrap().check().unwrap();
+    assert!(report.is_clean(), "{:?}", report.issues);
+}
+
+#[test]
+fn test_repair_backs_up_and_fixes() {
+    let dir = TempDir::new().unwrap();
+    seed(&dir);
+
+    edit_json(&dir, "users.json", |users| {
+        users.as_object_mut().unwrap().remove("bob");
+        users["alice"]["bulletin_reads"] = serde_json::json!({
+            "high_water": 0,
+            "read_above": [2, 9]
+        });
+    });
+    edit_json(&dir, "bulletins.json", |data| {
+        data["bulletins"]["2"]["id"] = serde_json::json!(1);
+        data["bulletins"]["1"]["area"] = serde_json::json!("Gone");
+    });
+    let damaged_users = std::fs::read_to_string(dir.path().join("users.json")).unwrap();
+
+    let mut checker = DataChecker::load(dir.path()).unwrap();
+    let backup_dir = checker.repair().unwrap();
+
+    // Backup holds the pre-repair files
+    assert_eq!(
+        std::fs::read_to_string(backup_dir.join("users.json")).unwrap(),
+        damaged_users
+    );
+
+    let report = DataChecker::load(dir.path()).unwrap().check().unwrap();
+    assert!(report.is_clean(), "{:?}", report.issues);
+
+    // The repaired files still load through the normal repositories, less
+    // the bulletin whose club is gone
+    let bulletins = JsonBulletinStorage::new(dir.path()).unwrap();
+    assert!(bulletins.load_bulletin(1).unwrap().is_none());
+    assert_eq!(bulletins.get_stats(None).total_bulletins, 1);
+    let messages = JsonMessageStorage::new(dir.path()).unwrap();
+    let sent = messages.get_sent("alice").unwrap();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].is_deleted_by_recipient);
+
+    let users = JsonUserStorage::new(dir.path()).unwrap();
+    let alice = users.load_user("alice").unwrap().unwrap();
+    assert!(!alice.bulletin_reads.read_above.contains(&9));
+}
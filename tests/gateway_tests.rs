@@ -0,0 +1,170 @@
+mod common;
+
+use moonbase::config::GatewayBoard;
+use moonbase::connection::Connection;
+use moonbase::gateway::{self, ESCAPE_BYTE, GatewayEnd};
+use moonbase::session_stream::SessionStream;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use telnet_negotiation::{TelnetStream, TerminalCapabilities};
+
+/// A caller's raw socket and the session-side stream it is connected to
+fn caller_pair() -> (TcpStream, SessionStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    (
+        client,
+        SessionStream::new(TelnetStream::new(Connection::from(server))),
+    )
+}
+
+/// A board listening on a local port, run by `serve` on its own thread
+fn spawn_board<F>(serve: F) -> (GatewayBoard, thread::JoinHandle<()>)
+where
+    F: FnOnce(TcpStream) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let board = GatewayBoard {
+        name: "Test Board".to_string(),
+        address: listener.local_addr().unwrap().to_string(),
+    };
+    let handle = thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        serve(socket);
+    });
+    (board, handle)
+}
+
+/// Read from `socket` until `expected` shows up
+fn read_until(socket: &mut TcpStream, expected: &[u8]) -> Vec<u8> {
+    let mut received = Vec::new();
+    let mut buf = [0u8; 256];
+    while !received.windows(expected.len()).any(|w| w == expected) {
+        let n = socket.read(&mut buf).unwrap();
+        assert!(n > 0, "connection closed before {:?}", expected);
+        received.extend_from_slice(&buf[..n]);
+    }
+    received
+}
+
+#[test]
+fn test_gateway_relays_both_ways_until_remote_closes() {
+    let (board, remote) = spawn_board(|mut socket| {
+        // IAC DO TTYPE, then a banner
+        socket.write_all(&[255, 253, 24]).unwrap();
+        socket.write_all(b"Welcome to Test Board\r\n").unwrap();
+        read_until(&mut socket, b"hello");
+    });
+    let (mut caller, mut session) = caller_pair();
+
+    let caps = TerminalCapabilities {
+        terminal_type: Some("XTERM".to_string()),
+        ..Default::default()
+    };
+    let mut connection = gateway::connect(&board, &caps).unwrap();
+
+    let typist = thread::spawn(move || {
+        read_until(&mut caller, b"Welcome to Test Board");
+        caller.write_all(b"hello\r\n").unwrap();
+        caller
+    });
+
+    let report = gateway::proxy(&mut session, &mut connection, Duration::from_secs(10)).unwrap();
+    remote.join().unwrap();
+    typist.join().unwrap();
+
+    assert_eq!(report.end, GatewayEnd::RemoteClosed);
+    assert!(report.bytes_in >= 23);
+    assert_eq!(report.bytes_out, 7);
+}
+
+#[test]
+fn test_gateway_escape_key_returns_caller() {
+    let (board, remote) = spawn_board(|mut socket| {
+        let received = read_until(&mut socket, b"bye");
+        assert!(!received.contains(&ESCAPE_BYTE));
+    });
+    let (mut caller, mut session) = caller_pair();
+    let mut connection = gateway::connect(&board, &TerminalCapabilities::default()).unwrap();
+
+    caller.write_all(b"bye").unwrap();
+    caller.write_all(&[ESCAPE_BYTE]).unwrap();
+
+    let report = gateway::proxy(&mut session, &mut connection, Duration::from_secs(10)).unwrap();
+    remote.join().unwrap();
+
+    assert_eq!(report.end, GatewayEnd::Escaped);
+    assert_eq!(report.bytes_out, 3);
+}
+
+#[test]
+fn test_gateway_time_limit_ends_call() {
+    let (board, remote) = spawn_board(|mut socket| {
+        // Stay quiet until the gateway hangs up
+        let mut buf = [0u8; 64];
+        while matches!(socket.read(&mut buf), Ok(n) if n > 0) {}
+    });
+    let (_caller, mut session) = caller_pair();
+    let mut connection = gateway::connect(&board, &TerminalCapabilities::default()).unwrap();
+
+    let report = gateway::proxy(&mut session, &mut connection, Duration::from_millis(200)).unwrap();
+    remote.join().unwrap();
+
+    assert_eq!(report.end, GatewayEnd::TimeLimit);
+    assert!(report.duration >= Duration::from_millis(200));
+}
+
+#[test]
+fn test_gateway_unreachable_board() {
+    // Bind then drop a listener so the port is almost certainly closed
+    let address = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let board = GatewayBoard {
+        name: "Gone".to_string(),
+        address,
+    };
+
+    assert!(gateway::connect(&board, &TerminalCapabilities::default()).is_err());
+}
+
+#[test]
+fn test_gateway_passes_on_window_resize() {
+    let (board, remote) = spawn_board(|mut socket| {
+        // IAC DO NAWS; the gateway reports the default size first
+        socket.write_all(&[255, 253, 31]).unwrap();
+        read_until(&mut socket, &[255, 250, 31, 0, 80, 0, 24, 255, 240]);
+        // One byte, since the relay may split longer text with Go Aheads
+        socket.write_all(b"!").unwrap();
+        read_until(&mut socket, &[255, 250, 31, 0, 100, 0, 40, 255, 240]);
+    });
+    let (mut caller, mut session) = caller_pair();
+    session.set_interrupt_on_resize(true);
+    let mut connection = gateway::connect(&board, &TerminalCapabilities::default()).unwrap();
+
+    let resizer = thread::spawn(move || {
+        read_until(&mut caller, b"!");
+        // IAC SB NAWS 100x40 IAC SE
+        caller
+            .write_all(&[255, 250, 31, 0, 100, 0, 40, 255, 240])
+            .unwrap();
+        caller
+    });
+
+    let report = gateway::proxy(&mut session, &mut connection, Duration::from_secs(10)).unwrap();
+    remote.join().unwrap();
+    resizer.join().unwrap();
+
+    assert_eq!(report.end, GatewayEnd::RemoteClosed);
+    assert_eq!(report.bytes_out, 0);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use moonbase::clubs::ClubRequest;
+use moonbase::config::{BbsConfig, FeatureAccess, GatewayBoard, SecurityLevel};
+use moonbase::guide::board_guide;
+use tempfile::TempDir;
+
+#[test]
+fn test_guide_follows_default_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let guide = board_guide(&config, &common::services(temp_dir.path(), &config));
+
+    assert!(guide.starts_with("Rust BBS - BOARD GUIDE\n"));
+    assert!(guide.contains("Bulletin Board (open to everyone)"));
+    assert!(guide.contains("Private Messages (members only)"));
+    assert!(guide.contains("Usernames are up to 20 characters"));
+    assert!(guide.contains("Callers idle for 30 minutes are disconnected."));
+    assert!(guide.contains("\nBULLETIN BOARD\n"));
+    // Off by default
+    assert!(!guide.contains("Other Boards"));
+    assert!(!guide.contains("ON THE WEB"));
+    assert!(!guide.contains("Clubs are bulletin areas"));
+}
+
+#[test]
+fn test_guide_follows_customized_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.features.allow_anonymous = false;
+    config.features.bulletins = FeatureAccess::Gated {
+        read: SecurityLevel::Guest,
+        write: SecurityLevel::User,
+    };
+    config.features.messages = FeatureAccess::Off;
+    config.gateway.enabled = true;
+    config.gateway.max_minutes = 15;
+    config.gateway.boards.push(GatewayBoard {
+        name: "Level 29".to_string(),
+        address: "bbs.example.com:23".to_string(),
+    });
+    config.feed.enabled = true;
+    config.feed.base_url = "https://bbs.example.com/".to_string();
+
+    let services = common::services(temp_dir.path(), &config);
+    let request = ClubRequest::new(
+        "Hams".to_string(),
+        "Amateur radio".to_string(),
+        Some("qsl73".to_string()),
+    );
+    services
+        .clubs
+        .create_club(request, "sysop", SecurityLevel::Sysop)
+        .unwrap();
+
+    let guide = board_guide(&config, &services);
+    assert!(guide.contains("You need to log in before using the board"));
+    assert!(!guide.contains("Login / Register"));
+    assert!(guide.contains("Bulletin Board (everyone can read, members can post)"));
+    assert!(guide.contains("Private Messages (turned off)"));
+    assert!(!guide.contains("\nPRIVATE MESSAGES\n"));
+    assert!(guide.contains("Other Boards (members only)"));
+    assert!(guide.contains("up to 15 minutes"));
+    assert!(guide.contains("  Level 29\n"));
+    assert!(guide.contains("Hams - Amateur radio (join with its password)"));
+    assert!(guide.contains("This guide: https://bbs.example.com/guide.txt"));
+}
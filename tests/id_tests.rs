@@ -0,0 +1,108 @@
+mod common;
+
+use moonbase::bulletin_repository::{BulletinStorage, JsonBulletinStorage};
+use moonbase::bulletins::BulletinRequest;
+use moonbase::config::BbsConfig;
+use moonbase::message_repository::{JsonMessageStorage, MessageStorage};
+use moonbase::messages::MessageRequest;
+use moonbase::services::IdService;
+use moonbase::services::id_service::IdKind;
+use std::collections::HashSet;
+use tempfile::TempDir;
+
+#[test]
+fn test_counters_persist_and_are_independent() {
+    let temp_dir = TempDir::new().unwrap();
+
+    {
+        let ids = IdService::new(temp_dir.path()).unwrap();
+        assert_eq!(ids.next_id(IdKind::Bulletin).unwrap(), 1);
+        assert_eq!(ids.next_id(IdKind::Bulletin).unwrap(), 2);
+        assert_eq!(ids.next_id(IdKind::Message).unwrap(), 1);
+    }
+
+    let ids = IdService::new(temp_dir.path()).unwrap();
+    assert_eq!(ids.last_id(IdKind::Bulletin).unwrap(), 2);
+    assert_eq!(ids.next_id(IdKind::Bulletin).unwrap(), 3);
+    assert_eq!(ids.next_id(IdKind::Message).unwrap(), 2);
+
+    // Reserving never moves a counter backwards
+    ids.reserve_through(IdKind::Message, 1).unwrap();
+    ids.reserve_through(IdKind::Bulletin, 10).unwrap();
+    assert_eq!(ids.next_id(IdKind::Message).unwrap(), 3);
+    assert_eq!(ids.next_id(IdKind::Bulletin).unwrap(), 11);
+}
+
+#[test]
+fn test_concurrent_allocation_is_unique() {
+    let temp_dir = TempDir::new().unwrap();
+    let ids = IdService::new(temp_dir.path()).unwrap();
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let ids = ids.clone();
+            std::thread::spawn(move || {
+                (0..25)
+                    .map(|_| ids.next_id(IdKind::Message).unwrap())
+                    .collect::<Vec<u32>>()
+            })
+        })
+        .collect();
+
+    let all: HashSet<u32> = handles
+        .into_iter()
+        .flat_map(|h| h.join().unwrap())
+        .collect();
+    assert_eq!(all.len(), 100);
+    assert_eq!(ids.last_id(IdKind::Message).unwrap(), 100);
+}
+
+#[test]
+fn test_purged_message_ids_are_not_reused() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let ids = IdService::new(temp_dir.path()).unwrap();
+    let mut storage = JsonMessageStorage::with_ids(temp_dir.path(), ids.clone()).unwrap();
+
+    let request = |subject: &str| {
+        MessageRequest::new(
+            "bob".to_string(),
+            subject.to_string(),
+            "Content".to_string(),
+            "alice".to_string(),
+        )
+    };
+
+    let first = storage.send_message(&request("First"), &config).unwrap();
+    let second = storage.send_message(&request("Second"), &config).unwrap();
+
+    // Both sides delete the newest message, purging it from storage
+    storage.delete_message(second, "alice").unwrap();
+    storage.delete_message(second, "bob").unwrap();
+
+    // Reopen so nothing is carried over in memory
+    let mut storage = JsonMessageStorage::with_ids(temp_dir.path(), ids).unwrap();
+    let third = storage.send_message(&request("Third"), &config).unwrap();
+    assert!(third > second && second > first);
+}
+
+#[test]
+fn test_existing_data_seeds_counter() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+
+    // Bulletins written with the legacy in-file next_id
+    std::fs::write(
+        temp_dir.path().join("bulletins.json"),
+        r#"{"bulletins": {}, "next_id": 8}"#,
+    )
+    .unwrap();
+
+    let mut storage = JsonBulletinStorage::new(temp_dir.path()).unwrap();
+    let request = BulletinRequest::new(
+        "Title".to_string(),
+        "Content".to_string(),
+        "alice".to_string(),
+    );
+    assert_eq!(storage.post_bulletin(&request, &config).unwrap(), 8);
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use moonbase::bulletin_repository::{BulletinStorage, JsonBulletinStorage};
+use moonbase::bulletins::BulletinRequest;
+use moonbase::config::BbsConfig;
+use moonbase::fsck::DataChecker;
+use moonbase::journal::{COMPACT_AFTER, Journal, JournalEntry};
+use moonbase::message_repository::{JsonMessageStorage, MessageStorage};
+use moonbase::messages::MessageRequest;
+use moonbase::user_repository::{JsonUserStorage, UserStorage};
+use moonbase::users::RegistrationRequest;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+fn register(users: &mut JsonUserStorage, name: &str) {
+    let request = RegistrationRequest::new(name.to_string(), None, "password123".to_string());
+    users
+        .register_user(&request, &BbsConfig::default())
+        .unwrap();
+}
+
+fn line_count(path: &Path) -> usize {
+    fs::read_to_string(path).map_or(0, |s| s.lines().count())
+}
+
+#[test]
+fn test_changes_go_to_journal_and_survive_a_crash() {
+    let temp_dir = common::create_temp_dir();
+    let users_file = temp_dir.path().join("users.json");
+    let journal_file = temp_dir.path().join("users.journal");
+
+    let mut users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    register(&mut users, "alice");
+    register(&mut users, "bob");
+
+    // users.json is still the empty snapshot written at startup
+    assert_eq!(fs::read_to_string(&users_file).unwrap().trim(), "{}");
+    assert_eq!(line_count(&journal_file), 2);
+
+    // Skip the compaction a clean close would do
+    std::mem::forget(users);
+
+    let users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    assert_eq!(users.get_user_count().unwrap(), 2);
+    assert!(users.user_exists("bob").unwrap());
+    // Replayed changes are folded into users.json straight away
+    assert!(!journal_file.exists());
+    assert!(fs::read_to_string(&users_file).unwrap().contains("alice"));
+}
+
+#[test]
+fn test_clean_close_compacts() {
+    let temp_dir = common::create_temp_dir();
+    let mut users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    register(&mut users, "alice");
+    drop(users);
+
+    assert!(!temp_dir.path().join("users.journal").exists());
+    let content = fs::read_to_string(temp_dir.path().join("users.json")).unwrap();
+    assert!(content.contains("alice"));
+}
+
+#[test]
+fn test_deletes_are_replayed() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+
+    let mut messages = JsonMessageStorage::new(temp_dir.path()).unwrap();
+    for subject in ["One", "Two"] {
+        let request = MessageRequest::new(
+            "bob".to_string(),
+            subject.to_string(),
+            "Body".to_string(),
+            "alice".to_string(),
+        );
+        messages.send_message(&request, &config).unwrap();
+    }
+    let first = messages.get_inbox("bob").unwrap()[1].id;
+    messages.delete_message(first, "alice").unwrap();
+    messages.delete_message(first, "bob").unwrap();
+    std::mem::forget(messages);
+
+    let messages = JsonMessageStorage::new(temp_dir.path()).unwrap();
+    let inbox = messages.get_inbox("bob").unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].subject, "Two");
+}
+
+#[test]
+fn test_compacts_after_enough_changes() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let journal_file = temp_dir.path().join("bulletins.journal");
+
+    let mut bulletins = JsonBulletinStorage::new(temp_dir.path()).unwrap();
+    for i in 0..COMPACT_AFTER + 3 {
+        let request = BulletinRequest::new(
+            format!("Post {}", i),
+            "Content".to_string(),
+            "alice".to_string(),
+        );
+        bulletins.post_bulletin(&request, &config).unwrap();
+    }
+
+    // Everything up to the compaction is in bulletins.json now
+    assert_eq!(line_count(&journal_file), 3);
+    std::mem::forget(bulletins);
+
+    let bulletins = JsonBulletinStorage::new(temp_dir.path()).unwrap();
+    assert_eq!(
+        bulletins.list_bulletins(None).unwrap().len(),
+        COMPACT_AFTER + 3
+    );
+}
+
+#[test]
+fn test_torn_last_line_is_ignored() {
+    let temp_dir = common::create_temp_dir();
+    let mut users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    register(&mut users, "alice");
+    std::mem::forget(users);
+
+    let journal_file = temp_dir.path().join("users.journal");
+    let mut content = fs::read_to_string(&journal_file).unwrap();
+    content.push_str("{\"op\":\"put\",\"key\":\"bob\",\"val");
+    fs::write(&journal_file, content).unwrap();
+
+    let users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    assert_eq!(users.list_users().unwrap(), ["alice"]);
+}
+
+#[test]
+fn test_damaged_journal_is_an_error() {
+    let temp_dir = common::create_temp_dir();
+    let mut users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    register(&mut users, "alice");
+    std::mem::forget(users);
+
+    let journal_file = temp_dir.path().join("users.journal");
+    let content = fs::read_to_string(&journal_file).unwrap();
+    fs::write(&journal_file, format!("not json\n{}", content)).unwrap();
+
+    assert!(JsonUserStorage::new(temp_dir.path()).is_err());
+}
+
+#[test]
+fn test_journal_older_than_file_is_skipped() {
+    let temp_dir = common::create_temp_dir();
+    let data_file = temp_dir.path().join("users.json");
+
+    let mut journal = Journal::for_file(&data_file);
+    journal
+        .append(&JournalEntry::<&str, u32>::Delete { key: "alice" })
+        .unwrap();
+    assert_eq!(journal.pending::<String, u32>(&data_file).unwrap().len(), 1);
+
+    // A compaction that crashed before clearing the journal
+    thread::sleep(Duration::from_millis(20));
+    fs::write(&data_file, "{}").unwrap();
+    assert!(
+        journal
+            .pending::<String, u32>(&data_file)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_fsck_reads_journaled_records() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+
+    let mut users = JsonUserStorage::new(temp_dir.path()).unwrap();
+    register(&mut users, "alice");
+    register(&mut users, "bob");
+    let mut messages = JsonMessageStorage::new(temp_dir.path()).unwrap();
+    let request = MessageRequest::new(
+        "bob".to_string(),
+        "Hello".to_string(),
+        "Hi bob".to_string(),
+        "alice".to_string(),
+    );
+    messages.send_message(&request, &config).unwrap();
+    std::mem::forget(users);
+    std::mem::forget(messages);
+
+    // Both users only exist in users.journal, so the message is not dangling
+    let report = DataChecker::load(temp_dir.path()).unwrap().check().unwrap();
+    assert!(report.is_clean(), "{:?}", report.issues);
+}
@@ -1,8 +1,10 @@
 mod common;
 
-use moonbase::config::BbsConfig;
+use moonbase::config::{BbsConfig, SecurityLevel};
 use moonbase::message_repository::{JsonMessageStorage, MessageStorage};
 use moonbase::messages::{MessageRequest, PrivateMessage};
+use moonbase::services::CoreServices;
+use moonbase::users::RegistrationRequest;
 use tempfile::TempDir;
 
 fn create_test_storage() -> (JsonMessageStorage, TempDir) {
@@ -11,6 +13,40 @@ fn create_test_storage() -> (JsonMessageStorage, TempDir) {
     (storage, temp_dir)
 }
 
+/// Services over fresh storage with the given users registered
+fn create_test_services(config: &BbsConfig, users: &[&str]) -> (CoreServices, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let services = common::services(temp_dir.path(), config);
+    for name in users {
+        let request = RegistrationRequest::new(name.to_string(), None, "password123".to_string());
+        services.users.register(request, config).unwrap();
+    }
+    (services, temp_dir)
+}
+
+fn send(services: &CoreServices, config: &BbsConfig, from: &str, to: &str, subject: &str) {
+    let request = MessageRequest::new(
+        to.to_string(),
+        subject.to_string(),
+        "Test content.".to_string(),
+        from.to_string(),
+    );
+    services
+        .messages
+        .send_message(request, config, SecurityLevel::User)
+        .unwrap();
+}
+
+fn inbox_subjects(services: &CoreServices, username: &str) -> Vec<String> {
+    services
+        .messages
+        .get_inbox(username, SecurityLevel::User)
+        .unwrap()
+        .into_iter()
+        .map(|m| m.subject)
+        .collect()
+}
+
 #[test]
 fn test_message_creation() {
     let message = PrivateMessage::new(
@@ -309,7 +345,6 @@ fn test_message_stats() {
     assert_eq!(alice_stats.total_received, 3);
     assert_eq!(alice_stats.total_sent, 1);
     assert_eq!(alice_stats.unread_count, 3); // All messages unread
-    assert_eq!(alice_stats.recent_messages.len(), 3);
 
     // Mark one message as read
     storage.mark_read(1, "alice").unwrap();
@@ -346,3 +381,153 @@ fn test_message_persistence() {
         assert_eq!(message.recipient, "bob");
     }
 }
+
+#[test]
+fn test_inbox_since_last_call() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let config = BbsConfig::default();
+
+    let send = |storage: &mut JsonMessageStorage, recipient: &str, subject: &str| {
+        let request = MessageRequest::new(
+            recipient.to_string(),
+            subject.to_string(),
+            "Message body.".to_string(),
+            "alice".to_string(),
+        );
+        storage.send_message(&request, &config).unwrap();
+    };
+
+    send(&mut storage, "bob", "Old news");
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let last_call = jiff::Timestamp::now();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    send(&mut storage, "bob", "Fresh");
+    send(&mut storage, "carol", "Not for bob");
+
+    let new_mail = storage.get_inbox_since("bob", last_call).unwrap();
+    assert_eq!(new_mail.len(), 1);
+    assert_eq!(new_mail[0].subject, "Fresh");
+
+    // Older mail still counts toward the full inbox
+    assert_eq!(storage.get_inbox("bob").unwrap().len(), 2);
+}
+
+#[test]
+fn test_message_batch_operations() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let config = BbsConfig::default();
+
+    let mut ids = vec![];
+    for subject in ["One", "Two", "Three"] {
+        let request = MessageRequest::new(
+            "bob".to_string(),
+            subject.to_string(),
+            "Test content.".to_string(),
+            "alice".to_string(),
+        );
+        ids.push(storage.send_message(&request, &config).unwrap());
+    }
+    storage.mark_read(ids[0], "bob").unwrap();
+
+    // Missing IDs are skipped and the order asked for is kept
+    let fetched = storage.get_many(&[ids[2], 999, ids[0]], "bob").unwrap();
+    assert_eq!(
+        fetched
+            .iter()
+            .map(|m| m.subject.as_str())
+            .collect::<Vec<_>>(),
+        ["Three", "One"]
+    );
+    assert!(storage.get_many(&ids, "carol").unwrap().is_empty());
+
+    // Only the recipient's unread messages count
+    assert_eq!(storage.mark_read_many(&ids, "alice").unwrap(), 0);
+    assert_eq!(storage.mark_read_many(&ids, "bob").unwrap(), 2);
+    assert_eq!(storage.get_stats("bob").unread_count, 0);
+
+    assert_eq!(storage.delete_many(&ids[..2], "bob").unwrap(), 2);
+    assert_eq!(storage.delete_many(&ids[..2], "bob").unwrap(), 0);
+    assert_eq!(storage.get_inbox("bob").unwrap().len(), 1);
+    // The sender keeps their copies
+    assert_eq!(storage.get_sent("alice").unwrap().len(), 3);
+}
+
+#[test]
+fn test_messages_follow_forwarding_chain() {
+    let config = BbsConfig::default();
+    let (services, _temp_dir) = create_test_services(&config, &["alice", "bob", "carol", "dave"]);
+    services.users.set_forward_to("bob", "carol").unwrap();
+    services.users.set_forward_to("carol", "dave").unwrap();
+    // Back to someone who already has it: the chain stops here
+    services.users.set_forward_to("dave", "bob").unwrap();
+
+    send(&services, &config, "alice", "bob", "Meeting");
+
+    assert_eq!(inbox_subjects(&services, "bob"), ["Meeting"]);
+    assert_eq!(inbox_subjects(&services, "carol"), ["Fwd: Meeting"]);
+    assert_eq!(inbox_subjects(&services, "dave"), ["Fwd: Meeting"]);
+    let forwarded = &services
+        .messages
+        .get_inbox("dave", SecurityLevel::User)
+        .unwrap()[0];
+    assert_eq!(forwarded.sender, "carol");
+    assert!(
+        forwarded
+            .content
+            .starts_with("Forwarded from alice to bob:")
+    );
+}
+
+#[test]
+fn test_forwarding_settings_are_checked() {
+    let config = BbsConfig::default();
+    let (services, _temp_dir) = create_test_services(&config, &["alice"]);
+
+    assert!(services.users.set_forward_to("alice", "alice").is_err());
+    assert!(services.users.set_forward_to("alice", "nobody").is_err());
+    let user = services.users.set_forward_to("alice", "  ").unwrap();
+    assert_eq!(user.forward_to, None);
+}
+
+#[test]
+fn test_vacation_reply_is_rate_limited() {
+    let config = BbsConfig::default();
+    let (services, _temp_dir) = create_test_services(&config, &["alice", "bob", "carol"]);
+    services
+        .users
+        .set_auto_reply("bob", "Away until Monday.", &config)
+        .unwrap();
+
+    send(&services, &config, "alice", "bob", "First");
+    send(&services, &config, "alice", "bob", "Second");
+    send(&services, &config, "carol", "bob", "Hello");
+
+    // One reply per sender, however many messages they send
+    assert_eq!(inbox_subjects(&services, "alice"), ["Auto-reply: First"]);
+    assert_eq!(inbox_subjects(&services, "carol"), ["Auto-reply: Hello"]);
+    assert_eq!(inbox_subjects(&services, "bob").len(), 3);
+
+    let user = services.users.set_auto_reply("bob", "", &config).unwrap();
+    assert_eq!(user.auto_reply, None);
+}
+
+#[test]
+fn test_vacation_replies_do_not_loop() {
+    let config = BbsConfig::default();
+    let (services, _temp_dir) = create_test_services(&config, &["alice", "bob"]);
+    services
+        .users
+        .set_auto_reply("alice", "Out.", &config)
+        .unwrap();
+    services
+        .users
+        .set_auto_reply("bob", "Also out.", &config)
+        .unwrap();
+    services.users.set_forward_to("alice", "bob").unwrap();
+
+    send(&services, &config, "alice", "bob", "Ping");
+
+    // Bob's reply is neither answered by Alice nor forwarded back to Bob
+    assert_eq!(inbox_subjects(&services, "alice"), ["Auto-reply: Ping"]);
+    assert_eq!(inbox_subjects(&services, "bob"), ["Ping"]);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use moonbase::config::BbsConfig;
+use moonbase::feed::{FEED_PATH, METRICS_PATH, serve};
+use moonbase::services::{LoopTiming, MetricsService};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use telnet_negotiation::ConnectionStats;
+
+/// Start an HTTP listener on a free port, returning its address and the
+/// metrics it reports
+fn start_http(data_dir: &Path, config: BbsConfig) -> (String, MetricsService) {
+    let services = common::services(data_dir, &config);
+    let metrics = services.metrics.clone();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || serve(listener, Arc::new(config), Arc::new(services)));
+    (address, metrics)
+}
+
+fn timing(render_ms: u64, storage_ms: u64, input_ms: u64) -> LoopTiming {
+    LoopTiming {
+        render: Duration::from_millis(render_ms),
+        storage: Duration::from_millis(storage_ms),
+        input_wait: Duration::from_millis(input_ms),
+    }
+}
+
+fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_timings_aggregate_per_menu() {
+    let metrics = MetricsService::new();
+    metrics.record("main", &timing(10, 5, 1000), false);
+    metrics.record("main", &timing(300, 20, 2000), true);
+    // Clones share the totals
+    metrics.clone().record("bulletins", &timing(1, 1, 1), false);
+
+    let menus = metrics.menus();
+    assert_eq!(
+        menus
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>(),
+        ["bulletins", "main"]
+    );
+    let main = menus[1].1;
+    assert_eq!(main.iterations, 2);
+    assert_eq!(main.slow_renders, 1);
+    assert_eq!(main.render_total, Duration::from_millis(310));
+    assert_eq!(main.render_max, Duration::from_millis(300));
+    assert_eq!(main.storage_total, Duration::from_millis(25));
+    assert_eq!(main.storage_max, Duration::from_millis(20));
+    assert_eq!(main.input_wait_total, Duration::from_secs(3));
+}
+
+#[test]
+fn test_prometheus_text() {
+    let metrics = MetricsService::new();
+    assert!(!metrics.render_prometheus().contains("{menu="));
+
+    metrics.record("main", &timing(1500, 2, 0), true);
+    let text = metrics.render_prometheus();
+
+    assert!(text.contains("# TYPE moonbase_menu_iterations_total counter\n"));
+    assert!(text.contains("# TYPE moonbase_menu_render_seconds_max gauge\n"));
+    assert!(text.contains("moonbase_menu_iterations_total{menu=\"main\"} 1\n"));
+    assert!(text.contains("moonbase_menu_slow_renders_total{menu=\"main\"} 1\n"));
+    assert!(text.contains("moonbase_menu_render_seconds_total{menu=\"main\"} 1.500000\n"));
+    assert!(text.contains("moonbase_menu_storage_seconds_max{menu=\"main\"} 0.002000\n"));
+    // Every sample line has a HELP and TYPE line for its family
+    assert_eq!(text.matches("# HELP ").count(), 7);
+    assert_eq!(text.matches("{menu=\"main\"}").count(), 7);
+}
+
+#[test]
+fn test_metrics_served_only_when_enabled() {
+    let temp_dir = common::create_temp_dir();
+    let mut config = BbsConfig::default();
+    config.metrics.enabled = true;
+    let (address, metrics) = start_http(temp_dir.path(), config);
+    metrics.record("users", &timing(4, 3, 2), false);
+
+    let response = get(&address, METRICS_PATH);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response.contains("moonbase_menu_iterations_total{menu=\"users\"} 1"));
+
+    // The feed stays off unless it is enabled too
+    let feed = get(&address, FEED_PATH);
+    assert!(feed.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let temp_dir = common::create_temp_dir();
+    let mut config = BbsConfig::default();
+    config.feed.enabled = true;
+    let (address, _) = start_http(temp_dir.path(), config);
+
+    let response = get(&address, METRICS_PATH);
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[test]
+fn test_telnet_counters_add_up_across_sessions() {
+    let metrics = MetricsService::new();
+    assert!(!metrics.render_prometheus().contains("moonbase_telnet_"));
+
+    let added = ConnectionStats {
+        bytes_received: 40,
+        negotiations_received: 3,
+        largest_subnegotiation: 6,
+        ..ConnectionStats::default()
+    };
+    metrics.record_telnet(&added);
+    metrics.clone().record_telnet(&ConnectionStats {
+        largest_subnegotiation: 2,
+        ..added
+    });
+
+    let text = metrics.render_prometheus();
+    assert!(text.contains("# TYPE moonbase_telnet_bytes_received_total counter\n"));
+    assert!(text.contains("moonbase_telnet_bytes_received_total 80\n"));
+    assert!(text.contains("moonbase_telnet_negotiations_received_total 6\n"));
+    assert!(text.contains("moonbase_telnet_subnegotiation_bytes_max 6\n"));
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use moonbase::motd::{AddressRange, ConnectionNotice, notices_for};
+use std::net::IpAddr;
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+fn range(range: &str) -> AddressRange {
+    range.parse().unwrap()
+}
+
+#[test]
+fn test_cidr_ranges() {
+    let lan = range("192.168.0.0/16");
+    assert!(lan.contains(ip("192.168.1.20")));
+    assert!(lan.contains(ip("192.168.255.255")));
+    assert!(!lan.contains(ip("192.169.0.1")));
+    assert!(!lan.contains(ip("10.0.0.1")));
+
+    let single = range("203.0.113.7");
+    assert!(single.contains(ip("203.0.113.7")));
+    assert!(!single.contains(ip("203.0.113.8")));
+
+    assert!(range("0.0.0.0/0").contains(ip("8.8.8.8")));
+    assert!(range("*").contains(ip("2001:db8::1")));
+}
+
+#[test]
+fn test_ipv6_ranges() {
+    let site = range("2001:db8::/32");
+    assert!(site.contains(ip("2001:db8:1234::1")));
+    assert!(!site.contains(ip("2001:db9::1")));
+    // IPv4 blocks don't match IPv6 callers, and the other way round
+    assert!(!site.contains(ip("10.0.0.1")));
+    assert!(!range("10.0.0.0/8").contains(ip("2001:db8::1")));
+}
+
+#[test]
+fn test_ipv4_callers_on_a_dual_stack_listener() {
+    // A listener bound to :: sees IPv4 callers as ::ffff:a.b.c.d
+    assert!(range("10.0.0.0/8").contains(ip("::ffff:10.1.2.3")));
+}
+
+#[test]
+fn test_hostname_ranges() {
+    let local = range("LocalHost");
+    assert_eq!(local, AddressRange::Host("localhost".to_string()));
+    assert!(local.contains(ip("127.0.0.1")));
+    assert!(!local.contains(ip("192.0.2.1")));
+}
+
+#[test]
+fn test_notices_picked_by_address() {
+    let notices: Vec<ConnectionNotice> = [
+        "!192.168.0.0/16|Outside the LAN",
+        "10.20.0.0/16|Hello, Partner BBS",
+        "*|Maintenance Sunday",
+    ]
+    .iter()
+    .map(|notice| notice.parse().unwrap())
+    .collect();
+
+    assert_eq!(
+        notices_for(ip("192.168.1.5"), &notices),
+        vec!["Maintenance Sunday"]
+    );
+    assert_eq!(
+        notices_for(ip("10.20.3.4"), &notices),
+        vec![
+            "Outside the LAN",
+            "Hello, Partner BBS",
+            "Maintenance Sunday"
+        ]
+    );
+    assert!(notices_for(ip("192.168.1.5"), &notices[..2]).is_empty());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use moonbase::config::{BbsConfig, FeatureAccess, SecurityLevel};
+use moonbase::errors::BbsError;
+use moonbase::services::{Access, Feature, PermissionService};
+
+fn service(bulletins: FeatureAccess) -> PermissionService {
+    let mut features = BbsConfig::default().features;
+    features.bulletins = bulletins;
+    PermissionService::new(features)
+}
+
+#[test]
+fn test_guests_read_users_post() {
+    let permissions = service(FeatureAccess::Gated {
+        read: SecurityLevel::Guest,
+        write: SecurityLevel::User,
+    });
+
+    assert_eq!(
+        permissions.access(Feature::Bulletins, SecurityLevel::Guest),
+        Access::Read
+    );
+    assert_eq!(
+        permissions.access(Feature::Bulletins, SecurityLevel::User),
+        Access::Full
+    );
+    assert_eq!(
+        permissions.access(Feature::Bulletins, SecurityLevel::Sysop),
+        Access::Full
+    );
+
+    assert!(
+        permissions
+            .require_read(Feature::Bulletins, SecurityLevel::Guest)
+            .is_ok()
+    );
+    assert!(matches!(
+        permissions.require_write(Feature::Bulletins, SecurityLevel::Guest),
+        Err(BbsError::PermissionDenied(_))
+    ));
+}
+
+#[test]
+fn test_off_and_read_only() {
+    let permissions = service(FeatureAccess::Off);
+    assert_eq!(
+        permissions.access(Feature::Bulletins, SecurityLevel::Sysop),
+        Access::None
+    );
+    assert!(matches!(
+        permissions.require_read(Feature::Bulletins, SecurityLevel::Sysop),
+        Err(BbsError::FeatureDisabled(_))
+    ));
+
+    let permissions = service(FeatureAccess::ReadOnly);
+    assert_eq!(
+        permissions.access(Feature::Bulletins, SecurityLevel::Sysop),
+        Access::Read
+    );
+    assert!(
+        permissions
+            .require_write(Feature::Bulletins, SecurityLevel::Sysop)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_level_gated_hides_feature_below_level() {
+    let permissions = service(FeatureAccess::level(SecurityLevel::User));
+
+    assert_eq!(
+        permissions.access(Feature::Bulletins, SecurityLevel::Guest),
+        Access::None
+    );
+    assert!(permissions.is_enabled(Feature::Bulletins));
+    assert!(matches!(
+        permissions.require_read(Feature::Bulletins, SecurityLevel::Guest),
+        Err(BbsError::PermissionDenied(_))
+    ));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use moonbase::bulletin_repository::{BulletinStorage, JsonBulletinStorage};
+use moonbase::bulletins::BulletinRequest;
+use moonbase::config::BbsConfig;
+use moonbase::errors::BbsError;
+use moonbase::messages::MessageRequest;
+use moonbase::post_check::{PostField, PostIssue, PostProblem, PostSummary, mark_filtered};
+use tempfile::TempDir;
+
+fn filtering(words: &[&str]) -> BbsConfig {
+    let mut config = BbsConfig::default();
+    config.features.filtered_words = words.iter().map(|w| w.to_string()).collect();
+    config
+}
+
+#[test]
+fn test_summary_counts_wrapped_lines_and_screens() {
+    let summary = PostSummary::of("Hello there world\nsecond line");
+    assert_eq!(summary.lines, 2);
+    assert_eq!(summary.words, 5);
+    assert_eq!(summary.characters, 29);
+    assert_eq!(summary.screens, 1);
+
+    // 200 columns wrap to three lines; 30 of them need two screens
+    let long = vec!["word ".repeat(40); 10].join("\n");
+    let summary = PostSummary::of(&long);
+    assert_eq!(summary.lines, 30);
+    assert_eq!(summary.screens, 2);
+
+    assert_eq!(
+        PostSummary::of("one").to_string(),
+        "1 line, 1 word, 3 characters - about 1 screen at 80x24"
+    );
+}
+
+#[test]
+fn test_bulletin_issues_name_each_field() {
+    let config = filtering(&["spam"]);
+
+    let request = BulletinRequest::new(
+        "Buy SPAM now".to_string(),
+        "x".repeat(config.features.max_message_length + 1),
+        "".to_string(),
+    );
+    let issues = request.issues(&config);
+    assert_eq!(
+        issues,
+        vec![
+            PostIssue::new(
+                PostField::Title,
+                PostProblem::FilteredWord("SPAM".to_string())
+            ),
+            PostIssue::new(
+                PostField::Content,
+                PostProblem::TooLong {
+                    max: config.features.max_message_length
+                }
+            ),
+            PostIssue::new(PostField::Author, PostProblem::Empty),
+        ]
+    );
+
+    let fine = BulletinRequest::new(
+        "Spammy classics".to_string(),
+        "Nothing to see".to_string(),
+        "alice".to_string(),
+    );
+    assert!(fine.issues(&config).is_empty());
+}
+
+#[test]
+fn test_message_issues() {
+    let config = filtering(&["darn"]);
+
+    let request = MessageRequest::new(
+        "alice".to_string(),
+        " ".to_string(),
+        "Darn it, darn it all".to_string(),
+        "alice".to_string(),
+    );
+    let issues = request.issues(&config);
+    assert_eq!(
+        issues,
+        vec![
+            PostIssue::new(PostField::Subject, PostProblem::Empty),
+            PostIssue::new(
+                PostField::Content,
+                PostProblem::FilteredWord("Darn".to_string())
+            ),
+            PostIssue::new(PostField::Recipient, PostProblem::ToSelf),
+        ]
+    );
+    assert_eq!(issues[0].to_string(), "Subject cannot be empty");
+    assert_eq!(
+        issues[1].to_string(),
+        "Content contains a filtered word: Darn"
+    );
+}
+
+#[test]
+fn test_mark_filtered_matches_whole_words_only() {
+    let words = vec!["ass".to_string()];
+    assert_eq!(
+        mark_filtered("A classic ASS, really.", &words),
+        "A classic >>ASS<<, really."
+    );
+    assert_eq!(mark_filtered("nothing here", &words), "nothing here");
+}
+
+#[test]
+fn test_storage_refuses_invalid_posts_with_issues() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut storage = JsonBulletinStorage::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let config = filtering(&["spam"]);
+
+    let request = BulletinRequest::new(
+        "".to_string(),
+        "spam spam spam".to_string(),
+        "alice".to_string(),
+    );
+    match storage.post_bulletin(&request, &config) {
+        Err(BbsError::InvalidPost(issues)) => {
+            assert_eq!(issues.len(), 2);
+            assert_eq!(issues[0].field, PostField::Title);
+            assert_eq!(issues[1].field, PostField::Content);
+        }
+        other => panic!("expected InvalidPost, got {:?}", other),
+    }
+    assert_eq!(storage.get_stats(None).total_bulletins, 0);
+}
+
+#[test]
+fn test_filtered_words_in_config_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("bbs.conf");
+    std::fs::write(&path, "[features]\nfiltered_words = \"spam, Darn\"\n").unwrap();
+
+    let config = BbsConfig::load_from_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(config.features.filtered_words, vec!["spam", "Darn"]);
+    assert!(BbsConfig::default().features.filtered_words.is_empty());
+}
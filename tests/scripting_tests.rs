@@ -0,0 +1,208 @@
+#![cfg(feature = "scripting")]
+
+mod common;
+
+use moonbase::config::{BbsConfig, SecurityLevel};
+use moonbase::scripting::{SCRIPT_SENDER, ScriptCaller, ScriptEvent, ScriptHost};
+use std::path::Path;
+use tempfile::TempDir;
+
+const RESERVED: &[&str] = &["1", "q"];
+
+fn write_script(dir: &Path, name: &str, source: &str) {
+    std::fs::write(dir.join(name), source).unwrap();
+}
+
+fn caller(username: &str) -> ScriptCaller<'_> {
+    ScriptCaller {
+        username: Some(username),
+        level: SecurityLevel::User,
+    }
+}
+
+#[test]
+fn test_missing_directory_loads_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let host = ScriptHost::load(temp_dir.path().join("scripts"), RESERVED).unwrap();
+    assert!(host.is_empty());
+}
+
+#[test]
+fn test_login_hook_output_and_conditions() {
+    let scripts = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    write_script(
+        scripts.path(),
+        "on_login.rhai",
+        "// greet callers\nsay(`Welcome back, ${user}!`);\nif unread > 0 { say(\"You have mail.\"); }\nif level == \"user\" { return; }\nsay(\"never shown\");\n",
+    );
+
+    let host = ScriptHost::load(scripts.path(), RESERVED).unwrap();
+    let lines = host.run_hook(
+        &ScriptEvent::Login,
+        caller("alice"),
+        &common::services(data.path(), &config),
+        &config,
+    );
+
+    assert_eq!(lines, vec!["Welcome back, alice!"]);
+    // No hook for this event
+    assert!(
+        host.run_hook(
+            &ScriptEvent::NewUser,
+            caller("alice"),
+            &common::services(data.path(), &config),
+            &config
+        )
+        .is_empty()
+    );
+}
+
+#[test]
+fn test_post_hook_notifies_sysop() {
+    let scripts = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    write_script(
+        scripts.path(),
+        "on_post.rhai",
+        "notify(sysop, \"New bulletin\", `${user} posted #${bulletin_id}: ${title}`);\n",
+    );
+
+    let host = ScriptHost::load(scripts.path(), RESERVED).unwrap();
+    let services = common::services(data.path(), &config);
+    host.run_hook(
+        &ScriptEvent::Post {
+            bulletin_id: 7,
+            title: "Hello".to_string(),
+        },
+        caller("alice"),
+        &services,
+        &config,
+    );
+
+    let inbox = services
+        .messages
+        .get_inbox(&config.bbs.sysop_name, SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].sender, SCRIPT_SENDER);
+    assert_eq!(inbox[0].subject, "New bulletin");
+    assert_eq!(inbox[0].content, "alice posted #7: Hello");
+}
+
+#[test]
+fn test_custom_menu_command() {
+    let scripts = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    write_script(
+        scripts.path(),
+        "cmd_rules.rhai",
+        "//: House rules\nsay(`Be excellent to each other on ${bbs_name}.`);\n",
+    );
+
+    let host = ScriptHost::load(scripts.path(), RESERVED).unwrap();
+    let commands: Vec<_> = host.commands().collect();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].key, "rules");
+    assert_eq!(commands[0].description, "House rules");
+
+    let services = common::services(data.path(), &config);
+    let lines = host
+        .run_command("rules", caller("alice"), &services, &config)
+        .unwrap();
+    assert_eq!(lines, vec!["Be excellent to each other on Rust BBS."]);
+    assert!(
+        host.run_command("nope", caller("alice"), &services, &config)
+            .is_none()
+    );
+}
+
+#[test]
+fn test_invalid_scripts_rejected_at_load() {
+    for (name, source) in [
+        ("on_login.rhai", "exec rm -rf /\n"),
+        ("on_login.rhai", "say(password);\n"),
+        ("on_login.rhai", "if unread ~ 3 { return; }\n"),
+        ("on_login.rhai", "eval(\"say(1)\");\n"),
+        ("cmd_q.rhai", "say(\"shadowing quit\");\n"),
+        ("on_logout.rhai", "say(\"unknown hook\");\n"),
+    ] {
+        let scripts = TempDir::new().unwrap();
+        write_script(scripts.path(), name, source);
+        assert!(
+            ScriptHost::load(scripts.path(), RESERVED).is_err(),
+            "{} should be rejected: {:?}",
+            name,
+            source
+        );
+    }
+}
+
+#[test]
+fn test_output_is_capped() {
+    let scripts = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    write_script(
+        scripts.path(),
+        "on_login.rhai",
+        "for i in 0..500 { say(\"spam\"); }\n",
+    );
+
+    let host = ScriptHost::load(scripts.path(), RESERVED).unwrap();
+    let lines = host.run_hook(
+        &ScriptEvent::Login,
+        caller("alice"),
+        &common::services(data.path(), &config),
+        &config,
+    );
+    assert_eq!(lines.len(), 50);
+}
+
+#[test]
+fn test_runaway_scripts_are_stopped() {
+    let data = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let services = common::services(data.path(), &config);
+
+    for source in [
+        "say(\"before\");\nloop { }\n",
+        "say(\"before\");\nfn deeper(n) { deeper(n + 1) }\ndeeper(0);\n",
+        "say(\"before\");\nlet s = \"x\";\nloop { s += s; }\n",
+        "say(\"before\");\nimport \"secrets\" as secrets;\nsay(\"after\");\n",
+        "say(\"before\");\nopen_file(\"/etc/passwd\");\n",
+    ] {
+        let scripts = TempDir::new().unwrap();
+        write_script(scripts.path(), "on_login.rhai", source);
+        let host = ScriptHost::load(scripts.path(), RESERVED).unwrap();
+
+        // The run ends with the output it made before being stopped
+        let lines = host.run_hook(&ScriptEvent::Login, caller("alice"), &services, &config);
+        assert_eq!(lines, vec!["before"], "{:?}", source);
+    }
+}
+
+#[test]
+fn test_notifications_are_capped() {
+    let scripts = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    write_script(
+        scripts.path(),
+        "on_new_user.rhai",
+        "for i in 0..10 { notify(sysop, `Hello ${i}`, \"spam\"); }\n",
+    );
+
+    let host = ScriptHost::load(scripts.path(), RESERVED).unwrap();
+    let services = common::services(data.path(), &config);
+    host.run_hook(&ScriptEvent::NewUser, caller("alice"), &services, &config);
+
+    let inbox = services
+        .messages
+        .get_inbox(&config.bbs.sysop_name, SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(inbox.len(), 3);
+}
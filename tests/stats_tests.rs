@@ -0,0 +1,183 @@
+mod common;
+
+use jiff::{SignedDuration, Timestamp};
+use moonbase::bulletin_repository::{BulletinStorage, JsonBulletinStorage};
+use moonbase::bulletins::{Bulletin, BulletinRequest};
+use moonbase::club_repository::JsonClubStorage;
+use moonbase::clubs::ClubRequest;
+use moonbase::config::{BbsConfig, SecurityLevel};
+use moonbase::errors::BbsError;
+use moonbase::menu::menu_stats::bar_chart;
+use moonbase::message_repository::JsonMessageStorage;
+use moonbase::services::stats_service::StatsService;
+use moonbase::services::{BoardStats, CoreServices};
+use moonbase::text_width::display_width;
+use moonbase::user_repository::{JsonUserStorage, UserStorage};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Storage = (
+    Arc<Mutex<dyn UserStorage + Send>>,
+    Arc<Mutex<dyn BulletinStorage + Send>>,
+);
+
+fn setup(data_dir: &Path, config: &BbsConfig) -> (CoreServices, Storage) {
+    let users: Arc<Mutex<dyn UserStorage + Send>> =
+        Arc::new(Mutex::new(JsonUserStorage::new(data_dir).unwrap()));
+    let bulletins: Arc<Mutex<dyn BulletinStorage + Send>> =
+        Arc::new(Mutex::new(JsonBulletinStorage::new(data_dir).unwrap()));
+    let services = CoreServices::new(
+        &config.features,
+        Arc::clone(&users),
+        Arc::clone(&bulletins),
+        Arc::new(Mutex::new(JsonMessageStorage::new(data_dir).unwrap())),
+        Arc::new(Mutex::new(JsonClubStorage::new(data_dir).unwrap())),
+    );
+    (services, (users, bulletins))
+}
+
+fn post(services: &CoreServices, config: &BbsConfig, area: Option<&str>) {
+    let request = BulletinRequest::new("Title".to_string(), "Body".to_string(), "sysop".into())
+        .with_area(area.map(str::to_string));
+    services
+        .bulletins
+        .post_bulletin(request, config, SecurityLevel::Sysop)
+        .unwrap();
+}
+
+fn bulletin(id: u32, area: Option<&str>, posted_at: Timestamp) -> Bulletin {
+    let mut bulletin = Bulletin::new(id, "Title".into(), "Body".into(), "alice".into());
+    bulletin.area = area.map(str::to_string);
+    bulletin.posted_at = posted_at;
+    bulletin
+}
+
+#[test]
+fn test_weekly_hourly_and_area_counts() {
+    // A Wednesday, 15:30 UTC
+    let now: Timestamp = "2026-03-18T15:30:00Z".parse().unwrap();
+    let hours = |h: i64| now - SignedDuration::from_hours(h);
+    let bulletins = vec![
+        bulletin(1, None, hours(1)),
+        bulletin(2, None, hours(25)),
+        bulletin(3, Some("Hams"), hours(24 * 7 + 1)),
+        bulletin(4, Some("hams"), hours(24 * 7 + 2)),
+        bulletin(5, Some("Hams"), hours(24 * 7 * 20)),
+        bulletin(6, Some("Poets"), hours(24 * 14)),
+    ];
+
+    let stats = BoardStats::from_bulletins(&bulletins, 3, now);
+    assert_eq!(stats.users, 3);
+    assert_eq!(stats.board.bulletins, 6);
+    assert_eq!(stats.board.per_week[..4], [2, 2, 1, 0]);
+    // Too old for the trend, but still counted
+    assert_eq!(stats.board.per_week.iter().sum::<usize>(), 5);
+
+    assert_eq!(stats.board.by_hour[14], 3);
+    assert_eq!(stats.board.by_hour[15], 2);
+    assert_eq!(stats.board.by_hour[13], 1);
+    assert_eq!(stats.board.busiest_hours(), [(14, 3), (15, 2), (13, 1)]);
+
+    // Areas match without regard to case and come busiest first
+    let names: Vec<&str> = stats.areas.iter().map(|a| a.name()).collect();
+    assert_eq!(names, ["Hams", "Main Board", "Poets"]);
+    assert_eq!(stats.areas[0].activity.bulletins, 3);
+    assert_eq!(stats.areas[1].activity.per_week[..2], [2, 0]);
+
+    // The main board is listed even when empty
+    let empty = BoardStats::from_bulletins(&[], 0, now);
+    assert_eq!(empty.areas.len(), 1);
+    assert_eq!(empty.areas[0].area, None);
+    assert!(empty.board.busiest_hours().is_empty());
+}
+
+#[test]
+fn test_figures_are_cached_until_max_age() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let (services, (users, bulletins)) = setup(temp_dir.path(), &config);
+
+    post(&services, &config, None);
+    assert_eq!(services.stats.board_stats().unwrap().board.bulletins, 1);
+
+    // Served from the cache
+    post(&services, &config, None);
+    assert_eq!(services.stats.board_stats().unwrap().board.bulletins, 1);
+
+    // Clones share the cache; a fresh service works the figures out again
+    let clone = services.stats.clone();
+    assert_eq!(clone.board_stats().unwrap().board.bulletins, 1);
+    let fresh = StatsService::new(
+        bulletins,
+        users,
+        Arc::clone(&services.permissions),
+        services.clubs.clone(),
+    )
+    .with_max_age(Duration::ZERO);
+    assert_eq!(fresh.board_stats().unwrap().board.bulletins, 2);
+    post(&services, &config, None);
+    assert_eq!(fresh.board_stats().unwrap().board.bulletins, 3);
+}
+
+#[test]
+fn test_stats_hide_clubs_from_outsiders() {
+    let temp_dir = common::create_temp_dir();
+    let config = BbsConfig::default();
+    let (services, _) = setup(temp_dir.path(), &config);
+
+    let request = ClubRequest::new("Hams".to_string(), "Radio".to_string(), None);
+    services
+        .clubs
+        .create_club(request, "sysop", SecurityLevel::Sysop)
+        .unwrap();
+    post(&services, &config, None);
+    post(&services, &config, Some("Hams"));
+
+    let outsider = services
+        .stats
+        .stats_for(Some("alice"), SecurityLevel::User)
+        .unwrap();
+    let names: Vec<&str> = outsider.areas.iter().map(|a| a.name()).collect();
+    assert_eq!(names, ["Main Board"]);
+    // Totals still cover the whole board
+    assert_eq!(outsider.board.bulletins, 2);
+
+    let member = services
+        .stats
+        .stats_for(Some("sysop"), SecurityLevel::Sysop)
+        .unwrap();
+    assert_eq!(member.areas.len(), 2);
+
+    let mut config = BbsConfig::default();
+    config.features.bulletins = moonbase::config::FeatureAccess::Off;
+    let temp_dir = common::create_temp_dir();
+    let (closed, _) = setup(temp_dir.path(), &config);
+    let result = closed.stats.stats_for(None, SecurityLevel::User);
+    assert!(matches!(result, Err(BbsError::FeatureDisabled(_))));
+}
+
+#[test]
+fn test_bar_chart_fits_the_width() {
+    let rows = vec![
+        ("this wk".to_string(), 40),
+        ("1 wk ago".to_string(), 10),
+        ("2 wk ago".to_string(), 1),
+        ("3 wk ago".to_string(), 0),
+    ];
+    let lines = bar_chart(&rows, 40);
+    assert_eq!(lines.len(), 4);
+    for line in &lines {
+        assert_eq!(display_width(line), 40, "{:?}", line);
+    }
+    assert!(lines[0].starts_with("this wk  ####"));
+    assert!(lines[0].ends_with("# 40"));
+    // Small counts still show, none shows nothing
+    assert_eq!(lines[2].matches('#').count(), 1);
+    assert_eq!(lines[3].matches('#').count(), 0);
+
+    // Too narrow for bars
+    let lines = bar_chart(&rows, 6);
+    assert!(lines.iter().all(|line| !line.contains('#')));
+    assert!(bar_chart(&[], 40).is_empty());
+}
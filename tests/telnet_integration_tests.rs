@@ -52,16 +52,16 @@ fn test_width_mode_enum() {
 #[test]
 fn test_clean_width_configuration() {
     let config = BbsConfig::default();
-    
+
     // Test clean width configuration
     assert!(matches!(config.ui.width_mode, WidthMode::Auto));
     assert_eq!(config.ui.width_value, 80);
-    
+
     // Verify no redundant fields
     // This test ensures we eliminated menu_width and fallback_width
     let config_content = format!("{:?}", config);
     assert!(!config_content.contains("menu_width"));
-    assert!(!config_content.contains("fallback_width")); 
+    assert!(!config_content.contains("fallback_width"));
     assert!(config_content.contains("width_mode"));
     assert!(config_content.contains("width_value"));
 }
@@ -0,0 +1,88 @@
+mod common;
+
+use moonbase::text_width::{
+    char_width, display_width, ellipsize, fit_to_width, pad_to_width, truncate_to_width,
+    wrap_to_width,
+};
+
+#[test]
+fn test_char_widths() {
+    assert_eq!(char_width('a'), 1);
+    assert_eq!(char_width('é'), 1);
+    assert_eq!(char_width('ユ'), 2);
+    assert_eq!(char_width('한'), 2);
+    assert_eq!(char_width('Ａ'), 2);
+    assert_eq!(char_width('🚀'), 2);
+    // Combining acute accent and zero-width joiner
+    assert_eq!(char_width('\u{0301}'), 0);
+    assert_eq!(char_width('\u{200D}'), 0);
+    assert_eq!(char_width('\u{FE0F}'), 0);
+    // Marks outside Latin: Devanagari vowel sign U, Hebrew qamats,
+    // Arabic fatha
+    assert_eq!(char_width('\u{0941}'), 0);
+    assert_eq!(char_width('\u{05B8}'), 0);
+    assert_eq!(char_width('\u{064E}'), 0);
+    assert_eq!(display_width("कु"), 1);
+    // Control characters don't advance the cursor
+    assert_eq!(char_width('\u{1B}'), 0);
+}
+
+#[test]
+fn test_display_width_of_mixed_text() {
+    assert_eq!(display_width("moonbase"), 8);
+    assert_eq!(display_width("ユーザー"), 8);
+    assert_eq!(display_width("hi 👋"), 5);
+    assert_eq!(display_width("e\u{0301}"), 1);
+}
+
+#[test]
+fn test_truncate_never_splits_wide_characters() {
+    assert_eq!(truncate_to_width("ユーザー", 4), "ユー");
+    // Three columns only fits one double-width character
+    assert_eq!(truncate_to_width("ユーザー", 3), "ユ");
+    assert_eq!(truncate_to_width("abc", 10), "abc");
+}
+
+#[test]
+fn test_padding_and_fitting() {
+    assert_eq!(pad_to_width("ユ", 4), "ユ  ");
+    assert_eq!(ellipsize("ユーザー名です", 9), "ユーザ...");
+    assert_eq!(ellipsize("short", 9), "short");
+
+    let fitted = fit_to_width("ユーザー名です", 10);
+    assert_eq!(display_width(&fitted), 10);
+    assert_eq!(fitted, "ユーザ... ");
+}
+
+#[test]
+fn test_wrapping_keeps_indent() {
+    assert_eq!(
+        wrap_to_width("  L   List all bulletins by number", 16),
+        vec!["  L List all", "  bulletins by", "  number"]
+    );
+    assert_eq!(
+        wrap_to_width("ユーザー ユーザー", 9),
+        vec!["ユーザー", "ユーザー"]
+    );
+    // Words longer than a line are broken across lines rather than cut
+    assert_eq!(wrap_to_width("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    assert_eq!(
+        wrap_to_width("see http://example.com/x ok", 8),
+        vec!["see", "http://e", "xample.c", "om/x ok"]
+    );
+    assert_eq!(wrap_to_width("ユーザー", 3), vec!["ユ", "ー", "ザ", "ー"]);
+    assert_eq!(wrap_to_width("  L   List", 16), vec!["  L   List"]);
+    assert_eq!(wrap_to_width("", 10), vec![""]);
+}
+
+#[test]
+fn test_wrap_keeps_long_words_intact() {
+    let word: String = ('a'..='z').cycle().take(200).collect();
+    let text = format!("  see {} here", word);
+    let lines = wrap_to_width(&text, 30);
+
+    assert!(lines.iter().all(|line| display_width(line) <= 30));
+    assert!(lines.iter().all(|line| line.starts_with("  ")));
+    let joined: String = lines.iter().map(|line| line.trim_start()).collect();
+    assert_eq!(joined, format!("see{} here", word));
+}
@@ -1,8 +1,10 @@
 mod common;
 
 use moonbase::config::BbsConfig;
+use moonbase::services::UserService;
 use moonbase::user_repository::{JsonUserStorage, UserStorage};
 use moonbase::users::{RegistrationRequest, User};
+use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 
 fn create_test_storage() -> (JsonUserStorage, TempDir) {
@@ -133,3 +135,242 @@ fn test_user_persistence() {
         assert!(storage.user_exists("testuser").unwrap());
     }
 }
+
+#[test]
+fn test_recent_logins() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let config = BbsConfig::default();
+
+    for name in ["alice", "bob", "carol"] {
+        let request = RegistrationRequest::new(name.to_string(), None, "password123".to_string());
+        storage.register_user(&request, &config).unwrap();
+    }
+
+    // Users who never logged in are not callers
+    assert!(storage.get_recent_logins(10).unwrap().is_empty());
+
+    storage.authenticate_user("alice", "password123").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    storage.authenticate_user("bob", "password123").unwrap();
+
+    let recent = storage.get_recent_logins(10).unwrap();
+    let names: Vec<&str> = recent.iter().map(|u| u.username.as_str()).collect();
+    assert_eq!(names, vec!["bob", "alice"]);
+
+    assert_eq!(storage.get_recent_logins(1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_bulletin_reads_persist_with_user() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+
+    {
+        let mut storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+        let request =
+            RegistrationRequest::new("reader".to_string(), None, "password123".to_string());
+        storage.register_user(&request, &config).unwrap();
+    }
+
+    {
+        let storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+        let service = UserService::new(Arc::new(Mutex::new(storage)));
+        let user = service.mark_bulletin_read("reader", 2).unwrap();
+        assert!(user.bulletin_reads.is_read(2));
+        let user = service.mark_bulletin_read("reader", 1).unwrap();
+        assert_eq!(user.bulletin_reads.high_water, 2);
+        assert!(service.mark_bulletin_read("nobody", 1).is_err());
+    }
+
+    let storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+    let user = storage.load_user("reader").unwrap().unwrap();
+    assert_eq!(user.bulletin_reads.high_water, 2);
+    assert!(!user.bulletin_reads.is_read(3));
+}
+
+#[test]
+fn test_mark_many_bulletins_read() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let request = RegistrationRequest::new("reader".to_string(), None, "password123".to_string());
+    storage
+        .register_user(&request, &BbsConfig::default())
+        .unwrap();
+    let service = UserService::new(Arc::new(Mutex::new(storage)));
+
+    let user = service
+        .mark_bulletins_read("reader", &[3, 1, 2, 7])
+        .unwrap();
+    assert_eq!(user.bulletin_reads.high_water, 3);
+    assert!(user.bulletin_reads.is_read(7));
+    assert!(!user.bulletin_reads.is_read(5));
+    assert!(service.mark_bulletins_read("nobody", &[1]).is_err());
+}
+
+#[test]
+fn test_screen_reader_preference_persists() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+
+    {
+        let mut storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+        let request =
+            RegistrationRequest::new("listener".to_string(), None, "password123".to_string());
+        let user = storage.register_user(&request, &config).unwrap();
+        // New accounts follow whatever the client reports
+        assert_eq!(user.screen_reader, None);
+    }
+
+    {
+        let storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+        let service = UserService::new(Arc::new(Mutex::new(storage)));
+        let user = service.set_screen_reader("listener", Some(true)).unwrap();
+        assert_eq!(user.screen_reader, Some(true));
+        assert!(service.set_screen_reader("nobody", Some(true)).is_err());
+    }
+
+    let storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+    let user = storage.load_user("listener").unwrap().unwrap();
+    assert_eq!(user.screen_reader, Some(true));
+}
+
+#[test]
+fn test_previous_login_tracks_last_call() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let config = BbsConfig::default();
+
+    let request = RegistrationRequest::new("caller".to_string(), None, "password123".to_string());
+    let registered = storage.register_user(&request, &config).unwrap();
+    assert!(registered.previous_login.is_none());
+
+    // The first login compares against the moment the account was created
+    let first = storage
+        .authenticate_user("caller", "password123")
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.previous_login, Some(registered.last_login));
+
+    let second = storage
+        .authenticate_user("caller", "password123")
+        .unwrap()
+        .unwrap();
+    assert_eq!(second.previous_login, Some(first.last_login));
+    assert!(second.previous_login_display().is_some());
+}
+
+#[test]
+fn test_reserved_usernames_rejected() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let config = BbsConfig::default();
+
+    for name in ["admin", "ALL", "Sysop_"] {
+        let request = RegistrationRequest::new(name.to_string(), None, "password123".to_string());
+        let result = storage.register_user(&request, &config);
+        if name == "Sysop_" {
+            assert!(result.is_ok());
+        } else {
+            assert!(result.is_err(), "{} should be reserved", name);
+        }
+    }
+
+    // The configured SysOp name stays available to the SysOp
+    let request = RegistrationRequest::new("SysOp".to_string(), None, "password123".to_string());
+    assert!(storage.register_user(&request, &config).is_ok());
+}
+
+#[test]
+fn test_username_character_rules() {
+    let (mut storage, _temp_dir) = create_test_storage();
+    let mut config = BbsConfig::default();
+
+    let request = RegistrationRequest::new("José".to_string(), None, "password123".to_string());
+    assert!(request.validate(&config).is_ok());
+
+    config.features.usernames.allow_unicode = false;
+    assert!(request.validate(&config).is_err());
+
+    config.features.usernames.symbols = "_-".to_string();
+    let request = RegistrationRequest::new("jo-se".to_string(), None, "password123".to_string());
+    assert!(storage.register_user(&request, &config).is_ok());
+}
+
+#[test]
+fn test_case_insensitive_usernames() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = BbsConfig::default();
+    config.features.usernames.case_insensitive = true;
+
+    let storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+    let service = UserService::new(Arc::new(Mutex::new(storage)))
+        .with_rules(config.features.usernames.clone());
+
+    let request = RegistrationRequest::new("Alice".to_string(), None, "password123".to_string());
+    service.register(request, &config).unwrap();
+
+    let request = RegistrationRequest::new("alice".to_string(), None, "password123".to_string());
+    assert!(service.register(request, &config).is_err());
+
+    let user = service
+        .authenticate("ALICE", "password123")
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.username, "Alice");
+}
+
+#[test]
+fn test_display_names_resist_impersonation() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = BbsConfig::default();
+    let storage = JsonUserStorage::new(temp_dir.path()).unwrap();
+    let service = UserService::new(Arc::new(Mutex::new(storage)));
+
+    let request = RegistrationRequest::new("alice".to_string(), None, "password123".to_string())
+        .with_display_name(Some("  Alice   Liddell ".to_string()));
+    let alice = service.register(request, &config).unwrap();
+    assert_eq!(alice.display_name.as_deref(), Some("Alice Liddell"));
+    assert_eq!(alice.shown_name(), "Alice Liddell");
+
+    let request = RegistrationRequest::new("mallory".to_string(), None, "password123".to_string());
+    service.register(request, &config).unwrap();
+
+    // Another user's username or display name, in disguise
+    assert!(
+        service
+            .set_display_name("mallory", "\u{0430}lice", &config)
+            .is_err()
+    );
+    assert!(
+        service
+            .set_display_name("mallory", "AIice_Liddell", &config)
+            .is_err()
+    );
+    // Reserved names and the SysOp
+    assert!(
+        service
+            .set_display_name("mallory", "Sys0p", &config)
+            .is_err()
+    );
+    assert!(
+        service
+            .set_display_name("mallory", "ADMIN", &config)
+            .is_err()
+    );
+    assert!(
+        service
+            .set_display_name("mallory", "bad\u{202E}name", &config)
+            .is_err()
+    );
+
+    let mallory = service.set_display_name("mallory", "Mal", &config).unwrap();
+    assert_eq!(mallory.shown_name(), "Mal");
+
+    // Your own username is fine, and blank clears the name
+    let alice = service.set_display_name("alice", "ALICE", &config).unwrap();
+    assert_eq!(alice.display_name.as_deref(), Some("ALICE"));
+    let alice = service.set_display_name("alice", "  ", &config).unwrap();
+    assert_eq!(alice.display_name, None);
+    assert_eq!(alice.shown_name(), "alice");
+
+    let request = RegistrationRequest::new("eve".to_string(), None, "password123".to_string())
+        .with_display_name(Some("MAL".to_string()));
+    assert!(service.register(request, &config).is_err());
+}